@@ -87,13 +87,17 @@ fn signup_sign(db_mtx: &State<RwLock<HashMap<Key, String>>>) -> Json<Result<Part
         .expect("Unable to read params, make sure config file is present in the same folder ");
     let params: Params = serde_json::from_str(&data).unwrap();
     let threshold = params.threshold.parse::<u16>().unwrap();
+    // more than t + 1 parties may start a signing ceremony, see `online`
+    let online = params
+        .online
+        .map_or(threshold + 1, |online| online.parse::<u16>().unwrap());
     let key = "signup-sign".to_string();
 
     let mut hm = db_mtx.write().unwrap();
     let party_signup = {
         let value = hm.get(&key).unwrap();
         let client_signup: PartySignup = serde_json::from_str(value).unwrap();
-        if client_signup.number < threshold + 1 {
+        if client_signup.number < online {
             PartySignup {
                 number: client_signup.number + 1,
                 uuid: client_signup.uuid,
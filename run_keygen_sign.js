@@ -12,6 +12,8 @@ const delay_ms = ms => new Promise(resolve => setTimeout(resolve, ms))
 async function keygen(m, arg, delay) {
   let context = await m.gg18_keygen_client_new_context(addr, t, n, delay);
   console.log("keygen new context: ", context);
+  context = await m.gg18_keygen_client_round0(context, delay);
+  console.log("keygen round0: ", context);
   context = await m.gg18_keygen_client_round1(context, delay);
   console.log("keygen round1: ", context);
   context = await m.gg18_keygen_client_round2(context, delay);
@@ -36,6 +38,8 @@ async function sign(m, arg, key_store, delay) {
   console.log("sign new context: ", context);
   context = await m.gg18_sign_client_round0(context, delay);
   console.log("sign round0: ", context);
+  // persist this context before round 1 to resume after a crash
+  context = m.gg18_sign_client_round1_prepare(context);
   context = await m.gg18_sign_client_round1(context, delay);
   console.log("sign round1: ", context);
   context = await m.gg18_sign_client_round2(context, delay);
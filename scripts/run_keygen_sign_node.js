@@ -11,6 +11,8 @@ const delay_ms = ms => new Promise(resolve => setTimeout(resolve, ms))
 async function keygen(m, delay) {
   let context = await m.gg18_keygen_client_new_context(addr, t, n, delay);
   console.log("keygen new context: ");
+  context = await m.gg18_keygen_client_round0(context, delay);
+  console.log("keygen round0: ");
   context = await m.gg18_keygen_client_round1(context, delay);
   console.log("keygen round1:");
   context = await m.gg18_keygen_client_round2(context, delay);
@@ -35,6 +37,8 @@ async function sign(m, key_store, delay) {
   console.log("sign new context: ", context);
   context = await m.gg18_sign_client_round0(context, delay);
   console.log("sign round0: ");
+  // persist this context before round 1 to resume after a crash
+  context = m.gg18_sign_client_round1_prepare(context);
   context = await m.gg18_sign_client_round1(context, delay);
   console.log("sign round1: ");
   context = await m.gg18_sign_client_round2(context, delay);
@@ -0,0 +1,166 @@
+//! Addresses for the group public key.
+//!
+//! Ethereum hashes the uncompressed key without its 0x04 prefix, while
+//! Bitcoin segwit v0 and Cosmos hash the 33 byte compressed key. Mixing these
+//! up yields a valid-looking address nobody can spend from, so integrators
+//! should use these helpers rather than hashing `y_sum` themselves.
+//!
+//! There is no taproot address: its key-path spends need a BIP-340 Schnorr
+//! signature, and the threshold signatures of this crate are ECDSA.
+
+use cryptoxide::digest::Digest as CryptoxideDigest;
+use cryptoxide::sha3::Sha3;
+use digest::Digest;
+use ripemd160::Ripemd160;
+use sha2::Sha256;
+
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::curv::elliptic::curves::traits::ECPoint;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum BitcoinNetwork {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl BitcoinNetwork {
+    fn hrp(&self) -> &'static str {
+        match self {
+            BitcoinNetwork::Mainnet => "bc",
+            BitcoinNetwork::Testnet => "tb",
+            BitcoinNetwork::Regtest => "bcrt",
+        }
+    }
+}
+
+pub(crate) fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3::keccak256();
+    hasher.input(data);
+    let mut out = [0u8; 32];
+    hasher.result(&mut out);
+    out
+}
+
+fn hash160(data: &[u8]) -> [u8; 20] {
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&Ripemd160::digest(&Sha256::digest(data)));
+    out
+}
+
+/// EIP-55 checksummed address, `0x` followed by 40 hex digits.
+pub fn ethereum_address(pk: &GE) -> String {
+    let uncompressed = pk.to_bytes(false);
+    let hash = keccak256(&uncompressed[1..]);
+    let lower = hex::encode(&hash[12..]);
+    let checksum = keccak256(lower.as_bytes());
+    let mixed = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (checksum[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", mixed)
+}
+
+/// Native segwit v0 pay-to-witness-public-key-hash address.
+pub fn bitcoin_p2wpkh_address(pk: &GE, network: BitcoinNetwork) -> String {
+    let mut data = vec![0];
+    data.extend(convert_bits(&hash160(&pk.to_bytes(true))));
+    bech32_encode(network.hrp(), &data)
+}
+
+/// Cosmos SDK account address for a secp256k1 key, e.g. with `hrp` "cosmos".
+pub fn cosmos_address(pk: &GE, hrp: &str) -> String {
+    let data = convert_bits(&hash160(&pk.to_bytes(true)));
+    bech32_encode(hrp, &data)
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GEN: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut chk: u32 = 1;
+    for v in values {
+        let top = chk >> 25;
+        chk = ((chk & 0x1ffffff) << 5) ^ u32::from(*v);
+        for (i, g) in GEN.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= g;
+            }
+        }
+    }
+    chk
+}
+
+// BIP-173 bech32; segwit v0 and Cosmos addresses both use it rather than
+// bech32m
+fn bech32_encode(hrp: &str, data: &[u8]) -> String {
+    let mut values = hrp.bytes().map(|b| b >> 5).collect::<Vec<u8>>();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 0x1f));
+    values.extend_from_slice(data);
+    values.extend_from_slice(&[0u8; 6]);
+    let polymod = bech32_polymod(&values) ^ 1;
+
+    let mut out = String::from(hrp);
+    out.push('1');
+    for d in data {
+        out.push(BECH32_CHARSET[*d as usize] as char);
+    }
+    for i in 0..6 {
+        let d = (polymod >> (5 * (5 - i))) & 0x1f;
+        out.push(BECH32_CHARSET[d as usize] as char);
+    }
+    out
+}
+
+// 8-bit bytes to 5-bit groups, zero padding the last group.
+fn convert_bits(data: &[u8]) -> Vec<u8> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let mut out = Vec::new();
+    for b in data {
+        acc = (acc << 8) | u32::from(*b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(((acc >> bits) & 0x1f) as u8);
+        }
+    }
+    if bits > 0 {
+        out.push(((acc << (5 - bits)) & 0x1f) as u8);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_addresses_of_generator() {
+        // the generator is the public key of private key 1
+        let g: GE = ECPoint::generator();
+        assert_eq!(
+            ethereum_address(&g),
+            "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+        );
+        assert_eq!(
+            bitcoin_p2wpkh_address(&g, BitcoinNetwork::Mainnet),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        let cosmos = cosmos_address(&g, "cosmos");
+        assert!(cosmos.starts_with("cosmos1w508d6qejxtdg4y5r3zarvary0c5xw7k"));
+    }
+}
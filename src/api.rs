@@ -1,36 +1,64 @@
-#![cfg(target_arch = "wasm32")]
+#![cfg(all(target_arch = "wasm32", feature = "gg18"))]
 #![allow(non_snake_case)]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 //use crate::log;
 
-use web_sys::{Request, RequestInit, RequestMode, Response};
+use web_sys::{CryptoKey, Request, RequestInit, RequestMode, Response};
 
+use crate::api_error::{ApiError, ErrorCode};
+use crate::gg_2018::adaptor::{adapted_nonce, AdaptorSignature, NonceShare};
+use crate::gg_2018::contribution::ContributionProof;
+#[cfg(feature = "escrow")]
+use crate::gg_2018::escrow::{EscrowTerms, EscrowedShare};
+use crate::gg_2018::handshake::{negotiate, Agreement, Capabilities, HandshakeError, WireFormat};
 use crate::gg_2018::mta::*;
 use crate::gg_2018::party_i::*;
+use crate::gg_2018::policy::{PolicyError, PolicyRejection, SigningPolicy, SigningRequest};
+use crate::gg_2018::presign::Presignature;
+use crate::gg_2018::proof_cache::ProofCache;
+use crate::gg_2018::quorum::{QuorumCache, QuorumKeys};
+use crate::gg_2018::range_proofs::AliceProofParams;
+use crate::gg_2018::streaming::MtaAccumulator;
+use crate::gg_2018::threshold_signature::ThresholdSignature;
+use crate::metrics::{metrics, RoundTimer};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 use reqwest::Client;
 
+use crate::address::BitcoinNetwork;
 use crate::common::{
-    aes_decrypt, aes_encrypt, broadcast, poll_for_broadcasts, poll_for_p2p, postb, sendp2p, Entry,
-    Params, PartySignup, AEAD, AES_KEY_BYTES_LEN,
+    aes_decrypt, aes_encrypt, broadcast, broadcast_key, p2p_key, poll_for_broadcasts_partial,
+    poll_for_broadcasts_until, poll_for_p2p_each, poll_for_p2p_until, postb, sendp2p, sleep, Entry,
+    Params, PartySignup, RoundTimeout, AEAD, AES_KEY_BYTES_LEN,
 };
 use crate::curv::elliptic::curves::traits::{ECPoint, ECScalar};
 use crate::curv::{
+    arithmetic::bigint_hex::write_as,
     arithmetic::num_bigint::BigInt,
     arithmetic::traits::ConvertFrom,
     arithmetic::traits::Converter,
+    arithmetic::traits::Samplable,
     cryptographic_primitives::{
         proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof,
         proofs::sigma_dlog::DLogProof, secret_sharing::feldman_vss::VerifiableSS,
     },
     elliptic::curves::secp256_k1::{Secp256k1Point as Point, Secp256k1Scalar as Scalar},
 };
+#[cfg(feature = "ethereum")]
+use crate::ethereum::Transaction;
 use crate::paillier::traits::EncryptWithChosenRandomness;
 
-use crate::paillier::EncryptionKey;
+use crate::paillier::{EncryptionKey, Keypair, KeypairSearch};
+use crate::session::{
+    Ceremony, Checkpoint, PeerLimiter, SessionError, SessionLimits, SessionRegistry,
+};
+use crate::storage::IndexedDbStorage;
+use crate::wallet::ThresholdWallet;
+use crate::webcrypto::{unwrap_key_store, wrap_key_store};
 use sha2::Sha256;
+use std::cell::RefCell;
 use std::{fs, time};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -51,6 +79,64 @@ pub struct GG18KeygenClientContext {
     dlog_proof: Option<DLogProof>,
     shared_keys: Option<SharedKeys>,
     vss_scheme_vec: Option<Vec<VerifiableSS>>,
+    paillier_search: Option<KeypairSearch>,
+    #[serde(default)]
+    agreement: Option<Agreement>,
+    // this party's round 1 and round 2 broadcasts, from the outgoing half of
+    // the round to the incoming half
+    #[serde(default)]
+    bc_i: Option<KeyGenBroadcastMessage1>,
+    #[serde(default)]
+    contribution_i: Option<ContributionProof>,
+    #[serde(default)]
+    round: RoundState,
+}
+
+/// Where a context is in its ceremony, so that a round run out of order, or
+/// twice, is refused before it reads what an earlier round left.
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
+struct RoundState {
+    next_round: u8,
+    // whether the outgoing half of `next_round` ran
+    sent: bool,
+}
+
+impl RoundState {
+    fn outgoing(&mut self, round: u8) -> Result<(), ApiError> {
+        self.expect(round)?;
+        self.sent = true;
+        Ok(())
+    }
+
+    // a context whose round fails is dropped, so the state moves on before
+    // the round has run
+    fn incoming(&mut self, round: u8) -> Result<(), ApiError> {
+        self.expect(round)?;
+        if !self.sent {
+            return Err(
+                ApiError::new(ErrorCode::WrongRound, "the round's messages were not sent")
+                    .in_round(&format!("round{}", round)),
+            );
+        }
+        self.next_round += 1;
+        self.sent = false;
+        Ok(())
+    }
+
+    fn started(&self) -> bool {
+        self.next_round > 0 || self.sent
+    }
+
+    fn expect(&self, round: u8) -> Result<(), ApiError> {
+        if round != self.next_round {
+            return Err(ApiError::new(
+                ErrorCode::WrongRound,
+                &format!("the context is at round {}", self.next_round),
+            )
+            .in_round(&format!("round{}", round)));
+        }
+        Ok(())
+    }
 }
 
 fn new_client_with_headers() -> Client {
@@ -76,18 +162,40 @@ pub async fn gg18_keygen_client_new_context(
     t: usize,
     n: usize,
     delay: u32,
-) -> String {
+) -> Result<String, JsValue> {
     let client = new_client_with_headers();
-    let params = Parameters {
-        threshold: t,
-        share_count: n,
-    };
+    let params = Parameters::new(t, n).map_err(ApiError::from)?;
 
-    let (party_num_int, uuid) = match signup_keygen(&client, &addr).await.unwrap() {
+    let (party_num_int, uuid) = match signup_keygen(&client, &addr).await? {
         PartySignup { number, uuid } => (number, uuid),
     };
 
-    serde_json::to_string(&GG18KeygenClientContext {
+    Ok(serde_json::to_string(&keygen_context(addr, params, party_num_int, uuid)).unwrap())
+}
+
+/// A keygen context for a ceremony whose messages the caller carries
+/// instead of the relay, see `gg18_keygen_round0_outgoing`. `party` is this
+/// party's number, 1 to `n`, and `uuid` the id of the ceremony, the same
+/// for all parties.
+#[wasm_bindgen]
+pub fn gg18_keygen_context(
+    party: u16,
+    uuid: String,
+    t: usize,
+    n: usize,
+) -> Result<String, JsValue> {
+    let params = Parameters::new(t, n).map_err(ApiError::from)?;
+    check_party_number(party, n)?;
+    Ok(serde_json::to_string(&keygen_context(String::new(), params, party, uuid)).unwrap())
+}
+
+fn keygen_context(
+    addr: String,
+    params: Parameters,
+    party_num_int: u16,
+    uuid: String,
+) -> GG18KeygenClientContext {
+    GG18KeygenClientContext {
         addr,
         params,
         party_num_int,
@@ -104,94 +212,272 @@ pub async fn gg18_keygen_client_new_context(
         dlog_proof: None,
         shared_keys: None,
         vss_scheme_vec: None,
-    })
-    .unwrap()
+        paillier_search: None,
+        agreement: None,
+        bc_i: None,
+        contribution_i: None,
+        round: RoundState::default(),
+    }
 }
 
+/// Tests up to `budget` Paillier prime candidates for this party's round 1
+/// keys and returns the context with the search saved in it. Calling this
+/// between turns of the event loop until `gg18_keygen_client_paillier_ready`
+/// keeps the page responsive; round 1 finishes whatever is left. `safe`
+/// asks for safe primes and only counts on the first call.
 #[wasm_bindgen]
-pub async fn gg18_keygen_client_round1(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18KeygenClientContext>(&context).unwrap();
-    let client = reqwest::Client::new();
-    let party_keys = Keys::create(context.party_num_int as usize);
-    let (bc_i, decom_i) = party_keys.phase1_broadcast_phase3_proof_of_correct_key();
+pub fn gg18_keygen_client_paillier_step(
+    context: String,
+    budget: usize,
+    safe: bool,
+) -> Result<String, JsValue> {
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    let mut rng = rand::thread_rng();
+    let mut search = context
+        .paillier_search
+        .take()
+        .unwrap_or_else(|| KeypairSearch::new(PAILLIER_MODULUS_BITS, safe, &mut rng));
+    search.step(budget, &mut rng);
+    context.paillier_search = Some(search);
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round1",
-        serde_json::to_string(&bc_i).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
+/// Whether `gg18_keygen_client_paillier_step` has found both primes.
+#[wasm_bindgen]
+pub fn gg18_keygen_client_paillier_ready(context: String) -> Result<bool, JsValue> {
+    let context: GG18KeygenClientContext = parse_context(&context)?;
+    Ok(context
+        .paillier_search
+        .and_then(|search| search.keypair())
+        .is_some())
+}
 
-    let round1_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.params.share_count as u16,
-        "round1",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+// Runs the search a few candidates at a time and yields to the event loop in
+// between, so that keygen does not freeze the page without a Web Worker.
+async fn paillier_keypair(search: Option<KeypairSearch>) -> Keypair {
+    let mut rng = rand::thread_rng();
+    let mut search =
+        search.unwrap_or_else(|| KeypairSearch::new(PAILLIER_MODULUS_BITS, false, &mut rng));
+    loop {
+        if let Some(keypair) = search.step(PRIME_CANDIDATES_PER_TURN, &mut rng) {
+            return keypair;
+        }
+        sleep(0).await;
+    }
+}
 
-    let mut bc1_vec = round1_ans_vec
-        .iter()
-        .map(|m| serde_json::from_str::<KeyGenBroadcastMessage1>(m).unwrap())
-        .collect::<Vec<_>>();
+// The same without yielding, for `gg18_keygen_round1_outgoing`.
+fn paillier_keypair_blocking(search: Option<KeypairSearch>) -> Keypair {
+    let mut rng = rand::thread_rng();
+    let mut search =
+        search.unwrap_or_else(|| KeypairSearch::new(PAILLIER_MODULUS_BITS, false, &mut rng));
+    loop {
+        if let Some(keypair) = search.step(PRIME_CANDIDATES_PER_TURN, &mut rng) {
+            return keypair;
+        }
+    }
+}
 
-    bc1_vec.insert(context.party_num_int as usize - 1, bc_i);
+/// The `Capabilities` of this version, as json.
+#[wasm_bindgen]
+pub fn gg18_capabilities() -> String {
+    serde_json::to_string(&Capabilities::local()).unwrap()
+}
 
-    context.bc1_vec = Some(bc1_vec);
-    context.party_keys = Some(party_keys);
-    context.decom_i = Some(decom_i);
+/// Round 0: the parties exchange their `Capabilities` and settle on the
+/// protocol and proofs the ceremony runs with. Fails with `Incompatible`,
+/// blaming the parties to upgrade, before any key material is sent.
+#[wasm_bindgen]
+pub async fn gg18_keygen_client_round0(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round0(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round0(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round0");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(0)?;
+    let client = new_client_with_headers();
+    let outgoing = keygen_round0_outgoing();
+    let answers = context
+        .relay()
+        .exchange(&client, "round0", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(0)?;
+    keygen_round0_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn keygen_round0_outgoing() -> Outgoing {
+    Outgoing::Broadcast(serde_json::to_string(&Capabilities::local()).unwrap())
+}
 
-    serde_json::to_string(&context).unwrap()
+fn keygen_round0_incoming(
+    context: &mut GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let mut announced = Vec::new();
+    format_vec_from_reads(
+        answers,
+        context.party_num_int as usize,
+        Capabilities::local(),
+        &mut announced,
+        "round0",
+    )?;
+    let announced = (1..).zip(announced).collect::<Vec<_>>();
+    context.agreement = Some(negotiate(&announced)?);
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_keygen_client_round2(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18KeygenClientContext>(&context).unwrap();
+pub async fn gg18_keygen_client_round1(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round1(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round1(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round1");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(1)?;
+    // the search yields to the event loop here, the outgoing half would
+    // block until it is done
+    if context.agreement.is_some() && context.party_keys.is_none() {
+        let keypair = paillier_keypair(context.paillier_search.take()).await;
+        let party_keys = Keys::create_from_keypair(keypair, context.party_num_int as usize);
+        context.party_keys = Some(party_keys);
+    }
+    let outgoing = written(context.agreement, || keygen_round1_outgoing(&mut context))?;
     let client = reqwest::Client::new();
-    // send ephemeral public keys and check commitments correctness
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round2",
-        serde_json::to_string(&context.decom_i.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
+    let answers = context
+        .relay()
+        .exchange(&client, "round1", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(1)?;
+    keygen_round1_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
-    let round2_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.params.share_count as u16,
-        "round2",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+// The keys and the commitment are drawn once, so a party resumed from the
+// context returned with its message sends the same commitment again.
+fn keygen_round1_outgoing(context: &mut GG18KeygenClientContext) -> Result<Outgoing, ApiError> {
+    if context.agreement.is_none() {
+        return Err(ApiError::new(ErrorCode::WrongRound, "round 0 has not run").in_round("round1"));
+    }
+    if context.party_keys.is_none() {
+        let keypair = paillier_keypair_blocking(context.paillier_search.take());
+        let party_keys = Keys::create_from_keypair(keypair, context.party_num_int as usize);
+        context.party_keys = Some(party_keys);
+    }
+    if context.bc_i.is_none() {
+        let (bc_i, decom_i) = required(&context.party_keys, "party_keys")?
+            .phase1_broadcast_phase3_proof_of_correct_key();
+        context.bc_i = Some(bc_i);
+        context.decom_i = Some(decom_i);
+    }
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.bc_i, "bc_i")?).unwrap(),
+    ))
+}
+
+fn keygen_round1_incoming(
+    context: &mut GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let mut bc1_vec: Vec<KeyGenBroadcastMessage1> = Vec::new();
+    format_vec_from_reads(
+        answers,
+        context.party_num_int as usize,
+        required(&context.bc_i, "bc_i")?.clone(),
+        &mut bc1_vec,
+        "round1",
+    )?;
+    context.bc1_vec = Some(bc1_vec);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn gg18_keygen_client_round2(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round2(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round2(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round2");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(2)?;
+    let client = reqwest::Client::new();
+    let outgoing = written(context.agreement, || keygen_round2_outgoing(&mut context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round2", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(2)?;
+    keygen_round2_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+// send ephemeral public keys and check commitments correctness
+fn keygen_round2_outgoing(context: &mut GG18KeygenClientContext) -> Result<Outgoing, ApiError> {
+    if context.contribution_i.is_none() {
+        context.contribution_i = Some(ContributionProof::prove(
+            required(&context.party_keys, "party_keys")?,
+            context.uuid.as_bytes(),
+            context.party_num_int,
+        ));
+    }
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(&(
+            required(&context.decom_i, "decom_i")?,
+            required(&context.contribution_i, "contribution_i")?,
+        ))
+        .unwrap(),
+    ))
+}
 
+fn keygen_round2_incoming(
+    context: &mut GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let contribution_i = required(&context.contribution_i, "contribution_i")?.clone();
     let mut j = 0;
     let mut point_vec: Vec<Point> = Vec::new();
     let mut decom_vec: Vec<KeyGenDecommitMessage1> = Vec::new();
+    let mut contribution_vec: Vec<ContributionProof> = Vec::new();
     let mut enc_keys: Vec<Vec<u8>> = Vec::new();
-    for i in 1..=context.params.share_count as u16 {
+    for i in 1..=context.params.share_count() as u16 {
         if i == context.party_num_int {
-            point_vec.push(context.decom_i.as_ref().unwrap().y_i.clone());
-            decom_vec.push(context.decom_i.as_ref().unwrap().clone());
+            point_vec.push(required(&context.decom_i, "decom_i")?.y_i.clone());
+            decom_vec.push(required(&context.decom_i, "decom_i")?.clone());
+            contribution_vec.push(contribution_i.clone());
         } else {
-            let decom_j: KeyGenDecommitMessage1 = serde_json::from_str(&round2_ans_vec[j]).unwrap();
+            let (decom_j, contribution_j): (KeyGenDecommitMessage1, ContributionProof) =
+                parse_message(&answers[j], "round2", i)?;
             point_vec.push(decom_j.y_i.clone());
             decom_vec.push(decom_j.clone());
+            contribution_vec.push(contribution_j);
             let key_bn: BigInt = (decom_j.y_i.clone()
-                * context.party_keys.as_ref().unwrap().u_i.clone())
+                * required(&context.party_keys, "party_keys")?.u_i.clone())
             .x_coor()
             .unwrap();
             let key_bytes = BigInt::to_vec(&key_bn);
@@ -202,78 +488,120 @@ pub async fn gg18_keygen_client_round2(context: String, delay: u32) -> String {
         }
     }
 
+    Keys::verify_contribution_proofs(
+        context.uuid.as_bytes(),
+        &contribution_vec,
+        &decom_vec,
+        required(&context.bc1_vec, "bc1_vec")?,
+    )
+    .map_err(|failed| {
+        ApiError::from(crate::Error::InvalidKey)
+            .in_round("round2")
+            .with_blame(failed.iter().map(|i| *i as u16 + 1).collect())
+    })?;
+
     let (head, tail) = point_vec.split_at(1);
     let y_sum = tail.iter().fold(head[0].clone(), |acc, x| acc + x);
 
-    let (vss_scheme, secret_shares, _index) = context
-        .party_keys
-        .as_ref()
-        .unwrap()
-        .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
-            &context.params,
-            &decom_vec,
-            &(context.bc1_vec.as_ref().unwrap()),
-        )
-        .expect("invalid key");
+    let party_keys = required(&context.party_keys, "party_keys")?;
+    let bc1_vec = required(&context.bc1_vec, "bc1_vec")?;
+    let (distributed, verified) = PROOFS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let known = cache.len();
+        let distributed = party_keys
+            .phase1_verify_com_phase3_verify_correct_key_phase2_distribute_cached(
+                &context.params,
+                &decom_vec,
+                bc1_vec,
+                &mut cache,
+                usize::from(context.party_num_int) - 1,
+            );
+        (distributed, cache.len() - known)
+    });
+    let (vss_scheme, secret_shares, _index) =
+        distributed.map_err(|e| ApiError::from(e).in_round("round2"))?;
+    metrics().proofs_verified("round2", verified);
 
     context.y_sum = Some(y_sum);
     context.vss_scheme = Some(vss_scheme);
     context.secret_shares = Some(secret_shares);
     context.enc_keys = Some(enc_keys);
     context.point_vec = Some(point_vec);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_keygen_client_round3(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18KeygenClientContext>(&context).unwrap();
+pub async fn gg18_keygen_client_round3(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round3(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round3(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round3");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(3)?;
     let client = reqwest::Client::new();
+    let outgoing = written(context.agreement, || keygen_round3_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round3", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(3)?;
+    keygen_round3_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn keygen_round3_outgoing(context: &GG18KeygenClientContext) -> Result<Outgoing, ApiError> {
+    let enc_keys = required(&context.enc_keys, "enc_keys")?;
+    let secret_shares = required(&context.secret_shares, "secret_shares")?;
+    let mut messages = Vec::new();
     let mut j = 0;
-    for (k, i) in (1..=context.params.share_count as u16).enumerate() {
+    for (k, i) in (1..=context.params.share_count() as u16).enumerate() {
         if i != context.party_num_int {
             // prepare encrypted ss for party i:
-            let key_i = &context.enc_keys.as_ref().unwrap()[j];
-            let plaintext =
-                BigInt::to_vec(&context.secret_shares.as_ref().unwrap()[k].to_big_int());
+            let key_i = &enc_keys[j];
+            let plaintext = BigInt::to_vec(&secret_shares[k].to_big_int());
             let aead_pack_i = aes_encrypt(key_i, &plaintext);
-            assert!(sendp2p(
-                &client,
-                &context.addr,
-                context.party_num_int,
-                i,
-                "round3",
-                serde_json::to_string(&aead_pack_i).unwrap(),
-                context.uuid.clone(),
-            )
-            .await
-            .is_ok());
+            messages.push((i, serde_json::to_string(&aead_pack_i).unwrap()));
             j += 1;
         }
     }
+    Ok(Outgoing::P2p(messages))
+}
 
-    let round3_ans_vec = poll_for_p2p(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.params.share_count as u16,
-        delay,
-        "round3",
-        context.uuid.clone(),
-    )
-    .await;
-
+fn keygen_round3_incoming(
+    context: &mut GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let enc_keys = required(&context.enc_keys, "enc_keys")?;
+    let secret_shares = required(&context.secret_shares, "secret_shares")?;
     let mut j = 0;
     let mut party_shares: Vec<Scalar> = Vec::new();
-    for i in 1..=context.params.share_count as u16 {
+    for i in 1..=context.params.share_count() as u16 {
         if i == context.party_num_int {
-            party_shares.push(context.secret_shares.as_ref().unwrap()[(i - 1) as usize].clone());
+            party_shares.push(secret_shares[(i - 1) as usize].clone());
         } else {
-            let aead_pack: AEAD = serde_json::from_str(&round3_ans_vec[j]).unwrap();
-            let key_i = &context.enc_keys.as_ref().unwrap()[j];
-            let out = aes_decrypt(key_i, aead_pack);
+            let aead_pack: AEAD = parse_message(&answers[j], "round3", i)?;
+            let key_i = &enc_keys[j];
+            let out = aes_decrypt(key_i, aead_pack).map_err(|_| {
+                ApiError::new(ErrorCode::InvalidMessage, "undecryptable secret share")
+                    .in_round("round3")
+                    .with_party(i)
+            })?;
             let out_bn = BigInt::from_bytes_be(&out[..]);
-            let out_fe = ECScalar::from(&out_bn);
+            // a share of zero or beyond q is malformed, not to be reduced
+            let out_fe = Scalar::from_big_int_checked(&out_bn).map_err(|_| {
+                ApiError::new(ErrorCode::InvalidMessage, "malformed secret share")
+                    .in_round("round3")
+                    .with_party(i)
+            })?;
             party_shares.push(out_fe);
 
             j += 1;
@@ -281,138 +609,174 @@ pub async fn gg18_keygen_client_round3(context: String, delay: u32) -> String {
     }
 
     context.party_shares = Some(party_shares);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_keygen_client_round4(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18KeygenClientContext>(&context).unwrap();
+pub async fn gg18_keygen_client_round4(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round4(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round4(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round4");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(4)?;
     let client = reqwest::Client::new();
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round4",
-        serde_json::to_string(&context.vss_scheme.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round4_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.params.share_count as u16,
-        "round4",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    let outgoing = written(context.agreement, || keygen_round4_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round4", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(4)?;
+    keygen_round4_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
-    let mut j = 0;
+fn keygen_round4_outgoing(context: &GG18KeygenClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.vss_scheme, "vss_scheme")?).unwrap(),
+    ))
+}
+
+fn keygen_round4_incoming(
+    context: &mut GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
     let mut vss_scheme_vec: Vec<VerifiableSS> = Vec::new();
-    for i in 1..=context.params.share_count as u16 {
-        if i == context.party_num_int {
-            vss_scheme_vec.push(context.vss_scheme.as_ref().unwrap().clone());
-        } else {
-            let vss_scheme_j: VerifiableSS = serde_json::from_str(&round4_ans_vec[j]).unwrap();
-            vss_scheme_vec.push(vss_scheme_j);
-            j += 1;
-        }
-    }
+    format_vec_from_reads(
+        answers,
+        context.party_num_int as usize,
+        required(&context.vss_scheme, "vss_scheme")?.clone(),
+        &mut vss_scheme_vec,
+        "round4",
+    )?;
 
-    let (shared_keys, dlog_proof) = context
-        .party_keys
-        .as_ref()
-        .unwrap()
+    let point_vec = required(&context.point_vec, "point_vec")?;
+    let party_shares = required(&context.party_shares, "party_shares")?;
+    let (shared_keys, dlog_proof) = required(&context.party_keys, "party_keys")?
         .phase2_verify_vss_construct_keypair_phase3_pok_dlog(
             &context.params,
-            &context.point_vec.as_ref().unwrap(),
-            &context.party_shares.as_ref().unwrap(),
+            point_vec,
+            party_shares,
             &vss_scheme_vec,
             &(context.party_num_int.clone() as usize), // FIXME
         )
-        .expect("invalid vss");
+        .map_err(|e| {
+            let blame = Keys::phase2_vss_blame(
+                &context.params,
+                point_vec,
+                party_shares,
+                &vss_scheme_vec,
+                context.party_num_int as usize,
+            );
+            ApiError::from(e)
+                .in_round("round4")
+                .with_blame(blamed_parties(&blame, None))
+        })?;
 
     context.shared_keys = Some(shared_keys);
     context.dlog_proof = Some(dlog_proof);
     context.vss_scheme_vec = Some(vss_scheme_vec);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_keygen_client_round5(context: String, delay: u32) -> String {
-    let context = serde_json::from_str::<GG18KeygenClientContext>(&context).unwrap();
+pub async fn gg18_keygen_client_round5(context: String, delay: u32) -> Result<String, JsValue> {
+    keygen_round5(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn keygen_round5(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Keygen, "round5");
+    let mut context: GG18KeygenClientContext = parse_context(&context)?;
+    context.round.outgoing(5)?;
     let client = reqwest::Client::new();
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round5",
-        serde_json::to_string(&context.dlog_proof.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round5_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.params.share_count as u16,
-        "round5",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    let outgoing = written(context.agreement, || keygen_round5_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round5", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(5)?;
+    let keygen_json = keygen_round5_incoming(&context, &answers)?;
+
+    timer.finish();
+    Ok(keygen_json)
+}
 
-    let mut j = 0;
+fn keygen_round5_outgoing(context: &GG18KeygenClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.dlog_proof, "dlog_proof")?).unwrap(),
+    ))
+}
+
+// returns the key store
+fn keygen_round5_incoming(
+    context: &GG18KeygenClientContext,
+    answers: &[String],
+) -> Result<String, ApiError> {
     let mut dlog_proof_vec: Vec<DLogProof> = Vec::new();
-    for i in 1..=context.params.share_count as u16 {
-        if i == context.party_num_int {
-            dlog_proof_vec.push(context.dlog_proof.as_ref().unwrap().clone());
-        } else {
-            let dlog_proof_j: DLogProof = serde_json::from_str(&round5_ans_vec[j]).unwrap();
-            dlog_proof_vec.push(dlog_proof_j);
-            j += 1;
-        }
-    }
+    format_vec_from_reads(
+        answers,
+        context.party_num_int as usize,
+        required(&context.dlog_proof, "dlog_proof")?.clone(),
+        &mut dlog_proof_vec,
+        "round5",
+    )?;
     Keys::verify_dlog_proofs(
         &context.params,
         &dlog_proof_vec,
-        &context.point_vec.as_ref().unwrap(),
+        required(&context.point_vec, "point_vec")?,
     )
-    .expect("bad dlog proof");
+    .map_err(|e| ApiError::from(e).in_round("round5"))?;
+    metrics().proofs_verified("round5", dlog_proof_vec.len());
 
     //save key to file:
-    let paillier_key_vec = (0..context.params.share_count as u16)
-        .map(|i| context.bc1_vec.as_ref().unwrap()[i as usize].e.clone())
+    let bc1_vec = required(&context.bc1_vec, "bc1_vec")?;
+    let paillier_key_vec = (0..context.params.share_count() as u16)
+        .map(|i| bc1_vec[i as usize].e.clone())
         .collect::<Vec<EncryptionKey>>();
 
     let keygen_json = serde_json::to_string(&(
-        context.party_keys.as_ref().unwrap(),
-        context.shared_keys.as_ref().unwrap(),
+        required(&context.party_keys, "party_keys")?,
+        required(&context.shared_keys, "shared_keys")?,
         context.party_num_int,
-        context.vss_scheme_vec.as_ref().unwrap(),
+        required(&context.vss_scheme_vec, "vss_scheme_vec")?,
         paillier_key_vec,
-        context.y_sum.as_ref().unwrap(),
+        required(&context.y_sum, "y_sum")?,
     ))
     .unwrap();
-
-    keygen_json
+    Ok(keygen_json)
 }
 
-pub async fn signup_keygen(client: &Client, addr: &str) -> Result<PartySignup, ()> {
+pub async fn signup_keygen(client: &Client, addr: &str) -> Result<PartySignup, ApiError> {
     let key = "signup-keygen".to_string();
-    let res_body = postb(client, addr, "signupkeygen", key).await.unwrap();
-    serde_json::from_str(&res_body).unwrap()
+    let res_body = postb(client, addr, "signupkeygen", key)
+        .await
+        .ok_or_else(|| network_error("signup"))?;
+    serde_json::from_str(&res_body)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidMessage, "bad signup response"))
 }
 
-pub async fn signup_sign(client: &Client, addr: &str) -> Result<PartySignup, ()> {
+pub async fn signup_sign(client: &Client, addr: &str) -> Result<PartySignup, ApiError> {
     let key = "signup-sign".to_string();
-    let res_body = postb(client, addr, "signupsign", key).await.unwrap();
-    serde_json::from_str(&res_body).unwrap()
+    let res_body = postb(client, addr, "signupsign", key)
+        .await
+        .ok_or_else(|| network_error("signup"))?;
+    serde_json::from_str(&res_body)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidMessage, "bad signup response"))
 }
 
 #[wasm_bindgen]
@@ -431,17 +795,16 @@ pub struct GG18SignClientContext {
     sign_keys: Option<SignKeys>,
     com: Option<SignBroadcastPhase1>,
     decommit: Option<SignDecommitPhase1>,
+    #[serde(default)]
+    m_a_k: Option<MessageA>,
     round1_ans_vec: Option<Vec<String>>,
     signers_vec: Option<Vec<usize>>,
-    round2_ans_vec: Option<Vec<String>>,
-    xi_com_vec: Option<Vec<crate::curv::elliptic::curves::secp256_k1::Secp256k1Point>>,
-    beta_vec: Option<Vec<Scalar>>,
-    ni_vec: Option<Vec<Scalar>>,
+    mta: Option<MtaAccumulator>,
     bc1_vec: Option<Vec<SignBroadcastPhase1>>,
-    m_b_gamma_rec_vec: Option<Vec<MessageB>>,
+    b_proof_vec: Option<Vec<DLogProof>>,
     delta_inv: Option<crate::curv::elliptic::curves::secp256_k1::Secp256k1Scalar>,
     sigma: Option<crate::curv::elliptic::curves::secp256_k1::Secp256k1Scalar>,
-    message: Vec<u8>,
+    message: SigningDigest,
     phase5_com: Option<Phase5Com1>,
     phase_5a_decom: Option<Phase5ADecom1>,
     helgamal_proof: Option<HomoELGamalProof>,
@@ -456,431 +819,1104 @@ pub struct GG18SignClientContext {
         Option<Vec<(Phase5ADecom1, HomoELGamalProof, DLogProof)>>,
     s_i: Option<crate::curv::elliptic::curves::secp256_k1::Secp256k1Scalar>,
     commit5c_vec: Option<Vec<Phase5Com2>>,
+    #[serde(default)]
+    agreement: Option<Agreement>,
+    #[serde(default)]
+    adaptor_point: Option<Point>,
+    /// Each signer's Gamma_i and gamma_i Y, for an adaptor signature.
+    #[serde(default)]
+    nonce_shares: Option<Vec<(Point, NonceShare)>>,
+    /// The parties that start the ceremony, `t + 1` if unset.
+    #[serde(default)]
+    online: Option<u16>,
+    /// Each party's number, key index and capabilities from round 0.
+    #[serde(default)]
+    hellos: Option<Vec<(u16, usize, Capabilities)>>,
+    /// The numbers the signers started with, once those left after a
+    /// dropout have numbered themselves 1 to k.
+    #[serde(default)]
+    relay_numbers: Option<Vec<u16>>,
+    #[serde(default)]
+    round: RoundState,
 }
 
+/// Checks a key store without starting a ceremony, e.g. after restoring it
+/// from a backup.
 #[wasm_bindgen]
-pub async fn gg18_sign_client_new_context(
-    addr: String,
-    t: usize,
-    n: usize,
-    key_store: String,
-    message_str: String,
-) -> String {
-    let message = match hex::decode(message_str.clone()) {
-        Ok(x) => x,
-        Err(_e) => message_str.as_bytes().to_vec(),
-    };
-    // let message = &message[..];
-    let client = new_client_with_headers();
+pub fn gg18_key_store_validate(key_store: String) -> Result<(), JsValue> {
+    read_key_store(&key_store)?;
+    Ok(())
+}
 
-    let (party_keys, shared_keys, party_id, vss_scheme_vec, paillier_key_vector, y_sum): (
-        Keys,
-        SharedKeys,
-        u16,
-        Vec<VerifiableSS>,
-        Vec<EncryptionKey>,
-        Point,
-    ) = serde_json::from_str(&key_store).unwrap();
+/// A `ThresholdWallet` over `key_store`, as json, for the `gg18_wallet_*`
+/// exports.
+#[wasm_bindgen]
+pub fn gg18_wallet_new(key_store: String) -> Result<String, JsValue> {
+    let wallet = ThresholdWallet::from_key_store(&key_store).map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&wallet).unwrap())
+}
 
-    //signup:
-    let (party_num_int, uuid) = match signup_sign(&client, &addr).await.unwrap() {
-        PartySignup { number, uuid } => (number, uuid),
-    };
+/// The wallet of the child key at `path`, e.g. `m/44/60/0/0/0`. Hardened
+/// steps are refused: they need the private key no party holds.
+#[wasm_bindgen]
+pub fn gg18_wallet_derive(wallet: String, path: String) -> Result<String, JsValue> {
+    let wallet: ThresholdWallet = parse_context(&wallet)?;
+    let child = wallet.derive_path(&path).map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&child).unwrap())
+}
 
-    serde_json::to_string(&GG18SignClientContext {
-        addr,
-        party_keys,
-        shared_keys,
-        party_id,
-        vss_scheme_vec,
-        paillier_key_vector,
-        y_sum,
-        threshould: t as u16,
-        party_num_int,
-        uuid,
-        sign_keys: None,
-        com: None,
-        decommit: None,
-        round1_ans_vec: None,
-        signers_vec: None,
-        round2_ans_vec: None,
-        xi_com_vec: None,
-        beta_vec: None,
-        ni_vec: None,
-        bc1_vec: None,
-        m_b_gamma_rec_vec: None,
-        delta_inv: None,
-        message, // TODO: The message is plain now
-        sigma: None,
-        phase5_com: None,
-        phase_5a_decom: None,
-        helgamal_proof: None,
-        dlog_proof_rho: None,
-        commit5a_vec: None,
-        local_sig: None,
-        r: None,
-        phase5_com2: None,
-        phase_5d_decom2: None,
-        decommit5a_and_elgamal_and_dlog_vec: None,
-        decommit5a_and_elgamal_and_dlog_vec_includes_i: None,
-        s_i: None,
-        commit5c_vec: None,
-    })
-    .unwrap()
+/// The wallet's key store, as `gg18_sign_client_new_context` takes it.
+#[wasm_bindgen]
+pub fn gg18_wallet_key_store(wallet: String) -> Result<String, JsValue> {
+    let wallet: ThresholdWallet = parse_context(&wallet)?;
+    Ok(wallet.to_key_store())
 }
 
+/// The wallet's group public key, compressed, in hex.
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round0(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub fn gg18_wallet_public_key(wallet: String) -> Result<String, JsValue> {
+    let wallet: ThresholdWallet = parse_context(&wallet)?;
+    Ok(hex::encode(wallet.public_key().to_bytes(true)))
+}
+
+/// The wallet's address on `chain`: `ethereum`, `bitcoin`, `bitcoin-testnet`,
+/// `bitcoin-regtest`, or `cosmos:` and the human readable part, e.g.
+/// `cosmos:osmo`. There is no taproot address: spending from one needs a
+/// Schnorr signature, and the wallet signs ECDSA only.
+#[wasm_bindgen]
+pub fn gg18_wallet_address(wallet: String, chain: String) -> Result<String, JsValue> {
+    let wallet: ThresholdWallet = parse_context(&wallet)?;
+    if let Some(hrp) = chain.strip_prefix("cosmos:") {
+        return Ok(wallet.cosmos_address(hrp));
+    }
+    let (chain, network) = match chain.split_once('-') {
+        Some((chain, "testnet")) => (chain, BitcoinNetwork::Testnet),
+        Some((chain, "regtest")) => (chain, BitcoinNetwork::Regtest),
+        Some(_) => ("", BitcoinNetwork::Mainnet),
+        None => (chain.as_str(), BitcoinNetwork::Mainnet),
+    };
+    match chain {
+        "ethereum" if network == BitcoinNetwork::Mainnet => Ok(wallet.ethereum_address()),
+        "bitcoin" => Ok(wallet.bitcoin_address(network)),
+        _ => Err(ApiError::new(ErrorCode::InvalidParameters, "unknown chain").into()),
+    }
+}
+
+/// Adds `presignature`, a `Presignature` as json computed for the wallet's
+/// key, to the wallet's presignatures in the IndexedDB database `db`, and
+/// returns its id. Presignatures are kept there only, never in the wallet's
+/// json.
+#[wasm_bindgen]
+pub async fn gg18_wallet_add_presignature(
+    wallet: String,
+    db: String,
+    presignature: String,
+) -> Result<String, JsValue> {
+    wallet_add_presignature(&wallet, &db, &presignature)
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn wallet_add_presignature(
+    wallet: &str,
+    db: &str,
+    presignature: &str,
+) -> Result<String, ApiError> {
+    let wallet: ThresholdWallet = parse_context(wallet)?;
+    let presignature: Presignature = parse_context(presignature)?;
+    let mut storage = IndexedDbStorage::open(db).await?;
+    let id = wallet.add_presignature(&mut storage, presignature)?;
+    storage.flush().await?;
+    Ok(id)
+}
+
+/// The ids of the wallet's presignatures still available in the IndexedDB
+/// database `db`, as a json array.
+#[wasm_bindgen]
+pub async fn gg18_wallet_presignature_ids(wallet: String, db: String) -> Result<String, JsValue> {
+    wallet_presignature_ids(&wallet, &db)
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn wallet_presignature_ids(wallet: &str, db: &str) -> Result<String, ApiError> {
+    let wallet: ThresholdWallet = parse_context(wallet)?;
+    let storage = IndexedDbStorage::open(db).await?;
+    Ok(serde_json::to_string(&wallet.presignature_ids(&storage)?).unwrap())
+}
+
+/// Consumes presignature `id` of the wallet in the IndexedDB database `db`
+/// and returns this party's `LocalSignature` over `message_hex`, hashed with
+/// SHA-256 or, if `prehashed`, as the 32 byte digest it already is, as json.
+/// The local signature is returned only once the database holds the
+/// presignature as consumed; if the write fails, nothing computed from the
+/// presignature leaves this call.
+#[wasm_bindgen]
+pub async fn gg18_wallet_sign_presigned(
+    wallet: String,
+    db: String,
+    id: String,
+    message_hex: String,
+    prehashed: bool,
+) -> Result<String, JsValue> {
+    wallet_sign_presigned(&wallet, &db, &id, &message_hex, prehashed)
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn wallet_sign_presigned(
+    wallet: &str,
+    db: &str,
+    id: &str,
+    message_hex: &str,
+    prehashed: bool,
+) -> Result<String, ApiError> {
+    let wallet: ThresholdWallet = parse_context(wallet)?;
+    let digest = signing_digest(message_hex, prehashed)?;
+    let mut storage = IndexedDbStorage::open(db).await?;
+    let local_sig = wallet.sign_presigned(&mut storage, id, &digest)?;
+    // IndexedDbStorage holds the consumed presignature in memory until the
+    // flush, so the local signature is dropped unless the write succeeds
+    storage.flush().await?;
+    Ok(serde_json::to_string(&local_sig).unwrap())
+}
+
+/// Encrypts this party's share to the escrow agents' key, with proofs that
+/// it is the live share. `agents` is the `VerifiableSS` of the agents' key
+/// and `terms` the `EscrowTerms`, both as json; returns the `EscrowedShare`
+/// as json.
+#[cfg(feature = "escrow")]
+#[wasm_bindgen]
+pub fn gg18_escrow_share(
+    key_store: String,
+    agents: String,
+    terms: String,
+) -> Result<String, JsValue> {
+    let key = read_key_store(&key_store)?;
+    let agents: VerifiableSS = parse_context(&agents)?;
+    let terms: EscrowTerms = parse_context(&terms)?;
+    if agents.commitments.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidParameters, "no escrow key").into());
+    }
+    Ok(serde_json::to_string(&key.escrow_share(&agents, terms)).unwrap())
+}
+
+/// Checks an `EscrowedShare` of any party of this key against the keygen
+/// commitments, e.g. when an escrow agent receives it.
+#[cfg(feature = "escrow")]
+#[wasm_bindgen]
+pub fn gg18_escrow_verify(
+    key_store: String,
+    agents: String,
+    escrowed: String,
+) -> Result<(), JsValue> {
+    let key = read_key_store(&key_store)?;
+    let agents: VerifiableSS = parse_context(&agents)?;
+    let escrowed: EscrowedShare = parse_context(&escrowed)?;
+    if agents.commitments.is_empty() {
+        return Err(ApiError::new(ErrorCode::InvalidParameters, "no escrow key").into());
+    }
+    key.verify_escrowed_share(&agents, &escrowed)
+        .map_err(ApiError::from)?;
+    Ok(())
+}
+
+/// Signs `message_str` unhashed: the bytes it decodes to as hex, or else
+/// its own bytes, read as an integer. Use
+/// `gg18_sign_client_new_context_with_digest` to have the message hashed or
+/// to pass a digest.
+#[wasm_bindgen]
+pub async fn gg18_sign_client_new_context(
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: String,
+    message_str: String,
+) -> Result<String, JsValue> {
+    let message = unhashed_message(&message_str);
+    sign_client_new_context(addr, t, n, &key_store, message)
+        .await
+        .map_err(JsValue::from)
+}
+
+/// Signs `message_hex` hashed with SHA-256 or, if `prehashed`, as the
+/// 32 byte digest it already is.
+#[wasm_bindgen]
+pub async fn gg18_sign_client_new_context_with_digest(
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: String,
+    message_hex: String,
+    prehashed: bool,
+) -> Result<String, JsValue> {
+    let message = signing_digest(&message_hex, prehashed)?;
+    sign_client_new_context(addr, t, n, &key_store, message)
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn sign_client_new_context(
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: &str,
+    message: SigningDigest,
+) -> Result<String, ApiError> {
     let client = new_client_with_headers();
-    // round 0: collect signers IDs
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round0",
-        serde_json::to_string(&context.party_id).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round0_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round0",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    // the key store and parameters are checked before signing up
+    let context = sign_context(addr, t, n, key_store, message, 0, String::new())?;
+
+    //signup:
+    let (party_num_int, uuid) = match signup_sign(&client, &context.addr).await? {
+        PartySignup { number, uuid } => (number, uuid),
+    };
+
+    Ok(serde_json::to_string(&GG18SignClientContext {
+        party_num_int,
+        uuid,
+        ..context
+    })
+    .unwrap())
+}
+
+// the message as gg18_sign_client_new_context has always taken it
+fn unhashed_message(message_str: &str) -> SigningDigest {
+    match hex::decode(message_str) {
+        Ok(x) => SigningDigest::from_unhashed(&x),
+        Err(_e) => SigningDigest::from_unhashed(message_str.as_bytes()),
+    }
+}
+
+fn signing_digest(message_hex: &str, prehashed: bool) -> Result<SigningDigest, ApiError> {
+    let message = hex::decode(message_hex)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidParameters, "message is not hex"))?;
+    if !prehashed {
+        return Ok(SigningDigest::hash::<Sha256>(&message));
+    }
+    SigningDigest::from_prehashed(&message)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidParameters, "digest is not 32 bytes"))
+}
+
+/// A signing context for a ceremony whose messages the caller carries
+/// instead of the relay, see `gg18_sign_round0_outgoing`. `party` is this
+/// party's number among the signers, 1 to `t + 1` or to the count set by
+/// `gg18_sign_client_set_online`, and `uuid` the id of the ceremony, the
+/// same for all signers. `message_hex` is signed as
+/// `gg18_sign_client_new_context_with_digest` signs it.
+#[wasm_bindgen]
+pub fn gg18_sign_context(
+    party: u16,
+    uuid: String,
+    t: usize,
+    n: usize,
+    key_store: String,
+    message_hex: String,
+    prehashed: bool,
+) -> Result<String, JsValue> {
+    check_party_number(party, n)?;
+    let message = signing_digest(&message_hex, prehashed)?;
+    let context = sign_context(String::new(), t, n, &key_store, message, party, uuid)?;
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn sign_context(
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: &str,
+    message: SigningDigest,
+    party_num_int: u16,
+    uuid: String,
+) -> Result<GG18SignClientContext, ApiError> {
+    let key = read_key_store(key_store)?;
+    let LocalKey {
+        party_keys,
+        shared_keys,
+        party_id,
+        vss_scheme_vec,
+        paillier_key_vec: paillier_key_vector,
+        y_sum,
+    } = key;
+    let params = Parameters::new(t, n)?;
+    if params.share_count() != vss_scheme_vec.len() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidParameters,
+            "party count does not match the key store",
+        ));
+    }
+
+    Ok(GG18SignClientContext {
+        addr,
+        party_keys,
+        shared_keys,
+        party_id,
+        vss_scheme_vec,
+        paillier_key_vector,
+        y_sum,
+        threshould: params.threshold() as u16,
+        party_num_int,
+        uuid,
+        sign_keys: None,
+        com: None,
+        decommit: None,
+        m_a_k: None,
+        round1_ans_vec: None,
+        signers_vec: None,
+        mta: None,
+        bc1_vec: None,
+        b_proof_vec: None,
+        delta_inv: None,
+        message,
+        sigma: None,
+        phase5_com: None,
+        phase_5a_decom: None,
+        helgamal_proof: None,
+        dlog_proof_rho: None,
+        commit5a_vec: None,
+        local_sig: None,
+        r: None,
+        phase5_com2: None,
+        phase_5d_decom2: None,
+        decommit5a_and_elgamal_and_dlog_vec: None,
+        decommit5a_and_elgamal_and_dlog_vec_includes_i: None,
+        s_i: None,
+        commit5c_vec: None,
+        agreement: None,
+        adaptor_point: None,
+        nonce_shares: None,
+        online: None,
+        hellos: None,
+        relay_numbers: None,
+        round: RoundState::default(),
+    })
+}
+
+/// Lets `parties` parties, at least `t + 1` and at most `n`, start the
+/// ceremony, numbered 1 to `parties`. Every one of them sets the same count
+/// before round 0.
+///
+/// A party may then drop out of round 0 or 1: at the round timeout of a
+/// session, or once the caller hands over the messages it has, the round
+/// goes on with whoever broadcast, if at least `t + 1` are left. Those
+/// interpolate their key shares over themselves after round 1, which is
+/// safe as nothing sent until then depends on them, and sign together. From
+/// round 2 every one of them is needed and a dropout fails the ceremony.
+/// `gg18_sign_client_round0` and `gg18_sign_client_round1` have no timeout
+/// and wait for everyone.
+///
+/// From round 2 the signers left number themselves 1 to k in a session of
+/// their own, so that signers that disagree on who is left fail instead of
+/// mixing messages. The relay entries of those rounds are keyed by these
+/// numbers; errors name parties by the numbers they started with.
+///
+/// With the relay, `/signupsign` numbers up to `online` parties, as set in
+/// its params.json.
+#[wasm_bindgen]
+pub fn gg18_sign_client_set_online(context: String, parties: u16) -> Result<String, JsValue> {
+    set_online(&context, parties).map_err(JsValue::from)
+}
+
+fn set_online(context: &str, parties: u16) -> Result<String, ApiError> {
+    let mut context: GG18SignClientContext = parse_context(context)?;
+    if context.round.started() {
+        return Err(ApiError::new(
+            ErrorCode::WrongRound,
+            "the online parties are set before round 0",
+        ));
+    }
+    let parties = usize::from(parties);
+    if parties <= usize::from(context.threshould) || parties > context.vss_scheme_vec.len() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidParameters,
+            "online parties out of range",
+        ));
+    }
+    check_party_number(context.party_num_int, parties)?;
+    context.online = Some(parties as u16);
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+/// Makes the ceremony produce an adaptor signature encrypted to
+/// `adaptor_point`, a point as json, instead of a signature: round 9 then
+/// returns an `AdaptorSignature` as json, which `gg18_adaptor_complete`
+/// turns into a signature given the point's discrete log. Every signer sets
+/// the same point before round 4.
+#[wasm_bindgen]
+pub fn gg18_sign_client_set_adaptor_point(
+    context: String,
+    adaptor_point: String,
+) -> Result<String, JsValue> {
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    if context.local_sig.is_some() {
+        return Err(ApiError::new(
+            ErrorCode::WrongRound,
+            "the adaptor point is set before round 4",
+        )
+        .into());
+    }
+    context.adaptor_point = Some(parse_context(&adaptor_point)?);
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+#[wasm_bindgen]
+pub async fn gg18_sign_client_round0(context: String, delay: u32) -> Result<String, JsValue> {
+    sign_round0(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn sign_round0(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round0");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(0)?;
+    let client = new_client_with_headers();
+    let outgoing = sign_round0_outgoing(&context)?;
+    let answers = context
+        .relay()
+        .exchange_partial(&client, "round0", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(0)?;
+    sign_round0_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+// round 0: collect signers IDs and capabilities
+fn sign_round0_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    check_party_number(context.party_num_int, usize::from(context.parties()))?;
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(&(context.party_id, Capabilities::local())).unwrap(),
+    ))
+}
 
+// Rounds 0 and 1 go on without the parties that did not broadcast, see
+// `gg18_sign_client_set_online`; `answers` has `None` for those.
+fn sign_round0_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[Option<String>],
+) -> Result<(), ApiError> {
     let mut j = 0;
-    let mut signers_vec: Vec<usize> = Vec::new();
-    for i in 1..=context.threshould + 1 {
+    let mut hellos: Vec<(u16, usize, Capabilities)> = Vec::new();
+    let mut dropped = Vec::new();
+    for i in 1..=context.parties() {
         if i == context.party_num_int {
-            signers_vec.push((context.party_id - 1).into());
+            hellos.push((i, usize::from(context.party_id - 1), Capabilities::local()));
         } else {
-            let signer_j: u16 = serde_json::from_str(&round0_ans_vec[j]).unwrap();
-            signers_vec.push((signer_j - 1).into());
+            match &answers[j] {
+                Some(answer) => {
+                    let (signer_j, capabilities_j): (u16, Capabilities) = parse_hello(answer, i)?;
+                    hellos.push((i, usize::from(signer_j - 1), capabilities_j));
+                }
+                None => dropped.push(i),
+            }
             j += 1;
         }
     }
+    check_left(context, hellos.len(), "round0", dropped)?;
+    let signers_vec = hellos
+        .iter()
+        .map(|(_, index, _)| *index)
+        .collect::<Vec<usize>>();
+    let announced = hellos
+        .iter()
+        .map(|(i, _, capabilities)| (*i, capabilities.clone()))
+        .collect::<Vec<_>>();
+    context.agreement = Some(negotiate(&announced)?);
+    Parameters::new(context.threshould as usize, context.vss_scheme_vec.len())
+        .and_then(|params| params.check_online_set(&signers_vec))
+        .map_err(|e| ApiError::from(e).in_round("round0"))?;
+
+    // a party restarted from a checkpoint keeps the nonce shares it already
+    // drew; drawing fresh ones would fork its nonce within the session.
+    if context.sign_keys.is_none() {
+        let quorum =
+            quorum_keys(context, &signers_vec).map_err(|e| ApiError::from(e).in_round("round0"))?;
+        let sign_keys = SignKeys::create_from_quorum(&quorum);
+        context.sign_keys = Some(sign_keys);
+    }
 
-    let private =
-        PartyPrivate::set_private(context.party_keys.clone(), context.shared_keys.clone());
-
-    let sign_keys = SignKeys::create(
-        &private,
-        &context.vss_scheme_vec[usize::from(signers_vec[usize::from(context.party_num_int - 1)])],
-        signers_vec[usize::from(context.party_num_int - 1)].into(),
-        &signers_vec,
-    );
-
-    let xi_com_vec = Keys::get_commitments_to_xi(&context.vss_scheme_vec);
-
-    context.sign_keys = Some(sign_keys);
     context.signers_vec = Some(signers_vec);
-    context.xi_com_vec = Some(xi_com_vec);
+    context.hellos = Some(hellos);
+    Ok(())
+}
 
-    serde_json::to_string(&context).unwrap()
+// fails the round unless `t + 1` parties are left, naming those that are not
+fn check_left(
+    context: &GG18SignClientContext,
+    left: usize,
+    round: &str,
+    dropped: Vec<u16>,
+) -> Result<(), ApiError> {
+    if left <= usize::from(context.threshould) {
+        return Err(RoundTimeout {
+            round: round.to_string(),
+            missing: dropped,
+        }
+        .into());
+    }
+    Ok(())
 }
 
-#[wasm_bindgen]
-pub async fn gg18_sign_client_round1(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
-    let client = new_client_with_headers();
-    let (com, decommit) = context.sign_keys.as_ref().unwrap().phase1_broadcast();
-    let (m_a_k, _) = MessageA::a(
-        &context.sign_keys.as_ref().unwrap().k_i,
+fn round1_prepared(context: &GG18SignClientContext) -> bool {
+    context.com.is_some() && context.decommit.is_some() && context.m_a_k.is_some()
+}
+
+fn prepare_round1(context: &mut GG18SignClientContext) -> Result<(), ApiError> {
+    if round1_prepared(context) {
+        return Ok(());
+    }
+    let sign_keys = required(&context.sign_keys, "sign_keys")?;
+    let (com, decommit) = sign_keys.phase1_broadcast();
+    let randomness = BigInt::sample_below(&context.party_keys.ek.n);
+    let m_a_k = MessageA::a_with_params(
+        &sign_keys.k_i,
         &context.party_keys.ek,
+        &randomness,
         &[],
+        &proof_params(context.agreement),
     );
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round1",
-        serde_json::to_string(&(com.clone(), m_a_k)).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round1_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round1",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
-
     context.com = Some(com);
     context.decommit = Some(decommit);
-    context.round1_ans_vec = Some(round1_ans_vec);
+    context.m_a_k = Some(m_a_k);
+    Ok(())
+}
 
-    serde_json::to_string(&context).unwrap()
+/// Computes the round 1 commitment to g^gamma_i and the encryption of k_i
+/// without sending them. Persist the returned context before calling
+/// `gg18_sign_client_round1`: a party that crashes after broadcasting then
+/// resumes from it and rebroadcasts the same commitments instead of new ones.
+/// `gg18_sign_client_round1` refuses a context that was not prepared.
+#[wasm_bindgen]
+pub fn gg18_sign_client_round1_prepare(context: String) -> Result<String, JsValue> {
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    prepare_round1(&mut context)?;
+    Ok(serde_json::to_string(&context).unwrap())
 }
 
+/// Broadcasts the commitments of `gg18_sign_client_round1_prepare`, which
+/// the caller has persisted, and collects the others'.
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round2(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round1(context: String, delay: u32) -> Result<String, JsValue> {
+    sign_round1(context, delay, &mut Intake::client())
+        .await
+        .map_err(JsValue::from)
+}
+
+async fn sign_round1(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round1");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(1)?;
+    // only commitments the caller already holds are sent; drawing them here
+    // would post ones that a crash before the context is saved loses
+    if !round1_prepared(&context) {
+        return Err(ApiError::new(
+            ErrorCode::InvalidContext,
+            "round 1 commitments are not prepared, save the context of gg18_sign_client_round1_prepare first",
+        ));
+    }
     let client = new_client_with_headers();
-    let mut j = 0;
-    let mut bc1_vec: Vec<SignBroadcastPhase1> = Vec::new();
-    let mut m_a_vec: Vec<MessageA> = Vec::new();
+    let outgoing = written(context.agreement, || sign_round1_outgoing(&mut context))?;
+    let answers = context
+        .relay()
+        .exchange_partial(&client, "round1", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(1)?;
+    sign_round1_incoming(&mut context, answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
-    for i in 1..context.threshould + 2 {
-        if i == context.party_num_int {
-            bc1_vec.push(context.com.as_ref().unwrap().clone());
-        //   m_a_vec.push(m_a_k.clone());
-        } else {
-            //     if signers_vec.contains(&(i as usize)) {
-            let (bc1_j, m_a_party_j): (SignBroadcastPhase1, MessageA) =
-                serde_json::from_str(&context.round1_ans_vec.as_ref().unwrap()[j]).unwrap();
-            bc1_vec.push(bc1_j);
-            m_a_vec.push(m_a_party_j);
+fn sign_round1_outgoing(context: &mut GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    prepare_round1(context)?;
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(&(
+            required(&context.com, "com")?.clone(),
+            required(&context.m_a_k, "m_a_k")?.clone(),
+        ))
+        .unwrap(),
+    ))
+}
 
-            j += 1;
-            //       }
+// The parties of round 0 that broadcast in round 1 sign; the answers are
+// parsed in round 2, one counterparty at a time. Nothing sent so far depends
+// on w_i, so after a dropout in either round the signers left interpolate it
+// over themselves and move to a session of their own, numbered 1 to k,
+// bound to who they are.
+fn sign_round1_incoming(
+    context: &mut GG18SignClientContext,
+    answers: Vec<Option<String>>,
+) -> Result<(), ApiError> {
+    let hellos = context.hellos.take().ok_or_else(|| missing("hellos"))?;
+    let me = context.party_num_int;
+    let mut answers = (1..=context.parties())
+        .filter(|i| *i != me)
+        .zip(answers)
+        .collect::<Vec<(u16, Option<String>)>>();
+    let mut signers = Vec::new();
+    let mut round1_ans_vec = Vec::new();
+    let mut dropped = Vec::new();
+    for (i, index, capabilities) in hellos {
+        if i != me {
+            let answer = answers
+                .iter_mut()
+                .find(|(j, _)| *j == i)
+                .and_then(|(_, answer)| answer.take());
+            match answer {
+                Some(answer) => round1_ans_vec.push(answer),
+                None => {
+                    dropped.push(i);
+                    continue;
+                }
+            }
         }
+        signers.push((i, index, capabilities));
     }
-    assert_eq!(context.signers_vec.as_ref().unwrap().len(), bc1_vec.len());
-
-    //////////////////////////////////////////////////////////////////////////////
-    let mut m_b_gamma_send_vec: Vec<MessageB> = Vec::new();
-    let mut beta_vec: Vec<Scalar> = Vec::new();
-    let mut m_b_w_send_vec: Vec<MessageB> = Vec::new();
-    let mut ni_vec: Vec<Scalar> = Vec::new();
-    let mut j = 0;
-    for i in 1..context.threshould + 2 {
-        if i != context.party_num_int {
-            let (m_b_gamma, beta_gamma, _, _) = MessageB::b(
-                &context.sign_keys.as_ref().unwrap().gamma_i,
-                &context.paillier_key_vector
-                    [usize::from(context.signers_vec.as_ref().unwrap()[usize::from(i - 1)])],
-                m_a_vec[j].clone(),
-                &[],
-            )
-            .unwrap();
-            let (m_b_w, beta_wi, _, _) = MessageB::b(
-                &context.sign_keys.as_ref().unwrap().w_i,
-                &context.paillier_key_vector
-                    [usize::from(context.signers_vec.as_ref().unwrap()[usize::from(i - 1)])],
-                m_a_vec[j].clone(),
-                &[],
-            )
-            .unwrap();
-            m_b_gamma_send_vec.push(m_b_gamma);
-            m_b_w_send_vec.push(m_b_w);
-            beta_vec.push(beta_gamma);
-            ni_vec.push(beta_wi);
-            j += 1;
-        }
+    check_left(context, signers.len(), "round1", dropped)?;
+    context.round1_ans_vec = Some(round1_ans_vec);
+    if signers.len() == usize::from(context.parties()) {
+        return Ok(());
     }
 
+    let announced = signers
+        .iter()
+        .map(|(i, _, capabilities)| (*i, capabilities.clone()))
+        .collect::<Vec<_>>();
+    context.agreement = Some(negotiate(&announced)?);
+    let signers_vec = signers
+        .iter()
+        .map(|(_, index, _)| *index)
+        .collect::<Vec<usize>>();
+    let quorum =
+        quorum_keys(context, &signers_vec).map_err(|e| ApiError::from(e).in_round("round1"))?;
+    context
+        .sign_keys
+        .as_mut()
+        .ok_or_else(|| missing("sign_keys"))?
+        .requorum(&quorum);
+    context.signers_vec = Some(signers_vec);
+
+    let relay_numbers = signers.iter().map(|(i, _, _)| *i).collect::<Vec<u16>>();
+    context.party_num_int = relay_numbers.iter().position(|i| *i == me).unwrap() as u16 + 1;
+    context.uuid = format!(
+        "{}-{}",
+        context.uuid,
+        relay_numbers
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<String>>()
+            .join(".")
+    );
+    context.relay_numbers = Some(relay_numbers);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn gg18_sign_client_round2(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round2(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round2(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round2");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(2)?;
+    let client = new_client_with_headers();
+    let sign_keys = required(&context.sign_keys, "sign_keys")?.clone();
+    let mut mta = MtaAccumulator::new(&sign_keys);
+    let mut bc1_vec: Vec<SignBroadcastPhase1> = Vec::new();
+
+    // each counterparty's MessageA is answered and its MessageBs sent before
+    // the next one is parsed, so that one counterparty's MtA messages are in
+    // memory at a time however large the committee
+    let round1_ans_vec = context
+        .round1_ans_vec
+        .take()
+        .ok_or_else(|| missing("round1_ans_vec"))?;
     let mut j = 0;
-    for i in 1..context.threshould + 2 {
-        if i != context.party_num_int {
-            assert!(sendp2p(
+    for i in 1..=context.parties() {
+        if i == context.party_num_int {
+            bc1_vec.push(required(&context.com, "com")?.clone());
+        } else {
+            let (bc1_j, answer) = written(context.agreement, || {
+                sign_round2_answer(&context, &sign_keys, &mut mta, i, &round1_ans_vec[j])
+            })?;
+            bc1_vec.push(bc1_j);
+            sendp2p(
                 &client,
                 &context.addr,
                 context.party_num_int,
                 i,
                 "round2",
-                serde_json::to_string(&(m_b_gamma_send_vec[j].clone(), m_b_w_send_vec[j].clone()))
-                    .unwrap(),
+                answer,
                 context.uuid.clone(),
             )
             .await
-            .is_ok());
+            .map_err(|_| network_error("round2"))?;
             j += 1;
         }
     }
-
-    let round2_ans_vec = poll_for_p2p(
+    drop(round1_ans_vec);
+    context.round.incoming(2)?;
+
+    // the answers are verified as they arrive; only the b proofs phase 4
+    // checks are kept, in party order
+    let signers_vec = required(&context.signers_vec, "signers_vec")?.clone();
+    let quorum =
+        quorum_keys(&context, &signers_vec).map_err(|e| ApiError::from(e).in_round("round2"))?;
+    let mut b_proof_vec: Vec<Option<DLogProof>> = vec![None; usize::from(context.parties() - 1)];
+    poll_for_p2p_each(
         &client,
         &context.addr,
         context.party_num_int,
-        context.threshould + 1,
+        context.parties(),
         delay,
         "round2",
         context.uuid.clone(),
+        intake.timeout_ms,
+        &mut intake.limiter,
+        |i, answer| {
+            sign_round2_receive(
+                &context,
+                &sign_keys,
+                &mut mta,
+                &quorum,
+                &mut b_proof_vec,
+                i,
+                &answer,
+            )
+        },
     )
-    .await;
+    .await?;
+    sign_round2_finish(&mut context, mta, bc1_vec, b_proof_vec)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
-    context.round2_ans_vec = Some(round2_ans_vec);
-    context.beta_vec = Some(beta_vec);
-    context.ni_vec = Some(ni_vec);
+// The MtA state is kept in the context between the two halves, with the
+// others' commitments gathered in the first.
+fn sign_round2_outgoing(context: &mut GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    let sign_keys = required(&context.sign_keys, "sign_keys")?.clone();
+    let mut mta = MtaAccumulator::new(&sign_keys);
+    let mut bc1_vec: Vec<SignBroadcastPhase1> = Vec::new();
+    let mut messages = Vec::new();
+    let round1_ans_vec = context
+        .round1_ans_vec
+        .take()
+        .ok_or_else(|| missing("round1_ans_vec"))?;
+    let mut j = 0;
+    for i in 1..=context.parties() {
+        if i == context.party_num_int {
+            bc1_vec.push(required(&context.com, "com")?.clone());
+        } else {
+            let (bc1_j, answer) =
+                sign_round2_answer(context, &sign_keys, &mut mta, i, &round1_ans_vec[j])?;
+            bc1_vec.push(bc1_j);
+            messages.push((i, answer));
+            j += 1;
+        }
+    }
+    context.mta = Some(mta);
     context.bc1_vec = Some(bc1_vec);
+    Ok(Outgoing::P2p(messages))
+}
 
-    serde_json::to_string(&context).unwrap()
+fn sign_round2_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let sign_keys = required(&context.sign_keys, "sign_keys")?.clone();
+    let mut mta = context.mta.take().ok_or_else(|| missing("mta"))?;
+    let bc1_vec = context.bc1_vec.take().ok_or_else(|| missing("bc1_vec"))?;
+    let quorum = quorum_keys(context, required(&context.signers_vec, "signers_vec")?)
+        .map_err(|e| ApiError::from(e).in_round("round2"))?;
+    let mut b_proof_vec: Vec<Option<DLogProof>> = vec![None; usize::from(context.parties() - 1)];
+    let others = (1..=context.parties()).filter(|i| *i != context.party_num_int);
+    for (i, answer) in others.zip(answers) {
+        sign_round2_receive(
+            context,
+            &sign_keys,
+            &mut mta,
+            &quorum,
+            &mut b_proof_vec,
+            i,
+            answer,
+        )?;
+    }
+    sign_round2_finish(context, mta, bc1_vec, b_proof_vec)
 }
 
-#[wasm_bindgen]
-pub async fn gg18_sign_client_round3(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
-    let client = new_client_with_headers();
-    let mut m_b_gamma_rec_vec: Vec<MessageB> = Vec::new();
-    let mut m_b_w_rec_vec: Vec<MessageB> = Vec::new();
+// Answers party `i`'s round 1 message: its commitment and the MessageBs for
+// it, as json.
+fn sign_round2_answer(
+    context: &GG18SignClientContext,
+    sign_keys: &SignKeys,
+    mta: &mut MtaAccumulator,
+    i: u16,
+    message: &str,
+) -> Result<(SignBroadcastPhase1, String), ApiError> {
+    let signers_vec = required(&context.signers_vec, "signers_vec")?;
+    let (bc1_j, m_a_party_j): (SignBroadcastPhase1, MessageA) =
+        parse_message(message, "round1", i)?;
+    let (m_b_gamma, m_b_w) = mta
+        .respond(
+            sign_keys,
+            &context.paillier_key_vector[signers_vec[usize::from(i - 1)]],
+            m_a_party_j,
+            &proof_params(context.agreement),
+            context.uuid.as_bytes(),
+            context.party_num_int,
+        )
+        .map_err(|e| ApiError::from(e).in_round("round1").with_party(i))?;
+    Ok((bc1_j, serde_json::to_string(&(m_b_gamma, m_b_w)).unwrap()))
+}
 
-    for i in 0..context.threshould {
-        //  if signers_vec.contains(&(i as usize)) {
-        let (m_b_gamma_i, m_b_w_i): (MessageB, MessageB) =
-            serde_json::from_str(&context.round2_ans_vec.as_ref().unwrap()[i as usize]).unwrap();
-        m_b_gamma_rec_vec.push(m_b_gamma_i);
-        m_b_w_rec_vec.push(m_b_w_i);
-        //     }
+// Checks party `i`'s MessageBs and keeps its b proof.
+fn sign_round2_receive(
+    context: &GG18SignClientContext,
+    sign_keys: &SignKeys,
+    mta: &mut MtaAccumulator,
+    quorum: &QuorumKeys,
+    b_proof_vec: &mut [Option<DLogProof>],
+    i: u16,
+    answer: &str,
+) -> Result<(), ApiError> {
+    let signers_vec = required(&context.signers_vec, "signers_vec")?;
+    let (m_b_gamma, m_b_w): (MessageB, MessageB) = parse_message(answer, "round2", i)?;
+    mta.receive(
+        sign_keys,
+        &context.party_keys.dk,
+        &m_b_gamma,
+        &m_b_w,
+        context.uuid.as_bytes(),
+        i,
+    )
+    .map_err(|e| ApiError::from(e).in_round("round2").with_party(i))?;
+    let g_w_i = quorum.g_w(signers_vec[usize::from(i - 1)]).unwrap();
+    if &m_b_w.b_proof.pk != g_w_i {
+        return Err(
+            ApiError::new(ErrorCode::InvalidProof, "MtA proof for the wrong key")
+                .in_round("round2")
+                .with_party(i),
+        );
     }
+    let slot = if i < context.party_num_int {
+        i - 1
+    } else {
+        i - 2
+    };
+    b_proof_vec[usize::from(slot)] = Some(m_b_gamma.b_proof);
+    Ok(())
+}
 
-    let mut alpha_vec: Vec<Scalar> = Vec::new();
-    let mut miu_vec: Vec<Scalar> = Vec::new();
+fn sign_round2_finish(
+    context: &mut GG18SignClientContext,
+    mta: MtaAccumulator,
+    bc1_vec: Vec<SignBroadcastPhase1>,
+    b_proof_vec: Vec<Option<DLogProof>>,
+) -> Result<(), ApiError> {
+    assert_eq!(
+        required(&context.signers_vec, "signers_vec")?.len(),
+        bc1_vec.len()
+    );
+    // two MtA proofs from each other signer
+    metrics().proofs_verified("round2", 2 * usize::from(context.parties() - 1));
 
-    let mut j = 0;
-    for i in 1..context.threshould + 2 {
-        if i != context.party_num_int {
-            let m_b = m_b_gamma_rec_vec[j].clone();
+    context.mta = Some(mta);
+    context.bc1_vec = Some(bc1_vec);
+    context.b_proof_vec = Some(b_proof_vec.into_iter().map(Option::unwrap).collect());
+    Ok(())
+}
 
-            let alpha_ij_gamma = m_b
-                .verify_proofs_get_alpha(
-                    &context.party_keys.dk,
-                    &context.sign_keys.as_ref().unwrap().k_i,
-                )
-                .expect("wrong dlog or m_b");
-            let m_b = m_b_w_rec_vec[j].clone();
-            let alpha_ij_wi = m_b
-                .verify_proofs_get_alpha(
-                    &context.party_keys.dk,
-                    &context.sign_keys.as_ref().unwrap().k_i,
-                )
-                .expect("wrong dlog or m_b");
-            alpha_vec.push(alpha_ij_gamma.0);
-            miu_vec.push(alpha_ij_wi.0);
-            let g_w_i = Keys::update_commitments_to_xi(
-                &context.xi_com_vec.as_ref().unwrap()
-                    [usize::from(context.signers_vec.as_ref().unwrap()[usize::from(i - 1)])],
-                &context.vss_scheme_vec
-                    [usize::from(context.signers_vec.as_ref().unwrap()[usize::from(i - 1)])],
-                context.signers_vec.as_ref().unwrap()[usize::from(i - 1)],
-                &context.signers_vec.as_ref().unwrap(),
-            );
-            assert_eq!(m_b.b_proof.pk, g_w_i);
-            j += 1;
-        }
-    }
-    //////////////////////////////////////////////////////////////////////////////
-    let delta_i = context
-        .sign_keys
-        .as_ref()
-        .unwrap()
-        .phase2_delta_i(&alpha_vec, &context.beta_vec.as_ref().unwrap());
-    let sigma = context
-        .sign_keys
-        .as_ref()
-        .unwrap()
-        .phase2_sigma_i(&miu_vec, &context.ni_vec.as_ref().unwrap());
+#[wasm_bindgen]
+pub async fn gg18_sign_client_round3(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round3(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
 
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round3",
+async fn sign_round3(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round3");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(3)?;
+    let client = new_client_with_headers();
+    let outgoing = written(context.agreement, || sign_round3_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round3", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(3)?;
+    sign_round3_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn sign_round3_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    let delta_i = required(&context.mta, "mta")?.delta_i();
+    Ok(Outgoing::Broadcast(
         serde_json::to_string(&delta_i).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round3_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round3",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    ))
+}
+
+fn sign_round3_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
+    let mta = context.mta.take().ok_or_else(|| missing("mta"))?;
+    let delta_i = mta.delta_i();
+    let sigma = mta.sigma_i();
     let mut delta_vec: Vec<Scalar> = Vec::new();
     format_vec_from_reads(
-        &round3_ans_vec,
+        answers,
         context.party_num_int as usize,
         delta_i,
         &mut delta_vec,
-    );
+        "round3",
+    )?;
     let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
 
-    context.m_b_gamma_rec_vec = Some(m_b_gamma_rec_vec);
     context.delta_inv = Some(delta_inv);
     context.sigma = Some(sigma);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round4(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round4(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round4(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round4(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round4");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(4)?;
     let client = new_client_with_headers();
-    // decommit to gamma_i
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round4",
-        serde_json::to_string(&context.decommit.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round4_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round4",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    let outgoing = written(context.agreement, || sign_round4_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round4", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(4)?;
+    sign_round4_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+// decommit to gamma_i, and for an adaptor signature send gamma_i Y
+fn sign_round4_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    let decommit = required(&context.decommit, "decommit")?;
+    let message = match &context.adaptor_point {
+        Some(point) => {
+            let share = NonceShare::new(&required(&context.sign_keys, "sign_keys")?.gamma_i, point);
+            serde_json::to_string(&(decommit, share)).unwrap()
+        }
+        None => serde_json::to_string(decommit).unwrap(),
+    };
+    Ok(Outgoing::Broadcast(message))
+}
 
+fn sign_round4_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
     let mut decommit_vec: Vec<SignDecommitPhase1> = Vec::new();
-    format_vec_from_reads(
-        &round4_ans_vec,
-        context.party_num_int as usize,
-        context.decommit.clone().unwrap(),
-        &mut decommit_vec,
-    );
+    let mut nonce_shares = None;
+    match context.adaptor_point.clone() {
+        Some(point) => {
+            let share =
+                NonceShare::new(&required(&context.sign_keys, "sign_keys")?.gamma_i, &point);
+            let mut messages: Vec<(SignDecommitPhase1, NonceShare)> = Vec::new();
+            format_vec_from_reads(
+                answers,
+                context.party_num_int as usize,
+                (required(&context.decommit, "decommit")?.clone(), share),
+                &mut messages,
+                "round4",
+            )?;
+            let (decommits, shares): (Vec<_>, Vec<_>) = messages.into_iter().unzip();
+            decommit_vec = decommits;
+            nonce_shares = Some(shares);
+        }
+        None => format_vec_from_reads(
+            answers,
+            context.party_num_int as usize,
+            required(&context.decommit, "decommit")?.clone(),
+            &mut decommit_vec,
+            "round4",
+        )?,
+    }
+    let g_gamma = decommit_vec
+        .iter()
+        .map(|decommit| decommit.g_gamma_i.clone())
+        .collect::<Vec<Point>>();
 
     let decomm_i = decommit_vec.remove(usize::from(context.party_num_int - 1));
     &context
         .bc1_vec
         .as_mut()
-        .unwrap()
+        .ok_or_else(|| missing("bc1_vec"))?
         .remove(usize::from(context.party_num_int - 1));
-    let b_proof_vec = (0..context.m_b_gamma_rec_vec.as_ref().unwrap().len())
-        .map(|i| &context.m_b_gamma_rec_vec.as_ref().unwrap()[i].b_proof)
+    let b_proof_vec = required(&context.b_proof_vec, "b_proof_vec")?
+        .iter()
         .collect::<Vec<&DLogProof>>();
 
+    let blame = SignKeys::phase4_blame(
+        &b_proof_vec,
+        &decommit_vec,
+        required(&context.bc1_vec, "bc1_vec")?,
+    );
     let R = SignKeys::phase4(
-        &context.delta_inv.as_ref().unwrap(),
+        required(&context.delta_inv, "delta_inv")?,
         &b_proof_vec,
         decommit_vec,
-        &context.bc1_vec.as_ref().unwrap(),
+        required(&context.bc1_vec, "bc1_vec")?,
     )
-    .expect("bad gamma_i decommit");
+    .map_err(|e| {
+        ApiError::from(e)
+            .in_round("round4")
+            .with_blame(blamed_parties(&blame, Some(context.party_num_int)))
+    })?;
+    metrics().proofs_verified("round4", b_proof_vec.len());
 
     // adding local g_gamma_i
-    let R = R + decomm_i.g_gamma_i * context.delta_inv.as_ref().unwrap();
-
-    // we assume the message is already hashed (by the signer).
-    let message = &context.message[..];
-    let message_bn = BigInt::from_bytes_be(message);
-    let local_sig = LocalSignature::phase5_local_sig(
-        &context.sign_keys.as_ref().unwrap().k_i,
-        &message_bn,
+    let R = R + decomm_i.g_gamma_i * required(&context.delta_inv, "delta_inv")?;
+
+    // the decommitted Gamma_i are checked, so each gamma_i Y is checked
+    // against them
+    let adapted_R = match &nonce_shares {
+        Some(shares) => {
+            let point = required(&context.adaptor_point, "adaptor_point")?;
+            for (i, (g_gamma_i, share)) in g_gamma.iter().zip(shares).enumerate() {
+                share.verify(g_gamma_i, point).map_err(|e| {
+                    ApiError::from(e)
+                        .in_round("round4")
+                        .with_party(i as u16 + 1)
+                })?;
+            }
+            Some(adapted_nonce(
+                shares,
+                required(&context.delta_inv, "delta_inv")?,
+            ))
+        }
+        None => None,
+    };
+
+    let local_sig = LocalSignature::phase5_local_presig(
+        &required(&context.sign_keys, "sign_keys")?.k_i,
+        &context.message,
         &R,
-        &context.sigma.as_ref().unwrap(),
+        adapted_R.as_ref(),
+        required(&context.sigma, "sigma")?,
         &context.y_sum,
     );
 
@@ -893,280 +1929,1706 @@ pub async fn gg18_sign_client_round4(context: String, delay: u32) -> String {
     context.dlog_proof_rho = Some(dlog_proof_rho);
     context.local_sig = Some(local_sig);
     context.r = Some(R);
-
-    serde_json::to_string(&context).unwrap()
+    context.nonce_shares = nonce_shares.map(|shares| g_gamma.into_iter().zip(shares).collect());
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round5(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round5(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round5(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round5(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round5");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(5)?;
     let client = new_client_with_headers();
-    //phase (5A)  broadcast commit
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round5",
-        serde_json::to_string(&context.phase5_com.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round5_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round5",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    let outgoing = written(context.agreement, || sign_round5_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round5", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(5)?;
+    sign_round5_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
 
+//phase (5A)  broadcast commit
+fn sign_round5_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.phase5_com, "phase5_com")?).unwrap(),
+    ))
+}
+
+fn sign_round5_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
     let mut commit5a_vec: Vec<Phase5Com1> = Vec::new();
     format_vec_from_reads(
-        &round5_ans_vec,
+        answers,
         context.party_num_int as usize,
-        context.phase5_com.clone().unwrap(),
+        required(&context.phase5_com, "phase5_com")?.clone(),
         &mut commit5a_vec,
-    );
+        "round5",
+    )?;
 
     context.commit5a_vec = Some(commit5a_vec);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round6(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round6(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round6(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round6(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round6");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(6)?;
     let client = new_client_with_headers();
-    //phase (5B)  broadcast decommit and (5B) ZK proof
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round6",
+    let outgoing = written(context.agreement, || sign_round6_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round6", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(6)?;
+    sign_round6_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+//phase (5B)  broadcast decommit and (5B) ZK proof
+fn sign_round6_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
         serde_json::to_string(&(
-            context.phase_5a_decom.clone().unwrap(),
-            context.helgamal_proof.clone().unwrap(),
-            context.dlog_proof_rho.clone().unwrap()
+            required(&context.phase_5a_decom, "phase_5a_decom")?.clone(),
+            required(&context.helgamal_proof, "helgamal_proof")?.clone(),
+            required(&context.dlog_proof_rho, "dlog_proof_rho")?.clone(),
         ))
         .unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round6_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round6",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    ))
+}
 
+fn sign_round6_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
     let mut decommit5a_and_elgamal_and_dlog_vec: Vec<(Phase5ADecom1, HomoELGamalProof, DLogProof)> =
         Vec::new();
     format_vec_from_reads(
-        &round6_ans_vec,
+        answers,
         context.party_num_int as usize,
         (
-            context.phase_5a_decom.clone().unwrap(),
-            context.helgamal_proof.clone().unwrap(),
-            context.dlog_proof_rho.clone().unwrap(),
+            required(&context.phase_5a_decom, "phase_5a_decom")?.clone(),
+            required(&context.helgamal_proof, "helgamal_proof")?.clone(),
+            required(&context.dlog_proof_rho, "dlog_proof_rho")?.clone(),
         ),
         &mut decommit5a_and_elgamal_and_dlog_vec,
-    );
+        "round6",
+    )?;
     let decommit5a_and_elgamal_and_dlog_vec_includes_i =
         decommit5a_and_elgamal_and_dlog_vec.clone();
     decommit5a_and_elgamal_and_dlog_vec.remove(usize::from(context.party_num_int - 1));
     context
         .commit5a_vec
         .as_mut()
-        .unwrap()
+        .ok_or_else(|| missing("commit5a_vec"))?
         .remove(usize::from(context.party_num_int - 1));
-    let phase_5a_decomm_vec = (0..context.threshould)
+    let others = context.parties() - 1;
+    let phase_5a_decomm_vec = (0..others)
         .map(|i| decommit5a_and_elgamal_and_dlog_vec[i as usize].0.clone())
         .collect::<Vec<Phase5ADecom1>>();
-    let phase_5a_elgamal_vec = (0..context.threshould)
+    let phase_5a_elgamal_vec = (0..others)
         .map(|i| decommit5a_and_elgamal_and_dlog_vec[i as usize].1.clone())
         .collect::<Vec<HomoELGamalProof>>();
-    let phase_5a_dlog_vec = (0..context.threshould)
+    let phase_5a_dlog_vec = (0..others)
         .map(|i| decommit5a_and_elgamal_and_dlog_vec[i as usize].2.clone())
         .collect::<Vec<DLogProof>>();
-    let (phase5_com2, phase_5d_decom2) = context
-        .local_sig
+    let commit5a_vec = required(&context.commit5a_vec, "commit5a_vec")?;
+    let r = required(&context.r, "r")?;
+    let (phase5_com2, phase_5d_decom2) = required(&context.local_sig, "local_sig")?
         .clone()
-        .unwrap()
         .phase5c(
             &phase_5a_decomm_vec,
-            &context.commit5a_vec.as_ref().unwrap(),
+            commit5a_vec,
             &phase_5a_elgamal_vec,
             &phase_5a_dlog_vec,
-            &context.phase_5a_decom.as_ref().unwrap().V_i,
-            &context.r.as_ref().unwrap(),
+            &required(&context.phase_5a_decom, "phase_5a_decom")?.V_i,
+            r,
         )
-        .expect("error phase5");
+        .map_err(|e| {
+            let blame = LocalSignature::phase5c_blame(
+                &phase_5a_decomm_vec,
+                commit5a_vec,
+                &phase_5a_elgamal_vec,
+                &phase_5a_dlog_vec,
+                r,
+            );
+            ApiError::from(e)
+                .in_round("round6")
+                .with_blame(blamed_parties(&blame, Some(context.party_num_int)))
+        })?;
+    metrics().proofs_verified("round6", 2 * usize::from(others));
 
     context.phase5_com2 = Some(phase5_com2);
     context.phase_5d_decom2 = Some(phase_5d_decom2);
     context.decommit5a_and_elgamal_and_dlog_vec_includes_i =
         Some(decommit5a_and_elgamal_and_dlog_vec_includes_i);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round7(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round7(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round7(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round7(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round7");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(7)?;
     let client = new_client_with_headers();
-    //////////////////////////////////////////////////////////////////////////////
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round7",
-        serde_json::to_string(&context.phase5_com2.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round7_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round7",
-        context.uuid.clone(),
-        delay,
-    )
-    .await;
+    let outgoing = written(context.agreement, || sign_round7_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round7", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(7)?;
+    sign_round7_incoming(&mut context, &answers)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn sign_round7_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.phase5_com2, "phase5_com2")?).unwrap(),
+    ))
+}
 
+fn sign_round7_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+) -> Result<(), ApiError> {
     let mut commit5c_vec: Vec<Phase5Com2> = Vec::new();
     format_vec_from_reads(
-        &round7_ans_vec,
+        answers,
         context.party_num_int as usize,
-        context.phase5_com2.clone().unwrap(),
+        required(&context.phase5_com2, "phase5_com2")?.clone(),
         &mut commit5c_vec,
-    );
+        "round7",
+    )?;
 
     context.commit5c_vec = Some(commit5c_vec);
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn gg18_sign_client_round8(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round8(context, delay, &mut Intake::client(), None)
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
 
-    serde_json::to_string(&context).unwrap()
+/// A JS function as a signing policy. It is called with the
+/// `SigningRequest` as JSON and approves by returning `true`; any other
+/// value vetoes, with the value as the reason if it is a string, and so
+/// does a throw.
+struct JsPolicy(js_sys::Function);
+
+impl SigningPolicy for JsPolicy {
+    fn approve(&self, request: &SigningRequest) -> Result<(), PolicyRejection> {
+        let request = JsValue::from_str(&serde_json::to_string(request).unwrap());
+        match self.0.call1(&JsValue::NULL, &request) {
+            Ok(approved) if approved.as_bool() == Some(true) => Ok(()),
+            Ok(reason) | Err(reason) => Err(PolicyRejection::new(
+                &reason.as_string().unwrap_or_else(|| "vetoed".to_string()),
+            )),
+        }
+    }
 }
 
+/// `gg18_sign_client_round8`, releasing this party's signature share only
+/// if `policy` approves. `metadata` is handed to the policy as the request's
+/// bytes, e.g. the transaction the message was computed from.
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round8(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
-    let client = new_client_with_headers();
-    //phase (5B)  broadcast decommit and (5B) ZK proof
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round8",
-        serde_json::to_string(&context.phase_5d_decom2.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round8_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round8",
-        context.uuid.clone(),
+pub async fn gg18_sign_client_round8_with_policy(
+    context: String,
+    delay: u32,
+    policy: js_sys::Function,
+    metadata: String,
+) -> Result<String, JsValue> {
+    let policy = JsPolicy(policy);
+    let numbers = StartedNumbers::of(&context);
+    sign_round8(
+        context,
         delay,
+        &mut Intake::client(),
+        Some((&policy, metadata.into_bytes())),
     )
-    .await;
+    .await
+    .map_err(|e| JsValue::from(numbers.apply(e)))
+}
 
+async fn sign_round8(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+    policy: Option<(&dyn SigningPolicy, Vec<u8>)>,
+) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round8");
+    let mut context: GG18SignClientContext = parse_context(&context)?;
+    context.round.outgoing(8)?;
+    let client = new_client_with_headers();
+    let outgoing = written(context.agreement, || sign_round8_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round8", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(8)?;
+    sign_round8_incoming(&mut context, &answers, policy)?;
+
+    timer.finish();
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+//phase (5B)  broadcast decommit and (5B) ZK proof
+fn sign_round8_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.phase_5d_decom2, "phase_5d_decom2")?).unwrap(),
+    ))
+}
+
+fn sign_round8_incoming(
+    context: &mut GG18SignClientContext,
+    answers: &[String],
+    policy: Option<(&dyn SigningPolicy, Vec<u8>)>,
+) -> Result<(), ApiError> {
     let mut decommit5d_vec: Vec<Phase5DDecom2> = Vec::new();
     format_vec_from_reads(
-        &round8_ans_vec,
+        answers,
         context.party_num_int as usize,
-        context.phase_5d_decom2.clone().unwrap(),
+        required(&context.phase_5d_decom2, "phase_5d_decom2")?.clone(),
         &mut decommit5d_vec,
-    );
-
-    let phase_5a_decomm_vec_includes_i = (0..=context.threshould)
+        "round8",
+    )?;
+    let decommit5a_and_elgamal_and_dlog_vec_includes_i = required(
+        &context.decommit5a_and_elgamal_and_dlog_vec_includes_i,
+        "decommit5a_and_elgamal_and_dlog_vec_includes_i",
+    )?;
+    let phase_5a_decomm_vec_includes_i = (0..context.parties())
         .map(|i| {
-            context
-                .decommit5a_and_elgamal_and_dlog_vec_includes_i
-                .clone()
-                .unwrap()[i as usize]
+            decommit5a_and_elgamal_and_dlog_vec_includes_i[i as usize]
                 .0
                 .clone()
         })
         .collect::<Vec<Phase5ADecom1>>();
-    let s_i = context
-        .local_sig
-        .clone()
-        .unwrap()
-        .phase5d(
-            &decommit5d_vec,
-            &context.commit5c_vec.as_ref().unwrap(),
-            &phase_5a_decomm_vec_includes_i,
-        )
-        .expect("bad com 5d");
+    let local_sig = required(&context.local_sig, "local_sig")?.clone();
+    let commit5c_vec = required(&context.commit5c_vec, "commit5c_vec")?;
+    let phase5d = match policy {
+        Some((policy, metadata)) => {
+            let request = SigningRequest {
+                digest: context.message.clone(),
+                public_key: context.y_sum.clone(),
+                signers: required(&context.signers_vec, "signers_vec")?.clone(),
+                metadata,
+            };
+            local_sig.phase5d_with_policy(
+                &decommit5d_vec,
+                commit5c_vec,
+                &phase_5a_decomm_vec_includes_i,
+                policy,
+                &request,
+            )
+        }
+        None => local_sig
+            .phase5d(
+                &decommit5d_vec,
+                commit5c_vec,
+                &phase_5a_decomm_vec_includes_i,
+            )
+            .map_err(PolicyError::Protocol),
+    };
+    let s_i = phase5d.map_err(|e| match e {
+        PolicyError::Protocol(e) => {
+            let blame = local_sig.phase5d_blame(
+                &decommit5d_vec,
+                commit5c_vec,
+                &phase_5a_decomm_vec_includes_i,
+            );
+            ApiError::from(e)
+                .in_round("round8")
+                .with_blame(blamed_parties(&blame, None))
+        }
+        e => ApiError::from(e).in_round("round8"),
+    })?;
 
     context.s_i = Some(s_i);
-
-    serde_json::to_string(&context).unwrap()
+    Ok(())
 }
 
 #[wasm_bindgen]
-pub async fn gg18_sign_client_round9(context: String, delay: u32) -> String {
-    let mut context = serde_json::from_str::<GG18SignClientContext>(&context).unwrap();
+pub async fn gg18_sign_client_round9(context: String, delay: u32) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round9(context, delay, &mut Intake::client())
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+async fn sign_round9(context: String, delay: u32, intake: &mut Intake) -> Result<String, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round9");
+    let (context, answers) = sign_round9_exchange(&context, delay, intake).await?;
+    let output = sign_round9_output(context, &answers)?;
+
+    timer.finish();
+    Ok(output)
+}
+
+async fn sign_round9_signature(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<ThresholdSignature, ApiError> {
+    let timer = RoundTimer::start(Ceremony::Sign, "round9");
+    let (context, answers) = sign_round9_exchange(&context, delay, intake).await?;
+    let sig = sign_round9_incoming(context, &answers)?;
+
+    timer.finish();
+    Ok(sig)
+}
+
+async fn sign_round9_exchange(
+    context: &str,
+    delay: u32,
+    intake: &mut Intake,
+) -> Result<(GG18SignClientContext, Vec<String>), ApiError> {
+    let mut context: GG18SignClientContext = parse_context(context)?;
+    context.round.outgoing(9)?;
     let client = new_client_with_headers();
-    //////////////////////////////////////////////////////////////////////////////
-    assert!(broadcast(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        "round9",
-        serde_json::to_string(&context.s_i.as_ref().unwrap()).unwrap(),
-        context.uuid.clone(),
-    )
-    .await
-    .is_ok());
-    let round9_ans_vec = poll_for_broadcasts(
-        &client,
-        &context.addr,
-        context.party_num_int,
-        context.threshould + 1,
-        "round9",
-        context.uuid.clone(),
-        delay,
+    let outgoing = written(context.agreement, || sign_round9_outgoing(&context))?;
+    let answers = context
+        .relay()
+        .exchange(&client, "round9", outgoing, delay, intake)
+        .await?;
+    context.round.incoming(9)?;
+    Ok((context, answers))
+}
+
+fn sign_round9_outgoing(context: &GG18SignClientContext) -> Result<Outgoing, ApiError> {
+    Ok(Outgoing::Broadcast(
+        serde_json::to_string(required(&context.s_i, "s_i")?).unwrap(),
+    ))
+}
+
+// the signature as `SignatureOutput`, or the adaptor signature, as json
+fn sign_round9_output(
+    context: GG18SignClientContext,
+    answers: &[String],
+) -> Result<String, ApiError> {
+    if context.adaptor_point.is_some() {
+        let adaptor_sig = sign_round9_adaptor_signature(context, answers)?;
+        return Ok(serde_json::to_string(&adaptor_sig).unwrap());
+    }
+    let sig = sign_round9_incoming(context, answers)?;
+    Ok(serde_json::to_string(&SignatureOutput::from(&sig)).unwrap())
+}
+
+fn sign_round9_incoming(
+    context: GG18SignClientContext,
+    answers: &[String],
+) -> Result<ThresholdSignature, ApiError> {
+    if context.adaptor_point.is_some() {
+        return Err(ApiError::new(
+            ErrorCode::InvalidContext,
+            "an adaptor signature is completed with gg18_adaptor_complete",
+        )
+        .in_round("round9"));
+    }
+    let s_i_vec = round9_shares(&context, answers)?;
+    let sig = required(&context.local_sig, "local_sig")?
+        .output_signature(&s_i_vec)
+        .map_err(|e| ApiError::from(e).in_round("round9"))?;
+
+    ThresholdSignature::new(
+        &sig,
+        &context.y_sum,
+        &context.message,
+        &signer_ids(&context)?,
+        None,
     )
-    .await;
+    .map_err(|e| ApiError::from(e).in_round("round9"))
+}
+
+fn sign_round9_adaptor_signature(
+    context: GG18SignClientContext,
+    answers: &[String],
+) -> Result<AdaptorSignature, ApiError> {
+    let s_i_vec = round9_shares(&context, answers)?;
+    let s = s_i_vec
+        .iter()
+        .fold(required(&context.s_i, "s_i")?.clone(), |acc, s_i| acc + s_i);
+    let signers = signer_ids(&context)?;
+    let (g_gamma, nonce_shares) = context
+        .nonce_shares
+        .ok_or_else(|| missing("nonce_shares"))?
+        .into_iter()
+        .unzip();
+    let adaptor_sig = AdaptorSignature {
+        s,
+        public_key: context.y_sum,
+        digest: context.message,
+        adaptor_point: context
+            .adaptor_point
+            .ok_or_else(|| missing("adaptor_point"))?,
+        delta_inv: context.delta_inv.ok_or_else(|| missing("delta_inv"))?,
+        g_gamma,
+        nonce_shares,
+        signers,
+    };
+    adaptor_sig
+        .verify()
+        .map_err(|e| ApiError::from(e).in_round("round9"))?;
+    Ok(adaptor_sig)
+}
 
+// the other signers' s_i
+fn round9_shares(
+    context: &GG18SignClientContext,
+    answers: &[String],
+) -> Result<Vec<Scalar>, ApiError> {
     let mut s_i_vec: Vec<Scalar> = Vec::new();
     format_vec_from_reads(
-        &round9_ans_vec,
+        answers,
         context.party_num_int as usize,
-        context.s_i.unwrap(),
+        required(&context.s_i, "s_i")?.clone(),
         &mut s_i_vec,
-    );
-
+        "round9",
+    )?;
     s_i_vec.remove(usize::from(context.party_num_int - 1));
-    let sig = context
-        .local_sig
-        .unwrap()
-        .output_signature(&s_i_vec)
-        .expect("verification failed");
+    Ok(s_i_vec)
+}
 
-    let sign_json = serde_json::to_string(&(
-        "r",
-        BigInt::from_bytes_be(sig.r.to_big_int().to_bytes_be().as_ref()).to_str_radix(16),
-        "s",
-        BigInt::from_bytes_be(sig.s.to_big_int().to_bytes_be().as_ref()).to_str_radix(16),
-    ))
-    .unwrap();
+fn signer_ids(context: &GG18SignClientContext) -> Result<Vec<u16>, ApiError> {
+    Ok(required(&context.signers_vec, "signers_vec")?
+        .iter()
+        .map(|i| *i as u16 + 1)
+        .collect())
+}
+
+/// Checks an adaptor signature from round 9, without the adaptor secret.
+#[wasm_bindgen]
+pub fn gg18_adaptor_verify(adaptor_signature: String) -> Result<(), JsValue> {
+    let adaptor_sig: AdaptorSignature = parse_context(&adaptor_signature)?;
+    adaptor_sig.verify().map_err(ApiError::from)?;
+    Ok(())
+}
+
+/// Completes an adaptor signature with `secret`, the discrete log of its
+/// adaptor point as 32 bytes of hex, into a signature returned as
+/// `gg18_sign_client_round9` returns one.
+#[wasm_bindgen]
+pub fn gg18_adaptor_complete(adaptor_signature: String, secret: String) -> Result<String, JsValue> {
+    let adaptor_sig: AdaptorSignature = parse_context(&adaptor_signature)?;
+    let secret = parse_scalar(&secret)?;
+    let sig = adaptor_sig.complete(&secret).map_err(ApiError::from)?;
+    Ok(serde_json::to_string(&SignatureOutput::from(&sig)).unwrap())
+}
+
+/// Recovers the adaptor secret, in hex, from an adaptor signature and the
+/// signature it was completed to, r || s in hex.
+#[wasm_bindgen]
+pub fn gg18_adaptor_extract(
+    adaptor_signature: String,
+    signature: String,
+) -> Result<String, JsValue> {
+    let adaptor_sig: AdaptorSignature = parse_context(&adaptor_signature)?;
+    let invalid = || ApiError::new(ErrorCode::InvalidSignature, "not a compact signature");
+    let bytes = hex::decode(signature).map_err(|_| invalid())?;
+    if bytes.len() != 64 {
+        return Err(invalid().into());
+    }
+    let sig = Signature {
+        r: Scalar::from_bytes(&bytes[..32]).map_err(|_| invalid())?,
+        s: Scalar::from_bytes(&bytes[32..]).map_err(|_| invalid())?,
+    };
+    let secret = adaptor_sig.extract(&sig).map_err(ApiError::from)?;
+    Ok(hex::encode(secret.to_bytes_be()))
+}
+
+fn parse_scalar(scalar: &str) -> Result<Scalar, ApiError> {
+    hex::decode(scalar)
+        .ok()
+        .and_then(|bytes| Scalar::from_bytes(&bytes).ok())
+        .ok_or_else(|| ApiError::new(ErrorCode::InvalidParameters, "not a 32 byte scalar"))
+}
 
-    sign_json
+/// The digest to sign for `transaction`, an `ethereum::Transaction` as
+/// json, in hex. Passed as the message of `gg18_sign_client_new_context` it
+/// is signed as is.
+#[cfg(feature = "ethereum")]
+#[wasm_bindgen]
+pub fn gg18_ethereum_signing_hash(transaction: String) -> Result<String, JsValue> {
+    let transaction: Transaction = parse_context(&transaction)?;
+    Ok(hex::encode(transaction.signing_digest().as_bytes()))
+}
+
+/// Runs round 9 like `gg18_sign_client_round9` for a ceremony signing
+/// `transaction`, and returns the signed raw transaction in `0x` hex, as
+/// `eth_sendRawTransaction` takes it.
+#[cfg(feature = "ethereum")]
+#[wasm_bindgen]
+pub async fn gg18_sign_client_round9_ethereum(
+    context: String,
+    delay: u32,
+    transaction: String,
+) -> Result<String, JsValue> {
+    let numbers = StartedNumbers::of(&context);
+    sign_round9_ethereum(context, delay, &mut Intake::client(), &transaction)
+        .await
+        .map_err(|e| JsValue::from(numbers.apply(e)))
+}
+
+#[cfg(feature = "ethereum")]
+async fn sign_round9_ethereum(
+    context: String,
+    delay: u32,
+    intake: &mut Intake,
+    transaction: &str,
+) -> Result<String, ApiError> {
+    let transaction: Transaction = parse_context(transaction)?;
+    let sig = sign_round9_signature(context, delay, intake).await?;
+    let raw = transaction
+        .encode_signed(&sig)
+        .map_err(|e| ApiError::from(e).in_round("round9"))?;
+    Ok(format!("0x{}", hex::encode(raw)))
+}
+
+/// What `gg18_sign_client_round9` returns: the signature in hex, in each
+/// encoding, with the signers by party id.
+#[derive(Serialize)]
+struct SignatureOutput {
+    r: String,
+    s: String,
+    recid: u8,
+    signers: Vec<u16>,
+    der: String,
+    compact: String,
+    /// r || s || v, absent in the rare case Ethereum cannot express.
+    ethereum: Option<String>,
+}
+
+impl From<&ThresholdSignature> for SignatureOutput {
+    fn from(sig: &ThresholdSignature) -> SignatureOutput {
+        SignatureOutput {
+            r: hex::encode(sig.r.to_bytes_be()),
+            s: hex::encode(sig.s.to_bytes_be()),
+            recid: sig.recid,
+            signers: sig.signers.clone(),
+            der: hex::encode(sig.to_der()),
+            compact: hex::encode(sig.to_compact()),
+            ethereum: sig.to_ethereum().ok().map(hex::encode),
+        }
+    }
+}
+
+/// The first half of keygen round 0 for a ceremony whose messages the
+/// caller carries, e.g. a mobile app with its own transport. Returns json
+/// `{ context, messages }`: the context to persist and pass on, and this
+/// party's messages as relay entries, keyed by sender, recipient of a p2p
+/// message, round and ceremony. Deliver those, collect the other parties'
+/// messages of the round and pass them to `gg18_keygen_round0_incoming`.
+/// Every keygen and signing round has such a pair of exports; neither
+/// keeps state in wasm between calls or touches the network. The context
+/// records the round it is at, and a half called out of turn fails with
+/// `WrongRound`.
+#[wasm_bindgen]
+pub fn gg18_keygen_round0_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 0).map_err(JsValue::from)
+}
+
+/// The second half of keygen round 0: reads the other parties' messages of
+/// the round from `messages`, a json array of relay entries, and returns
+/// the next context. Entries of other rounds are ignored; if some party's
+/// message is not among them it fails with `Timeout`, naming the `missing`
+/// parties.
+#[wasm_bindgen]
+pub fn gg18_keygen_round0_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 0, &messages).map_err(JsValue::from)
+}
+
+/// Keygen round 1, see `gg18_keygen_round0_outgoing`. Generates the Paillier
+/// keys first unless `gg18_keygen_client_paillier_step` already has.
+#[wasm_bindgen]
+pub fn gg18_keygen_round1_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 1).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round1_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 1, &messages).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round2_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 2).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round2_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 2, &messages).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round3_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 3).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round3_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 3, &messages).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round4_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 4).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round4_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 4, &messages).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_keygen_round5_outgoing(context: String) -> Result<String, JsValue> {
+    keygen_outgoing(&context, 5).map_err(JsValue::from)
+}
+
+/// The last keygen round; returns the key store instead of a context.
+#[wasm_bindgen]
+pub fn gg18_keygen_round5_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    keygen_incoming(&context, 5, &messages).map_err(JsValue::from)
+}
+
+/// Signing round 0, see `gg18_keygen_round0_outgoing`.
+#[wasm_bindgen]
+pub fn gg18_sign_round0_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 0).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round0_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 0, &messages, None).map_err(JsValue::from)
+}
+
+/// Signing round 1. The returned context holds the commitments sent, as
+/// after `gg18_sign_client_round1_prepare`.
+#[wasm_bindgen]
+pub fn gg18_sign_round1_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 1).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round1_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 1, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round2_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 2).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round2_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 2, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round3_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 3).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round3_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 3, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round4_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 4).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round4_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 4, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round5_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 5).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round5_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 5, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round6_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 6).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round6_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 6, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round7_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 7).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round7_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 7, &messages, None).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round8_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 8).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round8_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 8, &messages, None).map_err(JsValue::from)
+}
+
+/// `gg18_sign_round8_incoming`, computing this party's signature share only
+/// if `policy` approves, see `gg18_sign_client_round8_with_policy`.
+#[wasm_bindgen]
+pub fn gg18_sign_round8_incoming_with_policy(
+    context: String,
+    messages: String,
+    policy: js_sys::Function,
+    metadata: String,
+) -> Result<String, JsValue> {
+    let policy = JsPolicy(policy);
+    let policy = Some((&policy as &dyn SigningPolicy, metadata.into_bytes()));
+    sign_incoming(&context, 8, &messages, policy).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+pub fn gg18_sign_round9_outgoing(context: String) -> Result<String, JsValue> {
+    sign_outgoing(&context, 9).map_err(JsValue::from)
+}
+
+/// The last signing round; returns the signature as
+/// `gg18_sign_client_round9` does.
+#[wasm_bindgen]
+pub fn gg18_sign_round9_incoming(context: String, messages: String) -> Result<String, JsValue> {
+    sign_incoming(&context, 9, &messages, None).map_err(JsValue::from)
+}
+
+/// What the outgoing half of a round returns.
+#[derive(Serialize)]
+struct RoundOutput {
+    context: String,
+    messages: Vec<Entry>,
+}
+
+fn keygen_outgoing(context: &str, round: u8) -> Result<String, ApiError> {
+    check_round(Ceremony::Keygen, round)?;
+    let mut context: GG18KeygenClientContext = parse_context(context)?;
+    context.round.outgoing(round)?;
+    let outgoing = written(context.agreement, || {
+        Ok::<_, ApiError>(match round {
+            0 => keygen_round0_outgoing(),
+            1 => keygen_round1_outgoing(&mut context)?,
+            2 => keygen_round2_outgoing(&mut context)?,
+            3 => keygen_round3_outgoing(&context)?,
+            4 => keygen_round4_outgoing(&context)?,
+            5 => keygen_round5_outgoing(&context)?,
+            _ => unreachable!(),
+        })
+    })?;
+    let messages = context.relay().entries(round, outgoing);
+    Ok(serde_json::to_string(&RoundOutput {
+        context: serde_json::to_string(&context).unwrap(),
+        messages,
+    })
+    .unwrap())
+}
+
+fn keygen_incoming(context: &str, round: u8, messages: &str) -> Result<String, ApiError> {
+    check_round(Ceremony::Keygen, round)?;
+    let mut context: GG18KeygenClientContext = parse_context(context)?;
+    context.round.incoming(round)?;
+    let answers = context.relay().collect(Ceremony::Keygen, round, messages)?;
+    match round {
+        0 => keygen_round0_incoming(&mut context, &answers)?,
+        1 => keygen_round1_incoming(&mut context, &answers)?,
+        2 => keygen_round2_incoming(&mut context, &answers)?,
+        3 => keygen_round3_incoming(&mut context, &answers)?,
+        4 => keygen_round4_incoming(&mut context, &answers)?,
+        5 => return keygen_round5_incoming(&context, &answers),
+        _ => unreachable!(),
+    }
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn sign_outgoing(context: &str, round: u8) -> Result<String, ApiError> {
+    sign_round_outgoing(context, round).map_err(|e| StartedNumbers::of(context).apply(e))
+}
+
+fn sign_round_outgoing(context: &str, round: u8) -> Result<String, ApiError> {
+    check_round(Ceremony::Sign, round)?;
+    let mut context: GG18SignClientContext = parse_context(context)?;
+    context.round.outgoing(round)?;
+    let outgoing = written(context.agreement, || {
+        Ok::<_, ApiError>(match round {
+            0 => sign_round0_outgoing(&context)?,
+            1 => sign_round1_outgoing(&mut context)?,
+            2 => sign_round2_outgoing(&mut context)?,
+            3 => sign_round3_outgoing(&context)?,
+            4 => sign_round4_outgoing(&context)?,
+            5 => sign_round5_outgoing(&context)?,
+            6 => sign_round6_outgoing(&context)?,
+            7 => sign_round7_outgoing(&context)?,
+            8 => sign_round8_outgoing(&context)?,
+            9 => sign_round9_outgoing(&context)?,
+            _ => unreachable!(),
+        })
+    })?;
+    let messages = context.relay().entries(round, outgoing);
+    Ok(serde_json::to_string(&RoundOutput {
+        context: serde_json::to_string(&context).unwrap(),
+        messages,
+    })
+    .unwrap())
+}
+
+fn sign_incoming(
+    context: &str,
+    round: u8,
+    messages: &str,
+    policy: Option<(&dyn SigningPolicy, Vec<u8>)>,
+) -> Result<String, ApiError> {
+    sign_round_incoming(context, round, messages, policy)
+        .map_err(|e| StartedNumbers::of(context).apply(e))
+}
+
+fn sign_round_incoming(
+    context: &str,
+    round: u8,
+    messages: &str,
+    policy: Option<(&dyn SigningPolicy, Vec<u8>)>,
+) -> Result<String, ApiError> {
+    check_round(Ceremony::Sign, round)?;
+    let mut context: GG18SignClientContext = parse_context(context)?;
+    context.round.incoming(round)?;
+    if round < 2 {
+        // a party missing from `messages` has dropped out
+        let answers = context
+            .relay()
+            .collect_partial(Ceremony::Sign, round, messages)?;
+        match round {
+            0 => sign_round0_incoming(&mut context, &answers)?,
+            _ => sign_round1_incoming(&mut context, answers)?,
+        }
+        return Ok(serde_json::to_string(&context).unwrap());
+    }
+    let answers = context.relay().collect(Ceremony::Sign, round, messages)?;
+    match round {
+        2 => sign_round2_incoming(&mut context, &answers)?,
+        3 => sign_round3_incoming(&mut context, &answers)?,
+        4 => sign_round4_incoming(&mut context, &answers)?,
+        5 => sign_round5_incoming(&mut context, &answers)?,
+        6 => sign_round6_incoming(&mut context, &answers)?,
+        7 => sign_round7_incoming(&mut context, &answers)?,
+        8 => sign_round8_incoming(&mut context, &answers, policy)?,
+        9 => return sign_round9_output(context, &answers),
+        _ => unreachable!(),
+    }
+    Ok(serde_json::to_string(&context).unwrap())
+}
+
+fn check_round(ceremony: Ceremony, round: u8) -> Result<(), ApiError> {
+    if round > ceremony.last_round() {
+        return Err(ApiError::new(ErrorCode::WrongRound, "unknown round"));
+    }
+    Ok(())
+}
+
+const QUORUM_CACHE_SIZE: usize = 16;
+
+// the modulus Keys::create generates
+const PAILLIER_MODULUS_BITS: usize = 2048;
+
+// prime candidates tested between two turns of the event loop, a few
+// milliseconds of work at 1024 bits
+const PRIME_CANDIDATES_PER_TURN: usize = 16;
+
+thread_local! {
+    // the ceremonies this instance is running, between rounds
+    static SESSIONS: RefCell<SessionRegistry<Checkpoint>> = RefCell::new(SessionRegistry::new());
+    // signing material of the quorums signed with recently
+    static QUORUMS: RefCell<QuorumCache> = RefCell::new(QuorumCache::new(QUORUM_CACHE_SIZE));
+    // the peers' Paillier keys whose correct key proofs verified
+    static PROOFS: RefCell<ProofCache> = RefCell::new(ProofCache::new());
+}
+
+fn quorum_keys(
+    context: &GG18SignClientContext,
+    signers_vec: &[usize],
+) -> Result<QuorumKeys, crate::Error> {
+    QUORUMS.with(|q| {
+        q.borrow_mut()
+            .get(
+                &context.vss_scheme_vec,
+                &context.shared_keys.x_i,
+                &context.y_sum,
+                usize::from(context.party_id - 1),
+                signers_vec,
+            )
+            .cloned()
+    })
+}
+
+impl From<ApiError> for JsValue {
+    fn from(e: ApiError) -> JsValue {
+        let error = js_sys::Error::new(&e.message);
+        error.set_name("TssError");
+        let set = |key: &str, value: JsValue| {
+            js_sys::Reflect::set(&error, &JsValue::from_str(key), &value).unwrap();
+        };
+        set("code", JsValue::from_str(e.code.as_str()));
+        if let Some(round) = &e.round {
+            set("round", JsValue::from_str(round));
+        }
+        if let Some(party) = e.party {
+            set("party", JsValue::from(party));
+        }
+        if !e.missing.is_empty() {
+            let missing = e
+                .missing
+                .iter()
+                .map(|p| JsValue::from(*p))
+                .collect::<js_sys::Array>();
+            set("missing", missing.into());
+        }
+        if !e.blamed.is_empty() {
+            let blamed = e
+                .blamed
+                .iter()
+                .map(|p| JsValue::from(*p))
+                .collect::<js_sys::Array>();
+            set("blamed", blamed.into());
+        }
+        error.into()
+    }
+}
+
+// positions in a vector of the signers' messages to party numbers; `skip` is
+// the party whose own message was left out of the vector
+fn blamed_parties(blame: &[Blame], skip: Option<u16>) -> Vec<u16> {
+    blame
+        .iter()
+        .map(|b| {
+            let party = b.index as u16 + 1;
+            match skip {
+                Some(me) if party >= me => party + 1,
+                _ => party,
+            }
+        })
+        .collect()
+}
+
+// The numbers the signers started with, by which errors name parties: the
+// signers left after a dropout number themselves anew from round 2, see
+// `sign_round1_incoming`.
+#[derive(Deserialize)]
+struct StartedNumbers {
+    #[serde(default)]
+    relay_numbers: Option<Vec<u16>>,
+}
+
+impl StartedNumbers {
+    fn of(context: &str) -> StartedNumbers {
+        serde_json::from_str(context).unwrap_or(StartedNumbers {
+            relay_numbers: None,
+        })
+    }
+
+    fn apply(&self, e: ApiError) -> ApiError {
+        let numbers = match &self.relay_numbers {
+            Some(numbers) => numbers,
+            None => return e,
+        };
+        let number = |party: u16| {
+            usize::from(party)
+                .checked_sub(1)
+                .and_then(|i| numbers.get(i))
+                .copied()
+                .unwrap_or(party)
+        };
+        let party = e.party.map(number);
+        let missing = e.missing.iter().map(|p| number(*p)).collect();
+        let blamed = e.blamed.iter().map(|p| number(*p)).collect();
+        ApiError {
+            party,
+            missing,
+            blamed,
+            ..e
+        }
+    }
+}
+
+fn session_error(e: SessionError) -> JsValue {
+    ApiError::from(e).into()
+}
+
+/// What a party sends in a round: one message for all the other parties,
+/// or one for each of them.
+enum Outgoing {
+    Broadcast(String),
+    P2p(Vec<(u16, String)>),
+}
+
+// What a round takes from the relay: its deadline, and its peers' message
+// counts against the instance's `SessionLimits`.
+struct Intake {
+    timeout_ms: Option<u32>,
+    limiter: PeerLimiter,
+}
+
+impl Intake {
+    // a round of the client API, which keeps no counts between rounds
+    fn client() -> Intake {
+        Intake {
+            timeout_ms: None,
+            limiter: SESSIONS.with(|s| s.borrow().new_limiter()),
+        }
+    }
+
+    // a round of session `session_id`, counting on from its earlier rounds
+    fn session(session_id: &str, checkpoint: &Checkpoint) -> Result<Intake, JsValue> {
+        let limiter = SESSIONS
+            .with(|s| s.borrow().limiter(session_id))
+            .map_err(session_error)?;
+        Ok(Intake {
+            timeout_ms: checkpoint.round_timeout_ms,
+            limiter,
+        })
+    }
+
+    // hands the counts back to the session for its next round, whether or
+    // not this one succeeded
+    fn finish(self, session_id: &str) {
+        SESSIONS.with(|s| s.borrow_mut().update_limiter(session_id, self.limiter));
+    }
+}
+
+// the parties of a ceremony and the relay carrying their messages, if any
+struct Relay<'a> {
+    addr: &'a str,
+    party: u16,
+    parties: u16,
+    uuid: &'a str,
+}
+
+impl GG18KeygenClientContext {
+    fn relay(&self) -> Relay<'_> {
+        Relay {
+            addr: &self.addr,
+            party: self.party_num_int,
+            parties: self.params.share_count() as u16,
+            uuid: &self.uuid,
+        }
+    }
+}
+
+impl GG18SignClientContext {
+    fn relay(&self) -> Relay<'_> {
+        Relay {
+            addr: &self.addr,
+            party: self.party_num_int,
+            parties: self.parties(),
+            uuid: &self.uuid,
+        }
+    }
+
+    // the parties of the current round: those that started up to round 1,
+    // the signers left from round 2
+    fn parties(&self) -> u16 {
+        match &self.relay_numbers {
+            Some(numbers) => numbers.len() as u16,
+            None => self.online.unwrap_or(self.threshould + 1),
+        }
+    }
+}
+
+impl Relay<'_> {
+    // Sends this party's messages of `round` through the relay and waits
+    // for the others', returned in party order.
+    async fn exchange(
+        &self,
+        client: &Client,
+        round: &str,
+        outgoing: Outgoing,
+        delay: u32,
+        intake: &mut Intake,
+    ) -> Result<Vec<String>, ApiError> {
+        let uuid = self.uuid.to_string();
+        match outgoing {
+            Outgoing::Broadcast(data) => {
+                broadcast(client, self.addr, self.party, round, data, uuid.clone())
+                    .await
+                    .map_err(|_| network_error(round))?;
+                Ok(poll_for_broadcasts_until(
+                    client,
+                    self.addr,
+                    self.party,
+                    self.parties,
+                    round,
+                    uuid,
+                    delay,
+                    intake.timeout_ms,
+                    &mut intake.limiter,
+                )
+                .await?)
+            }
+            Outgoing::P2p(messages) => {
+                for (to, data) in messages {
+                    sendp2p(client, self.addr, self.party, to, round, data, uuid.clone())
+                        .await
+                        .map_err(|_| network_error(round))?;
+                }
+                Ok(poll_for_p2p_until(
+                    client,
+                    self.addr,
+                    self.party,
+                    self.parties,
+                    delay,
+                    round,
+                    uuid,
+                    intake.timeout_ms,
+                    &mut intake.limiter,
+                )
+                .await?)
+            }
+        }
+    }
+
+    // `exchange` for a broadcast round a party may drop out of: those that
+    // have not broadcast by the deadline are `None`.
+    async fn exchange_partial(
+        &self,
+        client: &Client,
+        round: &str,
+        outgoing: Outgoing,
+        delay: u32,
+        intake: &mut Intake,
+    ) -> Result<Vec<Option<String>>, ApiError> {
+        let data = match outgoing {
+            Outgoing::Broadcast(data) => data,
+            Outgoing::P2p(_) => unreachable!("only broadcast rounds tolerate a dropout"),
+        };
+        let uuid = self.uuid.to_string();
+        broadcast(client, self.addr, self.party, round, data, uuid.clone())
+            .await
+            .map_err(|_| network_error(round))?;
+        Ok(poll_for_broadcasts_partial(
+            client,
+            self.addr,
+            self.party,
+            self.parties,
+            round,
+            uuid,
+            delay,
+            intake.timeout_ms,
+            &mut intake.limiter,
+        )
+        .await?)
+    }
+
+    // The relay entries of this party's messages, for a caller that carries
+    // them itself.
+    fn entries(&self, round: u8, outgoing: Outgoing) -> Vec<Entry> {
+        let round = format!("round{}", round);
+        match outgoing {
+            Outgoing::Broadcast(value) => vec![Entry {
+                key: broadcast_key(self.party, &round, self.uuid),
+                value,
+            }],
+            Outgoing::P2p(messages) => messages
+                .into_iter()
+                .map(|(to, value)| Entry {
+                    key: p2p_key(self.party, to, &round, self.uuid),
+                    value,
+                })
+                .collect(),
+        }
+    }
+
+    // The other parties' messages of `round` among `messages`, a json array
+    // of relay entries, in party order.
+    fn collect(
+        &self,
+        ceremony: Ceremony,
+        round: u8,
+        messages: &str,
+    ) -> Result<Vec<String>, ApiError> {
+        let answers = self.collect_partial(ceremony, round, messages)?;
+        let missing = (1..=self.parties)
+            .filter(|i| *i != self.party)
+            .zip(answers.iter())
+            .filter(|(_, answer)| answer.is_none())
+            .map(|(i, _)| i)
+            .collect::<Vec<u16>>();
+        if !missing.is_empty() {
+            return Err(RoundTimeout {
+                round: format!("round{}", round),
+                missing,
+            }
+            .into());
+        }
+        Ok(answers.into_iter().map(Option::unwrap).collect())
+    }
+
+    // `collect`, with `None` for each party whose message is not among
+    // `messages`.
+    fn collect_partial(
+        &self,
+        ceremony: Ceremony,
+        round: u8,
+        messages: &str,
+    ) -> Result<Vec<Option<String>>, ApiError> {
+        let entries: Vec<Entry> = serde_json::from_str(messages)
+            .map_err(|e| ApiError::new(ErrorCode::InvalidMessage, &e.to_string()))?;
+        let round_name = format!("round{}", round);
+        Ok((1..=self.parties)
+            .filter(|i| *i != self.party)
+            .map(|i| {
+                let key = if ceremony.is_p2p_round(round) {
+                    p2p_key(i, self.party, &round_name, self.uuid)
+                } else {
+                    broadcast_key(i, &round_name, self.uuid)
+                };
+                entries
+                    .iter()
+                    .find(|entry| entry.key == key)
+                    .map(|entry| entry.value.clone())
+            })
+            .collect())
+    }
+}
+
+fn check_party_number(party: u16, parties: usize) -> Result<(), ApiError> {
+    if !(1..=parties).contains(&usize::from(party)) {
+        return Err(ApiError::new(
+            ErrorCode::InvalidParameters,
+            "party number out of range",
+        ));
+    }
+    Ok(())
+}
+
+fn network_error(round: &str) -> ApiError {
+    ApiError::new(ErrorCode::Network, "could not reach the relay").in_round(round)
+}
+
+// a context field an earlier round sets; missing only from a context that
+// did not come out of that round
+// Runs `f`, which serializes this party's messages, with BigInt fields in the
+// wire format agreed on in round 0.
+fn written<T>(agreement: Option<Agreement>, f: impl FnOnce() -> T) -> T {
+    write_as(agreement.map_or(WireFormat::Hex, |a| a.format), f)
+}
+
+// the range proof parameters agreed on in round 0
+fn proof_params(agreement: Option<Agreement>) -> AliceProofParams {
+    agreement.map_or_else(AliceProofParams::default, |a| a.proofs.params())
+}
+
+fn missing(name: &str) -> ApiError {
+    ApiError::new(
+        ErrorCode::InvalidContext,
+        &format!("context has no {}", name),
+    )
+}
+
+fn required<'a, T>(value: &'a Option<T>, name: &str) -> Result<&'a T, ApiError> {
+    value.as_ref().ok_or_else(|| missing(name))
+}
+
+fn parse_context<T: DeserializeOwned>(context: &str) -> Result<T, ApiError> {
+    serde_json::from_str(context)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidContext, &e.to_string()))
+}
+
+// a key store that parses but fails its health check is refused before any
+// message is sent
+fn read_key_store(key_store: &str) -> Result<LocalKey, ApiError> {
+    let key = LocalKey::from_key_store(key_store)
+        .map_err(|_| ApiError::new(ErrorCode::InvalidKeyStore, "unreadable key store"))?;
+    key.validate()?;
+    Ok(key)
+}
+
+// a message that does not parse is blamed on its sender
+fn parse_message<'a, T: Deserialize<'a>>(
+    message: &'a str,
+    round: &str,
+    party: u16,
+) -> Result<T, ApiError> {
+    serde_json::from_str(message).map_err(|e| {
+        ApiError::new(ErrorCode::InvalidMessage, &e.to_string())
+            .in_round(round)
+            .with_party(party)
+    })
+}
+
+// versions before the handshake send just their party id in round 0
+fn parse_hello<'a, T: Deserialize<'a>>(message: &'a str, party: u16) -> Result<T, ApiError> {
+    parse_message(message, "round0", party).map_err(|e| {
+        if serde_json::from_str::<u16>(message).is_ok() {
+            ApiError::from(HandshakeError::Outdated { party })
+        } else {
+            e
+        }
+    })
+}
+
+fn session_checkpoint(session_id: &str) -> Result<Checkpoint, JsValue> {
+    SESSIONS
+        .with(|s| s.borrow().get(session_id).cloned())
+        .map_err(session_error)
+}
+
+fn session_update(session_id: &str, checkpoint: Checkpoint) -> Result<(), JsValue> {
+    SESSIONS
+        .with(|s| s.borrow_mut().update(session_id, checkpoint).map(|_| ()))
+        .map_err(session_error)
+}
+
+fn session_close(session_id: &str) -> Result<(), JsValue> {
+    SESSIONS
+        .with(|s| s.borrow_mut().close(session_id).map(|_| ()))
+        .map_err(session_error)
+}
+
+fn session_open(session_id: &str, checkpoint: Checkpoint) -> Result<(), JsValue> {
+    SESSIONS
+        .with(|s| s.borrow_mut().open(session_id, checkpoint))
+        .map_err(session_error)
+}
+
+// Copies the checkpoint out so that other sessions can progress while this
+// round waits on the network, and refuses rounds out of order.
+fn session_begin_round(
+    session_id: &str,
+    ceremony: Ceremony,
+    round: u8,
+) -> Result<Checkpoint, JsValue> {
+    let checkpoint = session_checkpoint(session_id)?;
+    if checkpoint.ceremony != ceremony {
+        return Err(ApiError::new(
+            ErrorCode::WrongCeremony,
+            "session runs a different ceremony",
+        )
+        .into());
+    }
+    if checkpoint.next_round != round {
+        return Err(ApiError::new(
+            ErrorCode::WrongRound,
+            &format!("session expects round {}", checkpoint.next_round),
+        )
+        .into());
+    }
+    Ok(checkpoint)
+}
+
+/// Ids of the ceremonies currently held by this instance, as a json array.
+#[wasm_bindgen]
+pub fn gg18_session_ids() -> String {
+    SESSIONS.with(|s| serde_json::to_string(&s.borrow().ids()).unwrap())
+}
+
+/// Drops a ceremony, e.g. after it was aborted.
+#[wasm_bindgen]
+pub fn gg18_session_abort(session_id: String) -> Result<(), JsValue> {
+    session_close(&session_id)
+}
+
+/// The session's state as json, to be stored by the caller after each round.
+/// It contains secret shares and must be kept as confidential as the key store.
+#[wasm_bindgen]
+pub fn gg18_session_checkpoint(session_id: String) -> Result<String, JsValue> {
+    session_checkpoint(&session_id).map(|c| serde_json::to_string(&c).unwrap())
+}
+
+/// Reopens a session from `gg18_session_checkpoint` output, e.g. after a page
+/// reload. The ceremony continues with the round that was next when the
+/// checkpoint was taken.
+#[wasm_bindgen]
+pub fn gg18_session_restore(session_id: String, checkpoint: String) -> Result<(), JsValue> {
+    let checkpoint = serde_json::from_str::<Checkpoint>(&checkpoint)
+        .map_err(|e| ApiError::new(ErrorCode::InvalidContext, &e.to_string()))?;
+    session_open(&session_id, checkpoint)
+}
+
+/// The round the session will run next.
+#[wasm_bindgen]
+pub fn gg18_session_next_round(session_id: String) -> Result<u8, JsValue> {
+    session_checkpoint(&session_id).map(|c| c.next_round)
+}
+
+/// Sets how long each following round of the session waits for the other
+/// parties; 0 waits forever, which is the default. A round that runs out of
+/// time fails with a `Timeout` error whose `missing` names the parties that
+/// did not deliver, and the session stays at that round. Rounds 0 and 1 of
+/// a signing session started by more than `t + 1` parties instead go on
+/// without them, see `gg18_sign_session_set_online`.
+#[wasm_bindgen]
+pub fn gg18_session_set_round_timeout(session_id: String, timeout_ms: u32) -> Result<(), JsValue> {
+    let mut checkpoint = session_checkpoint(&session_id)?;
+    checkpoint.round_timeout_ms = if timeout_ms == 0 {
+        None
+    } else {
+        Some(timeout_ms)
+    };
+    session_update(&session_id, checkpoint)
+}
+
+/// Installs `SessionLimits`, given as json, for all sessions of this
+/// instance. Opening a session beyond `max_sessions` fails with
+/// `TooManySessions`; a round that receives a message over
+/// `max_message_bytes` fails with `MessageTooLarge`, without reading more
+/// of it than the limit allows, and one beyond `message_rate` with
+/// `RateLimited`, naming the sending party. Each session counts its own
+/// peers' messages; a round of the client API counts only its own.
+#[wasm_bindgen]
+pub fn gg18_session_set_limits(limits: String) -> Result<(), JsValue> {
+    let limits: SessionLimits = parse_context(&limits)?;
+    SESSIONS.with(|s| s.borrow_mut().set_limits(limits));
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn gg18_keygen_session_new(
+    session_id: String,
+    addr: String,
+    t: usize,
+    n: usize,
+    delay: u32,
+) -> Result<(), JsValue> {
+    let context = gg18_keygen_client_new_context(addr, t, n, delay).await?;
+    session_open(&session_id, Checkpoint::new(Ceremony::Keygen, context))
+}
+
+/// Runs keygen round `round` (0 to 5) of the session. The last round closes
+/// the session and returns the key store; earlier rounds return an empty string.
+#[wasm_bindgen]
+pub async fn gg18_keygen_session_round(
+    session_id: String,
+    round: u8,
+    delay: u32,
+) -> Result<String, JsValue> {
+    let mut checkpoint = session_begin_round(&session_id, Ceremony::Keygen, round)?;
+    // moved rather than cloned, contexts grow with the committee size
+    let context = std::mem::take(&mut checkpoint.context);
+    let mut intake = Intake::session(&session_id, &checkpoint)?;
+    let output = match round {
+        0 => keygen_round0(context, delay, &mut intake).await,
+        1 => keygen_round1(context, delay, &mut intake).await,
+        2 => keygen_round2(context, delay, &mut intake).await,
+        3 => keygen_round3(context, delay, &mut intake).await,
+        4 => keygen_round4(context, delay, &mut intake).await,
+        5 => {
+            let key_store = keygen_round5(context, delay, &mut intake)
+                .await
+                .map_err(JsValue::from)?;
+            session_close(&session_id)?;
+            return Ok(key_store);
+        }
+        _ => return Err(ApiError::new(ErrorCode::WrongRound, "unknown keygen round").into()),
+    };
+    intake.finish(&session_id);
+    let output = output.map_err(JsValue::from)?;
+    session_update(&session_id, checkpoint.advance(output))?;
+    Ok(String::new())
+}
+
+/// Runs the last keygen round of the session like `gg18_keygen_session_round`,
+/// but returns the key store encrypted under `key`, see `gg18_wrap_key_store`.
+#[wasm_bindgen]
+pub async fn gg18_keygen_session_finish_wrapped(
+    session_id: String,
+    delay: u32,
+    key: CryptoKey,
+) -> Result<String, JsValue> {
+    let last_round = Ceremony::Keygen.last_round();
+    let checkpoint = session_begin_round(&session_id, Ceremony::Keygen, last_round)?;
+    let mut intake = Intake::session(&session_id, &checkpoint)?;
+    let key_store = keygen_round5(checkpoint.context, delay, &mut intake).await;
+    intake.finish(&session_id);
+    let key_store = key_store.map_err(JsValue::from)?;
+    session_close(&session_id)?;
+    wrap_key_store(&key_store, &key).await
+}
+
+/// Runs the last signing round of the session like `gg18_sign_session_round`,
+/// but returns `transaction` signed, see `gg18_sign_client_round9_ethereum`.
+#[cfg(feature = "ethereum")]
+#[wasm_bindgen]
+pub async fn gg18_sign_session_finish_ethereum(
+    session_id: String,
+    delay: u32,
+    transaction: String,
+) -> Result<String, JsValue> {
+    let last_round = Ceremony::Sign.last_round();
+    let checkpoint = session_begin_round(&session_id, Ceremony::Sign, last_round)?;
+    let numbers = StartedNumbers::of(&checkpoint.context);
+    let mut intake = Intake::session(&session_id, &checkpoint)?;
+    let raw = sign_round9_ethereum(checkpoint.context, delay, &mut intake, &transaction).await;
+    intake.finish(&session_id);
+    let raw = raw.map_err(|e| JsValue::from(numbers.apply(e)))?;
+    session_close(&session_id)?;
+    Ok(raw)
+}
+
+/// Opens a signing session with `message_str` signed unhashed, as
+/// `gg18_sign_client_new_context` signs it. Use
+/// `gg18_sign_session_new_with_digest` to have the message hashed or to pass
+/// a digest.
+#[wasm_bindgen]
+pub async fn gg18_sign_session_new(
+    session_id: String,
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: String,
+    message_str: String,
+) -> Result<(), JsValue> {
+    let context = gg18_sign_client_new_context(addr, t, n, key_store, message_str).await?;
+    session_open(&session_id, Checkpoint::new(Ceremony::Sign, context))
+}
+
+/// Like `gg18_sign_session_new`, for a key store wrapped under `key`. The
+/// key store is decrypted inside wasm and never returned.
+#[wasm_bindgen]
+pub async fn gg18_sign_session_new_wrapped(
+    session_id: String,
+    addr: String,
+    t: usize,
+    n: usize,
+    wrapped_key_store: String,
+    key: CryptoKey,
+    message_str: String,
+) -> Result<(), JsValue> {
+    let key_store = unwrap_key_store(&wrapped_key_store, &key).await?;
+    gg18_sign_session_new(session_id, addr, t, n, key_store, message_str).await
+}
+
+/// Like `gg18_sign_session_new`, signing `message_hex` as
+/// `gg18_sign_client_new_context_with_digest` does: hashed with SHA-256 or,
+/// if `prehashed`, as the 32 byte digest it already is.
+#[wasm_bindgen]
+pub async fn gg18_sign_session_new_with_digest(
+    session_id: String,
+    addr: String,
+    t: usize,
+    n: usize,
+    key_store: String,
+    message_hex: String,
+    prehashed: bool,
+) -> Result<(), JsValue> {
+    let context =
+        gg18_sign_client_new_context_with_digest(addr, t, n, key_store, message_hex, prehashed)
+            .await?;
+    session_open(&session_id, Checkpoint::new(Ceremony::Sign, context))
+}
+
+/// `gg18_sign_session_new_with_digest` for a key store wrapped under `key`.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub async fn gg18_sign_session_new_wrapped_with_digest(
+    session_id: String,
+    addr: String,
+    t: usize,
+    n: usize,
+    wrapped_key_store: String,
+    key: CryptoKey,
+    message_hex: String,
+    prehashed: bool,
+) -> Result<(), JsValue> {
+    let key_store = unwrap_key_store(&wrapped_key_store, &key).await?;
+    gg18_sign_session_new_with_digest(session_id, addr, t, n, key_store, message_hex, prehashed)
+        .await
+}
+
+/// `gg18_sign_client_set_online` for a signing session, before its round 0.
+/// The session's round timeout is when rounds 0 and 1 go on without the
+/// parties that have not broadcast.
+#[wasm_bindgen]
+pub fn gg18_sign_session_set_online(session_id: String, parties: u16) -> Result<(), JsValue> {
+    let mut checkpoint = session_begin_round(&session_id, Ceremony::Sign, 0)?;
+    checkpoint.context = set_online(&checkpoint.context, parties)?;
+    session_update(&session_id, checkpoint)
+}
+
+/// Runs signing round `round` (0 to 9) of the session. The last round closes
+/// the session and returns the signature; earlier rounds return an empty string.
+#[wasm_bindgen]
+pub async fn gg18_sign_session_round(
+    session_id: String,
+    round: u8,
+    delay: u32,
+) -> Result<String, JsValue> {
+    let mut checkpoint = session_begin_round(&session_id, Ceremony::Sign, round)?;
+    let context = std::mem::take(&mut checkpoint.context);
+    let mut intake = Intake::session(&session_id, &checkpoint)?;
+    let numbers = StartedNumbers::of(&context);
+    let output = match round {
+        0 => sign_round0(context, delay, &mut intake).await,
+        1 => {
+            // the commitments are checkpointed before they are sent
+            checkpoint.context = gg18_sign_client_round1_prepare(context)?;
+            session_update(&session_id, checkpoint.clone())?;
+            sign_round1(checkpoint.context.clone(), delay, &mut intake).await
+        }
+        2 => sign_round2(context, delay, &mut intake).await,
+        3 => sign_round3(context, delay, &mut intake).await,
+        4 => sign_round4(context, delay, &mut intake).await,
+        5 => sign_round5(context, delay, &mut intake).await,
+        6 => sign_round6(context, delay, &mut intake).await,
+        7 => sign_round7(context, delay, &mut intake).await,
+        8 => sign_round8(context, delay, &mut intake, None).await,
+        9 => {
+            let signature = sign_round9(context, delay, &mut intake)
+                .await
+                .map_err(|e| JsValue::from(numbers.apply(e)))?;
+            session_close(&session_id)?;
+            return Ok(signature);
+        }
+        _ => return Err(ApiError::new(ErrorCode::WrongRound, "unknown signing round").into()),
+    };
+    intake.finish(&session_id);
+    let output = output.map_err(|e| JsValue::from(numbers.apply(e)))?;
+    session_update(&session_id, checkpoint.advance(output))?;
+    Ok(String::new())
 }
 
 fn format_vec_from_reads<'a, T: serde::Deserialize<'a> + Clone>(
@@ -1174,15 +3636,17 @@ fn format_vec_from_reads<'a, T: serde::Deserialize<'a> + Clone>(
     party_num: usize,
     value_i: T,
     new_vec: &'a mut Vec<T>,
-) {
+    round: &str,
+) -> Result<(), ApiError> {
     let mut j = 0;
     for i in 1..ans_vec.len() + 2 {
         if i == party_num {
             new_vec.push(value_i.clone());
         } else {
-            let value_j: T = serde_json::from_str(&ans_vec[j]).unwrap();
+            let value_j: T = parse_message(&ans_vec[j], round, i as u16)?;
             new_vec.push(value_j);
             j += 1;
         }
     }
+    Ok(())
 }
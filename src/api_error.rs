@@ -0,0 +1,358 @@
+//! The errors the wasm API reports.
+//!
+//! In JS each one is thrown as an `Error` named `TssError` with a stable
+//! `code`, and where known the `round` it happened in, the 1-based `party`
+//! whose message failed a check, and the `missing` parties of a timed out
+//! round. Frontends can retry on `Timeout` or `Network` and blame `party` on
+//! a failed check without parsing the message. Where a signing check names
+//! every signer it caught, they are all listed in `blamed`.
+
+#![cfg(feature = "gg18")]
+
+use crate::common::{RoundError, RoundTimeout};
+#[cfg(feature = "ethereum")]
+use crate::ethereum::EthereumError;
+use crate::gg_2018::adaptor::AdaptorError;
+#[cfg(feature = "escrow")]
+use crate::gg_2018::escrow::EscrowError;
+use crate::gg_2018::handshake::HandshakeError;
+use crate::gg_2018::health::KeyHealthError;
+use crate::gg_2018::party_i::ParametersError;
+use crate::gg_2018::policy::PolicyError;
+#[cfg(feature = "escrow")]
+use crate::gg_2018::verifiable_encryption::EncryptionError;
+use crate::session::SessionError;
+use crate::storage::StorageError;
+use crate::wallet::WalletError;
+use crate::Error;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum ErrorCode {
+    InvalidParameters,
+    InvalidKeyStore,
+    InvalidContext,
+    InvalidMessage,
+    Network,
+    Timeout,
+    InvalidKey,
+    InvalidShare,
+    InvalidCommitment,
+    InvalidProof,
+    InvalidSignature,
+    UnknownSession,
+    SessionExists,
+    WrongCeremony,
+    WrongRound,
+    PolicyRejected,
+    TooManySessions,
+    RateLimited,
+    Incompatible,
+    Storage,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidParameters => "InvalidParameters",
+            ErrorCode::InvalidKeyStore => "InvalidKeyStore",
+            ErrorCode::InvalidContext => "InvalidContext",
+            ErrorCode::InvalidMessage => "InvalidMessage",
+            ErrorCode::Network => "Network",
+            ErrorCode::Timeout => "Timeout",
+            ErrorCode::InvalidKey => "InvalidKey",
+            ErrorCode::InvalidShare => "InvalidShare",
+            ErrorCode::InvalidCommitment => "InvalidCommitment",
+            ErrorCode::InvalidProof => "InvalidProof",
+            ErrorCode::InvalidSignature => "InvalidSignature",
+            ErrorCode::UnknownSession => "UnknownSession",
+            ErrorCode::SessionExists => "SessionExists",
+            ErrorCode::WrongCeremony => "WrongCeremony",
+            ErrorCode::WrongRound => "WrongRound",
+            ErrorCode::PolicyRejected => "PolicyRejected",
+            ErrorCode::TooManySessions => "TooManySessions",
+            ErrorCode::RateLimited => "RateLimited",
+            ErrorCode::Incompatible => "Incompatible",
+            ErrorCode::Storage => "Storage",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub round: Option<String>,
+    pub party: Option<u16>,
+    pub missing: Vec<u16>,
+    pub blamed: Vec<u16>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: &str) -> ApiError {
+        ApiError {
+            code,
+            message: message.to_string(),
+            round: None,
+            party: None,
+            missing: Vec::new(),
+            blamed: Vec::new(),
+        }
+    }
+
+    pub fn in_round(mut self, round: &str) -> ApiError {
+        self.round = Some(round.to_string());
+        self
+    }
+
+    pub fn with_party(mut self, party: u16) -> ApiError {
+        self.party = Some(party);
+        self
+    }
+
+    /// Blames all of `parties`, the first of them as `party`.
+    pub fn with_blame(mut self, parties: Vec<u16>) -> ApiError {
+        self.party = parties.first().cloned();
+        self.blamed = parties;
+        self
+    }
+}
+
+impl From<RoundTimeout> for ApiError {
+    fn from(e: RoundTimeout) -> ApiError {
+        ApiError {
+            code: ErrorCode::Timeout,
+            message: format!("parties {:?} did not deliver {}", e.missing, e.round),
+            round: Some(e.round),
+            party: None,
+            missing: e.missing,
+            blamed: Vec::new(),
+        }
+    }
+}
+
+impl From<SessionError> for ApiError {
+    fn from(e: SessionError) -> ApiError {
+        match e {
+            SessionError::UnknownSession => {
+                ApiError::new(ErrorCode::UnknownSession, "unknown session")
+            }
+            SessionError::SessionExists => {
+                ApiError::new(ErrorCode::SessionExists, "session already exists")
+            }
+            SessionError::TooManySessions => {
+                ApiError::new(ErrorCode::TooManySessions, "too many open sessions")
+            }
+            SessionError::MessageTooLarge { party } => {
+                ApiError::new(ErrorCode::RateLimited, "message over the size limit")
+                    .with_party(party)
+            }
+            SessionError::RateLimited { party } => {
+                ApiError::new(ErrorCode::RateLimited, "too many messages").with_party(party)
+            }
+        }
+    }
+}
+
+impl From<RoundError> for ApiError {
+    fn from(e: RoundError) -> ApiError {
+        match e {
+            RoundError::Timeout(e) => ApiError::from(e),
+            RoundError::Rejected(e) => ApiError::from(e),
+            RoundError::Network(round) => {
+                ApiError::new(ErrorCode::Network, "could not reach the relay").in_round(&round)
+            }
+        }
+    }
+}
+
+impl From<ParametersError> for ApiError {
+    fn from(e: ParametersError) -> ApiError {
+        ApiError::new(ErrorCode::InvalidParameters, &e.to_string())
+    }
+}
+
+impl From<HandshakeError> for ApiError {
+    fn from(e: HandshakeError) -> ApiError {
+        let parties = match &e {
+            HandshakeError::ProtocolVersion { parties, .. }
+            | HandshakeError::ProofBackend { parties }
+            | HandshakeError::WireFormat { parties } => parties.clone(),
+            HandshakeError::Outdated { party } => vec![*party],
+        };
+        ApiError::new(ErrorCode::Incompatible, &e.to_string())
+            .in_round("round0")
+            .with_blame(parties)
+    }
+}
+
+impl From<AdaptorError> for ApiError {
+    fn from(e: AdaptorError) -> ApiError {
+        let code = match e {
+            AdaptorError::InvalidProof => ErrorCode::InvalidProof,
+            AdaptorError::InvalidPresignature
+            | AdaptorError::WrongSecret
+            | AdaptorError::NotCompleted => ErrorCode::InvalidSignature,
+        };
+        ApiError::new(code, &format!("{:?}", e))
+    }
+}
+
+#[cfg(feature = "escrow")]
+impl From<EscrowError> for ApiError {
+    fn from(e: EscrowError) -> ApiError {
+        let code = match e {
+            EscrowError::Encryption(EncryptionError::WrongRecoveryKey)
+            | EscrowError::WrongAgentKey => ErrorCode::InvalidKey,
+            EscrowError::Encryption(EncryptionError::InvalidCiphertext) => {
+                ErrorCode::InvalidMessage
+            }
+            EscrowError::Encryption(EncryptionError::InvalidProof)
+            | EscrowError::InvalidPartialDecryption { .. } => ErrorCode::InvalidProof,
+            EscrowError::Encryption(EncryptionError::InvalidShare) => ErrorCode::InvalidShare,
+            EscrowError::WrongTerms | EscrowError::TooEarly | EscrowError::NotEnoughAgents => {
+                ErrorCode::InvalidParameters
+            }
+        };
+        ApiError::new(code, &format!("{:?}", e))
+    }
+}
+
+impl From<WalletError> for ApiError {
+    fn from(e: WalletError) -> ApiError {
+        let code = match e {
+            WalletError::InvalidKeyStore => ErrorCode::InvalidKeyStore,
+            WalletError::InvalidTweak | WalletError::WrongKey => ErrorCode::InvalidKey,
+            WalletError::Presignature(_) | WalletError::InvalidContext => ErrorCode::InvalidContext,
+            WalletError::Storage => ErrorCode::Storage,
+            WalletError::Session(e) => return ApiError::from(e),
+            WalletError::WrongCeremony => ErrorCode::WrongCeremony,
+            #[cfg(feature = "ethereum")]
+            WalletError::Ethereum(e) => return ApiError::from(e),
+            WalletError::UnknownUser
+            | WalletError::UserExists
+            | WalletError::InvalidExportKey
+            | WalletError::InvalidPath
+            | WalletError::HardenedPath => ErrorCode::InvalidParameters,
+        };
+        ApiError::new(code, &format!("{:?}", e))
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(e: StorageError) -> ApiError {
+        ApiError::new(ErrorCode::Storage, &format!("{:?}", e))
+    }
+}
+
+impl From<KeyHealthError> for ApiError {
+    fn from(e: KeyHealthError) -> ApiError {
+        ApiError::new(
+            ErrorCode::InvalidKeyStore,
+            &format!("key store failed its health check: {:?}", e),
+        )
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> ApiError {
+        let code = match e {
+            Error::InvalidKey => ErrorCode::InvalidKey,
+            Error::InvalidSS | Error::MtaShareOutOfRange => ErrorCode::InvalidShare,
+            Error::InvalidCom => ErrorCode::InvalidCommitment,
+            Error::InvalidSig => ErrorCode::InvalidSignature,
+            Error::InvalidCiphertext
+            | Error::InvalidPlaintext
+            | Error::InvalidRandomness
+            | Error::InvalidDigest => ErrorCode::InvalidProof,
+            Error::ReusedRandomness => ErrorCode::InvalidParameters,
+        };
+        ApiError::new(code, &format!("{:?}", e))
+    }
+}
+
+#[cfg(feature = "ethereum")]
+impl From<EthereumError> for ApiError {
+    fn from(e: EthereumError) -> ApiError {
+        let message = match e {
+            EthereumError::DigestMismatch => "the signature is not over the transaction",
+            EthereumError::Unexpressible => "Ethereum cannot express the recovery id",
+        };
+        ApiError::new(ErrorCode::InvalidSignature, message)
+    }
+}
+
+impl From<PolicyError> for ApiError {
+    fn from(e: PolicyError) -> ApiError {
+        match e {
+            PolicyError::Rejected(rejection) => {
+                ApiError::new(ErrorCode::PolicyRejected, &rejection.to_string())
+            }
+            PolicyError::RequestMismatch => ApiError::new(
+                ErrorCode::PolicyRejected,
+                "policy request does not match the signature",
+            ),
+            PolicyError::Protocol(e) => ApiError::from(e),
+            PolicyError::Presignature(e) => {
+                ApiError::new(ErrorCode::InvalidContext, &format!("{:?}", e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_api_errors() {
+        let timeout = ApiError::from(RoundTimeout {
+            round: "round3".to_string(),
+            missing: vec![2, 4],
+        });
+        assert_eq!(timeout.code, ErrorCode::Timeout);
+        assert_eq!(timeout.round.as_deref(), Some("round3"));
+        assert_eq!(timeout.missing, vec![2, 4]);
+
+        let blamed = ApiError::from(Error::InvalidKey)
+            .in_round("round5")
+            .with_party(3);
+        assert_eq!(blamed.code, ErrorCode::InvalidKey);
+        assert_eq!(blamed.party, Some(3));
+        let blamed = ApiError::from(Error::InvalidCom).with_blame(vec![2, 4]);
+        assert_eq!(blamed.party, Some(2));
+        assert_eq!(blamed.blamed, vec![2, 4]);
+        assert_eq!(
+            ApiError::from(Error::InvalidSS).code,
+            ErrorCode::InvalidShare
+        );
+        assert_eq!(
+            ApiError::from(ParametersError::ThresholdTooLarge).code,
+            ErrorCode::InvalidParameters
+        );
+        assert_eq!(
+            ApiError::from(KeyHealthError::InvalidShare).code,
+            ErrorCode::InvalidKeyStore
+        );
+        assert_eq!(
+            ApiError::from(PolicyError::Protocol(Error::InvalidKey)).code,
+            ErrorCode::InvalidKey
+        );
+        let flooded = ApiError::from(RoundError::Rejected(SessionError::RateLimited { party: 3 }));
+        assert_eq!(flooded.code, ErrorCode::RateLimited);
+        assert_eq!(flooded.party, Some(3));
+        let unreachable = ApiError::from(RoundError::Network("round2".to_string()));
+        assert_eq!(unreachable.code, ErrorCode::Network);
+        assert_eq!(unreachable.round.as_deref(), Some("round2"));
+        let outdated = ApiError::from(HandshakeError::WireFormat {
+            parties: vec![2, 3],
+        });
+        assert_eq!(outdated.code, ErrorCode::Incompatible);
+        assert_eq!(outdated.round.as_deref(), Some("round0"));
+        assert_eq!(outdated.blamed, vec![2, 3]);
+        assert_eq!(ErrorCode::WrongRound.as_str(), "WrongRound");
+    }
+}
@@ -0,0 +1,325 @@
+//! Signed statements of how a threshold key was generated.
+//!
+//! After keygen each party signs, with its secp256k1 identity key, a
+//! `KeygenStatement`: the group key, t and n, every party's identity key
+//! and a SHA-256 digest of each dealer's VSS commitments. The statements
+//! are public data, so any party or the relay can collect the signatures
+//! into an `AttestationBundle`, and a third party holding the bundle checks
+//! that all n parties vouch for the same key, parameters and commitments.
+//! Given the commitments themselves it can also check that they are the
+//! ones attested and produce the group key, e.g. with `verify`.
+//!
+//! Identity keys are those the parties are known by outside the ceremony,
+//! as for `noise`; the bundle proves nothing about a party whose identity
+//! key the verifier does not already trust. Parties sign through an
+//! `IdentitySigner`, so the key may stay in an HSM.
+#![cfg(feature = "gg18")]
+
+use sha2::{Digest, Sha256};
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::curv::elliptic::curves::traits::*;
+use crate::ecdsa::{self, RecoverableSignature};
+use crate::gg_2018::party_i::LocalKey;
+use crate::identity::{IdentityError, IdentitySigner};
+
+const STATEMENT_VERSION: u8 = 1;
+const STATEMENT_DOMAIN: &[u8] = b"tss-wasm keygen attestation";
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum AttestationError {
+    /// Not one sharing per party, or sharings with differing parameters.
+    InvalidCommitments,
+    /// Not one identity key per party.
+    WrongPartyCount,
+    /// A party index outside 1..=n.
+    UnknownParty,
+    /// A signature that does not verify under its party's identity key.
+    InvalidSignature,
+    /// A party with no signature in the bundle.
+    MissingSignature,
+    /// Commitments other than those attested.
+    CommitmentMismatch,
+}
+
+/// What the parties of a keygen attest to.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct KeygenStatement {
+    pub group_public_key: GE,
+    pub threshold: u16,
+    pub share_count: u16,
+    /// By party index, counted from 1.
+    pub identity_keys: Vec<GE>,
+    /// SHA-256 of each dealer's commitments, by party index.
+    pub commitment_digests: Vec<[u8; 32]>,
+}
+
+/// One party's signature over a `KeygenStatement`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PartyAttestation {
+    pub party_id: u16,
+    pub signature: RecoverableSignature,
+}
+
+/// A statement with a signature from every party.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AttestationBundle {
+    pub statement: KeygenStatement,
+    pub attestations: Vec<PartyAttestation>,
+}
+
+/// SHA-256 of the compressed commitments of one dealer, in order.
+pub fn commitment_digest(vss: &VerifiableSS) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for commitment in &vss.commitments {
+        hasher.update(commitment.to_bytes(true));
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+impl KeygenStatement {
+    /// The statement for the commitments every party broadcast in keygen,
+    /// and the parties' identity keys by index.
+    pub fn from_commitments(
+        vss_scheme_vec: &[VerifiableSS],
+        identity_keys: Vec<GE>,
+    ) -> Result<KeygenStatement, AttestationError> {
+        let first = vss_scheme_vec
+            .first()
+            .ok_or(AttestationError::InvalidCommitments)?;
+        let consistent = vss_scheme_vec.len() == first.parameters.share_count
+            && vss_scheme_vec.iter().all(|vss| {
+                vss.parameters == first.parameters
+                    && vss.commitments.len() == vss.reconstruct_limit()
+            });
+        if !consistent {
+            return Err(AttestationError::InvalidCommitments);
+        }
+        if identity_keys.len() != vss_scheme_vec.len() {
+            return Err(AttestationError::WrongPartyCount);
+        }
+        let group_public_key = vss_scheme_vec
+            .iter()
+            .skip(1)
+            .fold(first.commitments[0].clone(), |acc, vss| {
+                acc + &vss.commitments[0]
+            });
+        Ok(KeygenStatement {
+            group_public_key,
+            threshold: first.parameters.threshold as u16,
+            share_count: first.parameters.share_count as u16,
+            identity_keys,
+            commitment_digests: vss_scheme_vec.iter().map(commitment_digest).collect(),
+        })
+    }
+
+    pub fn from_local_key(
+        key: &LocalKey,
+        identity_keys: Vec<GE>,
+    ) -> Result<KeygenStatement, AttestationError> {
+        KeygenStatement::from_commitments(&key.vss_scheme_vec, identity_keys)
+    }
+
+    /// The digest the parties sign.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(STATEMENT_DOMAIN);
+        hasher.update([STATEMENT_VERSION]);
+        hasher.update(self.threshold.to_be_bytes());
+        hasher.update(self.share_count.to_be_bytes());
+        hasher.update(self.group_public_key.to_bytes(true));
+        for identity_key in &self.identity_keys {
+            hasher.update(identity_key.to_bytes(true));
+        }
+        for digest in &self.commitment_digests {
+            hasher.update(digest);
+        }
+        let mut out = [0u8; 32];
+        out.copy_from_slice(&hasher.finalize());
+        out
+    }
+
+    /// Signs the statement as party `party_id` with its identity key.
+    pub fn attest<I: IdentitySigner + ?Sized>(
+        &self,
+        party_id: u16,
+        identity: &I,
+    ) -> Result<PartyAttestation, IdentityError> {
+        Ok(PartyAttestation {
+            party_id,
+            signature: identity.sign_digest(&self.digest())?,
+        })
+    }
+
+    /// Checks one party's signature.
+    pub fn verify_attestation(
+        &self,
+        attestation: &PartyAttestation,
+    ) -> Result<(), AttestationError> {
+        let index = usize::from(attestation.party_id);
+        if index == 0 || index > self.identity_keys.len() {
+            return Err(AttestationError::UnknownParty);
+        }
+        let sig = &attestation.signature;
+        ecdsa::verify(
+            &self.identity_keys[index - 1],
+            &self.digest(),
+            &sig.r,
+            &sig.s,
+        )
+        .map_err(|_| AttestationError::InvalidSignature)
+    }
+
+    /// Checks that `vss_scheme_vec` are the commitments attested to.
+    pub fn check_commitments(
+        &self,
+        vss_scheme_vec: &[VerifiableSS],
+    ) -> Result<(), AttestationError> {
+        let same = vss_scheme_vec.len() == self.commitment_digests.len()
+            && vss_scheme_vec
+                .iter()
+                .zip(&self.commitment_digests)
+                .all(|(vss, digest)| commitment_digest(vss) == *digest);
+        if same {
+            Ok(())
+        } else {
+            Err(AttestationError::CommitmentMismatch)
+        }
+    }
+}
+
+impl AttestationBundle {
+    /// Collects the attestations, checking each, in party order.
+    pub fn new(
+        statement: KeygenStatement,
+        mut attestations: Vec<PartyAttestation>,
+    ) -> Result<AttestationBundle, AttestationError> {
+        attestations.sort_by_key(|a| a.party_id);
+        attestations.dedup_by_key(|a| a.party_id);
+        let bundle = AttestationBundle {
+            statement,
+            attestations,
+        };
+        bundle.verify()?;
+        Ok(bundle)
+    }
+
+    /// Checks that the statement is consistent and that every party signed
+    /// it.
+    pub fn verify(&self) -> Result<(), AttestationError> {
+        let statement = &self.statement;
+        let n = usize::from(statement.share_count);
+        if statement.identity_keys.len() != n || statement.commitment_digests.len() != n {
+            return Err(AttestationError::WrongPartyCount);
+        }
+        if statement.threshold >= statement.share_count {
+            return Err(AttestationError::InvalidCommitments);
+        }
+        for party_id in 1..=statement.share_count {
+            let attestation = self
+                .attestations
+                .iter()
+                .find(|a| a.party_id == party_id)
+                .ok_or(AttestationError::MissingSignature)?;
+            statement.verify_attestation(attestation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealings;
+    use crate::identity::LocalIdentity;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    struct Ceremony {
+        vss_scheme_vec: Vec<VerifiableSS>,
+        statement: KeygenStatement,
+        attestations: Vec<PartyAttestation>,
+    }
+
+    fn ceremony() -> Ceremony {
+        let (_, vss_scheme_vec, _) = dealings(1, 3);
+        let identities = (0..3)
+            .map(|_| LocalIdentity::new(ECScalar::new_random()))
+            .collect::<Vec<LocalIdentity>>();
+        let identity_keys = identities.iter().map(|i| i.public_key()).collect();
+        let statement = KeygenStatement::from_commitments(&vss_scheme_vec, identity_keys).unwrap();
+        let attestations = identities
+            .iter()
+            .enumerate()
+            .map(|(i, identity)| statement.attest(i as u16 + 1, identity).unwrap())
+            .collect();
+        Ceremony {
+            vss_scheme_vec,
+            statement,
+            attestations,
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_attestation_bundle() {
+        let ceremony = ceremony();
+        assert_eq!(
+            ceremony.statement.group_public_key,
+            VerifiableSS::combine(&ceremony.vss_scheme_vec)
+                .unwrap()
+                .commitments[0]
+        );
+        let bundle = AttestationBundle::new(ceremony.statement, ceremony.attestations).unwrap();
+        assert_eq!(
+            bundle.statement.check_commitments(&ceremony.vss_scheme_vec),
+            Ok(())
+        );
+
+        // a bundle survives JSON, and a changed statement no longer verifies
+        let json = serde_json::to_string(&bundle).unwrap();
+        let mut parsed: AttestationBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.verify(), Ok(()));
+        parsed.statement.threshold = 0;
+        assert_eq!(parsed.verify(), Err(AttestationError::InvalidSignature));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_missing_signature() {
+        let ceremony = ceremony();
+        assert_eq!(
+            AttestationBundle::new(ceremony.statement, ceremony.attestations[..2].to_vec()).err(),
+            Some(AttestationError::MissingSignature)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_forged_signature() {
+        let ceremony = ceremony();
+        // party 2 signing as party 1
+        let mut forged = ceremony.attestations;
+        forged[0].signature = forged[1].signature.clone();
+        assert_eq!(
+            AttestationBundle::new(ceremony.statement, forged).err(),
+            Some(AttestationError::InvalidSignature)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_commitment_mismatch() {
+        let ceremony = ceremony();
+        assert_eq!(
+            ceremony
+                .statement
+                .check_commitments(&ceremony.vss_scheme_vec[1..]),
+            Err(AttestationError::CommitmentMismatch)
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Paper backup of a key share as a sequence of BIP39 words.
+//!
+//! The backup holds a version byte, the party id, the 32 byte share `x_i` and
+//! one byte of the group key's fingerprint: 36 bytes, which BIP39 encodes
+//! with a 9 bit SHA-256 checksum as 27 words from the English list. The
+//! words are not a BIP39 wallet seed and must not be imported as one.
+//!
+//! Only the share is backed up. The public keygen data and the Paillier
+//! keys are not secret and large, so restoring a full `LocalKey` combines
+//! the share with public data from the other parties and a new Paillier key.
+#![cfg(feature = "gg18")]
+
+use bip39::Language;
+use sha2::{Digest, Sha256};
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::LocalKey;
+
+const BACKUP_VERSION: u8 = 1;
+const BACKUP_BYTES_LEN: usize = 36;
+const WORD_BITS: usize = 11;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum BackupError {
+    InvalidWord,
+    InvalidLength,
+    InvalidChecksum,
+    UnsupportedVersion,
+    InvalidShare,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ShareBackup {
+    pub party_id: u16,
+    pub x_i: FE,
+    pub key_fingerprint: u8,
+}
+
+fn key_fingerprint(y_sum: &GE) -> u8 {
+    Sha256::digest(&y_sum.to_bytes(true))[0]
+}
+
+fn bit(bytes: &[u8], i: usize) -> u16 {
+    u16::from((bytes[i / 8] >> (7 - i % 8)) & 1)
+}
+
+impl ShareBackup {
+    pub fn from_local_key(key: &LocalKey) -> ShareBackup {
+        ShareBackup {
+            party_id: key.party_id,
+            x_i: key.shared_keys.x_i.clone(),
+            key_fingerprint: key_fingerprint(&key.y_sum),
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; BACKUP_BYTES_LEN] {
+        let mut bytes = [0u8; BACKUP_BYTES_LEN];
+        bytes[0] = BACKUP_VERSION;
+        bytes[1..3].copy_from_slice(&self.party_id.to_be_bytes());
+        let x_i = self.x_i.to_big_int().to_bytes_be();
+        bytes[35 - x_i.len()..35].copy_from_slice(&x_i);
+        bytes[35] = self.key_fingerprint;
+        bytes
+    }
+
+    /// The backup as 27 space separated words.
+    pub fn to_mnemonic(&self) -> String {
+        let mut bits = self.to_bytes().to_vec();
+        // the checksum is the first len/4 bits of the hash, here 9
+        let hash = Sha256::digest(&bits);
+        bits.extend_from_slice(&hash[..2]);
+
+        let word_list = Language::English.word_list();
+        let words = (BACKUP_BYTES_LEN * 8 + BACKUP_BYTES_LEN / 4) / WORD_BITS;
+        (0..words)
+            .map(|w| {
+                let index =
+                    (0..WORD_BITS).fold(0u16, |acc, i| (acc << 1) | bit(&bits, w * WORD_BITS + i));
+                word_list[index as usize]
+            })
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Result<ShareBackup, BackupError> {
+        let indices = mnemonic
+            .split_whitespace()
+            .map(|w| {
+                Language::English
+                    .find_word(&w.to_lowercase())
+                    .ok_or(BackupError::InvalidWord)
+            })
+            .collect::<Result<Vec<u16>, BackupError>>()?;
+        if indices.len() * WORD_BITS != BACKUP_BYTES_LEN * 8 + BACKUP_BYTES_LEN / 4 {
+            return Err(BackupError::InvalidLength);
+        }
+
+        let mut bits = vec![0u8; (indices.len() * WORD_BITS).div_ceil(8)];
+        for (w, index) in indices.iter().enumerate() {
+            for i in 0..WORD_BITS {
+                if (index >> (WORD_BITS - 1 - i)) & 1 == 1 {
+                    let pos = w * WORD_BITS + i;
+                    bits[pos / 8] |= 1 << (7 - pos % 8);
+                }
+            }
+        }
+        let (bytes, checksum) = bits.split_at(BACKUP_BYTES_LEN);
+        let hash = Sha256::digest(bytes);
+        if checksum[0] != hash[0] || (checksum[1] ^ hash[1]) & 0x80 != 0 {
+            return Err(BackupError::InvalidChecksum);
+        }
+
+        if bytes[0] != BACKUP_VERSION {
+            return Err(BackupError::UnsupportedVersion);
+        }
+        let x_i = FE::from_bytes(&bytes[3..35]).map_err(|_| BackupError::InvalidShare)?;
+        Ok(ShareBackup {
+            party_id: u16::from_be_bytes([bytes[1], bytes[2]]),
+            x_i,
+            key_fingerprint: bytes[35],
+        })
+    }
+
+    /// A quick check that the backup belongs to the wallet with group key
+    /// `y_sum`. One byte of fingerprint misses one wrong wallet in 256; use
+    /// `verify` when the keygen commitments are at hand.
+    pub fn matches(&self, y_sum: &GE) -> bool {
+        self.key_fingerprint == key_fingerprint(y_sum)
+    }
+
+    /// Checks the share against the keygen commitments of every dealer.
+    pub fn verify(&self, vss_scheme_vec: &[VerifiableSS]) -> Result<(), BackupError> {
+        let index = self.party_id as usize;
+        let mut points = vss_scheme_vec
+            .iter()
+            .map(|vss| vss.get_point_commitment(index));
+        let head = points.next().ok_or(BackupError::InvalidShare)?;
+        let public_share = points.fold(head, |acc, p| acc + &p);
+        let g: GE = ECPoint::generator();
+        if &g * &self.x_i == public_share {
+            Ok(())
+        } else {
+            Err(BackupError::InvalidShare)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn local_key() -> LocalKey {
+        dealer_keys(1, 3).1.remove(2)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_mnemonic_roundtrip() {
+        let key = local_key();
+        let backup = ShareBackup::from_local_key(&key);
+        let mnemonic = backup.to_mnemonic();
+        assert_eq!(mnemonic.split(' ').count(), 27);
+        let restored = ShareBackup::from_mnemonic(&mnemonic.to_uppercase()).unwrap();
+        assert_eq!(restored, backup);
+        assert!(restored.matches(&key.y_sum));
+        assert_eq!(restored.verify(&key.vss_scheme_vec), Ok(()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_swapped_words() {
+        let key = local_key();
+        let mnemonic = ShareBackup::from_local_key(&key).to_mnemonic();
+
+        // a swapped word breaks the checksum, or at worst the share check
+        let mut words = mnemonic.split(' ').collect::<Vec<&str>>();
+        words.swap(4, 5);
+        if words[4] != words[5] {
+            match ShareBackup::from_mnemonic(&words.join(" ")) {
+                Err(_) => (),
+                Ok(b) => assert!(b.verify(&key.vss_scheme_vec).is_err()),
+            }
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_malformed_mnemonic() {
+        assert_eq!(
+            ShareBackup::from_mnemonic("abandon abandon"),
+            Err(BackupError::InvalidLength)
+        );
+        assert_eq!(
+            ShareBackup::from_mnemonic("notaword"),
+            Err(BackupError::InvalidWord)
+        );
+    }
+}
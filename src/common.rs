@@ -1,6 +1,9 @@
+#![cfg(feature = "gg18")]
 #![allow(dead_code)]
 
-use crate::gg_2018::party_i::Signature;
+use crate::metrics::metrics;
+use crate::session::{PeerLimiter, SessionError, SessionLimits};
+use crate::Error;
 use aes_gcm::aead::{Aead, NewAead};
 use aes_gcm::{Aes256Gcm, Nonce};
 use rand::{rngs::OsRng, RngCore};
@@ -8,13 +11,8 @@ use rand::{rngs::OsRng, RngCore};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen_futures::JsFuture;
 
-use crate::curv::{
-    arithmetic::num_bigint::BigInt,
-    arithmetic::traits::Converter,
-    elliptic::curves::secp256_k1::{Secp256k1Point as Point, Secp256k1Scalar as Scalar},
-};
-
-use reqwest::{Body, Client};
+use futures_util::{Stream, StreamExt};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 pub type Key = String;
@@ -22,24 +20,30 @@ pub type Key = String;
 #[allow(dead_code)]
 pub const AES_KEY_BYTES_LEN: usize = 32;
 
+const AES_NONCE_BYTES_LEN: usize = 12;
+
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct AEAD {
     pub ciphertext: Vec<u8>,
     pub tag: Vec<u8>,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct PartySignup {
     pub number: u16,
     pub uuid: String,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Index {
     pub key: Key,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Entry {
     pub key: Key,
     pub value: String,
@@ -49,6 +53,10 @@ pub struct Entry {
 pub struct Params {
     pub parties: String,
     pub threshold: String,
+    /// How many parties `/signupsign` numbers before starting a new
+    /// ceremony, `threshold + 1` if absent.
+    #[serde(default)]
+    pub online: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -56,7 +64,7 @@ pub fn aes_encrypt(key: &[u8], plaintext: &[u8]) -> AEAD {
     let aes_key = aes_gcm::Key::from_slice(key);
     let cipher = Aes256Gcm::new(aes_key);
 
-    let mut nonce = [0u8; 12];
+    let mut nonce = [0u8; AES_NONCE_BYTES_LEN];
     let mut rng = OsRng::new().unwrap();
     rng.fill_bytes(&mut nonce);
     let nonce = Nonce::from_slice(&nonce);
@@ -66,23 +74,26 @@ pub fn aes_encrypt(key: &[u8], plaintext: &[u8]) -> AEAD {
         .expect("encryption failure!");
 
     AEAD {
-        ciphertext: ciphertext,
+        ciphertext,
         tag: nonce.to_vec(),
     }
 }
 
+/// Fails if the nonce is not 12 bytes or the ciphertext does not
+/// authenticate under `key`.
 #[allow(dead_code)]
-pub fn aes_decrypt(key: &[u8], aead_pack: AEAD) -> Vec<u8> {
+pub fn aes_decrypt(key: &[u8], aead_pack: AEAD) -> Result<Vec<u8>, Error> {
+    if aead_pack.tag.len() != AES_NONCE_BYTES_LEN {
+        return Err(Error::InvalidCiphertext);
+    }
     let aes_key = aes_gcm::Key::from_slice(key);
     let nonce = Nonce::from_slice(&aead_pack.tag);
     let gcm = Aes256Gcm::new(aes_key);
 
-    let out = gcm.decrypt(nonce, aead_pack.ciphertext.as_slice());
-    out.unwrap()
+    gcm.decrypt(nonce, aead_pack.ciphertext.as_slice())
+        .map_err(|_| Error::InvalidCiphertext)
 }
 
-use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-
 #[cfg(target_arch = "wasm32")]
 pub async fn sleep(ms: u32) {
     let promise = js_sys::Promise::new(&mut |resolve, _| {
@@ -91,7 +102,7 @@ pub async fn sleep(ms: u32) {
             .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, ms as i32)
             .unwrap();
     });
-    wasm_bindgen_futures::JsFuture::from(promise).await;
+    JsFuture::from(promise).await;
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -100,6 +111,29 @@ pub async fn sleep(ms: u32) {
 }
 
 pub async fn postb<T>(client: &Client, addr: &str, path: &str, body: T) -> Option<String>
+where
+    T: serde::ser::Serialize,
+{
+    postb_capped(client, addr, path, body, None).await.ok()
+}
+
+/// Why the body of a relay answer was not read.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BodyError {
+    Network,
+    /// The body is longer than the cap it was read under.
+    TooLarge,
+}
+
+/// Like `postb`, but stops reading the answer as soon as it is longer than
+/// `cap` bytes, so that a relay or peer cannot make this party buffer more.
+pub async fn postb_capped<T>(
+    client: &Client,
+    addr: &str,
+    path: &str,
+    body: T,
+    cap: Option<usize>,
+) -> Result<String, BodyError>
 where
     T: serde::ser::Serialize,
 {
@@ -114,10 +148,50 @@ where
             .await;
 
         if let Ok(res) = res {
-            return Some(res.text().await.unwrap());
+            if matches!((cap, res.content_length()), (Some(cap), Some(len)) if len > cap as u64) {
+                return Err(BodyError::TooLarge);
+            }
+            let bytes = read_capped(res.bytes_stream(), cap).await?;
+            return String::from_utf8(bytes).map_err(|_| BodyError::Network);
+        }
+    }
+    Err(BodyError::Network)
+}
+
+// Collects `chunks`, failing once more than `cap` bytes have come in.
+async fn read_capped<S, B, E>(mut chunks: S, cap: Option<usize>) -> Result<Vec<u8>, BodyError>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let mut body = Vec::new();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|_| BodyError::Network)?;
+        if matches!(cap, Some(cap) if body.len() + chunk.as_ref().len() > cap) {
+            return Err(BodyError::TooLarge);
         }
+        body.extend_from_slice(chunk.as_ref());
     }
-    None
+    Ok(body)
+}
+
+// The longest relay answer that can carry a message within `limits`: json
+// escaping at most doubles an honest message, which is itself json, and the
+// entry's key and wrapping take well under a kilobyte.
+fn answer_cap(limits: &SessionLimits) -> Option<usize> {
+    limits
+        .max_message_bytes
+        .map(|max| max.saturating_mul(2).saturating_add(1024))
+}
+
+/// The relay key of `from`'s broadcast in `round`.
+pub fn broadcast_key(from: u16, round: &str, sender_uuid: &str) -> Key {
+    format!("{}-{}-{}", from, round, sender_uuid)
+}
+
+/// The relay key of `from`'s message to `to` in `round`.
+pub fn p2p_key(from: u16, to: u16, round: &str, sender_uuid: &str) -> Key {
+    format!("{}-{}-{}-{}", from, to, round, sender_uuid)
 }
 
 pub async fn broadcast(
@@ -128,11 +202,12 @@ pub async fn broadcast(
     data: String,
     sender_uuid: String,
 ) -> Result<(), ()> {
-    let key = format!("{}-{}-{}", party_num, round, sender_uuid);
+    let key = broadcast_key(party_num, round, &sender_uuid);
+    metrics().message_sent(round, data.len());
     let entry = Entry { key, value: data };
 
-    let res_body = postb(client, addr, "set", entry).await.unwrap();
-    serde_json::from_str(&res_body).unwrap()
+    let res_body = postb(client, addr, "set", entry).await.ok_or(())?;
+    serde_json::from_str::<Result<(), ()>>(&res_body).map_err(|_| ())?
 }
 
 pub async fn sendp2p(
@@ -144,12 +219,145 @@ pub async fn sendp2p(
     data: String,
     sender_uuid: String,
 ) -> Result<(), ()> {
-    let key = format!("{}-{}-{}-{}", party_from, party_to, round, sender_uuid);
+    let key = p2p_key(party_from, party_to, round, &sender_uuid);
+    metrics().message_sent(round, data.len());
 
     let entry = Entry { key, value: data };
 
-    let res_body = postb(client, addr, "set", entry).await.unwrap();
-    serde_json::from_str(&res_body).unwrap()
+    let res_body = postb(client, addr, "set", entry).await.ok_or(())?;
+    serde_json::from_str::<Result<(), ()>>(&res_body).map_err(|_| ())?
+}
+
+/// The parties that had not delivered their message for `round` when its
+/// deadline passed.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RoundTimeout {
+    pub round: String,
+    pub missing: Vec<u16>,
+}
+
+/// Why collecting a round's messages stopped.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RoundError {
+    Timeout(RoundTimeout),
+    /// A party's message broke the `SessionLimits`.
+    Rejected(SessionError),
+    /// The relay could not be reached, or did not answer with an entry,
+    /// while collecting the named round.
+    Network(String),
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now_ms() -> f64 {
+    js_sys::Date::now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now_ms() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as f64
+}
+
+// Polls every party's key until all have answered, in party order. Parties are
+// polled round-robin so that a deadline reports all of the absent ones, not
+// just the first. An answer over the message limits counted by `limiter`
+// fails the round.
+async fn poll_for_keys(
+    client: &Client,
+    addr: &str,
+    round: &str,
+    keys: Vec<(u16, Index)>,
+    delay: u32,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+) -> Result<Vec<String>, RoundError> {
+    let parties = keys.iter().map(|(i, _)| *i).collect::<Vec<u16>>();
+    let mut answers: Vec<Option<String>> = vec![None; keys.len()];
+    poll_for_keys_each(
+        client,
+        addr,
+        round,
+        keys,
+        delay,
+        timeout_ms,
+        limiter,
+        |i, answer| {
+            let slot = parties.iter().position(|j| *j == i).unwrap();
+            answers[slot] = Some(answer);
+            Ok::<(), RoundError>(())
+        },
+    )
+    .await?;
+    Ok(answers.into_iter().map(|a| a.unwrap()).collect())
+}
+
+// `poll_for_keys`, handing each answer to `on_message` as soon as it is read
+// rather than holding the round, so that only one answer is in memory at a
+// time. Answers come in arrival order; an error from `on_message` ends the
+// round.
+#[allow(clippy::too_many_arguments)]
+async fn poll_for_keys_each<E: From<RoundError>>(
+    client: &Client,
+    addr: &str,
+    round: &str,
+    keys: Vec<(u16, Index)>,
+    delay: u32,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+    mut on_message: impl FnMut(u16, String) -> Result<(), E>,
+) -> Result<(), E> {
+    let deadline = timeout_ms.map(|t| now_ms() + t as f64);
+    let mut done = vec![false; keys.len()];
+    let mut bytes = 0;
+    let cap = answer_cap(limiter.limits());
+    loop {
+        // add delay to allow the server to process request:
+        sleep(delay).await;
+        for ((i, index), answered) in keys.iter().zip(done.iter_mut()) {
+            if *answered {
+                continue;
+            }
+            let network = || RoundError::Network(round.to_string());
+            let res_body = postb_capped(client, addr, "get", index.clone(), cap)
+                .await
+                .map_err(|e| match e {
+                    BodyError::TooLarge => {
+                        RoundError::Rejected(SessionError::MessageTooLarge { party: *i })
+                    }
+                    BodyError::Network => network(),
+                })?;
+            let answer: Result<Entry, ()> =
+                serde_json::from_str(&res_body).map_err(|_| network())?;
+            if let Ok(answer) = answer {
+                limiter
+                    .admit(*i, answer.value.len(), now_ms())
+                    .map_err(RoundError::Rejected)?;
+                metrics().message_received(round, *i, answer.value.len());
+                bytes += answer.value.len();
+                *answered = true;
+                on_message(*i, answer.value)?;
+            }
+        }
+        if done.iter().all(|d| *d) {
+            metrics().messages_received(round, done.len(), bytes);
+            return Ok(());
+        }
+        if deadline.is_some_and(|d| now_ms() >= d) {
+            let missing = keys
+                .iter()
+                .zip(done.iter())
+                .filter(|(_, d)| !**d)
+                .map(|((i, _), _)| *i)
+                .collect();
+            return Err(RoundError::Timeout(RoundTimeout {
+                round: round.to_string(),
+                missing,
+            })
+            .into());
+        }
+    }
 }
 
 pub async fn poll_for_broadcasts(
@@ -161,25 +369,44 @@ pub async fn poll_for_broadcasts(
     sender_uuid: String,
     delay: u32,
 ) -> Vec<String> {
-    let mut ans_vec = Vec::new();
-    for i in 1..=n {
-        if i != party_num {
-            let key = format!("{}-{}-{}", i, round, sender_uuid);
-            let index = Index { key };
-            loop {
-                sleep(delay).await;
-                // add delay to allow the server to process request:
-                let res_body = postb(client, addr, "get", index.clone()).await.unwrap();
-                let answer: Result<Entry, ()> = serde_json::from_str(&res_body).unwrap();
-                if let Ok(answer) = answer {
-                    ans_vec.push(answer.value);
-                    println!("[{:?}] party {:?} => party {:?}", round, i, party_num);
-                    break;
-                }
-            }
-        }
-    }
-    ans_vec
+    poll_for_broadcasts_until(
+        client,
+        addr,
+        party_num,
+        n,
+        round,
+        sender_uuid,
+        delay,
+        None,
+        &mut PeerLimiter::default(),
+    )
+    .await
+    .unwrap()
+}
+
+/// Like `poll_for_broadcasts`, but gives up `timeout_ms` after it started
+/// and reports which parties have not broadcast. Messages are checked
+/// against the limits of `limiter`, which counts them.
+#[allow(clippy::too_many_arguments)]
+pub async fn poll_for_broadcasts_until(
+    client: &Client,
+    addr: &str,
+    party_num: u16,
+    n: u16,
+    round: &str,
+    sender_uuid: String,
+    delay: u32,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+) -> Result<Vec<String>, RoundError> {
+    let keys = (1..=n)
+        .filter(|i| *i != party_num)
+        .map(|i| {
+            let key = broadcast_key(i, round, &sender_uuid);
+            (i, Index { key })
+        })
+        .collect();
+    poll_for_keys(client, addr, round, keys, delay, timeout_ms, limiter).await
 }
 
 pub async fn poll_for_p2p(
@@ -191,25 +418,117 @@ pub async fn poll_for_p2p(
     round: &str,
     sender_uuid: String,
 ) -> Vec<String> {
-    let mut ans_vec = Vec::new();
-    for i in 1..=n {
-        if i != party_num {
-            let key = format!("{}-{}-{}-{}", i, party_num, round, sender_uuid);
-            let index = Index { key };
-            loop {
-                // add delay to allow the server to process request:
-                sleep(delay).await;
-                let res_body = postb(client, addr, "get", index.clone()).await.unwrap();
-                let answer: Result<Entry, ()> = serde_json::from_str(&res_body).unwrap();
-                if let Ok(answer) = answer {
-                    ans_vec.push(answer.value);
-                    println!("[{:?}] party {:?} => party {:?}", round, i, party_num);
-                    break;
-                }
-            }
-        }
+    poll_for_p2p_until(
+        client,
+        addr,
+        party_num,
+        n,
+        delay,
+        round,
+        sender_uuid,
+        None,
+        &mut PeerLimiter::default(),
+    )
+    .await
+    .unwrap()
+}
+
+/// Like `poll_for_p2p`, but gives up `timeout_ms` after it started and
+/// reports which parties have not sent their message. Messages are checked
+/// against the limits of `limiter`, which counts them.
+#[allow(clippy::too_many_arguments)]
+pub async fn poll_for_p2p_until(
+    client: &Client,
+    addr: &str,
+    party_num: u16,
+    n: u16,
+    delay: u32,
+    round: &str,
+    sender_uuid: String,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+) -> Result<Vec<String>, RoundError> {
+    let keys = (1..=n)
+        .filter(|i| *i != party_num)
+        .map(|i| {
+            let key = p2p_key(i, party_num, round, &sender_uuid);
+            (i, Index { key })
+        })
+        .collect();
+    poll_for_keys(client, addr, round, keys, delay, timeout_ms, limiter).await
+}
+
+/// Like `poll_for_p2p_until`, but hands each message to `on_message`, with
+/// its sender, as it arrives instead of returning them all at the end.
+#[allow(clippy::too_many_arguments)]
+pub async fn poll_for_p2p_each<E: From<RoundError>>(
+    client: &Client,
+    addr: &str,
+    party_num: u16,
+    n: u16,
+    delay: u32,
+    round: &str,
+    sender_uuid: String,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+    on_message: impl FnMut(u16, String) -> Result<(), E>,
+) -> Result<(), E> {
+    let keys = (1..=n)
+        .filter(|i| *i != party_num)
+        .map(|i| {
+            let key = p2p_key(i, party_num, round, &sender_uuid);
+            (i, Index { key })
+        })
+        .collect();
+    poll_for_keys_each(
+        client, addr, round, keys, delay, timeout_ms, limiter, on_message,
+    )
+    .await
+}
+
+/// Like `poll_for_broadcasts_until`, but at the deadline returns what has
+/// arrived instead of failing, with `None` for each party that has not
+/// broadcast. Without a timeout it waits for every party.
+#[allow(clippy::too_many_arguments)]
+pub async fn poll_for_broadcasts_partial(
+    client: &Client,
+    addr: &str,
+    party_num: u16,
+    n: u16,
+    round: &str,
+    sender_uuid: String,
+    delay: u32,
+    timeout_ms: Option<u32>,
+    limiter: &mut PeerLimiter,
+) -> Result<Vec<Option<String>>, RoundError> {
+    let keys: Vec<(u16, Index)> = (1..=n)
+        .filter(|i| *i != party_num)
+        .map(|i| {
+            let key = broadcast_key(i, round, &sender_uuid);
+            (i, Index { key })
+        })
+        .collect();
+    let parties = keys.iter().map(|(i, _)| *i).collect::<Vec<u16>>();
+    let mut answers: Vec<Option<String>> = vec![None; keys.len()];
+    let polled = poll_for_keys_each(
+        client,
+        addr,
+        round,
+        keys,
+        delay,
+        timeout_ms,
+        limiter,
+        |i, answer| {
+            let slot = parties.iter().position(|j| *j == i).unwrap();
+            answers[slot] = Some(answer);
+            Ok::<(), RoundError>(())
+        },
+    )
+    .await;
+    match polled {
+        Ok(()) | Err(RoundError::Timeout(_)) => Ok(answers),
+        Err(e) => Err(e),
     }
-    ans_vec
 }
 
 /*
@@ -261,3 +580,56 @@ pub fn public_key_address(public_key: &PublicKey) -> web3::types::Address {
     web3::types::Address::from_slice(&hash[12..])
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_aes_decrypt_rejects_malformed_packs() {
+        let key = [7u8; AES_KEY_BYTES_LEN];
+        let pack = aes_encrypt(&key, b"share");
+        assert_eq!(aes_decrypt(&key, pack.clone()).unwrap(), b"share".to_vec());
+
+        let mut short_nonce = pack.clone();
+        short_nonce.tag.pop();
+        assert_eq!(
+            aes_decrypt(&key, short_nonce),
+            Err(Error::InvalidCiphertext)
+        );
+
+        let mut tampered = pack;
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(aes_decrypt(&key, tampered), Err(Error::InvalidCiphertext));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_read_capped_stops_at_the_cap() {
+        use futures_util::FutureExt;
+
+        let chunks = || futures_util::stream::iter(vec![Ok::<_, ()>(vec![1u8; 60]); 3]);
+        let read = |cap| read_capped(chunks(), cap).now_or_never().unwrap();
+        assert_eq!(read(None).unwrap().len(), 180);
+        assert_eq!(read(Some(180)).unwrap().len(), 180);
+        assert_eq!(read(Some(179)), Err(BodyError::TooLarge));
+
+        // the stream is not read past the chunk over the cap
+        let mut pulled = 0;
+        let counted = futures_util::stream::iter(vec![Ok::<_, ()>(vec![1u8; 60]); 3])
+            .inspect(|_| pulled += 1);
+        let capped = read_capped(counted, Some(100)).now_or_never().unwrap();
+        assert_eq!(capped, Err(BodyError::TooLarge));
+        assert_eq!(pulled, 2);
+
+        let limits = SessionLimits {
+            max_message_bytes: Some(100),
+            ..SessionLimits::default()
+        };
+        assert_eq!(answer_cap(&limits), Some(1224));
+        assert_eq!(answer_cap(&SessionLimits::default()), None);
+    }
+}
@@ -0,0 +1,366 @@
+//! Relay-side coordination of a keygen or signing ceremony.
+//!
+//! The relay in `examples/gg18_sm_manager.rs` stores whatever it is sent.
+//! `Coordinator` is the bookkeeping a production relay adds on top of it,
+//! with no I/O of its own so that it runs natively or in wasm: it numbers
+//! parties as they join, holds the ceremony at a barrier until every party
+//! has joined and declared itself ready, checks each `Entry` against the
+//! round in progress, says which parties a message fans out to, and names
+//! the stragglers when a round overruns its deadline.
+//!
+//! Message keys are those of `common::broadcast` and `common::sendp2p`:
+//! `{from}-round{r}-{uuid}` and `{from}-{to}-round{r}-{uuid}`.
+#![cfg(feature = "gg18")]
+
+use std::collections::HashSet;
+
+use crate::common::{Entry, PartySignup};
+use crate::session::Ceremony;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CoordinatorError {
+    /// Every party number is taken, or the barrier already opened.
+    CeremonyFull,
+    /// A party number that has not joined.
+    UnknownParty(u16),
+    /// A key that does not name a message of this ceremony.
+    InvalidKey,
+    /// A message for another round than the one in progress.
+    WrongRound,
+    /// A second message under a key already stored.
+    Duplicate,
+    /// The ceremony was aborted, blaming these parties.
+    Aborted(Vec<u16>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Phase {
+    /// Waiting for the parties to join and declare themselves ready.
+    Enrolling,
+    Running {
+        round: u8,
+    },
+    Finished,
+    Aborted {
+        blamed: Vec<u16>,
+    },
+}
+
+/// Where an accepted message goes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Delivery {
+    pub from: u16,
+    pub round: u8,
+    /// The parties that poll for it.
+    pub to: Vec<u16>,
+    /// Whether it was the last message of its round.
+    pub round_complete: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Coordinator {
+    ceremony: Ceremony,
+    uuid: String,
+    parties: u16,
+    joined: u16,
+    // by party number, counted from 1
+    ready: Vec<bool>,
+    phase: Phase,
+    // the keys stored in the round in progress, and how many each party sent
+    received: HashSet<String>,
+    sent: Vec<u16>,
+    phase_started_ms: f64,
+    round_timeout_ms: Option<u32>,
+}
+
+impl Coordinator {
+    /// A ceremony among `parties`: n for a keygen, t + 1 for signing.
+    pub fn new(ceremony: Ceremony, uuid: &str, parties: u16, now_ms: f64) -> Coordinator {
+        Coordinator {
+            ceremony,
+            uuid: uuid.to_string(),
+            parties,
+            joined: 0,
+            ready: vec![false; usize::from(parties)],
+            phase: Phase::Enrolling,
+            received: HashSet::new(),
+            sent: vec![0; usize::from(parties)],
+            phase_started_ms: now_ms,
+            round_timeout_ms: None,
+        }
+    }
+
+    /// How long enrolment, and then each round, may take before
+    /// `check_deadline` aborts. `None`, the default, waits forever.
+    pub fn set_round_timeout(&mut self, timeout_ms: Option<u32>) {
+        self.round_timeout_ms = timeout_ms;
+    }
+
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// Hands out the next party number, as `/signupkeygen` and
+    /// `/signupsign` do.
+    pub fn join(&mut self) -> Result<PartySignup, CoordinatorError> {
+        self.check_not_aborted()?;
+        if self.phase != Phase::Enrolling || self.joined == self.parties {
+            return Err(CoordinatorError::CeremonyFull);
+        }
+        self.joined += 1;
+        Ok(PartySignup {
+            number: self.joined,
+            uuid: self.uuid.clone(),
+        })
+    }
+
+    /// Marks `party` ready. Returns true once every party is, when the
+    /// first round opens.
+    pub fn ready(&mut self, party: u16, now_ms: f64) -> Result<bool, CoordinatorError> {
+        self.check_not_aborted()?;
+        self.check_joined(party)?;
+        if self.phase != Phase::Enrolling {
+            return Err(CoordinatorError::WrongRound);
+        }
+        self.ready[usize::from(party - 1)] = true;
+        let all_ready = self.joined == self.parties && self.ready.iter().all(|r| *r);
+        if all_ready {
+            self.start(self.ceremony.first_round(), now_ms);
+        }
+        Ok(all_ready)
+    }
+
+    /// Checks a message the relay is asked to store. The relay stores it
+    /// only if this succeeds.
+    pub fn accept(&mut self, entry: &Entry, now_ms: f64) -> Result<Delivery, CoordinatorError> {
+        self.check_not_aborted()?;
+        let (from, to, round) = self.parse_key(&entry.key)?;
+        self.check_joined(from)?;
+        if let Some(to) = to {
+            self.check_joined(to)?;
+            if to == from {
+                return Err(CoordinatorError::InvalidKey);
+            }
+        }
+        if self.phase != (Phase::Running { round }) {
+            return Err(CoordinatorError::WrongRound);
+        }
+        if to.is_some() != self.ceremony.is_p2p_round(round) {
+            return Err(CoordinatorError::InvalidKey);
+        }
+        if !self.received.insert(entry.key.clone()) {
+            return Err(CoordinatorError::Duplicate);
+        }
+        self.sent[usize::from(from - 1)] += 1;
+
+        let to = match to {
+            Some(to) => vec![to],
+            None => (1..=self.parties).filter(|j| *j != from).collect(),
+        };
+        let round_complete = self.missing().is_empty();
+        if round_complete {
+            if round == self.ceremony.last_round() {
+                self.phase = Phase::Finished;
+            } else {
+                self.start(round + 1, now_ms);
+            }
+        }
+        Ok(Delivery {
+            from,
+            round,
+            to,
+            round_complete,
+        })
+    }
+
+    /// The parties holding the ceremony up: while enrolling, those not yet
+    /// joined or ready; while running, those yet to send their messages
+    /// of the round.
+    pub fn missing(&self) -> Vec<u16> {
+        match self.phase {
+            Phase::Enrolling => (1..=self.parties)
+                .filter(|j| *j > self.joined || !self.ready[usize::from(j - 1)])
+                .collect(),
+            Phase::Running { round } => {
+                let expected = if self.ceremony.is_p2p_round(round) {
+                    self.parties - 1
+                } else {
+                    1
+                };
+                (1..=self.parties)
+                    .filter(|j| self.sent[usize::from(j - 1)] < expected)
+                    .collect()
+            }
+            Phase::Finished | Phase::Aborted { .. } => Vec::new(),
+        }
+    }
+
+    /// Aborts the ceremony, blaming the stragglers, if enrolment or the
+    /// round in progress has overrun the round timeout.
+    pub fn check_deadline(&mut self, now_ms: f64) -> Result<(), CoordinatorError> {
+        self.check_not_aborted()?;
+        let overrun = match self.round_timeout_ms {
+            Some(timeout) => now_ms - self.phase_started_ms >= f64::from(timeout),
+            None => false,
+        };
+        if overrun && self.phase != Phase::Finished {
+            let blamed = self.missing();
+            self.abort(blamed.clone());
+            return Err(CoordinatorError::Aborted(blamed));
+        }
+        Ok(())
+    }
+
+    /// Aborts the ceremony, e.g. on a party's report of an invalid message.
+    pub fn abort(&mut self, blamed: Vec<u16>) {
+        self.phase = Phase::Aborted { blamed };
+    }
+
+    fn start(&mut self, round: u8, now_ms: f64) {
+        self.phase = Phase::Running { round };
+        self.received.clear();
+        self.sent = vec![0; usize::from(self.parties)];
+        self.phase_started_ms = now_ms;
+    }
+
+    fn check_not_aborted(&self) -> Result<(), CoordinatorError> {
+        match &self.phase {
+            Phase::Aborted { blamed } => Err(CoordinatorError::Aborted(blamed.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_joined(&self, party: u16) -> Result<(), CoordinatorError> {
+        if (1..=self.joined).contains(&party) {
+            Ok(())
+        } else {
+            Err(CoordinatorError::UnknownParty(party))
+        }
+    }
+
+    // (from, to for p2p, round) from `{from}[-{to}]-round{r}-{uuid}`
+    fn parse_key(&self, key: &str) -> Result<(u16, Option<u16>, u8), CoordinatorError> {
+        let head = key
+            .strip_suffix(&self.uuid)
+            .and_then(|k| k.strip_suffix('-'))
+            .ok_or(CoordinatorError::InvalidKey)?;
+        let fields = head.split('-').collect::<Vec<&str>>();
+        let number = |s: &str| s.parse::<u16>().map_err(|_| CoordinatorError::InvalidKey);
+        let (from, to, round) = match fields.as_slice() {
+            [from, round] => (number(from)?, None, *round),
+            [from, to, round] => (number(from)?, Some(number(to)?), *round),
+            _ => return Err(CoordinatorError::InvalidKey),
+        };
+        let round = round
+            .strip_prefix("round")
+            .and_then(|r| r.parse::<u8>().ok())
+            .ok_or(CoordinatorError::InvalidKey)?;
+        Ok((from, to, round))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn entry(key: &str) -> Entry {
+        Entry {
+            key: key.to_string(),
+            value: "{}".to_string(),
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_keygen_ceremony() {
+        let uuid = "7b0c-4e1f";
+        let mut coordinator = Coordinator::new(Ceremony::Keygen, uuid, 3, 0.0);
+        coordinator.set_round_timeout(Some(1000));
+        for number in 1..=3 {
+            assert_eq!(coordinator.join().unwrap().number, number);
+        }
+        assert_eq!(coordinator.join(), Err(CoordinatorError::CeremonyFull));
+
+        // nothing passes the barrier before everyone is ready
+        assert_eq!(coordinator.ready(1, 0.0), Ok(false));
+        assert_eq!(
+            coordinator.accept(&entry("1-round1-7b0c-4e1f"), 0.0),
+            Err(CoordinatorError::WrongRound)
+        );
+        assert_eq!(coordinator.missing(), vec![2, 3]);
+        assert_eq!(coordinator.ready(2, 0.0), Ok(false));
+        assert_eq!(coordinator.ready(3, 10.0), Ok(true));
+        assert_eq!(*coordinator.phase(), Phase::Running { round: 0 });
+
+        // the version handshake
+        for from in 1..=3 {
+            coordinator
+                .accept(&entry(&format!("{}-round0-{}", from, uuid)), 15.0)
+                .unwrap();
+        }
+        assert_eq!(*coordinator.phase(), Phase::Running { round: 1 });
+
+        let delivery = coordinator
+            .accept(&entry("1-round1-7b0c-4e1f"), 20.0)
+            .unwrap();
+        assert_eq!(delivery.to, vec![2, 3]);
+        assert_eq!(
+            coordinator.accept(&entry("1-round1-7b0c-4e1f"), 20.0),
+            Err(CoordinatorError::Duplicate)
+        );
+        assert_eq!(
+            coordinator.accept(&entry("4-round1-7b0c-4e1f"), 20.0),
+            Err(CoordinatorError::UnknownParty(4))
+        );
+        assert_eq!(
+            coordinator.accept(&entry("2-round1-other"), 20.0),
+            Err(CoordinatorError::InvalidKey)
+        );
+        coordinator
+            .accept(&entry("2-round1-7b0c-4e1f"), 30.0)
+            .unwrap();
+        assert!(
+            coordinator
+                .accept(&entry("3-round1-7b0c-4e1f"), 40.0)
+                .unwrap()
+                .round_complete
+        );
+        assert_eq!(*coordinator.phase(), Phase::Running { round: 2 });
+
+        for from in 1..=3 {
+            coordinator
+                .accept(&entry(&format!("{}-round2-{}", from, uuid)), 50.0)
+                .unwrap();
+        }
+        // round 3 goes to each party separately
+        assert_eq!(
+            coordinator.accept(&entry("1-round3-7b0c-4e1f"), 60.0),
+            Err(CoordinatorError::InvalidKey)
+        );
+        let delivery = coordinator
+            .accept(&entry("1-3-round3-7b0c-4e1f"), 60.0)
+            .unwrap();
+        assert_eq!(delivery.to, vec![3]);
+        coordinator
+            .accept(&entry("1-2-round3-7b0c-4e1f"), 60.0)
+            .unwrap();
+        coordinator
+            .accept(&entry("2-1-round3-7b0c-4e1f"), 60.0)
+            .unwrap();
+        assert_eq!(coordinator.missing(), vec![2, 3]);
+
+        // party 3 stalls past the deadline of the round, which started at 50
+        assert_eq!(coordinator.check_deadline(1000.0), Ok(()));
+        assert_eq!(
+            coordinator.check_deadline(1050.0),
+            Err(CoordinatorError::Aborted(vec![2, 3]))
+        );
+        assert_eq!(
+            coordinator.accept(&entry("2-3-round3-7b0c-4e1f"), 1060.0),
+            Err(CoordinatorError::Aborted(vec![2, 3]))
+        );
+    }
+}
@@ -0,0 +1,213 @@
+/*
+    Wire encoding of BigInt fields.
+
+    The serde encoding of num-bigint is an array of little-endian base 2^32
+    digits, which every other language has to reassemble by hand. Protocol
+    messages use this module instead, with `#[serde(with = ...)]`:
+
+    - a value is written as the lowercase hex of its big-endian bytes, with
+      no leading zero bytes, so always an even number of digits; zero is
+      "00";
+    - values are unsigned, there is no sign;
+    - on reading, leading zero bytes and uppercase digits are accepted, and
+      so is the digit array written by earlier versions.
+
+    Messages for a committee with a party that reads only digit arrays are
+    serialized inside `write_as(WireFormat::DigitArrays, ..)`, which the
+    version handshake settles on.
+*/
+
+use std::cell::Cell;
+use std::fmt;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+
+/// How `BigInt` fields are encoded in messages.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+#[derive(Default)]
+pub enum WireFormat {
+    /// Arrays of little-endian u32 digits, as num-bigint serializes them.
+    DigitArrays,
+    /// Big-endian hex, the default.
+    #[default]
+    Hex,
+}
+
+impl WireFormat {
+    /// In order of preference.
+    pub const ALL: [WireFormat; 2] = [WireFormat::Hex, WireFormat::DigitArrays];
+}
+
+thread_local! {
+    // what `serialize` writes, see `write_as`
+    static WRITES: Cell<WireFormat> = const { Cell::new(WireFormat::Hex) };
+}
+
+/// Runs `f` with the fields of this module serialized in `format`.
+pub fn write_as<R>(format: WireFormat, f: impl FnOnce() -> R) -> R {
+    // put back even if `f` panics
+    struct Restore(WireFormat);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            WRITES.with(|w| w.set(self.0));
+        }
+    }
+    let _restore = Restore(WRITES.with(|w| w.replace(format)));
+    f()
+}
+
+pub fn to_hex(value: &BigInt) -> String {
+    hex::encode(value.to_bytes_be())
+}
+
+pub fn from_hex(s: &str) -> Option<BigInt> {
+    if s.is_empty() {
+        return None;
+    }
+    hex::decode(s)
+        .ok()
+        .map(|bytes| BigInt::from_bytes_be(&bytes))
+}
+
+pub fn serialize<S: Serializer>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+    match WRITES.with(|w| w.get()) {
+        WireFormat::Hex => serializer.serialize_str(&to_hex(value)),
+        WireFormat::DigitArrays => value.serialize(serializer),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+    deserializer.deserialize_any(BigIntVisitor)
+}
+
+struct BigIntVisitor;
+
+impl<'de> Visitor<'de> for BigIntVisitor {
+    type Value = BigInt;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("big-endian hex digits")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<BigInt, E> {
+        from_hex(s).ok_or_else(|| E::custom("invalid hex integer"))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<BigInt, E> {
+        Ok(BigInt::from_bytes_be(bytes))
+    }
+
+    // the u32 digits, least significant first, of earlier versions
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<BigInt, A::Error> {
+        let mut digits = Vec::new();
+        while let Some(digit) = seq.next_element::<u32>()? {
+            digits.push(digit);
+        }
+        Ok(BigInt::new(digits))
+    }
+}
+
+struct Hex(BigInt);
+
+struct Written<'a>(&'a BigInt);
+
+impl Serialize for Written<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+impl Serialize for Hex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Hex, D::Error> {
+        deserialize(deserializer).map(Hex)
+    }
+}
+
+/// The same for `Vec<BigInt>` fields.
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(values: &[BigInt], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(values.len()))?;
+        for value in values {
+            seq.serialize_element(&Written(value))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<BigInt>, D::Error> {
+        let values = Vec::<Hex>::deserialize(deserializer)?;
+        Ok(values.into_iter().map(|value| value.0).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(with = "super")]
+        x: BigInt,
+        #[serde(with = "super::vec")]
+        xs: Vec<BigInt>,
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_encoding() {
+        let message = Message {
+            x: BigInt::from(0x1_0000_0000u64),
+            xs: vec![BigInt::from(0u32), BigInt::from(255u32)],
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"x":"0100000000","xs":["00","ff"]}"#);
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+
+        // padding, uppercase and the digit arrays of earlier versions
+        let lenient = r#"{"x":"000100000000","xs":[[],"00FF"]}"#;
+        assert_eq!(serde_json::from_str::<Message>(lenient).unwrap(), message);
+        let legacy = r#"{"x":[0,1],"xs":[[0],[255]]}"#;
+        assert_eq!(serde_json::from_str::<Message>(legacy).unwrap(), message);
+
+        for invalid in &[r#""""#, r#""abc""#, r#""-01""#, r#""0x01""#] {
+            let json = format!(r#"{{"x":{},"xs":[]}}"#, invalid);
+            assert!(serde_json::from_str::<Message>(&json).is_err());
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_write_as_digit_arrays() {
+        let message = Message {
+            x: BigInt::from(0x1_0000_0000u64),
+            xs: vec![BigInt::from(0u32), BigInt::from(255u32)],
+        };
+        let json = write_as(WireFormat::DigitArrays, || {
+            serde_json::to_string(&message).unwrap()
+        });
+        assert_eq!(json, r#"{"x":[0,1],"xs":[[],[255]]}"#);
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+        // hex again outside of it
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"x":"0100000000","xs":["00","ff"]}"#
+        );
+    }
+}
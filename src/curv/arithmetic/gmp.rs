@@ -0,0 +1,66 @@
+//! GMP-backed arithmetic for native builds, behind the `gmp` feature.
+//!
+//! `BigInt` stays the num-bigint type everywhere, so keys and messages are
+//! the same with or without the feature. Only modular exponentiation and
+//! primality testing, which dominate Paillier and prime generation, convert
+//! to GMP integers and back. wasm builds keep the pure Rust code.
+//!
+//! The conversions copy through byte buffers, num-bigint 0.2 exposing no
+//! limbs. That is linear in the size of the operands against the cubic
+//! cost of an exponentiation; the `paillier keypair 2048` benchmark, run
+//! with and without the feature, measures what is gained:
+//!
+//! ```text
+//! cargo bench --bench keygen -- paillier
+//! cargo bench --bench keygen --features gmp -- paillier
+//! ```
+
+#![cfg(all(feature = "gmp", not(target_arch = "wasm32")))]
+
+use rug::integer::{IsPrime, Order};
+use rug::Integer;
+
+use super::num_bigint::BigInt;
+
+fn to_gmp(n: &BigInt) -> Integer {
+    Integer::from_digits(&n.to_bytes_be(), Order::Msf)
+}
+
+fn from_gmp(n: &Integer) -> BigInt {
+    BigInt::from_bytes_be(&n.to_digits::<u8>(Order::Msf))
+}
+
+pub fn mod_pow(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    let result = to_gmp(base)
+        .pow_mod(&to_gmp(exponent), &to_gmp(modulus))
+        .expect("non-negative exponent");
+    from_gmp(&result)
+}
+
+/// GMP's Baillie-PSW test followed by `reps - 24` Miller-Rabin rounds, if
+/// positive.
+pub fn is_probably_prime(candidate: &BigInt, reps: u32) -> bool {
+    to_gmp(candidate).is_probably_prime(reps) != IsPrime::No
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::arithmetic::traits::Samplable;
+
+    #[test]
+    fn test_matches_num_bigint() {
+        let modulus = BigInt::sample(1024) | BigInt::from(1u32);
+        let base = BigInt::sample_below(&modulus);
+        let exponent = BigInt::sample(1024);
+        assert_eq!(
+            mod_pow(&base, &exponent, &modulus),
+            base.modpow(&exponent, &modulus)
+        );
+
+        // 2^127 - 1 is a Mersenne prime
+        let mersenne = (BigInt::from(1u32) << 127) - BigInt::from(1u32);
+        assert!(is_probably_prime(&mersenne, 5));
+        assert!(!is_probably_prime(&(&mersenne + BigInt::from(2u32)), 5));
+    }
+}
@@ -14,5 +14,7 @@
     @license GPL-3.0+ <https://github.com/KZen-networks/cryptography-utils/blob/master/LICENSE>
 */
 
+pub mod bigint_hex;
+pub mod gmp;
 pub mod num_bigint;
 pub mod traits;
@@ -34,7 +34,7 @@ impl NumberTests for BigUint {
         me.eq(&BigUint::zero())
     }
     fn is_even(me: &Self) -> bool {
-        (me % BigUint::from(2 as u32)).eq(&BigUint::zero())
+        (me % BigUint::from(2_u32)).eq(&BigUint::zero())
     }
     fn is_negative(me: &Self) -> bool {
         me < &BigUint::zero()
@@ -43,7 +43,7 @@ impl NumberTests for BigUint {
 
 impl Modulo for BigUint {
     fn mod_pow(base: &Self, exponent: &Self, modulus: &Self) -> Self {
-        base.modpow(&exponent, &modulus)
+        backend_mod_pow(base, exponent, modulus)
     }
 
     fn mod_mul(a: &Self, b: &Self, modulus: &Self) -> Self {
@@ -64,25 +64,31 @@ impl Modulo for BigUint {
 
     fn mod_inv(a: &Self, modulus: &Self) -> Self {
         let x = egcd(a, modulus).1; //[d,x,y]
-        let x_ubn = x
-            .mod_floor(&modulus.to_bigint().unwrap())
+        x.mod_floor(&modulus.to_bigint().unwrap())
             .to_biguint()
-            .unwrap();
-        x_ubn
+            .unwrap()
     }
 }
 
+#[cfg(all(feature = "gmp", not(target_arch = "wasm32")))]
+use super::gmp::mod_pow as backend_mod_pow;
+
+#[cfg(not(all(feature = "gmp", not(target_arch = "wasm32"))))]
+fn backend_mod_pow(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    base.modpow(exponent, modulus)
+}
+
 fn egcd(a: &BigUint, b: &BigUint) -> (BN, BN, BN) {
     if a.mod_floor(b) == BigUint::zero() {
-        return (
+        (
             b.clone().to_bigint().unwrap(),
             BigUint::zero().to_bigint().unwrap(),
             BigUint::one().to_bigint().unwrap(),
-        );
+        )
     } else {
         let (q, r) = a.div_rem(b);
         let (d, x, y) = egcd(b, &r);
-        return (d, y.clone(), x - y * (q.to_bigint().unwrap()));
+        (d, y.clone(), x - y * (q.to_bigint().unwrap()))
     }
 }
 impl ConvertFrom<BigUint> for usize {
@@ -153,17 +159,17 @@ pub fn from(bytes: &[u8]) -> BigInt {
 
 impl BitManipulation for BigUint {
     fn set_bit(&self, bit: usize, bit_val: bool) -> BigUint {
-        let one = BigInt::from(1 as u16);
+        let one = BigInt::from(1_u16);
         let one_shl = one.shl(bit);
-        if bit_val == false {
-            return self.bitand(&one_shl);
+        if !bit_val {
+            self.bitand(&one_shl)
         } else {
-            return self.bitor(&one_shl);
+            self.bitor(&one_shl)
         }
     }
 
-    fn test_bit(self: &Self, _bit: usize) -> bool {
-        return true; //stub
+    fn test_bit(&self, _bit: usize) -> bool {
+        true //stub
     }
 }
 
@@ -227,6 +233,30 @@ impl Converter for BigUint {
     fn from_hex(value: &str) -> BigUint {
         BigInt::from_str_radix(value, 16).expect("Error in serialization")
     }
+
+    fn to_bytes_be_padded(&self, len: usize) -> Option<Vec<u8>> {
+        // zero encodes as a single 0 byte; as padding it takes no room
+        let bytes = if self.is_zero() {
+            Vec::new()
+        } else {
+            self.to_bytes_be()
+        };
+        if bytes.len() > len {
+            return None;
+        }
+        let mut padded = vec![0u8; len - bytes.len()];
+        padded.extend_from_slice(&bytes);
+        Some(padded)
+    }
+
+    fn to_hex_padded(&self, len: usize) -> Option<String> {
+        self.to_bytes_be_padded(len).map(hex::encode)
+    }
+
+    fn try_from_hex(value: &str) -> Option<BigUint> {
+        let value = value.strip_prefix("0x").unwrap_or(value);
+        BigUint::from_str_radix(value, 16).ok()
+    }
 }
 
 /*
@@ -24,6 +24,17 @@ pub trait Converter {
 
     fn to_bytes(value: &BigUint) -> Vec<u8>;
     fn from_bytes(bytes: &[u8]) -> Self;
+
+    /// Big-endian bytes left-padded to `len`, or `None` if the number does
+    /// not fit, e.g. 32 for a scalar or coordinate in chain formats.
+    fn to_bytes_be_padded(&self, len: usize) -> Option<Vec<u8>>;
+    /// `to_bytes_be_padded` as `2 * len` hex digits.
+    fn to_hex_padded(&self, len: usize) -> Option<String>;
+    /// Like `from_hex`, but returns `None` on invalid input instead of
+    /// panicking. An optional `0x` prefix is accepted.
+    fn try_from_hex(n: &str) -> Option<Self>
+    where
+        Self: Sized;
 }
 
 pub trait Modulo {
@@ -55,7 +66,7 @@ where
 
 pub trait BitManipulation {
     fn set_bit(&self, bit: usize, bit_val: bool) -> Self;
-    fn test_bit(self: &Self, bit: usize) -> bool;
+    fn test_bit(&self, bit: usize) -> bool;
 }
 
 pub trait ConvertFrom<T> {
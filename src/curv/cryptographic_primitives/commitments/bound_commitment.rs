@@ -0,0 +1,132 @@
+/*
+    Hash commitments bound to the session, round and committer.
+
+    c = H(session id, round, committer, m, r), with every input framed by the
+    transcript. A party that copies another party's commitment, or replays
+    one from an earlier round or session, can not open it: the opening is
+    checked against the context of the party that broadcast it, so the copy
+    only opens for the party it was copied from.
+*/
+
+use merlin::Transcript;
+
+use super::SECURITY_BITS;
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::arithmetic::traits::Samplable;
+use crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode;
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum CommitmentError {
+    InvalidOpening,
+}
+
+/// Where a commitment is made: the ceremony, its round and the committing
+/// party.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CommitmentContext {
+    pub session_id: String,
+    pub round: u16,
+    pub party: u16,
+}
+
+impl CommitmentContext {
+    pub fn new(session_id: &str, round: u16, party: u16) -> CommitmentContext {
+        CommitmentContext {
+            session_id: session_id.to_string(),
+            round,
+            party,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Commit {
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
+    pub com: BigInt,
+}
+
+/// The blinding factor that opens a `Commit`. The committed message is sent
+/// along with it.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Open {
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
+    pub blind_factor: BigInt,
+}
+
+impl Commit {
+    pub fn new<T: CanonicalEncode + ?Sized>(
+        context: &CommitmentContext,
+        message: &T,
+    ) -> (Commit, Open) {
+        let blind_factor = BigInt::sample(SECURITY_BITS);
+        let commit = Commit::commit_with_blinding(context, message, &blind_factor);
+        (commit, Open { blind_factor })
+    }
+
+    pub fn commit_with_blinding<T: CanonicalEncode + ?Sized>(
+        context: &CommitmentContext,
+        message: &T,
+        blind_factor: &BigInt,
+    ) -> Commit {
+        let mut transcript = Transcript::new(b"tss-wasm bound commitment");
+        transcript.append_canonical(b"session id", &context.session_id);
+        transcript.append_canonical(b"round", &context.round);
+        transcript.append_canonical(b"party", &context.party);
+        transcript.append_canonical(b"message", message);
+        transcript.append_canonical(b"blinding factor", blind_factor);
+        Commit {
+            com: transcript.challenge_bigint(b"commitment"),
+        }
+    }
+}
+
+impl Open {
+    /// Checks that `self` opens `commit` to `message`, made by the party and
+    /// in the round and session of `context`.
+    pub fn verify<T: CanonicalEncode + ?Sized>(
+        &self,
+        commit: &Commit,
+        context: &CommitmentContext,
+        message: &T,
+    ) -> Result<(), CommitmentError> {
+        if Commit::commit_with_blinding(context, message, &self.blind_factor) == *commit {
+            Ok(())
+        } else {
+            Err(CommitmentError::InvalidOpening)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_commitment_bound_to_context() {
+        let context = CommitmentContext::new("session", 1, 2);
+        let message = BigInt::from(42u32);
+        let (commit, open) = Commit::new(&context, &message);
+        assert_eq!(open.verify(&commit, &context, &message), Ok(()));
+        assert_eq!(
+            open.verify(&commit, &context, &BigInt::from(43u32)),
+            Err(CommitmentError::InvalidOpening)
+        );
+
+        // a copy broadcast by another party, or replayed, does not open
+        for other in [
+            CommitmentContext::new("session", 1, 3),
+            CommitmentContext::new("session", 2, 2),
+            CommitmentContext::new("other session", 1, 2),
+        ] {
+            assert_eq!(
+                open.verify(&commit, &other, &message),
+                Err(CommitmentError::InvalidOpening)
+            );
+        }
+    }
+}
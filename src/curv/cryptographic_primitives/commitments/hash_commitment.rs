@@ -6,7 +6,7 @@
 */
 
 //TODO: (open issue) use this struct to represent the commitment HashCommitment{comm: BigInt, r: BigInt, m: BigInt}
-/// calculate commitment c = H(m,r) using SHA3 CRHF.
+/// calculate commitment c = H(m,r) from a merlin transcript.
 /// r is 256bit blinding factor, m is the commited value
 pub struct HashCommitment;
 
@@ -14,11 +14,9 @@ use crate::curv::arithmetic::num_bigint::BigInt;
 
 use super::traits::Commitment;
 use super::SECURITY_BITS;
-use crate::curv::arithmetic::num_bigint::from;
-use crate::curv::arithmetic::traits::Converter;
 use crate::curv::arithmetic::traits::Samplable;
-use cryptoxide::digest::Digest;
-use cryptoxide::sha3::Sha3;
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use merlin::Transcript;
 
 //TODO:  using the function with BigInt's as input instead of string's makes it impossible to commit to empty message or use empty randomness
 impl Commitment<BigInt> for HashCommitment {
@@ -26,15 +24,10 @@ impl Commitment<BigInt> for HashCommitment {
         message: &BigInt,
         blinding_factor: &BigInt,
     ) -> BigInt {
-        let mut digest = Sha3::sha3_256();
-        let bytes_message: Vec<u8> = BigInt::to_vec(&message);
-        digest.input(&bytes_message);
-        let bytes_blinding_factor: Vec<u8> = BigInt::to_vec(&blinding_factor);
-        digest.input(&bytes_blinding_factor);
-
-        let mut result = [0; 32];
-        digest.result(&mut result);
-        from(result.as_ref())
+        let mut transcript = Transcript::new(b"tss-wasm hash commitment");
+        transcript.append_canonical(b"message", message);
+        transcript.append_canonical(b"blinding factor", blinding_factor);
+        transcript.challenge_bigint(b"commitment")
     }
 
     fn create_commitment(message: &BigInt) -> (BigInt, BigInt) {
@@ -52,12 +45,11 @@ mod tests {
     use super::Commitment;
     use super::HashCommitment;
     use super::SECURITY_BITS;
-    use crate::curv::arithmetic::num_bigint::from;
     use crate::curv::arithmetic::num_bigint::BigInt;
     use crate::curv::arithmetic::traits::Converter;
     use crate::curv::arithmetic::traits::Samplable;
-    use cryptoxide::digest::Digest;
-    use cryptoxide::sha3::Sha3;
+    use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+    use merlin::Transcript;
     use num_traits::{One, Zero};
 
     #[cfg(target_arch = "wasm32")]
@@ -74,10 +66,10 @@ mod tests {
             let message = BigInt::sample(SECURITY_BITS);
             let (commitment, blind_factor) = HashCommitment::create_commitment(&message);
             if commitment.to_str_radix(2).len() == hex_len {
-                ctr_commit_len = ctr_commit_len + 1;
+                ctr_commit_len += 1;
             }
             if blind_factor.to_str_radix(2).len() == hex_len {
-                ctr_blind_len = ctr_blind_len + 1;
+                ctr_blind_len += 1;
             }
         }
         //test commitment length  - works because SHA256 output length the same as sec_bits
@@ -112,19 +104,26 @@ mod tests {
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_hashing_create_commitment_with_user_defined_randomness() {
-        let mut digest = Sha3::sha3_256();
         let message = BigInt::one();
         let commitment = HashCommitment::create_commitment_with_user_defined_randomness(
             &message,
             &BigInt::zero(),
         );
-        let message2: Vec<u8> = BigInt::to_vec(&message);
-        digest.input(&message2);
-        let bytes_blinding_factor: Vec<u8> = (&BigInt::zero()).to_bytes_be();
-        digest.input(&bytes_blinding_factor);
-        let mut result = [0; 32];
-        digest.result(&mut result);
-        let hash_result = from(result.as_ref());
+        let mut transcript = Transcript::new(b"tss-wasm hash commitment");
+        transcript.append_canonical(b"message", &message);
+        transcript.append_canonical(b"blinding factor", &BigInt::zero());
+        let hash_result = transcript.challenge_bigint(b"commitment");
         assert_eq!(&commitment, &hash_result);
+
+        // 0x01 || 0x0101 and 0x0101 || 0x01 concatenate to the same bytes
+        let left = HashCommitment::create_commitment_with_user_defined_randomness(
+            &BigInt::one(),
+            &BigInt::from(0x0101u32),
+        );
+        let right = HashCommitment::create_commitment_with_user_defined_randomness(
+            &BigInt::from(0x0101u32),
+            &BigInt::one(),
+        );
+        assert_ne!(left, right);
     }
 }
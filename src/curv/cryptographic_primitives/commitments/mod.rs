@@ -7,5 +7,6 @@
 
 const SECURITY_BITS: usize = 256;
 
+pub mod bound_commitment;
 pub mod hash_commitment;
 pub mod traits;
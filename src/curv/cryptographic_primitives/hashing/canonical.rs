@@ -0,0 +1,237 @@
+/*
+    Canonical byte encoding for values that enter a hash.
+
+    The encoding is injective and does not depend on serde: unsigned integers
+    are fixed-width big-endian (usize as u64), big integers and strings carry a
+    u32 length prefix, scalars are 32 bytes, points are 33 byte compressed,
+    sequences carry a u32 element count and options a one byte tag. Structs
+    encode their fields in declaration order, so any implementation that
+    follows the field lists below reproduces the same bytes.
+*/
+
+use digest::Digest;
+use sha2::Sha256;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::hashing::constants::SECRET_KEY_SIZE;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+pub trait CanonicalEncode {
+    fn encode_canonical(&self, out: &mut Vec<u8>);
+
+    fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_canonical(&mut out);
+        out
+    }
+}
+
+/// Implements `CanonicalEncode` for a struct by encoding the listed fields in order.
+#[macro_export]
+macro_rules! impl_canonical_encode {
+    ($t:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode for $t {
+            fn encode_canonical(&self, out: &mut Vec<u8>) {
+                $(
+                    $crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode::encode_canonical(
+                        &self.$field,
+                        out,
+                    );
+                )*
+            }
+        }
+    };
+}
+
+/// SHA-256 over a length-prefixed domain label followed by the canonical encoding of `value`.
+pub fn canonical_hash<T: CanonicalEncode + ?Sized>(label: &str, value: &T) -> BigInt {
+    let mut bytes = Vec::new();
+    label.encode_canonical(&mut bytes);
+    value.encode_canonical(&mut bytes);
+    let digest = Sha256::digest(&bytes);
+    BigInt::from_bytes_be(&digest[..])
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) {
+    assert!(len <= u32::MAX as usize);
+    out.extend_from_slice(&(len as u32).to_be_bytes());
+}
+
+impl CanonicalEncode for u8 {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+}
+
+impl CanonicalEncode for bool {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl CanonicalEncode for u16 {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl CanonicalEncode for u32 {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl CanonicalEncode for u64 {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+impl CanonicalEncode for usize {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode_canonical(out);
+    }
+}
+
+impl CanonicalEncode for str {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl CanonicalEncode for String {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.as_str().encode_canonical(out);
+    }
+}
+
+/// Minimal big-endian magnitude; zero encodes as an empty string.
+impl CanonicalEncode for BigInt {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        let bytes = if *self == BigInt::from(0_u16) {
+            Vec::new()
+        } else {
+            self.to_bytes_be()
+        };
+        encode_len(bytes.len(), out);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+impl CanonicalEncode for FE {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        let value = self.to_big_int();
+        let bytes = if value == BigInt::from(0_u16) {
+            Vec::new()
+        } else {
+            value.to_bytes_be()
+        };
+        out.extend(vec![0u8; SECRET_KEY_SIZE - bytes.len()]);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+impl CanonicalEncode for GE {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_bytes(true));
+    }
+}
+
+impl<T: CanonicalEncode + ?Sized> CanonicalEncode for &T {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        (**self).encode_canonical(out);
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for [T] {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        for item in self {
+            item.encode_canonical(out);
+        }
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Vec<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode_canonical(out);
+    }
+}
+
+impl<T: CanonicalEncode> CanonicalEncode for Option<T> {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0),
+            Some(value) => {
+                out.push(1);
+                value.encode_canonical(out);
+            }
+        }
+    }
+}
+
+impl<A: CanonicalEncode, B: CanonicalEncode> CanonicalEncode for (A, B) {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.0.encode_canonical(out);
+        self.1.encode_canonical(out);
+    }
+}
+
+impl<A: CanonicalEncode, B: CanonicalEncode, C: CanonicalEncode> CanonicalEncode for (A, B, C) {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.0.encode_canonical(out);
+        self.1.encode_canonical(out);
+        self.2.encode_canonical(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::arithmetic::traits::Converter;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_bigint_encoding_is_length_prefixed() {
+        let a = (BigInt::from_hex("0102"), BigInt::from_hex("03"));
+        let b = (BigInt::from_hex("01"), BigInt::from_hex("0203"));
+        assert_ne!(a.to_canonical_bytes(), b.to_canonical_bytes());
+        assert_eq!(
+            BigInt::from_hex("0102").to_canonical_bytes(),
+            vec![0, 0, 0, 2, 1, 2]
+        );
+        assert_eq!(BigInt::from(0_u16).to_canonical_bytes(), vec![0, 0, 0, 0]);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_curve_encoding_is_fixed_width() {
+        let one: FE = ECScalar::from(&BigInt::from(1_u16));
+        let scalar_bytes = one.to_canonical_bytes();
+        assert_eq!(scalar_bytes.len(), 32);
+        assert_eq!(scalar_bytes[31], 1);
+
+        let g = GE::generator();
+        assert_eq!(g.to_canonical_bytes(), g.to_bytes(true));
+        assert_eq!(vec![g.clone(), g].to_canonical_bytes().len(), 4 + 2 * 33);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_canonical_hash_is_domain_separated() {
+        let value = BigInt::from(7_u16);
+        assert_eq!(
+            canonical_hash("label", &value),
+            canonical_hash("label", &value)
+        );
+        assert_ne!(
+            canonical_hash("label", &value),
+            canonical_hash("other", &value)
+        );
+    }
+}
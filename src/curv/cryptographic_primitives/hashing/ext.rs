@@ -1,10 +1,6 @@
 use digest::Digest;
-use hmac::crypto_mac::MacError;
-use hmac::{Hmac, Mac};
-use typenum::Unsigned;
 
 use crate::curv::arithmetic::num_bigint::*;
-use crate::curv::arithmetic::*;
 use crate::curv::elliptic::curves::secp256_k1::{
     Secp256k1Point as Point, Secp256k1Scalar as Scalar,
 };
@@ -66,7 +62,7 @@ where
     D: Digest + Clone,
 {
     fn input_bigint(&mut self, n: &BigInt) {
-        self.update(&n.to_bytes_be())
+        self.update(n.to_bytes_be())
     }
 
     fn input_point(&mut self, point: &Point) {
@@ -74,7 +70,7 @@ where
     }
 
     fn input_scalar(&mut self, scalar: &Scalar) {
-        self.update(&scalar.to_big_int().to_bytes_be())
+        self.update(scalar.to_big_int().to_bytes_be())
     }
 
     fn result_bigint(self) -> BigInt {
@@ -153,7 +149,7 @@ where
 mod test {
     use digest::generic_array::ArrayLength;
     use digest::{BlockInput, FixedOutput, Reset, Update};
-    use hmac::Hmac;
+    use hmac::{Hmac, Mac};
     use sha2::{Sha256, Sha512};
 
     use super::*;
@@ -59,9 +59,9 @@ mod tests {
     #[test]
     // Very basic test here, TODO: suggest better testing
     fn create_hash_test() {
-        HSha256::create_hash(&vec![]);
+        HSha256::create_hash(&[]);
 
-        let result = HSha256::create_hash(&vec![&BigInt::one(), &BigInt::zero()]);
+        let result = HSha256::create_hash(&[&BigInt::one(), &BigInt::zero()]);
         assert!(result > BigInt::zero());
     }
 
@@ -69,11 +69,11 @@ mod tests {
     #[test]
     fn create_hash_from_ge_test() {
         let point = GE::base_point2();
-        let result1 = HSha256::create_hash_from_ge(&vec![&point, &GE::generator()]);
+        let result1 = HSha256::create_hash_from_ge(&[&point, &GE::generator()]);
         assert!(result1.to_big_int().to_str_radix(2).len() > 240);
-        let result2 = HSha256::create_hash_from_ge(&vec![&GE::generator(), &point]);
+        let result2 = HSha256::create_hash_from_ge(&[&GE::generator(), &point]);
         assert_ne!(result1, result2);
-        let result3 = HSha256::create_hash_from_ge(&vec![&GE::generator(), &point]);
+        let result3 = HSha256::create_hash_from_ge(&[&GE::generator(), &point]);
         assert_eq!(result2, result3);
     }
 }
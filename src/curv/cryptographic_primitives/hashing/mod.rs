@@ -5,7 +5,9 @@
     License MIT: https://github.com/KZen-networks/curv/blob/master/LICENSE
 */
 
+pub mod canonical;
 pub mod constants;
 pub mod ext;
 pub mod hash_sha256;
 pub mod traits;
+pub mod transcript;
@@ -0,0 +1,69 @@
+/*
+    Fiat-Shamir challenges from merlin transcripts.
+
+    Every value enters the transcript under a label and in its canonical
+    encoding, and merlin frames each message with its label and length, so
+    two different statements can not hash to the same transcript. Each proof
+    starts its transcript with its own domain label, which keeps a challenge
+    of one proof from being replayed as the challenge of another.
+*/
+
+use merlin::Transcript;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode;
+use crate::curv::elliptic::curves::secp256_k1::FE;
+use crate::curv::elliptic::curves::traits::ECScalar;
+
+pub trait TranscriptExt {
+    fn append_canonical<T: CanonicalEncode + ?Sized>(&mut self, label: &'static [u8], value: &T);
+
+    /// A 256 bit challenge, as the SHA-256 based challenges it replaces.
+    fn challenge_bigint(&mut self, label: &'static [u8]) -> BigInt;
+
+    /// A challenge scalar, reduced from 512 bits so that its bias mod q is
+    /// negligible.
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> FE;
+}
+
+impl TranscriptExt for Transcript {
+    fn append_canonical<T: CanonicalEncode + ?Sized>(&mut self, label: &'static [u8], value: &T) {
+        self.append_message(label, &value.to_canonical_bytes());
+    }
+
+    fn challenge_bigint(&mut self, label: &'static [u8]) -> BigInt {
+        let mut bytes = [0u8; 32];
+        self.challenge_bytes(label, &mut bytes);
+        BigInt::from_bytes_be(&bytes)
+    }
+
+    fn challenge_scalar(&mut self, label: &'static [u8]) -> FE {
+        let mut bytes = [0u8; 64];
+        self.challenge_bytes(label, &mut bytes);
+        ECScalar::from(&BigInt::from_bytes_be(&bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::secp256_k1::GE;
+    use crate::curv::elliptic::curves::traits::ECPoint;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_challenges_are_domain_separated() {
+        let g: GE = ECPoint::generator();
+        let challenge = |domain: &'static [u8], label: &'static [u8]| {
+            let mut transcript = Transcript::new(domain);
+            transcript.append_canonical(label, &g);
+            transcript.challenge_scalar(b"c")
+        };
+        assert_eq!(challenge(b"a", b"g"), challenge(b"a", b"g"));
+        assert_ne!(challenge(b"a", b"g"), challenge(b"b", b"g"));
+        assert_ne!(challenge(b"a", b"g"), challenge(b"a", b"h"));
+    }
+}
@@ -8,7 +8,9 @@
 use std::error::Error;
 use std::fmt;
 
+#[cfg(feature = "elgamal-proof")]
 pub mod sigma_correct_homomorphic_elgamal_enc;
+#[cfg(feature = "dlog-proof")]
 pub mod sigma_dlog;
 
 pub const PROOF_ERROR_DESCRIPTION: &str = "Error while verifying";
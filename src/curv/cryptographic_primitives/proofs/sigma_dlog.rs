@@ -8,9 +8,10 @@
 use super::ProofError;
 use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
 use crate::curv::elliptic::curves::traits::*;
+use crate::impl_canonical_encode;
 
-use crate::curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
-use crate::curv::cryptographic_primitives::hashing::traits::Hash;
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use merlin::Transcript;
 use zeroize::Zeroize;
 
 /// This is implementation of Schnorr's identification protocol for elliptic curve groups or a
@@ -23,33 +24,75 @@ use zeroize::Zeroize;
 /// How to prove yourself: Practical solutions to identification and signature problems.
 /// In Advances in Cryptology - CRYPTO ’86, Santa Barbara, California, USA, 1986, Proceedings,
 /// pages 186–194, 1986.
+///
+/// The challenge is drawn from a merlin transcript over the generator, the
+/// public key and the commitment. `prove_in_context` additionally binds, as
+/// in RFC 8235, a context string naming the statement and the index of the
+/// prover: such a proof does not verify for another statement or party. The
+/// `ProveDLog` methods are the context-free proofs existing messages carry.
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct DLogProof {
     pub pk: GE,
     pub pk_t_rand_commitment: GE,
     pub challenge_response: FE,
 }
 
+impl_canonical_encode!(DLogProof {
+    pk,
+    pk_t_rand_commitment,
+    challenge_response
+});
+
 pub trait ProveDLog {
     fn prove(sk: &FE) -> DLogProof;
 
     fn verify(proof: &DLogProof) -> Result<(), ProofError>;
 }
 
-impl ProveDLog for DLogProof {
-    fn prove(sk: &FE) -> DLogProof {
+fn challenge(pk: &GE, pk_t_rand_commitment: &GE) -> FE {
+    let base_point: GE = ECPoint::generator();
+    let mut transcript = Transcript::new(b"tss-wasm dlog proof");
+    transcript.append_canonical(b"G", &base_point);
+    transcript.append_canonical(b"pk", pk);
+    transcript.append_canonical(b"commitment", pk_t_rand_commitment);
+    transcript.challenge_scalar(b"challenge")
+}
+
+fn challenge_in_context(pk: &GE, pk_t_rand_commitment: &GE, context: &[u8], prover: u16) -> FE {
+    let base_point: GE = ECPoint::generator();
+    let mut transcript = Transcript::new(b"tss-wasm schnorr pok");
+    transcript.append_canonical(b"G", &base_point);
+    transcript.append_canonical(b"pk", pk);
+    transcript.append_canonical(b"commitment", pk_t_rand_commitment);
+    transcript.append_message(b"context", context);
+    transcript.append_canonical(b"prover", &prover);
+    transcript.challenge_scalar(b"challenge")
+}
+
+impl DLogProof {
+    pub fn prove_in_context(sk: &FE, context: &[u8], prover: u16) -> DLogProof {
+        DLogProof::prove_with(sk, |pk, commitment| {
+            challenge_in_context(pk, commitment, context, prover)
+        })
+    }
+
+    pub fn verify_in_context(
+        proof: &DLogProof,
+        context: &[u8],
+        prover: u16,
+    ) -> Result<(), ProofError> {
+        let challenge_fe =
+            challenge_in_context(&proof.pk, &proof.pk_t_rand_commitment, context, prover);
+        proof.verify_with(&challenge_fe)
+    }
+
+    fn prove_with(sk: &FE, challenge: impl Fn(&GE, &GE) -> FE) -> DLogProof {
         let base_point: GE = ECPoint::generator();
-        let generator_x = base_point.bytes_compressed_to_big_int();
         let mut sk_t_rand_commitment: FE = ECScalar::new_random();
         let pk_t_rand_commitment = base_point.scalar_mul(&sk_t_rand_commitment.get_element());
-        let ec_point: GE = ECPoint::generator();
-        let pk = ec_point.scalar_mul(&sk.get_element());
-        let challenge = HSha256::create_hash(&[
-            &pk_t_rand_commitment.bytes_compressed_to_big_int(),
-            &generator_x,
-            &pk.bytes_compressed_to_big_int(),
-        ]);
-        let challenge_fe: FE = ECScalar::from(&challenge);
+        let pk = base_point.scalar_mul(&sk.get_element());
+        let challenge_fe = challenge(&pk, &pk_t_rand_commitment);
         let challenge_mul_sk = challenge_fe.mul(&sk.get_element());
         let challenge_response = sk_t_rand_commitment.sub(&challenge_mul_sk.get_element());
         sk_t_rand_commitment.zeroize();
@@ -60,25 +103,13 @@ impl ProveDLog for DLogProof {
         }
     }
 
-    fn verify(proof: &DLogProof) -> Result<(), ProofError> {
-        let ec_point: GE = ECPoint::generator();
-        let challenge = HSha256::create_hash(&[
-            &proof.pk_t_rand_commitment.bytes_compressed_to_big_int(),
-            &ec_point.bytes_compressed_to_big_int(),
-            &proof.pk.clone().bytes_compressed_to_big_int(),
-        ]);
-
-        let sk_challenge: FE = ECScalar::from(&challenge);
-        let pk = proof.pk.clone();
-        let pk_challenge = pk.scalar_mul(&sk_challenge.get_element());
-
+    fn verify_with(&self, challenge_fe: &FE) -> Result<(), ProofError> {
+        let pk_challenge = self.pk.scalar_mul(&challenge_fe.get_element());
         let base_point: GE = ECPoint::generator();
-
-        let mut pk_verifier = base_point.scalar_mul(&proof.challenge_response.get_element());
-
-        pk_verifier = pk_verifier.add_point(&pk_challenge.get_element());
-
-        if pk_verifier == proof.pk_t_rand_commitment {
+        let pk_verifier = base_point
+            .scalar_mul(&self.challenge_response.get_element())
+            .add_point(&pk_challenge.get_element());
+        if pk_verifier == self.pk_t_rand_commitment {
             Ok(())
         } else {
             Err(ProofError)
@@ -86,6 +117,16 @@ impl ProveDLog for DLogProof {
     }
 }
 
+impl ProveDLog for DLogProof {
+    fn prove(sk: &FE) -> DLogProof {
+        DLogProof::prove_with(sk, challenge)
+    }
+
+    fn verify(proof: &DLogProof) -> Result<(), ProofError> {
+        proof.verify_with(&challenge(&proof.pk, &proof.pk_t_rand_commitment))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::curv::cryptographic_primitives::proofs::sigma_dlog::*;
@@ -100,9 +141,19 @@ mod tests {
         let witness: FE = ECScalar::new_random();
         let dlog_proof = DLogProof::prove(&witness);
         let verified = DLogProof::verify(&dlog_proof);
-        match verified {
-            Ok(_t) => assert!(true),
-            Err(_e) => assert!(false),
-        }
+        assert!(verified.is_ok());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_dlog_proof_in_context() {
+        let witness: FE = ECScalar::new_random();
+        let proof = DLogProof::prove_in_context(&witness, b"statement", 2);
+        assert!(DLogProof::verify_in_context(&proof, b"statement", 2).is_ok());
+        assert!(DLogProof::verify_in_context(&proof, b"other statement", 2).is_err());
+        assert!(DLogProof::verify_in_context(&proof, b"statement", 3).is_err());
+        assert!(DLogProof::verify(&proof).is_err());
+        let legacy = DLogProof::prove(&witness);
+        assert!(DLogProof::verify_in_context(&legacy, b"", 0).is_err());
     }
 }
@@ -9,9 +9,12 @@
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
 use crate::curv::elliptic::curves::traits::*;
+use crate::impl_canonical_encode;
+use crate::Error::{self, InvalidSS};
 use crate::ErrorSS::{self, VerifyShareError};
 use num_traits::One;
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ShamirSecretSharing {
     pub threshold: usize,   //t
     pub share_count: usize, //n
@@ -22,11 +25,21 @@ pub struct ShamirSecretSharing {
 /// implementation details: The code is using FE and GE. Each party is given an index from 1,..,n and a secret share of type FE.
 /// The index of the party is also the point on the polynomial where we treat this number as u32 but converting it to FE internally.
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct VerifiableSS {
     pub parameters: ShamirSecretSharing,
     pub commitments: Vec<GE>,
 }
 
+impl_canonical_encode!(ShamirSecretSharing {
+    threshold,
+    share_count
+});
+impl_canonical_encode!(VerifiableSS {
+    parameters,
+    commitments
+});
+
 impl VerifiableSS {
     pub fn reconstruct_limit(&self) -> usize {
         self.parameters.threshold + 1
@@ -36,8 +49,15 @@ impl VerifiableSS {
     pub fn share(t: usize, n: usize, secret: &FE) -> (VerifiableSS, Vec<FE>) {
         assert!(t < n);
         let poly = VerifiableSS::sample_polynomial(t, secret);
+        VerifiableSS::share_polynomial(n, &poly)
+    }
+
+    // like share, with the polynomial chosen by the caller; its degree is the threshold
+    pub fn share_polynomial(n: usize, poly: &[FE]) -> (VerifiableSS, Vec<FE>) {
+        let t = poly.len() - 1;
+        assert!(t < n);
         let index_vec: Vec<usize> = (1..=n).collect();
-        let secret_shares = VerifiableSS::evaluate_polynomial(&poly, &index_vec);
+        let secret_shares = VerifiableSS::evaluate_polynomial(poly, &index_vec);
 
         let G: GE = ECPoint::generator();
         let commitments = (0..poly.len())
@@ -122,11 +142,11 @@ impl VerifiableSS {
         let points = indices
             .iter()
             .map(|i| {
-                let index_bn = BigInt::from(*i as u32 + 1 as u32);
+                let index_bn = BigInt::from(*i as u32 + 1_u32);
                 ECScalar::from(&index_bn)
             })
             .collect::<Vec<FE>>();
-        VerifiableSS::lagrange_interpolation_at_zero(&points, &shares)
+        VerifiableSS::lagrange_interpolation_at_zero(&points, shares)
     }
 
     // Performs a Lagrange interpolation in field Zp at the origin
@@ -177,13 +197,13 @@ impl VerifiableSS {
         tail.fold(head.clone(), |acc, x| acc.add(&x.get_element()))
     }
 
-    pub fn validate_share(&self, secret_share: &FE, index: usize) -> Result<(), (ErrorSS)> {
+    pub fn validate_share(&self, secret_share: &FE, index: usize) -> Result<(), ErrorSS> {
         let G: GE = ECPoint::generator();
         let ss_point = G * secret_share;
         self.validate_share_public(&ss_point, index)
     }
 
-    pub fn validate_share_public(&self, ss_point: &GE, index: usize) -> Result<(), (ErrorSS)> {
+    pub fn validate_share_public(&self, ss_point: &GE, index: usize) -> Result<(), ErrorSS> {
         let comm_to_point = self.get_point_commitment(index);
         if *ss_point == comm_to_point {
             Ok(())
@@ -197,10 +217,34 @@ impl VerifiableSS {
         let mut comm_iterator = self.commitments.iter().rev();
         let head = comm_iterator.next().unwrap();
         let tail = comm_iterator;
-        let comm_to_point = tail.fold(head.clone(), |acc, x: &GE| {
+        tail.fold(head.clone(), |acc, x: &GE| {
             x.clone() + acc * index_fe.clone()
+        })
+    }
+
+    // the sharing of the sum of the dealers' secrets: commitments add up
+    // coefficient-wise, so one evaluation gives a point commitment to the
+    // sum of every dealer's share at that index. The dealings come from
+    // peers, so all of them must share the parameters and have t + 1
+    // commitments.
+    pub fn combine(vss_scheme_vec: &[VerifiableSS]) -> Result<VerifiableSS, Error> {
+        let (head, tail) = vss_scheme_vec.split_first().ok_or(InvalidSS)?;
+        let well_formed = vss_scheme_vec.iter().all(|vss| {
+            vss.parameters == head.parameters && vss.commitments.len() == vss.reconstruct_limit()
         });
-        comm_to_point
+        if !well_formed {
+            return Err(InvalidSS);
+        }
+        let mut combined = head.clone();
+        for vss in tail {
+            combined.commitments = combined
+                .commitments
+                .iter()
+                .zip(vss.commitments.iter())
+                .map(|(acc, c)| acc + c)
+                .collect();
+        }
+        Ok(combined)
     }
 
     //compute \lambda_{index,S}, a lagrangian coefficient that change the (t,n) scheme to (|S|,|S|)
@@ -211,7 +255,7 @@ impl VerifiableSS {
         // add one to indices to get points
         let points: Vec<FE> = (0..self.parameters.share_count)
             .map(|i| {
-                let index_bn = BigInt::from(i as u32 + 1 as u32);
+                let index_bn = BigInt::from(i as u32 + 1_u32);
                 ECScalar::from(&index_bn)
             })
             .collect::<Vec<FE>>();
@@ -243,6 +287,7 @@ impl VerifiableSS {
 mod tests {
     use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::*;
     use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+    use crate::Error::InvalidSS;
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::*;
@@ -254,14 +299,15 @@ mod tests {
         let parties = [1, 2, 4, 5, 6];
         let (vss_scheme, secret_shares) = VerifiableSS::share_at_indices(3, 5, &secret, &parties);
 
-        let mut shares_vec = Vec::new();
-        shares_vec.push(secret_shares[0].clone());
-        shares_vec.push(secret_shares[1].clone());
-        shares_vec.push(secret_shares[3].clone());
-        shares_vec.push(secret_shares[4].clone());
+        let shares_vec = vec![
+            secret_shares[0].clone(),
+            secret_shares[1].clone(),
+            secret_shares[3].clone(),
+            secret_shares[4].clone(),
+        ];
         //test reconstruction
 
-        let secret_reconstructed = vss_scheme.reconstruct(&vec![0, 1, 4, 5], &shares_vec);
+        let secret_reconstructed = vss_scheme.reconstruct(&[0, 1, 4, 5], &shares_vec);
         assert_eq!(secret, secret_reconstructed);
     }
 
@@ -272,14 +318,15 @@ mod tests {
 
         let (vss_scheme, secret_shares) = VerifiableSS::share(3, 5, &secret);
 
-        let mut shares_vec = Vec::new();
-        shares_vec.push(secret_shares[0].clone());
-        shares_vec.push(secret_shares[1].clone());
-        shares_vec.push(secret_shares[2].clone());
-        shares_vec.push(secret_shares[4].clone());
+        let shares_vec = vec![
+            secret_shares[0].clone(),
+            secret_shares[1].clone(),
+            secret_shares[2].clone(),
+            secret_shares[4].clone(),
+        ];
         //test reconstruction
 
-        let secret_reconstructed = vss_scheme.reconstruct(&vec![0, 1, 2, 4], &shares_vec);
+        let secret_reconstructed = vss_scheme.reconstruct(&[0, 1, 2, 4], &shares_vec);
 
         assert_eq!(secret, secret_reconstructed);
         // test secret shares are verifiable
@@ -294,12 +341,12 @@ mod tests {
         assert!(valid1_public.is_ok());
 
         // test map (t,n) - (t',t')
-        let s = &vec![0, 1, 2, 3, 4];
-        let l0 = vss_scheme.map_share_to_new_params(0, &s);
-        let l1 = vss_scheme.map_share_to_new_params(1, &s);
-        let l2 = vss_scheme.map_share_to_new_params(2, &s);
-        let l3 = vss_scheme.map_share_to_new_params(3, &s);
-        let l4 = vss_scheme.map_share_to_new_params(4, &s);
+        let s = &[0, 1, 2, 3, 4];
+        let l0 = vss_scheme.map_share_to_new_params(0, s);
+        let l1 = vss_scheme.map_share_to_new_params(1, s);
+        let l2 = vss_scheme.map_share_to_new_params(2, s);
+        let l3 = vss_scheme.map_share_to_new_params(3, s);
+        let l4 = vss_scheme.map_share_to_new_params(4, s);
         let w = l0 * secret_shares[0].clone()
             + l1 * secret_shares[1].clone()
             + l2 * secret_shares[2].clone()
@@ -315,14 +362,15 @@ mod tests {
 
         let (vss_scheme, secret_shares) = VerifiableSS::share(3, 7, &secret);
 
-        let mut shares_vec = Vec::new();
-        shares_vec.push(secret_shares[0].clone());
-        shares_vec.push(secret_shares[6].clone());
-        shares_vec.push(secret_shares[2].clone());
-        shares_vec.push(secret_shares[4].clone());
+        let shares_vec = vec![
+            secret_shares[0].clone(),
+            secret_shares[6].clone(),
+            secret_shares[2].clone(),
+            secret_shares[4].clone(),
+        ];
 
         //test reconstruction
-        let secret_reconstructed = vss_scheme.reconstruct(&vec![0, 6, 2, 4], &shares_vec);
+        let secret_reconstructed = vss_scheme.reconstruct(&[0, 6, 2, 4], &shares_vec);
         assert_eq!(secret, secret_reconstructed);
 
         // test secret shares are verifiable
@@ -332,12 +380,12 @@ mod tests {
         assert!(valid1.is_ok());
 
         // test map (t,n) - (t',t')
-        let s = &vec![0, 1, 3, 4, 6];
-        let l0 = vss_scheme.map_share_to_new_params(0, &s);
-        let l1 = vss_scheme.map_share_to_new_params(1, &s);
-        let l3 = vss_scheme.map_share_to_new_params(3, &s);
-        let l4 = vss_scheme.map_share_to_new_params(4, &s);
-        let l6 = vss_scheme.map_share_to_new_params(6, &s);
+        let s = &[0, 1, 3, 4, 6];
+        let l0 = vss_scheme.map_share_to_new_params(0, s);
+        let l1 = vss_scheme.map_share_to_new_params(1, s);
+        let l3 = vss_scheme.map_share_to_new_params(3, s);
+        let l4 = vss_scheme.map_share_to_new_params(4, s);
+        let l6 = vss_scheme.map_share_to_new_params(6, s);
         let w = l0 * secret_shares[0].clone()
             + l1 * secret_shares[1].clone()
             + l3 * secret_shares[3].clone()
@@ -346,6 +394,37 @@ mod tests {
         assert_eq!(w, secret_reconstructed);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_combine() {
+        let (vss_a, shares_a) = VerifiableSS::share(2, 4, &ECScalar::new_random());
+        let (vss_b, shares_b) = VerifiableSS::share(2, 4, &ECScalar::new_random());
+        let combined = VerifiableSS::combine(&[vss_a.clone(), vss_b.clone()]).unwrap();
+        for i in 0..4 {
+            assert_eq!(
+                combined.get_point_commitment(i + 1),
+                vss_a.get_point_commitment(i + 1) + vss_b.get_point_commitment(i + 1)
+            );
+            let sum = shares_a[i].clone() + shares_b[i].clone();
+            assert!(combined.validate_share(&sum, i + 1).is_ok());
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_combine_rejects_malformed() {
+        let (vss_a, _) = VerifiableSS::share(2, 4, &ECScalar::new_random());
+        let (vss_b, _) = VerifiableSS::share(1, 4, &ECScalar::new_random());
+        assert_eq!(VerifiableSS::combine(&[]), Err(InvalidSS));
+        assert_eq!(
+            VerifiableSS::combine(&[vss_a.clone(), vss_b]),
+            Err(InvalidSS)
+        );
+        let mut short = vss_a.clone();
+        short.commitments.pop();
+        assert_eq!(VerifiableSS::combine(&[vss_a, short]), Err(InvalidSS));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_secret_sharing_1_out_of_2() {
@@ -353,12 +432,10 @@ mod tests {
 
         let (vss_scheme, secret_shares) = VerifiableSS::share(1, 2, &secret);
 
-        let mut shares_vec = Vec::new();
-        shares_vec.push(secret_shares[0].clone());
-        shares_vec.push(secret_shares[1].clone());
+        let shares_vec = vec![secret_shares[0].clone(), secret_shares[1].clone()];
 
         //test reconstruction
-        let secret_reconstructed = vss_scheme.reconstruct(&vec![0, 1], &shares_vec);
+        let secret_reconstructed = vss_scheme.reconstruct(&[0, 1], &shares_vec);
         assert_eq!(secret, secret_reconstructed);
 
         // test secret shares are verifiable
@@ -368,9 +445,9 @@ mod tests {
         assert!(valid1.is_ok());
 
         // test map (t,n) - (t',t')
-        let s = &vec![0, 1];
-        let l0 = vss_scheme.map_share_to_new_params(0, &s);
-        let l1 = vss_scheme.map_share_to_new_params(1, &s);
+        let s = &[0, 1];
+        let l0 = vss_scheme.map_share_to_new_params(0, s);
+        let l1 = vss_scheme.map_share_to_new_params(1, s);
 
         let w = l0 * secret_shares[0].clone() + l1 * secret_shares[1].clone();
         assert_eq!(w, secret_reconstructed);
@@ -383,9 +460,7 @@ mod tests {
 
         let (vss_scheme, secret_shares) = VerifiableSS::share(1, 3, &secret);
 
-        let mut shares_vec = Vec::new();
-        shares_vec.push(secret_shares[0].clone());
-        shares_vec.push(secret_shares[1].clone());
+        let shares_vec = vec![secret_shares[0].clone(), secret_shares[1].clone()];
 
         // test commitment to point and sum of commitments
         let (vss_scheme2, secret_shares2) = VerifiableSS::share(1, 3, &secret);
@@ -401,7 +476,7 @@ mod tests {
         assert_eq!(point1_sum_com, g_sum);
 
         //test reconstruction
-        let secret_reconstructed = vss_scheme.reconstruct(&vec![0, 1], &shares_vec);
+        let secret_reconstructed = vss_scheme.reconstruct(&[0, 1], &shares_vec);
         assert_eq!(secret, secret_reconstructed);
 
         // test secret shares are verifiable
@@ -411,9 +486,9 @@ mod tests {
         assert!(valid1.is_ok());
 
         // test map (t,n) - (t',t')
-        let s = &vec![0, 2];
-        let l0 = vss_scheme.map_share_to_new_params(0, &s);
-        let l2 = vss_scheme.map_share_to_new_params(2, &s);
+        let s = &[0, 2];
+        let l0 = vss_scheme.map_share_to_new_params(0, s);
+        let l2 = vss_scheme.map_share_to_new_params(2, s);
 
         let w = l0 * secret_shares[0].clone() + l2 * secret_shares[2].clone();
         assert_eq!(w, secret_reconstructed);
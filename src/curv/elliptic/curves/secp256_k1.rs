@@ -30,13 +30,13 @@ use crate::curv::cryptographic_primitives::hashing::traits::Hash;
 use num_traits::Num;
 use serde::de;
 use serde::de::{MapAccess, Visitor};
-use serde::ser::SerializeStruct;
 use serde::ser::{Serialize, Serializer};
 use serde::{Deserialize, Deserializer};
 use std::fmt;
 use std::ops::{Add, Mul};
 use std::ptr;
 use std::sync::atomic;
+use subtle::{Choice, ConstantTimeEq};
 use zeroize::Zeroize;
 
 use super::secp256k1::curve::Scalar;
@@ -70,9 +70,88 @@ impl Secp256k1Scalar {
             fe: self.fe.clone(),
         }
     }
+
+    /// Unlike `ECScalar::from`, does not reduce: zero and values not below the
+    /// group order are rejected. Use this for scalars received from other parties.
+    pub fn from_big_int_checked(n: &BigInt) -> Result<Secp256k1Scalar, ErrorKey> {
+        if *n == BigInt::from(0_u16) || n >= FE::group_order() {
+            return Err(ErrorKey::InvalidScalar);
+        }
+        let v = BigInt::to_vec(n);
+        let mut template = vec![0; SECRET_KEY_SIZE - v.len()];
+        template.extend_from_slice(&v);
+        SK::parse_slice(&template)
+            .map(|fe| Secp256k1Scalar {
+                purpose: "from_big_int",
+                fe,
+            })
+            .map_err(|_err| ErrorKey::InvalidScalar)
+    }
+
+    /// Parses a 32 byte big-endian scalar with the same checks as `from_big_int_checked`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Secp256k1Scalar, ErrorKey> {
+        if bytes.len() != SECRET_KEY_SIZE {
+            return Err(ErrorKey::InvalidScalar);
+        }
+        Secp256k1Scalar::from_big_int_checked(&BigInt::from_bytes_be(bytes))
+    }
+
+    /// The scalar as 32 big-endian bytes, the inverse of `from_bytes`.
+    pub fn to_bytes_be(&self) -> [u8; SECRET_KEY_SIZE] {
+        self.fe.serialize()
+    }
+
+    /// `to_bytes_be` as 64 hex digits.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes_be())
+    }
+
+    /// Parses 64 hex digits, with or without `0x`, with the checks of `from_bytes`.
+    pub fn from_hex(s: &str) -> Result<Secp256k1Scalar, ErrorKey> {
+        let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))
+            .map_err(|_err| ErrorKey::InvalidScalar)?;
+        Secp256k1Scalar::from_bytes(&bytes)
+    }
 }
 
 impl Secp256k1Point {
+    /// Parses a SEC1 encoded point: 33 bytes compressed or 65 bytes
+    /// uncompressed, as produced by `to_bytes`. Unlike `ECPoint::from_bytes`
+    /// the prefix byte is checked rather than guessed or implied.
+    pub fn from_sec1_bytes(bytes: &[u8]) -> Result<Secp256k1Point, ErrorKey> {
+        let ge = match bytes.len() {
+            33 => {
+                let mut compressed = [0u8; 33];
+                compressed.copy_from_slice(bytes);
+                PK::parse_compressed(&compressed)
+            }
+            65 => {
+                let mut uncompressed = [0u8; 65];
+                uncompressed.copy_from_slice(bytes);
+                PK::parse(&uncompressed)
+            }
+            _ => return Err(ErrorKey::InvalidPublicKey),
+        }
+        .map_err(|_err| ErrorKey::InvalidPublicKey)?;
+        Ok(Secp256k1Point {
+            purpose: "from_sec1_bytes",
+            ge,
+        })
+    }
+
+    /// The compressed SEC1 encoding as 66 hex digits.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes(true))
+    }
+
+    /// Parses a compressed or uncompressed SEC1 point in hex, with or
+    /// without `0x`.
+    pub fn from_hex(s: &str) -> Result<Secp256k1Point, ErrorKey> {
+        let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))
+            .map_err(|_err| ErrorKey::InvalidPublicKey)?;
+        Secp256k1Point::from_sec1_bytes(&bytes)
+    }
+
     pub fn random_point() -> Secp256k1Point {
         let random_scalar: Secp256k1Scalar = Secp256k1Scalar::new_random();
         let base_point = Secp256k1Point::generator();
@@ -111,6 +190,30 @@ impl Secp256k1Point {
             ge: self.ge.clone(),
         }
     }
+
+    /// Fallible counterpart of `ECPoint::from_coor`. Coordinates wider than a
+    /// field element, points off the curve and the identity are rejected.
+    pub fn try_from_coor(x: &BigInt, y: &BigInt) -> Result<Secp256k1Point, ErrorKey> {
+        let coor_size = (UNCOMPRESSED_PUBLIC_KEY_SIZE - 1) / 2;
+        let vec_x = BigInt::to_vec(x);
+        let vec_y = BigInt::to_vec(y);
+        if vec_x.len() > coor_size || vec_y.len() > coor_size {
+            return Err(ErrorKey::InvalidPublicKey);
+        }
+
+        let mut v = vec![4_u8];
+        v.extend(vec![0; coor_size - vec_x.len()]);
+        v.extend(vec_x);
+        v.extend(vec![0; coor_size - vec_y.len()]);
+        v.extend(vec_y);
+
+        PK::parse_slice(&v, None)
+            .map(|pk| Secp256k1Point {
+                purpose: "base_fe",
+                ge: pk,
+            })
+            .map_err(|_err| ErrorKey::InvalidPublicKey)
+    }
 }
 
 impl Zeroize for FE {
@@ -152,7 +255,7 @@ impl ECScalar<SK> for Secp256k1Scalar {
 
     fn from(n: &BigInt) -> Secp256k1Scalar {
         let curve_order = FE::q();
-        let n_reduced = BigInt::mod_add(n, &BigInt::from(0 as u16), &curve_order);
+        let n_reduced = BigInt::mod_add(n, &BigInt::from(0_u16), &curve_order);
         let mut v = BigInt::to_vec(&n_reduced);
 
         if v.len() < SECRET_KEY_SIZE {
@@ -232,28 +335,28 @@ impl ECScalar<SK> for Secp256k1Scalar {
 impl Mul<Secp256k1Scalar> for Secp256k1Scalar {
     type Output = Secp256k1Scalar;
     fn mul(self, other: Secp256k1Scalar) -> Secp256k1Scalar {
-        (&self).mul(&other.get_element())
+        ECScalar::mul(&self, &other.get_element())
     }
 }
 
 impl<'o> Mul<&'o Secp256k1Scalar> for Secp256k1Scalar {
     type Output = Secp256k1Scalar;
     fn mul(self, other: &'o Secp256k1Scalar) -> Secp256k1Scalar {
-        (&self).mul(&other.get_element())
+        ECScalar::mul(&self, &other.get_element())
     }
 }
 
 impl Add<Secp256k1Scalar> for Secp256k1Scalar {
     type Output = Secp256k1Scalar;
     fn add(self, other: Secp256k1Scalar) -> Secp256k1Scalar {
-        (&self).add(&other.get_element())
+        ECScalar::add(&self, &other.get_element())
     }
 }
 
 impl<'o> Add<&'o Secp256k1Scalar> for Secp256k1Scalar {
     type Output = Secp256k1Scalar;
     fn add(self, other: &'o Secp256k1Scalar) -> Secp256k1Scalar {
-        (&self).add(&other.get_element())
+        ECScalar::add(&self, &other.get_element())
     }
 }
 
@@ -285,20 +388,34 @@ impl<'de> Visitor<'de> for Secp256k1ScalarVisitor {
     }
 
     fn visit_str<E: de::Error>(self, s: &str) -> Result<Secp256k1Scalar, E> {
-        let v = BigInt::from_str_radix(s, 16).expect("Failed in serde");
-        Ok(ECScalar::from(&v))
+        let v = BigInt::from_str_radix(s, 16).map_err(|_| E::custom("invalid scalar encoding"))?;
+        Secp256k1Scalar::from_big_int_checked(&v).map_err(|_| E::custom("invalid scalar"))
+    }
+}
+
+// Scalars are mostly secret and points are often derived from secrets, so
+// both compare their fixed-width encodings in constant time.
+impl ConstantTimeEq for Secp256k1Scalar {
+    fn ct_eq(&self, other: &Secp256k1Scalar) -> Choice {
+        self.fe.serialize()[..].ct_eq(&other.fe.serialize()[..])
+    }
+}
+
+impl ConstantTimeEq for Secp256k1Point {
+    fn ct_eq(&self, other: &Secp256k1Point) -> Choice {
+        self.ge.serialize()[..].ct_eq(&other.ge.serialize()[..])
     }
 }
 
 impl PartialEq for Secp256k1Scalar {
     fn eq(&self, other: &Secp256k1Scalar) -> bool {
-        self.get_element() == other.get_element()
+        self.ct_eq(other).into()
     }
 }
 
 impl PartialEq for Secp256k1Point {
     fn eq(&self, other: &Secp256k1Point) -> bool {
-        self.get_element() == other.get_element()
+        self.ct_eq(other).into()
     }
 }
 
@@ -314,7 +431,7 @@ impl ECPoint<PK, SK> for Secp256k1Point {
     type CompressedPointLength = typenum::U33;
     type UncompressedPointLength = typenum::U65;
     fn generator() -> Secp256k1Point {
-        let mut v = vec![4 as u8];
+        let mut v = vec![4_u8];
         v.extend(GENERATOR_X.as_ref());
         v.extend(GENERATOR_Y.as_ref());
         Secp256k1Point {
@@ -333,7 +450,7 @@ impl ECPoint<PK, SK> for Secp256k1Point {
     /// 3) call from_bytes
     fn bytes_compressed_to_big_int(&self) -> BigInt {
         let serial = self.ge.serialize();
-        let y_coor_last_byte = serial[64].clone();
+        let y_coor_last_byte = serial[64];
         let y_coor_parity = (y_coor_last_byte << 7) >> 7;
         let mut compressed = vec![2 + y_coor_parity];
         compressed.append(&mut serial[1..33].to_vec());
@@ -354,68 +471,20 @@ impl ECPoint<PK, SK> for Secp256k1Point {
         Some(from(&y_vec[..]))
     }
 
+    /// Parses a SEC1 point, 33 bytes compressed or 65 uncompressed, or the
+    /// 64 bytes x || y of an uncompressed one without its prefix. Other
+    /// lengths are rejected rather than padded, truncated or completed with
+    /// a guessed parity.
     fn from_bytes(bytes: &[u8]) -> Result<Secp256k1Point, ErrorKey> {
-        let bytes_vec = bytes.to_vec();
-        let mut bytes_array_65 = [0u8; 65];
-        let mut bytes_array_33 = [0u8; 33];
-
-        let byte_len = bytes_vec.len();
-        match byte_len {
-            33..=63 => {
-                let mut template = vec![0; 64 - bytes_vec.len()];
-                template.extend_from_slice(&bytes);
-                let bytes_vec = template;
-                let mut template: Vec<u8> = vec![4];
-                template.append(&mut bytes_vec.clone());
-                let bytes_slice = &template[..];
-
-                bytes_array_65.copy_from_slice(&bytes_slice[0..65]);
-                let result = PK::parse(&bytes_array_65);
-                let test = result.map(|pk| Secp256k1Point {
-                    purpose: "random",
-                    ge: pk,
-                });
-                test.map_err(|_err| ErrorKey::InvalidPublicKey)
-            }
-
-            0..=32 => {
-                let mut template = vec![0; 32 - bytes_vec.len()];
-                template.extend_from_slice(&bytes);
-                let bytes_vec = template;
-                let mut rng = rand::thread_rng();
-                let bit: bool = rng.gen();
-                println!("rand {:?}", 2 + bit as u8);
-                let mut template: Vec<u8> = vec![2 + bit as u8];
-                template.append(&mut bytes_vec.clone());
-                let bytes_slice = &template[..];
-
-                bytes_array_33.copy_from_slice(&bytes_slice[0..33]);
-                let result = PK::parse_compressed(&bytes_array_33);
-                let test = result.map(|pk| Secp256k1Point {
-                    purpose: "random",
-                    ge: pk,
-                });
-                test.map_err(|_err| ErrorKey::InvalidPublicKey)
-            }
-            _ => {
-                let bytes_slice = &bytes_vec[0..64];
-                let bytes_vec = bytes_slice.to_vec();
-                let mut template: Vec<u8> = vec![4];
-                template.append(&mut bytes_vec.clone());
-                let bytes_slice = &template[..];
-
-                bytes_array_65.copy_from_slice(&bytes_slice[0..65]);
-                let result = PK::parse(&bytes_array_65);
-                let test = result.map(|pk| Secp256k1Point {
-                    purpose: "random",
-                    ge: pk,
-                });
-                test.map_err(|_err| ErrorKey::InvalidPublicKey)
-            }
+        if bytes.len() == 64 {
+            let mut uncompressed = vec![4u8];
+            uncompressed.extend_from_slice(bytes);
+            return Secp256k1Point::from_sec1_bytes(&uncompressed);
         }
+        Secp256k1Point::from_sec1_bytes(bytes)
     }
     fn pk_to_key_slice(&self) -> Vec<u8> {
-        let mut v = vec![4 as u8];
+        let mut v = vec![4_u8];
 
         v.extend(BigInt::to_vec(&self.x_coor().unwrap()));
         v.extend(BigInt::to_vec(&self.y_coor().unwrap()));
@@ -501,7 +570,7 @@ impl ECPoint<PK, SK> for Secp256k1Point {
         assert_eq!(x, &from(vec_x.as_ref()));
         assert_eq!(y, &from(vec_y.as_ref()));
 
-        let mut v = vec![4 as u8];
+        let mut v = vec![4_u8];
         v.extend(vec_x);
         v.extend(vec_y);
 
@@ -554,15 +623,15 @@ impl<'o> Add<&'o Secp256k1Point> for &'o Secp256k1Point {
     }
 }
 
+/// A point serializes as its 33 byte compressed SEC1 encoding in hex, see
+/// `to_hex`. The `{"x", "y"}` map of affine coordinates written by earlier
+/// versions is still read, as are raw SEC1 bytes from binary formats.
 impl Serialize for Secp256k1Point {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Secp256k1Point", 2)?;
-        state.serialize_field("x", &self.x_coor().unwrap().to_hex())?;
-        state.serialize_field("y", &self.y_coor().unwrap().to_hex())?;
-        state.end()
+        serializer.serialize_str(&self.to_hex())
     }
 }
 
@@ -571,8 +640,7 @@ impl<'de> Deserialize<'de> for Secp256k1Point {
     where
         D: Deserializer<'de>,
     {
-        let fields = &["x", "y"];
-        deserializer.deserialize_struct("Secp256k1Point", fields, Secp256k1PointVisitor)
+        deserializer.deserialize_any(Secp256k1PointVisitor)
     }
 }
 
@@ -585,25 +653,37 @@ impl<'de> Visitor<'de> for Secp256k1PointVisitor {
         formatter.write_str("Secp256k1Point")
     }
 
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Secp256k1Point, E> {
+        Secp256k1Point::from_hex(s).map_err(|_| E::custom("invalid point"))
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Secp256k1Point, E> {
+        Secp256k1Point::from_sec1_bytes(bytes).map_err(|_| E::custom("invalid point"))
+    }
+
     fn visit_map<E: MapAccess<'de>>(self, mut map: E) -> Result<Secp256k1Point, E::Error> {
-        let mut x = String::new();
-        let mut y = String::new();
+        let mut x: Option<String> = None;
+        let mut y: Option<String> = None;
 
         while let Some(ref key) = map.next_key::<String>()? {
             let v = map.next_value::<String>()?;
             if key == "x" {
-                x = v
+                x = Some(v)
             } else if key == "y" {
-                y = v
+                y = Some(v)
             } else {
-                panic!("Serialization failed!")
+                return Err(de::Error::unknown_field(key, &["x", "y"]));
             }
         }
 
-        let bx = BigInt::from_hex(&x);
-        let by = BigInt::from_hex(&y);
+        let x = x.ok_or_else(|| <E::Error as de::Error>::missing_field("x"))?;
+        let y = y.ok_or_else(|| <E::Error as de::Error>::missing_field("y"))?;
+        let bx = BigInt::from_str_radix(&x, 16)
+            .map_err(|_| <E::Error as de::Error>::custom("invalid point encoding"))?;
+        let by = BigInt::from_str_radix(&y, 16)
+            .map_err(|_| <E::Error as de::Error>::custom("invalid point encoding"))?;
 
-        Ok(Secp256k1Point::from_coor(&bx, &by))
+        Secp256k1Point::try_from_coor(&bx, &by).map_err(|_| de::Error::custom("invalid point"))
     }
 }
 #[cfg(test)]
@@ -620,6 +700,7 @@ mod tests {
     use crate::curv::elliptic::curves::traits::ECScalar;
     use crate::ErrorKey;
     use serde_json;
+    use subtle::ConstantTimeEq;
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::*;
@@ -627,7 +708,7 @@ mod tests {
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn serialize_sk() {
-        let scalar: Secp256k1Scalar = ECScalar::from(&BigInt::from(123456 as u32));
+        let scalar: Secp256k1Scalar = ECScalar::from(&BigInt::from(123456_u32));
         println!("TEST4 {:?}", scalar.clone());
         let s = serde_json::to_string(&scalar).expect("Failed in serialization");
         assert_eq!(s, "\"1e240\"");
@@ -636,23 +717,18 @@ mod tests {
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn serialize_rand_pk_verify_pad() {
-        let vx = BigInt::from_hex(
-            &"ccaf75ab7960a01eb421c0e2705f6e84585bd0a094eb6af928c892a4a2912508".to_string(),
-        );
+        let vx =
+            BigInt::from_hex("ccaf75ab7960a01eb421c0e2705f6e84585bd0a094eb6af928c892a4a2912508");
 
-        let vy = BigInt::from_hex(
-            &"e788e294bd64eee6a73d2fc966897a31eb370b7e8e9393b0d8f4f820b48048df".to_string(),
-        );
+        let vy =
+            BigInt::from_hex("e788e294bd64eee6a73d2fc966897a31eb370b7e8e9393b0d8f4f820b48048df");
 
         Secp256k1Point::from_coor(&vx, &vy); // x and y of size 32
 
-        let x = BigInt::from_hex(
-            &"5f6853305467a385b56a5d87f382abb52d10835a365ec265ce510e04b3c3366f".to_string(),
-        );
+        let x =
+            BigInt::from_hex("5f6853305467a385b56a5d87f382abb52d10835a365ec265ce510e04b3c3366f");
 
-        let y = BigInt::from_hex(
-            &"b868891567ca1ee8c44706c0dc190dd7779fe6f9b92ced909ad870800451e3".to_string(),
-        );
+        let y = BigInt::from_hex("b868891567ca1ee8c44706c0dc190dd7779fe6f9b92ced909ad870800451e3");
 
         Secp256k1Point::from_coor(&x, &y); // x and y not of size 32 each
 
@@ -669,7 +745,7 @@ mod tests {
         let s = "\"1e240\"";
         let dummy: Secp256k1Scalar = serde_json::from_str(s).expect("Failed in serialization");
         println!("TEST3,{:?}", dummy.clone());
-        let sk: Secp256k1Scalar = ECScalar::from(&BigInt::from(123456 as u32));
+        let sk: Secp256k1Scalar = ECScalar::from(&BigInt::from(123456_u32));
 
         assert_eq!(dummy, sk);
     }
@@ -681,12 +757,22 @@ mod tests {
         let x = pk.x_coor().unwrap();
         let y = pk.y_coor().unwrap();
         let s = serde_json::to_string(&pk).expect("Failed in serialization");
-
-        let expected = format!("{{\"x\":\"{}\",\"y\":\"{}\"}}", x.to_hex(), y.to_hex());
-        assert_eq!(s, expected);
+        assert_eq!(
+            s,
+            "\"0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798\""
+        );
 
         let des_pk: Secp256k1Point = serde_json::from_str(&s).expect("Failed in serialization");
         assert_eq!(des_pk.ge, pk.ge);
+
+        // the affine coordinates written by earlier versions
+        let legacy = format!("{{\"x\":\"{}\",\"y\":\"{}\"}}", x.to_hex(), y.to_hex());
+        let des_pk: Secp256k1Point =
+            serde_json::from_str(&legacy).expect("Failed in serialization");
+        assert_eq!(des_pk.ge, pk.ge);
+        let uncompressed = format!("\"{}\"", hex::encode(pk.to_bytes(false)));
+        let des_pk: Secp256k1Point = serde_json::from_str(&uncompressed).unwrap();
+        assert_eq!(des_pk.ge, pk.ge);
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
@@ -710,21 +796,83 @@ mod tests {
         let pk = GE::generator();
         let s = serde_json::to_string(&pk).expect("Failed in serialization");
         // we make sure that the string encodes invalid point:
-        let s: String = s.replace("79be", "79bf");
+        let s: String = s.replace("\"02", "\"05");
         let des_pk: GE = serde_json::from_str(&s).expect("Failed in deserialization");
         assert_eq!(des_pk, pk);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_deserialize_bad_sk() {
+        let zero: Result<Secp256k1Scalar, _> = serde_json::from_str("\"0\"");
+        assert!(zero.is_err());
+
+        let q = format!("\"{}\"", FE::q().to_hex());
+        let q: Result<Secp256k1Scalar, _> = serde_json::from_str(&q);
+        assert!(q.is_err());
+
+        let not_hex: Result<Secp256k1Scalar, _> = serde_json::from_str("\"xyz\"");
+        assert!(not_hex.is_err());
+
+        assert_eq!(
+            Secp256k1Scalar::from_bytes(&[0u8; 31]).unwrap_err(),
+            ErrorKey::InvalidScalar
+        );
+        let one: FE = ECScalar::from(&BigInt::from(1_u16));
+        let mut bytes = [0u8; 32];
+        bytes[31] = 1;
+        assert_eq!(Secp256k1Scalar::from_bytes(&bytes).unwrap(), one);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_try_from_coor() {
+        let g = GE::generator();
+        let x = g.x_coor().unwrap();
+        let y = g.y_coor().unwrap();
+        assert_eq!(Secp256k1Point::try_from_coor(&x, &y).unwrap(), g);
+
+        let off_curve = Secp256k1Point::try_from_coor(&x, &(y.clone() + BigInt::from(1_u16)));
+        assert_eq!(off_curve.unwrap_err(), ErrorKey::InvalidPublicKey);
+
+        let zero = BigInt::from(0_u16);
+        let identity = Secp256k1Point::try_from_coor(&zero, &zero);
+        assert_eq!(identity.unwrap_err(), ErrorKey::InvalidPublicKey);
+
+        let wide = Secp256k1Point::try_from_coor(&(x << 8), &y);
+        assert_eq!(wide.unwrap_err(), ErrorKey::InvalidPublicKey);
+
+        let unknown_field = "{\"x\":\"1\",\"z\":\"2\"}";
+        let result: Result<GE, _> = serde_json::from_str(unknown_field);
+        assert!(result.is_err());
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_from_bytes() {
         let g = Secp256k1Point::generator();
-        let hash = HSha256::create_hash(&vec![&g.bytes_compressed_to_big_int()]);
+        let hash = HSha256::create_hash(&[&g.bytes_compressed_to_big_int()]);
         let hash_vec = BigInt::to_vec(&hash);
         let result = Secp256k1Point::from_bytes(&hash_vec);
         assert_eq!(result.unwrap_err(), ErrorKey::InvalidPublicKey)
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_from_bytes_2() {
+        let g = Secp256k1Point::generator();
+        let compressed = g.to_bytes(true);
+        let uncompressed = g.to_bytes(false);
+        assert_eq!(Secp256k1Point::from_bytes(&compressed).unwrap(), g);
+        assert_eq!(Secp256k1Point::from_bytes(&uncompressed).unwrap(), g);
+        assert_eq!(Secp256k1Point::from_bytes(&uncompressed[1..]).unwrap(), g);
+        // an x alone, or a point with trailing bytes, is not a point
+        assert!(Secp256k1Point::from_bytes(&compressed[1..]).is_err());
+        let mut long = uncompressed;
+        long.push(0);
+        assert!(Secp256k1Point::from_bytes(&long).is_err());
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_from_bytes_3() {
@@ -733,7 +881,7 @@ mod tests {
             0, 0, 0, 1, 2, 3, 4, 5, 6,
         ];
         let result = Secp256k1Point::from_bytes(&test_vec);
-        assert!(result.is_ok() | result.is_err())
+        assert_eq!(result.unwrap_err(), ErrorKey::InvalidPublicKey)
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
@@ -743,7 +891,7 @@ mod tests {
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6,
         ];
         let result = Secp256k1Point::from_bytes(&test_vec);
-        assert!(result.is_ok() | result.is_err())
+        assert_eq!(result.unwrap_err(), ErrorKey::InvalidPublicKey)
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
@@ -756,7 +904,7 @@ mod tests {
             4, 5, 6,
         ];
         let result = Secp256k1Point::from_bytes(&test_vec);
-        assert!(result.is_ok() | result.is_err())
+        assert_eq!(result.unwrap_err(), ErrorKey::InvalidPublicKey)
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
@@ -798,4 +946,52 @@ mod tests {
         let c2 = a * b;
         assert_eq!(c1.get_element(), c2.get_element());
     }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_ct_eq() {
+        let a: FE = ECScalar::new_random();
+        let b: FE = ECScalar::new_random();
+        let base: GE = ECPoint::generator();
+        assert!(bool::from(a.ct_eq(&a.copy())));
+        assert!(!bool::from(a.ct_eq(&b)));
+        assert!(bool::from((base.clone() * &a).ct_eq(&(base.clone() * &a))));
+        assert!(!bool::from((base.clone() * &a).ct_eq(&(base * &b))));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_hex_and_byte_conversions() {
+        let a: FE = ECScalar::new_random();
+        assert_eq!(a.to_hex().len(), 64);
+        assert_eq!(FE::from_hex(&a.to_hex()).unwrap(), a);
+        assert_eq!(FE::from_bytes(&a.to_bytes_be()).unwrap(), a);
+        assert_eq!(
+            a.to_big_int().to_bytes_be_padded(32).unwrap(),
+            a.to_bytes_be().to_vec()
+        );
+        assert!(FE::from_hex(&"ff".repeat(32)).is_err());
+
+        let one: FE = ECScalar::from(&BigInt::from(1_u32));
+        assert_eq!(
+            one.to_hex(),
+            "0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(BigInt::from(1_u32).to_hex_padded(2).unwrap(), "0001");
+        assert!(BigInt::from(0x10000_u32).to_bytes_be_padded(2).is_none());
+        assert_eq!(
+            BigInt::try_from_hex("0x0101"),
+            Some(BigInt::from(0x101_u32))
+        );
+        assert_eq!(BigInt::try_from_hex("0xzz"), None);
+
+        let p = GE::generator() * a;
+        assert_eq!(p.to_hex().len(), 66);
+        assert_eq!(GE::from_hex(&p.to_hex()).unwrap(), p);
+        assert_eq!(GE::from_sec1_bytes(&p.to_bytes(false)).unwrap(), p);
+        let mut bad_prefix = p.to_bytes(true);
+        bad_prefix[0] = 5;
+        assert!(GE::from_sec1_bytes(&bad_prefix).is_err());
+        assert!(GE::from_sec1_bytes(&p.to_bytes(false)[1..]).is_err());
+    }
 }
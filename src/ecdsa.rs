@@ -0,0 +1,159 @@
+//! Plain single-key ECDSA over secp256k1, on the same curve code as the
+//! threshold protocols.
+//!
+//! This is a reference for interop tests and for the conventions the
+//! threshold signatures are exported with: the digest is read big-endian and
+//! reduced mod q, `s` is normalized to the lower half of the group order, and
+//! the recovery id has bit 0 set for an odd `R.y` and bit 1 when `R.x`
+//! overflowed q. A threshold signature carries no recovery id; `recovery_id`
+//! finds it from the group key.
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::Error::{self, InvalidSig};
+use num_integer::Integer;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct RecoverableSignature {
+    pub r: FE,
+    pub s: FE,
+    pub recid: u8,
+}
+
+fn digest_scalar(digest: &[u8; 32]) -> FE {
+    ECScalar::from(&BigInt::from_bytes_be(digest))
+}
+
+pub fn is_low_s(s: &FE) -> bool {
+    s.to_big_int() <= FE::q() / BigInt::from(2_u32)
+}
+
+/// `(s, recid)` with `s` in the lower half, flipping the parity bit of the
+/// recovery id when `s` is negated.
+pub fn normalize_s(s: &FE, recid: u8) -> (FE, u8) {
+    if is_low_s(s) {
+        (s.clone(), recid)
+    } else {
+        (ECScalar::from(&(FE::q() - s.to_big_int())), recid ^ 1)
+    }
+}
+
+#[cfg(feature = "signing")]
+pub fn sign(secret: &FE, digest: &[u8; 32]) -> RecoverableSignature {
+    let g: GE = ECPoint::generator();
+    let e = digest_scalar(digest);
+    loop {
+        let k: FE = ECScalar::new_random();
+        let big_r = &g * &k;
+        let x = big_r.x_coor().unwrap();
+        let r: FE = ECScalar::from(&x.mod_floor(&FE::q()));
+        let s = k.invert() * (e.clone() + r.clone() * secret);
+        if r == FE::zero() || s == FE::zero() {
+            continue;
+        }
+        let recid = u8::from(big_r.y_coor().unwrap().is_odd()) | (u8::from(x >= FE::q()) << 1);
+        let (s, recid) = normalize_s(&s, recid);
+        return RecoverableSignature { r, s, recid };
+    }
+}
+
+/// Verifies `(r, s)` under `pk`. High `s` values are accepted, as by
+/// `gg_2018::party_i::verify`; check `is_low_s` where a chain requires it.
+pub fn verify(pk: &GE, digest: &[u8; 32], r: &FE, s: &FE) -> Result<(), Error> {
+    if *r == FE::zero() || *s == FE::zero() {
+        return Err(InvalidSig);
+    }
+    let s_inv = s.invert();
+    let u1 = digest_scalar(digest) * &s_inv;
+    let u2 = r.clone() * &s_inv;
+    let g: GE = ECPoint::generator();
+    let big_r = (&g * &u1) + &(pk * &u2);
+    let x: FE = ECScalar::from(&big_r.x_coor().unwrap().mod_floor(&FE::q()));
+    if x == *r {
+        Ok(())
+    } else {
+        Err(InvalidSig)
+    }
+}
+
+/// The public key that produced `(r, s)` over `digest`, `Q = r^-1 (s R - e G)`.
+pub fn recover(digest: &[u8; 32], r: &FE, s: &FE, recid: u8) -> Result<GE, Error> {
+    if recid > 3 || *r == FE::zero() || *s == FE::zero() {
+        return Err(InvalidSig);
+    }
+    let mut x = r.to_big_int();
+    if recid & 2 != 0 {
+        x += FE::q();
+    }
+    let x_bytes = x.to_bytes_be();
+    if x_bytes.len() > 32 {
+        return Err(InvalidSig);
+    }
+    let mut compressed = [0u8; 33];
+    compressed[0] = 2 + (recid & 1);
+    compressed[33 - x_bytes.len()..].copy_from_slice(&x_bytes);
+    // fails when x is not below the field prime or not on the curve
+    let big_r = GE::from_sec1_bytes(&compressed).map_err(|_| InvalidSig)?;
+
+    let g: GE = ECPoint::generator();
+    let e_g = &g * &digest_scalar(digest);
+    let s_r = &big_r * s;
+    let pk = s_r.sub_point(&e_g.get_element()) * r.invert();
+    verify(&pk, digest, r, s)?;
+    Ok(pk)
+}
+
+/// The recovery id under which `(r, s)` recovers to `pk`, for signatures
+/// that were produced without one.
+pub fn recovery_id(pk: &GE, digest: &[u8; 32], r: &FE, s: &FE) -> Option<u8> {
+    (0..4).find(|recid| recover(digest, r, s, *recid).as_ref() == Ok(pk))
+}
+
+#[cfg(all(test, feature = "signing"))]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_sign_verify_recover() {
+        let secret: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let pk = &g * &secret;
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&Sha256::digest(b"interop"));
+
+        let sig = sign(&secret, &digest);
+        assert!(is_low_s(&sig.s));
+        assert_eq!(verify(&pk, &digest, &sig.r, &sig.s), Ok(()));
+        assert_eq!(recover(&digest, &sig.r, &sig.s, sig.recid), Ok(pk.clone()));
+        assert_eq!(recovery_id(&pk, &digest, &sig.r, &sig.s), Some(sig.recid));
+
+        // the high-s twin verifies too, and recovers with the other parity
+        let high_s: FE = ECScalar::from(&(FE::q() - sig.s.to_big_int()));
+        assert!(!is_low_s(&high_s));
+        assert_eq!(verify(&pk, &digest, &sig.r, &high_s), Ok(()));
+        let high_recid = recovery_id(&pk, &digest, &sig.r, &high_s).unwrap();
+        assert_eq!(high_recid, sig.recid ^ 1);
+        assert_eq!(normalize_s(&high_s, high_recid), (sig.s.clone(), sig.recid));
+
+        // cross-check with the backend's own ECDSA
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&sig.r.to_bytes_be());
+        compact[32..].copy_from_slice(&sig.s.to_bytes_be());
+        let recovered = secp256k1::recover(
+            &secp256k1::Message::parse(&digest),
+            &secp256k1::Signature::parse(&compact),
+            &secp256k1::RecoveryId::parse(sig.recid).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(recovered, pk.get_element());
+
+        digest[0] ^= 1;
+        assert_eq!(verify(&pk, &digest, &sig.r, &sig.s), Err(InvalidSig));
+    }
+}
@@ -0,0 +1,143 @@
+//! ECIES over secp256k1 for the messages the protocol sends privately.
+//!
+//! GG18 assumes private channels for the keygen shares and the MtA
+//! responses. Here each message is encrypted to the recipient's long-term
+//! identity key: a fresh ephemeral key gives an ECDH secret, SHA-256 of that
+//! secret and the ephemeral key gives an AES-256-GCM key, and the associated
+//! data binds the ciphertext to where it belongs (sender, recipient, round,
+//! session) so it cannot be replayed into another slot.
+#![cfg(feature = "signing")]
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::{rngs::OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::Error::{self, InvalidCiphertext};
+
+const NONCE_BYTES_LEN: usize = 12;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EciesCiphertext {
+    pub ephemeral: GE,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+fn derive_key(shared: &GE, ephemeral: &GE) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"tss-wasm ecies");
+    hasher.update(shared.to_bytes(true));
+    hasher.update(ephemeral.to_bytes(true));
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&hasher.finalize());
+    key
+}
+
+/// Encrypts `plaintext` to the holder of the secret key for `recipient`.
+pub fn encrypt(recipient: &GE, aad: &[u8], plaintext: &[u8]) -> EciesCiphertext {
+    let g: GE = ECPoint::generator();
+    let ephemeral_sk: FE = ECScalar::new_random();
+    let ephemeral = &g * &ephemeral_sk;
+    let key = derive_key(&(recipient * &ephemeral_sk), &ephemeral);
+    let cipher = Aes256Gcm::new(aes_gcm::Key::from_slice(&key));
+
+    let mut nonce = [0u8; NONCE_BYTES_LEN];
+    let mut rng = OsRng::new().unwrap();
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .expect("encryption failure!");
+
+    EciesCiphertext {
+        ephemeral,
+        nonce: nonce.to_vec(),
+        ciphertext,
+    }
+}
+
+/// Decrypts with the recipient's secret key. Fails if the key, the
+/// associated data or the ciphertext do not match.
+pub fn decrypt(secret: &FE, aad: &[u8], ct: &EciesCiphertext) -> Result<Vec<u8>, Error> {
+    if ct.nonce.len() != NONCE_BYTES_LEN {
+        return Err(InvalidCiphertext);
+    }
+    let key = derive_key(&(&ct.ephemeral * secret), &ct.ephemeral);
+    let cipher = Aes256Gcm::new(aes_gcm::Key::from_slice(&key));
+    cipher
+        .decrypt(
+            Nonce::from_slice(&ct.nonce),
+            Payload {
+                msg: &ct.ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| InvalidCiphertext)
+}
+
+/// Encrypts the json encoding of `value`, e.g. a VSS share or a `MessageB`.
+pub fn encrypt_message<T: Serialize>(recipient: &GE, aad: &[u8], value: &T) -> EciesCiphertext {
+    encrypt(
+        recipient,
+        aad,
+        serde_json::to_string(value).unwrap().as_bytes(),
+    )
+}
+
+pub fn decrypt_message<T: DeserializeOwned>(
+    secret: &FE,
+    aad: &[u8],
+    ct: &EciesCiphertext,
+) -> Result<T, Error> {
+    let plaintext = decrypt(secret, aad, ct)?;
+    serde_json::from_slice(&plaintext).map_err(|_| InvalidCiphertext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_share_roundtrip() {
+        let g: GE = ECPoint::generator();
+        let secret: FE = ECScalar::new_random();
+        let public = &g * &secret;
+        let share: FE = ECScalar::new_random();
+
+        let ct = encrypt_message(&public, b"keygen round3 1->2", &share);
+        let opened: FE = decrypt_message(&secret, b"keygen round3 1->2", &ct).unwrap();
+        assert_eq!(opened, share);
+
+        // bound to its slot and to the recipient
+        assert_eq!(
+            decrypt(&secret, b"keygen round3 1->3", &ct),
+            Err(InvalidCiphertext)
+        );
+        let other: FE = ECScalar::new_random();
+        assert_eq!(
+            decrypt(&other, b"keygen round3 1->2", &ct),
+            Err(InvalidCiphertext)
+        );
+
+        let mut tampered = ct.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert_eq!(
+            decrypt(&secret, b"keygen round3 1->2", &tampered),
+            Err(InvalidCiphertext)
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! Ethereum transactions signed with the group key.
+//!
+//! A signing ceremony ends with r, s and a recovery id; a wallet wants the
+//! raw transaction to hand to `eth_sendRawTransaction`. `Transaction`
+//! covers legacy transactions, with EIP-155 replay protection when they
+//! name a chain, and EIP-1559 ones. It gives the digest to sign, queues it
+//! on a `Signer` with the unsigned transaction as the request's metadata so
+//! that policies see what they approve, and RLP-encodes the signed
+//! transaction once the signature comes back.
+//!
+//! In json, as in the node RPC, field names are camelCase, addresses and
+//! data are `0x` hex, and wei amounts are `0x` hex quantities.
+#![cfg(feature = "ethereum")]
+
+use std::fmt;
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::address::keccak256;
+use crate::gg_2018::party_i::SigningDigest;
+use crate::gg_2018::threshold_signature::ThresholdSignature;
+use crate::signer::{QuorumSelector, RequestId, Signer};
+
+const EIP1559_TYPE: u8 = 0x02;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum EthereumError {
+    /// The signature is over another digest than the transaction's.
+    DigestMismatch,
+    /// The recovery id says R.x overflowed q, which Ethereum cannot express.
+    Unexpressible,
+}
+
+/// `N` bytes, `0x` hex in json.
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub struct FixedBytes<const N: usize>(pub [u8; N]);
+
+pub type Address = FixedBytes<20>;
+pub type StorageKey = FixedBytes<32>;
+
+impl<const N: usize> fmt::Display for FixedBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl<const N: usize> Serialize for FixedBytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedBytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = data::deserialize(deserializer)?;
+        let bytes = <[u8; N]>::try_from(bytes)
+            .map_err(|_| de::Error::custom(format!("expected {} bytes", N)))?;
+        Ok(FixedBytes(bytes))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<StorageKey>,
+}
+
+/// A transaction from before EIP-2718. With a `chain_id` it is signed as
+/// EIP-155 specifies, without one it can be replayed on any chain.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyTransaction {
+    pub chain_id: Option<u64>,
+    pub nonce: u64,
+    #[serde(with = "quantity")]
+    pub gas_price: u128,
+    pub gas_limit: u64,
+    /// `None` creates a contract.
+    pub to: Option<Address>,
+    #[serde(with = "quantity")]
+    pub value: u128,
+    #[serde(with = "data", default)]
+    pub data: Vec<u8>,
+}
+
+/// An EIP-1559 transaction, type 2.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    #[serde(with = "quantity")]
+    pub max_priority_fee_per_gas: u128,
+    #[serde(with = "quantity")]
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: Option<Address>,
+    #[serde(with = "quantity")]
+    pub value: u128,
+    #[serde(with = "data", default)]
+    pub data: Vec<u8>,
+    #[serde(default)]
+    pub access_list: Vec<AccessListItem>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Transaction {
+    Legacy(LegacyTransaction),
+    Eip1559(Eip1559Transaction),
+}
+
+impl Transaction {
+    /// The bytes whose keccak-256 is signed.
+    pub fn signing_payload(&self) -> Vec<u8> {
+        match self {
+            Transaction::Legacy(tx) => {
+                let mut fields = tx.fields();
+                if let Some(chain_id) = tx.chain_id {
+                    uint(u128::from(chain_id), &mut fields);
+                    uint(0, &mut fields);
+                    uint(0, &mut fields);
+                }
+                list(&fields)
+            }
+            Transaction::Eip1559(tx) => {
+                let mut payload = vec![EIP1559_TYPE];
+                payload.extend_from_slice(&list(&tx.fields()));
+                payload
+            }
+        }
+    }
+
+    pub fn signing_digest(&self) -> SigningDigest {
+        SigningDigest::from_prehashed(&keccak256(&self.signing_payload())).unwrap()
+    }
+
+    /// Queues the transaction on `signer`. The unsigned transaction goes
+    /// along as `SigningRequest::metadata`, for the signing policy.
+    pub fn submit<S: QuorumSelector>(&self, signer: &mut Signer<S>) -> RequestId {
+        signer.submit(self.signing_digest(), self.signing_payload())
+    }
+
+    /// The signed transaction, as `eth_sendRawTransaction` takes it.
+    pub fn encode_signed(&self, signature: &ThresholdSignature) -> Result<Vec<u8>, EthereumError> {
+        if signature.digest != *self.signing_digest().as_bytes() {
+            return Err(EthereumError::DigestMismatch);
+        }
+        let r = signature.r.to_bytes_be();
+        let s = signature.s.to_bytes_be();
+        match self {
+            Transaction::Legacy(tx) => {
+                let v = signature
+                    .ethereum_v(tx.chain_id)
+                    .map_err(|_| EthereumError::Unexpressible)?;
+                let mut fields = tx.fields();
+                uint(u128::from(v), &mut fields);
+                bytes(strip_zeros(&r), &mut fields);
+                bytes(strip_zeros(&s), &mut fields);
+                Ok(list(&fields))
+            }
+            Transaction::Eip1559(tx) => {
+                if signature.recid > 1 {
+                    return Err(EthereumError::Unexpressible);
+                }
+                let mut fields = tx.fields();
+                uint(u128::from(signature.recid), &mut fields);
+                bytes(strip_zeros(&r), &mut fields);
+                bytes(strip_zeros(&s), &mut fields);
+                let mut raw = vec![EIP1559_TYPE];
+                raw.extend_from_slice(&list(&fields));
+                Ok(raw)
+            }
+        }
+    }
+}
+
+/// The hash a node knows a signed transaction by.
+pub fn transaction_hash(raw: &[u8]) -> [u8; 32] {
+    keccak256(raw)
+}
+
+impl LegacyTransaction {
+    // the RLP items before the chain id or the signature
+    fn fields(&self) -> Vec<u8> {
+        let mut fields = Vec::new();
+        uint(u128::from(self.nonce), &mut fields);
+        uint(self.gas_price, &mut fields);
+        uint(u128::from(self.gas_limit), &mut fields);
+        address(&self.to, &mut fields);
+        uint(self.value, &mut fields);
+        bytes(&self.data, &mut fields);
+        fields
+    }
+}
+
+impl Eip1559Transaction {
+    // the RLP items before the signature
+    fn fields(&self) -> Vec<u8> {
+        let mut fields = Vec::new();
+        uint(u128::from(self.chain_id), &mut fields);
+        uint(u128::from(self.nonce), &mut fields);
+        uint(self.max_priority_fee_per_gas, &mut fields);
+        uint(self.max_fee_per_gas, &mut fields);
+        uint(u128::from(self.gas_limit), &mut fields);
+        address(&self.to, &mut fields);
+        uint(self.value, &mut fields);
+        bytes(&self.data, &mut fields);
+        let mut access_list = Vec::new();
+        for item in &self.access_list {
+            let mut entry = Vec::new();
+            bytes(&item.address.0, &mut entry);
+            let mut keys = Vec::new();
+            for key in &item.storage_keys {
+                bytes(&key.0, &mut keys);
+            }
+            entry.extend_from_slice(&list(&keys));
+            access_list.extend_from_slice(&list(&entry));
+        }
+        fields.extend_from_slice(&list(&access_list));
+        fields
+    }
+}
+
+// RLP, from the yellow paper, appendix B: a string is a single byte below
+// 0x80 as is, else its length and then its bytes; a list is the length of
+// its encoded items and then the items
+
+fn length(len: usize, offset: u8, out: &mut Vec<u8>) {
+    if len < 56 {
+        out.push(offset + len as u8);
+    } else {
+        let len = len.to_be_bytes();
+        let len = strip_zeros(&len);
+        out.push(offset + 55 + len.len() as u8);
+        out.extend_from_slice(len);
+    }
+}
+
+fn bytes(value: &[u8], out: &mut Vec<u8>) {
+    if value.len() == 1 && value[0] < 0x80 {
+        out.push(value[0]);
+    } else {
+        length(value.len(), 0x80, out);
+        out.extend_from_slice(value);
+    }
+}
+
+// integers are big-endian without leading zeros, so 0 is the empty string
+fn uint(value: u128, out: &mut Vec<u8>) {
+    bytes(strip_zeros(&value.to_be_bytes()), out);
+}
+
+fn address(to: &Option<Address>, out: &mut Vec<u8>) {
+    match to {
+        Some(to) => bytes(&to.0, out),
+        None => bytes(&[], out),
+    }
+}
+
+fn list(items: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(items.len() + 9);
+    length(items.len(), 0xc0, &mut out);
+    out.extend_from_slice(items);
+    out
+}
+
+fn strip_zeros(value: &[u8]) -> &[u8] {
+    let start = value.iter().position(|b| *b != 0).unwrap_or(value.len());
+    &value[start..]
+}
+
+fn strip_prefix(s: &str) -> &str {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+/// `0x` hex byte strings.
+mod data {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hex::encode(value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(strip_prefix(&s)).map_err(de::Error::custom)
+    }
+}
+
+/// `0x` hex quantities, without leading zeros as the node RPC writes them.
+mod quantity {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u128::from_str_radix(strip_prefix(&s), 16).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::arithmetic::num_bigint::BigInt;
+    use crate::curv::arithmetic::traits::Converter;
+    use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+    use crate::curv::elliptic::curves::traits::{ECPoint, ECScalar};
+    use crate::ecdsa;
+    use crate::gg_2018::party_i::Signature;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn scalar(hex: &str) -> FE {
+        ECScalar::from(&BigInt::from_hex(hex))
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_rlp() {
+        let mut out = Vec::new();
+        bytes(b"dog", &mut out);
+        assert_eq!(out, hex::decode("83646f67").unwrap());
+        let mut items = Vec::new();
+        bytes(b"cat", &mut items);
+        bytes(b"dog", &mut items);
+        assert_eq!(list(&items), hex::decode("c88363617483646f67").unwrap());
+        let mut out = Vec::new();
+        uint(0, &mut out);
+        uint(15, &mut out);
+        uint(1024, &mut out);
+        assert_eq!(out, hex::decode("800f820400").unwrap());
+        let mut out = Vec::new();
+        bytes(&[0x61; 56], &mut out);
+        assert_eq!(&out[..2], &[0xb8, 56]);
+        assert_eq!(list(&[]), vec![0xc0]);
+    }
+
+    // the example of EIP-155
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_legacy_transaction() {
+        let json = r#"{
+            "type": "legacy",
+            "chainId": 1,
+            "nonce": 9,
+            "gasPrice": "0x4a817c800",
+            "gasLimit": 21000,
+            "to": "0x3535353535353535353535353535353535353535",
+            "value": "0xde0b6b3a7640000"
+        }"#;
+        let tx: Transaction = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            hex::encode(tx.signing_payload()),
+            "ec098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             80018080"
+        );
+        assert_eq!(
+            hex::encode(tx.signing_digest().as_bytes()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+
+        let g: GE = ECPoint::generator();
+        let public_key = &g * &scalar(&"46".repeat(32));
+        let sig = Signature {
+            r: scalar("28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276"),
+            s: scalar("67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"),
+        };
+        let signature =
+            ThresholdSignature::new(&sig, &public_key, &tx.signing_digest(), &[1, 2], None)
+                .unwrap();
+        assert_eq!(
+            hex::encode(tx.encode_signed(&signature).unwrap()),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a7640000\
+             8025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d899\
+             7f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+
+        let other: Transaction = serde_json::from_str(&json.replace("9,", "10,")).unwrap();
+        assert_eq!(
+            other.encode_signed(&signature),
+            Err(EthereumError::DigestMismatch)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_eip1559_transaction() {
+        let tx = Transaction::Eip1559(Eip1559Transaction {
+            chain_id: 5,
+            nonce: 0,
+            max_priority_fee_per_gas: 1_500_000_000,
+            max_fee_per_gas: 30_000_000_000,
+            gas_limit: 50_000,
+            to: None,
+            value: 0,
+            data: vec![0x60, 0x00],
+            access_list: vec![AccessListItem {
+                address: FixedBytes([0x11; 20]),
+                storage_keys: vec![FixedBytes([0; 32])],
+            }],
+        });
+        let json = serde_json::to_string(&tx).unwrap();
+        assert!(json.contains(r#""type":"eip1559""#));
+        assert!(json.contains(r#""maxFeePerGas":"0x6fc23ac00""#));
+        assert_eq!(serde_json::from_str::<Transaction>(&json).unwrap(), tx);
+
+        let payload = tx.signing_payload();
+        assert_eq!(payload[0], EIP1559_TYPE);
+        // a list of 79 bytes, opened by the chain id, nonce 0 and the fees
+        assert_eq!(&payload[1..7], &[0xf8, 79, 0x05, 0x80, 0x84, 0x59]);
+
+        let secret: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let public_key = &g * &secret;
+        let digest = tx.signing_digest();
+        let reference = ecdsa::sign(&secret, digest.as_bytes());
+        let sig = Signature {
+            r: reference.r,
+            s: reference.s,
+        };
+        let signature = ThresholdSignature::new(&sig, &public_key, &digest, &[1, 3], None).unwrap();
+        let raw = tx.encode_signed(&signature).unwrap();
+        assert_eq!(raw[0], EIP1559_TYPE);
+        // the unsigned fields, then y parity, r and s
+        let mut fields = match &tx {
+            Transaction::Eip1559(unsigned) => unsigned.fields(),
+            Transaction::Legacy(_) => unreachable!(),
+        };
+        uint(u128::from(signature.recid), &mut fields);
+        bytes(strip_zeros(&signature.r.to_bytes_be()), &mut fields);
+        bytes(strip_zeros(&signature.s.to_bytes_be()), &mut fields);
+        assert_eq!(&raw[1..], &list(&fields)[..]);
+        assert_eq!(transaction_hash(&raw), keccak256(&raw));
+    }
+}
@@ -0,0 +1,61 @@
+//! Key material for unit tests, shared so that every module's tests start
+//! from the same well-formed keys.
+//!
+//! The keys are built as keygen builds them, from one dealing per party,
+//! without running the rounds and their proofs.
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::FE;
+#[cfg(feature = "gg18")]
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::curv::elliptic::curves::traits::*;
+#[cfg(feature = "gg18")]
+use crate::gg_2018::party_i::{Keys, LocalKey, SharedKeys};
+
+/// One `t`-of-`n` dealing per party, as keygen's round three leaves them:
+/// each party's secret `u_i`, its VSS commitments and the shares it sends
+/// out.
+pub fn dealings(t: usize, n: usize) -> (Vec<FE>, Vec<VerifiableSS>, Vec<Vec<FE>>) {
+    let secrets: Vec<FE> = (0..n).map(|_| ECScalar::new_random()).collect();
+    let (vss_scheme_vec, shares) = secrets
+        .iter()
+        .map(|secret| VerifiableSS::share(t, n, secret))
+        .unzip();
+    (secrets, vss_scheme_vec, shares)
+}
+
+/// The group secret and every party's key of a `t`-of-`n` keygen, each with
+/// its own Paillier key: `x_i` sums the shares party `i` received, and the
+/// key holds all `n` VSS schemes.
+#[cfg(feature = "gg18")]
+pub fn dealer_keys(t: usize, n: usize) -> (FE, Vec<LocalKey>) {
+    let (secrets, vss_scheme_vec, shares) = dealings(t, n);
+    let secret = secrets.iter().fold(FE::zero(), |acc, u| acc + u);
+    let g: GE = ECPoint::generator();
+    let y = &g * &secret;
+    let party_keys_vec = secrets
+        .into_iter()
+        .enumerate()
+        .map(|(i, u_i)| Keys::create_from(u_i, i + 1))
+        .collect::<Vec<Keys>>();
+    let paillier_key_vec = party_keys_vec
+        .iter()
+        .map(|k| k.ek.clone())
+        .collect::<Vec<_>>();
+    let keys = party_keys_vec
+        .into_iter()
+        .enumerate()
+        .map(|(i, party_keys)| LocalKey {
+            party_keys,
+            shared_keys: SharedKeys {
+                y: y.clone(),
+                x_i: shares.iter().fold(FE::zero(), |acc, s| acc + &s[i]),
+            },
+            party_id: i as u16 + 1,
+            vss_scheme_vec: vss_scheme_vec.clone(),
+            paillier_key_vec: paillier_key_vec.clone(),
+            y_sum: y.clone(),
+        })
+        .collect();
+    (secret, keys)
+}
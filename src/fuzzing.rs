@@ -0,0 +1,343 @@
+//! Generators for fuzzing the wire types.
+//!
+//! Enabled with the `fuzzing` feature. `Arbitrary` builds structurally
+//! well-formed messages and proofs: scalars are nonzero and reduced,
+//! points are on the curve, Paillier moduli are odd with `nn = n^2`. They
+//! exercise the arithmetic and proof checks rather than the decoder, and
+//! almost none of them verify.
+//!
+//! `Malformed<T>` builds a well-formed value and then breaks one thing a
+//! receiver has to catch: a zero or oversized integer, a proof for the
+//! wrong point, a missing or surplus range proof, an even modulus.
+//!
+//! `strategy` and `malformed_strategy` hand both to proptest, drawing the
+//! input bytes from proptest so that its shrinking applies.
+#![cfg(feature = "fuzzing")]
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::arithmetic::traits::Converter;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::{DLogProof, ProveDLog};
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::gg_2018::party_i::{KeyGenBroadcastMessage1, KeyGenDecommitMessage1};
+use crate::gg_2018::range_proofs::AliceProof;
+use crate::paillier::zkproofs::{CompositeDLogProof, NICorrectKeyProof};
+use crate::paillier::EncryptionKey;
+
+// bytes of the largest well-formed integer, a 2048 bit modulus squared
+const MAX_BIG_INT_BYTES: usize = 512;
+// the correct key proof has 11 elements, see correct_key_ni.rs
+const MAX_SIGMA_VEC: usize = 16;
+// range proofs come one per counterparty
+const MAX_RANGE_PROOFS: usize = 8;
+
+fn big_int(u: &mut Unstructured, max_bytes: usize) -> Result<BigInt> {
+    // short input gives short integers rather than an error
+    let len = u.int_in_range(0..=max_bytes)?.min(u.len());
+    Ok(BigInt::from_bytes(u.bytes(len)?))
+}
+
+fn scalar(u: &mut Unstructured) -> Result<FE> {
+    let n = big_int(u, 32)? % FE::q();
+    let one = BigInt::from(1u32);
+    // the scalar codec rejects zero
+    Ok(ECScalar::from(if n == BigInt::from(0u32) {
+        &one
+    } else {
+        &n
+    }))
+}
+
+fn point(u: &mut Unstructured) -> Result<GE> {
+    let g: GE = ECPoint::generator();
+    Ok(&g * &scalar(u)?)
+}
+
+// zero, one past the largest well-formed size, or all ones
+fn bad_big_int(u: &mut Unstructured) -> Result<BigInt> {
+    Ok(match u.choose_index(3)? {
+        0 => BigInt::from(0u32),
+        1 => BigInt::from(1u32) << (8 * MAX_BIG_INT_BYTES + 8),
+        _ => BigInt::from_bytes(&[0xff; MAX_BIG_INT_BYTES]),
+    })
+}
+
+/// A value with one defect a receiver must reject.
+#[derive(Clone, Debug)]
+pub struct Malformed<T>(pub T);
+
+impl<'a> Arbitrary<'a> for EncryptionKey {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut bytes = u.bytes(u.len().min(256))?.to_vec();
+        bytes.resize(256, 0);
+        // 2048 bits and odd
+        bytes[0] |= 0x80;
+        bytes[255] |= 1;
+        let n = BigInt::from_bytes(&bytes);
+        Ok(EncryptionKey { nn: &n * &n, n })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<EncryptionKey> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut ek = EncryptionKey::arbitrary(u)?;
+        match u.choose_index(3)? {
+            0 => ek.n = &ek.n - BigInt::from(1u32),
+            1 => ek.nn = &ek.nn + BigInt::from(2u32),
+            _ => ek.n = bad_big_int(u)?,
+        }
+        Ok(Malformed(ek))
+    }
+}
+
+impl<'a> Arbitrary<'a> for DLogProof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(DLogProof {
+            pk: point(u)?,
+            pk_t_rand_commitment: point(u)?,
+            challenge_response: scalar(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<DLogProof> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let s: FE = scalar(u)?;
+        let proof = DLogProof::prove(&s);
+        // a proof made for another key
+        let wrong_key = DLogProof {
+            pk: point(u)?,
+            ..proof
+        };
+        Ok(Malformed(wrong_key))
+    }
+}
+
+impl<'a> Arbitrary<'a> for AliceProof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(AliceProof::from_parts(
+            big_int(u, MAX_BIG_INT_BYTES)?,
+            big_int(u, 32)?,
+            big_int(u, MAX_BIG_INT_BYTES)?,
+            big_int(u, MAX_BIG_INT_BYTES)?,
+            big_int(u, MAX_BIG_INT_BYTES)?,
+        ))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<AliceProof> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut fields = (0..5)
+            .map(|_| big_int(u, MAX_BIG_INT_BYTES))
+            .collect::<Result<Vec<BigInt>>>()?;
+        let broken = u.choose_index(5)?;
+        fields[broken] = bad_big_int(u)?;
+        let mut fields = fields.into_iter();
+        let mut next = || fields.next().unwrap();
+        Ok(Malformed(AliceProof::from_parts(
+            next(),
+            next(),
+            next(),
+            next(),
+            next(),
+        )))
+    }
+}
+
+impl<'a> Arbitrary<'a> for NICorrectKeyProof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=MAX_SIGMA_VEC)?;
+        Ok(NICorrectKeyProof {
+            sigma_vec: (0..len)
+                .map(|_| big_int(u, MAX_BIG_INT_BYTES / 2))
+                .collect::<Result<Vec<BigInt>>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<NICorrectKeyProof> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut proof = NICorrectKeyProof::arbitrary(u)?;
+        match u.choose_index(3)? {
+            0 => proof.sigma_vec.clear(),
+            1 => proof
+                .sigma_vec
+                .resize(4 * MAX_SIGMA_VEC, BigInt::from(1u32)),
+            _ => proof.sigma_vec.push(bad_big_int(u)?),
+        }
+        Ok(Malformed(proof))
+    }
+}
+
+impl<'a> Arbitrary<'a> for CompositeDLogProof {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(CompositeDLogProof {
+            x: big_int(u, MAX_BIG_INT_BYTES / 2)?,
+            y: big_int(u, MAX_BIG_INT_BYTES / 2)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<CompositeDLogProof> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut proof = CompositeDLogProof::arbitrary(u)?;
+        if u.arbitrary::<bool>()? {
+            proof.x = bad_big_int(u)?;
+        } else {
+            proof.y = bad_big_int(u)?;
+        }
+        Ok(Malformed(proof))
+    }
+}
+
+impl<'a> Arbitrary<'a> for MessageA {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let len = u.int_in_range(0..=MAX_RANGE_PROOFS)?;
+        Ok(MessageA {
+            c: big_int(u, MAX_BIG_INT_BYTES)?,
+            range_proofs: (0..len)
+                .map(|_| AliceProof::arbitrary(u))
+                .collect::<Result<Vec<AliceProof>>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<MessageA> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut m_a = MessageA::arbitrary(u)?;
+        match u.choose_index(3)? {
+            0 => m_a.c = bad_big_int(u)?,
+            1 => m_a
+                .range_proofs
+                .push(Malformed::<AliceProof>::arbitrary(u)?.0),
+            _ => m_a
+                .range_proofs
+                .resize(4 * MAX_RANGE_PROOFS, AliceProof::arbitrary(u)?),
+        }
+        Ok(Malformed(m_a))
+    }
+}
+
+impl<'a> Arbitrary<'a> for MessageB {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(MessageB {
+            c: big_int(u, MAX_BIG_INT_BYTES)?,
+            b_proof: DLogProof::arbitrary(u)?,
+            beta_tag_proof: DLogProof::arbitrary(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<MessageB> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut m_b = MessageB::arbitrary(u)?;
+        match u.choose_index(3)? {
+            0 => m_b.c = bad_big_int(u)?,
+            1 => m_b.b_proof = Malformed::<DLogProof>::arbitrary(u)?.0,
+            _ => m_b.beta_tag_proof = Malformed::<DLogProof>::arbitrary(u)?.0,
+        }
+        Ok(Malformed(m_b))
+    }
+}
+
+impl<'a> Arbitrary<'a> for KeyGenBroadcastMessage1 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(KeyGenBroadcastMessage1 {
+            e: EncryptionKey::arbitrary(u)?,
+            com: big_int(u, 32)?,
+            correct_key_proof: NICorrectKeyProof::arbitrary(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<KeyGenBroadcastMessage1> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut bc1 = KeyGenBroadcastMessage1::arbitrary(u)?;
+        match u.choose_index(3)? {
+            0 => bc1.e = Malformed::<EncryptionKey>::arbitrary(u)?.0,
+            1 => bc1.com = bad_big_int(u)?,
+            _ => bc1.correct_key_proof = Malformed::<NICorrectKeyProof>::arbitrary(u)?.0,
+        }
+        Ok(Malformed(bc1))
+    }
+}
+
+impl<'a> Arbitrary<'a> for KeyGenDecommitMessage1 {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(KeyGenDecommitMessage1 {
+            blind_factor: big_int(u, 32)?,
+            y_i: point(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Malformed<KeyGenDecommitMessage1> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut decom = KeyGenDecommitMessage1::arbitrary(u)?;
+        decom.blind_factor = bad_big_int(u)?;
+        Ok(Malformed(decom))
+    }
+}
+
+// enough input for the largest value above
+const STRATEGY_INPUT_BYTES: usize = 8 * 1024;
+
+/// A proptest strategy over the `Arbitrary` values of `T`.
+pub fn strategy<T>() -> impl Strategy<Value = T>
+where
+    T: for<'a> Arbitrary<'a> + std::fmt::Debug,
+{
+    vec(any::<u8>(), 0..STRATEGY_INPUT_BYTES).prop_filter_map("input too short", |bytes| {
+        T::arbitrary(&mut Unstructured::new(&bytes)).ok()
+    })
+}
+
+/// A proptest strategy over malformed values of `T`.
+pub fn malformed_strategy<T>() -> impl Strategy<Value = T>
+where
+    Malformed<T>: for<'a> Arbitrary<'a>,
+    T: std::fmt::Debug,
+{
+    strategy::<Malformed<T>>().prop_map(|m| m.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_integer::Integer;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_generators() {
+        let bytes = (0..4096).map(|i| (i * 7 + 3) as u8).collect::<Vec<u8>>();
+        let mut u = Unstructured::new(&bytes);
+
+        let m_b = MessageB::arbitrary(&mut u).unwrap();
+        let json = serde_json::to_string(&m_b).unwrap();
+        let decoded: MessageB = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.c, m_b.c);
+
+        let bad = Malformed::<DLogProof>::arbitrary(&mut u).unwrap().0;
+        assert!(DLogProof::verify(&bad).is_err());
+        let bad = Malformed::<EncryptionKey>::arbitrary(&mut u).unwrap().0;
+        let well_formed = bad.n.bits() == 2048 && &bad.n * &bad.n == bad.nn && bad.n.is_odd();
+        assert!(!well_formed);
+    }
+
+    proptest! {
+        #[test]
+        fn test_malformed_message_a_rejected(m_a in malformed_strategy::<MessageA>()) {
+            let ek = EncryptionKey::arbitrary(&mut Unstructured::new(&[0x5a; 256])).unwrap();
+            let b: FE = ECScalar::new_random();
+            prop_assert!(MessageB::b(&b, &ek, m_a, &[], b"session", 2).is_err());
+        }
+    }
+}
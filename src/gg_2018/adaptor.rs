@@ -0,0 +1,317 @@
+#![allow(non_snake_case)]
+/*
+    Adaptor signatures from the threshold signing flow.
+
+    An adaptor signature, or pre-signature, over a digest is encrypted to an
+    adaptor point Y = y G. It is not a valid ECDSA signature, but whoever
+    learns y completes it into one, and whoever sees both the pre-signature
+    and the completed signature learns y. Atomic swaps and discreet log
+    contracts are built on that exchange.
+
+    GG18 signs with R = k^-1 G, which the signers compute as delta^-1 times
+    the sum of their Gamma_i = gamma_i G. For a pre-signature each signer
+    also publishes gamma_i Y, with a proof that it has the discrete log of
+    Gamma_i, and r is taken from R' = k^-1 Y instead of R. The s' = k (m + r x)
+    the signers add up to then satisfies R = s'^-1 (m G + r X), and
+    (r, s' / y) is an ordinary signature with nonce point R'. The phase 5
+    checks run against R unchanged.
+
+    `AdaptorSignature` keeps the signers' nonce shares and delta^-1, so that
+    a counterparty checks without any secret that the pre-signature
+    completes to a signature under the group key once y is known.
+*/
+
+use merlin::Transcript;
+
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::{message_point, Signature, SigningDigest};
+use crate::gg_2018::threshold_signature::ThresholdSignature;
+use crate::impl_canonical_encode;
+use num_integer::Integer;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum AdaptorError {
+    /// A nonce share whose proof does not verify.
+    InvalidProof,
+    /// A pre-signature that does not complete to a signature under the key.
+    InvalidPresignature,
+    /// A secret that is not the discrete log of the adaptor point.
+    WrongSecret,
+    /// A signature that is not the completion of the pre-signature.
+    NotCompleted,
+}
+
+/// Chaum-Pedersen proof that `x G` and `x Y` have the same discrete log `x`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct DleqProof {
+    pub a_g: GE,
+    pub a_y: GE,
+    pub z: FE,
+}
+
+impl DleqProof {
+    pub fn prove(x: &FE, base: &GE) -> DleqProof {
+        let g: GE = ECPoint::generator();
+        DleqProof::prove_with_bases(x, &g, base)
+    }
+
+    /// `prove` with `h` in place of G, for `x h` and `x base`.
+    pub fn prove_with_bases(x: &FE, h: &GE, base: &GE) -> DleqProof {
+        let w: FE = ECScalar::new_random();
+        let a_g = h * &w;
+        let a_y = base * &w;
+        let c = dleq_challenge(h, &(h * x), base, &(base * x), &a_g, &a_y);
+        DleqProof {
+            a_g,
+            a_y,
+            z: w + c * x,
+        }
+    }
+
+    /// Checks the proof for `x_g = x G` and `x_base = x base`.
+    pub fn verify(&self, x_g: &GE, base: &GE, x_base: &GE) -> Result<(), AdaptorError> {
+        let g: GE = ECPoint::generator();
+        self.verify_with_bases(&g, x_g, base, x_base)
+    }
+
+    /// Checks the proof for `x_h = x h` and `x_base = x base`.
+    pub fn verify_with_bases(
+        &self,
+        h: &GE,
+        x_h: &GE,
+        base: &GE,
+        x_base: &GE,
+    ) -> Result<(), AdaptorError> {
+        let c = dleq_challenge(h, x_h, base, x_base, &self.a_g, &self.a_y);
+        if h * &self.z == self.a_g.clone() + &(x_h * &c)
+            && base * &self.z == self.a_y.clone() + &(x_base * &c)
+        {
+            Ok(())
+        } else {
+            Err(AdaptorError::InvalidProof)
+        }
+    }
+}
+
+impl_canonical_encode!(DleqProof { a_g, a_y, z });
+
+fn dleq_challenge(h: &GE, x_h: &GE, base: &GE, x_base: &GE, a_g: &GE, a_y: &GE) -> FE {
+    let mut transcript = Transcript::new(b"tss-wasm adaptor dleq");
+    transcript.append_canonical(b"h", h);
+    transcript.append_canonical(b"x_g", x_h);
+    transcript.append_canonical(b"base", base);
+    transcript.append_canonical(b"x_base", x_base);
+    transcript.append_canonical(b"a_g", a_g);
+    transcript.append_canonical(b"a_y", a_y);
+    transcript.challenge_scalar(b"challenge")
+}
+
+/// A signer's gamma_i Y, broadcast with its phase 4 decommitment to
+/// Gamma_i = gamma_i G.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct NonceShare {
+    pub gamma_y: GE,
+    pub proof: DleqProof,
+}
+
+impl NonceShare {
+    pub fn new(gamma_i: &FE, adaptor_point: &GE) -> NonceShare {
+        NonceShare {
+            gamma_y: adaptor_point * gamma_i,
+            proof: DleqProof::prove(gamma_i, adaptor_point),
+        }
+    }
+
+    pub fn verify(&self, g_gamma_i: &GE, adaptor_point: &GE) -> Result<(), AdaptorError> {
+        self.proof.verify(g_gamma_i, adaptor_point, &self.gamma_y)
+    }
+}
+
+/// R' = delta^-1 times the sum of every signer's gamma_i Y.
+pub fn adapted_nonce(shares: &[NonceShare], delta_inv: &FE) -> GE {
+    let mut points = shares.iter().map(|share| share.gamma_y.clone());
+    let head = points.next().expect("no nonce shares");
+    points.fold(head, |acc, point| acc + &point) * delta_inv
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AdaptorSignature {
+    /// s' = k (m + r x), with r the x coordinate of the adapted nonce.
+    pub s: FE,
+    pub public_key: GE,
+    pub digest: SigningDigest,
+    pub adaptor_point: GE,
+    pub delta_inv: FE,
+    /// Each signer's Gamma_i and nonce share, in party order.
+    pub g_gamma: Vec<GE>,
+    pub nonce_shares: Vec<NonceShare>,
+    /// The party ids of the signers, counted from 1, ascending.
+    pub signers: Vec<u16>,
+}
+
+impl AdaptorSignature {
+    /// Checks the nonce shares and that the pre-signature completes to a
+    /// signature under `public_key`.
+    pub fn verify(&self) -> Result<(), AdaptorError> {
+        self.nonces().map(|_| ())
+    }
+
+    /// The r the completed signature will have.
+    pub fn r(&self) -> Result<FE, AdaptorError> {
+        self.nonces().map(|(_, adapted_R)| x_mod_q(&adapted_R))
+    }
+
+    /// Completes the pre-signature with the discrete log of the adaptor
+    /// point.
+    pub fn complete(&self, secret: &FE) -> Result<ThresholdSignature, AdaptorError> {
+        let g: GE = ECPoint::generator();
+        if &g * secret != self.adaptor_point {
+            return Err(AdaptorError::WrongSecret);
+        }
+        let sig = Signature {
+            r: self.r()?,
+            s: self.s.clone() * &secret.invert(),
+        };
+        ThresholdSignature::new(&sig, &self.public_key, &self.digest, &self.signers, None)
+            .map_err(|_| AdaptorError::InvalidPresignature)
+    }
+
+    /// Recovers the adaptor secret from the completed signature. Either sign
+    /// of s is accepted, as the signature may have been normalized.
+    pub fn extract(&self, sig: &Signature) -> Result<FE, AdaptorError> {
+        if sig.r != self.r()? || sig.s == FE::zero() {
+            return Err(AdaptorError::NotCompleted);
+        }
+        let g: GE = ECPoint::generator();
+        let secret = self.s.clone() * &sig.s.invert();
+        if &g * &secret == self.adaptor_point {
+            return Ok(secret);
+        }
+        let negated: FE = ECScalar::from(&(FE::q() - secret.to_big_int()));
+        if &g * &negated == self.adaptor_point {
+            Ok(negated)
+        } else {
+            Err(AdaptorError::NotCompleted)
+        }
+    }
+
+    // R and R', recomputed from the signers' shares
+    fn nonces(&self) -> Result<(GE, GE), AdaptorError> {
+        if self.g_gamma.is_empty() || self.g_gamma.len() != self.nonce_shares.len() {
+            return Err(AdaptorError::InvalidPresignature);
+        }
+        for (g_gamma_i, share) in self.g_gamma.iter().zip(&self.nonce_shares) {
+            share.verify(g_gamma_i, &self.adaptor_point)?;
+        }
+        let mut g_gamma = self.g_gamma.iter().cloned();
+        let head = g_gamma.next().unwrap();
+        let R = g_gamma.fold(head, |acc, point| acc + &point) * &self.delta_inv;
+        let adapted_R = adapted_nonce(&self.nonce_shares, &self.delta_inv);
+
+        if self.s == FE::zero() {
+            return Err(AdaptorError::InvalidPresignature);
+        }
+        let r = x_mod_q(&adapted_R);
+        let s_inv = self.s.invert();
+        let yr = &self.public_key * &(r * &s_inv);
+        let expected = match message_point(&self.digest.to_big_int()) {
+            Some(gm) => gm * &s_inv + &yr,
+            None => yr,
+        };
+        if expected != R {
+            return Err(AdaptorError::InvalidPresignature);
+        }
+        Ok((R, adapted_R))
+    }
+}
+
+fn x_mod_q(point: &GE) -> FE {
+    ECScalar::from(&point.x_coor().unwrap().mod_floor(&FE::q()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gg_2018::party_i::verify;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    // the signers' arithmetic done in the clear: k = delta^-1 sum gamma_i
+    // stands in for the MtA shares
+    fn presign(x: &FE, digest: &SigningDigest, y: &FE) -> AdaptorSignature {
+        let g: GE = ECPoint::generator();
+        let adaptor_point = &g * y;
+        let gammas: Vec<FE> = (0..3)
+            .map(|_| {
+                let gamma: FE = ECScalar::new_random();
+                gamma
+            })
+            .collect();
+        let delta: FE = ECScalar::new_random();
+        let delta_inv = delta.invert();
+        let gamma_sum = gammas[1..]
+            .iter()
+            .fold(gammas[0].clone(), |acc, gamma| acc + gamma);
+        // R = k^-1 G = delta^-1 sum gamma_i G
+        let k = (gamma_sum * &delta_inv).invert();
+        let nonce_shares: Vec<NonceShare> = gammas
+            .iter()
+            .map(|gamma| NonceShare::new(gamma, &adaptor_point))
+            .collect();
+        let adapted_R = adapted_nonce(&nonce_shares, &delta_inv);
+        let r = x_mod_q(&adapted_R);
+        let s = k * &(digest.to_scalar() + r * x);
+        AdaptorSignature {
+            s,
+            public_key: &g * x,
+            digest: digest.clone(),
+            adaptor_point,
+            delta_inv,
+            g_gamma: gammas.iter().map(|gamma| &g * gamma).collect(),
+            nonce_shares,
+            signers: vec![1, 2, 3],
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_complete_and_extract() {
+        let x: FE = ECScalar::new_random();
+        let y: FE = ECScalar::new_random();
+        let digest = SigningDigest::from_prehashed(&[7u8; 32]).unwrap();
+        let presig = presign(&x, &digest, &y);
+        presig.verify().unwrap();
+
+        let sig = presig.complete(&y).unwrap();
+        assert!(verify(&sig.signature(), &sig.public_key, &digest.to_big_int()).is_ok());
+        assert_eq!(presig.extract(&sig.signature()).unwrap(), y);
+
+        let other: FE = ECScalar::new_random();
+        assert_eq!(presig.complete(&other), Err(AdaptorError::WrongSecret));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_tampered_presignature() {
+        let x: FE = ECScalar::new_random();
+        let y: FE = ECScalar::new_random();
+        let digest = SigningDigest::from_prehashed(&[7u8; 32]).unwrap();
+        let presig = presign(&x, &digest, &y);
+
+        let mut bad_s = presig.clone();
+        let e: FE = ECScalar::new_random();
+        bad_s.s = bad_s.s.clone() + &e;
+        assert_eq!(bad_s.verify(), Err(AdaptorError::InvalidPresignature));
+
+        // a share for some other point than gamma_i Y
+        let mut bad_share = presig.clone();
+        let g: GE = ECPoint::generator();
+        let z: FE = ECScalar::new_random();
+        bad_share.nonce_shares[1].gamma_y = &g * &z;
+        assert_eq!(bad_share.verify(), Err(AdaptorError::InvalidProof));
+    }
+}
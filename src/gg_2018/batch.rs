@@ -0,0 +1,600 @@
+#![allow(non_snake_case)]
+/*
+    Batch presigning: phases 1 to 4 of GG18 for many signatures at once.
+
+    Every message of a round carries one entry per signature in the batch, so
+    a batch of any size costs the four rounds of a single signature:
+
+    1. broadcast `BatchRound1`: the commitments to g^gamma_i and the
+       encryptions of k_i,
+    2. p2p `BatchRound2`: both MtA responses for every signature,
+    3. broadcast `BatchRound3`: the delta_i,
+    4. broadcast `BatchRound4`: the openings of the round 1 commitments.
+
+    The round 1 commitments are bound to the session and the committing
+    party, so a signer can not echo another signer's commitment and later
+    open it to a g^gamma of its choosing.
+
+    The result is one `Presignature` per signature, to be put into a
+    `PresignatureStore` and consumed by one message each. Phase 5 then runs
+    as usual, with the messages of all signatures sent together.
+
+    Messages name their sender by 0-based party index, as in `signers_vec`.
+
+    A batch may be started by more than t + 1 online parties. Whoever has not
+    delivered round 1 by the time `round2` is called is left out, and the
+    survivors interpolate their w_i over the remaining set: nothing sent in
+    round 1 depends on w_i, so this is as safe as having started with that
+    set. Every round 2 message names the set it was computed for and round 3
+    refuses a mismatch. A party that drops out after round 1 still fails the
+    batch: from round 2 the MtA shares are bound to w_i over that set, and
+    each one is needed. Single signatures tolerate dropouts in the same
+    rounds, see `gg18_sign_client_set_online`.
+*/
+
+use crate::curv::cryptographic_primitives::commitments::bound_commitment::{
+    Commit, CommitmentContext, Open,
+};
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::gg_2018::party_i::{LocalKey, Parameters, SignKeys};
+use crate::gg_2018::presign::Presignature;
+use crate::gg_2018::quorum::QuorumKeys;
+use crate::paillier::{DecryptionKey, EncryptionKey};
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum BatchError {
+    EmptyBatch,
+    WrongSigners,
+    /// Fewer than t + 1 parties delivered round 1.
+    TooFewSigners,
+    /// A round 2 message was computed for a different set of signers.
+    SignerSetMismatch,
+    WrongRound,
+    /// A signer's message is missing, duplicated or of the wrong size.
+    InvalidMessage,
+    InvalidProof,
+    InvalidCommitment,
+    /// The key's keygen commitments are malformed.
+    InvalidKey,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchRound1 {
+    pub from: usize,
+    pub coms: Vec<Commit>,
+    pub m_a: Vec<MessageA>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchRound2 {
+    pub from: usize,
+    pub to: usize,
+    pub signers: Vec<usize>,
+    pub m_b_gamma: Vec<MessageB>,
+    pub m_b_w: Vec<MessageB>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchRound3 {
+    pub from: usize,
+    pub deltas: Vec<FE>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchRound4 {
+    pub from: usize,
+    pub g_gamma: Vec<GE>,
+    pub opens: Vec<Open>,
+}
+
+/// One party's state through the four rounds of a batch.
+pub struct BatchSigner {
+    session_id: String,
+    quorum: QuorumKeys,
+    vss_scheme_vec: Vec<VerifiableSS>,
+    x_i: FE,
+    signer_count: usize,
+    dk: DecryptionKey,
+    paillier_key_vec: Vec<EncryptionKey>,
+    y: GE,
+    round: usize,
+    keys: Vec<SignKeys>,
+    opens: Vec<Open>,
+    // per other signer, in `peers()` order
+    peer_round1: Vec<BatchRound1>,
+    peer_beta: Vec<Vec<FE>>,
+    peer_ni: Vec<Vec<FE>>,
+    peer_b_proofs: Vec<Vec<DLogProof>>,
+    // per signature
+    sigma: Vec<FE>,
+    delta: Vec<FE>,
+    delta_inv: Vec<FE>,
+}
+
+impl BatchSigner {
+    /// Starts a batch of `size` signatures among the 0-based `online`
+    /// parties, at least t + 1, and returns the round 1 broadcast.
+    /// `session_id` must be unique to the batch and known to all parties.
+    pub fn new(
+        key: &LocalKey,
+        session_id: &str,
+        online: &[usize],
+        size: usize,
+    ) -> Result<(BatchSigner, BatchRound1), BatchError> {
+        if size == 0 {
+            return Err(BatchError::EmptyBatch);
+        }
+        let index = usize::from(key.party_id) - 1;
+        let vss_params = &key
+            .vss_scheme_vec
+            .first()
+            .ok_or(BatchError::InvalidKey)?
+            .parameters;
+        let params = Parameters::new(vss_params.threshold, vss_params.share_count)
+            .map_err(|_| BatchError::WrongSigners)?;
+        params
+            .check_online_set(online)
+            .map_err(|_| BatchError::WrongSigners)?;
+        if !online.contains(&index) || key.paillier_key_vec.len() != vss_params.share_count {
+            return Err(BatchError::WrongSigners);
+        }
+
+        let quorum = QuorumKeys::new(&key.vss_scheme_vec, &key.shared_keys.x_i, index, online)
+            .map_err(|_| BatchError::InvalidKey)?;
+        let keys = (0..size)
+            .map(|_| SignKeys::create_from_quorum(&quorum))
+            .collect::<Vec<SignKeys>>();
+        let context = commitment_context(session_id, index);
+        let (coms, opens): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .map(|k| Commit::new(&context, &k.g_gamma_i))
+            .unzip();
+        let m_a = keys
+            .iter()
+            .map(|k| MessageA::a(&k.k_i, &key.party_keys.ek, &[]).0)
+            .collect::<Vec<MessageA>>();
+
+        let signer = BatchSigner {
+            session_id: session_id.to_string(),
+            quorum,
+            vss_scheme_vec: key.vss_scheme_vec.clone(),
+            x_i: key.shared_keys.x_i.clone(),
+            signer_count: params.signer_count(),
+            dk: key.party_keys.dk.clone(),
+            paillier_key_vec: key.paillier_key_vec.clone(),
+            y: key.y_sum.clone(),
+            round: 1,
+            keys,
+            opens,
+            peer_round1: Vec::new(),
+            peer_beta: Vec::new(),
+            peer_ni: Vec::new(),
+            peer_b_proofs: Vec::new(),
+            sigma: Vec::new(),
+            delta: Vec::new(),
+            delta_inv: Vec::new(),
+        };
+        let round1 = BatchRound1 {
+            from: index,
+            coms,
+            m_a,
+        };
+        Ok((signer, round1))
+    }
+
+    pub fn size(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn peers(&self) -> Vec<usize> {
+        let index = self.quorum.index();
+        self.quorum
+            .signers()
+            .iter()
+            .cloned()
+            .filter(|j| *j != index)
+            .collect()
+    }
+
+    fn enter_round(&mut self, round: usize) -> Result<(), BatchError> {
+        if self.round + 1 != round {
+            return Err(BatchError::WrongRound);
+        }
+        self.round = round;
+        Ok(())
+    }
+
+    // orders the messages as `peers()`, exactly one from each
+    fn by_peer<'a, T>(
+        &self,
+        messages: &'a [T],
+        from: impl Fn(&T) -> usize,
+    ) -> Result<Vec<&'a T>, BatchError> {
+        let peers = self.peers();
+        // a broadcast channel may hand our own message back
+        let index = self.quorum.index();
+        let messages = messages
+            .iter()
+            .filter(|m| from(m) != index)
+            .collect::<Vec<&T>>();
+        if messages.len() != peers.len() {
+            return Err(BatchError::InvalidMessage);
+        }
+        peers
+            .iter()
+            .map(|j| {
+                messages
+                    .iter()
+                    .find(|m| from(m) == *j)
+                    .copied()
+                    .ok_or(BatchError::InvalidMessage)
+            })
+            .collect()
+    }
+
+    /// Answers the encryptions of k_j of every other signer, with gamma_i
+    /// and w_i. Returns the p2p messages, one per other signer. Online
+    /// parties missing from `round1` are dropped from the signer set.
+    pub fn round2(&mut self, round1: &[BatchRound1]) -> Result<Vec<BatchRound2>, BatchError> {
+        self.enter_round(2)?;
+        let size = self.size();
+        let index = self.quorum.index();
+        let online = self.quorum.signers().to_vec();
+        let mut survivors = vec![index];
+        for message in round1.iter().filter(|m| m.from != index) {
+            if !online.contains(&message.from) || survivors.contains(&message.from) {
+                return Err(BatchError::InvalidMessage);
+            }
+            survivors.push(message.from);
+        }
+        if survivors.len() < self.signer_count {
+            return Err(BatchError::TooFewSigners);
+        }
+        if survivors.len() < online.len() {
+            self.quorum = QuorumKeys::new(&self.vss_scheme_vec, &self.x_i, index, &survivors)
+                .map_err(|_| BatchError::InvalidKey)?;
+            let g_w_i = self.quorum.g_w(index).unwrap();
+            for keys in self.keys.iter_mut() {
+                keys.w_i = self.quorum.w_i().clone();
+                keys.g_w_i = g_w_i.clone();
+            }
+        }
+        let messages = self.by_peer(round1, |m| m.from)?;
+        if messages
+            .iter()
+            .any(|m| m.coms.len() != size || m.m_a.len() != size)
+        {
+            return Err(BatchError::InvalidMessage);
+        }
+
+        let context = self.session_id.as_bytes();
+        let bob = index as u16 + 1;
+        let mut responses = Vec::new();
+        for message in messages.iter() {
+            let ek = &self.paillier_key_vec[message.from];
+            let mut m_b_gamma = Vec::with_capacity(size);
+            let mut m_b_w = Vec::with_capacity(size);
+            let mut beta = Vec::with_capacity(size);
+            let mut ni = Vec::with_capacity(size);
+            for (keys, m_a) in self.keys.iter().zip(message.m_a.iter()) {
+                let (m_b, beta_gamma, _, _) =
+                    MessageB::b(&keys.gamma_i, ek, m_a.clone(), &[], context, bob)
+                        .map_err(|_| BatchError::InvalidMessage)?;
+                m_b_gamma.push(m_b);
+                beta.push(beta_gamma);
+                let (m_b, beta_wi, _, _) =
+                    MessageB::b(&keys.w_i, ek, m_a.clone(), &[], context, bob)
+                        .map_err(|_| BatchError::InvalidMessage)?;
+                m_b_w.push(m_b);
+                ni.push(beta_wi);
+            }
+            responses.push(BatchRound2 {
+                from: index,
+                to: message.from,
+                signers: self.quorum.signers().to_vec(),
+                m_b_gamma,
+                m_b_w,
+            });
+            self.peer_beta.push(beta);
+            self.peer_ni.push(ni);
+        }
+        self.peer_round1 = messages.into_iter().cloned().collect();
+        Ok(responses)
+    }
+
+    /// Completes the MtA instances and returns the delta_i of every
+    /// signature for broadcast.
+    pub fn round3(&mut self, round2: &[BatchRound2]) -> Result<BatchRound3, BatchError> {
+        self.enter_round(3)?;
+        let size = self.size();
+        let index = self.quorum.index();
+        let messages = self.by_peer(round2, |m| m.from)?;
+        if messages
+            .iter()
+            .any(|m| m.to != index || m.m_b_gamma.len() != size || m.m_b_w.len() != size)
+        {
+            return Err(BatchError::InvalidMessage);
+        }
+        if messages
+            .iter()
+            .any(|m| m.signers.as_slice() != self.quorum.signers())
+        {
+            return Err(BatchError::SignerSetMismatch);
+        }
+
+        let mut peer_alpha = Vec::new();
+        let mut peer_miu = Vec::new();
+        let context = self.session_id.as_bytes();
+        for message in messages.iter() {
+            let g_w_j = self.quorum.g_w(message.from).unwrap();
+            let bob = message.from as u16 + 1;
+            let mut alpha = Vec::with_capacity(size);
+            let mut miu = Vec::with_capacity(size);
+            let mut b_proofs = Vec::with_capacity(size);
+            for ((keys, m_b_gamma), m_b_w) in self
+                .keys
+                .iter()
+                .zip(message.m_b_gamma.iter())
+                .zip(message.m_b_w.iter())
+            {
+                if !MessageB::verify_b_against_public(g_w_j, &m_b_w.b_proof.pk) {
+                    return Err(BatchError::InvalidProof);
+                }
+                let (alpha_ij, _) = m_b_gamma
+                    .verify_proofs_get_alpha(&self.dk, &keys.k_i, false, context, bob)
+                    .map_err(|_| BatchError::InvalidProof)?;
+                let (miu_ij, _) = m_b_w
+                    .verify_proofs_get_alpha(&self.dk, &keys.k_i, false, context, bob)
+                    .map_err(|_| BatchError::InvalidProof)?;
+                alpha.push(alpha_ij);
+                miu.push(miu_ij);
+                b_proofs.push(m_b_gamma.b_proof.clone());
+            }
+            peer_alpha.push(alpha);
+            peer_miu.push(miu);
+            self.peer_b_proofs.push(b_proofs);
+        }
+
+        let column =
+            |rows: &[Vec<FE>], b: usize| -> Vec<FE> { rows.iter().map(|r| r[b].clone()).collect() };
+        let mut deltas = Vec::with_capacity(size);
+        for (b, keys) in self.keys.iter().enumerate() {
+            deltas.push(keys.phase2_delta_i(&column(&peer_alpha, b), &column(&self.peer_beta, b)));
+            self.sigma
+                .push(keys.phase2_sigma_i(&column(&peer_miu, b), &column(&self.peer_ni, b)));
+        }
+        self.delta = deltas.clone();
+        Ok(BatchRound3 {
+            from: index,
+            deltas,
+        })
+    }
+
+    /// Inverts the delta of every signature and returns the openings of the
+    /// round 1 commitments for broadcast.
+    pub fn round4(&mut self, round3: &[BatchRound3]) -> Result<BatchRound4, BatchError> {
+        self.enter_round(4)?;
+        let size = self.size();
+        let messages = self.by_peer(round3, |m| m.from)?;
+        if messages.iter().any(|m| m.deltas.len() != size) {
+            return Err(BatchError::InvalidMessage);
+        }
+        self.delta_inv = self
+            .delta
+            .iter()
+            .enumerate()
+            .map(|(b, own)| {
+                let mut delta_vec = vec![own.clone()];
+                delta_vec.extend(messages.iter().map(|m| m.deltas[b].clone()));
+                SignKeys::phase3_reconstruct_delta(&delta_vec)
+            })
+            .collect();
+        Ok(BatchRound4 {
+            from: self.quorum.index(),
+            g_gamma: self.keys.iter().map(|k| k.g_gamma_i.clone()).collect(),
+            opens: self.opens.clone(),
+        })
+    }
+
+    /// Checks the openings against the round 1 commitments and the MtA
+    /// proofs, and returns the presignatures in batch order.
+    pub fn finish(mut self, round4: &[BatchRound4]) -> Result<Vec<Presignature>, BatchError> {
+        self.enter_round(5)?;
+        let size = self.size();
+        let messages = self.by_peer(round4, |m| m.from)?;
+        if messages
+            .iter()
+            .any(|m| m.g_gamma.len() != size || m.opens.len() != size)
+        {
+            return Err(BatchError::InvalidMessage);
+        }
+
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(b, keys)| {
+                let mut gamma_sum = keys.g_gamma_i.clone();
+                for ((message, round1), b_proofs) in messages
+                    .iter()
+                    .zip(self.peer_round1.iter())
+                    .zip(self.peer_b_proofs.iter())
+                {
+                    let g_gamma_j = &message.g_gamma[b];
+                    message.opens[b]
+                        .verify(
+                            &round1.coms[b],
+                            &commitment_context(&self.session_id, message.from),
+                            g_gamma_j,
+                        )
+                        .map_err(|_| BatchError::InvalidCommitment)?;
+                    // the MtA of gamma_j must have been run with the committed value
+                    if !MessageB::verify_b_against_public(g_gamma_j, &b_proofs[b].pk) {
+                        return Err(BatchError::InvalidProof);
+                    }
+                    gamma_sum = gamma_sum + g_gamma_j;
+                }
+                Ok(Presignature {
+                    k_i: keys.k_i.clone(),
+                    sigma_i: self.sigma[b].clone(),
+                    R: gamma_sum * &self.delta_inv[b],
+                    y: self.y.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+// round 1 is the only round with commitments
+fn commitment_context(session_id: &str, index: usize) -> CommitmentContext {
+    CommitmentContext::new(session_id, 1, index as u16 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+    use crate::gg_2018::party_i::{LocalSignature, SigningDigest};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_batch_signer_new() {
+        let (_, keys) = dealer_keys(1, 3);
+        let signers = vec![0, 2];
+        assert_eq!(
+            BatchSigner::new(&keys[1], "batch", &signers, 3).err(),
+            Some(BatchError::WrongSigners)
+        );
+        assert_eq!(
+            BatchSigner::new(&keys[0], "batch", &signers, 0).err(),
+            Some(BatchError::EmptyBatch)
+        );
+        let mut no_commitments = keys[0].clone();
+        no_commitments.vss_scheme_vec.clear();
+        assert_eq!(
+            BatchSigner::new(&no_commitments, "batch", &signers, 3).err(),
+            Some(BatchError::InvalidKey)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_batch_presign() {
+        let (_, keys) = dealer_keys(1, 3);
+        let signers = vec![0, 2];
+        let (mut parties, round1): (Vec<BatchSigner>, Vec<BatchRound1>) = signers
+            .iter()
+            .map(|i| BatchSigner::new(&keys[*i], "batch", &signers, 3).unwrap())
+            .unzip();
+        let round2 = parties
+            .iter_mut()
+            .flat_map(|p| p.round2(&round1).unwrap())
+            .collect::<Vec<BatchRound2>>();
+        assert_eq!(
+            parties[0].round2(&round1).unwrap_err(),
+            BatchError::WrongRound
+        );
+        let round3 = parties
+            .iter_mut()
+            .zip(signers.iter())
+            .map(|(p, i)| {
+                let inbox = round2
+                    .iter()
+                    .filter(|m| m.to == *i)
+                    .cloned()
+                    .collect::<Vec<BatchRound2>>();
+                p.round3(&inbox).unwrap()
+            })
+            .collect::<Vec<BatchRound3>>();
+        let round4 = parties
+            .iter_mut()
+            .map(|p| p.round4(&round3).unwrap())
+            .collect::<Vec<BatchRound4>>();
+        let presignatures = parties
+            .into_iter()
+            .map(|p| p.finish(&round4).unwrap())
+            .collect::<Vec<Vec<Presignature>>>();
+
+        for (b, (first, second)) in presignatures[0]
+            .iter()
+            .zip(presignatures[1].iter())
+            .enumerate()
+        {
+            assert_eq!(first.R, second.R);
+            let message = SigningDigest::from_prehashed(&[b as u8 + 1; 32]).unwrap();
+            let local_sig = |p: &Presignature| {
+                LocalSignature::phase5_local_sig(&p.k_i, &message, &p.R, &p.sigma_i, &p.y)
+            };
+            let other = local_sig(second);
+            assert!(local_sig(first).output_signature(&[other.s_i]).is_ok());
+        }
+        assert_ne!(presignatures[0][0].R, presignatures[0][1].R);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_batch_presign_dropout() {
+        let (_, keys) = dealer_keys(1, 3);
+        let online = vec![0, 1, 2];
+        let (mut parties, mut round1): (Vec<BatchSigner>, Vec<BatchRound1>) = online
+            .iter()
+            .map(|i| BatchSigner::new(&keys[*i], "batch", &online, 1).unwrap())
+            .unzip();
+        // party 1 goes offline before its round 1 arrives
+        parties.remove(1);
+        round1.remove(1);
+
+        let round2 = parties
+            .iter_mut()
+            .flat_map(|p| p.round2(&round1).unwrap())
+            .collect::<Vec<BatchRound2>>();
+        assert!(round2.iter().all(|m| m.signers == vec![0, 2]));
+        let round3 = parties
+            .iter_mut()
+            .enumerate()
+            .map(|(k, p)| {
+                let inbox = round2
+                    .iter()
+                    .filter(|m| m.to == 2 * k)
+                    .cloned()
+                    .collect::<Vec<BatchRound2>>();
+                p.round3(&inbox).unwrap()
+            })
+            .collect::<Vec<BatchRound3>>();
+        let round4 = parties
+            .iter_mut()
+            .map(|p| p.round4(&round3).unwrap())
+            .collect::<Vec<BatchRound4>>();
+        let presignatures = parties
+            .into_iter()
+            .map(|p| p.finish(&round4).unwrap().remove(0))
+            .collect::<Vec<Presignature>>();
+
+        let message = SigningDigest::from_prehashed(&[9u8; 32]).unwrap();
+        let local_sigs = presignatures
+            .iter()
+            .map(|p| LocalSignature::phase5_local_sig(&p.k_i, &message, &p.R, &p.sigma_i, &p.y))
+            .collect::<Vec<LocalSignature>>();
+        assert!(local_sigs[0]
+            .output_signature(&[local_sigs[1].s_i.clone()])
+            .is_ok());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_batch_presign_alone() {
+        // one survivor alone is below the threshold
+        let (_, keys) = dealer_keys(1, 3);
+        let online = vec![0, 1, 2];
+        let (mut alone, round1) = BatchSigner::new(&keys[0], "batch", &online, 1).unwrap();
+        assert_eq!(
+            alone.round2(&[round1]).unwrap_err(),
+            BatchError::TooFewSigners
+        );
+    }
+}
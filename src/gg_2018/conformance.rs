@@ -0,0 +1,929 @@
+/*
+    Conformance vectors: complete keygen and signing transcripts from a seed.
+
+    Other implementations of the protocol (Go, TypeScript) are checked
+    against this crate with fixtures. `generate` runs a keygen and a signing
+    in process with every party's secrets taken from one seed, and records
+    every message of every round in the order the relay carries them.
+    `ConformanceTranscript::replay` re-derives the secrets from the seed,
+    recomputes every value they determine and checks every proof, so a
+    transcript written by another implementation can be checked here, and
+    one written here can be checked there.
+
+    The secrets are the keygen polynomials and blinding of `KeygenSeed`, the
+    Paillier keys, k_i, gamma_i, the MtA randomness and masks, and the
+    phase 5 l_i and rho_i; each is an HMAC-SHA256 stream of the seed under a
+    label and the party indices. The nonces of the zero knowledge proofs and
+    the phase 5 commitment blinding stay random, so two transcripts of one
+    seed differ in those and agree in everything else. The replay does not
+    regenerate the Paillier keys, the slowest step, and relies on the
+    correct key and contribution proofs for them instead.
+
+    As the relay ceremonies, the MtA runs without range proofs. The seed and
+    the secret shares are in the clear: the vectors are test data, never a
+    key anyone uses.
+*/
+
+use hmac::{Hmac, Mac, NewMac};
+use num_integer::Integer;
+use sha2::Sha256;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
+use crate::curv::cryptographic_primitives::commitments::traits::Commitment;
+use crate::curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::{DLogProof, ProveDLog};
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::contribution::ContributionProof;
+use crate::gg_2018::mta::{beta_tag_bound, MessageA, MessageB};
+use crate::gg_2018::party_i::{
+    message_point, verify, KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, LocalSignature,
+    Parameters, Phase5ADecom1, Phase5Com1, Phase5Com2, Phase5DDecom2, SignBroadcastPhase1,
+    SignDecommitPhase1, SignKeys, Signature, SigningDigest,
+};
+use crate::gg_2018::seeded::KeygenSeed;
+use crate::paillier::EncryptionKey;
+use crate::Error;
+
+pub const TRANSCRIPT_VERSION: u8 = 1;
+/// The session of every transcript: the `KeygenSeed` context and the
+/// context of the contribution and MtA proofs.
+pub const CONFORMANCE_CONTEXT: &[u8] = b"tss-wasm conformance";
+const DERIVATION_DOMAIN: &[u8] = b"tss-wasm conformance secret";
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum ConformanceError {
+    /// t and n out of range, or signers not ascending party ids, at least
+    /// two and more than t.
+    InvalidParameters,
+    /// A seed `KeygenSeed` does not accept.
+    InvalidSeed,
+    /// Another transcript version, a digest out of range, or rounds with
+    /// the wrong number of messages.
+    Malformed,
+    /// A keygen message of party `party`, counted from 1, that is not the
+    /// one the seed gives or does not verify.
+    Keygen { round: u8, party: u16 },
+    /// As `Keygen`, for a signing message.
+    Sign { round: u8, party: u16 },
+    /// A public key or signature that does not follow from the rounds.
+    Output,
+}
+
+/// The messages of a keygen, each round indexed by party from 0.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeygenTranscript {
+    pub round1: Vec<KeyGenBroadcastMessage1>,
+    pub round2: Vec<(KeyGenDecommitMessage1, ContributionProof)>,
+    /// `round3[i][j]` is the share party i deals to party j.
+    pub round3: Vec<Vec<FE>>,
+    pub round4: Vec<VerifiableSS>,
+    pub round5: Vec<DLogProof>,
+}
+
+/// The messages of a signing, each round indexed by signer from 0.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignTranscript {
+    pub round1: Vec<(SignBroadcastPhase1, MessageA)>,
+    /// `round2[a]` are signer a's answers, with gamma_i and with w_i, to
+    /// the `MessageA` of every other signer, in signer order.
+    pub round2: Vec<Vec<(MessageB, MessageB)>>,
+    /// delta_i.
+    pub round3: Vec<FE>,
+    pub round4: Vec<SignDecommitPhase1>,
+    pub round5: Vec<Phase5Com1>,
+    pub round6: Vec<(Phase5ADecom1, HomoELGamalProof, DLogProof)>,
+    pub round7: Vec<Phase5Com2>,
+    pub round8: Vec<Phase5DDecom2>,
+    /// s_i.
+    pub round9: Vec<FE>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConformanceTranscript {
+    pub version: u8,
+    pub seed: Vec<u8>,
+    pub threshold: u16,
+    pub share_count: u16,
+    /// The party ids of the signers, counted from 1, ascending.
+    pub signers: Vec<u16>,
+    pub digest: [u8; 32],
+    pub keygen: KeygenTranscript,
+    pub sign: SignTranscript,
+    pub public_key: GE,
+    pub signature: Signature,
+}
+
+// what one signer holds for the signing, all but w_i from the seed
+struct SignerSecrets {
+    keys: SignKeys,
+    gamma_blind: BigInt,
+    l_i: FE,
+    rho_i: FE,
+}
+
+// one MtA answer and what Bob keeps of it
+struct Response {
+    m_b: MessageB,
+    beta: FE,
+    beta_tag: BigInt,
+}
+
+// HMAC-SHA256 of the seed over the label and indices, in counter mode
+fn derive_bytes(seed: &[u8], label: &[u8], indices: &[usize], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while out.len() < len {
+        let mut mac = Hmac::<Sha256>::new_from_slice(seed).unwrap();
+        mac.update(DERIVATION_DOMAIN);
+        mac.update(&(label.len() as u32).to_be_bytes());
+        mac.update(label);
+        for index in indices {
+            mac.update(&(*index as u64).to_be_bytes());
+        }
+        mac.update(&counter.to_be_bytes());
+        out.extend_from_slice(&mac.finalize().into_bytes());
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+// 512 bits reduced mod q, so the bias is negligible
+fn derive_scalar(seed: &[u8], label: &[u8], indices: &[usize]) -> FE {
+    ECScalar::from(&BigInt::from_bytes_be(&derive_bytes(
+        seed, label, indices, 64,
+    )))
+}
+
+// 128 bits more than `upper` before the reduction, as `derive_scalar`
+fn derive_below(seed: &[u8], label: &[u8], indices: &[usize], upper: &BigInt) -> BigInt {
+    let len = upper.bits().div_ceil(8) + 16;
+    BigInt::from_bytes_be(&derive_bytes(seed, label, indices, len)) % upper
+}
+
+fn keygen_error(round: u8, index: usize) -> ConformanceError {
+    ConformanceError::Keygen {
+        round,
+        party: index as u16 + 1,
+    }
+}
+
+fn sign_error(round: u8, index: usize) -> ConformanceError {
+    ConformanceError::Sign {
+        round,
+        party: index as u16 + 1,
+    }
+}
+
+// the signers as party indices from 0
+fn signer_indices(t: usize, n: usize, signers: &[u16]) -> Result<Vec<usize>, ConformanceError> {
+    let ascending = signers.windows(2).all(|w| w[0] < w[1]);
+    let in_range = signers
+        .iter()
+        .all(|party| (1..=n).contains(&usize::from(*party)));
+    if !ascending || !in_range || signers.len() <= t || signers.len() < 2 {
+        return Err(ConformanceError::InvalidParameters);
+    }
+    Ok(signers
+        .iter()
+        .map(|party| usize::from(*party) - 1)
+        .collect())
+}
+
+fn keygen_seeds(seed: &[u8], n: usize) -> Result<Vec<KeygenSeed>, ConformanceError> {
+    (0..n)
+        .map(|i| KeygenSeed::new(seed, CONFORMANCE_CONTEXT, i))
+        .collect::<Result<Vec<KeygenSeed>, _>>()
+        .map_err(|_| ConformanceError::InvalidSeed)
+}
+
+// the position of signer `of` among the answers of signer `row`
+fn position(of: usize, row: usize) -> usize {
+    if of < row {
+        of
+    } else {
+        of - 1
+    }
+}
+
+fn signer_secrets(
+    seed: &[u8],
+    vss_scheme: &VerifiableSS,
+    signers: &[usize],
+    party: usize,
+    x_i: &FE,
+) -> SignerSecrets {
+    let g: GE = ECPoint::generator();
+    let w_i = vss_scheme.map_share_to_new_params(party, signers) * x_i;
+    let gamma_i = derive_scalar(seed, b"gamma_i", &[party]);
+    SignerSecrets {
+        keys: SignKeys {
+            g_w_i: &g * &w_i,
+            w_i,
+            k_i: derive_scalar(seed, b"k_i", &[party]),
+            g_gamma_i: &g * &gamma_i,
+            gamma_i,
+        },
+        gamma_blind: BigInt::from_bytes_be(&derive_bytes(seed, b"gamma blind", &[party], 32)),
+        l_i: derive_scalar(seed, b"l_i", &[party]),
+        rho_i: derive_scalar(seed, b"rho_i", &[party]),
+    }
+}
+
+fn sign_round1(
+    seed: &[u8],
+    party: usize,
+    secrets: &SignerSecrets,
+    ek: &EncryptionKey,
+) -> (SignBroadcastPhase1, MessageA) {
+    let com = HashCommitment::create_commitment_with_user_defined_randomness(
+        &secrets.keys.g_gamma_i.bytes_compressed_to_big_int(),
+        &secrets.gamma_blind,
+    );
+    let randomness = derive_below(seed, b"mta k_i", &[party], &ek.n);
+    (
+        SignBroadcastPhase1 { com },
+        MessageA::a_with_predefined_randomness(&secrets.keys.k_i, ek, &randomness, &[]),
+    )
+}
+
+// party `from` answers the MessageA of party `to` with `b`
+fn mta_response(
+    seed: &[u8],
+    label: &[u8],
+    b: &FE,
+    ek: &EncryptionKey,
+    m_a: &MessageA,
+    from: usize,
+    to: usize,
+) -> Result<Response, Error> {
+    let randomness = derive_below(seed, label, &[from, to, 0], &ek.n);
+    let beta_tag = derive_below(seed, label, &[from, to, 1], &beta_tag_bound(ek, false));
+    let (m_b, beta) = MessageB::b_with_predefined_randomness(
+        b,
+        ek,
+        m_a.clone(),
+        &randomness,
+        &beta_tag,
+        &[],
+        CONFORMANCE_CONTEXT,
+        from as u16 + 1,
+    )?;
+    Ok(Response {
+        m_b,
+        beta,
+        beta_tag,
+    })
+}
+
+// `[a][position(b, a)]`: signer a's answers to signer b, with gamma_i and w_i
+fn mta_responses(
+    seed: &[u8],
+    signers: &[usize],
+    secrets: &[SignerSecrets],
+    ek_vec: &[EncryptionKey],
+    m_a_vec: &[MessageA],
+) -> Result<Vec<Vec<(Response, Response)>>, ConformanceError> {
+    let mut responses = Vec::new();
+    for (a, secret) in secrets.iter().enumerate() {
+        let mut row = Vec::new();
+        for b in (0..signers.len()).filter(|b| *b != a) {
+            let (from, to) = (signers[a], signers[b]);
+            let ek = &ek_vec[to];
+            let gamma = mta_response(
+                seed,
+                b"mta gamma",
+                &secret.keys.gamma_i,
+                ek,
+                &m_a_vec[b],
+                from,
+                to,
+            )
+            .map_err(|_| sign_error(2, from))?;
+            let w = mta_response(seed, b"mta w", &secret.keys.w_i, ek, &m_a_vec[b], from, to)
+                .map_err(|_| sign_error(2, from))?;
+            row.push((gamma, w));
+        }
+        responses.push(row);
+    }
+    Ok(responses)
+}
+
+#[allow(non_snake_case)]
+fn local_signature(
+    secrets: &SignerSecrets,
+    digest: &SigningDigest,
+    R: &GE,
+    sigma_i: &FE,
+    public_key: &GE,
+) -> LocalSignature {
+    let mut local =
+        LocalSignature::phase5_local_sig(&secrets.keys.k_i, digest, R, sigma_i, public_key);
+    local.l_i = secrets.l_i.clone();
+    local.rho_i = secrets.rho_i.clone();
+    local
+}
+
+// what Alice decrypts from an answer of Bob with `b`: k b + beta' mod N
+fn alpha(k_i: &FE, b: &FE, beta_tag: &BigInt, n: &BigInt) -> FE {
+    let share = (k_i.to_big_int() * b.to_big_int() + beta_tag).mod_floor(n);
+    ECScalar::from(&share.mod_floor(&FE::q()))
+}
+
+fn same_response(recorded: &MessageB, expected: &MessageB, bob: u16) -> bool {
+    recorded.c == expected.c
+        && recorded.b_proof.pk == expected.b_proof.pk
+        && recorded.beta_tag_proof.pk == expected.beta_tag_proof.pk
+        && recorded.verify_proofs(CONFORMANCE_CONTEXT, bob).is_ok()
+}
+
+/// Runs a (`threshold`, `share_count`) keygen and a signing of `digest` by
+/// `signers`, party ids counted from 1, with all secrets from `seed`.
+#[allow(non_snake_case)]
+pub fn generate(
+    seed: &[u8],
+    threshold: u16,
+    share_count: u16,
+    signers: &[u16],
+    digest: &SigningDigest,
+) -> Result<ConformanceTranscript, ConformanceError> {
+    let (t, n) = (usize::from(threshold), usize::from(share_count));
+    let params = Parameters::new(t, n).map_err(|_| ConformanceError::InvalidParameters)?;
+    let s = signer_indices(t, n, signers)?;
+    let seeds = keygen_seeds(seed, n)?;
+
+    let keys = seeds
+        .iter()
+        .enumerate()
+        .map(|(i, seed_i)| Keys::create_from_seed(seed_i, i))
+        .collect::<Vec<Keys>>();
+    let (bc1_vec, decom_vec): (Vec<_>, Vec<_>) = keys
+        .iter()
+        .zip(&seeds)
+        .map(|(k, seed_i)| k.phase1_broadcast_seeded(seed_i))
+        .unzip();
+    let keygen_round2 = keys
+        .iter()
+        .zip(&decom_vec)
+        .enumerate()
+        .map(|(i, (k, decom))| {
+            let proof = ContributionProof::prove(k, CONFORMANCE_CONTEXT, i as u16 + 1);
+            (decom.clone(), proof)
+        })
+        .collect();
+    let mut vss_scheme_vec = Vec::new();
+    let mut dealt_vec = Vec::new();
+    for (i, (k, seed_i)) in keys.iter().zip(&seeds).enumerate() {
+        let (vss_scheme, dealt, _) = k
+            .phase2_distribute_seeded(seed_i, &params, &decom_vec, &bc1_vec)
+            .map_err(|_| keygen_error(2, i))?;
+        vss_scheme_vec.push(vss_scheme);
+        dealt_vec.push(dealt);
+    }
+    let y_vec = decom_vec
+        .iter()
+        .map(|decom| decom.y_i.clone())
+        .collect::<Vec<GE>>();
+    let mut shared_keys_vec = Vec::new();
+    let mut dlog_proof_vec = Vec::new();
+    for (i, k) in keys.iter().enumerate() {
+        let received = dealt_vec
+            .iter()
+            .map(|dealt| dealt[i].clone())
+            .collect::<Vec<FE>>();
+        let (shared_keys, dlog_proof) = k
+            .phase2_verify_vss_construct_keypair_phase3_pok_dlog(
+                &params,
+                &y_vec,
+                &received,
+                &vss_scheme_vec,
+                &(i + 1),
+            )
+            .map_err(|_| keygen_error(4, i))?;
+        shared_keys_vec.push(shared_keys);
+        dlog_proof_vec.push(dlog_proof);
+    }
+    let public_key = shared_keys_vec[0].y.clone();
+
+    let ek_vec = keys.iter().map(|k| k.ek.clone()).collect::<Vec<_>>();
+    let secrets = s
+        .iter()
+        .map(|i| signer_secrets(seed, &vss_scheme_vec[0], &s, *i, &shared_keys_vec[*i].x_i))
+        .collect::<Vec<SignerSecrets>>();
+    let sign_round1_vec = s
+        .iter()
+        .zip(&secrets)
+        .map(|(i, secret)| sign_round1(seed, *i, secret, &ek_vec[*i]))
+        .collect::<Vec<_>>();
+    let bc_vec = sign_round1_vec
+        .iter()
+        .map(|(bc, _)| bc.clone())
+        .collect::<Vec<SignBroadcastPhase1>>();
+    let m_a_vec = sign_round1_vec
+        .iter()
+        .map(|(_, m_a)| m_a.clone())
+        .collect::<Vec<MessageA>>();
+    let responses = mta_responses(seed, &s, &secrets, &ek_vec, &m_a_vec)?;
+
+    let mut delta_vec = Vec::new();
+    let mut sigma_vec = Vec::new();
+    for (a, secret) in secrets.iter().enumerate() {
+        let dk = &keys[s[a]].dk;
+        let mut alpha_vec = Vec::new();
+        let mut miu_vec = Vec::new();
+        for b in (0..s.len()).filter(|b| *b != a) {
+            let (gamma, w) = &responses[b][position(a, b)];
+            let bob = s[b] as u16 + 1;
+            let (alpha, _) = gamma
+                .m_b
+                .verify_proofs_get_alpha(dk, &secret.keys.k_i, false, CONFORMANCE_CONTEXT, bob)
+                .map_err(|_| sign_error(2, s[b]))?;
+            let (miu, _) = w
+                .m_b
+                .verify_proofs_get_alpha(dk, &secret.keys.k_i, false, CONFORMANCE_CONTEXT, bob)
+                .map_err(|_| sign_error(2, s[b]))?;
+            alpha_vec.push(alpha);
+            miu_vec.push(miu);
+        }
+        let beta_vec = responses[a]
+            .iter()
+            .map(|(gamma, _)| gamma.beta.clone())
+            .collect::<Vec<FE>>();
+        let ni_vec = responses[a]
+            .iter()
+            .map(|(_, w)| w.beta.clone())
+            .collect::<Vec<FE>>();
+        delta_vec.push(secret.keys.phase2_delta_i(&alpha_vec, &beta_vec));
+        sigma_vec.push(secret.keys.phase2_sigma_i(&miu_vec, &ni_vec));
+    }
+
+    let decommit_vec = secrets
+        .iter()
+        .map(|secret| SignDecommitPhase1 {
+            blind_factor: secret.gamma_blind.clone(),
+            g_gamma_i: secret.keys.g_gamma_i.clone(),
+        })
+        .collect::<Vec<SignDecommitPhase1>>();
+    let b_proof_vec = responses
+        .iter()
+        .map(|row| &row[0].0.m_b.b_proof)
+        .collect::<Vec<&DLogProof>>();
+    let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
+    let R = SignKeys::phase4(&delta_inv, &b_proof_vec, decommit_vec.clone(), &bc_vec)
+        .map_err(|_| sign_error(4, s[0]))?;
+
+    let local_sig_vec = secrets
+        .iter()
+        .zip(&sigma_vec)
+        .map(|(secret, sigma_i)| local_signature(secret, digest, &R, sigma_i, &public_key))
+        .collect::<Vec<LocalSignature>>();
+    let mut com1_vec = Vec::new();
+    let mut decom1_vec = Vec::new();
+    let mut elgamal_proof_vec = Vec::new();
+    let mut dlog_proof_rho_vec = Vec::new();
+    for local_sig in &local_sig_vec {
+        let (com1, decom1, elgamal_proof, dlog_proof_rho) =
+            local_sig.phase5a_broadcast_5b_zkproof();
+        com1_vec.push(com1);
+        decom1_vec.push(decom1);
+        elgamal_proof_vec.push(elgamal_proof);
+        dlog_proof_rho_vec.push(dlog_proof_rho);
+    }
+    let mut com2_vec = Vec::new();
+    let mut decom2_vec = Vec::new();
+    for (a, local_sig) in local_sig_vec.iter().enumerate() {
+        let mut decom1_others = decom1_vec.clone();
+        let mut com1_others = com1_vec.clone();
+        let mut elgamal_others = elgamal_proof_vec.clone();
+        let mut dlog_others = dlog_proof_rho_vec.clone();
+        decom1_others.remove(a);
+        com1_others.remove(a);
+        elgamal_others.remove(a);
+        dlog_others.remove(a);
+        let (com2, decom2) = local_sig
+            .phase5c(
+                &decom1_others,
+                &com1_others,
+                &elgamal_others,
+                &dlog_others,
+                &decom1_vec[a].V_i,
+                &R,
+            )
+            .map_err(|_| sign_error(6, s[a]))?;
+        com2_vec.push(com2);
+        decom2_vec.push(decom2);
+    }
+    let mut s_vec = Vec::new();
+    for (a, local_sig) in local_sig_vec.iter().enumerate() {
+        let s_i = local_sig
+            .phase5d(&decom2_vec, &com2_vec, &decom1_vec)
+            .map_err(|_| sign_error(8, s[a]))?;
+        s_vec.push(s_i);
+    }
+    let signature = local_sig_vec[0]
+        .output_signature(&s_vec[1..])
+        .map_err(|_| ConformanceError::Output)?;
+
+    Ok(ConformanceTranscript {
+        version: TRANSCRIPT_VERSION,
+        seed: seed.to_vec(),
+        threshold,
+        share_count,
+        signers: signers.to_vec(),
+        digest: *digest.as_bytes(),
+        keygen: KeygenTranscript {
+            round1: bc1_vec,
+            round2: keygen_round2,
+            round3: dealt_vec,
+            round4: vss_scheme_vec,
+            round5: dlog_proof_vec,
+        },
+        sign: SignTranscript {
+            round1: sign_round1_vec,
+            round2: responses
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|(gamma, w)| (gamma.m_b, w.m_b))
+                        .collect()
+                })
+                .collect(),
+            round3: delta_vec,
+            round4: decommit_vec,
+            round5: com1_vec,
+            round6: decom1_vec
+                .into_iter()
+                .zip(elgamal_proof_vec)
+                .zip(dlog_proof_rho_vec)
+                .map(|((decom1, elgamal_proof), dlog_proof_rho)| {
+                    (decom1, elgamal_proof, dlog_proof_rho)
+                })
+                .collect(),
+            round7: com2_vec,
+            round8: decom2_vec,
+            round9: s_vec,
+        },
+        public_key,
+        signature,
+    })
+}
+
+impl ConformanceTranscript {
+    /// Checks that every message is the one the seed gives or, for the
+    /// proofs, verifies, and that the key and signature follow. Names the
+    /// first message that does not.
+    pub fn replay(&self) -> Result<(), ConformanceError> {
+        if self.version != TRANSCRIPT_VERSION {
+            return Err(ConformanceError::Malformed);
+        }
+        let (t, n) = (usize::from(self.threshold), usize::from(self.share_count));
+        Parameters::new(t, n).map_err(|_| ConformanceError::InvalidParameters)?;
+        let s = signer_indices(t, n, &self.signers)?;
+        self.check_shape(n, s.len())?;
+        let digest =
+            SigningDigest::from_prehashed(&self.digest).map_err(|_| ConformanceError::Malformed)?;
+        let seeds = keygen_seeds(&self.seed, n)?;
+        let x_vec = self.replay_keygen(t, &seeds)?;
+        self.replay_sign(&s, &x_vec, &digest)
+    }
+
+    fn check_shape(&self, n: usize, signer_count: usize) -> Result<(), ConformanceError> {
+        let keygen = &self.keygen;
+        let sign = &self.sign;
+        let keygen_shape = [
+            keygen.round1.len(),
+            keygen.round2.len(),
+            keygen.round3.len(),
+            keygen.round4.len(),
+            keygen.round5.len(),
+        ]
+        .iter()
+        .all(|len| *len == n)
+            && keygen.round3.iter().all(|dealt| dealt.len() == n);
+        let sign_shape = [
+            sign.round1.len(),
+            sign.round2.len(),
+            sign.round3.len(),
+            sign.round4.len(),
+            sign.round5.len(),
+            sign.round6.len(),
+            sign.round7.len(),
+            sign.round8.len(),
+            sign.round9.len(),
+        ]
+        .iter()
+        .all(|len| *len == signer_count)
+            && sign.round2.iter().all(|row| row.len() == signer_count - 1);
+        if keygen_shape && sign_shape {
+            Ok(())
+        } else {
+            Err(ConformanceError::Malformed)
+        }
+    }
+
+    // returns x_i of every party
+    fn replay_keygen(&self, t: usize, seeds: &[KeygenSeed]) -> Result<Vec<FE>, ConformanceError> {
+        let g: GE = ECPoint::generator();
+        let keygen = &self.keygen;
+        let n = seeds.len();
+        for (i, seed_i) in seeds.iter().enumerate() {
+            let bc1 = &keygen.round1[i];
+            if bc1.correct_key_proof.verify(&bc1.e).is_err() {
+                return Err(keygen_error(1, i));
+            }
+            let (decom, contribution) = &keygen.round2[i];
+            let opens = HashCommitment::create_commitment_with_user_defined_randomness(
+                &decom.y_i.bytes_compressed_to_big_int(),
+                &decom.blind_factor,
+            ) == bc1.com;
+            if !opens
+                || decom.y_i != &g * &seed_i.u_i()
+                || decom.blind_factor != seed_i.blind_factor()
+                || contribution
+                    .verify(CONFORMANCE_CONTEXT, i as u16 + 1, &decom.y_i, &bc1.e)
+                    .is_err()
+            {
+                return Err(keygen_error(2, i));
+            }
+            let (vss_scheme, dealt) = VerifiableSS::share_polynomial(n, &seed_i.polynomial(t));
+            if keygen.round3[i] != dealt {
+                return Err(keygen_error(3, i));
+            }
+            if keygen.round4[i] != vss_scheme {
+                return Err(keygen_error(4, i));
+            }
+        }
+
+        let x_vec = (0..n)
+            .map(|i| {
+                keygen
+                    .round3
+                    .iter()
+                    .fold(FE::zero(), |acc, dealt| acc + &dealt[i])
+            })
+            .collect::<Vec<FE>>();
+        for (i, (dlog_proof, x_i)) in keygen.round5.iter().zip(&x_vec).enumerate() {
+            if dlog_proof.pk != &g * x_i || DLogProof::verify(dlog_proof).is_err() {
+                return Err(keygen_error(5, i));
+            }
+        }
+        let public_key = keygen
+            .round2
+            .iter()
+            .skip(1)
+            .fold(keygen.round2[0].0.y_i.clone(), |acc, (decom, _)| {
+                acc + &decom.y_i
+            });
+        let combined =
+            VerifiableSS::combine(&keygen.round4).map_err(|_| ConformanceError::Output)?;
+        if public_key != self.public_key || combined.commitments[0] != public_key {
+            return Err(ConformanceError::Output);
+        }
+        Ok(x_vec)
+    }
+
+    #[allow(non_snake_case)]
+    fn replay_sign(
+        &self,
+        s: &[usize],
+        x_vec: &[FE],
+        digest: &SigningDigest,
+    ) -> Result<(), ConformanceError> {
+        let g: GE = ECPoint::generator();
+        let seed = &self.seed[..];
+        let sign = &self.sign;
+        let ek_vec = self
+            .keygen
+            .round1
+            .iter()
+            .map(|bc1| bc1.e.clone())
+            .collect::<Vec<EncryptionKey>>();
+        let secrets = s
+            .iter()
+            .map(|i| signer_secrets(seed, &self.keygen.round4[0], s, *i, &x_vec[*i]))
+            .collect::<Vec<SignerSecrets>>();
+
+        for (a, secret) in secrets.iter().enumerate() {
+            let (bc, m_a) = sign_round1(seed, s[a], secret, &ek_vec[s[a]]);
+            let (recorded_bc, recorded_m_a) = &sign.round1[a];
+            if recorded_bc.com != bc.com
+                || recorded_m_a.c != m_a.c
+                || !recorded_m_a.range_proofs.is_empty()
+            {
+                return Err(sign_error(1, s[a]));
+            }
+        }
+        let m_a_vec = sign
+            .round1
+            .iter()
+            .map(|(_, m_a)| m_a.clone())
+            .collect::<Vec<MessageA>>();
+        let responses = mta_responses(seed, s, &secrets, &ek_vec, &m_a_vec)?;
+        for (a, (row, expected)) in sign.round2.iter().zip(&responses).enumerate() {
+            let bob = s[a] as u16 + 1;
+            let same = row
+                .iter()
+                .zip(expected)
+                .all(|((gamma, w), (gamma_e, w_e))| {
+                    same_response(gamma, &gamma_e.m_b, bob) && same_response(w, &w_e.m_b, bob)
+                });
+            if !same {
+                return Err(sign_error(2, s[a]));
+            }
+        }
+
+        // delta_i and sigma_i from the secrets, without decrypting
+        let mut sigma_vec = Vec::new();
+        for (a, secret) in secrets.iter().enumerate() {
+            let n_a = &ek_vec[s[a]].n;
+            let k_i = &secret.keys.k_i;
+            let mut delta = k_i.clone() * &secret.keys.gamma_i;
+            let mut sigma = k_i.clone() * &secret.keys.w_i;
+            for b in (0..s.len()).filter(|b| *b != a) {
+                let (gamma_in, w_in) = &responses[b][position(a, b)];
+                let (gamma_out, w_out) = &responses[a][position(b, a)];
+                delta = delta
+                    + alpha(k_i, &secrets[b].keys.gamma_i, &gamma_in.beta_tag, n_a)
+                    + &gamma_out.beta;
+                sigma = sigma + alpha(k_i, &secrets[b].keys.w_i, &w_in.beta_tag, n_a) + &w_out.beta;
+            }
+            if sign.round3[a] != delta {
+                return Err(sign_error(3, s[a]));
+            }
+            sigma_vec.push(sigma);
+        }
+
+        for (a, (decommit, secret)) in sign.round4.iter().zip(&secrets).enumerate() {
+            if decommit.g_gamma_i != secret.keys.g_gamma_i
+                || decommit.blind_factor != secret.gamma_blind
+            {
+                return Err(sign_error(4, s[a]));
+            }
+        }
+        let bc_vec = sign
+            .round1
+            .iter()
+            .map(|(bc, _)| bc.clone())
+            .collect::<Vec<SignBroadcastPhase1>>();
+        let b_proof_vec = sign
+            .round2
+            .iter()
+            .map(|row| &row[0].0.b_proof)
+            .collect::<Vec<&DLogProof>>();
+        if let Some(blame) = SignKeys::phase4_blame(&b_proof_vec, &sign.round4, &bc_vec).first() {
+            return Err(sign_error(4, s[blame.index]));
+        }
+        let delta_inv = SignKeys::phase3_reconstruct_delta(&sign.round3);
+        let R = SignKeys::phase4(&delta_inv, &b_proof_vec, sign.round4.clone(), &bc_vec)
+            .map_err(|_| sign_error(4, s[0]))?;
+
+        let local_sig_vec = secrets
+            .iter()
+            .zip(&sigma_vec)
+            .map(|(secret, sigma_i)| local_signature(secret, digest, &R, sigma_i, &self.public_key))
+            .collect::<Vec<LocalSignature>>();
+        for (a, ((decom1, _, _), local_sig)) in sign.round6.iter().zip(&local_sig_vec).enumerate() {
+            let l_i_rho_i = local_sig.l_i.clone() * &local_sig.rho_i;
+            if decom1.A_i != &g * &local_sig.rho_i
+                || decom1.B_i != &g * &l_i_rho_i
+                || decom1.V_i != &R * &local_sig.s_i + &g * &local_sig.l_i
+            {
+                return Err(sign_error(6, s[a]));
+            }
+        }
+        let decom1_vec = sign
+            .round6
+            .iter()
+            .map(|(decom1, _, _)| decom1.clone())
+            .collect::<Vec<Phase5ADecom1>>();
+        let elgamal_proof_vec = sign
+            .round6
+            .iter()
+            .map(|(_, elgamal_proof, _)| elgamal_proof.clone())
+            .collect::<Vec<HomoELGamalProof>>();
+        let dlog_proof_rho_vec = sign
+            .round6
+            .iter()
+            .map(|(_, _, dlog_proof_rho)| dlog_proof_rho.clone())
+            .collect::<Vec<DLogProof>>();
+        if let Some(blame) = LocalSignature::phase5c_blame(
+            &decom1_vec,
+            &sign.round5,
+            &elgamal_proof_vec,
+            &dlog_proof_rho_vec,
+            &R,
+        )
+        .first()
+        {
+            return Err(sign_error(6, s[blame.index]));
+        }
+
+        // u_i = rho_i (sum V_j - m G - r y) and t_i = l_i sum_{j != i} A_j
+        let r: FE = ECScalar::from(&R.x_coor().unwrap().mod_floor(&FE::q()));
+        let v_sum = decom1_vec
+            .iter()
+            .skip(1)
+            .fold(decom1_vec[0].V_i.clone(), |acc, decom1| acc + &decom1.V_i);
+        let a_sum = decom1_vec
+            .iter()
+            .skip(1)
+            .fold(decom1_vec[0].A_i.clone(), |acc, decom1| acc + &decom1.A_i);
+        let yr = &self.public_key * &r;
+        let v = match message_point(&digest.to_big_int()) {
+            Some(gm) => v_sum.sub_point(&gm.get_element()),
+            None => v_sum,
+        };
+        let v = v.sub_point(&yr.get_element());
+        for (a, (decom2, local_sig)) in sign.round8.iter().zip(&local_sig_vec).enumerate() {
+            let a_others = a_sum.sub_point(&decom1_vec[a].A_i.get_element());
+            if decom2.u_i != &v * &local_sig.rho_i || decom2.t_i != &a_others * &local_sig.l_i {
+                return Err(sign_error(8, s[a]));
+            }
+        }
+        if let Some(blame) = local_sig_vec[0]
+            .phase5d_blame(&sign.round8, &sign.round7, &decom1_vec)
+            .first()
+        {
+            return Err(sign_error(8, s[blame.index]));
+        }
+        local_sig_vec[0]
+            .phase5d(&sign.round8, &sign.round7, &decom1_vec)
+            .map_err(|_| ConformanceError::Output)?;
+
+        for (a, (s_i, local_sig)) in sign.round9.iter().zip(&local_sig_vec).enumerate() {
+            if *s_i != local_sig.s_i {
+                return Err(sign_error(9, s[a]));
+            }
+        }
+        let s_sum = sign.round9.iter().fold(FE::zero(), |acc, s_i| acc + s_i);
+        if self.signature.r != r
+            || self.signature.s != s_sum
+            || verify(&self.signature, &self.public_key, &digest.to_big_int()).is_err()
+        {
+            return Err(ConformanceError::Output);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_generate_and_replay() {
+        let seed = [5u8; 32];
+        let digest = SigningDigest::hash::<Sha256>(b"conformance");
+        let transcript = generate(&seed, 1, 3, &[1, 3], &digest).unwrap();
+        assert_eq!(transcript.sign.round2[1].len(), 1);
+
+        // a fixture survives JSON
+        let json = serde_json::to_string(&transcript).unwrap();
+        let parsed: ConformanceTranscript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.replay(), Ok(()));
+
+        let mut other_seed = parsed.clone();
+        other_seed.seed = vec![6u8; 32];
+        assert_eq!(
+            other_seed.replay(),
+            Err(ConformanceError::Keygen { round: 2, party: 1 })
+        );
+
+        let mut wrong_share = parsed.clone();
+        wrong_share.keygen.round3[1][2] = wrong_share.keygen.round3[1][0].clone();
+        assert_eq!(
+            wrong_share.replay(),
+            Err(ConformanceError::Keygen { round: 3, party: 2 })
+        );
+
+        let mut wrong_delta = parsed.clone();
+        wrong_delta.sign.round3.swap(0, 1);
+        assert_eq!(
+            wrong_delta.replay(),
+            Err(ConformanceError::Sign { round: 3, party: 1 })
+        );
+
+        let mut wrong_s = parsed.clone();
+        wrong_s.sign.round9[1] = wrong_s.sign.round9[0].clone();
+        assert_eq!(
+            wrong_s.replay(),
+            Err(ConformanceError::Sign { round: 9, party: 3 })
+        );
+
+        let mut truncated = parsed;
+        truncated.sign.round8.pop();
+        assert_eq!(truncated.replay(), Err(ConformanceError::Malformed));
+
+        assert_eq!(
+            generate(&seed, 1, 3, &[3, 1], &digest).err(),
+            Some(ConformanceError::InvalidParameters)
+        );
+    }
+}
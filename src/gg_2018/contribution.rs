@@ -0,0 +1,187 @@
+/*
+    Proofs that each party knows what it contributes to keygen.
+
+    In round 2 a party reveals y_i = u_i G, the constant term of its sharing
+    polynomial, and it announced a Paillier key in round 1. Nothing so far
+    showed that it knows u_i or the factors of N: a party can reveal a y_i
+    chosen from the others' to bias the group key, or copy another party's
+    Paillier key together with its correct key proof. Either passes keygen
+    and fails much later, in signing, where the failure cannot be pinned on
+    anyone.
+
+    `ContributionProof` accompanies the round 2 decommitment: a Schnorr
+    proof for y_i and N-th roots mod N of challenges only the holder of the
+    factorization can take. Both are bound to the session and the prover, so
+    neither can be replayed from another session or party, and a party whose
+    proof fails is named.
+*/
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::arithmetic::traits::*;
+use crate::curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
+use crate::curv::cryptographic_primitives::hashing::traits::Hash;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::{KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys};
+use crate::impl_canonical_encode;
+use crate::paillier::zkproofs::mask_generation;
+use crate::paillier::{extract_nroot, EncryptionKey};
+use crate::Error::{self, InvalidKey};
+
+/// N-th roots per proof. Under the RSA assumption one root of a challenge
+/// the prover cannot choose already shows it knows the factors.
+const PAILLIER_ROOTS: usize = 2;
+const PAILLIER_DOMAIN: &[u8] = b"tss-wasm paillier knowledge";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ContributionProof {
+    /// Knowledge of u_i, for `pk` = y_i.
+    pub constant_term: DLogProof,
+    /// N-th roots of the challenges of `paillier_challenges`.
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "Vec<crate::schema::BigIntSchema>")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex::vec")]
+    pub paillier_roots: Vec<BigInt>,
+}
+
+impl_canonical_encode!(ContributionProof {
+    constant_term,
+    paillier_roots
+});
+
+// challenges in Z_N bound to the session, the prover and its y_i
+fn paillier_challenges(ek: &EncryptionKey, y_i: &GE, context: &[u8], prover: u16) -> Vec<BigInt> {
+    let domain = BigInt::from_bytes(PAILLIER_DOMAIN);
+    let context = BigInt::from_bytes(context);
+    let prover = BigInt::from(u32::from(prover));
+    let y_i = y_i.bytes_compressed_to_big_int();
+    let key_length = ek.n.bits();
+    (0..PAILLIER_ROOTS)
+        .map(|j| {
+            let seed = HSha256::create_hash(&[
+                &domain,
+                &context,
+                &prover,
+                &ek.n,
+                &y_i,
+                &BigInt::from(j as u32),
+            ]);
+            mask_generation(&key_length, &seed) % &ek.n
+        })
+        .collect()
+}
+
+impl ContributionProof {
+    /// The proof of `keys`, as party `prover` of the session `context`.
+    pub fn prove(keys: &Keys, context: &[u8], prover: u16) -> ContributionProof {
+        let paillier_roots = paillier_challenges(&keys.ek, &keys.y_i, context, prover)
+            .iter()
+            .map(|rho| extract_nroot(&keys.dk, rho))
+            .collect();
+        ContributionProof {
+            constant_term: DLogProof::prove_in_context(&keys.u_i, context, prover),
+            paillier_roots,
+        }
+    }
+
+    /// Checks the proof of party `prover` for the y_i it decommitted and the
+    /// Paillier key it announced.
+    pub fn verify(
+        &self,
+        context: &[u8],
+        prover: u16,
+        y_i: &GE,
+        ek: &EncryptionKey,
+    ) -> Result<(), Error> {
+        if self.constant_term.pk != *y_i
+            || DLogProof::verify_in_context(&self.constant_term, context, prover).is_err()
+        {
+            return Err(InvalidKey);
+        }
+        let challenges = paillier_challenges(ek, y_i, context, prover);
+        let roots_valid = self.paillier_roots.len() == challenges.len()
+            && self
+                .paillier_roots
+                .iter()
+                .zip(&challenges)
+                .all(|(root, rho)| root < &ek.n && BigInt::mod_pow(root, &ek.n, &ek.n) == *rho);
+        if roots_valid {
+            Ok(())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+}
+
+impl Keys {
+    /// Checks every party's contribution proof against its round 1 and 2
+    /// messages, all indexed by party from 0. Returns the indices of the
+    /// parties whose proofs fail.
+    pub fn verify_contribution_proofs(
+        context: &[u8],
+        proofs: &[ContributionProof],
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> Result<(), Vec<usize>> {
+        assert_eq!(proofs.len(), decom_vec.len());
+        assert_eq!(proofs.len(), bc1_vec.len());
+        let failed = (0..proofs.len())
+            .filter(|i| {
+                proofs[*i]
+                    .verify(context, *i as u16 + 1, &decom_vec[*i].y_i, &bc1_vec[*i].e)
+                    .is_err()
+            })
+            .collect::<Vec<usize>>();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_contribution_proofs() {
+        let keys = (1..=3).map(Keys::create).collect::<Vec<Keys>>();
+        let (bc1_vec, decom_vec): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .map(|k| k.phase1_broadcast_phase3_proof_of_correct_key())
+            .unzip();
+        let context = b"session-1";
+        let mut proofs = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| ContributionProof::prove(k, context, i as u16 + 1))
+            .collect::<Vec<ContributionProof>>();
+        assert_eq!(
+            Keys::verify_contribution_proofs(context, &proofs, &decom_vec, &bc1_vec),
+            Ok(())
+        );
+        // proofs do not carry over to another session
+        assert_eq!(
+            Keys::verify_contribution_proofs(b"session-2", &proofs, &decom_vec, &bc1_vec),
+            Err(vec![0, 1, 2])
+        );
+
+        // party 3 replays party 1's Paillier key and proof
+        let mut bc1_copied = bc1_vec.clone();
+        bc1_copied[2].e = bc1_vec[0].e.clone();
+        proofs[2].paillier_roots = proofs[0].paillier_roots.clone();
+        assert_eq!(
+            Keys::verify_contribution_proofs(context, &proofs, &decom_vec, &bc1_copied),
+            Err(vec![2])
+        );
+    }
+}
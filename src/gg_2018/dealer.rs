@@ -0,0 +1,180 @@
+/*
+    Dealer-provisioned auxiliary material.
+
+    Paillier keys and the h1, h2, N~ statements are the slow part of keygen
+    and need no secret share, so where a central provisioning service exists
+    it can generate all of them and hand each party its decryption key. The
+    signing key itself is still shared by the parties in the usual rounds,
+    the dealer never sees u_i or x_i.
+
+    The dealer is trusted, not verified: it knows every decryption key and
+    every dlog secret. It must not see the keygen and signing traffic, which
+    is encrypted to those keys, and it must not sign. What the parties can
+    check is that the material is well formed: each modulus carries a proof
+    of a correct Paillier key and each h2 a proof that it is a power of h1.
+*/
+
+use crate::gg_2018::party_i::{Keys, Parameters};
+use crate::gg_2018::proof_cache::ProofCache;
+use crate::paillier::zkproofs::{CompositeDLogProof, DLogStatement, NICorrectKeyProof};
+use crate::paillier::{DecryptionKey, EncryptionKey, KeyGeneration, Paillier};
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum DealerError {
+    /// Vectors of the wrong length, or a secret for another party.
+    WrongParty,
+    /// A modulus whose correct key proof fails, or a decryption key that
+    /// does not factor it.
+    InvalidPaillierKey,
+    /// An h1, h2, N~ statement that is malformed or whose proof fails.
+    InvalidDLogStatement,
+}
+
+/// What the dealer publishes to every party, indexed by party from 0.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuxiliaryPublic {
+    pub paillier_key_vec: Vec<EncryptionKey>,
+    pub correct_key_proofs: Vec<NICorrectKeyProof>,
+    pub dlog_statements: Vec<DLogStatement>,
+    pub dlog_proofs: Vec<CompositeDLogProof>,
+}
+
+/// What the dealer sends to party `index` alone, counted from 1.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuxiliarySecret {
+    pub index: usize,
+    pub dk: DecryptionKey,
+}
+
+/// Generates a Paillier key and an h1, h2, N~ statement for each of the
+/// parties. The dlog secrets are dropped once proven.
+pub fn provision(params: &Parameters) -> (AuxiliaryPublic, Vec<AuxiliarySecret>) {
+    let n = params.share_count();
+    let mut public = AuxiliaryPublic {
+        paillier_key_vec: Vec::with_capacity(n),
+        correct_key_proofs: Vec::with_capacity(n),
+        dlog_statements: Vec::with_capacity(n),
+        dlog_proofs: Vec::with_capacity(n),
+    };
+    let mut secrets = Vec::with_capacity(n);
+    for index in 1..=n {
+        let (ek, dk) = Paillier::keypair().keys();
+        let (statement, secret) = DLogStatement::generate();
+        public.paillier_key_vec.push(ek);
+        public
+            .correct_key_proofs
+            .push(NICorrectKeyProof::proof(&dk));
+        public
+            .dlog_proofs
+            .push(CompositeDLogProof::prove(&statement, &secret));
+        public.dlog_statements.push(statement);
+        secrets.push(AuxiliarySecret { index, dk });
+    }
+    (public, secrets)
+}
+
+impl AuxiliaryPublic {
+    /// Checks every key and statement against its proof. Each party runs
+    /// this once on receipt, before keygen.
+    pub fn verify(&self, share_count: usize) -> Result<(), DealerError> {
+        self.verify_cached(share_count, &mut ProofCache::new())
+    }
+
+    /// `verify`, skipping the proofs of keys and statements `cache` has
+    /// seen verified, e.g. when the dealer re-provisions a committee.
+    pub fn verify_cached(
+        &self,
+        share_count: usize,
+        cache: &mut ProofCache,
+    ) -> Result<(), DealerError> {
+        let complete = self.paillier_key_vec.len() == share_count
+            && self.correct_key_proofs.len() == share_count
+            && self.dlog_statements.len() == share_count
+            && self.dlog_proofs.len() == share_count;
+        if !complete {
+            return Err(DealerError::WrongParty);
+        }
+        for (ek, proof) in self.paillier_key_vec.iter().zip(&self.correct_key_proofs) {
+            cache
+                .verify_correct_key(ek, proof)
+                .map_err(|_| DealerError::InvalidPaillierKey)?;
+        }
+        for (statement, proof) in self.dlog_statements.iter().zip(&self.dlog_proofs) {
+            cache
+                .verify_dlog_statement(statement, proof)
+                .map_err(|_| DealerError::InvalidDLogStatement)?;
+        }
+        Ok(())
+    }
+}
+
+impl Keys {
+    /// `Keys::create` with the dealer's Paillier key in place of a fresh
+    /// one. `index` is the party's keygen index, counted from 1.
+    pub fn create_with_auxiliary(
+        index: usize,
+        public: &AuxiliaryPublic,
+        secret: &AuxiliarySecret,
+    ) -> Result<Keys, DealerError> {
+        let in_range = index >= 1 && index <= public.paillier_key_vec.len();
+        if !in_range || secret.index != index {
+            return Err(DealerError::WrongParty);
+        }
+        let ek = &public.paillier_key_vec[index - 1];
+        let dk = &secret.dk;
+        let matches = dk.p != dk.q && &dk.p * &dk.q == ek.n;
+        if !matches {
+            return Err(DealerError::InvalidPaillierKey);
+        }
+
+        let u: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let y = &g * &u;
+        Ok(Keys {
+            u_i: u,
+            y_i: y,
+            dk: dk.clone(),
+            ek: ek.clone(),
+            party_index: index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::arithmetic::num_bigint::BigInt;
+    use crate::num_traits::One;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_provision() {
+        let params = Parameters::new(1, 2).unwrap();
+        let (public, secrets) = provision(&params);
+        assert_eq!(public.verify(2), Ok(()));
+        assert_eq!(public.verify(3), Err(DealerError::WrongParty));
+
+        let keys = Keys::create_with_auxiliary(2, &public, &secrets[1]).unwrap();
+        assert_eq!(keys.ek, public.paillier_key_vec[1]);
+        assert_eq!(
+            Keys::create_with_auxiliary(1, &public, &secrets[1]).err(),
+            Some(DealerError::WrongParty)
+        );
+        let mut swapped = secrets[1].clone();
+        swapped.index = 1;
+        assert_eq!(
+            Keys::create_with_auxiliary(1, &public, &swapped).err(),
+            Some(DealerError::InvalidPaillierKey)
+        );
+
+        let mut tampered = public.clone();
+        tampered.dlog_statements[0].ni = &tampered.dlog_statements[0].ni + BigInt::one();
+        assert_eq!(tampered.verify(2), Err(DealerError::InvalidDLogStatement));
+    }
+}
@@ -0,0 +1,632 @@
+/*
+    Dry runs of keygen and signing with an injected fault.
+
+    `dry_run` runs a keygen and a signing in process, with the checks the
+    relay ceremonies of `api` make, and applies one `Fault` on the way: a
+    party's message of a round goes missing, carries a proof that does not
+    verify, or carries a wrong share. It returns what the honest parties
+    see: the round that stalls, or the round whose check fails with the
+    error and the parties the check names. Operators rehearse their
+    runbooks against these outcomes, and they show which faults the
+    protocol can attribute: a bad contribution or phase 5 proof names its
+    sender, while a wrong keygen share or delta_i only fails a sum.
+*/
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::contribution::ContributionProof;
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::gg_2018::party_i::{
+    Blame, Keys, LocalSignature, Parameters, PartyPrivate, SignKeys, Signature, SigningDigest,
+};
+use crate::session::Ceremony;
+use crate::Error::{self, InvalidCom, InvalidKey};
+
+const DRY_RUN_CONTEXT: &[u8] = b"tss-wasm dry run";
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum DryRunError {
+    /// t and n out of range, or signers not ascending party ids, at least
+    /// two and more than t.
+    InvalidParameters,
+    /// A fault on a party outside the ceremony, or of a kind the round has
+    /// no message for.
+    InapplicableFault,
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum FaultKind {
+    /// The party never sends the round's message.
+    Drop,
+    /// The party sends a proof that does not verify: its correct key proof
+    /// in keygen round 1, contribution proof in round 2 or dlog proof in
+    /// round 5; its MtA proofs in signing round 2 or phase 5B proofs in
+    /// round 6.
+    CorruptProof,
+    /// The party sends a wrong value: its shares to the others in keygen
+    /// round 3, its delta_i in signing round 3 or its s_i in round 9.
+    WrongShare,
+}
+
+/// `party` is counted from 1, and must be a signer for a signing fault.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct Fault {
+    pub ceremony: Ceremony,
+    pub round: u8,
+    pub party: u16,
+    pub kind: FaultKind,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Outcome {
+    /// Both ceremonies finished.
+    Completed {
+        public_key: GE,
+        signature: Signature,
+    },
+    /// The round never completes for lack of the parties' messages.
+    Stalled {
+        ceremony: Ceremony,
+        round: u8,
+        missing: Vec<u16>,
+    },
+    /// A check of the round failed; `blamed` are the parties it names,
+    /// empty when it cannot tell.
+    Aborted {
+        ceremony: Ceremony,
+        round: u8,
+        error: Error,
+        blamed: Vec<u16>,
+    },
+}
+
+impl Fault {
+    fn check(&self, n: u16, signers: &[u16]) -> Result<(), DryRunError> {
+        let in_ceremony = match self.ceremony {
+            Ceremony::Keygen => (1..=n).contains(&self.party),
+            Ceremony::Sign => signers.contains(&self.party),
+        };
+        let applies = matches!(
+            (self.ceremony, self.kind, self.round),
+            (Ceremony::Keygen, FaultKind::Drop, 1..=5)
+                | (Ceremony::Keygen, FaultKind::CorruptProof, 1 | 2 | 5)
+                | (Ceremony::Keygen, FaultKind::WrongShare, 3)
+                | (Ceremony::Sign, FaultKind::Drop, 1..=9)
+                | (Ceremony::Sign, FaultKind::CorruptProof, 2 | 6)
+                | (Ceremony::Sign, FaultKind::WrongShare, 3 | 9)
+        );
+        if in_ceremony && applies {
+            Ok(())
+        } else {
+            Err(DryRunError::InapplicableFault)
+        }
+    }
+}
+
+// the party `fault` hits in this round with this kind of fault, from 0
+fn hit(fault: Option<Fault>, ceremony: Ceremony, round: u8, kind: FaultKind) -> Option<usize> {
+    fault
+        .filter(|f| f.ceremony == ceremony && f.round == round && f.kind == kind)
+        .map(|f| usize::from(f.party) - 1)
+}
+
+fn stalled(fault: Option<Fault>, ceremony: Ceremony, round: u8) -> Option<Outcome> {
+    hit(fault, ceremony, round, FaultKind::Drop).map(|party| Outcome::Stalled {
+        ceremony,
+        round,
+        missing: vec![party as u16 + 1],
+    })
+}
+
+fn aborted(ceremony: Ceremony, round: u8, error: Error, blamed: Vec<u16>) -> Outcome {
+    Outcome::Aborted {
+        ceremony,
+        round,
+        error,
+        blamed,
+    }
+}
+
+fn perturb(x: &FE) -> FE {
+    let one: FE = ECScalar::from(&BigInt::from(1u32));
+    x.clone() + one
+}
+
+// a proof that no longer verifies, with the commitment of another
+fn corrupt_dlog(proof: &mut DLogProof, other: &DLogProof) {
+    proof.pk_t_rand_commitment = other.pk_t_rand_commitment.clone();
+}
+
+// the position of signer `of` among the answers of signer `row`
+fn position(of: usize, row: usize) -> usize {
+    if of < row {
+        of
+    } else {
+        of - 1
+    }
+}
+
+/// Runs a (`t`, `n`) keygen and a signing of `digest` by `signers`, party
+/// ids counted from 1, with `fault` injected, and reports the first round
+/// the honest parties cannot complete.
+#[allow(non_snake_case)]
+pub fn dry_run(
+    t: u16,
+    n: u16,
+    signers: &[u16],
+    digest: &SigningDigest,
+    fault: Option<Fault>,
+) -> Result<Outcome, DryRunError> {
+    let params = Parameters::new(usize::from(t), usize::from(n))
+        .map_err(|_| DryRunError::InvalidParameters)?;
+    let ascending = signers.windows(2).all(|w| w[0] < w[1]);
+    if !ascending
+        || signers.len() <= usize::from(t)
+        || signers.len() < 2
+        || !signers.iter().all(|party| (1..=n).contains(party))
+    {
+        return Err(DryRunError::InvalidParameters);
+    }
+    if let Some(fault) = fault {
+        fault.check(n, signers)?;
+    }
+    let n = usize::from(n);
+    let keygen = Ceremony::Keygen;
+
+    // keygen round 1: commitments and Paillier keys
+    if let Some(outcome) = stalled(fault, keygen, 1) {
+        return Ok(outcome);
+    }
+    let keys = (0..n).map(Keys::create).collect::<Vec<Keys>>();
+    let (mut bc1_vec, decom_vec): (Vec<_>, Vec<_>) = keys
+        .iter()
+        .map(|k| k.phase1_broadcast_phase3_proof_of_correct_key())
+        .unzip();
+    if let Some(p) = hit(fault, keygen, 1, FaultKind::CorruptProof) {
+        bc1_vec[p].correct_key_proof = bc1_vec[(p + 1) % n].correct_key_proof.clone();
+    }
+
+    // keygen round 2: decommitments and contribution proofs
+    if let Some(outcome) = stalled(fault, keygen, 2) {
+        return Ok(outcome);
+    }
+    let mut contribution_vec = keys
+        .iter()
+        .enumerate()
+        .map(|(i, k)| ContributionProof::prove(k, DRY_RUN_CONTEXT, i as u16 + 1))
+        .collect::<Vec<ContributionProof>>();
+    if let Some(p) = hit(fault, keygen, 2, FaultKind::CorruptProof) {
+        contribution_vec[p] = contribution_vec[(p + 1) % n].clone();
+    }
+    if let Err(failed) =
+        Keys::verify_contribution_proofs(DRY_RUN_CONTEXT, &contribution_vec, &decom_vec, &bc1_vec)
+    {
+        let blamed = failed.iter().map(|i| *i as u16 + 1).collect();
+        return Ok(aborted(keygen, 2, InvalidKey, blamed));
+    }
+    let mut vss_scheme_vec = Vec::new();
+    let mut dealt_vec = Vec::new();
+    for k in &keys {
+        match k.phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
+            &params, &decom_vec, &bc1_vec,
+        ) {
+            Ok((vss_scheme, dealt, _)) => {
+                vss_scheme_vec.push(vss_scheme);
+                dealt_vec.push(dealt);
+            }
+            Err(e) => return Ok(aborted(keygen, 2, e, Vec::new())),
+        }
+    }
+
+    // keygen round 3: shares, one to each party
+    if let Some(outcome) = stalled(fault, keygen, 3) {
+        return Ok(outcome);
+    }
+    if let Some(p) = hit(fault, keygen, 3, FaultKind::WrongShare) {
+        for (j, share) in dealt_vec[p].iter_mut().enumerate() {
+            if j != p {
+                *share = perturb(share);
+            }
+        }
+    }
+
+    // keygen round 4: commitments to the sharings
+    if let Some(outcome) = stalled(fault, keygen, 4) {
+        return Ok(outcome);
+    }
+    let y_vec = decom_vec
+        .iter()
+        .map(|decom| decom.y_i.clone())
+        .collect::<Vec<GE>>();
+    let mut shared_keys_vec = Vec::new();
+    let mut dlog_proof_vec = Vec::new();
+    for (i, k) in keys.iter().enumerate() {
+        let received = dealt_vec
+            .iter()
+            .map(|dealt| dealt[i].clone())
+            .collect::<Vec<FE>>();
+        match k.phase2_verify_vss_construct_keypair_phase3_pok_dlog(
+            &params,
+            &y_vec,
+            &received,
+            &vss_scheme_vec,
+            &(i + 1),
+        ) {
+            Ok((shared_keys, dlog_proof)) => {
+                shared_keys_vec.push(shared_keys);
+                dlog_proof_vec.push(dlog_proof);
+            }
+            Err(e) => {
+                let blame =
+                    Keys::phase2_vss_blame(&params, &y_vec, &received, &vss_scheme_vec, i + 1);
+                let blamed = blame.iter().map(|b| b.index as u16 + 1).collect();
+                return Ok(aborted(keygen, 4, e, blamed));
+            }
+        }
+    }
+
+    // keygen round 5: proofs of x_i
+    if let Some(outcome) = stalled(fault, keygen, 5) {
+        return Ok(outcome);
+    }
+    if let Some(p) = hit(fault, keygen, 5, FaultKind::CorruptProof) {
+        let other = dlog_proof_vec[(p + 1) % n].clone();
+        corrupt_dlog(&mut dlog_proof_vec[p], &other);
+    }
+    if let Err(e) = Keys::verify_dlog_proofs(&params, &dlog_proof_vec, &y_vec) {
+        return Ok(aborted(keygen, 5, e, Vec::new()));
+    }
+    let y = shared_keys_vec[0].y.clone();
+
+    let sign = Ceremony::Sign;
+    let s = signers
+        .iter()
+        .map(|party| usize::from(*party) - 1)
+        .collect::<Vec<usize>>();
+    let signer_count = s.len();
+    // a signing fault hits a party; the rounds index signers
+    let signer_hit = |round: u8, kind: FaultKind| {
+        hit(fault, sign, round, kind).map(|p| s.iter().position(|i| *i == p).unwrap())
+    };
+    let party_of = |a: usize| s[a] as u16 + 1;
+    let blamed_parties =
+        |blame: &[Blame]| -> Vec<u16> { blame.iter().map(|b| party_of(b.index)).collect() };
+
+    let sign_keys_vec = s
+        .iter()
+        .map(|i| {
+            let private = PartyPrivate::set_private(keys[*i].clone(), shared_keys_vec[*i].clone());
+            SignKeys::create(&private, &vss_scheme_vec[0], *i, &s)
+        })
+        .collect::<Vec<SignKeys>>();
+
+    // signing round 1: commitments to g^gamma_i and encryptions of k_i
+    if let Some(outcome) = stalled(fault, sign, 1) {
+        return Ok(outcome);
+    }
+    let (bc_vec, decommit_vec): (Vec<_>, Vec<_>) =
+        sign_keys_vec.iter().map(|sk| sk.phase1_broadcast()).unzip();
+    let m_a_vec = sign_keys_vec
+        .iter()
+        .zip(&s)
+        .map(|(sk, i)| MessageA::a(&sk.k_i, &keys[*i].ek, &[]).0)
+        .collect::<Vec<MessageA>>();
+
+    // signing round 2: MtA answers, one to each signer
+    if let Some(outcome) = stalled(fault, sign, 2) {
+        return Ok(outcome);
+    }
+    let mut responses = Vec::new();
+    for (a, sk) in sign_keys_vec.iter().enumerate() {
+        let mut row = Vec::new();
+        for b in (0..signer_count).filter(|b| *b != a) {
+            let ek = &keys[s[b]].ek;
+            let m_a = &m_a_vec[b];
+            let bob = party_of(a);
+            let answers = MessageB::b(&sk.gamma_i, ek, m_a.clone(), &[], DRY_RUN_CONTEXT, bob)
+                .and_then(|(m_b_gamma, beta, _, _)| {
+                    let (m_b_w, ni, _, _) =
+                        MessageB::b(&sk.w_i, ek, m_a.clone(), &[], DRY_RUN_CONTEXT, bob)?;
+                    Ok((m_b_gamma, beta, m_b_w, ni))
+                });
+            match answers {
+                Ok(answers) => row.push(answers),
+                Err(e) => return Ok(aborted(sign, 2, e, vec![party_of(b)])),
+            }
+        }
+        responses.push(row);
+    }
+    if let Some(a) = signer_hit(2, FaultKind::CorruptProof) {
+        let other = responses[(a + 1) % signer_count][0].0.b_proof.clone();
+        for answers in responses[a].iter_mut() {
+            corrupt_dlog(&mut answers.0.b_proof, &other);
+            corrupt_dlog(&mut answers.2.b_proof, &other);
+        }
+    }
+    let mut delta_vec = Vec::new();
+    let mut sigma_vec = Vec::new();
+    for (a, sk) in sign_keys_vec.iter().enumerate() {
+        let dk = &keys[s[a]].dk;
+        let mut alpha_vec = Vec::new();
+        let mut miu_vec = Vec::new();
+        for b in (0..signer_count).filter(|b| *b != a) {
+            let (m_b_gamma, _, m_b_w, _) = &responses[b][position(a, b)];
+            let bob = party_of(b);
+            let alphas = m_b_gamma
+                .verify_proofs_get_alpha(dk, &sk.k_i, false, DRY_RUN_CONTEXT, bob)
+                .and_then(|(alpha, _)| {
+                    let (miu, _) =
+                        m_b_w.verify_proofs_get_alpha(dk, &sk.k_i, false, DRY_RUN_CONTEXT, bob)?;
+                    Ok((alpha, miu))
+                });
+            match alphas {
+                Ok((alpha, miu)) => {
+                    alpha_vec.push(alpha);
+                    miu_vec.push(miu);
+                }
+                Err(e) => return Ok(aborted(sign, 2, e, vec![party_of(b)])),
+            }
+        }
+        let beta_vec = responses[a]
+            .iter()
+            .map(|answers| answers.1.clone())
+            .collect::<Vec<FE>>();
+        let ni_vec = responses[a]
+            .iter()
+            .map(|answers| answers.3.clone())
+            .collect::<Vec<FE>>();
+        delta_vec.push(sk.phase2_delta_i(&alpha_vec, &beta_vec));
+        sigma_vec.push(sk.phase2_sigma_i(&miu_vec, &ni_vec));
+    }
+
+    // signing round 3: delta_i
+    if let Some(outcome) = stalled(fault, sign, 3) {
+        return Ok(outcome);
+    }
+    if let Some(a) = signer_hit(3, FaultKind::WrongShare) {
+        delta_vec[a] = perturb(&delta_vec[a]);
+    }
+
+    // signing round 4: decommitments to g^gamma_i
+    if let Some(outcome) = stalled(fault, sign, 4) {
+        return Ok(outcome);
+    }
+    let b_proof_vec = responses
+        .iter()
+        .map(|row| &row[0].0.b_proof)
+        .collect::<Vec<&DLogProof>>();
+    let blame = SignKeys::phase4_blame(&b_proof_vec, &decommit_vec, &bc_vec);
+    if !blame.is_empty() {
+        return Ok(aborted(sign, 4, InvalidKey, blamed_parties(&blame)));
+    }
+    let delta_inv = SignKeys::phase3_reconstruct_delta(&delta_vec);
+    let R = match SignKeys::phase4(&delta_inv, &b_proof_vec, decommit_vec, &bc_vec) {
+        Ok(R) => R,
+        Err(e) => return Ok(aborted(sign, 4, e, Vec::new())),
+    };
+    let local_sig_vec = sign_keys_vec
+        .iter()
+        .zip(&sigma_vec)
+        .map(|(sk, sigma_i)| LocalSignature::phase5_local_sig(&sk.k_i, digest, &R, sigma_i, &y))
+        .collect::<Vec<LocalSignature>>();
+
+    // signing round 5: commitments to V_i, A_i and B_i
+    if let Some(outcome) = stalled(fault, sign, 5) {
+        return Ok(outcome);
+    }
+    let mut com1_vec = Vec::new();
+    let mut decom1_vec = Vec::new();
+    let mut elgamal_proof_vec = Vec::new();
+    let mut dlog_proof_rho_vec = Vec::new();
+    for local_sig in &local_sig_vec {
+        let (com1, decom1, elgamal_proof, dlog_proof_rho) =
+            local_sig.phase5a_broadcast_5b_zkproof();
+        com1_vec.push(com1);
+        decom1_vec.push(decom1);
+        elgamal_proof_vec.push(elgamal_proof);
+        dlog_proof_rho_vec.push(dlog_proof_rho);
+    }
+
+    // signing round 6: their decommitments and proofs
+    if let Some(outcome) = stalled(fault, sign, 6) {
+        return Ok(outcome);
+    }
+    if let Some(a) = signer_hit(6, FaultKind::CorruptProof) {
+        elgamal_proof_vec[a] = elgamal_proof_vec[(a + 1) % signer_count].clone();
+    }
+    let blame = LocalSignature::phase5c_blame(
+        &decom1_vec,
+        &com1_vec,
+        &elgamal_proof_vec,
+        &dlog_proof_rho_vec,
+        &R,
+    );
+    if !blame.is_empty() {
+        return Ok(aborted(sign, 6, InvalidCom, blamed_parties(&blame)));
+    }
+    let mut com2_vec = Vec::new();
+    let mut decom2_vec = Vec::new();
+    for (a, local_sig) in local_sig_vec.iter().enumerate() {
+        let mut decom1_others = decom1_vec.clone();
+        let mut com1_others = com1_vec.clone();
+        let mut elgamal_others = elgamal_proof_vec.clone();
+        let mut dlog_others = dlog_proof_rho_vec.clone();
+        decom1_others.remove(a);
+        com1_others.remove(a);
+        elgamal_others.remove(a);
+        dlog_others.remove(a);
+        match local_sig.phase5c(
+            &decom1_others,
+            &com1_others,
+            &elgamal_others,
+            &dlog_others,
+            &decom1_vec[a].V_i,
+            &R,
+        ) {
+            Ok((com2, decom2)) => {
+                com2_vec.push(com2);
+                decom2_vec.push(decom2);
+            }
+            Err(e) => return Ok(aborted(sign, 6, e, Vec::new())),
+        }
+    }
+
+    // signing round 7: commitments to U_i and T_i
+    if let Some(outcome) = stalled(fault, sign, 7) {
+        return Ok(outcome);
+    }
+
+    // signing round 8: their decommitments
+    if let Some(outcome) = stalled(fault, sign, 8) {
+        return Ok(outcome);
+    }
+    let blame = local_sig_vec[0].phase5d_blame(&decom2_vec, &com2_vec, &decom1_vec);
+    if !blame.is_empty() {
+        return Ok(aborted(sign, 8, InvalidCom, blamed_parties(&blame)));
+    }
+    let mut s_vec = Vec::new();
+    for local_sig in &local_sig_vec {
+        match local_sig.phase5d(&decom2_vec, &com2_vec, &decom1_vec) {
+            Ok(s_i) => s_vec.push(s_i),
+            Err(e) => return Ok(aborted(sign, 8, e, Vec::new())),
+        }
+    }
+
+    // signing round 9: s_i
+    if let Some(outcome) = stalled(fault, sign, 9) {
+        return Ok(outcome);
+    }
+    if let Some(a) = signer_hit(9, FaultKind::WrongShare) {
+        s_vec[a] = perturb(&s_vec[a]);
+    }
+    let mut signature = None;
+    for (a, local_sig) in local_sig_vec.iter().enumerate() {
+        let mut others = s_vec.clone();
+        others.remove(a);
+        match local_sig.output_signature(&others) {
+            Ok(sig) => signature = Some(sig),
+            Err(e) => return Ok(aborted(sign, 9, e, Vec::new())),
+        }
+    }
+    Ok(Outcome::Completed {
+        public_key: y,
+        signature: signature.unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error::InvalidSS;
+    use sha2::Sha256;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn fault(ceremony: Ceremony, round: u8, party: u16, kind: FaultKind) -> Option<Fault> {
+        Some(Fault {
+            ceremony,
+            round,
+            party,
+            kind,
+        })
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_dry_run_faults() {
+        let digest = SigningDigest::hash::<Sha256>(b"dry run");
+        match dry_run(1, 2, &[1, 2], &digest, None).unwrap() {
+            Outcome::Completed {
+                public_key,
+                signature,
+            } => assert!(crate::gg_2018::party_i::verify(
+                &signature,
+                &public_key,
+                &digest.to_big_int()
+            )
+            .is_ok()),
+            outcome => panic!("unexpected {:?}", outcome),
+        }
+
+        let outcome = dry_run(
+            1,
+            2,
+            &[1, 2],
+            &digest,
+            fault(Ceremony::Keygen, 2, 2, FaultKind::CorruptProof),
+        );
+        assert_eq!(
+            outcome,
+            Ok(Outcome::Aborted {
+                ceremony: Ceremony::Keygen,
+                round: 2,
+                error: InvalidKey,
+                blamed: vec![2],
+            })
+        );
+
+        // the combined share check fails, the per-dealer checks name the dealer
+        let outcome = dry_run(
+            1,
+            2,
+            &[1, 2],
+            &digest,
+            fault(Ceremony::Keygen, 3, 2, FaultKind::WrongShare),
+        );
+        assert_eq!(
+            outcome,
+            Ok(Outcome::Aborted {
+                ceremony: Ceremony::Keygen,
+                round: 4,
+                error: InvalidSS,
+                blamed: vec![2],
+            })
+        );
+
+        let outcome = dry_run(
+            1,
+            2,
+            &[1, 2],
+            &digest,
+            fault(Ceremony::Sign, 6, 1, FaultKind::CorruptProof),
+        );
+        assert_eq!(
+            outcome,
+            Ok(Outcome::Aborted {
+                ceremony: Ceremony::Sign,
+                round: 6,
+                error: InvalidCom,
+                blamed: vec![1],
+            })
+        );
+
+        assert_eq!(
+            dry_run(
+                1,
+                2,
+                &[1, 2],
+                &digest,
+                fault(Ceremony::Keygen, 4, 1, FaultKind::Drop)
+            )
+            .unwrap(),
+            Outcome::Stalled {
+                ceremony: Ceremony::Keygen,
+                round: 4,
+                missing: vec![1],
+            }
+        );
+        assert_eq!(
+            dry_run(
+                1,
+                2,
+                &[1, 2],
+                &digest,
+                fault(Ceremony::Sign, 4, 1, FaultKind::WrongShare)
+            ),
+            Err(DryRunError::InapplicableFault)
+        );
+    }
+}
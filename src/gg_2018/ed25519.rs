@@ -0,0 +1,625 @@
+/*
+    Ed25519 keys from a GG18 keygen.
+
+    One keygen provisions the secp256k1 key and, through this follow-up, an
+    ed25519 key for the chains that sign with EdDSA. The secp256k1 shares can
+    not be used as they are: they lie on a polynomial over the secp256k1
+    group order, and the ed25519 group has another order. Instead each party
+    rerandomizes its share into the ed25519 scalar field, v_i = H(x_i,
+    context, y), and deals v_i with a Feldman VSS over ed25519, with a proof
+    of knowledge of v_i against rogue keys. The ed25519 secret is the sum of
+    the v_i, which no party learns.
+
+    1. broadcast `DerivationCommitments`, p2p `DerivationShare`,
+    2. `finish` checks both and returns the `Ed25519Key`.
+
+    Every coefficient is derived from x_i, so deriving again with the same
+    `context` gives the same key, e.g. on a restored device, and distinct
+    contexts give unrelated keys.
+
+    `Ed25519Signer` signs in three rounds, committing to the nonce points
+    before revealing them, and outputs RFC 8032 signatures that any Ed25519
+    verifier accepts.
+
+    Messages name their sender by 0-based party index, as in batch.rs.
+*/
+#![cfg(feature = "ed25519")]
+
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use hmac::{Hmac, Mac, NewMac};
+use merlin::Transcript;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
+
+use crate::curv::elliptic::curves::traits::ECPoint;
+use crate::gg_2018::party_i::LocalKey;
+
+const ED25519_DERIVATION: &[u8] = b"tss-wasm ed25519 derivation";
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum Ed25519Error {
+    /// A party's message is missing, duplicated or from a stranger.
+    InvalidMessage,
+    InvalidCommitment,
+    InvalidProof,
+    /// A share does not match its dealer's commitments.
+    InvalidShare,
+    WrongSigners,
+    WrongRound,
+    /// A signer's share of s does not match its nonce and public share.
+    InvalidPartialSignature,
+    InvalidSignature,
+}
+
+/// Proof of knowledge of the discrete log of a commitment.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchnorrProof {
+    pub big_r: EdwardsPoint,
+    pub z: Scalar,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DerivationCommitments {
+    pub from: usize,
+    /// a_k B for the coefficients a_k of the dealt polynomial, a_0 = v_i.
+    pub commitments: Vec<EdwardsPoint>,
+    pub proof: SchnorrProof,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DerivationShare {
+    pub from: usize,
+    pub to: usize,
+    pub share: Scalar,
+}
+
+/// A party's share of an ed25519 key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ed25519Key {
+    pub index: usize,
+    pub threshold: usize,
+    pub share: Scalar,
+    pub public_key: EdwardsPoint,
+    /// x_j B for every party j, to check partial signatures with.
+    pub public_shares: Vec<EdwardsPoint>,
+}
+
+impl Ed25519Key {
+    /// The public key as the 32 bytes Ed25519 verifiers take.
+    pub fn public_key_bytes(&self) -> [u8; 32] {
+        self.public_key.compress().to_bytes()
+    }
+}
+
+/// One party's state between the two steps of a derivation.
+pub struct Ed25519Derivation {
+    index: usize,
+    threshold: usize,
+    share_count: usize,
+    domain: Vec<u8>,
+}
+
+impl LocalKey {
+    /// Starts deriving the ed25519 key named `context`. Returns the
+    /// broadcast and one share for every party, its own included.
+    pub fn derive_ed25519(
+        &self,
+        context: &str,
+    ) -> (
+        Ed25519Derivation,
+        DerivationCommitments,
+        Vec<DerivationShare>,
+    ) {
+        let index = usize::from(self.party_id) - 1;
+        let threshold = self.threshold();
+        let share_count = self.share_count();
+        let domain = derivation_domain(self, ED25519_DERIVATION, context);
+
+        let mut coefficients = (0..=threshold)
+            .map(|k| self.derivation_scalar(&domain, k))
+            .collect::<Vec<Scalar>>();
+        let commitments = coefficients
+            .iter()
+            .map(|a| a * ED25519_BASEPOINT_POINT)
+            .collect::<Vec<EdwardsPoint>>();
+        let proof = SchnorrProof::prove(&coefficients[0], &commitments[0], &domain, index);
+        let shares = (0..share_count)
+            .map(|to| DerivationShare {
+                from: index,
+                to,
+                share: evaluate(&coefficients, to),
+            })
+            .collect();
+        coefficients.zeroize();
+
+        let derivation = Ed25519Derivation {
+            index,
+            threshold,
+            share_count,
+            domain,
+        };
+        let broadcast = DerivationCommitments {
+            from: index,
+            commitments,
+            proof,
+        };
+        (derivation, broadcast, shares)
+    }
+
+    // keyed by x_i, and 512 bits reduced mod l so the bias is negligible
+    pub(crate) fn derivation_scalar(&self, domain: &[u8], k: usize) -> Scalar {
+        let mut mac = Hmac::<Sha512>::new_from_slice(&self.shared_keys.x_i.to_bytes_be()).unwrap();
+        mac.update(domain);
+        mac.update(&(k as u64).to_be_bytes());
+        let mut wide = [0u8; 64];
+        wide.copy_from_slice(&mac.finalize().into_bytes());
+        let scalar = Scalar::from_bytes_mod_order_wide(&wide);
+        wide.zeroize();
+        scalar
+    }
+}
+
+// the scheme, the context and the secp256k1 group key, so that neither the
+// derived key nor its proofs carry over to another key
+pub(crate) fn derivation_domain(key: &LocalKey, label: &[u8], context: &str) -> Vec<u8> {
+    let mut domain = label.to_vec();
+    domain.extend_from_slice(&(context.len() as u64).to_be_bytes());
+    domain.extend_from_slice(context.as_bytes());
+    domain.extend_from_slice(&key.y_sum.to_bytes(true));
+    domain
+}
+
+impl Ed25519Derivation {
+    /// Checks every party's broadcast and the share it sent here, own
+    /// included, and returns the key.
+    pub fn finish(
+        self,
+        commitments: &[DerivationCommitments],
+        shares: &[DerivationShare],
+    ) -> Result<Ed25519Key, Ed25519Error> {
+        let commitments = by_party(commitments, self.share_count, |m| m.from)?;
+        let shares = by_party(
+            shares.iter().filter(|s| s.to == self.index),
+            self.share_count,
+            |m| m.from,
+        )?;
+
+        for ((j, dealt), share) in commitments.iter().enumerate().zip(&shares) {
+            let well_formed = dealt.commitments.len() == self.threshold + 1
+                && dealt.commitments[0] != EdwardsPoint::identity()
+                && dealt.commitments.iter().all(|c| c.is_torsion_free());
+            if !well_formed {
+                return Err(Ed25519Error::InvalidCommitment);
+            }
+            dealt.proof.verify(&dealt.commitments[0], &self.domain, j)?;
+            if share.share * ED25519_BASEPOINT_POINT
+                != evaluate_commitments(&dealt.commitments, self.index)
+            {
+                return Err(Ed25519Error::InvalidShare);
+            }
+        }
+
+        // commitments add up coefficient-wise to those of the summed sharing
+        let combined = (0..=self.threshold)
+            .map(|k| commitments.iter().map(|c| c.commitments[k]).sum())
+            .collect::<Vec<EdwardsPoint>>();
+        Ok(Ed25519Key {
+            index: self.index,
+            threshold: self.threshold,
+            share: shares.iter().map(|s| s.share).sum(),
+            public_key: combined[0],
+            public_shares: (0..self.share_count)
+                .map(|j| evaluate_commitments(&combined, j))
+                .collect(),
+        })
+    }
+}
+
+impl SchnorrProof {
+    fn prove(secret: &Scalar, public: &EdwardsPoint, domain: &[u8], prover: usize) -> SchnorrProof {
+        let mut r = random_scalar();
+        let big_r = r * ED25519_BASEPOINT_POINT;
+        let c = schnorr_challenge(public, &big_r, domain, prover);
+        let z = r + c * secret;
+        r.zeroize();
+        SchnorrProof { big_r, z }
+    }
+
+    fn verify(
+        &self,
+        public: &EdwardsPoint,
+        domain: &[u8],
+        prover: usize,
+    ) -> Result<(), Ed25519Error> {
+        let c = schnorr_challenge(public, &self.big_r, domain, prover);
+        if self.z * ED25519_BASEPOINT_POINT == self.big_r + c * public {
+            Ok(())
+        } else {
+            Err(Ed25519Error::InvalidProof)
+        }
+    }
+}
+
+fn schnorr_challenge(
+    public: &EdwardsPoint,
+    big_r: &EdwardsPoint,
+    domain: &[u8],
+    prover: usize,
+) -> Scalar {
+    let mut transcript = Transcript::new(b"tss-wasm ed25519 pok");
+    transcript.append_message(b"domain", domain);
+    transcript.append_u64(b"prover", prover as u64);
+    transcript.append_message(b"public", public.compress().as_bytes());
+    transcript.append_message(b"R", big_r.compress().as_bytes());
+    let mut wide = [0u8; 64];
+    transcript.challenge_bytes(b"challenge", &mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NonceCommitment {
+    pub from: usize,
+    pub com: [u8; 32],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NonceReveal {
+    pub from: usize,
+    pub big_r: EdwardsPoint,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialSignature {
+    pub from: usize,
+    pub s: Scalar,
+}
+
+/// One signer's state through the three rounds of an ed25519 signature:
+///
+/// 1. broadcast `NonceCommitment`, from `new`,
+/// 2. broadcast `NonceReveal`, from `round2`,
+/// 3. broadcast `PartialSignature`, from `round3`,
+///
+/// and `finish` puts the signature together. Each step takes the messages
+/// of every signer, its own included.
+pub struct Ed25519Signer {
+    key: Ed25519Key,
+    session_id: String,
+    signers: Vec<usize>,
+    message: Vec<u8>,
+    round: usize,
+    nonce: Scalar,
+    big_r_i: EdwardsPoint,
+    // per signer, in `signers` order
+    coms: Vec<[u8; 32]>,
+    big_r_vec: Vec<EdwardsPoint>,
+    big_r: EdwardsPoint,
+    challenge: Scalar,
+}
+
+impl Drop for Ed25519Signer {
+    fn drop(&mut self) {
+        self.nonce.zeroize();
+    }
+}
+
+impl Ed25519Signer {
+    /// Starts signing `message` among the 0-based `signers`, at least t + 1
+    /// of them. `session_id` must be unique to the signature.
+    pub fn new(
+        key: &Ed25519Key,
+        session_id: &str,
+        signers: &[usize],
+        message: &[u8],
+    ) -> Result<(Ed25519Signer, NonceCommitment), Ed25519Error> {
+        let mut distinct = signers.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let valid = distinct.len() == signers.len()
+            && signers.len() > key.threshold
+            && signers.iter().all(|j| *j < key.public_shares.len())
+            && signers.contains(&key.index);
+        if !valid {
+            return Err(Ed25519Error::WrongSigners);
+        }
+
+        let nonce = random_scalar();
+        let big_r_i = nonce * ED25519_BASEPOINT_POINT;
+        let com = nonce_commitment(session_id, key.index, &big_r_i);
+        let signer = Ed25519Signer {
+            key: key.clone(),
+            session_id: session_id.to_string(),
+            signers: signers.to_vec(),
+            message: message.to_vec(),
+            round: 1,
+            nonce,
+            big_r_i,
+            coms: Vec::new(),
+            big_r_vec: Vec::new(),
+            big_r: EdwardsPoint::identity(),
+            challenge: Scalar::zero(),
+        };
+        Ok((
+            signer,
+            NonceCommitment {
+                from: key.index,
+                com,
+            },
+        ))
+    }
+
+    pub fn round2(&mut self, coms: &[NonceCommitment]) -> Result<NonceReveal, Ed25519Error> {
+        self.expect_round(1)?;
+        self.coms = self
+            .by_signer(coms, |m| m.from)?
+            .iter()
+            .map(|m| m.com)
+            .collect();
+        self.round = 2;
+        Ok(NonceReveal {
+            from: self.key.index,
+            big_r: self.big_r_i,
+        })
+    }
+
+    pub fn round3(&mut self, reveals: &[NonceReveal]) -> Result<PartialSignature, Ed25519Error> {
+        self.expect_round(2)?;
+        let reveals = self.by_signer(reveals, |m| m.from)?;
+        for (reveal, com) in reveals.iter().zip(&self.coms) {
+            let opens = reveal.big_r.is_torsion_free()
+                && nonce_commitment(&self.session_id, reveal.from, &reveal.big_r) == *com;
+            if !opens {
+                return Err(Ed25519Error::InvalidCommitment);
+            }
+        }
+        self.big_r_vec = reveals.iter().map(|m| m.big_r).collect();
+        self.big_r = self.big_r_vec.iter().sum();
+        self.challenge = challenge(
+            self.big_r.compress().as_bytes(),
+            &self.key.public_key,
+            &self.message,
+        );
+        let lambda = lagrange_at_zero(&self.signers, self.key.index);
+        self.round = 3;
+        Ok(PartialSignature {
+            from: self.key.index,
+            s: self.nonce + self.challenge * lambda * self.key.share,
+        })
+    }
+
+    /// Checks every signer's share of s and returns the signature R || s.
+    pub fn finish(&self, partials: &[PartialSignature]) -> Result<[u8; 64], Ed25519Error> {
+        self.expect_round(3)?;
+        let partials = self.by_signer(partials, |m| m.from)?;
+        for ((partial, big_r_j), j) in partials.iter().zip(&self.big_r_vec).zip(&self.signers) {
+            let lambda = lagrange_at_zero(&self.signers, *j);
+            let expected = big_r_j + self.challenge * lambda * self.key.public_shares[*j];
+            if partial.s * ED25519_BASEPOINT_POINT != expected {
+                return Err(Ed25519Error::InvalidPartialSignature);
+            }
+        }
+        let s: Scalar = partials.iter().map(|m| m.s).sum();
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(self.big_r.compress().as_bytes());
+        signature[32..].copy_from_slice(s.as_bytes());
+        verify(&self.key.public_key, &self.message, &signature)?;
+        Ok(signature)
+    }
+
+    fn expect_round(&self, round: usize) -> Result<(), Ed25519Error> {
+        if self.round == round {
+            Ok(())
+        } else {
+            Err(Ed25519Error::WrongRound)
+        }
+    }
+
+    fn by_signer<'a, T>(
+        &self,
+        messages: &'a [T],
+        from: impl Fn(&T) -> usize,
+    ) -> Result<Vec<&'a T>, Ed25519Error> {
+        by_party(messages, self.signers.len(), |m| {
+            self.signers
+                .iter()
+                .position(|j| *j == from(m))
+                .unwrap_or(usize::MAX)
+        })
+    }
+}
+
+/// Verifies a 64 byte Ed25519 signature as RFC 8032 does, without the
+/// cofactor.
+pub fn verify(
+    public_key: &EdwardsPoint,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<(), Ed25519Error> {
+    let mut r_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&signature[32..]);
+    let big_r = CompressedEdwardsY(r_bytes)
+        .decompress()
+        .ok_or(Ed25519Error::InvalidSignature)?;
+    let s = Scalar::from_canonical_bytes(s_bytes).ok_or(Ed25519Error::InvalidSignature)?;
+    let c = challenge(&r_bytes, public_key, message);
+    if s * ED25519_BASEPOINT_POINT == big_r + c * public_key {
+        Ok(())
+    } else {
+        Err(Ed25519Error::InvalidSignature)
+    }
+}
+
+// SHA-512(R || A || M) mod l
+fn challenge(r_bytes: &[u8; 32], public_key: &EdwardsPoint, message: &[u8]) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(r_bytes);
+    hasher.update(public_key.compress().as_bytes());
+    hasher.update(message);
+    let mut wide = [0u8; 64];
+    wide.copy_from_slice(&hasher.finalize());
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn nonce_commitment(session_id: &str, from: usize, big_r: &EdwardsPoint) -> [u8; 32] {
+    let mut transcript = Transcript::new(b"tss-wasm ed25519 nonce commitment");
+    transcript.append_message(b"session id", session_id.as_bytes());
+    transcript.append_u64(b"signer", from as u64);
+    transcript.append_message(b"R", big_r.compress().as_bytes());
+    let mut com = [0u8; 32];
+    transcript.challenge_bytes(b"commitment", &mut com);
+    com
+}
+
+// the polynomial at the point of the 0-based party `index`
+pub(crate) fn evaluate(coefficients: &[Scalar], index: usize) -> Scalar {
+    let x = Scalar::from((index + 1) as u64);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::zero(), |acc, a| acc * x + a)
+}
+
+fn evaluate_commitments(commitments: &[EdwardsPoint], index: usize) -> EdwardsPoint {
+    let x = Scalar::from((index + 1) as u64);
+    commitments
+        .iter()
+        .rev()
+        .fold(EdwardsPoint::identity(), |acc, c| acc * x + c)
+}
+
+pub(crate) fn lagrange_at_zero(signers: &[usize], index: usize) -> Scalar {
+    let x_i = Scalar::from((index + 1) as u64);
+    signers
+        .iter()
+        .filter(|j| **j != index)
+        .fold(Scalar::one(), |acc, j| {
+            let x_j = Scalar::from((*j + 1) as u64);
+            acc * x_j * (x_j - x_i).invert()
+        })
+}
+
+pub(crate) fn random_scalar() -> Scalar {
+    let mut wide = [0u8; 64];
+    OsRng::new().unwrap().fill_bytes(&mut wide);
+    let scalar = Scalar::from_bytes_mod_order_wide(&wide);
+    wide.zeroize();
+    scalar
+}
+
+// exactly one message from each of `count` slots, in slot order
+pub(crate) fn by_party<'a, T: 'a>(
+    messages: impl IntoIterator<Item = &'a T>,
+    count: usize,
+    slot: impl Fn(&T) -> usize,
+) -> Result<Vec<&'a T>, Ed25519Error> {
+    let mut ordered: Vec<Option<&T>> = vec![None; count];
+    for message in messages {
+        let entry = ordered
+            .get_mut(slot(message))
+            .ok_or(Ed25519Error::InvalidMessage)?;
+        if entry.replace(message).is_some() {
+            return Err(Ed25519Error::InvalidMessage);
+        }
+    }
+    ordered
+        .into_iter()
+        .map(|m| m.ok_or(Ed25519Error::InvalidMessage))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn derive(keys: &[LocalKey], context: &str) -> Vec<Ed25519Key> {
+        let (derivations, (broadcasts, shares)): (Vec<_>, (Vec<_>, Vec<_>)) = keys
+            .iter()
+            .map(|key| {
+                let (derivation, broadcast, shares) = key.derive_ed25519(context);
+                (derivation, (broadcast, shares))
+            })
+            .unzip();
+        let shares = shares.into_iter().flatten().collect::<Vec<_>>();
+        derivations
+            .into_iter()
+            .map(|d| d.finish(&broadcasts, &shares).unwrap())
+            .collect()
+    }
+
+    // the signers of `ed_keys` at `signers` up to their partial signatures
+    fn sign(
+        ed_keys: &[Ed25519Key],
+        signers: &[usize],
+        message: &[u8],
+    ) -> (Vec<Ed25519Signer>, Vec<PartialSignature>) {
+        let (mut parties, coms): (Vec<_>, Vec<_>) = signers
+            .iter()
+            .map(|j| Ed25519Signer::new(&ed_keys[*j], "ed-session", signers, message).unwrap())
+            .unzip();
+        let reveals = parties
+            .iter_mut()
+            .map(|p| p.round2(&coms).unwrap())
+            .collect::<Vec<_>>();
+        let partials = parties
+            .iter_mut()
+            .map(|p| p.round3(&reveals).unwrap())
+            .collect::<Vec<_>>();
+        (parties, partials)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_derive() {
+        let (_, keys) = dealer_keys(1, 3);
+        let ed_keys = derive(&keys, "solana");
+        assert!(ed_keys
+            .iter()
+            .all(|k| k.public_key == ed_keys[0].public_key));
+        for (j, key) in ed_keys.iter().enumerate() {
+            assert_eq!(key.share * ED25519_BASEPOINT_POINT, key.public_shares[j]);
+        }
+        // the same context derives the same key, another one does not
+        assert_eq!(derive(&keys, "solana")[0].public_key, ed_keys[0].public_key);
+        assert_ne!(derive(&keys, "near")[0].public_key, ed_keys[0].public_key);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_sign() {
+        let (_, keys) = dealer_keys(1, 3);
+        let ed_keys = derive(&keys, "solana");
+        let (parties, partials) = sign(&ed_keys, &[0, 2], b"transfer");
+        let signature = parties[0].finish(&partials).unwrap();
+        assert_eq!(
+            verify(&ed_keys[0].public_key, b"transfer", &signature),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&ed_keys[0].public_key, b"other", &signature),
+            Err(Ed25519Error::InvalidSignature)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_invalid_partial_signature() {
+        let (_, keys) = dealer_keys(1, 3);
+        let ed_keys = derive(&keys, "solana");
+        let (parties, mut partials) = sign(&ed_keys, &[0, 2], b"transfer");
+        partials[1].s += Scalar::one();
+        assert_eq!(
+            parties[0].finish(&partials),
+            Err(Ed25519Error::InvalidPartialSignature)
+        );
+    }
+}
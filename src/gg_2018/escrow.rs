@@ -0,0 +1,349 @@
+/*
+    Escrow of a key share with a quorum of escrow agents.
+
+    The agents hold a t-of-n Shamir sharing of an escrow key, given as the
+    `VerifiableSS` of its dealing, whose free commitment is the escrow key E.
+    A party exports its share x_i encrypted to E with the proofs of
+    `verifiable_encryption`, so the agents or a regulator check, without any
+    secret, that the escrowed ciphertext holds the live share committed to in
+    keygen. After a refresh the shares change and the escrow is redone. The
+    terms, an escrow id and the time before which the share is not to be
+    opened, are bound into the proofs and cannot be changed once exported.
+
+    Opening takes t + 1 agents. Each one checks the terms, the release time
+    against its own clock, and publishes s_j c1 for every bit of the
+    ciphertext, with a proof that it used its share s_j of the escrow key.
+    Any t + 1 of those combine into the masks that decrypt the share. The
+    time lock is as strong as the honesty of t + 1 agents: no single agent
+    and no holder of the export can open it early, but a quorum that ignores
+    its clock can.
+*/
+#![cfg(feature = "escrow")]
+
+use merlin::Transcript;
+
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::adaptor::DleqProof;
+use crate::gg_2018::party_i::LocalKey;
+use crate::gg_2018::verifiable_encryption::{EncryptedShare, EncryptionError};
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum EscrowError {
+    Encryption(EncryptionError),
+    /// The terms are not the ones bound into the ciphertext.
+    WrongTerms,
+    /// Opening was asked for before the terms' `not_before`.
+    TooEarly,
+    /// The agent's secret is not its share of the escrow key.
+    WrongAgentKey,
+    InvalidPartialDecryption {
+        agent: u16,
+    },
+    NotEnoughAgents,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EscrowTerms {
+    pub escrow_id: String,
+    /// Unix time in seconds before which the agents refuse to open.
+    pub not_before: u64,
+}
+
+impl EscrowTerms {
+    // the bytes bound into the ciphertext's proofs
+    fn encode(&self) -> Vec<u8> {
+        let mut out = b"tss-wasm escrow terms".to_vec();
+        out.extend_from_slice(&(self.escrow_id.len() as u64).to_be_bytes());
+        out.extend_from_slice(self.escrow_id.as_bytes());
+        out.extend_from_slice(&self.not_before.to_be_bytes());
+        out
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EscrowedShare {
+    pub terms: EscrowTerms,
+    pub encrypted: EncryptedShare,
+}
+
+/// An agent's part of opening an escrow: `s_j c1` for every bit, with one
+/// proof for all of them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartialDecryption {
+    /// The agent's index in the escrow key's sharing, counted from 1.
+    pub agent: u16,
+    pub masks: Vec<GE>,
+    pub proof: DleqProof,
+}
+
+impl LocalKey {
+    /// Encrypts this party's share to the key `agents` share, under `terms`.
+    pub fn escrow_share(&self, agents: &VerifiableSS, terms: EscrowTerms) -> EscrowedShare {
+        let encrypted = self.encrypt_share_with_context(&agents.commitments[0], &terms.encode());
+        EscrowedShare { terms, encrypted }
+    }
+
+    /// Checks that `escrowed` holds, for `agents`, the live share of the
+    /// party it names, as committed to in this key's keygen data.
+    pub fn verify_escrowed_share(
+        &self,
+        agents: &VerifiableSS,
+        escrowed: &EscrowedShare,
+    ) -> Result<(), EscrowError> {
+        escrowed.check_terms(agents)?;
+        self.verify_encrypted_share(&agents.commitments[0], &escrowed.encrypted)
+            .map_err(EscrowError::Encryption)
+    }
+}
+
+impl EscrowedShare {
+    /// Agent `agent`'s part of opening the escrow with its share of the
+    /// escrow key, at `now` in unix seconds.
+    pub fn partial_decrypt(
+        &self,
+        agents: &VerifiableSS,
+        agent: u16,
+        agent_secret: &FE,
+        now: u64,
+    ) -> Result<PartialDecryption, EscrowError> {
+        self.check_terms(agents)?;
+        if now < self.terms.not_before {
+            return Err(EscrowError::TooEarly);
+        }
+        let g: GE = ECPoint::generator();
+        if !is_agent(agents, agent)
+            || &g * agent_secret != agents.get_point_commitment(agent.into())
+        {
+            return Err(EscrowError::WrongAgentKey);
+        }
+        let masks = self
+            .encrypted
+            .c1
+            .iter()
+            .map(|u| u * agent_secret)
+            .collect::<Vec<GE>>();
+        let (base, _) = self.folded(agents, agent, &masks);
+        Ok(PartialDecryption {
+            agent,
+            masks,
+            proof: DleqProof::prove(agent_secret, &base),
+        })
+    }
+
+    pub fn verify_partial_decryption(
+        &self,
+        agents: &VerifiableSS,
+        partial: &PartialDecryption,
+    ) -> Result<(), EscrowError> {
+        let invalid = EscrowError::InvalidPartialDecryption {
+            agent: partial.agent,
+        };
+        if !is_agent(agents, partial.agent) || partial.masks.len() != self.encrypted.c1.len() {
+            return Err(invalid);
+        }
+        let (base, masks) = self.folded(agents, partial.agent, &partial.masks);
+        let agent_key = agents.get_point_commitment(partial.agent.into());
+        partial
+            .proof
+            .verify(&agent_key, &base, &masks)
+            .map_err(|_| invalid)
+    }
+
+    /// Recovers the share from the partial decryptions of at least t + 1
+    /// agents, and checks it against the keygen commitments.
+    pub fn open(
+        &self,
+        agents: &VerifiableSS,
+        partials: &[PartialDecryption],
+        vss_scheme_vec: &[VerifiableSS],
+    ) -> Result<FE, EscrowError> {
+        self.check_terms(agents)?;
+        let mut quorum: Vec<&PartialDecryption> = Vec::new();
+        for partial in partials {
+            if quorum.iter().any(|p| p.agent == partial.agent) {
+                continue;
+            }
+            self.verify_partial_decryption(agents, partial)?;
+            quorum.push(partial);
+        }
+        if quorum.len() <= agents.parameters.threshold {
+            return Err(EscrowError::NotEnoughAgents);
+        }
+        quorum.truncate(agents.parameters.threshold + 1);
+
+        let indices = quorum
+            .iter()
+            .map(|p| usize::from(p.agent) - 1)
+            .collect::<Vec<usize>>();
+        let lagrange = indices
+            .iter()
+            .map(|i| agents.map_share_to_new_params(*i, &indices))
+            .collect::<Vec<FE>>();
+        let masks = (0..self.encrypted.c1.len())
+            .map(|k| {
+                let mut terms = quorum.iter().zip(&lagrange).map(|(p, l)| &p.masks[k] * l);
+                let head = terms.next().unwrap();
+                terms.fold(head, |acc, term| acc + &term)
+            })
+            .collect::<Vec<GE>>();
+        self.encrypted
+            .decrypt_with_masks(&masks, vss_scheme_vec)
+            .map_err(EscrowError::Encryption)
+    }
+
+    fn check_terms(&self, agents: &VerifiableSS) -> Result<(), EscrowError> {
+        if self.encrypted.recovery_key != agents.commitments[0] {
+            return Err(EscrowError::Encryption(EncryptionError::WrongRecoveryKey));
+        }
+        if self.encrypted.context != self.terms.encode() {
+            return Err(EscrowError::WrongTerms);
+        }
+        Ok(())
+    }
+
+    // c1 and the masks folded with weights drawn from a transcript of both,
+    // so that one proof covers every bit
+    fn folded(&self, agents: &VerifiableSS, agent: u16, masks: &[GE]) -> (GE, GE) {
+        let mut transcript = Transcript::new(b"tss-wasm escrow partial decryption");
+        transcript.append_canonical(b"agents", agents);
+        transcript.append_canonical(b"agent", &agent);
+        transcript.append_canonical(b"c1", &self.encrypted.c1[..]);
+        transcript.append_canonical(b"masks", masks);
+        let mut terms = self.encrypted.c1.iter().zip(masks).map(|(u, mask)| {
+            let weight = transcript.challenge_scalar(b"weight");
+            (u * &weight, mask * &weight)
+        });
+        let head = terms.next().unwrap();
+        terms.fold(head, |(base, folded), (u, mask)| {
+            (base + &u, folded + &mask)
+        })
+    }
+}
+
+fn is_agent(agents: &VerifiableSS, agent: u16) -> bool {
+    agent >= 1 && usize::from(agent) <= agents.parameters.share_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    struct Escrow {
+        key: LocalKey,
+        agents: VerifiableSS,
+        agent_secrets: Vec<FE>,
+        escrowed: EscrowedShare,
+    }
+
+    // party 2's share escrowed with three agents, any two of which open it
+    // from time 1000 on
+    fn escrow() -> Escrow {
+        let key = dealer_keys(1, 3).1.remove(1);
+        let escrow_secret: FE = ECScalar::new_random();
+        let (agents, agent_secrets) = VerifiableSS::share(1, 3, &escrow_secret);
+        let terms = EscrowTerms {
+            escrow_id: "escrow-1".to_string(),
+            not_before: 1_000,
+        };
+        let escrowed = key.escrow_share(&agents, terms);
+        Escrow {
+            key,
+            agents,
+            agent_secrets,
+            escrowed,
+        }
+    }
+
+    impl Escrow {
+        fn partial(&self, agent: u16) -> PartialDecryption {
+            let secret = &self.agent_secrets[usize::from(agent) - 1];
+            self.escrowed
+                .partial_decrypt(&self.agents, agent, secret, 1_000)
+                .unwrap()
+        }
+
+        fn open(&self, partials: &[PartialDecryption]) -> Result<FE, EscrowError> {
+            self.escrowed
+                .open(&self.agents, partials, &self.key.vss_scheme_vec)
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_escrow() {
+        let escrow = escrow();
+        assert_eq!(
+            escrow
+                .key
+                .verify_escrowed_share(&escrow.agents, &escrow.escrowed),
+            Ok(())
+        );
+        assert_eq!(
+            escrow.open(&[escrow.partial(3), escrow.partial(1)]),
+            Ok(escrow.key.shared_keys.x_i.clone())
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_partial_decrypt_refused() {
+        let escrow = escrow();
+        assert_eq!(
+            escrow
+                .escrowed
+                .partial_decrypt(&escrow.agents, 1, &escrow.agent_secrets[0], 999)
+                .err(),
+            Some(EscrowError::TooEarly)
+        );
+        assert_eq!(
+            escrow
+                .escrowed
+                .partial_decrypt(&escrow.agents, 1, &escrow.agent_secrets[1], 1_000)
+                .err(),
+            Some(EscrowError::WrongAgentKey)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_open_refused() {
+        let escrow = escrow();
+        let first = escrow.partial(1);
+        assert_eq!(
+            escrow.open(std::slice::from_ref(&first)),
+            Err(EscrowError::NotEnoughAgents)
+        );
+
+        let mut forged = escrow.partial(3);
+        forged.masks.swap(0, 1);
+        assert_eq!(
+            escrow.open(&[first, forged]),
+            Err(EscrowError::InvalidPartialDecryption { agent: 3 })
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_changed_terms() {
+        // earlier terms do not match the proofs
+        let escrow = escrow();
+        let mut early = escrow.escrowed.clone();
+        early.terms.not_before = 0;
+        assert_eq!(
+            escrow.key.verify_escrowed_share(&escrow.agents, &early),
+            Err(EscrowError::WrongTerms)
+        );
+        early.encrypted.context = early.terms.encode();
+        assert_eq!(
+            escrow.key.verify_escrowed_share(&escrow.agents, &early),
+            Err(EscrowError::Encryption(EncryptionError::InvalidProof))
+        );
+    }
+}
@@ -0,0 +1,281 @@
+/*
+    Social recovery of a party's share through guardians.
+
+    1. The party splits its share x_i k-of-m with Feldman VSS, encrypts each
+       guardian's share to that guardian's key, and encrypts the rest of its
+       `LocalKey` to its own public share x_i G. The result is a
+       `GuardianSetup`, which holds nothing secret and can be stored anywhere.
+    2. The other parties check with `verify_guardian_setup` that the VSS
+       commits to x_i G as they know it from keygen. A guardian checks its
+       own share with `GuardianSetup::open`.
+    3. To recover, k guardians hand their `GuardianShare`s to the user, over a
+       private channel, e.g. ECIES to the key of the new device. Each share is
+       checked against the VSS, x_i is interpolated and checked against the
+       public share, and x_i opens the key backup.
+
+    No guardian, and neither the service provider, learns anything about x_i
+    from fewer than k shares.
+*/
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::ecies::{self, EciesCiphertext};
+use crate::gg_2018::party_i::LocalKey;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum GuardianError {
+    InvalidThreshold,
+    WrongGuardian,
+    InvalidSetup,
+    InvalidPackage,
+    InvalidShare,
+    NotEnoughShares,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuardianPackage {
+    /// 1-based, the guardian's point on the VSS polynomial.
+    pub guardian_index: u16,
+    pub guardian_key: GE,
+    pub ciphertext: EciesCiphertext,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuardianSetup {
+    pub party_id: u16,
+    /// Commits to x_i as its constant term.
+    pub vss: VerifiableSS,
+    pub packages: Vec<GuardianPackage>,
+    /// The `LocalKey`, encrypted to x_i G.
+    pub key_backup: EciesCiphertext,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuardianShare {
+    pub party_id: u16,
+    pub guardian_index: u16,
+    pub share: FE,
+}
+
+fn package_aad(party_id: u16, guardian_index: u16) -> Vec<u8> {
+    let mut aad = b"tss-wasm guardian share".to_vec();
+    aad.extend_from_slice(&party_id.to_be_bytes());
+    aad.extend_from_slice(&guardian_index.to_be_bytes());
+    aad
+}
+
+fn backup_aad(party_id: u16) -> Vec<u8> {
+    let mut aad = b"tss-wasm guardian key backup".to_vec();
+    aad.extend_from_slice(&party_id.to_be_bytes());
+    aad
+}
+
+fn public_share(vss_scheme_vec: &[VerifiableSS], party_id: u16) -> Result<GE, GuardianError> {
+    VerifiableSS::combine(vss_scheme_vec)
+        .map(|combined| combined.get_point_commitment(party_id as usize))
+        .map_err(|_| GuardianError::InvalidShare)
+}
+
+impl LocalKey {
+    /// Splits this party's share so that any `threshold` of the guardians
+    /// behind `guardian_keys` can restore it.
+    pub fn escrow_to_guardians(
+        &self,
+        guardian_keys: &[GE],
+        threshold: usize,
+    ) -> Result<GuardianSetup, GuardianError> {
+        if threshold == 0
+            || threshold > guardian_keys.len()
+            || guardian_keys.len() > u16::MAX as usize
+        {
+            return Err(GuardianError::InvalidThreshold);
+        }
+        let (vss, shares) =
+            VerifiableSS::share(threshold - 1, guardian_keys.len(), &self.shared_keys.x_i);
+        let packages = guardian_keys
+            .iter()
+            .zip(shares.iter())
+            .enumerate()
+            .map(|(i, (guardian_key, share))| {
+                let guardian_index = i as u16 + 1;
+                GuardianPackage {
+                    guardian_index,
+                    guardian_key: guardian_key.clone(),
+                    ciphertext: ecies::encrypt_message(
+                        guardian_key,
+                        &package_aad(self.party_id, guardian_index),
+                        share,
+                    ),
+                }
+            })
+            .collect();
+        let key_backup =
+            ecies::encrypt_message(&vss.commitments[0], &backup_aad(self.party_id), self);
+        Ok(GuardianSetup {
+            party_id: self.party_id,
+            vss,
+            packages,
+            key_backup,
+        })
+    }
+
+    /// Checks that `setup` shares the share of the party it names, as
+    /// committed to in this key's keygen data.
+    pub fn verify_guardian_setup(&self, setup: &GuardianSetup) -> Result<(), GuardianError> {
+        setup.check(&public_share(&self.vss_scheme_vec, setup.party_id)?)
+    }
+}
+
+impl GuardianSetup {
+    fn check(&self, public_share: &GE) -> Result<(), GuardianError> {
+        let params = &self.vss.parameters;
+        if self.vss.commitments.len() != params.threshold + 1
+            || self.packages.len() != params.share_count
+            || params.threshold >= params.share_count
+        {
+            return Err(GuardianError::InvalidSetup);
+        }
+        if &self.vss.commitments[0] != public_share {
+            return Err(GuardianError::InvalidSetup);
+        }
+        Ok(())
+    }
+
+    /// The guardian's side: decrypts its share and checks it against the VSS
+    /// and `public_share`, the party's x_i G from keygen.
+    pub fn open(
+        &self,
+        guardian_index: u16,
+        guardian_secret: &FE,
+        public_share: &GE,
+    ) -> Result<GuardianShare, GuardianError> {
+        self.check(public_share)?;
+        let g: GE = ECPoint::generator();
+        let package = self
+            .packages
+            .iter()
+            .find(|p| p.guardian_index == guardian_index)
+            .ok_or(GuardianError::WrongGuardian)?;
+        if package.guardian_key != &g * guardian_secret {
+            return Err(GuardianError::WrongGuardian);
+        }
+        let share: FE = ecies::decrypt_message(
+            guardian_secret,
+            &package_aad(self.party_id, guardian_index),
+            &package.ciphertext,
+        )
+        .map_err(|_| GuardianError::InvalidPackage)?;
+        self.vss
+            .validate_share(&share, guardian_index as usize)
+            .map_err(|_| GuardianError::InvalidShare)?;
+        Ok(GuardianShare {
+            party_id: self.party_id,
+            guardian_index,
+            share,
+        })
+    }
+
+    /// Restores the party's `LocalKey` from the shares of at least
+    /// `threshold` guardians.
+    pub fn restore(
+        &self,
+        shares: &[GuardianShare],
+        public_share: &GE,
+    ) -> Result<LocalKey, GuardianError> {
+        self.check(public_share)?;
+        let mut indices: Vec<usize> = Vec::new();
+        let mut values: Vec<FE> = Vec::new();
+        for share in shares {
+            let index = share.guardian_index as usize;
+            if share.party_id != self.party_id || index == 0 || index > self.packages.len() {
+                return Err(GuardianError::WrongGuardian);
+            }
+            if indices.contains(&(index - 1)) {
+                continue;
+            }
+            self.vss
+                .validate_share(&share.share, index)
+                .map_err(|_| GuardianError::InvalidShare)?;
+            indices.push(index - 1);
+            values.push(share.share.clone());
+        }
+        if indices.len() < self.vss.reconstruct_limit() {
+            return Err(GuardianError::NotEnoughShares);
+        }
+
+        let x_i = self.vss.reconstruct(&indices, &values);
+        let g: GE = ECPoint::generator();
+        if &g * &x_i != *public_share {
+            return Err(GuardianError::InvalidShare);
+        }
+        let key: LocalKey =
+            ecies::decrypt_message(&x_i, &backup_aad(self.party_id), &self.key_backup)
+                .map_err(|_| GuardianError::InvalidPackage)?;
+        if key.party_id != self.party_id || key.shared_keys.x_i != x_i {
+            return Err(GuardianError::InvalidPackage);
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_guardian_recovery() {
+        let (_, keys) = dealer_keys(1, 2);
+        let (user, other) = (&keys[0], &keys[1]);
+        let g: GE = ECPoint::generator();
+        let public_share = &g * &user.shared_keys.x_i;
+
+        let guardian_secrets = (0..3).map(|_| ECScalar::new_random()).collect::<Vec<FE>>();
+        let guardian_keys = guardian_secrets.iter().map(|s| &g * s).collect::<Vec<GE>>();
+        assert_eq!(
+            user.escrow_to_guardians(&guardian_keys, 4).unwrap_err(),
+            GuardianError::InvalidThreshold
+        );
+        let setup = user.escrow_to_guardians(&guardian_keys, 2).unwrap();
+        assert_eq!(other.verify_guardian_setup(&setup), Ok(()));
+
+        let opened = (1..=3)
+            .map(|i| {
+                setup
+                    .open(i, &guardian_secrets[i as usize - 1], &public_share)
+                    .unwrap()
+            })
+            .collect::<Vec<GuardianShare>>();
+        assert_eq!(
+            setup
+                .open(1, &guardian_secrets[1], &public_share)
+                .unwrap_err(),
+            GuardianError::WrongGuardian
+        );
+
+        let restored = setup.restore(&opened[1..], &public_share).unwrap();
+        assert_eq!(restored.shared_keys.x_i, user.shared_keys.x_i);
+        assert_eq!(restored.party_keys.u_i, user.party_keys.u_i);
+
+        // one guardian, counted twice, is not enough
+        let same = [opened[0].clone(), opened[0].clone()];
+        assert_eq!(
+            setup.restore(&same, &public_share).unwrap_err(),
+            GuardianError::NotEnoughShares
+        );
+        let noise: FE = ECScalar::new_random();
+        let mut forged = opened[2].clone();
+        forged.share = forged.share.clone() + noise;
+        assert_eq!(
+            setup
+                .restore(&[opened[0].clone(), forged], &public_share)
+                .unwrap_err(),
+            GuardianError::InvalidShare
+        );
+    }
+}
@@ -0,0 +1,331 @@
+/*
+    Version handshake, round 0 of keygen and signing.
+
+    Parties running different crate versions used to find out mid-ceremony,
+    from a proof that did not verify or a message that did not parse. Now
+    each party first broadcasts its `Capabilities`: the protocol versions it
+    runs, the range proof parameters it proves and verifies with, and the
+    wire formats it writes and reads. `negotiate` settles on what every
+    party supports, or names the parties that stand in the way, before any
+    secret is committed to. The parties then prove with the agreed
+    parameters and write the agreed format, see `bigint_hex::write_as`.
+*/
+
+use std::fmt;
+
+pub use crate::curv::arithmetic::bigint_hex::WireFormat;
+use crate::gg_2018::range_proofs::AliceProofParams;
+
+/// The protocol this version runs: the MtA bounds of `mta::beta_tag_bound`
+/// and the messages of `party_i`.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// The oldest protocol this version still runs.
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/// Range proof parameters, see `AliceProofParams`.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum ProofBackend {
+    /// `AliceProofParams::BITS_128`, the default.
+    RangeProofs128,
+    /// `AliceProofParams::BITS_256`, what versions before configurable
+    /// parameters prove with.
+    RangeProofs256,
+}
+
+impl ProofBackend {
+    /// In order of preference.
+    pub const ALL: [ProofBackend; 2] = [ProofBackend::RangeProofs128, ProofBackend::RangeProofs256];
+
+    pub fn params(&self) -> AliceProofParams {
+        match self {
+            ProofBackend::RangeProofs128 => AliceProofParams::BITS_128,
+            ProofBackend::RangeProofs256 => AliceProofParams::BITS_256,
+        }
+    }
+}
+
+/// What a party announces in round 0.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Capabilities {
+    pub min_protocol: u16,
+    pub max_protocol: u16,
+    pub proofs: Vec<ProofBackend>,
+    /// What the party writes unless the agreement asks for another of
+    /// `also_writes`.
+    pub writes: WireFormat,
+    /// Empty for versions that write only `writes`.
+    #[serde(default)]
+    pub also_writes: Vec<WireFormat>,
+    pub reads: Vec<WireFormat>,
+}
+
+impl Capabilities {
+    /// Those of this version.
+    pub fn local() -> Capabilities {
+        Capabilities {
+            min_protocol: MIN_PROTOCOL_VERSION,
+            max_protocol: PROTOCOL_VERSION,
+            proofs: ProofBackend::ALL.to_vec(),
+            writes: WireFormat::Hex,
+            also_writes: vec![WireFormat::DigitArrays],
+            reads: vec![WireFormat::DigitArrays, WireFormat::Hex],
+        }
+    }
+
+    fn can_write(&self, format: &WireFormat) -> bool {
+        self.writes == *format || self.also_writes.contains(format)
+    }
+}
+
+/// What the ceremony runs with.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct Agreement {
+    pub protocol: u16,
+    pub proofs: ProofBackend,
+    /// Hex in agreements of versions before the format was agreed on.
+    #[serde(default)]
+    pub format: WireFormat,
+}
+
+/// Why the parties cannot run a ceremony together. Each names the parties
+/// to upgrade, or to leave out.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum HandshakeError {
+    /// The newest protocol of `parties` is older than `required`, the
+    /// oldest another party still runs.
+    ProtocolVersion { parties: Vec<u16>, required: u16 },
+    /// No range proofs all parties support; `parties` lack those the most
+    /// parties do.
+    ProofBackend { parties: Vec<u16> },
+    /// No wire format all parties write and read; `parties` lack the one
+    /// the most parties do.
+    WireFormat { parties: Vec<u16> },
+    /// `party` sent something else than a handshake in round 0: it runs a
+    /// version from before the handshake.
+    Outdated { party: u16 },
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandshakeError::ProtocolVersion { parties, required } => write!(
+                f,
+                "parties {:?} do not run protocol version {} or newer",
+                parties, required
+            ),
+            HandshakeError::ProofBackend { parties } => {
+                write!(
+                    f,
+                    "parties {:?} share no range proofs with the others",
+                    parties
+                )
+            }
+            HandshakeError::WireFormat { parties } => {
+                write!(
+                    f,
+                    "parties {:?} share no wire format with the others",
+                    parties
+                )
+            }
+            HandshakeError::Outdated { party } => {
+                write!(f, "party {} runs a version without the handshake", party)
+            }
+        }
+    }
+}
+
+/// Settles on the newest protocol every party runs, and the preferred range
+/// proofs and wire format every party supports, given each party's number and capabilities,
+/// this party's included. Every party reaches the same agreement from the
+/// same announcements.
+pub fn negotiate(parties: &[(u16, Capabilities)]) -> Result<Agreement, HandshakeError> {
+    let required = parties
+        .iter()
+        .map(|(_, c)| c.min_protocol)
+        .max()
+        .unwrap_or(0);
+    let protocol = parties
+        .iter()
+        .map(|(_, c)| c.max_protocol)
+        .min()
+        .unwrap_or(PROTOCOL_VERSION);
+    if protocol < required {
+        return Err(HandshakeError::ProtocolVersion {
+            parties: numbers(parties, |c| c.max_protocol < required),
+            required,
+        });
+    }
+
+    let format = preferred(parties, &WireFormat::ALL, |c, format| {
+        c.can_write(format) && c.reads.contains(format)
+    })
+    .map_err(|parties| HandshakeError::WireFormat { parties })?;
+    let proofs = preferred(parties, &ProofBackend::ALL, |c, backend| {
+        c.proofs.contains(backend)
+    })
+    .map_err(|parties| HandshakeError::ProofBackend { parties })?;
+    Ok(Agreement {
+        protocol,
+        proofs,
+        format,
+    })
+}
+
+// The first of `options` that every party `supports`, or the parties lacking
+// the option the most parties support.
+fn preferred<T: Copy, F: Fn(&Capabilities, &T) -> bool>(
+    parties: &[(u16, Capabilities)],
+    options: &[T],
+    supports: F,
+) -> Result<T, Vec<u16>> {
+    // the options in order of preference, each with the parties lacking it
+    let lacking = options
+        .iter()
+        .map(|option| (*option, numbers(parties, |c| !supports(c, option))))
+        .collect::<Vec<_>>();
+    if let Some((option, _)) = lacking.iter().find(|(_, parties)| parties.is_empty()) {
+        return Ok(*option);
+    }
+    let (_, parties) = lacking
+        .into_iter()
+        .min_by_key(|(_, parties)| parties.len())
+        .unwrap();
+    Err(parties)
+}
+
+fn numbers<F: Fn(&Capabilities) -> bool>(parties: &[(u16, Capabilities)], f: F) -> Vec<u16> {
+    parties
+        .iter()
+        .filter(|(_, c)| f(c))
+        .map(|(party, _)| *party)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn committee(capabilities: Vec<Capabilities>) -> Vec<(u16, Capabilities)> {
+        (1..).zip(capabilities).collect()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_negotiate() {
+        let local = Capabilities::local();
+        let agreement = negotiate(&committee(vec![local.clone(); 3])).unwrap();
+        assert_eq!(agreement.protocol, PROTOCOL_VERSION);
+        assert_eq!(agreement.proofs, ProofBackend::RangeProofs128);
+
+        // a newer party that still runs this protocol falls back to it
+        let newer = Capabilities {
+            max_protocol: PROTOCOL_VERSION + 1,
+            ..local.clone()
+        };
+        assert_eq!(
+            negotiate(&committee(vec![local.clone(), newer.clone()])),
+            Ok(agreement)
+        );
+        let only_newer = Capabilities {
+            min_protocol: PROTOCOL_VERSION + 1,
+            ..newer
+        };
+        assert_eq!(
+            negotiate(&committee(vec![local.clone(), only_newer, local.clone()])),
+            Err(HandshakeError::ProtocolVersion {
+                parties: vec![1, 3],
+                required: PROTOCOL_VERSION + 1,
+            })
+        );
+
+        let legacy_proofs = Capabilities {
+            proofs: vec![ProofBackend::RangeProofs256],
+            ..local.clone()
+        };
+        let agreement = negotiate(&committee(vec![local.clone(), legacy_proofs.clone()]));
+        assert_eq!(agreement.unwrap().proofs, ProofBackend::RangeProofs256);
+        let new_proofs = Capabilities {
+            proofs: vec![ProofBackend::RangeProofs128],
+            ..local.clone()
+        };
+        assert_eq!(
+            negotiate(&committee(vec![
+                new_proofs.clone(),
+                legacy_proofs,
+                new_proofs
+            ])),
+            Err(HandshakeError::ProofBackend { parties: vec![2] })
+        );
+
+        let hex_only = Capabilities {
+            also_writes: Vec::new(),
+            reads: vec![WireFormat::Hex],
+            ..local.clone()
+        };
+        let digits_only = Capabilities {
+            writes: WireFormat::DigitArrays,
+            also_writes: Vec::new(),
+            reads: vec![WireFormat::DigitArrays],
+            ..local.clone()
+        };
+        assert_eq!(
+            negotiate(&committee(vec![hex_only.clone(), digits_only, hex_only])),
+            Err(HandshakeError::WireFormat { parties: vec![2] })
+        );
+    }
+
+    // A party of an older version, which proves only with the 256 bit
+    // parameters and reads and writes only digit arrays, signs with two of
+    // this one: they prove and write as it does.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_mixed_versions() {
+        use crate::curv::arithmetic::bigint_hex::write_as;
+        use crate::curv::arithmetic::num_bigint::BigInt;
+        use crate::curv::elliptic::curves::secp256_k1::FE;
+        use crate::curv::elliptic::curves::traits::ECScalar;
+        use crate::gg_2018::mta::MessageA;
+        use crate::paillier::{KeyGeneration, Paillier};
+
+        // what the older version parses a MessageA as
+        #[derive(Deserialize)]
+        struct LegacyMessageA {
+            #[allow(dead_code)]
+            c: BigInt,
+        }
+
+        let legacy = Capabilities {
+            proofs: vec![ProofBackend::RangeProofs256],
+            writes: WireFormat::DigitArrays,
+            also_writes: Vec::new(),
+            reads: vec![WireFormat::DigitArrays],
+            ..Capabilities::local()
+        };
+        let local = Capabilities::local();
+        let legacy_json = serde_json::to_string(&legacy).unwrap();
+        let legacy_json = legacy_json.replace(r#","also_writes":[]"#, "");
+        let announced = committee(vec![
+            local.clone(),
+            serde_json::from_str(&legacy_json).unwrap(),
+            local,
+        ]);
+        let agreement = negotiate(&announced).unwrap();
+        assert_eq!(agreement.proofs, ProofBackend::RangeProofs256);
+        assert_eq!(agreement.format, WireFormat::DigitArrays);
+
+        let (ek, _) = Paillier::keypair().keys();
+        let a: FE = ECScalar::new_random();
+        let randomness = BigInt::from(7u32);
+        let m_a = MessageA::a_with_params(&a, &ek, &randomness, &[], &agreement.proofs.params());
+        let written = write_as(agreement.format, || serde_json::to_string(&m_a).unwrap());
+        assert!(serde_json::from_str::<LegacyMessageA>(&written).is_ok());
+        let hex = serde_json::to_string(&m_a).unwrap();
+        assert!(serde_json::from_str::<LegacyMessageA>(&hex).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+/*
+    Health check of a stored key share.
+
+    A key file that was truncated, corrupted or mixed up with another party's
+    still parses, and then fails half way through a signing ceremony with an
+    error that blames nobody. `LocalKey::validate` re-derives what the file
+    says about itself instead: the share against the VSS commitments, the
+    group key against the committed constant terms, u_i against y_i and y_i
+    against the party's own committed constant term, and the Paillier key by
+    its factorisation and an encryption roundtrip. The other parties'
+    Paillier moduli and any h1, h2, N~ statements can only be checked for
+    sanity, not for correctness.
+*/
+
+use num_integer::Integer;
+use num_traits::One;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::arithmetic::traits::Samplable;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::LocalKey;
+use crate::paillier::zkproofs::DLogStatement;
+use crate::paillier::{Decrypt, Encrypt, EncryptionKey, Paillier, RawCiphertext, RawPlaintext};
+
+// two 1024 bit primes with their top bits set
+const MIN_MODULUS_BITS: usize = 2047;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum KeyHealthError {
+    /// A vector of the wrong length, e.g. fewer than n VSS schemes,
+    /// inconsistent VSS parameters or a party id out of range.
+    Malformed,
+    /// x_i G is not the point the VSS commitments give for the party.
+    InvalidShare,
+    /// y_sum, the shared y and the committed constant terms differ.
+    InvalidGroupKey,
+    /// u_i G is not y_i, or y_i is not the constant term the party
+    /// committed to in keygen.
+    InvalidPartyKey,
+    /// A malformed modulus, p q is not n, or a ciphertext does not decrypt
+    /// to its plaintext.
+    InvalidPaillierKey,
+    /// An h1, h2, N~ statement fails `check_dlog_statement`.
+    InvalidDLogStatement,
+}
+
+impl LocalKey {
+    /// Checks the key against itself. Cheap next to a signing ceremony: a
+    /// few point multiplications and one Paillier roundtrip.
+    pub fn validate(&self) -> Result<(), KeyHealthError> {
+        let first = self
+            .vss_scheme_vec
+            .first()
+            .ok_or(KeyHealthError::Malformed)?;
+        let n = first.parameters.share_count;
+        let consistent_vss = self.vss_scheme_vec.iter().all(|vss| {
+            vss.parameters == first.parameters && vss.commitments.len() == vss.reconstruct_limit()
+        });
+        let party_in_range = self.party_id >= 1 && usize::from(self.party_id) <= n;
+        let consistent = consistent_vss
+            && party_in_range
+            && self.vss_scheme_vec.len() == n
+            && self.paillier_key_vec.len() == n;
+        if !consistent {
+            return Err(KeyHealthError::Malformed);
+        }
+
+        let combined =
+            VerifiableSS::combine(&self.vss_scheme_vec).map_err(|_| KeyHealthError::Malformed)?;
+        let g: GE = ECPoint::generator();
+        let group_key_matches =
+            combined.commitments[0] == self.y_sum && self.shared_keys.y == self.y_sum;
+        if !group_key_matches {
+            return Err(KeyHealthError::InvalidGroupKey);
+        }
+        if combined.get_point_commitment(usize::from(self.party_id)) != &g * &self.shared_keys.x_i {
+            return Err(KeyHealthError::InvalidShare);
+        }
+        let own_vss = &self.vss_scheme_vec[usize::from(self.party_id) - 1];
+        let party_key_matches = &g * &self.party_keys.u_i == self.party_keys.y_i
+            && own_vss.commitments[0] == self.party_keys.y_i;
+        if !party_key_matches {
+            return Err(KeyHealthError::InvalidPartyKey);
+        }
+
+        self.validate_paillier_key()?;
+        if !self.paillier_key_vec.iter().all(modulus_is_sane) {
+            return Err(KeyHealthError::InvalidPaillierKey);
+        }
+        Ok(())
+    }
+
+    /// `validate`, and the h1, h2, N~ statements the key is used with. The
+    /// key file does not hold them, so they are passed in.
+    pub fn validate_with_dlog_statements(
+        &self,
+        dlog_statements: &[DLogStatement],
+    ) -> Result<(), KeyHealthError> {
+        self.validate()?;
+        if dlog_statements.len() != self.share_count() {
+            return Err(KeyHealthError::Malformed);
+        }
+        dlog_statements.iter().try_for_each(check_dlog_statement)
+    }
+
+    fn validate_paillier_key(&self) -> Result<(), KeyHealthError> {
+        let ek = &self.party_keys.ek;
+        let dk = &self.party_keys.dk;
+        let own_key = &self.paillier_key_vec[usize::from(self.party_id) - 1];
+        let factored = dk.p != dk.q && &dk.p * &dk.q == ek.n;
+        let sane = factored && own_key == ek && modulus_is_sane(ek);
+        if !sane {
+            return Err(KeyHealthError::InvalidPaillierKey);
+        }
+        let m = BigInt::sample_below(&ek.n);
+        let c: RawCiphertext = Paillier::encrypt(ek, RawPlaintext::from(&m));
+        if *Paillier::decrypt(dk, &c).0 != m {
+            return Err(KeyHealthError::InvalidPaillierKey);
+        }
+        Ok(())
+    }
+}
+
+fn modulus_is_sane(ek: &EncryptionKey) -> bool {
+    ek.n.is_odd() && ek.n.bits() >= MIN_MODULUS_BITS && ek.nn == &ek.n * &ek.n
+}
+
+/// Sanity of an h1, h2, N~ statement: an odd modulus of full size, and
+/// h1 and h2 distinct units other than 1 and -1. That h2 is a power of h1
+/// needs the statement's proof.
+pub fn check_dlog_statement(statement: &DLogStatement) -> Result<(), KeyHealthError> {
+    let one = BigInt::one();
+    let modulus = &statement.N;
+    let sane_modulus = modulus.is_odd() && modulus.bits() >= MIN_MODULUS_BITS;
+    if !sane_modulus {
+        return Err(KeyHealthError::InvalidDLogStatement);
+    }
+    let minus_one = modulus - &one;
+    let is_unit = |h: &BigInt| *h > one && *h < minus_one && h.gcd(modulus) == one;
+    let sane = is_unit(&statement.g) && is_unit(&statement.ni) && statement.g != statement.ni;
+    if !sane {
+        return Err(KeyHealthError::InvalidDLogStatement);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::secp256_k1::FE;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn local_key() -> LocalKey {
+        dealer_keys(1, 2).1.remove(0)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_validate() {
+        assert_eq!(local_key().validate(), Ok(()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_validate_malformed() {
+        let key = local_key();
+        let mut truncated = key.clone();
+        truncated.paillier_key_vec.pop();
+        assert_eq!(truncated.validate(), Err(KeyHealthError::Malformed));
+
+        let mut no_commitments = key.clone();
+        no_commitments.vss_scheme_vec.clear();
+        assert_eq!(no_commitments.validate(), Err(KeyHealthError::Malformed));
+
+        // one scheme, as a single dealer would leave it, not one per party
+        let mut one_scheme = key;
+        one_scheme.vss_scheme_vec.truncate(1);
+        assert_eq!(one_scheme.validate(), Err(KeyHealthError::Malformed));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_validate_wrong_share() {
+        let mut other_share = local_key();
+        other_share.party_id = 2;
+        assert_eq!(other_share.validate(), Err(KeyHealthError::InvalidShare));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_validate_wrong_keys() {
+        let key = local_key();
+        let mut wrong_group_key = key.clone();
+        wrong_group_key.y_sum = ECPoint::generator();
+        assert_eq!(
+            wrong_group_key.validate(),
+            Err(KeyHealthError::InvalidGroupKey)
+        );
+
+        let mut wrong_party_key = key.clone();
+        wrong_party_key.party_keys.u_i = ECScalar::new_random();
+        assert_eq!(
+            wrong_party_key.validate(),
+            Err(KeyHealthError::InvalidPartyKey)
+        );
+
+        // a consistent u_i, y_i pair that is not the committed one
+        let g: GE = ECPoint::generator();
+        let u_i: FE = ECScalar::new_random();
+        let mut uncommitted_party_key = key.clone();
+        uncommitted_party_key.party_keys.y_i = &g * &u_i;
+        uncommitted_party_key.party_keys.u_i = u_i;
+        assert_eq!(
+            uncommitted_party_key.validate(),
+            Err(KeyHealthError::InvalidPartyKey)
+        );
+
+        let mut wrong_paillier_key = key;
+        wrong_paillier_key.party_keys.dk.q = wrong_paillier_key.party_keys.dk.p.clone();
+        assert_eq!(
+            wrong_paillier_key.validate(),
+            Err(KeyHealthError::InvalidPaillierKey)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_check_dlog_statement() {
+        let n = local_key().party_keys.ek.n;
+        let statement = DLogStatement {
+            N: n.clone(),
+            g: BigInt::from(2u32),
+            ni: BigInt::from(3u32),
+        };
+        assert_eq!(check_dlog_statement(&statement), Ok(()));
+        let degenerate = DLogStatement {
+            N: n,
+            g: BigInt::one(),
+            ni: BigInt::from(3u32),
+        };
+        assert_eq!(
+            check_dlog_statement(&degenerate),
+            Err(KeyHealthError::InvalidDLogStatement)
+        );
+    }
+}
@@ -0,0 +1,247 @@
+/*
+    Moving a party's share to a replacement device.
+
+    1. The new device creates an ephemeral key and sends a
+       `MigrationRequest` to the old device.
+    2. The user compares `MigrationRequest::code` on both screens, which
+       keeps a relay from substituting its own key, and the old device
+       encrypts its `LocalKey` to the ephemeral key.
+    3. The new device decrypts the key, checks the share against the keygen
+       commitments and proves to the other parties that it holds it, bound
+       to this migration so an old proof of the same share can not be
+       replayed.
+    4. Each other party checks the proof against its own copy of the
+       commitments and acknowledges. Once the acks are in, the old device
+       deletes its key.
+
+    The share itself does not change, so nothing is reshared and the group
+    key and the other parties' keys stay as they are.
+*/
+
+use merlin::Transcript;
+use sha2::{Digest, Sha256};
+
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::ecies::{self, EciesCiphertext};
+use crate::gg_2018::party_i::LocalKey;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum MigrationError {
+    WrongParty,
+    InvalidPackage,
+    InvalidShare,
+    InvalidProof,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MigrationRequest {
+    pub party_id: u16,
+    pub device_key: GE,
+}
+
+/// The key store encrypted to the new device.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MigrationPackage {
+    pub party_id: u16,
+    pub ciphertext: EciesCiphertext,
+}
+
+/// Proof of knowledge of `x_i`, bound to the party, its group key and the
+/// new device key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PossessionProof {
+    pub commitment: GE,
+    pub response: FE,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MigrationAck {
+    pub party_id: u16,
+    pub device_key: GE,
+    pub acked_by: u16,
+}
+
+/// The new device's side of a migration. Its key only lives for the
+/// handoff.
+pub struct NewDevice {
+    party_id: u16,
+    secret: FE,
+    device_key: GE,
+}
+
+impl MigrationRequest {
+    /// Six digits derived from the device key, for the user to compare on
+    /// both devices before the old one sends its key.
+    pub fn code(&self) -> String {
+        let hash = Sha256::digest(&self.device_key.to_bytes(true));
+        let n = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+        format!("{:06}", n % 1_000_000)
+    }
+}
+
+fn package_aad(party_id: u16, device_key: &GE) -> Vec<u8> {
+    let mut aad = b"tss-wasm device migration".to_vec();
+    aad.extend_from_slice(&party_id.to_be_bytes());
+    aad.extend_from_slice(&device_key.to_bytes(true));
+    aad
+}
+
+fn possession_challenge(
+    request: &MigrationRequest,
+    y_sum: &GE,
+    public_share: &GE,
+    commitment: &GE,
+) -> FE {
+    let mut transcript = Transcript::new(b"tss-wasm device migration");
+    transcript.append_canonical(b"party_id", &request.party_id);
+    transcript.append_canonical(b"device_key", &request.device_key);
+    transcript.append_canonical(b"y_sum", y_sum);
+    transcript.append_canonical(b"public_share", public_share);
+    transcript.append_canonical(b"commitment", commitment);
+    transcript.challenge_scalar(b"challenge")
+}
+
+fn public_share(vss_scheme_vec: &[VerifiableSS], party_id: u16) -> Result<GE, MigrationError> {
+    VerifiableSS::combine(vss_scheme_vec)
+        .map(|combined| combined.get_point_commitment(party_id as usize))
+        .map_err(|_| MigrationError::InvalidShare)
+}
+
+impl NewDevice {
+    pub fn new(party_id: u16) -> (NewDevice, MigrationRequest) {
+        let g: GE = ECPoint::generator();
+        let secret: FE = ECScalar::new_random();
+        let device_key = &g * &secret;
+        let request = MigrationRequest {
+            party_id,
+            device_key: device_key.clone(),
+        };
+        (
+            NewDevice {
+                party_id,
+                secret,
+                device_key,
+            },
+            request,
+        )
+    }
+
+    /// Opens the package and proves possession of the share it holds.
+    pub fn receive(
+        &self,
+        package: &MigrationPackage,
+    ) -> Result<(LocalKey, PossessionProof), MigrationError> {
+        if package.party_id != self.party_id {
+            return Err(MigrationError::WrongParty);
+        }
+        let aad = package_aad(self.party_id, &self.device_key);
+        let key: LocalKey = ecies::decrypt_message(&self.secret, &aad, &package.ciphertext)
+            .map_err(|_| MigrationError::InvalidPackage)?;
+        if key.party_id != self.party_id {
+            return Err(MigrationError::WrongParty);
+        }
+        let g: GE = ECPoint::generator();
+        let public_share = public_share(&key.vss_scheme_vec, key.party_id)?;
+        if &g * &key.shared_keys.x_i != public_share {
+            return Err(MigrationError::InvalidShare);
+        }
+
+        let request = MigrationRequest {
+            party_id: self.party_id,
+            device_key: self.device_key.clone(),
+        };
+        let nonce: FE = ECScalar::new_random();
+        let commitment = &g * &nonce;
+        let challenge = possession_challenge(&request, &key.y_sum, &public_share, &commitment);
+        let response = nonce + challenge * &key.shared_keys.x_i;
+        Ok((
+            key,
+            PossessionProof {
+                commitment,
+                response,
+            },
+        ))
+    }
+}
+
+impl LocalKey {
+    /// The old device's side: encrypts this key to the requesting device.
+    /// Call only after the user has compared `request.code()`.
+    pub fn migrate_to(
+        &self,
+        request: &MigrationRequest,
+    ) -> Result<MigrationPackage, MigrationError> {
+        if request.party_id != self.party_id {
+            return Err(MigrationError::WrongParty);
+        }
+        let aad = package_aad(request.party_id, &request.device_key);
+        Ok(MigrationPackage {
+            party_id: request.party_id,
+            ciphertext: ecies::encrypt_message(&request.device_key, &aad, self),
+        })
+    }
+
+    /// Another party's check that the new device holds the migrating
+    /// party's share.
+    pub fn confirm_migration(
+        &self,
+        request: &MigrationRequest,
+        proof: &PossessionProof,
+    ) -> Result<MigrationAck, MigrationError> {
+        if request.party_id == self.party_id {
+            return Err(MigrationError::WrongParty);
+        }
+        let public_share = public_share(&self.vss_scheme_vec, request.party_id)?;
+        let challenge =
+            possession_challenge(request, &self.y_sum, &public_share, &proof.commitment);
+        let g: GE = ECPoint::generator();
+        if &g * &proof.response != proof.commitment.clone() + &(&public_share * &challenge) {
+            return Err(MigrationError::InvalidProof);
+        }
+        Ok(MigrationAck {
+            party_id: request.party_id,
+            device_key: request.device_key.clone(),
+            acked_by: self.party_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_migration() {
+        let (_, keys) = dealer_keys(1, 2);
+        let (old, other) = (&keys[0], &keys[1]);
+
+        let (device, request) = NewDevice::new(1);
+        assert_eq!(request.code().len(), 6);
+        let package = old.migrate_to(&request).unwrap();
+        let (migrated, proof) = device.receive(&package).unwrap();
+        assert_eq!(migrated.shared_keys.x_i, old.shared_keys.x_i);
+        let ack = other.confirm_migration(&request, &proof).unwrap();
+        assert_eq!(ack.acked_by, 2);
+
+        // the proof does not carry over to another device key
+        let (_, substituted) = NewDevice::new(1);
+        assert_eq!(
+            other.confirm_migration(&substituted, &proof).unwrap_err(),
+            MigrationError::InvalidProof
+        );
+        // nor does the package
+        let (stranger, _) = NewDevice::new(1);
+        assert_eq!(
+            stranger.receive(&package).unwrap_err(),
+            MigrationError::InvalidPackage
+        );
+    }
+}
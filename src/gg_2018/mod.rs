@@ -13,7 +13,32 @@
 
     @license GPL-3.0+ <https://github.com/KZen-networks/multi-party-ecdsa/blob/master/LICENSE>
 */
+#![cfg(feature = "gg18")]
 
+pub mod adaptor;
+pub mod batch;
+pub mod conformance;
+pub mod contribution;
+pub mod dealer;
+pub mod dry_run;
+pub mod ed25519;
+pub mod escrow;
+pub mod guardians;
+pub mod handshake;
+pub mod health;
+pub mod migration;
 pub mod mta;
+pub mod nested;
 pub mod party_i;
+pub mod policy;
+pub mod presign;
+pub mod proof_cache;
+pub mod quorum;
+pub mod randomness;
 pub mod range_proofs;
+pub mod seeded;
+pub mod sr25519;
+pub mod streaming;
+pub mod threshold_signature;
+pub mod tweak;
+pub mod verifiable_encryption;
@@ -15,35 +15,83 @@
 */
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::arithmetic::traits::Samplable;
-use crate::curv::cryptographic_primitives::proofs::sigma_dlog::{DLogProof, ProveDLog};
+use crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
 use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
 use crate::curv::elliptic::curves::traits::*;
-use crate::paillier::{Add, Decrypt, Encrypt, Mul};
+use crate::paillier::{Add, Decrypt, Mul};
 use crate::paillier::{DecryptionKey, EncryptionKey, Paillier, RawCiphertext, RawPlaintext};
+use num_integer::Integer;
+use num_traits::Pow;
+use subtle::ConstantTimeEq;
 
-use crate::gg_2018::party_i::PartyPrivate;
-use crate::Error::{self, InvalidKey};
+use crate::Error::{
+    self, InvalidCiphertext, InvalidKey, InvalidPlaintext, InvalidRandomness, MtaShareOutOfRange,
+};
 
-use crate::gg_2018::range_proofs::AliceProof;
+use crate::gg_2018::range_proofs::{AliceProof, AliceProofParams};
 use crate::paillier::zkproofs::DLogStatement;
 use crate::paillier::Randomness;
 
-use crate::curv::elliptic::curves::secp256_k1::{Secp256k1Point, Secp256k1Scalar};
+use crate::curv::elliptic::curves::secp256_k1::Secp256k1Scalar;
+use crate::impl_canonical_encode;
 use crate::paillier::traits::EncryptWithChosenRandomness;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MessageA {
-    pub c: BigInt,                     // paillier encryption
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
+    pub c: BigInt, // paillier encryption
     pub range_proofs: Vec<AliceProof>, // proofs (using other parties' h1,h2,N_tilde) that the plaintext is small
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MessageB {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub c: BigInt, // paillier encryption
     pub b_proof: DLogProof,
     pub beta_tag_proof: DLogProof,
 }
 
+/// The bound of Bob's mask beta'. Where Alice's range proofs were checked,
+/// a is below q^3 and q^5 hides a*b up to a statistical distance of 1/q, as
+/// in GG18 section 3. Without them a can be anything below N, and only a
+/// mask uniform below N hides a*b.
+pub fn beta_tag_bound(alice_ek: &EncryptionKey, range_checked: bool) -> BigInt {
+    match range_checked {
+        true => FE::q().pow(5u32),
+        false => alice_ek.n.clone(),
+    }
+}
+
+/// The bound of an honest Bob's share a*b + beta' for Alice's a below q:
+/// q^5 + q^4 where her range proofs were checked. A larger one means Bob
+/// multiplied by more than a scalar, which is how MtA attacks read bits of
+/// a out of Alice's answers. Without range proofs the share is uniform
+/// below N, which bounds nothing.
+pub fn alpha_bound(alice_ek: &EncryptionKey, range_checked: bool) -> BigInt {
+    match range_checked {
+        true => FE::q().pow(5u32) + FE::q().pow(4u32),
+        false => alice_ek.n.clone(),
+    }
+}
+
+impl_canonical_encode!(MessageA { c, range_proofs });
+impl_canonical_encode!(MessageB {
+    c,
+    b_proof,
+    beta_tag_proof
+});
+
 impl MessageA {
     pub fn a(
         a: &Secp256k1Scalar,
@@ -60,6 +108,23 @@ impl MessageA {
         alice_ek: &EncryptionKey,
         randomness: &BigInt,
         dlog_statements: &[DLogStatement],
+    ) -> Self {
+        MessageA::a_with_params(
+            a,
+            alice_ek,
+            randomness,
+            dlog_statements,
+            &AliceProofParams::default(),
+        )
+    }
+
+    /// `a_with_predefined_randomness` with range proofs under `params`.
+    pub fn a_with_params(
+        a: &Secp256k1Scalar,
+        alice_ek: &EncryptionKey,
+        randomness: &BigInt,
+        dlog_statements: &[DLogStatement],
+        params: &AliceProofParams,
     ) -> Self {
         let c_a = Paillier::encrypt_with_chosen_randomness(
             alice_ek,
@@ -72,7 +137,14 @@ impl MessageA {
         let alice_range_proofs = dlog_statements
             .iter()
             .map(|dlog_statement| {
-                AliceProof::generate(&a.to_big_int(), &c_a, alice_ek, dlog_statement, randomness)
+                AliceProof::generate_with_params(
+                    &a.to_big_int(),
+                    &c_a,
+                    alice_ek,
+                    dlog_statement,
+                    randomness,
+                    params,
+                )
             })
             .collect::<Vec<AliceProof>>();
 
@@ -84,26 +156,55 @@ impl MessageA {
 }
 
 impl MessageB {
+    /// Bob's answer to `m_a` with `b`, as party `bob` of the session
+    /// `context`.
     pub fn b(
         b: &Secp256k1Scalar,
         alice_ek: &EncryptionKey,
         m_a: MessageA,
         dlog_statements: &[DLogStatement],
+        context: &[u8],
+        bob: u16,
     ) -> Result<(Self, Secp256k1Scalar, BigInt, BigInt), Error> {
-        let beta_tag = BigInt::sample_below(&alice_ek.n);
+        MessageB::b_with_proof_params(
+            b,
+            alice_ek,
+            m_a,
+            dlog_statements,
+            &AliceProofParams::default(),
+            context,
+            bob,
+        )
+    }
+
+    /// `b`, checking Alice's range proofs under `params`.
+    pub fn b_with_proof_params(
+        b: &Secp256k1Scalar,
+        alice_ek: &EncryptionKey,
+        m_a: MessageA,
+        dlog_statements: &[DLogStatement],
+        params: &AliceProofParams,
+        context: &[u8],
+        bob: u16,
+    ) -> Result<(Self, Secp256k1Scalar, BigInt, BigInt), Error> {
+        let beta_tag = BigInt::sample_below(&beta_tag_bound(alice_ek, !dlog_statements.is_empty()));
         let randomness = BigInt::sample_below(&alice_ek.n);
-        let (m_b, beta) = MessageB::b_with_predefined_randomness(
+        let (m_b, beta) = MessageB::b_with_params(
             b,
             alice_ek,
             m_a,
             &randomness,
             &beta_tag,
             dlog_statements,
+            params,
+            context,
+            bob,
         )?;
 
         Ok((m_b, beta, randomness, beta_tag))
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn b_with_predefined_randomness(
         b: &Secp256k1Scalar,
         alice_ek: &EncryptionKey,
@@ -111,21 +212,69 @@ impl MessageB {
         randomness: &BigInt,
         beta_tag: &BigInt,
         dlog_statements: &[DLogStatement],
+        context: &[u8],
+        bob: u16,
+    ) -> Result<(Self, Secp256k1Scalar), Error> {
+        MessageB::b_with_params(
+            b,
+            alice_ek,
+            m_a,
+            randomness,
+            beta_tag,
+            dlog_statements,
+            &AliceProofParams::default(),
+            context,
+            bob,
+        )
+    }
+
+    /// `b_with_predefined_randomness`, checking Alice's range proofs under
+    /// `params`. `beta_tag` must be below `beta_tag_bound`, for range proofs
+    /// checked if `dlog_statements` is not empty.
+    ///
+    /// The proofs for b and beta' are bound to `context` and `bob`, so they
+    /// do not verify in another session or as another party's.
+    #[allow(clippy::too_many_arguments)]
+    pub fn b_with_params(
+        b: &Secp256k1Scalar,
+        alice_ek: &EncryptionKey,
+        m_a: MessageA,
+        randomness: &BigInt,
+        beta_tag: &BigInt,
+        dlog_statements: &[DLogStatement],
+        params: &AliceProofParams,
+        context: &[u8],
+        bob: u16,
     ) -> Result<(Self, Secp256k1Scalar), Error> {
         if m_a.range_proofs.len() != dlog_statements.len() {
             return Err(InvalidKey);
         }
+        if !alice_ek.is_valid_ciphertext(&m_a.c) {
+            return Err(InvalidCiphertext);
+        }
+        let range_checked = !dlog_statements.is_empty();
+        if !alice_ek.is_valid_plaintext(beta_tag)
+            || *beta_tag >= beta_tag_bound(alice_ek, range_checked)
+        {
+            return Err(InvalidPlaintext);
+        }
+        if !alice_ek.is_valid_randomness(randomness) {
+            return Err(InvalidRandomness);
+        }
         // verify proofs
         if !m_a
             .range_proofs
             .iter()
             .zip(dlog_statements)
-            .map(|(proof, dlog_statement)| proof.verify(&m_a.c, alice_ek, dlog_statement))
-            .all(|x| x)
+            .all(|(proof, dlog_statement)| {
+                proof.verify_with_params(&m_a.c, alice_ek, dlog_statement, params)
+            })
         {
             return Err(InvalidKey);
         };
-        let beta_tag_fe: Secp256k1Scalar = ECScalar::from(beta_tag);
+        // a beta' of 0 mod q has no proof, and would leave a*b in the clear
+        let beta_tag_fe = FE::from_big_int_checked(&beta_tag.mod_floor(&FE::q()))
+            .map_err(|_| InvalidPlaintext)?;
         let c_beta_tag = Paillier::encrypt_with_chosen_randomness(
             alice_ek,
             RawPlaintext::from(beta_tag),
@@ -140,8 +289,9 @@ impl MessageB {
         );
         let c_b = Paillier::add(alice_ek, b_c_a, c_beta_tag);
         let beta = FE::zero().sub(&beta_tag_fe.get_element());
-        let dlog_proof_b = DLogProof::prove(b);
-        let dlog_proof_beta_tag = DLogProof::prove(&beta_tag_fe);
+        let dlog_proof_b = DLogProof::prove_in_context(b, &proof_context("b", context), bob);
+        let dlog_proof_beta_tag =
+            DLogProof::prove_in_context(&beta_tag_fe, &proof_context("beta'", context), bob);
 
         Ok((
             Self {
@@ -153,48 +303,64 @@ impl MessageB {
         ))
     }
 
+    /// Alice's share of the answer of party `bob` of the session `context`
+    /// to her `a`, with the plaintext it was reduced from. `range_checked`
+    /// says whether her `MessageA` carried range proofs, and so which
+    /// `alpha_bound` the share is held to.
     pub fn verify_proofs_get_alpha(
         &self,
         dk: &DecryptionKey,
         a: &Secp256k1Scalar,
+        range_checked: bool,
+        context: &[u8],
+        bob: u16,
     ) -> Result<(Secp256k1Scalar, BigInt), Error> {
+        let ek = EncryptionKey::from(&(&dk.p * &dk.q));
+        if !ek.is_valid_ciphertext(&self.c) {
+            return Err(InvalidCiphertext);
+        }
         let alice_share = Paillier::decrypt(dk, &RawCiphertext::from(self.c.clone()));
+        // checked before the reduction mod q, which would hide it
+        if *alice_share.0 >= alpha_bound(&ek, range_checked) {
+            return Err(MtaShareOutOfRange);
+        }
+        // alpha is only meaningful mod q; reduce explicitly rather than rely
+        // on the scalar codec, which can not hold a zero alpha
+        let alpha_bn = alice_share.0.mod_floor(&FE::q());
+        let alpha = FE::from_big_int_checked(&alpha_bn).map_err(|_| InvalidPlaintext)?;
         let g: GE = ECPoint::generator();
-        let alpha: FE = ECScalar::from(&alice_share.0);
         let g_alpha = g * &alpha;
         let ba_btag = &self.b_proof.pk * a + &self.beta_tag_proof.pk;
-        match DLogProof::verify(&self.b_proof).is_ok()
-            && DLogProof::verify(&self.beta_tag_proof).is_ok()
-            && ba_btag == g_alpha
-        {
+        self.verify_proofs(context, bob)?;
+        match bool::from(ba_btag.ct_eq(&g_alpha)) {
             true => Ok((alpha, alice_share.0.into_owned())),
             false => Err(InvalidKey),
         }
     }
 
-    //  another version, supportion PartyPrivate therefore binding mta to gg18.
-    //  with the regular version mta can be used in general
-    pub fn verify_proofs_get_alpha_gg18(
-        &self,
-        private: &PartyPrivate,
-        a: &FE,
-    ) -> Result<FE, Error> {
-        let alice_share = private.decrypt(self.c.clone());
-        let g: GE = ECPoint::generator();
-        let alpha: FE = ECScalar::from(&alice_share.0);
-        let g_alpha = g * &alpha;
-        let ba_btag = &self.b_proof.pk * a + &self.beta_tag_proof.pk;
-
-        match DLogProof::verify(&self.b_proof).is_ok()
-            && DLogProof::verify(&self.beta_tag_proof).is_ok()
-            && ba_btag.get_element() == g_alpha.get_element()
-        {
-            true => Ok(alpha),
+    /// Checks that the proofs for b and beta' are party `bob`'s, made in
+    /// the session `context`.
+    pub fn verify_proofs(&self, context: &[u8], bob: u16) -> Result<(), Error> {
+        let b_valid =
+            DLogProof::verify_in_context(&self.b_proof, &proof_context("b", context), bob).is_ok();
+        let beta_tag_valid = DLogProof::verify_in_context(
+            &self.beta_tag_proof,
+            &proof_context("beta'", context),
+            bob,
+        )
+        .is_ok();
+        match b_valid && beta_tag_valid {
+            true => Ok(()),
             false => Err(InvalidKey),
         }
     }
 
     pub fn verify_b_against_public(public_gb: &GE, mta_gb: &GE) -> bool {
-        public_gb.get_element() == mta_gb.get_element()
+        public_gb.ct_eq(mta_gb).into()
     }
 }
+
+// what a proof of Bob's is for: the witness, and the session it is sent in
+fn proof_context(witness: &str, context: &[u8]) -> Vec<u8> {
+    ("tss-wasm mta", witness, context).to_canonical_bytes()
+}
@@ -0,0 +1,201 @@
+/*
+    Nested sharing of a party's key share.
+
+    A party can split its share x_i into a t'-of-n' Feldman sharing held by
+    its own devices, e.g. a hot and a cold sub-share with t' = 1, n' = 2. The
+    stored key keeps everything but x_i, and the party can only sign after
+    t'+1 devices hand in their sub-shares. The sub-sharing is local to the
+    party: the other parties and the group key are unaffected.
+
+    The sub-shares are recombined into x_i on the signing device for the
+    duration of the ceremony, so the quorum gates access to the share but
+    does not replace the device's own protection while signing.
+*/
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::{Keys, LocalKey, SharedKeys};
+use crate::paillier::EncryptionKey;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum NestedError {
+    InvalidThreshold,
+    InvalidSubShare,
+    NotEnoughSubShares,
+    WrongParty,
+}
+
+/// One device's piece of a party's share. `index` is 1-based.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubShare {
+    pub party_id: u16,
+    pub index: usize,
+    pub x: FE,
+}
+
+/// A `LocalKey` whose share `x_i` is held as sub-shares. `sub_vss` commits
+/// to the sub-sharing, its constant term being `x_i * G`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NestedKey {
+    pub party_keys: Keys,
+    pub y: GE,
+    pub party_id: u16,
+    pub vss_scheme_vec: Vec<VerifiableSS>,
+    pub paillier_key_vec: Vec<EncryptionKey>,
+    pub y_sum: GE,
+    pub sub_vss: VerifiableSS,
+}
+
+impl LocalKey {
+    // x_i * G as the other parties see it, from every dealer's commitments
+    fn public_share(&self) -> GE {
+        let index = self.party_id as usize;
+        let mut points = self
+            .vss_scheme_vec
+            .iter()
+            .map(|vss| vss.get_point_commitment(index));
+        let head = points.next().unwrap();
+        points.fold(head, |acc, p| acc + &p)
+    }
+
+    /// Splits `x_i` into `n` sub-shares, any `t + 1` of which recombine it.
+    pub fn split_share(
+        &self,
+        t: usize,
+        n: usize,
+    ) -> Result<(NestedKey, Vec<SubShare>), NestedError> {
+        if t >= n {
+            return Err(NestedError::InvalidThreshold);
+        }
+        let (sub_vss, shares) = VerifiableSS::share(t, n, &self.shared_keys.x_i);
+        let sub_shares = shares
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| SubShare {
+                party_id: self.party_id,
+                index: i + 1,
+                x,
+            })
+            .collect();
+        let nested = NestedKey {
+            party_keys: self.party_keys.clone(),
+            y: self.shared_keys.y.clone(),
+            party_id: self.party_id,
+            vss_scheme_vec: self.vss_scheme_vec.clone(),
+            paillier_key_vec: self.paillier_key_vec.clone(),
+            y_sum: self.y_sum.clone(),
+            sub_vss,
+        };
+        Ok((nested, sub_shares))
+    }
+}
+
+impl NestedKey {
+    /// Checks a device's sub-share against the sub-sharing commitments.
+    pub fn verify_sub_share(&self, sub_share: &SubShare) -> Result<(), NestedError> {
+        if sub_share.party_id != self.party_id {
+            return Err(NestedError::WrongParty);
+        }
+        if sub_share.index == 0 || sub_share.index > self.sub_vss.parameters.share_count {
+            return Err(NestedError::InvalidSubShare);
+        }
+        self.sub_vss
+            .validate_share(&sub_share.x, sub_share.index)
+            .map_err(|_| NestedError::InvalidSubShare)
+    }
+
+    /// Rebuilds the signing key from at least `t + 1` distinct sub-shares.
+    pub fn recombine(&self, sub_shares: &[SubShare]) -> Result<LocalKey, NestedError> {
+        let mut indices = Vec::new();
+        let mut shares = Vec::new();
+        for sub_share in sub_shares {
+            self.verify_sub_share(sub_share)?;
+            if !indices.contains(&(sub_share.index - 1)) {
+                indices.push(sub_share.index - 1);
+                shares.push(sub_share.x.clone());
+            }
+        }
+        if indices.len() < self.sub_vss.reconstruct_limit() {
+            return Err(NestedError::NotEnoughSubShares);
+        }
+        let x_i = self.sub_vss.reconstruct(&indices, &shares);
+
+        let key = LocalKey {
+            party_keys: self.party_keys.clone(),
+            shared_keys: SharedKeys {
+                y: self.y.clone(),
+                x_i,
+            },
+            party_id: self.party_id,
+            vss_scheme_vec: self.vss_scheme_vec.clone(),
+            paillier_key_vec: self.paillier_key_vec.clone(),
+            y_sum: self.y_sum.clone(),
+        };
+        // the sub-sharing must be of this party's share, not of any value
+        let g: GE = ECPoint::generator();
+        if &g * &key.shared_keys.x_i != key.public_share() {
+            return Err(NestedError::InvalidSubShare);
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn local_key() -> LocalKey {
+        dealer_keys(1, 3).1.remove(1)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_split_and_recombine() {
+        // hot and cold device, both needed
+        let key = local_key();
+        let (nested, sub_shares) = key.split_share(1, 2).unwrap();
+        let recombined = nested.recombine(&sub_shares).unwrap();
+        assert_eq!(recombined.shared_keys.x_i, key.shared_keys.x_i);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_not_enough_sub_shares() {
+        let (nested, sub_shares) = local_key().split_share(1, 2).unwrap();
+        assert_eq!(
+            nested.recombine(&sub_shares[..1]).unwrap_err(),
+            NestedError::NotEnoughSubShares
+        );
+        let duplicated = vec![sub_shares[0].clone(), sub_shares[0].clone()];
+        assert_eq!(
+            nested.recombine(&duplicated).unwrap_err(),
+            NestedError::NotEnoughSubShares
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_invalid_sub_share() {
+        let (nested, sub_shares) = local_key().split_share(1, 2).unwrap();
+        let mut forged = sub_shares[1].clone();
+        forged.x = ECScalar::new_random();
+        assert_eq!(
+            nested.verify_sub_share(&forged),
+            Err(NestedError::InvalidSubShare)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_invalid_threshold() {
+        assert_eq!(
+            local_key().split_share(2, 2).unwrap_err(),
+            NestedError::InvalidThreshold
+        );
+    }
+}
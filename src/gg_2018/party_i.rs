@@ -16,11 +16,13 @@
     @license GPL-3.0+ <https://github.com/KZen-networks/multi-party-ecdsa/blob/master/LICENSE>
 */
 
+use crate::gg_2018::adaptor::DleqProof;
+use crate::gg_2018::proof_cache::ProofCache;
 use crate::paillier::zkproofs::NICorrectKeyProof;
 use crate::paillier::KeyGeneration;
 use crate::paillier::Paillier;
-use crate::paillier::{DecryptionKey, EncryptionKey};
-use crate::Error::{self, InvalidCom, InvalidKey, InvalidSS, InvalidSig};
+use crate::paillier::{DecryptionKey, EncryptionKey, Keypair};
+use crate::Error::{self, InvalidCom, InvalidDigest, InvalidKey, InvalidSS, InvalidSig};
 
 use crate::curv::arithmetic::traits::*;
 
@@ -35,8 +37,12 @@ use crate::curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elg
 use crate::curv::cryptographic_primitives::proofs::sigma_dlog::{DLogProof, ProveDLog};
 use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
 use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::impl_canonical_encode;
 use crate::paillier::{Decrypt, RawCiphertext, RawPlaintext};
+use digest::Digest;
 use num_integer::Integer;
+use std::fmt;
+use subtle::ConstantTimeEq;
 
 const SECURITY: usize = 256;
 
@@ -57,22 +63,94 @@ pub struct PartyPrivate {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct KeyGenBroadcastMessage1 {
     pub e: EncryptionKey,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub com: BigInt,
     pub correct_key_proof: NICorrectKeyProof,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct KeyGenDecommitMessage1 {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub blind_factor: BigInt,
     pub y_i: GE,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Threshold `t` and party count `n`; any `t + 1` of the `n` parties can
+/// sign. Only built through `Parameters::new`, also when deserialized.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedParameters")]
 pub struct Parameters {
-    pub threshold: usize,   //t
-    pub share_count: usize, //n
+    threshold: usize,   //t
+    share_count: usize, //n
+}
+
+#[derive(Deserialize)]
+struct UncheckedParameters {
+    threshold: usize,
+    share_count: usize,
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum ParametersError {
+    TooFewParties,
+    TooManyParties,
+    ThresholdTooLarge,
+    IndexOutOfRange,
+    WrongSignerCount,
+    DuplicateSigner,
+}
+
+impl fmt::Display for ParametersError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid parameters: {:?}", self)
+    }
+}
+
+/// A signer whose message failed a check, by its position in the vectors
+/// handed to the check. The message is a broadcast every signer holds, so
+/// anyone can re-run the check and confirm the blame.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct Blame {
+    pub index: usize,
+    pub reason: BlameReason,
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum BlameReason {
+    /// Phase 4: g^gamma_i does not open the phase 1 commitment, or is not
+    /// the value the MtA with gamma_i was run with.
+    GammaDecommitment,
+    /// Phase 5: a decommitment does not open its commitment.
+    Phase5Commitment,
+    /// Phase 5B: the proof for V_i, A_i and B_i does not verify.
+    Phase5Proof,
+    /// Phase 5D: u_i or t_i was not computed with the rho_i and l_i of
+    /// A_i and B_i.
+    Phase5DProof,
+    /// Keygen phase 2: the dealer's commitments are malformed, do not
+    /// commit to its y_i, or do not match the share it sent. Only the
+    /// receiver of the share can re-run this check.
+    VssShare,
+}
+
+impl TryFrom<UncheckedParameters> for Parameters {
+    type Error = ParametersError;
+
+    fn try_from(p: UncheckedParameters) -> Result<Self, Self::Error> {
+        Parameters::new(p.threshold, p.share_count)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -91,12 +169,24 @@ pub struct SignKeys {
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SignBroadcastPhase1 {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub com: BigInt,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SignDecommitPhase1 {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub blind_factor: BigInt,
     pub g_gamma_i: GE,
 }
@@ -107,40 +197,318 @@ pub struct LocalSignature {
     pub rho_i: FE,
     pub R: GE,
     pub s_i: FE,
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub m: BigInt,
     pub y: GE,
+    /// For an adaptor signature, R adapted to the adaptor point, whose x
+    /// coordinate is r instead of R's; see `gg_2018::adaptor`.
+    #[serde(default)]
+    pub adapted_R: Option<GE>,
 }
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Phase5Com1 {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub com: BigInt,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Phase5Com2 {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub com: BigInt,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Phase5ADecom1 {
     pub V_i: GE,
     pub A_i: GE,
     pub B_i: GE,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub blind_factor: BigInt,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Phase5DDecom2 {
     pub u_i: GE,
     pub t_i: GE,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub blind_factor: BigInt,
+    /// u_i = rho_i V for the rho_i of A_i = rho_i G.
+    pub u_proof: DleqProof,
+    /// t_i = l_i A for the l_i of B_i = l_i A_i, where A sums the other
+    /// signers' A_j.
+    pub t_proof: DleqProof,
 }
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Signature {
     pub r: FE,
     pub s: FE,
 }
 
+/// The 32 byte value a signature is computed over.
+///
+/// ECDSA signs the digest read as a big-endian integer and reduced mod q.
+/// Digests longer than 32 bytes keep only their leftmost 32 bytes (SEC1
+/// 4.1.3) and shorter ones are left-padded with zeros, so the reduction never
+/// sees more than 256 bits. Messages are signed unhashed only through
+/// `from_unhashed`: otherwise either pass a digest computed elsewhere to
+/// `from_prehashed`, or let `hash` apply a `Digest` to the raw bytes.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SigningDigest([u8; 32]);
+
+impl SigningDigest {
+    /// Wraps a digest computed by the caller. Anything but 32 bytes is rejected.
+    pub fn from_prehashed(digest: &[u8]) -> Result<SigningDigest, Error> {
+        if digest.len() != 32 {
+            return Err(InvalidDigest);
+        }
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(digest);
+        Ok(SigningDigest(bytes))
+    }
+
+    /// The message itself read as an integer and reduced mod q, whatever its
+    /// length, as `gg18_sign_client_new_context` has always signed it.
+    pub fn from_unhashed(message: &[u8]) -> SigningDigest {
+        let m = BigInt::from_bytes_be(message).mod_floor(FE::group_order());
+        let m = BigInt::to_vec(&m);
+        let mut bytes = [0u8; 32];
+        bytes[32 - m.len()..].copy_from_slice(&m);
+        SigningDigest(bytes)
+    }
+
+    pub fn hash<D: Digest>(message: &[u8]) -> SigningDigest {
+        let digest = D::digest(message);
+        let mut bytes = [0u8; 32];
+        if digest.len() >= 32 {
+            bytes.copy_from_slice(&digest[..32]);
+        } else {
+            bytes[32 - digest.len()..].copy_from_slice(&digest);
+        }
+        SigningDigest(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The digest as an integer, before reduction mod q.
+    pub fn to_big_int(&self) -> BigInt {
+        BigInt::from_bytes_be(&self.0)
+    }
+
+    /// The digest reduced mod q, as used in `s = k(m + r x)`. A digest of 0
+    /// or q is as valid as any other and gives the zero scalar.
+    pub fn to_scalar(&self) -> FE {
+        message_scalar(&self.to_big_int())
+    }
+}
+
+/// Everything a party keeps after keygen. The wasm key store is the json
+/// encoding of this struct's fields as a tuple, in declaration order. A key
+/// is refused when deserialized, or read from a key store, without the
+/// keygen's VSS commitments.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedLocalKey")]
+pub struct LocalKey {
+    pub party_keys: Keys,
+    pub shared_keys: SharedKeys,
+    pub party_id: u16,
+    pub vss_scheme_vec: Vec<VerifiableSS>,
+    pub paillier_key_vec: Vec<EncryptionKey>,
+    pub y_sum: GE,
+}
+
+#[derive(Deserialize)]
+struct UncheckedLocalKey {
+    party_keys: Keys,
+    shared_keys: SharedKeys,
+    party_id: u16,
+    vss_scheme_vec: Vec<VerifiableSS>,
+    paillier_key_vec: Vec<EncryptionKey>,
+    y_sum: GE,
+}
+
+impl TryFrom<UncheckedLocalKey> for LocalKey {
+    type Error = &'static str;
+
+    fn try_from(key: UncheckedLocalKey) -> Result<Self, Self::Error> {
+        if key.vss_scheme_vec.is_empty() {
+            return Err("key has no VSS commitments");
+        }
+        Ok(LocalKey {
+            party_keys: key.party_keys,
+            shared_keys: key.shared_keys,
+            party_id: key.party_id,
+            vss_scheme_vec: key.vss_scheme_vec,
+            paillier_key_vec: key.paillier_key_vec,
+            y_sum: key.y_sum,
+        })
+    }
+}
+
+impl LocalKey {
+    pub fn from_key_store(key_store: &str) -> serde_json::Result<LocalKey> {
+        let (party_keys, shared_keys, party_id, vss_scheme_vec, paillier_key_vec, y_sum): (
+            Keys,
+            SharedKeys,
+            u16,
+            Vec<VerifiableSS>,
+            Vec<EncryptionKey>,
+            GE,
+        ) = serde_json::from_str(key_store)?;
+        LocalKey::try_from(UncheckedLocalKey {
+            party_keys,
+            shared_keys,
+            party_id,
+            vss_scheme_vec,
+            paillier_key_vec,
+            y_sum,
+        })
+        .map_err(serde::de::Error::custom)
+    }
+
+    pub fn to_key_store(&self) -> String {
+        serde_json::to_string(&(
+            &self.party_keys,
+            &self.shared_keys,
+            self.party_id,
+            &self.vss_scheme_vec,
+            &self.paillier_key_vec,
+            &self.y_sum,
+        ))
+        .unwrap()
+    }
+
+    /// Panics on a key built with no VSS commitments, which deserializing
+    /// refuses.
+    pub fn threshold(&self) -> usize {
+        self.vss_scheme_vec[0].parameters.threshold
+    }
+
+    pub fn share_count(&self) -> usize {
+        self.vss_scheme_vec[0].parameters.share_count
+    }
+}
+
+impl_canonical_encode!(KeyGenBroadcastMessage1 {
+    e,
+    com,
+    correct_key_proof
+});
+impl_canonical_encode!(KeyGenDecommitMessage1 { blind_factor, y_i });
+impl_canonical_encode!(Parameters {
+    threshold,
+    share_count
+});
+impl_canonical_encode!(SignBroadcastPhase1 { com });
+impl_canonical_encode!(SignDecommitPhase1 {
+    blind_factor,
+    g_gamma_i
+});
+impl_canonical_encode!(Phase5Com1 { com });
+impl_canonical_encode!(Phase5Com2 { com });
+impl_canonical_encode!(Phase5ADecom1 {
+    V_i,
+    A_i,
+    B_i,
+    blind_factor
+});
+impl_canonical_encode!(Phase5DDecom2 {
+    u_i,
+    t_i,
+    blind_factor,
+    u_proof,
+    t_proof
+});
+impl_canonical_encode!(Signature { r, s });
+
+impl Parameters {
+    /// Checks `t < n` and `2 <= n`, with `n` fitting the `u16` party ids.
+    pub fn new(threshold: usize, share_count: usize) -> Result<Parameters, ParametersError> {
+        if share_count < 2 {
+            return Err(ParametersError::TooFewParties);
+        }
+        if share_count > u16::MAX as usize {
+            return Err(ParametersError::TooManyParties);
+        }
+        if threshold >= share_count {
+            return Err(ParametersError::ThresholdTooLarge);
+        }
+        Ok(Parameters {
+            threshold,
+            share_count,
+        })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn share_count(&self) -> usize {
+        self.share_count
+    }
+
+    /// The size of a signing set, `t + 1`.
+    pub fn signer_count(&self) -> usize {
+        self.threshold + 1
+    }
+
+    /// Checks a 1-based party index, as used in keygen and for party ids.
+    pub fn check_index(&self, index: usize) -> Result<(), ParametersError> {
+        if index == 0 || index > self.share_count {
+            return Err(ParametersError::IndexOutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Checks a signing set of 0-based party indices, as in `signers_vec`.
+    pub fn check_signers(&self, signers: &[usize]) -> Result<(), ParametersError> {
+        if signers.len() != self.signer_count() {
+            return Err(ParametersError::WrongSignerCount);
+        }
+        self.check_online_set(signers)
+    }
+
+    /// Like `check_signers`, for a set of at least `t + 1` parties that start
+    /// a ceremony some of which may drop out.
+    pub fn check_online_set(&self, online: &[usize]) -> Result<(), ParametersError> {
+        if online.len() < self.signer_count() {
+            return Err(ParametersError::WrongSignerCount);
+        }
+        for (k, signer) in online.iter().enumerate() {
+            if *signer >= self.share_count {
+                return Err(ParametersError::IndexOutOfRange);
+            }
+            if online[..k].contains(signer) {
+                return Err(ParametersError::DuplicateSigner);
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Keys {
     pub fn create(index: usize) -> Keys {
         let u: FE = ECScalar::new_random();
@@ -152,7 +520,7 @@ impl Keys {
             y_i: y,
             dk,
             ek,
-            party_index: index.clone(),
+            party_index: index,
         }
     }
 
@@ -165,14 +533,36 @@ impl Keys {
             y_i: y,
             dk,
             ek,
-            party_index: index.clone(),
+            party_index: index,
+        }
+    }
+
+    /// `Keys::create` with a Paillier keypair generated beforehand, e.g. by a
+    /// `KeypairSearch` run a few candidates at a time.
+    pub fn create_from_keypair(keypair: Keypair, index: usize) -> Keys {
+        let u: FE = ECScalar::new_random();
+        let y = &ECPoint::generator() * &u;
+        let (ek, dk) = keypair.keys();
+
+        Keys {
+            u_i: u,
+            y_i: y,
+            dk,
+            ek,
+            party_index: index,
         }
     }
 
     pub fn phase1_broadcast_phase3_proof_of_correct_key(
         &self,
     ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
-        let blind_factor = BigInt::sample(SECURITY);
+        self.phase1_broadcast_with_blind_factor(BigInt::sample(SECURITY))
+    }
+
+    pub(crate) fn phase1_broadcast_with_blind_factor(
+        &self,
+        blind_factor: BigInt,
+    ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
         let correct_key_proof = NICorrectKeyProof::proof(&self.dk);
         let com = HashCommitment::create_commitment_with_user_defined_randomness(
             &self.y_i.bytes_compressed_to_big_int(),
@@ -193,27 +583,75 @@ impl Keys {
     pub fn phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
         &self,
         params: &Parameters,
-        decom_vec: &Vec<KeyGenDecommitMessage1>,
-        bc1_vec: &Vec<KeyGenBroadcastMessage1>,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> Result<(VerifiableSS, Vec<FE>, usize), Error> {
+        let poly = VerifiableSS::sample_polynomial(params.threshold, &self.u_i);
+        self.phase1_verify_com_phase2_distribute_polynomial(
+            &poly,
+            params,
+            decom_vec,
+            bc1_vec,
+            &mut ProofCache::new(),
+            None,
+        )
+    }
+
+    /// `phase1_verify_com_phase3_verify_correct_key_phase2_distribute`,
+    /// skipping the correct key proofs of Paillier keys `cache` has seen
+    /// verified, and of this party's own key, at the 0-based position `own`
+    /// of the vectors.
+    pub fn phase1_verify_com_phase3_verify_correct_key_phase2_distribute_cached(
+        &self,
+        params: &Parameters,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        cache: &mut ProofCache,
+        own: usize,
+    ) -> Result<(VerifiableSS, Vec<FE>, usize), Error> {
+        let poly = VerifiableSS::sample_polynomial(params.threshold, &self.u_i);
+        self.phase1_verify_com_phase2_distribute_polynomial(
+            &poly,
+            params,
+            decom_vec,
+            bc1_vec,
+            cache,
+            Some(own),
+        )
+    }
+
+    // `poly` is this party's sharing polynomial, its constant term `u_i`;
+    // `own` is this party's position in the vectors, if its proof may be
+    // skipped
+    pub(crate) fn phase1_verify_com_phase2_distribute_polynomial(
+        &self,
+        poly: &[FE],
+        params: &Parameters,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+        cache: &mut ProofCache,
+        own: Option<usize>,
     ) -> Result<(VerifiableSS, Vec<FE>, usize), Error> {
         // test length:
         assert_eq!(decom_vec.len(), params.share_count);
         assert_eq!(bc1_vec.len(), params.share_count);
         // test paillier correct key and test decommitments
-        let correct_key_correct_decom_all = (0..bc1_vec.len())
-            .map(|i| {
-                HashCommitment::create_commitment_with_user_defined_randomness(
+        let correct_key_correct_decom_all = (0..bc1_vec.len()).all(|i| {
+            HashCommitment::create_commitment_with_user_defined_randomness(
                     &decom_vec[i].y_i.bytes_compressed_to_big_int(),
                     &decom_vec[i].blind_factor,
                 ) == bc1_vec[i].com
-                    && bc1_vec[i].correct_key_proof.verify(&bc1_vec[i].e).is_ok()
-            })
-            .all(|x| x == true);
-
-        let (vss_scheme, secret_shares) =
-            VerifiableSS::share(params.threshold, params.share_count, &self.u_i);
+                    // our own key needs no proof; the proofs are the
+                    // costliest part of this round
+                    && (own == Some(i)
+                        || cache
+                            .verify_correct_key(&bc1_vec[i].e, &bc1_vec[i].correct_key_proof)
+                            .is_ok())
+        });
+
+        let (vss_scheme, secret_shares) = VerifiableSS::share_polynomial(params.share_count, poly);
         match correct_key_correct_decom_all {
-            true => Ok((vss_scheme, secret_shares, self.party_index.clone())),
+            true => Ok((vss_scheme, secret_shares, self.party_index)),
             false => Err(InvalidKey),
         }
     }
@@ -221,30 +659,34 @@ impl Keys {
     pub fn phase2_verify_vss_construct_keypair_phase3_pok_dlog(
         &self,
         params: &Parameters,
-        y_vec: &Vec<GE>,
-        secret_shares_vec: &Vec<FE>,
-        vss_scheme_vec: &Vec<VerifiableSS>,
+        y_vec: &[GE],
+        secret_shares_vec: &[FE],
+        vss_scheme_vec: &[VerifiableSS],
         index: &usize,
     ) -> Result<(SharedKeys, DLogProof), Error> {
         assert_eq!(y_vec.len(), params.share_count);
         assert_eq!(secret_shares_vec.len(), params.share_count);
         assert_eq!(vss_scheme_vec.len(), params.share_count);
-
-        let correct_ss_verify = (0..y_vec.len())
-            .map(|i| {
-                vss_scheme_vec[i]
-                    .validate_share(&secret_shares_vec[i], *index)
-                    .is_ok()
-                    && vss_scheme_vec[i].commitments[0].get_element() == y_vec[i].get_element()
-            })
-            .all(|x| x == true);
+        params.check_index(*index).map_err(|_| InvalidSS)?;
+
+        // x_i is the sum of the shares, so checking the sum against the
+        // combined commitments costs one evaluation instead of n and
+        // still catches any single dealer's bad share. `phase2_vss_blame`
+        // finds the dealer when it does.
+        let combined = VerifiableSS::combine(vss_scheme_vec)?;
+        let correct_y =
+            (0..y_vec.len()).all(|i| bool::from(vss_scheme_vec[i].commitments[0].ct_eq(&y_vec[i])));
+        let sum_of_shares = secret_shares_vec.iter().fold(FE::zero(), |acc, x| acc + x);
+        let correct_ss_verify = correct_y
+            && combined.parameters.threshold == params.threshold
+            && combined.validate_share(&sum_of_shares, *index).is_ok();
 
         match correct_ss_verify {
             true => {
                 let mut y_vec_iter = y_vec.iter();
                 let y0 = y_vec_iter.next().unwrap();
                 let y = y_vec_iter.fold(y0.clone(), |acc, x| acc + x);
-                let x_i = secret_shares_vec.iter().fold(FE::zero(), |acc, x| acc + x);
+                let x_i = sum_of_shares;
                 let dlog_proof = DLogProof::prove(&x_i);
                 Ok((SharedKeys { y, x_i }, dlog_proof))
             }
@@ -252,30 +694,49 @@ impl Keys {
         }
     }
 
-    pub fn get_commitments_to_xi(vss_scheme_vec: &Vec<VerifiableSS>) -> Vec<GE> {
-        let len = vss_scheme_vec.len();
-        let xi_points_vec = (1..len + 1)
-            .map(|i| {
-                let xij_points_vec = (0..len)
-                    .map(|j| vss_scheme_vec[j].get_point_commitment(i))
-                    .collect::<Vec<GE>>();
-
-                let mut xij_points_iter = xij_points_vec.iter();
-                let first = xij_points_iter.next().unwrap();
-
-                let tail = xij_points_iter;
-                tail.fold(first.clone(), |acc, x| acc + x)
+    /// The dealers to blame when `phase2_verify_vss_construct_keypair_phase3_pok_dlog`
+    /// fails: its combined check does not say whose share was wrong, so
+    /// every dealing is checked on its own.
+    pub fn phase2_vss_blame(
+        params: &Parameters,
+        y_vec: &[GE],
+        secret_shares_vec: &[FE],
+        vss_scheme_vec: &[VerifiableSS],
+        index: usize,
+    ) -> Vec<Blame> {
+        vss_scheme_vec
+            .iter()
+            .zip(y_vec)
+            .zip(secret_shares_vec)
+            .enumerate()
+            .filter(|(_, ((vss, y_i), share))| {
+                vss.parameters.threshold != params.threshold
+                    || vss.parameters.share_count != params.share_count
+                    || vss.commitments.len() != params.threshold + 1
+                    || !bool::from(vss.commitments[0].ct_eq(y_i))
+                    || vss.validate_share(share, index).is_err()
             })
-            .collect::<Vec<GE>>();
+            .map(|(index, _)| Blame {
+                index,
+                reason: BlameReason::VssShare,
+            })
+            .collect()
+    }
 
-        xi_points_vec
+    pub fn get_commitments_to_xi(vss_scheme_vec: &[VerifiableSS]) -> Result<Vec<GE>, Error> {
+        // evaluating the combined sharing costs n evaluations instead of n^2
+        let len = vss_scheme_vec.len();
+        let combined = VerifiableSS::combine(vss_scheme_vec)?;
+        Ok((1..len + 1)
+            .map(|i| combined.get_point_commitment(i))
+            .collect::<Vec<GE>>())
     }
 
     pub fn update_commitments_to_xi(
         comm: &GE,
         vss_scheme: &VerifiableSS,
         index: usize,
-        s: &Vec<usize>,
+        s: &[usize],
     ) -> GE {
         let li = vss_scheme.map_share_to_new_params(index, s);
         comm * &li
@@ -283,14 +744,13 @@ impl Keys {
 
     pub fn verify_dlog_proofs(
         params: &Parameters,
-        dlog_proofs_vec: &Vec<DLogProof>,
-        y_vec: &Vec<GE>,
+        dlog_proofs_vec: &[DLogProof],
+        y_vec: &[GE],
     ) -> Result<(), Error> {
         assert_eq!(y_vec.len(), params.share_count);
         assert_eq!(dlog_proofs_vec.len(), params.share_count);
-        let xi_dlog_verify = (0..y_vec.len())
-            .map(|i| DLogProof::verify(&dlog_proofs_vec[i]).is_ok())
-            .all(|x| x == true);
+        let xi_dlog_verify =
+            (0..y_vec.len()).all(|i| DLogProof::verify(&dlog_proofs_vec[i]).is_ok());
 
         match xi_dlog_verify {
             true => Ok(()),
@@ -301,12 +761,11 @@ impl Keys {
 
 impl PartyPrivate {
     pub fn set_private(key: Keys, shared_key: SharedKeys) -> PartyPrivate {
-        let key_private = PartyPrivate {
+        PartyPrivate {
             u_i: key.u_i,
             x_i: shared_key.x_i,
             dk: key.dk,
-        };
-        key_private
+        }
     }
 
     pub fn y_i(&self) -> GE {
@@ -314,7 +773,7 @@ impl PartyPrivate {
         g * self.u_i.clone()
     }
 
-    pub fn decrypt(&self, ciphertext: BigInt) -> RawPlaintext {
+    pub fn decrypt(&self, ciphertext: BigInt) -> RawPlaintext<'_> {
         Paillier::decrypt(&self.dk, &RawCiphertext::from(ciphertext))
     }
 
@@ -328,7 +787,7 @@ impl PartyPrivate {
             y_i: y,
             dk,
             ek,
-            party_index: index.clone(),
+            party_index: index,
         }
     }
 
@@ -346,7 +805,7 @@ impl SignKeys {
         private: &PartyPrivate,
         vss_scheme: &VerifiableSS,
         index: usize,
-        s: &Vec<usize>,
+        s: &[usize],
     ) -> SignKeys {
         let li = vss_scheme.map_share_to_new_params(index, s);
         let w_i = li * &private.x_i;
@@ -381,29 +840,27 @@ impl SignKeys {
         )
     }
 
-    pub fn phase2_delta_i(&self, alpha_vec: &Vec<FE>, beta_vec: &Vec<FE>) -> FE {
+    pub fn phase2_delta_i(&self, alpha_vec: &[FE], beta_vec: &[FE]) -> FE {
         let vec_len = alpha_vec.len();
         assert_eq!(alpha_vec.len(), beta_vec.len());
         // assert_eq!(alpha_vec.len(), self.s.len() - 1);
         let ki_gamma_i = self.k_i.mul(&self.gamma_i.get_element());
-        let sum = (0..vec_len)
+        (0..vec_len)
             .map(|i| alpha_vec[i].add(&beta_vec[i].get_element()))
-            .fold(ki_gamma_i, |acc, x| acc + x);
-        return sum;
+            .fold(ki_gamma_i, |acc, x| acc + x)
     }
 
-    pub fn phase2_sigma_i(&self, miu_vec: &Vec<FE>, ni_vec: &Vec<FE>) -> FE {
+    pub fn phase2_sigma_i(&self, miu_vec: &[FE], ni_vec: &[FE]) -> FE {
         let vec_len = miu_vec.len();
         assert_eq!(miu_vec.len(), ni_vec.len());
         //assert_eq!(miu_vec.len(), self.s.len() - 1);
         let ki_w_i = self.k_i.mul(&self.w_i.get_element());
-        let sum = (0..vec_len)
+        (0..vec_len)
             .map(|i| miu_vec[i].add(&ni_vec[i].get_element()))
-            .fold(ki_w_i, |acc, x| acc + x);
-        return sum;
+            .fold(ki_w_i, |acc, x| acc + x)
     }
 
-    pub fn phase3_reconstruct_delta(delta_vec: &Vec<FE>) -> FE {
+    pub fn phase3_reconstruct_delta(delta_vec: &[FE]) -> FE {
         let sum = delta_vec.iter().fold(FE::zero(), |acc, x| acc + x);
         sum.invert()
     }
@@ -412,21 +869,12 @@ impl SignKeys {
         delta_inv: &FE,
         b_proof_vec: &Vec<&DLogProof>,
         phase1_decommit_vec: Vec<SignDecommitPhase1>,
-        // blind_vec: &Vec<BigInt>,
-        //  g_gamma_i_vec: &Vec<GE>,
-        bc1_vec: &Vec<SignBroadcastPhase1>,
+        // blind_vec: &[BigInt],
+        //  g_gamma_i_vec: &[GE],
+        bc1_vec: &[SignBroadcastPhase1],
     ) -> Result<GE, Error> {
-        let test_b_vec_and_com = (0..b_proof_vec.len())
-            .map(|i| {
-                b_proof_vec[i].pk.get_element() == phase1_decommit_vec[i].g_gamma_i.get_element()
-                    && HashCommitment::create_commitment_with_user_defined_randomness(
-                        &phase1_decommit_vec[i]
-                            .g_gamma_i
-                            .bytes_compressed_to_big_int(),
-                        &phase1_decommit_vec[i].blind_factor,
-                    ) == bc1_vec[i].com
-            })
-            .all(|x| x == true);
+        let test_b_vec_and_com =
+            SignKeys::phase4_blame(b_proof_vec, &phase1_decommit_vec, bc1_vec).is_empty();
 
         let mut g_gamma_i_iter = phase1_decommit_vec.iter();
 
@@ -437,25 +885,70 @@ impl SignKeys {
         match test_b_vec_and_com {
             true => Ok({
                 let gamma_sum = tail.fold(head.g_gamma_i.clone(), |acc, x| acc + &x.g_gamma_i);
-                let R = gamma_sum * delta_inv;
-                R
+                gamma_sum * delta_inv
             }),
             false => Err(InvalidKey),
         }
     }
+
+    /// The signers whose g^gamma_i does not open their phase 1 commitment or
+    /// is not the one their MtA was run with.
+    pub fn phase4_blame(
+        b_proof_vec: &[&DLogProof],
+        phase1_decommit_vec: &[SignDecommitPhase1],
+        bc1_vec: &[SignBroadcastPhase1],
+    ) -> Vec<Blame> {
+        assert_eq!(b_proof_vec.len(), phase1_decommit_vec.len());
+        assert_eq!(b_proof_vec.len(), bc1_vec.len());
+        b_proof_vec
+            .iter()
+            .zip(phase1_decommit_vec)
+            .zip(bc1_vec)
+            .enumerate()
+            .filter(|(_, ((b_proof, decommit), bc1))| {
+                let same_gamma = bool::from(b_proof.pk.ct_eq(&decommit.g_gamma_i));
+                let opens = HashCommitment::create_commitment_with_user_defined_randomness(
+                    &decommit.g_gamma_i.bytes_compressed_to_big_int(),
+                    &decommit.blind_factor,
+                ) == bc1.com;
+                !same_gamma || !opens
+            })
+            .map(|(index, _)| Blame {
+                index,
+                reason: BlameReason::GammaDecommitment,
+            })
+            .collect()
+    }
 }
 
 impl LocalSignature {
     pub fn phase5_local_sig(
         k_i: &FE,
-        message: &BigInt,
+        message: &SigningDigest,
+        R: &GE,
+        sigma_i: &FE,
+        pubkey: &GE,
+    ) -> LocalSignature {
+        LocalSignature::phase5_local_presig(k_i, message, R, None, sigma_i, pubkey)
+    }
+
+    /// `phase5_local_sig` with r taken from `adapted_R` if given. The phase 5
+    /// checks still run against R, since the s they add up to is the same.
+    pub fn phase5_local_presig(
+        k_i: &FE,
+        message: &SigningDigest,
         R: &GE,
+        adapted_R: Option<&GE>,
         sigma_i: &FE,
         pubkey: &GE,
     ) -> LocalSignature {
-        let m_fe: FE = ECScalar::from(message);
-        let r: FE = ECScalar::from(&R.x_coor().unwrap().mod_floor(&FE::q()));
-        let s_i = m_fe * k_i + r * sigma_i;
+        let m_fe: FE = message.to_scalar();
+        let r = x_mod_q(adapted_R.unwrap_or(R));
+        let s_i = if m_fe == FE::zero() {
+            r * sigma_i
+        } else {
+            m_fe * k_i + r * sigma_i
+        };
         let l_i: FE = ECScalar::new_random();
         let rho_i: FE = ECScalar::new_random();
         LocalSignature {
@@ -463,11 +956,17 @@ impl LocalSignature {
             rho_i,
             R: R.clone(),
             s_i,
-            m: message.clone(),
+            m: message.to_big_int(),
             y: pubkey.clone(),
+            adapted_R: adapted_R.cloned(),
         }
     }
 
+    /// The r of the signature: the x coordinate of R, or of `adapted_R`.
+    pub fn r(&self) -> FE {
+        x_mod_q(self.adapted_R.as_ref().unwrap_or(&self.R))
+    }
+
     pub fn phase5a_broadcast_5b_zkproof(
         &self,
     ) -> (Phase5Com1, Phase5ADecom1, HomoELGamalProof, DLogProof) {
@@ -511,40 +1010,18 @@ impl LocalSignature {
 
     pub fn phase5c(
         &self,
-        decom_vec: &Vec<Phase5ADecom1>,
-        com_vec: &Vec<Phase5Com1>,
-        elgamal_proofs: &Vec<HomoELGamalProof>,
+        decom_vec: &[Phase5ADecom1],
+        com_vec: &[Phase5Com1],
+        elgamal_proofs: &[HomoELGamalProof],
         dlog_proofs_rho: &[DLogProof],
         v_i: &GE,
         R: &GE,
     ) -> Result<(Phase5Com2, Phase5DDecom2), Error> {
         assert_eq!(decom_vec.len(), com_vec.len());
 
-        let g: GE = ECPoint::generator();
-        let test_com_elgamal = (0..com_vec.len())
-            .map(|i| {
-                let delta = HomoElGamalStatement {
-                    G: decom_vec[i].A_i.clone(),
-                    H: R.clone(),
-                    Y: g.clone(),
-                    D: decom_vec[i].V_i.clone(),
-                    E: decom_vec[i].B_i.clone(),
-                };
-                let input_hash = HSha256::create_hash_from_ge(&[
-                    &decom_vec[i].V_i,
-                    &decom_vec[i].A_i,
-                    &decom_vec[i].B_i,
-                ])
-                .to_big_int();
-
-                HashCommitment::create_commitment_with_user_defined_randomness(
-                    &input_hash,
-                    &decom_vec[i].blind_factor,
-                ) == com_vec[i].com
-                    && elgamal_proofs[i].verify(&delta).is_ok()
-                    && DLogProof::verify(&dlog_proofs_rho[i]).is_ok()
-            })
-            .all(|x| x == true);
+        let test_com_elgamal =
+            LocalSignature::phase5c_blame(decom_vec, com_vec, elgamal_proofs, dlog_proofs_rho, R)
+                .is_empty();
 
         let v_vec = (0..com_vec.len())
             .map(|i| &decom_vec[i].V_i)
@@ -560,14 +1037,13 @@ impl LocalSignature {
         let tail = a_i_iter;
         let a = tail.fold((*head).clone(), |acc, x| acc.add_point(&(*x).get_element()));
 
-        let r: FE = ECScalar::from(&self.R.x_coor().unwrap().mod_floor(&FE::q()));
-        let yr = &self.y * &r;
-        let g: GE = ECPoint::generator();
-        let m_fe: FE = ECScalar::from(&self.m);
-        let gm = &g * &m_fe;
-        let v = v.sub_point(&gm.get_element()).sub_point(&yr.get_element());
+        let v = self.phase5_v(v);
         let u_i = &v * &self.rho_i;
         let t_i = &a * &self.l_i;
+        let g: GE = ECPoint::generator();
+        let a_i = &g * &self.rho_i;
+        let u_proof = DleqProof::prove(&self.rho_i, &v);
+        let t_proof = DleqProof::prove_with_bases(&self.l_i, &a_i, &a);
         let input_hash = HSha256::create_hash_from_ge(&[&u_i, &t_i]).to_big_int();
         let blind_factor = BigInt::sample(SECURITY);
         let com = HashCommitment::create_commitment_with_user_defined_randomness(
@@ -583,6 +1059,8 @@ impl LocalSignature {
                         u_i,
                         t_i,
                         blind_factor,
+                        u_proof,
+                        t_proof,
                     },
                 )
             }),
@@ -592,24 +1070,21 @@ impl LocalSignature {
 
     pub fn phase5d(
         &self,
-        decom_vec2: &Vec<Phase5DDecom2>,
-        com_vec2: &Vec<Phase5Com2>,
-        decom_vec1: &Vec<Phase5ADecom1>,
+        decom_vec2: &[Phase5DDecom2],
+        com_vec2: &[Phase5Com2],
+        decom_vec1: &[Phase5ADecom1],
     ) -> Result<FE, Error> {
         assert_eq!(decom_vec2.len(), decom_vec1.len());
         assert_eq!(decom_vec2.len(), com_vec2.len());
 
-        let test_com = (0..com_vec2.len())
-            .map(|i| {
-                let input_hash =
-                    HSha256::create_hash_from_ge(&[&decom_vec2[i].u_i, &decom_vec2[i].t_i])
-                        .to_big_int();
-                HashCommitment::create_commitment_with_user_defined_randomness(
-                    &input_hash,
-                    &decom_vec2[i].blind_factor,
-                ) == com_vec2[i].com
-            })
-            .all(|x| x == true);
+        // a u_i the proofs did not hold to could sum to the point at
+        // infinity below, which the point arithmetic does not take
+        if !self
+            .phase5d_blame(decom_vec2, com_vec2, decom_vec1)
+            .is_empty()
+        {
+            return Err(InvalidCom);
+        }
 
         let t_vec = (0..com_vec2.len())
             .map(|i| &decom_vec2[i].t_i)
@@ -629,21 +1104,130 @@ impl LocalSignature {
         let biased_sum_tb_minus_u = u_vec
             .iter()
             .fold(biased_sum_tb, |acc, x| acc.sub_point(&x.get_element()));
-        match test_com {
-            true => {
-                if g == biased_sum_tb_minus_u {
-                    Ok(self.s_i.clone())
-                } else {
-                    Err(InvalidKey)
-                }
+        if g == biased_sum_tb_minus_u {
+            Ok(self.s_i.clone())
+        } else {
+            Err(InvalidKey)
+        }
+    }
+
+    /// The signers whose phase 5A decommitment does not open their
+    /// commitment, or whose phase 5B proofs do not verify.
+    pub fn phase5c_blame(
+        decom_vec: &[Phase5ADecom1],
+        com_vec: &[Phase5Com1],
+        elgamal_proofs: &[HomoELGamalProof],
+        dlog_proofs_rho: &[DLogProof],
+        R: &GE,
+    ) -> Vec<Blame> {
+        assert_eq!(decom_vec.len(), com_vec.len());
+        assert_eq!(decom_vec.len(), elgamal_proofs.len());
+        assert_eq!(decom_vec.len(), dlog_proofs_rho.len());
+        let g: GE = ECPoint::generator();
+        let mut blame = Vec::new();
+        for (index, (((decom, com), elgamal_proof), dlog_proof_rho)) in decom_vec
+            .iter()
+            .zip(com_vec)
+            .zip(elgamal_proofs)
+            .zip(dlog_proofs_rho)
+            .enumerate()
+        {
+            let input_hash =
+                HSha256::create_hash_from_ge(&[&decom.V_i, &decom.A_i, &decom.B_i]).to_big_int();
+            if HashCommitment::create_commitment_with_user_defined_randomness(
+                &input_hash,
+                &decom.blind_factor,
+            ) != com.com
+            {
+                blame.push(Blame {
+                    index,
+                    reason: BlameReason::Phase5Commitment,
+                });
+                continue;
+            }
+            let delta = HomoElGamalStatement {
+                G: decom.A_i.clone(),
+                H: R.clone(),
+                Y: g.clone(),
+                D: decom.V_i.clone(),
+                E: decom.B_i.clone(),
+            };
+            if elgamal_proof.verify(&delta).is_err() || DLogProof::verify(dlog_proof_rho).is_err() {
+                blame.push(Blame {
+                    index,
+                    reason: BlameReason::Phase5Proof,
+                });
             }
-            false => Err(InvalidCom),
         }
+        blame
     }
-    pub fn output_signature(&self, s_vec: &Vec<FE>) -> Result<Signature, Error> {
+
+    /// The signers whose phase 5D decommitment does not open their
+    /// commitment, or whose u_i and t_i do not match their phase 5A A_i
+    /// and B_i. All three lists are in signer order.
+    pub fn phase5d_blame(
+        &self,
+        decom_vec2: &[Phase5DDecom2],
+        com_vec2: &[Phase5Com2],
+        decom_vec1: &[Phase5ADecom1],
+    ) -> Vec<Blame> {
+        assert_eq!(decom_vec2.len(), com_vec2.len());
+        assert_eq!(decom_vec2.len(), decom_vec1.len());
+        let v_sum = decom_vec1
+            .iter()
+            .skip(1)
+            .fold(decom_vec1[0].V_i.clone(), |acc, decom| acc + &decom.V_i);
+        let a_sum = decom_vec1
+            .iter()
+            .skip(1)
+            .fold(decom_vec1[0].A_i.clone(), |acc, decom| acc + &decom.A_i);
+        let v = self.phase5_v(v_sum);
+        let mut blame = Vec::new();
+        for (index, ((decom2, com), decom1)) in
+            decom_vec2.iter().zip(com_vec2).zip(decom_vec1).enumerate()
+        {
+            let input_hash = HSha256::create_hash_from_ge(&[&decom2.u_i, &decom2.t_i]).to_big_int();
+            if HashCommitment::create_commitment_with_user_defined_randomness(
+                &input_hash,
+                &decom2.blind_factor,
+            ) != com.com
+            {
+                blame.push(Blame {
+                    index,
+                    reason: BlameReason::Phase5Commitment,
+                });
+                continue;
+            }
+            // the sum of the other signers' A_j
+            let a_others = a_sum.sub_point(&decom1.A_i.get_element());
+            let u_ok = decom2.u_proof.verify(&decom1.A_i, &v, &decom2.u_i).is_ok();
+            let t_ok = decom2
+                .t_proof
+                .verify_with_bases(&decom1.A_i, &decom1.B_i, &a_others, &decom2.t_i)
+                .is_ok();
+            if !(u_ok && t_ok) {
+                blame.push(Blame {
+                    index,
+                    reason: BlameReason::Phase5DProof,
+                });
+            }
+        }
+        blame
+    }
+
+    // V = sum V_j - m G - r y, which is sum l_j G for honest signers
+    fn phase5_v(&self, v_sum: GE) -> GE {
+        let yr = &self.y * &self.r();
+        let v = match message_point(&self.m) {
+            Some(gm) => v_sum.sub_point(&gm.get_element()),
+            None => v_sum,
+        };
+        v.sub_point(&yr.get_element())
+    }
+
+    pub fn output_signature(&self, s_vec: &[FE]) -> Result<Signature, Error> {
         let s = s_vec.iter().fold(self.s_i.clone(), |acc, x| acc + x);
-        let r: FE = ECScalar::from(&self.R.x_coor().unwrap().mod_floor(&FE::q()));
-        let sig = Signature { r, s };
+        let sig = Signature { r: self.r(), s };
         let ver = verify(&sig, &self.y, &self.m).is_ok();
         match ver {
             true => Ok(sig),
@@ -652,17 +1236,37 @@ impl LocalSignature {
     }
 }
 
+fn x_mod_q(point: &GE) -> FE {
+    ECScalar::from(&point.x_coor().unwrap().mod_floor(&FE::q()))
+}
+
+// m mod q, where `ECScalar::from` would panic on 0
+fn message_scalar(m: &BigInt) -> FE {
+    FE::from_big_int_checked(&m.mod_floor(FE::group_order())).unwrap_or_else(|_| FE::zero())
+}
+
+/// m G for the message m reduced mod q, or `None` if that is 0 and m G the
+/// point at infinity, which `GE` does not represent.
+pub fn message_point(m: &BigInt) -> Option<GE> {
+    let m_fe = message_scalar(m);
+    if m_fe == FE::zero() {
+        return None;
+    }
+    let g: GE = ECPoint::generator();
+    Some(&g * &m_fe)
+}
+
 pub fn verify(sig: &Signature, y: &GE, message: &BigInt) -> Result<(), Error> {
     let b = sig.s.invert();
-    let a: FE = ECScalar::from(message);
-    let u1 = a * &b;
     let u2 = sig.r.clone() * &b;
-
-    let g: GE = ECPoint::generator();
-    let gu1 = &g * &u1;
     let yu2 = y * &u2;
+    // m G s^-1 + r y s^-1, of which a zero message leaves the second term
+    let point = match message_point(message) {
+        Some(gm) => gm * &b + yu2,
+        None => yu2,
+    };
     // can be faster using shamir trick
-    if sig.r.clone() == ECScalar::from(&(gu1 + yu2).x_coor().unwrap().mod_floor(&FE::q())) {
+    if sig.r.clone() == ECScalar::from(&point.x_coor().unwrap().mod_floor(&FE::q())) {
         Ok(())
     } else {
         Err(InvalidSig)
@@ -0,0 +1,214 @@
+/*
+    Signing policy hooks.
+
+    A co-signer that enforces transaction rules has to do it where its share
+    leaves the process, not in the service around it: once s_i is broadcast
+    the other parties can finish the signature. `phase5d_with_policy` and
+    `PresignatureStore::sign_with_policy` ask a `SigningPolicy` first and
+    release nothing if it vetoes. The request the policy sees is checked
+    against the local signature, so an approved request cannot stand in for
+    a different message.
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::gg_2018::party_i::{
+    LocalSignature, Phase5ADecom1, Phase5Com2, Phase5DDecom2, SigningDigest,
+};
+use crate::gg_2018::presign::{PresignatureError, PresignatureStore};
+use crate::storage::Storage;
+use crate::Error;
+
+/// What a policy decides on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SigningRequest {
+    pub digest: SigningDigest,
+    pub public_key: GE,
+    /// The signers by keygen index counted from 0, as passed to
+    /// `Parameters::check_signers`.
+    pub signers: Vec<usize>,
+    /// Whatever the integrator attaches for the policy, e.g. the unsigned
+    /// transaction the digest was computed from. Not checked here.
+    pub metadata: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PolicyRejection {
+    pub reason: String,
+}
+
+impl PolicyRejection {
+    pub fn new(reason: &str) -> PolicyRejection {
+        PolicyRejection {
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PolicyRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rejected by signing policy: {}", self.reason)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PolicyError {
+    /// The policy vetoed the request.
+    Rejected(PolicyRejection),
+    /// The request names another digest or key than the local signature.
+    RequestMismatch,
+    /// A phase 5 check failed after the policy approved.
+    Protocol(Error),
+    Presignature(PresignatureError),
+}
+
+pub trait SigningPolicy {
+    fn approve(&self, request: &SigningRequest) -> Result<(), PolicyRejection>;
+}
+
+impl<F> SigningPolicy for F
+where
+    F: Fn(&SigningRequest) -> Result<(), PolicyRejection>,
+{
+    fn approve(&self, request: &SigningRequest) -> Result<(), PolicyRejection> {
+        self(request)
+    }
+}
+
+/// Approves only digests added beforehand, e.g. by the service that
+/// checked the transaction they were computed from.
+#[derive(Clone, Debug, Default)]
+pub struct DigestAllowList {
+    digests: HashSet<[u8; 32]>,
+}
+
+impl DigestAllowList {
+    pub fn new() -> DigestAllowList {
+        DigestAllowList::default()
+    }
+
+    pub fn allow(&mut self, digest: &SigningDigest) {
+        self.digests.insert(*digest.as_bytes());
+    }
+
+    pub fn revoke(&mut self, digest: &SigningDigest) {
+        self.digests.remove(digest.as_bytes());
+    }
+}
+
+impl SigningPolicy for DigestAllowList {
+    fn approve(&self, request: &SigningRequest) -> Result<(), PolicyRejection> {
+        if self.digests.contains(request.digest.as_bytes()) {
+            Ok(())
+        } else {
+            Err(PolicyRejection::new("digest not on the allow-list"))
+        }
+    }
+}
+
+impl LocalSignature {
+    /// `phase5d`, releasing s_i only if `policy` approves `request`.
+    pub fn phase5d_with_policy<P: SigningPolicy + ?Sized>(
+        &self,
+        decom_vec2: &[Phase5DDecom2],
+        com_vec2: &[Phase5Com2],
+        decom_vec1: &[Phase5ADecom1],
+        policy: &P,
+        request: &SigningRequest,
+    ) -> Result<FE, PolicyError> {
+        let matches = request.digest.to_big_int() == self.m && request.public_key == self.y;
+        if !matches {
+            return Err(PolicyError::RequestMismatch);
+        }
+        policy.approve(request).map_err(PolicyError::Rejected)?;
+        self.phase5d(decom_vec2, com_vec2, decom_vec1)
+            .map_err(PolicyError::Protocol)
+    }
+}
+
+impl PresignatureStore {
+    /// `sign`, asking `policy` before the presignature is consumed. A veto
+    /// leaves it available.
+    pub fn sign_with_policy<S: Storage + ?Sized, P: SigningPolicy + ?Sized>(
+        &mut self,
+        storage: &mut S,
+        name: &str,
+        id: &str,
+        policy: &P,
+        request: &SigningRequest,
+    ) -> Result<LocalSignature, PolicyError> {
+        policy.approve(request).map_err(PolicyError::Rejected)?;
+        self.sign(storage, name, id, &request.digest)
+            .map_err(PolicyError::Presignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::traits::*;
+    use crate::gg_2018::presign::Presignature;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_policy_veto() {
+        let y = GE::random_point();
+        let presignature = Presignature {
+            k_i: ECScalar::new_random(),
+            sigma_i: ECScalar::new_random(),
+            R: GE::random_point(),
+            y: y.clone(),
+        };
+        let mut store = PresignatureStore::new();
+        let id = store.insert(presignature).unwrap();
+        let mut storage = crate::storage::MemoryStorage::new();
+
+        let digest = SigningDigest::from_prehashed(&[7u8; 32]).unwrap();
+        let request = SigningRequest {
+            digest: digest.clone(),
+            public_key: y,
+            signers: vec![0, 1],
+            metadata: b"transfer 1 BTC".to_vec(),
+        };
+        let mut allow_list = DigestAllowList::new();
+        assert_eq!(
+            store
+                .sign_with_policy(&mut storage, "party-1", &id, &allow_list, &request)
+                .err(),
+            Some(PolicyError::Rejected(PolicyRejection::new(
+                "digest not on the allow-list"
+            )))
+        );
+        assert!(!store.is_consumed(&id));
+
+        allow_list.allow(&digest);
+        let local_sig = store
+            .sign_with_policy(&mut storage, "party-1", &id, &allow_list, &request)
+            .unwrap();
+        assert!(store.is_consumed(&id));
+
+        // a closure vetoing on the metadata, and a request for another digest
+        let no_btc = |request: &SigningRequest| {
+            if request.metadata.ends_with(b"BTC") {
+                Err(PolicyRejection::new("no BTC"))
+            } else {
+                Ok(())
+            }
+        };
+        assert_eq!(
+            local_sig.phase5d_with_policy(&[], &[], &[], &no_btc, &request),
+            Err(PolicyError::Rejected(PolicyRejection::new("no BTC")))
+        );
+        let mut other = request.clone();
+        other.digest = SigningDigest::from_prehashed(&[8u8; 32]).unwrap();
+        assert_eq!(
+            local_sig.phase5d_with_policy(&[], &[], &[], &allow_list, &other),
+            Err(PolicyError::RequestMismatch)
+        );
+    }
+}
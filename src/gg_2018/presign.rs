@@ -0,0 +1,223 @@
+#![allow(non_snake_case)]
+/*
+    Presignatures and their one-time-use bookkeeping.
+
+    Everything GG18 computes up to phase 4 is independent of the message:
+    the nonce share k_i, the share sigma_i of k*x and the nonce point R. Keeping
+    those around lets the message-dependent part run later in a single round,
+    but signing two messages with the same presignature reveals the key. The
+    store below hands each presignature out once and remembers every R it has
+    released, so a presignature restored from an old backup is refused too.
+    `PresignatureStore::sign` saves the store with the presignature marked
+    consumed before it computes anything from it, so a crash after a local
+    signature left the process can not bring the presignature back.
+*/
+
+use std::collections::{HashMap, HashSet};
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::{LocalSignature, SigningDigest};
+use crate::storage::{self, Storage, StorageError, PRESIGNATURES};
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum PresignatureError {
+    UnknownPresignature,
+    AlreadyConsumed,
+    /// Saving the store failed. The presignature stays consumed and nothing
+    /// computed from it was released.
+    NotPersisted,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Presignature {
+    pub k_i: FE,
+    pub sigma_i: FE,
+    pub R: GE,
+    pub y: GE,
+}
+
+impl Presignature {
+    /// R is fresh for every presignature, so its compressed encoding names it.
+    pub fn id(&self) -> String {
+        hex::encode(self.R.to_bytes(true))
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PresignatureStore {
+    available: HashMap<String, Presignature>,
+    consumed: HashSet<String>,
+}
+
+impl PresignatureStore {
+    pub fn new() -> PresignatureStore {
+        PresignatureStore::default()
+    }
+
+    /// Adds a presignature and returns its id. Presignatures whose R was
+    /// already handed out are refused.
+    pub fn insert(&mut self, presignature: Presignature) -> Result<String, PresignatureError> {
+        let id = presignature.id();
+        if self.consumed.contains(&id) {
+            return Err(PresignatureError::AlreadyConsumed);
+        }
+        self.available.insert(id.clone(), presignature);
+        Ok(id)
+    }
+
+    /// Ids of the presignatures still available, sorted.
+    pub fn available_ids(&self) -> Vec<String> {
+        let mut ids = self.available.keys().cloned().collect::<Vec<String>>();
+        ids.sort();
+        ids
+    }
+
+    pub fn is_consumed(&self, id: &str) -> bool {
+        self.consumed.contains(id)
+    }
+
+    /// Removes the presignature and marks it consumed in one step. When the
+    /// store is persisted, persist it after this call and before releasing
+    /// anything computed from the returned presignature.
+    pub fn take(&mut self, id: &str) -> Result<Presignature, PresignatureError> {
+        if self.consumed.contains(id) {
+            return Err(PresignatureError::AlreadyConsumed);
+        }
+        let presignature = self
+            .available
+            .remove(id)
+            .ok_or(PresignatureError::UnknownPresignature)?;
+        self.consumed.insert(id.to_string());
+        Ok(presignature)
+    }
+
+    /// Saves the store, consumed ids included, as `name` in the
+    /// `PRESIGNATURES` namespace.
+    pub fn save<S: Storage + ?Sized>(
+        &self,
+        storage: &mut S,
+        name: &str,
+    ) -> Result<(), StorageError> {
+        storage::save(storage, PRESIGNATURES, name, self)
+    }
+
+    pub fn load<S: Storage + ?Sized>(
+        storage: &S,
+        name: &str,
+    ) -> Result<Option<PresignatureStore>, StorageError> {
+        storage::load(storage, PRESIGNATURES, name)
+    }
+
+    /// Consumes the presignature, saves the store as `name` and only then
+    /// computes this party's phase 5 local signature.
+    pub fn sign<S: Storage + ?Sized>(
+        &mut self,
+        storage: &mut S,
+        name: &str,
+        id: &str,
+        message: &SigningDigest,
+    ) -> Result<LocalSignature, PresignatureError> {
+        let presignature = self.take(id)?;
+        self.save(storage, name)
+            .map_err(|_| PresignatureError::NotPersisted)?;
+        Ok(LocalSignature::phase5_local_sig(
+            &presignature.k_i,
+            message,
+            &presignature.R,
+            &presignature.sigma_i,
+            &presignature.y,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn random_presignature() -> Presignature {
+        Presignature {
+            k_i: ECScalar::new_random(),
+            sigma_i: ECScalar::new_random(),
+            R: GE::random_point(),
+            y: GE::random_point(),
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_presignature_used_once() {
+        let mut store = PresignatureStore::new();
+        let presignature = random_presignature();
+        let id = store.insert(presignature.clone()).unwrap();
+        assert_eq!(store.available_ids(), vec![id.clone()]);
+
+        let mut storage = MemoryStorage::new();
+        let message = SigningDigest::from_prehashed(&[7u8; 32]).unwrap();
+        store.sign(&mut storage, "party-1", &id, &message).unwrap();
+        assert!(store.is_consumed(&id));
+        assert_eq!(
+            store
+                .sign(&mut storage, "party-1", &id, &message)
+                .unwrap_err(),
+            PresignatureError::AlreadyConsumed
+        );
+
+        // the copy saved before the signature was released still refuses it
+        let mut restored = PresignatureStore::load(&storage, "party-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            restored.insert(presignature).unwrap_err(),
+            PresignatureError::AlreadyConsumed
+        );
+        assert_eq!(
+            restored.take("00").unwrap_err(),
+            PresignatureError::UnknownPresignature
+        );
+    }
+    struct FailingStorage;
+
+    impl Storage for FailingStorage {
+        fn get(&self, _: &str, _: &str) -> Result<Option<Vec<u8>>, StorageError> {
+            Ok(None)
+        }
+
+        fn put(&mut self, _: &str, _: &str, _: &[u8]) -> Result<(), StorageError> {
+            Err(StorageError::Backend("disk full".to_string()))
+        }
+
+        fn delete(&mut self, _: &str, _: &str) -> Result<(), StorageError> {
+            Ok(())
+        }
+
+        fn keys(&self, _: &str) -> Result<Vec<String>, StorageError> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_sign_fails_closed() {
+        let mut store = PresignatureStore::new();
+        let id = store.insert(random_presignature()).unwrap();
+        let message = SigningDigest::from_prehashed(&[7u8; 32]).unwrap();
+        assert_eq!(
+            store
+                .sign(&mut FailingStorage, "party-1", &id, &message)
+                .unwrap_err(),
+            PresignatureError::NotPersisted
+        );
+        // and it is not handed out again once the storage is back
+        assert_eq!(
+            store
+                .sign(&mut MemoryStorage::new(), "party-1", &id, &message)
+                .unwrap_err(),
+            PresignatureError::AlreadyConsumed
+        );
+    }
+}
@@ -0,0 +1,137 @@
+/*
+    Verified static proofs, remembered per key.
+
+    A party's Paillier key and its h1, h2, N~ statement do not change from
+    one ceremony to the next, and neither does the fact that their proofs
+    verified. `ProofCache` remembers the fingerprint of every key and
+    statement whose proof it has verified, so a committee with stable
+    membership verifies each peer's proofs once instead of once per
+    ceremony. The fingerprint covers the key or statement, not the proof: a
+    statement shown correct once stays correct whatever proof comes with it
+    next time.
+*/
+
+use std::collections::HashSet;
+
+use crate::curv::arithmetic::traits::Converter;
+use crate::curv::cryptographic_primitives::hashing::canonical::canonical_hash;
+use crate::gg_2018::health::check_dlog_statement;
+use crate::paillier::zkproofs::{CompositeDLogProof, DLogStatement, NICorrectKeyProof};
+use crate::paillier::EncryptionKey;
+
+const PAILLIER_KEY_LABEL: &str = "tss-wasm/proof-cache/paillier-key";
+const DLOG_STATEMENT_LABEL: &str = "tss-wasm/proof-cache/dlog-statement";
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum ProofCacheError {
+    InvalidPaillierKey,
+    InvalidDLogStatement,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProofCache {
+    verified: HashSet<String>,
+}
+
+impl ProofCache {
+    pub fn new() -> ProofCache {
+        ProofCache::default()
+    }
+
+    /// Verifies `proof` for `ek` unless a proof for `ek` verified before.
+    pub fn verify_correct_key(
+        &mut self,
+        ek: &EncryptionKey,
+        proof: &NICorrectKeyProof,
+    ) -> Result<(), ProofCacheError> {
+        let fingerprint = canonical_hash(PAILLIER_KEY_LABEL, ek).to_hex();
+        if self.verified.contains(&fingerprint) {
+            return Ok(());
+        }
+        proof
+            .verify(ek)
+            .map_err(|_| ProofCacheError::InvalidPaillierKey)?;
+        self.verified.insert(fingerprint);
+        Ok(())
+    }
+
+    /// Checks `statement` and verifies `proof` for it unless both passed
+    /// before.
+    pub fn verify_dlog_statement(
+        &mut self,
+        statement: &DLogStatement,
+        proof: &CompositeDLogProof,
+    ) -> Result<(), ProofCacheError> {
+        let fingerprint = canonical_hash(DLOG_STATEMENT_LABEL, statement).to_hex();
+        if self.verified.contains(&fingerprint) {
+            return Ok(());
+        }
+        // CompositeDLogProof::verify panics on a statement that is not made
+        // of units
+        check_dlog_statement(statement).map_err(|_| ProofCacheError::InvalidDLogStatement)?;
+        proof
+            .verify(statement)
+            .map_err(|_| ProofCacheError::InvalidDLogStatement)?;
+        self.verified.insert(fingerprint);
+        Ok(())
+    }
+
+    /// Number of keys and statements remembered.
+    pub fn len(&self) -> usize {
+        self.verified.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.verified.is_empty()
+    }
+
+    /// Forgets everything, e.g. when the committee changes.
+    pub fn clear(&mut self) {
+        self.verified.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paillier::{KeyGeneration, Paillier};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_proofs_verified_once() {
+        let (ek, dk) = Paillier::keypair().keys();
+        let proof = NICorrectKeyProof::proof(&dk);
+        let (other_ek, _) = Paillier::keypair().keys();
+
+        let mut cache = ProofCache::new();
+        assert_eq!(
+            cache.verify_correct_key(&other_ek, &proof),
+            Err(ProofCacheError::InvalidPaillierKey)
+        );
+        assert!(cache.is_empty());
+        assert_eq!(cache.verify_correct_key(&ek, &proof), Ok(()));
+        assert_eq!(cache.len(), 1);
+
+        let (statement, secret) = DLogStatement::generate();
+        let dlog_proof = CompositeDLogProof::prove(&statement, &secret);
+        assert_eq!(cache.verify_dlog_statement(&statement, &dlog_proof), Ok(()));
+        assert_eq!(cache.len(), 2);
+
+        // a remembered statement is not verified again
+        let mut restored: ProofCache =
+            serde_json::from_str(&serde_json::to_string(&cache).unwrap()).unwrap();
+        let garbage = CompositeDLogProof {
+            x: dlog_proof.y.clone(),
+            y: dlog_proof.x.clone(),
+        };
+        assert_eq!(restored.verify_dlog_statement(&statement, &garbage), Ok(()));
+        restored.clear();
+        assert_eq!(
+            restored.verify_dlog_statement(&statement, &garbage),
+            Err(ProofCacheError::InvalidDLogStatement)
+        );
+    }
+}
@@ -0,0 +1,298 @@
+/*
+    Per-quorum signing material and its cache.
+
+    Everything GG18 derives from the key before the nonces depends only on
+    the signer set: the Lagrange coefficients, this party's additive share
+    w_i and every signer's g^w_j, against which the MtA proofs are checked.
+    With a stable quorum these are identical for every signature, so they
+    are computed once per (key, signer set) and kept.
+
+    The cache holds w_i, which is as sensitive as x_i, and lives only in
+    memory.
+*/
+
+use std::collections::{HashMap, VecDeque};
+
+use sha2::{Digest, Sha256};
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::SignKeys;
+use crate::Error;
+
+/// Signing material for one signer set. Indices are 0-based party indices,
+/// as in `signers_vec`.
+#[derive(Clone, Debug)]
+pub struct QuorumKeys {
+    signers: Vec<usize>,
+    lagrange: Vec<FE>,
+    g_w_vec: Vec<GE>,
+    index: usize,
+    w_i: FE,
+}
+
+impl QuorumKeys {
+    pub fn new(
+        vss_scheme_vec: &[VerifiableSS],
+        x_i: &FE,
+        index: usize,
+        signers: &[usize],
+    ) -> Result<QuorumKeys, Error> {
+        let mut signers = signers.to_vec();
+        signers.sort_unstable();
+        signers.dedup();
+        let position = signers
+            .iter()
+            .position(|j| *j == index)
+            .ok_or(Error::InvalidKey)?;
+
+        let combined = VerifiableSS::combine(vss_scheme_vec)?;
+        let lagrange = signers
+            .iter()
+            .map(|j| combined.map_share_to_new_params(*j, &signers))
+            .collect::<Vec<FE>>();
+        let g_w_vec = signers
+            .iter()
+            .zip(lagrange.iter())
+            .map(|(j, l)| combined.get_point_commitment(j + 1) * l)
+            .collect::<Vec<GE>>();
+        let w_i = lagrange[position].clone() * x_i;
+        Ok(QuorumKeys {
+            signers,
+            lagrange,
+            g_w_vec,
+            index,
+            w_i,
+        })
+    }
+
+    /// The signer set, sorted.
+    pub fn signers(&self) -> &[usize] {
+        &self.signers
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    fn position(&self, index: usize) -> Option<usize> {
+        self.signers.binary_search(&index).ok()
+    }
+
+    pub fn lagrange(&self, index: usize) -> Option<&FE> {
+        self.position(index).map(|p| &self.lagrange[p])
+    }
+
+    /// `g^w_j` of signer `index`, the key its MtA proofs must be made with.
+    pub fn g_w(&self, index: usize) -> Option<&GE> {
+        self.position(index).map(|p| &self.g_w_vec[p])
+    }
+
+    pub fn w_i(&self) -> &FE {
+        &self.w_i
+    }
+}
+
+impl SignKeys {
+    /// Like `SignKeys::create`, with the key material taken from `quorum`.
+    pub fn create_from_quorum(quorum: &QuorumKeys) -> SignKeys {
+        let g: GE = ECPoint::generator();
+        let gamma_i: FE = ECScalar::new_random();
+        let g_gamma_i = &g * &gamma_i;
+        SignKeys {
+            w_i: quorum.w_i.clone(),
+            g_w_i: quorum.g_w(quorum.index).unwrap().clone(),
+            k_i: ECScalar::new_random(),
+            gamma_i,
+            g_gamma_i,
+        }
+    }
+
+    /// Takes the key material from `quorum`, keeping k_i and gamma_i. For a
+    /// signer set that shrank before anything depending on w_i was sent:
+    /// the commitment to g^gamma_i and the encryption of k_i stay valid.
+    pub fn requorum(&mut self, quorum: &QuorumKeys) {
+        self.w_i = quorum.w_i.clone();
+        self.g_w_i = quorum.g_w(quorum.index).unwrap().clone();
+    }
+}
+
+// a key is named by a hash of its share and group key, so that a refreshed
+// or tweaked key does not hit the entries of the one it replaced
+fn key_id(x_i: &FE, y_sum: &GE) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(x_i.to_big_int().to_bytes_be());
+    hasher.update(y_sum.to_bytes(true));
+    let mut id = [0u8; 32];
+    id.copy_from_slice(&hasher.finalize());
+    id
+}
+
+/// The `QuorumKeys` of recently used (key, signer set) pairs, dropping the
+/// oldest entry beyond `capacity`.
+pub struct QuorumCache {
+    capacity: usize,
+    entries: HashMap<([u8; 32], Vec<usize>), QuorumKeys>,
+    order: VecDeque<([u8; 32], Vec<usize>)>,
+}
+
+impl QuorumCache {
+    pub fn new(capacity: usize) -> QuorumCache {
+        QuorumCache {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(
+        &mut self,
+        vss_scheme_vec: &[VerifiableSS],
+        x_i: &FE,
+        y_sum: &GE,
+        index: usize,
+        signers: &[usize],
+    ) -> Result<&QuorumKeys, Error> {
+        let mut sorted = signers.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let id = (key_id(x_i, y_sum), sorted);
+        if !self.entries.contains_key(&id) {
+            let keys = QuorumKeys::new(vss_scheme_vec, x_i, index, signers)?;
+            if self.order.len() >= self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.entries.remove(&oldest);
+                }
+            }
+            self.entries.insert(id.clone(), keys);
+            self.order.push_back(id.clone());
+        }
+        Ok(&self.entries[&id])
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealings;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_quorum_keys() {
+        let (secrets, vss_scheme_vec, shares) = dealings(1, 4);
+        let x = (0..4)
+            .map(|i| shares.iter().fold(FE::zero(), |acc, s| acc + &s[i]))
+            .collect::<Vec<FE>>();
+        let secret = secrets.iter().fold(FE::zero(), |acc, s| acc + s);
+        let g: GE = ECPoint::generator();
+
+        let signers = vec![3, 1];
+        let quorums = signers
+            .iter()
+            .map(|i| QuorumKeys::new(&vss_scheme_vec, &x[*i], *i, &signers).unwrap())
+            .collect::<Vec<_>>();
+        // the additive shares sum to the key and match the public g^w_j
+        let w = quorums[0].w_i().clone() + quorums[1].w_i();
+        assert_eq!(w, secret);
+        assert_eq!(quorums[1].g_w(3).unwrap(), &(&g * quorums[0].w_i()));
+        assert!(quorums[0].g_w(2).is_none());
+
+        let mut cache = QuorumCache::new(1);
+        let y = &g * &secret;
+        let cached = cache
+            .get(&vss_scheme_vec, &x[1], &y, 1, &[1, 3])
+            .unwrap()
+            .clone();
+        assert_eq!(cached.w_i(), quorums[1].w_i());
+        cache.get(&vss_scheme_vec, &x[1], &y, 1, &[3, 1]).unwrap();
+        assert_eq!(cache.len(), 1);
+        cache.get(&vss_scheme_vec, &x[1], &y, 1, &[0, 1]).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // a dealing with a missing commitment is refused, not a panic
+        let mut malformed = vss_scheme_vec.clone();
+        malformed[2].commitments.pop();
+        assert_eq!(
+            cache.get(&malformed, &x[1], &y, 1, &[1, 2]).err(),
+            Some(Error::InvalidSS)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_quorum_keys_party_outside_signers() {
+        let (_, vss_scheme_vec, shares) = dealings(1, 3);
+        let x_0 = shares.iter().fold(FE::zero(), |acc, s| acc + &s[0]);
+        assert_eq!(
+            QuorumKeys::new(&vss_scheme_vec, &x_0, 0, &[1, 2]).err(),
+            Some(Error::InvalidKey)
+        );
+        // nor through the cache, which leaves no entry behind
+        let g: GE = ECPoint::generator();
+        let mut cache = QuorumCache::new(2);
+        assert_eq!(
+            cache
+                .get(&vss_scheme_vec, &x_0, &(&g * &x_0), 0, &[1, 2])
+                .err(),
+            Some(Error::InvalidKey)
+        );
+        assert!(cache.is_empty());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_requorum_after_dropout() {
+        let (secrets, vss_scheme_vec, shares) = dealings(1, 4);
+        let x = (0..4)
+            .map(|i| shares.iter().fold(FE::zero(), |acc, s| acc + &s[i]))
+            .collect::<Vec<FE>>();
+        let secret = secrets.iter().fold(FE::zero(), |acc, s| acc + s);
+        let g: GE = ECPoint::generator();
+
+        // three parties start, party 1 drops out after the nonce shares
+        let online = vec![0, 1, 3];
+        let mut sign_keys = online
+            .iter()
+            .map(|i| {
+                let quorum = QuorumKeys::new(&vss_scheme_vec, &x[*i], *i, &online).unwrap();
+                SignKeys::create_from_quorum(&quorum)
+            })
+            .collect::<Vec<SignKeys>>();
+        sign_keys.remove(1);
+        let survivors = vec![0, 3];
+        let nonces = sign_keys
+            .iter()
+            .map(|k| (k.k_i.clone(), k.gamma_i.clone()))
+            .collect::<Vec<_>>();
+        for (keys, i) in sign_keys.iter_mut().zip(survivors.iter()) {
+            let quorum = QuorumKeys::new(&vss_scheme_vec, &x[*i], *i, &survivors).unwrap();
+            keys.requorum(&quorum);
+            assert_eq!(keys.g_w_i, &g * &keys.w_i);
+        }
+
+        // the shares now sum to the key over the survivors alone
+        let w = sign_keys[0].w_i.clone() + &sign_keys[1].w_i;
+        assert_eq!(w, secret);
+        for (keys, (k_i, gamma_i)) in sign_keys.iter().zip(nonces.iter()) {
+            assert_eq!(&keys.k_i, k_i);
+            assert_eq!(&keys.gamma_i, gamma_i);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+/*
+    Paillier randomness for the MtA messages.
+
+    `MessageA::a_with_predefined_randomness` and
+    `MessageB::b_with_predefined_randomness` encrypt with whatever r the
+    caller passes. Two encryptions under one r are linked: their quotient is
+    g^(m1 - m2), and the range proofs built over them no longer hide the
+    plaintexts as they should. `RandomnessLedger` remembers every r spent in
+    a session and refuses a second use; the `_with_ledger` constructors
+    consult it.
+
+    Callers that need reproducible randomness, e.g. to replay a session
+    from its transcript, take it from `derive_randomness`, keyed by a
+    secret of their own: whoever learns r can decrypt.
+*/
+
+use std::collections::HashSet;
+
+use hmac::{Hmac, Mac, NewMac};
+use num_integer::Integer;
+use num_traits::One;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::arithmetic::traits::Converter;
+use crate::curv::elliptic::curves::secp256_k1::FE;
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::paillier::zkproofs::DLogStatement;
+use crate::paillier::EncryptionKey;
+use crate::Error;
+
+/// The Paillier randomness spent in one session, under any key, by hash.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RandomnessLedger {
+    spent: HashSet<[u8; 32]>,
+}
+
+impl RandomnessLedger {
+    pub fn new() -> RandomnessLedger {
+        RandomnessLedger::default()
+    }
+
+    pub fn is_spent(&self, randomness: &BigInt) -> bool {
+        self.spent.contains(&fingerprint(randomness))
+    }
+
+    /// Records `randomness` as spent, or fails with `ReusedRandomness` if
+    /// it already was.
+    pub fn spend(&mut self, randomness: &BigInt) -> Result<(), Error> {
+        if self.spent.insert(fingerprint(randomness)) {
+            Ok(())
+        } else {
+            Err(Error::ReusedRandomness)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.spent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spent.is_empty()
+    }
+}
+
+fn fingerprint(randomness: &BigInt) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"tss-wasm paillier randomness");
+    hasher.update(BigInt::to_bytes(randomness));
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+/// A unit of Z_n for `ek`, derived from `transcript` under `secret`. The
+/// same inputs give the same r; distinct transcripts, e.g. the session id
+/// with the message and counterparty the r is for, give unrelated ones.
+pub fn derive_randomness(secret: &[u8], transcript: &[u8], ek: &EncryptionKey) -> BigInt {
+    // 256 bits beyond n so that the reduction is close to uniform
+    let wide_bytes = (ek.n.bits() + 256).div_ceil(8);
+    let mut attempt: u32 = 0;
+    loop {
+        let mut wide = Vec::with_capacity(wide_bytes + 64);
+        let mut block: u32 = 0;
+        while wide.len() < wide_bytes {
+            let mut mac = Hmac::<Sha512>::new_from_slice(secret).unwrap();
+            mac.update(b"tss-wasm paillier randomness");
+            mac.update(&(transcript.len() as u64).to_be_bytes());
+            mac.update(transcript);
+            mac.update(&attempt.to_be_bytes());
+            mac.update(&block.to_be_bytes());
+            wide.extend_from_slice(&mac.finalize().into_bytes());
+            block += 1;
+        }
+        wide.truncate(wide_bytes);
+        let r = BigInt::from_bytes(&wide) % &ek.n;
+        if r.gcd(&ek.n).is_one() {
+            return r;
+        }
+        attempt += 1;
+    }
+}
+
+impl MessageA {
+    /// `a_with_predefined_randomness`, refusing `randomness` that `ledger`
+    /// has seen spent and recording it otherwise.
+    pub fn a_with_ledger(
+        a: &FE,
+        alice_ek: &EncryptionKey,
+        randomness: &BigInt,
+        dlog_statements: &[DLogStatement],
+        ledger: &mut RandomnessLedger,
+    ) -> Result<MessageA, Error> {
+        if ledger.is_spent(randomness) {
+            return Err(Error::ReusedRandomness);
+        }
+        if !alice_ek.is_valid_randomness(randomness) {
+            return Err(Error::InvalidRandomness);
+        }
+        let m_a = MessageA::a_with_predefined_randomness(a, alice_ek, randomness, dlog_statements);
+        ledger.spend(randomness)?;
+        Ok(m_a)
+    }
+}
+
+impl MessageB {
+    /// `b_with_predefined_randomness`, refusing `randomness` that `ledger`
+    /// has seen spent and recording it once the message is built.
+    #[allow(clippy::too_many_arguments)]
+    pub fn b_with_ledger(
+        b: &FE,
+        alice_ek: &EncryptionKey,
+        m_a: MessageA,
+        randomness: &BigInt,
+        beta_tag: &BigInt,
+        dlog_statements: &[DLogStatement],
+        context: &[u8],
+        bob: u16,
+        ledger: &mut RandomnessLedger,
+    ) -> Result<(MessageB, FE), Error> {
+        if ledger.is_spent(randomness) {
+            return Err(Error::ReusedRandomness);
+        }
+        let answer = MessageB::b_with_predefined_randomness(
+            b,
+            alice_ek,
+            m_a,
+            randomness,
+            beta_tag,
+            dlog_statements,
+            context,
+            bob,
+        )?;
+        ledger.spend(randomness)?;
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::traits::*;
+    use crate::paillier::{KeyGeneration, Paillier};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_reuse_rejected() {
+        let (ek, dk) = Paillier::keypair().keys();
+        let secret = BigInt::to_bytes(&dk.p);
+        let r = derive_randomness(&secret, b"session-1/a", &ek);
+        assert_eq!(r, derive_randomness(&secret, b"session-1/a", &ek));
+        assert_ne!(r, derive_randomness(&secret, b"session-1/b", &ek));
+        assert!(ek.is_valid_randomness(&r));
+
+        let mut ledger = RandomnessLedger::new();
+        let a: FE = ECScalar::new_random();
+        let m_a = MessageA::a_with_ledger(&a, &ek, &r, &[], &mut ledger).unwrap();
+        assert_eq!(
+            MessageA::a_with_ledger(&a, &ek, &r, &[], &mut ledger).err(),
+            Some(Error::ReusedRandomness)
+        );
+
+        // the same ledger covers the MessageB encryptions
+        let b: FE = ECScalar::new_random();
+        let beta_tag = derive_randomness(b"bob", b"session-1/beta", &ek)
+            % crate::gg_2018::mta::beta_tag_bound(&ek, false);
+        let answer = |m_a: &MessageA, r: &BigInt, ledger: &mut RandomnessLedger| {
+            MessageB::b_with_ledger(
+                &b,
+                &ek,
+                m_a.clone(),
+                r,
+                &beta_tag,
+                &[],
+                b"session-1",
+                2,
+                ledger,
+            )
+        };
+        assert_eq!(
+            answer(&m_a, &r, &mut ledger).err(),
+            Some(Error::ReusedRandomness)
+        );
+        let r_b = derive_randomness(b"bob", b"session-1/b", &ek);
+        answer(&m_a, &r_b, &mut ledger).unwrap();
+        assert_eq!(ledger.len(), 2);
+        assert_eq!(
+            answer(&m_a, &r_b, &mut ledger).err(),
+            Some(Error::ReusedRandomness)
+        );
+    }
+}
@@ -7,29 +7,26 @@
 //! Formal description can be found in Appendix A of https://eprint.iacr.org/2019/114.pdf
 //! There are some deviations from the original specification:
 //! 1) In Bob's proofs `gamma` is sampled from `[0;q^2 * N]` and `tau` from `[0;q^3 * N_tilde]`.
-//! 2) A non-interactive version is implemented, with challenge `e` computed via Fiat-Shamir.
+//! 2) A non-interactive version is implemented, with challenge `e` computed via Fiat-Shamir
+//!    from a merlin transcript.
 
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::arithmetic::traits::*;
-use crate::curv::elliptic::curves::secp256_k1::{
-    Secp256k1Point as Point, Secp256k1Scalar as Scalar,
-};
-use crate::curv::elliptic::curves::traits::{ECPoint, ECScalar};
-use sha2::Sha256;
+use crate::curv::elliptic::curves::secp256_k1::Secp256k1Scalar as Scalar;
+use crate::curv::elliptic::curves::traits::ECScalar;
+use merlin::Transcript;
 
-use crate::curv::cryptographic_primitives::hashing::ext::DigestExt;
-use crate::sha2::Digest;
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
 
 use crate::num_integer::Integer;
 use crate::num_traits::One;
-use num_traits::pow::Pow;
 
+use crate::impl_canonical_encode;
 use crate::paillier::zkproofs::DLogStatement;
-use crate::paillier::{EncryptionKey, Randomness};
+use crate::paillier::EncryptionKey;
 
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
-use zeroize::Zeroize;
 
 /// Represents the first round of the interactive version of the proof
 //#[derive(Zeroize)]
@@ -50,14 +47,15 @@ impl AliceZkpRound1 {
         dlog_statement: &DLogStatement,
         a: &BigInt,
         q: &BigInt,
+        params: &AliceProofParams,
     ) -> Self {
         let h1 = &dlog_statement.g;
         let h2 = &dlog_statement.ni;
         let N_tilde = &dlog_statement.N;
-        //        let q_pow_3 = BigInt::pow(q.clone(), 3);
-        let alpha = BigInt::sample_below(&q.pow(3u32));
+        let alpha_bound = params.alpha_bound();
+        let alpha = BigInt::sample_below(&alpha_bound);
         let beta = BigInt::from_paillier_key(alice_ek);
-        let gamma = BigInt::sample_below(&(q.pow(3u32) * N_tilde));
+        let gamma = BigInt::sample_below(&(alpha_bound * N_tilde));
         let ro = BigInt::sample_below(&(q * N_tilde));
         let z = (BigInt::mod_pow(h1, a, N_tilde) * BigInt::mod_pow(h2, &ro, N_tilde)) % N_tilde;
         let u = ((alpha.borrow() * &alice_ek.n + 1u32)
@@ -100,32 +98,191 @@ impl AliceZkpRound2 {
     }
 }
 
+fn alice_challenge(
+    alice_ek: &EncryptionKey,
+    cipher: &BigInt,
+    z: &BigInt,
+    u: &BigInt,
+    w: &BigInt,
+    params: &AliceProofParams,
+) -> BigInt {
+    let Gen = alice_ek.n.borrow() + 1u32;
+    let mut transcript = Transcript::new(b"tss-wasm alice range proof");
+    transcript.append_canonical(b"N", &alice_ek.n);
+    transcript.append_canonical(b"Gen", &Gen);
+    transcript.append_canonical(b"cipher", cipher);
+    transcript.append_canonical(b"z", z);
+    transcript.append_canonical(b"u", u);
+    transcript.append_canonical(b"w", w);
+    // the top challenge_bits of a 256 bit challenge
+    transcript.challenge_bigint(b"e") >> (MAX_CHALLENGE_BITS - params.challenge_bits) as usize
+}
+
+/// Bounds on `AliceProofParams`.
+pub const MIN_CHALLENGE_BITS: u32 = 80;
+pub const MAX_CHALLENGE_BITS: u32 = 256;
+pub const MIN_STATISTICAL_BITS: u32 = 80;
+pub const MAX_STATISTICAL_BITS: u32 = 512;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum ProofParamsError {
+    /// A challenge outside MIN_CHALLENGE_BITS..=MAX_CHALLENGE_BITS.
+    ChallengeBits,
+    /// A slack outside MIN_STATISTICAL_BITS..=MAX_STATISTICAL_BITS.
+    StatisticalBits,
+}
+
+/// Security parameters of `AliceProof`.
+///
+/// The challenge e has `challenge_bits` bits, so a cheating prover
+/// succeeds with probability 2^-challenge_bits. The response s1 = e a + alpha
+/// hides a < q when alpha is drawn from q 2^(challenge_bits +
+/// statistical_bits), up to a statistical distance of 2^-statistical_bits;
+/// the verifier rejects any s1 above that bound, and the Pedersen
+/// randomness gamma gets the same slack over N~. Larger parameters mean
+/// larger s1 and s2 and slower proofs.
+///
+/// Prover and verifier must use the same parameters: the challenge and the
+/// bound both depend on them. Peers on versions before the parameters were
+/// configurable prove and verify with `BITS_256`.
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub struct AliceProofParams {
+    challenge_bits: u32,
+    statistical_bits: u32,
+}
+
+impl AliceProofParams {
+    /// 128-bit soundness and statistical hiding, the default.
+    pub const BITS_128: AliceProofParams = AliceProofParams {
+        challenge_bits: 128,
+        statistical_bits: 128,
+    };
+
+    /// A 256-bit challenge and 256 bits of slack, an alpha bound of about
+    /// q^3 as in GG18.
+    pub const BITS_256: AliceProofParams = AliceProofParams {
+        challenge_bits: 256,
+        statistical_bits: 256,
+    };
+
+    pub fn new(challenge_bits: u32, statistical_bits: u32) -> Result<Self, ProofParamsError> {
+        if !(MIN_CHALLENGE_BITS..=MAX_CHALLENGE_BITS).contains(&challenge_bits) {
+            return Err(ProofParamsError::ChallengeBits);
+        }
+        if !(MIN_STATISTICAL_BITS..=MAX_STATISTICAL_BITS).contains(&statistical_bits) {
+            return Err(ProofParamsError::StatisticalBits);
+        }
+        Ok(AliceProofParams {
+            challenge_bits,
+            statistical_bits,
+        })
+    }
+
+    pub fn challenge_bits(&self) -> u32 {
+        self.challenge_bits
+    }
+
+    pub fn statistical_bits(&self) -> u32 {
+        self.statistical_bits
+    }
+
+    /// q 2^(challenge_bits + statistical_bits), the range of alpha and the
+    /// largest s1 accepted.
+    pub fn alpha_bound(&self) -> BigInt {
+        Scalar::group_order()
+            * (BigInt::one() << (self.challenge_bits + self.statistical_bits) as usize)
+    }
+}
+
+impl Default for AliceProofParams {
+    fn default() -> Self {
+        AliceProofParams::BITS_128
+    }
+}
+
 /// Alice's proof
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct AliceProof {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     z: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     e: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     s: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     s1: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     s2: BigInt,
 }
 
+impl_canonical_encode!(AliceProof { z, e, s, s1, s2 });
+
 impl AliceProof {
+    /// Assembles a proof from its fields as they are, for the fuzzing
+    /// generators.
+    #[cfg(feature = "fuzzing")]
+    pub(crate) fn from_parts(
+        z: BigInt,
+        e: BigInt,
+        s: BigInt,
+        s1: BigInt,
+        s2: BigInt,
+    ) -> AliceProof {
+        AliceProof { z, e, s, s1, s2 }
+    }
+
     /// verify Alice's proof using the proof and public keys
     pub fn verify(
         &self,
         cipher: &BigInt,
         alice_ek: &EncryptionKey,
         dlog_statement: &DLogStatement,
+    ) -> bool {
+        self.verify_with_params(
+            cipher,
+            alice_ek,
+            dlog_statement,
+            &AliceProofParams::default(),
+        )
+    }
+
+    /// `verify` under `params`, which must be those the proof was
+    /// generated with.
+    pub fn verify_with_params(
+        &self,
+        cipher: &BigInt,
+        alice_ek: &EncryptionKey,
+        dlog_statement: &DLogStatement,
+        params: &AliceProofParams,
     ) -> bool {
         let N = &alice_ek.n;
         let NN = &alice_ek.nn;
         let N_tilde = &dlog_statement.N;
         let h1 = &dlog_statement.g;
         let h2 = &dlog_statement.ni;
-        let Gen = alice_ek.n.borrow() + 1u32;
 
-        if self.s1 > Scalar::group_order().pow(3u32) {
+        if self.s1 > params.alpha_bound() {
             return false;
         }
 
@@ -141,14 +298,7 @@ impl AliceProof {
 
         let u = (gs1 * BigInt::mod_pow(&self.s, N, NN) * cipher_e_inv) % NN;
 
-        let e = Sha256::new()
-            .chain_bigint(N)
-            .chain_bigint(&Gen)
-            .chain_bigint(cipher)
-            .chain_bigint(&self.z)
-            .chain_bigint(&u)
-            .chain_bigint(&w)
-            .result_bigint();
+        let e = alice_challenge(alice_ek, cipher, &self.z, &u, &w, params);
         if e != self.e {
             return false;
         }
@@ -165,17 +315,29 @@ impl AliceProof {
         dlog_statement: &DLogStatement,
         r: &BigInt,
     ) -> Self {
-        let round1 = AliceZkpRound1::from(alice_ek, dlog_statement, a, Scalar::group_order());
-
-        let Gen = alice_ek.n.borrow() + 1u32;
-        let e = Sha256::new()
-            .chain_bigint(&alice_ek.n)
-            .chain_bigint(&Gen)
-            .chain_bigint(cipher)
-            .chain_bigint(&round1.z)
-            .chain_bigint(&round1.u)
-            .chain_bigint(&round1.w)
-            .result_bigint();
+        AliceProof::generate_with_params(
+            a,
+            cipher,
+            alice_ek,
+            dlog_statement,
+            r,
+            &AliceProofParams::default(),
+        )
+    }
+
+    /// `generate` under `params`.
+    pub fn generate_with_params(
+        a: &BigInt,
+        cipher: &BigInt,
+        alice_ek: &EncryptionKey,
+        dlog_statement: &DLogStatement,
+        r: &BigInt,
+        params: &AliceProofParams,
+    ) -> Self {
+        let round1 =
+            AliceZkpRound1::from(alice_ek, dlog_statement, a, Scalar::group_order(), params);
+
+        let e = alice_challenge(alice_ek, cipher, &round1.z, &round1.u, &round1.w, params);
 
         let round2 = AliceZkpRound2::from(alice_ek, &round1, &e, a, r);
 
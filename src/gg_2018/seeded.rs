@@ -0,0 +1,255 @@
+/*
+    Keygen from a master seed.
+
+    A party that runs keygen with a `KeygenSeed` draws its secret u_i, the
+    other coefficients of its sharing polynomial, its commitment blinding and
+    the prime candidates of its Paillier key from the seed, so after losing
+    its device it can derive all of them again instead of restoring a share
+    file. Proof nonces stay random; they are not part of the key.
+
+    The share x_i = sum_j f_j(i) also depends on the other parties'
+    polynomials. When every party keygens from a seed, party i is restored
+    by collecting `share_for(t, i)` from each party j and checking the sum
+    against the keygen commitments, as in phase 2.
+
+    The seed is derived from the master seed, a context naming the key, e.g.
+    a wallet id, and the party index, so one master seed can back several
+    keys without any two of them sharing secrets.
+*/
+
+use hmac::{Hmac, Mac, NewMac};
+use rand::{Error as RandError, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::FE;
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::{KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Keys, Parameters};
+use crate::gg_2018::proof_cache::ProofCache;
+use crate::paillier::{Keypair, Paillier};
+use crate::Error;
+
+const MASTER_SEED_MIN_LEN: usize = 16;
+const PAILLIER_MODULUS_BITS: usize = 2048;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum SeedError {
+    SeedTooShort,
+}
+
+/// The keygen randomness of one party for one key.
+pub struct KeygenSeed {
+    key: [u8; 32],
+}
+
+impl Drop for KeygenSeed {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+// HMAC-SHA256 in counter mode, one stream per label
+struct SeedRng {
+    mac: Hmac<Sha256>,
+    label: &'static [u8],
+    counter: u64,
+    block: [u8; 32],
+    used: usize,
+}
+
+impl SeedRng {
+    fn refill(&mut self) {
+        let mut mac = self.mac.clone();
+        mac.update(self.label);
+        mac.update(&self.counter.to_be_bytes());
+        self.block.copy_from_slice(&mac.finalize().into_bytes());
+        self.counter += 1;
+        self.used = 0;
+    }
+}
+
+impl RngCore for SeedRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_be_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_be_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            if self.used == self.block.len() {
+                self.refill();
+            }
+            *byte = self.block[self.used];
+            self.used += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), RandError> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl KeygenSeed {
+    pub fn new(
+        master_seed: &[u8],
+        context: &[u8],
+        party_index: usize,
+    ) -> Result<KeygenSeed, SeedError> {
+        if master_seed.len() < MASTER_SEED_MIN_LEN {
+            return Err(SeedError::SeedTooShort);
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(master_seed).unwrap();
+        mac.update(b"tss-wasm keygen seed");
+        mac.update(&(context.len() as u32).to_be_bytes());
+        mac.update(context);
+        mac.update(&(party_index as u64).to_be_bytes());
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&mac.finalize().into_bytes());
+        Ok(KeygenSeed { key })
+    }
+
+    fn rng(&self, label: &'static [u8]) -> SeedRng {
+        SeedRng {
+            mac: Hmac::<Sha256>::new_from_slice(&self.key).unwrap(),
+            label,
+            counter: 0,
+            block: [0u8; 32],
+            used: 32,
+        }
+    }
+
+    // 512 bits reduced mod q, so the bias is negligible
+    fn scalars(&self, label: &'static [u8], count: usize) -> Vec<FE> {
+        let mut rng = self.rng(label);
+        (0..count)
+            .map(|_| {
+                let mut bytes = [0u8; 64];
+                rng.fill_bytes(&mut bytes);
+                ECScalar::from(&BigInt::from_bytes_be(&bytes))
+            })
+            .collect()
+    }
+
+    pub fn u_i(&self) -> FE {
+        self.scalars(b"u_i", 1).remove(0)
+    }
+
+    /// The sharing polynomial of degree `t` with constant term `u_i`.
+    pub fn polynomial(&self, t: usize) -> Vec<FE> {
+        let mut poly = vec![self.u_i()];
+        poly.extend(self.scalars(b"polynomial", t));
+        poly
+    }
+
+    pub fn blind_factor(&self) -> BigInt {
+        let mut bytes = [0u8; 32];
+        self.rng(b"blind factor").fill_bytes(&mut bytes);
+        BigInt::from_bytes_be(&bytes)
+    }
+
+    pub fn paillier_keypair(&self) -> Keypair {
+        Paillier::keypair_with_rng(PAILLIER_MODULUS_BITS, &mut self.rng(b"paillier"))
+    }
+
+    /// `f_i(recipient)`, the share this party dealt to `recipient` in keygen.
+    pub fn share_for(&self, t: usize, recipient: usize) -> FE {
+        VerifiableSS::evaluate_polynomial(&self.polynomial(t), &[recipient]).remove(0)
+    }
+}
+
+impl Keys {
+    pub fn create_from_seed(seed: &KeygenSeed, index: usize) -> Keys {
+        let u = seed.u_i();
+        let y = &ECPoint::generator() * &u;
+        let (ek, dk) = seed.paillier_keypair().keys();
+
+        Keys {
+            u_i: u,
+            y_i: y,
+            dk,
+            ek,
+            party_index: index,
+        }
+    }
+
+    /// `phase1_broadcast_phase3_proof_of_correct_key` with the blinding
+    /// taken from `seed`.
+    pub fn phase1_broadcast_seeded(
+        &self,
+        seed: &KeygenSeed,
+    ) -> (KeyGenBroadcastMessage1, KeyGenDecommitMessage1) {
+        self.phase1_broadcast_with_blind_factor(seed.blind_factor())
+    }
+
+    /// `phase1_verify_com_phase3_verify_correct_key_phase2_distribute` with
+    /// the polynomial taken from `seed`. The keys must come from the same
+    /// seed, see `create_from_seed`.
+    pub fn phase2_distribute_seeded(
+        &self,
+        seed: &KeygenSeed,
+        params: &Parameters,
+        decom_vec: &[KeyGenDecommitMessage1],
+        bc1_vec: &[KeyGenBroadcastMessage1],
+    ) -> Result<(VerifiableSS, Vec<FE>, usize), Error> {
+        let poly = seed.polynomial(params.threshold());
+        if poly[0] != self.u_i {
+            return Err(Error::InvalidKey);
+        }
+        self.phase1_verify_com_phase2_distribute_polynomial(
+            &poly,
+            params,
+            decom_vec,
+            bc1_vec,
+            &mut ProofCache::new(),
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_seed_derivation() {
+        let master = [7u8; 32];
+        let seed = KeygenSeed::new(&master, b"wallet 1", 2).unwrap();
+        let again = KeygenSeed::new(&master, b"wallet 1", 2).unwrap();
+        assert_eq!(seed.polynomial(2), again.polynomial(2));
+        assert_eq!(seed.blind_factor(), again.blind_factor());
+        assert_ne!(
+            seed.u_i(),
+            KeygenSeed::new(&master, b"wallet 2", 2).unwrap().u_i()
+        );
+        assert_ne!(
+            seed.u_i(),
+            KeygenSeed::new(&master, b"wallet 1", 3).unwrap().u_i()
+        );
+        assert!(KeygenSeed::new(&master[..8], b"wallet 1", 2).is_err());
+
+        // the dealt shares are re-derivable and match the commitments
+        let poly = seed.polynomial(1);
+        let (vss, shares) = VerifiableSS::share_polynomial(3, &poly);
+        assert_eq!(seed.share_for(1, 3), shares[2]);
+        assert!(vss.validate_share(&seed.share_for(1, 3), 3).is_ok());
+
+        // a small modulus keeps the test fast; the primes are the same
+        let p1 = Paillier::keypair_with_rng(512, &mut seed.rng(b"paillier"));
+        let p2 = Paillier::keypair_with_rng(512, &mut again.rng(b"paillier"));
+        assert_eq!(p1, p2);
+    }
+}
@@ -0,0 +1,603 @@
+/*
+    sr25519 keys from a GG18 keygen, for Substrate chains.
+
+    The derivation of ed25519.rs run over ristretto255: each party deals
+    v_i = H(x_i, context, y) with a Feldman VSS over the ristretto group and
+    a proof of knowledge of v_i, and the sr25519 secret is the sum of the
+    v_i. Ristretto has the order l of the ed25519 subgroup, so the scalar
+    helpers are shared; the domain differs, so an sr25519 and an ed25519 key
+    derived under the same context are unrelated.
+
+    1. broadcast `Sr25519Commitments`, p2p `DerivationShare`,
+    2. `finish` checks both and returns the `Sr25519Key`.
+
+    `Sr25519Signer` signs in the three rounds of `Ed25519Signer`, with the
+    challenge schnorrkel derives from its merlin signing transcript, so the
+    signatures verify as sr25519 under the signing context given, e.g.
+    b"substrate".
+
+    Messages name their sender by 0-based party index, as in batch.rs.
+*/
+#![cfg(feature = "sr25519")]
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use merlin::Transcript;
+use zeroize::Zeroize;
+
+use crate::gg_2018::ed25519::{
+    by_party, derivation_domain, evaluate, lagrange_at_zero, random_scalar, DerivationShare,
+    Ed25519Error, NonceCommitment, PartialSignature,
+};
+use crate::gg_2018::party_i::LocalKey;
+
+const SR25519_DERIVATION: &[u8] = b"tss-wasm sr25519 derivation";
+
+/// The failures are those of the ed25519 derivation and signer.
+pub type Sr25519Error = Ed25519Error;
+
+/// Proof of knowledge of the discrete log of a ristretto commitment.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RistrettoSchnorrProof {
+    pub big_r: RistrettoPoint,
+    pub z: Scalar,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sr25519Commitments {
+    pub from: usize,
+    /// a_k B for the coefficients a_k of the dealt polynomial, a_0 = v_i.
+    pub commitments: Vec<RistrettoPoint>,
+    pub proof: RistrettoSchnorrProof,
+}
+
+/// A party's share of an sr25519 key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sr25519Key {
+    pub index: usize,
+    pub threshold: usize,
+    pub share: Scalar,
+    pub public_key: RistrettoPoint,
+    /// x_j B for every party j, to check partial signatures with.
+    pub public_shares: Vec<RistrettoPoint>,
+}
+
+impl Sr25519Key {
+    /// The public key as the 32 bytes of a Substrate account.
+    pub fn public_key_bytes(&self) -> [u8; 32] {
+        self.public_key.compress().to_bytes()
+    }
+}
+
+impl Drop for Sr25519Key {
+    fn drop(&mut self) {
+        self.share.zeroize();
+    }
+}
+
+/// One party's state between the two steps of a derivation.
+pub struct Sr25519Derivation {
+    index: usize,
+    threshold: usize,
+    share_count: usize,
+    domain: Vec<u8>,
+}
+
+impl LocalKey {
+    /// Starts deriving the sr25519 key named `context`. Returns the
+    /// broadcast and one share for every party, its own included.
+    pub fn derive_sr25519(
+        &self,
+        context: &str,
+    ) -> (Sr25519Derivation, Sr25519Commitments, Vec<DerivationShare>) {
+        let index = usize::from(self.party_id) - 1;
+        let threshold = self.threshold();
+        let share_count = self.share_count();
+        let domain = derivation_domain(self, SR25519_DERIVATION, context);
+
+        let mut coefficients = (0..=threshold)
+            .map(|k| self.derivation_scalar(&domain, k))
+            .collect::<Vec<Scalar>>();
+        let commitments = coefficients
+            .iter()
+            .map(|a| a * RISTRETTO_BASEPOINT_POINT)
+            .collect::<Vec<RistrettoPoint>>();
+        let proof = RistrettoSchnorrProof::prove(&coefficients[0], &commitments[0], &domain, index);
+        let shares = (0..share_count)
+            .map(|to| DerivationShare {
+                from: index,
+                to,
+                share: evaluate(&coefficients, to),
+            })
+            .collect();
+        coefficients.zeroize();
+
+        let derivation = Sr25519Derivation {
+            index,
+            threshold,
+            share_count,
+            domain,
+        };
+        let broadcast = Sr25519Commitments {
+            from: index,
+            commitments,
+            proof,
+        };
+        (derivation, broadcast, shares)
+    }
+}
+
+impl Sr25519Derivation {
+    /// Checks every party's broadcast and the share it sent here, own
+    /// included, and returns the key.
+    pub fn finish(
+        self,
+        commitments: &[Sr25519Commitments],
+        shares: &[DerivationShare],
+    ) -> Result<Sr25519Key, Sr25519Error> {
+        let commitments = by_party(commitments, self.share_count, |m| m.from)?;
+        let shares = by_party(
+            shares.iter().filter(|s| s.to == self.index),
+            self.share_count,
+            |m| m.from,
+        )?;
+
+        for ((j, dealt), share) in commitments.iter().enumerate().zip(&shares) {
+            // ristretto has no small torsion to rule out
+            let well_formed = dealt.commitments.len() == self.threshold + 1
+                && dealt.commitments[0] != RistrettoPoint::identity();
+            if !well_formed {
+                return Err(Ed25519Error::InvalidCommitment);
+            }
+            dealt.proof.verify(&dealt.commitments[0], &self.domain, j)?;
+            if share.share * RISTRETTO_BASEPOINT_POINT
+                != evaluate_commitments(&dealt.commitments, self.index)
+            {
+                return Err(Ed25519Error::InvalidShare);
+            }
+        }
+
+        let combined = (0..=self.threshold)
+            .map(|k| commitments.iter().map(|c| c.commitments[k]).sum())
+            .collect::<Vec<RistrettoPoint>>();
+        Ok(Sr25519Key {
+            index: self.index,
+            threshold: self.threshold,
+            share: shares.iter().map(|s| s.share).sum(),
+            public_key: combined[0],
+            public_shares: (0..self.share_count)
+                .map(|j| evaluate_commitments(&combined, j))
+                .collect(),
+        })
+    }
+}
+
+impl RistrettoSchnorrProof {
+    fn prove(
+        secret: &Scalar,
+        public: &RistrettoPoint,
+        domain: &[u8],
+        prover: usize,
+    ) -> RistrettoSchnorrProof {
+        let mut r = random_scalar();
+        let big_r = r * RISTRETTO_BASEPOINT_POINT;
+        let c = schnorr_challenge(public, &big_r, domain, prover);
+        let z = r + c * secret;
+        r.zeroize();
+        RistrettoSchnorrProof { big_r, z }
+    }
+
+    fn verify(
+        &self,
+        public: &RistrettoPoint,
+        domain: &[u8],
+        prover: usize,
+    ) -> Result<(), Sr25519Error> {
+        let c = schnorr_challenge(public, &self.big_r, domain, prover);
+        if self.z * RISTRETTO_BASEPOINT_POINT == self.big_r + c * public {
+            Ok(())
+        } else {
+            Err(Ed25519Error::InvalidProof)
+        }
+    }
+}
+
+fn schnorr_challenge(
+    public: &RistrettoPoint,
+    big_r: &RistrettoPoint,
+    domain: &[u8],
+    prover: usize,
+) -> Scalar {
+    let mut transcript = Transcript::new(b"tss-wasm sr25519 pok");
+    transcript.append_message(b"domain", domain);
+    transcript.append_u64(b"prover", prover as u64);
+    transcript.append_message(b"public", public.compress().as_bytes());
+    transcript.append_message(b"R", big_r.compress().as_bytes());
+    let mut wide = [0u8; 64];
+    transcript.challenge_bytes(b"challenge", &mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sr25519NonceReveal {
+    pub from: usize,
+    pub big_r: RistrettoPoint,
+}
+
+/// One signer's state through the three rounds of an sr25519 signature:
+///
+/// 1. broadcast `NonceCommitment`, from `new`,
+/// 2. broadcast `Sr25519NonceReveal`, from `round2`,
+/// 3. broadcast `PartialSignature`, from `round3`,
+///
+/// and `finish` puts the signature together. Each step takes the messages
+/// of every signer, its own included.
+pub struct Sr25519Signer {
+    key: Sr25519Key,
+    session_id: String,
+    signers: Vec<usize>,
+    signing_context: Vec<u8>,
+    message: Vec<u8>,
+    round: usize,
+    nonce: Scalar,
+    big_r_i: RistrettoPoint,
+    // per signer, in `signers` order
+    coms: Vec<[u8; 32]>,
+    big_r_vec: Vec<RistrettoPoint>,
+    big_r: RistrettoPoint,
+    challenge: Scalar,
+}
+
+impl Drop for Sr25519Signer {
+    fn drop(&mut self) {
+        self.nonce.zeroize();
+    }
+}
+
+impl Sr25519Signer {
+    /// Starts signing `message` under `signing_context` among the 0-based
+    /// `signers`, at least t + 1 of them. `session_id` must be unique to
+    /// the signature.
+    pub fn new(
+        key: &Sr25519Key,
+        session_id: &str,
+        signers: &[usize],
+        signing_context: &[u8],
+        message: &[u8],
+    ) -> Result<(Sr25519Signer, NonceCommitment), Sr25519Error> {
+        let mut distinct = signers.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let valid = distinct.len() == signers.len()
+            && signers.len() > key.threshold
+            && signers.iter().all(|j| *j < key.public_shares.len())
+            && signers.contains(&key.index);
+        if !valid {
+            return Err(Ed25519Error::WrongSigners);
+        }
+
+        let nonce = random_scalar();
+        let big_r_i = nonce * RISTRETTO_BASEPOINT_POINT;
+        let com = nonce_commitment(session_id, key.index, &big_r_i);
+        let signer = Sr25519Signer {
+            key: key.clone(),
+            session_id: session_id.to_string(),
+            signers: signers.to_vec(),
+            signing_context: signing_context.to_vec(),
+            message: message.to_vec(),
+            round: 1,
+            nonce,
+            big_r_i,
+            coms: Vec::new(),
+            big_r_vec: Vec::new(),
+            big_r: RistrettoPoint::identity(),
+            challenge: Scalar::zero(),
+        };
+        Ok((
+            signer,
+            NonceCommitment {
+                from: key.index,
+                com,
+            },
+        ))
+    }
+
+    pub fn round2(&mut self, coms: &[NonceCommitment]) -> Result<Sr25519NonceReveal, Sr25519Error> {
+        self.expect_round(1)?;
+        self.coms = self
+            .by_signer(coms, |m| m.from)?
+            .iter()
+            .map(|m| m.com)
+            .collect();
+        self.round = 2;
+        Ok(Sr25519NonceReveal {
+            from: self.key.index,
+            big_r: self.big_r_i,
+        })
+    }
+
+    pub fn round3(
+        &mut self,
+        reveals: &[Sr25519NonceReveal],
+    ) -> Result<PartialSignature, Sr25519Error> {
+        self.expect_round(2)?;
+        let reveals = self.by_signer(reveals, |m| m.from)?;
+        for (reveal, com) in reveals.iter().zip(&self.coms) {
+            if nonce_commitment(&self.session_id, reveal.from, &reveal.big_r) != *com {
+                return Err(Ed25519Error::InvalidCommitment);
+            }
+        }
+        self.big_r_vec = reveals.iter().map(|m| m.big_r).collect();
+        self.big_r = self.big_r_vec.iter().sum();
+        self.challenge = challenge(
+            &self.signing_context,
+            &self.message,
+            &self.key.public_key.compress(),
+            &self.big_r.compress(),
+        );
+        let lambda = lagrange_at_zero(&self.signers, self.key.index);
+        self.round = 3;
+        Ok(PartialSignature {
+            from: self.key.index,
+            s: self.nonce + self.challenge * lambda * self.key.share,
+        })
+    }
+
+    /// Checks every signer's share of s and returns the signature R || s,
+    /// with the schnorrkel marker bit set.
+    pub fn finish(&self, partials: &[PartialSignature]) -> Result<[u8; 64], Sr25519Error> {
+        self.expect_round(3)?;
+        let partials = self.by_signer(partials, |m| m.from)?;
+        for ((partial, big_r_j), j) in partials.iter().zip(&self.big_r_vec).zip(&self.signers) {
+            let lambda = lagrange_at_zero(&self.signers, *j);
+            let expected = big_r_j + self.challenge * lambda * self.key.public_shares[*j];
+            if partial.s * RISTRETTO_BASEPOINT_POINT != expected {
+                return Err(Ed25519Error::InvalidPartialSignature);
+            }
+        }
+        let s: Scalar = partials.iter().map(|m| m.s).sum();
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(self.big_r.compress().as_bytes());
+        signature[32..].copy_from_slice(s.as_bytes());
+        signature[63] |= 0x80;
+        verify(
+            &self.key.public_key,
+            &self.signing_context,
+            &self.message,
+            &signature,
+        )?;
+        Ok(signature)
+    }
+
+    fn expect_round(&self, round: usize) -> Result<(), Sr25519Error> {
+        if self.round == round {
+            Ok(())
+        } else {
+            Err(Ed25519Error::WrongRound)
+        }
+    }
+
+    fn by_signer<'a, T>(
+        &self,
+        messages: &'a [T],
+        from: impl Fn(&T) -> usize,
+    ) -> Result<Vec<&'a T>, Sr25519Error> {
+        by_party(messages, self.signers.len(), |m| {
+            self.signers
+                .iter()
+                .position(|j| *j == from(m))
+                .unwrap_or(usize::MAX)
+        })
+    }
+}
+
+/// Verifies a 64 byte sr25519 signature as schnorrkel does, rejecting
+/// signatures without the marker bit.
+pub fn verify(
+    public_key: &RistrettoPoint,
+    signing_context: &[u8],
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<(), Sr25519Error> {
+    if signature[63] & 0x80 == 0 {
+        return Err(Ed25519Error::InvalidSignature);
+    }
+    let mut r_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    let mut s_bytes = [0u8; 32];
+    s_bytes.copy_from_slice(&signature[32..]);
+    s_bytes[31] &= 0x7f;
+    let compressed_r = CompressedRistretto(r_bytes);
+    let big_r = compressed_r
+        .decompress()
+        .ok_or(Ed25519Error::InvalidSignature)?;
+    let s = Scalar::from_canonical_bytes(s_bytes).ok_or(Ed25519Error::InvalidSignature)?;
+    let k = challenge(
+        signing_context,
+        message,
+        &public_key.compress(),
+        &compressed_r,
+    );
+    if s * RISTRETTO_BASEPOINT_POINT == big_r + k * public_key {
+        Ok(())
+    } else {
+        Err(Ed25519Error::InvalidSignature)
+    }
+}
+
+// schnorrkel's signing transcript: the context and message as
+// `signing_context(ctx).bytes(msg)` adds them, then "Schnorr-sig", A and R
+fn challenge(
+    signing_context: &[u8],
+    message: &[u8],
+    public_key: &CompressedRistretto,
+    big_r: &CompressedRistretto,
+) -> Scalar {
+    let mut transcript = Transcript::new(b"SigningContext");
+    transcript.append_message(b"", signing_context);
+    transcript.append_message(b"sign-bytes", message);
+    transcript.append_message(b"proto-name", b"Schnorr-sig");
+    transcript.append_message(b"sign:pk", public_key.as_bytes());
+    transcript.append_message(b"sign:R", big_r.as_bytes());
+    let mut wide = [0u8; 64];
+    transcript.challenge_bytes(b"sign:c", &mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn nonce_commitment(session_id: &str, from: usize, big_r: &RistrettoPoint) -> [u8; 32] {
+    let mut transcript = Transcript::new(b"tss-wasm sr25519 nonce commitment");
+    transcript.append_message(b"session id", session_id.as_bytes());
+    transcript.append_u64(b"signer", from as u64);
+    transcript.append_message(b"R", big_r.compress().as_bytes());
+    let mut com = [0u8; 32];
+    transcript.challenge_bytes(b"commitment", &mut com);
+    com
+}
+
+fn evaluate_commitments(commitments: &[RistrettoPoint], index: usize) -> RistrettoPoint {
+    let x = Scalar::from((index + 1) as u64);
+    commitments
+        .iter()
+        .rev()
+        .fold(RistrettoPoint::identity(), |acc, c| acc * x + c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn derive(context: &str) -> Vec<Sr25519Key> {
+        let (_, keys) = dealer_keys(1, 3);
+        let (derivations, (broadcasts, shares)): (Vec<_>, (Vec<_>, Vec<_>)) = keys
+            .iter()
+            .map(|key| {
+                let (derivation, broadcast, shares) = key.derive_sr25519(context);
+                (derivation, (broadcast, shares))
+            })
+            .unzip();
+        let shares = shares.into_iter().flatten().collect::<Vec<_>>();
+        derivations
+            .into_iter()
+            .map(|d| d.finish(&broadcasts, &shares).unwrap())
+            .collect()
+    }
+
+    fn sign(
+        sr_keys: &[Sr25519Key],
+        signers: &[usize],
+        signing_context: &[u8],
+        message: &[u8],
+    ) -> [u8; 64] {
+        let (mut parties, coms): (Vec<_>, Vec<_>) = signers
+            .iter()
+            .map(|j| {
+                Sr25519Signer::new(
+                    &sr_keys[*j],
+                    "sr-session",
+                    signers,
+                    signing_context,
+                    message,
+                )
+                .unwrap()
+            })
+            .unzip();
+        let reveals = parties
+            .iter_mut()
+            .map(|p| p.round2(&coms).unwrap())
+            .collect::<Vec<_>>();
+        let partials = parties
+            .iter_mut()
+            .map(|p| p.round3(&reveals).unwrap())
+            .collect::<Vec<_>>();
+        parties[0].finish(&partials).unwrap()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_derive_and_sign() {
+        let sr_keys = derive("polkadot");
+        assert!(sr_keys
+            .iter()
+            .all(|k| k.public_key == sr_keys[0].public_key));
+
+        let message = b"transfer";
+        let mut signature = sign(&sr_keys, &[1, 2], b"substrate", message);
+        let public_key = sr_keys[0].public_key;
+        assert_eq!(
+            verify(&public_key, b"substrate", message, &signature),
+            Ok(())
+        );
+
+        // without the marker bit it reads as an old-style signature
+        signature[63] &= 0x7f;
+        assert_eq!(
+            verify(&public_key, b"substrate", message, &signature),
+            Err(Ed25519Error::InvalidSignature)
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_schnorrkel_verifies_threshold_signature() {
+        let sr_keys = derive("kusama");
+        let signature = sign(&sr_keys, &[0, 2], b"substrate", b"known answer");
+
+        let public_key = schnorrkel::PublicKey::from_bytes(&sr_keys[0].public_key_bytes()).unwrap();
+        let signature = schnorrkel::Signature::from_bytes(&signature).unwrap();
+        assert!(public_key
+            .verify_simple(b"substrate", b"known answer", &signature)
+            .is_ok());
+        assert!(public_key
+            .verify_simple(b"polkadot", b"known answer", &signature)
+            .is_err());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_verify_schnorrkel_signature() {
+        let keypair = schnorrkel::MiniSecretKey::from_bytes(&[7u8; 32])
+            .unwrap()
+            .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+        let signature = keypair
+            .sign_simple(b"substrate", b"known answer")
+            .to_bytes();
+        let public_key = CompressedRistretto(keypair.public.to_bytes())
+            .decompress()
+            .unwrap();
+
+        assert_eq!(
+            verify(&public_key, b"substrate", b"known answer", &signature),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&public_key, b"polkadot", b"known answer", &signature),
+            Err(Ed25519Error::InvalidSignature)
+        );
+        assert_eq!(
+            verify(&public_key, b"substrate", b"other answer", &signature),
+            Err(Ed25519Error::InvalidSignature)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_wrong_context_is_rejected() {
+        let sr_keys = derive("polkadot");
+        let signature = sign(&sr_keys, &[0, 1], b"substrate", b"transfer");
+        assert_eq!(
+            verify(
+                &sr_keys[0].public_key,
+                b"other context",
+                b"transfer",
+                &signature
+            ),
+            Err(Ed25519Error::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+/*
+    MtA shares folded in one counterparty at a time.
+
+    `phase2_delta_i` and `phase2_sigma_i` take the alphas and betas of all
+    counterparties at once, so a signer ends up holding every MessageA and
+    MessageB of the round together with their shares. In a large committee
+    those Paillier ciphertexts and range proofs outgrow a browser tab.
+    `MtaAccumulator` keeps only the running delta_i and sigma_i: each
+    MessageA is answered, and each MessageB verified, as it comes in and can
+    be dropped straight after.
+
+    The client API pairs this with `poll_for_p2p_each`, which hands over
+    each counterparty's message as it arrives instead of the whole round.
+*/
+
+use crate::curv::elliptic::curves::secp256_k1::FE;
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::gg_2018::party_i::SignKeys;
+use crate::gg_2018::range_proofs::AliceProofParams;
+use crate::paillier::{DecryptionKey, EncryptionKey};
+use crate::Error;
+
+/// A signer's delta_i and sigma_i so far, with how many counterparties
+/// have been answered and received from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MtaAccumulator {
+    delta_i: FE,
+    sigma_i: FE,
+    answered: usize,
+    received: usize,
+}
+
+impl MtaAccumulator {
+    /// Starts from the local terms k_i gamma_i and k_i w_i.
+    pub fn new(sign_keys: &SignKeys) -> MtaAccumulator {
+        MtaAccumulator {
+            delta_i: sign_keys.k_i.mul(&sign_keys.gamma_i.get_element()),
+            sigma_i: sign_keys.k_i.mul(&sign_keys.w_i.get_element()),
+            answered: 0,
+            received: 0,
+        }
+    }
+
+    /// Answers one counterparty's MessageA for gamma_i and for w_i, as
+    /// party `bob` of the session `context`, and folds in the betas.
+    /// `alice_ek` is that counterparty's Paillier key, and `params` the
+    /// range proof parameters the parties agreed on.
+    pub fn respond(
+        &mut self,
+        sign_keys: &SignKeys,
+        alice_ek: &EncryptionKey,
+        m_a: MessageA,
+        params: &AliceProofParams,
+        context: &[u8],
+        bob: u16,
+    ) -> Result<(MessageB, MessageB), Error> {
+        let answer =
+            |b, m_a| MessageB::b_with_proof_params(b, alice_ek, m_a, &[], params, context, bob);
+        let (m_b_gamma, beta, _, _) = answer(&sign_keys.gamma_i, m_a.clone())?;
+        let (m_b_w, ni, _, _) = answer(&sign_keys.w_i, m_a)?;
+        self.delta_i = self.delta_i.add(&beta.get_element());
+        self.sigma_i = self.sigma_i.add(&ni.get_element());
+        self.answered += 1;
+        Ok((m_b_gamma, m_b_w))
+    }
+
+    /// Verifies the answers of party `bob` of the session `context` to our
+    /// MessageA and folds in the alphas. The caller checks
+    /// `m_b_w.b_proof.pk` against the counterparty's g^w_j, and keeps
+    /// `m_b_gamma.b_proof` for phase 4.
+    pub fn receive(
+        &mut self,
+        sign_keys: &SignKeys,
+        dk: &DecryptionKey,
+        m_b_gamma: &MessageB,
+        m_b_w: &MessageB,
+        context: &[u8],
+        bob: u16,
+    ) -> Result<(), Error> {
+        let (alpha, _) =
+            m_b_gamma.verify_proofs_get_alpha(dk, &sign_keys.k_i, false, context, bob)?;
+        let (miu, _) = m_b_w.verify_proofs_get_alpha(dk, &sign_keys.k_i, false, context, bob)?;
+        self.delta_i = self.delta_i.add(&alpha.get_element());
+        self.sigma_i = self.sigma_i.add(&miu.get_element());
+        self.received += 1;
+        Ok(())
+    }
+
+    pub fn answered(&self) -> usize {
+        self.answered
+    }
+
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// delta_i, complete once every counterparty is answered and received.
+    pub fn delta_i(&self) -> FE {
+        self.delta_i.clone()
+    }
+
+    /// sigma_i, complete once every counterparty is answered and received.
+    pub fn sigma_i(&self) -> FE {
+        self.sigma_i.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::secp256_k1::GE;
+    use crate::paillier::{KeyGeneration, Paillier};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn sign_keys() -> SignKeys {
+        let w_i: FE = ECScalar::new_random();
+        let k_i: FE = ECScalar::new_random();
+        let gamma_i: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        SignKeys {
+            g_w_i: &g * &w_i,
+            g_gamma_i: &g * &gamma_i,
+            w_i,
+            k_i,
+            gamma_i,
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_accumulated_shares() {
+        let keys = [sign_keys(), sign_keys()];
+        let paillier = [Paillier::keypair().keys(), Paillier::keypair().keys()];
+        let mut acc = [MtaAccumulator::new(&keys[0]), MtaAccumulator::new(&keys[1])];
+        let params = AliceProofParams::default();
+
+        // each party's MessageA answered by the other, one at a time
+        for (a, b) in [(0, 1), (1, 0)] {
+            let (m_a, _) = MessageA::a(&keys[a].k_i, &paillier[a].0, &[]);
+            let bob = b as u16 + 1;
+            let (m_b_gamma, m_b_w) = acc[b]
+                .respond(&keys[b], &paillier[a].0, m_a, &params, b"session", bob)
+                .unwrap();
+            acc[a]
+                .receive(
+                    &keys[a],
+                    &paillier[a].1,
+                    &m_b_gamma,
+                    &m_b_w,
+                    b"session",
+                    bob,
+                )
+                .unwrap();
+        }
+        assert!(acc.iter().all(|x| x.answered() == 1 && x.received() == 1));
+
+        let k = keys[0].k_i.clone() + &keys[1].k_i;
+        let delta = acc[0].delta_i() + acc[1].delta_i();
+        let sigma = acc[0].sigma_i() + acc[1].sigma_i();
+        assert_eq!(
+            delta,
+            k.clone() * (keys[0].gamma_i.clone() + &keys[1].gamma_i)
+        );
+        assert_eq!(sigma, k * (keys[0].w_i.clone() + &keys[1].w_i));
+
+        // a MessageB decrypted with the wrong key is rejected
+        let (m_a, _) = MessageA::a(&keys[0].k_i, &paillier[0].0, &[]);
+        let (m_b_gamma, m_b_w) = acc[1]
+            .respond(&keys[1], &paillier[0].0, m_a, &params, b"session", 2)
+            .unwrap();
+        assert!(acc[0]
+            .receive(&keys[0], &paillier[1].1, &m_b_gamma, &m_b_w, b"session", 2)
+            .is_err());
+        // and so is one taken for another party's, or another session's
+        assert!(acc[0]
+            .receive(&keys[0], &paillier[0].1, &m_b_gamma, &m_b_w, b"session", 3)
+            .is_err());
+        assert!(acc[0]
+            .receive(&keys[0], &paillier[0].1, &m_b_gamma, &m_b_w, b"other", 2)
+            .is_err());
+    }
+}
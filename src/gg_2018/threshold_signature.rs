@@ -0,0 +1,184 @@
+/*
+    The result of a signing ceremony, with its provenance.
+
+    `LocalSignature::output_signature` gives r and s only. A wallet or an
+    audit log also wants the recovery id, which parties signed, and for a
+    presigned signature which presignature it consumed. `ThresholdSignature`
+    carries those next to a low-s signature, and encodes it for the
+    consumers that want DER (X.509, Bitcoin scripts), the 64 byte compact
+    form (libsecp256k1, WebCrypto after conversion) or Ethereum's r || s || v.
+*/
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::ecdsa;
+use crate::gg_2018::party_i::{Signature, SigningDigest};
+use crate::Error::{self, InvalidSig};
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ThresholdSignature {
+    pub r: FE,
+    /// In the lower half of the group order.
+    pub s: FE,
+    /// Bit 0 is the parity of R.y, bit 1 set when R.x overflowed q.
+    pub recid: u8,
+    pub public_key: GE,
+    pub digest: [u8; 32],
+    /// The party ids of the signers, counted from 1, ascending.
+    pub signers: Vec<u16>,
+    /// The `Presignature::id` consumed, when signed from a presignature.
+    pub presignature_id: Option<String>,
+}
+
+impl ThresholdSignature {
+    /// Checks `sig` over `digest` under `public_key`, normalizes s and
+    /// finds the recovery id.
+    pub fn new(
+        sig: &Signature,
+        public_key: &GE,
+        digest: &SigningDigest,
+        signers: &[u16],
+        presignature_id: Option<String>,
+    ) -> Result<ThresholdSignature, Error> {
+        let (s, _) = ecdsa::normalize_s(&sig.s, 0);
+        let recid =
+            ecdsa::recovery_id(public_key, digest.as_bytes(), &sig.r, &s).ok_or(InvalidSig)?;
+        let mut signers = signers.to_vec();
+        signers.sort_unstable();
+        signers.dedup();
+        Ok(ThresholdSignature {
+            r: sig.r.clone(),
+            s,
+            recid,
+            public_key: public_key.clone(),
+            digest: *digest.as_bytes(),
+            signers,
+            presignature_id,
+        })
+    }
+
+    pub fn signature(&self) -> Signature {
+        Signature {
+            r: self.r.clone(),
+            s: self.s.clone(),
+        }
+    }
+
+    /// r || s, 32 bytes each.
+    pub fn to_compact(&self) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        out[..32].copy_from_slice(&self.r.to_bytes_be());
+        out[32..].copy_from_slice(&self.s.to_bytes_be());
+        out
+    }
+
+    /// r || s || recid, as libsecp256k1's recoverable signatures.
+    pub fn to_recoverable(&self) -> [u8; 65] {
+        let mut out = [0u8; 65];
+        out[..64].copy_from_slice(&self.to_compact());
+        out[64] = self.recid;
+        out
+    }
+
+    /// The ASN.1 DER `SEQUENCE { INTEGER r, INTEGER s }`.
+    pub fn to_der(&self) -> Vec<u8> {
+        let r = der_integer(&self.r.to_bytes_be());
+        let s = der_integer(&self.s.to_bytes_be());
+        // at most 2 * 35 bytes, so the short length form always fits
+        let mut out = vec![0x30, (r.len() + s.len()) as u8];
+        out.extend_from_slice(&r);
+        out.extend_from_slice(&s);
+        out
+    }
+
+    /// Ethereum's v: 27 + parity before EIP-155, 35 + 2 chain_id + parity
+    /// with a chain id. Fails for the R.x overflow ids, which Ethereum
+    /// cannot express.
+    pub fn ethereum_v(&self, chain_id: Option<u64>) -> Result<u64, Error> {
+        if self.recid > 1 {
+            return Err(InvalidSig);
+        }
+        let parity = u64::from(self.recid);
+        Ok(match chain_id {
+            Some(chain_id) => 35 + 2 * chain_id + parity,
+            None => 27 + parity,
+        })
+    }
+
+    /// r || s || v with v = 27 + parity, as `eth_sign` returns.
+    pub fn to_ethereum(&self) -> Result<[u8; 65], Error> {
+        let v = self.ethereum_v(None)?;
+        let mut out = [0u8; 65];
+        out[..64].copy_from_slice(&self.to_compact());
+        out[64] = v as u8;
+        Ok(out)
+    }
+}
+
+// a DER INTEGER of an unsigned big-endian value: no leading zeros, except
+// one where the high bit would read as a sign
+fn der_integer(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes
+        .iter()
+        .position(|b| *b != 0)
+        .unwrap_or(bytes.len() - 1);
+    let value = &bytes[start..];
+    let pad = value[0] & 0x80 != 0;
+    let mut out = vec![0x02, (value.len() + usize::from(pad)) as u8];
+    if pad {
+        out.push(0);
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::traits::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_encodings() {
+        let secret: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let public_key = &g * &secret;
+        let digest = SigningDigest::from_prehashed(&[9u8; 32]).unwrap();
+        let reference = ecdsa::sign(&secret, digest.as_bytes());
+
+        // a high-s signature as the threshold rounds may output
+        let high_s: FE = ECScalar::from(&(FE::q() - reference.s.to_big_int()));
+        let sig = Signature {
+            r: reference.r.clone(),
+            s: high_s,
+        };
+        let output = ThresholdSignature::new(
+            &sig,
+            &public_key,
+            &digest,
+            &[3, 1, 3],
+            Some("ab".to_string()),
+        )
+        .unwrap();
+        assert_eq!(output.s, reference.s);
+        assert_eq!(output.recid, reference.recid);
+        assert_eq!(output.signers, vec![1, 3]);
+
+        let compact = output.to_compact();
+        let secp_sig = secp256k1::Signature::parse(&compact);
+        assert_eq!(output.to_der(), secp_sig.serialize_der().as_ref().to_vec());
+        assert_eq!(output.to_recoverable()[64], output.recid);
+        if output.recid < 2 {
+            assert_eq!(output.to_ethereum().unwrap()[64], 27 + output.recid);
+            assert_eq!(output.ethereum_v(Some(1)), Ok(37 + u64::from(output.recid)));
+        }
+
+        let other = SigningDigest::from_prehashed(&[8u8; 32]).unwrap();
+        assert_eq!(
+            ThresholdSignature::new(&sig, &public_key, &other, &[1, 3], None).err(),
+            Some(InvalidSig)
+        );
+    }
+}
@@ -0,0 +1,118 @@
+/*
+    Key tweaking for GG18 local keys.
+
+    Every party applies the same public tweak `t` to its own `LocalKey`. The
+    tweak is folded into the constant term of the first dealer's VSS
+    polynomial, so each share becomes `x_i + t`, every public share commitment
+    moves by `t*G`, and since Lagrange coefficients over any signing set sum to
+    one the group key becomes `y + t*G`. Signing then runs unchanged and the
+    ECDSA signatures verify under the tweaked key.
+
+    The tweak is deterministic in public data, so parties coordinate by
+    agreeing on `t`; no extra round is needed. Parties that apply different
+    tweaks will fail the `g_w_i` consistency check in the MtA round.
+
+    There is no BIP-341 x-only tweak: a taproot key-path spend needs a
+    BIP-340 Schnorr signature, and GG18 only signs ECDSA.
+*/
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::LocalKey;
+
+fn negate_scalar(s: &FE) -> FE {
+    ECScalar::from(&(FE::q() - s.to_big_int()))
+}
+
+fn negate_point(p: &GE) -> GE {
+    let minus_one: FE = ECScalar::from(&(FE::q() - BigInt::from(1_u16)));
+    p * &minus_one
+}
+
+impl LocalKey {
+    /// Returns the key for group public key `y + tweak*G`.
+    pub fn tweak_add(&self, tweak: &FE) -> LocalKey {
+        let g: GE = ECPoint::generator();
+        let tweak_point = &g * tweak;
+        let mut key = self.clone();
+
+        // the first dealer's polynomial carries the tweak, so that party's
+        // keygen contribution moves with it.
+        key.vss_scheme_vec[0].commitments[0] =
+            key.vss_scheme_vec[0].commitments[0].clone() + &tweak_point;
+        if self.party_id == 1 {
+            key.party_keys.u_i = key.party_keys.u_i.clone() + tweak;
+            key.party_keys.y_i = key.party_keys.y_i.clone() + &tweak_point;
+        }
+
+        key.shared_keys.x_i = key.shared_keys.x_i.clone() + tweak;
+        key.shared_keys.y = key.shared_keys.y.clone() + &tweak_point;
+        key.y_sum = key.y_sum.clone() + &tweak_point;
+        key
+    }
+
+    /// Returns the key with every secret and public value negated, i.e. the
+    /// key for group public key `-y`.
+    pub fn negate(&self) -> LocalKey {
+        let mut key = self.clone();
+        key.party_keys.u_i = negate_scalar(&key.party_keys.u_i);
+        key.party_keys.y_i = negate_point(&key.party_keys.y_i);
+        for vss in key.vss_scheme_vec.iter_mut() {
+            vss.commitments = vss.commitments.iter().map(negate_point).collect();
+        }
+        key.shared_keys.x_i = negate_scalar(&key.shared_keys.x_i);
+        key.shared_keys.y = negate_point(&key.shared_keys.y);
+        key.y_sum = negate_point(&key.y_sum);
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn check_consistent(secret: &FE, keys: &[LocalKey]) {
+        let g: GE = ECPoint::generator();
+        let combined = VerifiableSS::combine(&keys[0].vss_scheme_vec).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(key.y_sum, &g * secret);
+            assert_eq!(
+                combined.get_point_commitment(i + 1),
+                &g * &key.shared_keys.x_i
+            );
+            assert_eq!(key.vss_scheme_vec[i].commitments[0], key.party_keys.y_i);
+        }
+        let indices = (0..keys.len()).collect::<Vec<usize>>();
+        let shares = keys
+            .iter()
+            .map(|k| k.shared_keys.x_i.clone())
+            .collect::<Vec<FE>>();
+        assert_eq!(combined.reconstruct(&indices, &shares), *secret);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_tweak_add() {
+        let (secret, keys) = dealer_keys(1, 3);
+        let tweak: FE = ECScalar::new_random();
+        let tweaked = keys.iter().map(|k| k.tweak_add(&tweak)).collect::<Vec<_>>();
+        check_consistent(&(secret + &tweak), &tweaked);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_negate() {
+        let (secret, keys) = dealer_keys(1, 3);
+        let negated = keys.iter().map(|k| k.negate()).collect::<Vec<_>>();
+        check_consistent(&negate_scalar(&secret), &negated);
+        // negating twice gives the key back
+        let restored = negated.iter().map(|k| k.negate()).collect::<Vec<_>>();
+        check_consistent(&secret, &restored);
+    }
+}
@@ -0,0 +1,431 @@
+/*
+    Verifiable encryption of a share to an offline recovery key.
+
+    The share x_i is encrypted bit by bit under the recovery key R with
+    exponential ElGamal, (r_k G, b_k G + r_k R) for bit b_k, and comes with
+
+    - for every bit, an OR proof that the ciphertext holds 0 or 1, and
+    - a Chaum-Pedersen proof that the ciphertexts weighted by 2^k hold the
+      discrete log of the party's public share x_i G, which the other
+      parties know from the keygen commitments.
+
+    So any party can check, without the recovery key, that the recovery key
+    holder will get the real share. Decryption needs no discrete log search:
+    each bit is 0 or 1, so the holder compares the ciphertext with the two
+    candidates. The ciphertext is large, 256 bit encryptions and proofs, which
+    suits a one-off disaster recovery backup.
+*/
+
+use merlin::Transcript;
+
+use crate::curv::arithmetic::num_bigint::BigInt;
+use crate::curv::cryptographic_primitives::hashing::transcript::TranscriptExt;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::gg_2018::party_i::LocalKey;
+
+const SHARE_BITS: usize = 256;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum EncryptionError {
+    WrongRecoveryKey,
+    InvalidCiphertext,
+    InvalidProof,
+    InvalidShare,
+}
+
+/// Proof that `(c1, c2)` encrypts 0 or 1: for `j` in {0, 1}, `z_j G = a_j +
+/// c_j c1` and `z_j R = b_j + c_j (c2 - j G)`, with `c_0 + c_1` the
+/// transcript challenge.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BitProof {
+    pub a0: GE,
+    pub b0: GE,
+    pub a1: GE,
+    pub b1: GE,
+    pub c0: FE,
+    pub z0: FE,
+    pub z1: FE,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedShare {
+    pub party_id: u16,
+    pub recovery_key: GE,
+    /// `r_k G`, least significant bit first.
+    pub c1: Vec<GE>,
+    /// `b_k G + r_k R`.
+    pub c2: Vec<GE>,
+    pub bit_proofs: Vec<BitProof>,
+    pub sum_a: GE,
+    pub sum_b: GE,
+    pub sum_z: FE,
+    /// Bound into the proofs, e.g. the terms of an escrow; empty for
+    /// `encrypt_share_to`.
+    #[serde(default)]
+    pub context: Vec<u8>,
+}
+
+fn sub(a: &GE, b: &GE) -> GE {
+    a.sub_point(&b.get_element())
+}
+
+// 2^k for every bit position
+fn weights() -> Vec<FE> {
+    let mut w: FE = ECScalar::from(&BigInt::from(1u32));
+    (0..SHARE_BITS)
+        .map(|_| {
+            let current = w.clone();
+            w = w.clone() + &w;
+            current
+        })
+        .collect()
+}
+
+fn weighted_sum(points: &[GE], weights: &[FE]) -> GE {
+    let mut terms = points.iter().zip(weights).map(|(p, w)| p * w);
+    let head = terms.next().unwrap();
+    terms.fold(head, |acc, p| acc + &p)
+}
+
+// everything but the responses; the challenges are drawn in the order
+// bit 0 .. bit 255, then the sum
+fn transcript(
+    party_id: u16,
+    recovery_key: &GE,
+    public_share: &GE,
+    c1: &[GE],
+    c2: &[GE],
+    bit_commitments: &[[GE; 4]],
+    sum_commitment: (&GE, &GE),
+) -> Transcript {
+    let mut transcript = Transcript::new(b"tss-wasm verifiable share encryption");
+    transcript.append_canonical(b"party_id", &party_id);
+    transcript.append_canonical(b"recovery_key", recovery_key);
+    transcript.append_canonical(b"public_share", public_share);
+    transcript.append_canonical(b"c1", c1);
+    transcript.append_canonical(b"c2", c2);
+    for commitments in bit_commitments {
+        transcript.append_canonical(b"bit commitments", &commitments[..]);
+    }
+    transcript.append_canonical(b"sum commitment", &sum_commitment);
+    transcript
+}
+
+// appended only when set, so that shares encrypted without a context keep
+// their transcript
+fn bind_context(transcript: &mut Transcript, context: &[u8]) {
+    if !context.is_empty() {
+        transcript.append_message(b"context", context);
+    }
+}
+
+fn public_share(vss_scheme_vec: &[VerifiableSS], party_id: u16) -> Result<GE, EncryptionError> {
+    VerifiableSS::combine(vss_scheme_vec)
+        .map(|combined| combined.get_point_commitment(party_id as usize))
+        .map_err(|_| EncryptionError::InvalidShare)
+}
+
+impl LocalKey {
+    /// Encrypts this party's share to `recovery_key`, with proofs any other
+    /// party can check with `verify_encrypted_share`.
+    pub fn encrypt_share_to(&self, recovery_key: &GE) -> EncryptedShare {
+        self.encrypt_share_with_context(recovery_key, &[])
+    }
+
+    /// `encrypt_share_to` with `context` bound into the proofs, so that it
+    /// cannot be changed without invalidating them.
+    pub fn encrypt_share_with_context(&self, recovery_key: &GE, context: &[u8]) -> EncryptedShare {
+        let g: GE = ECPoint::generator();
+        let x_bytes = self.shared_keys.x_i.to_bytes_be();
+        let bits = (0..SHARE_BITS)
+            .map(|k| (x_bytes[31 - k / 8] >> (k % 8)) & 1)
+            .collect::<Vec<u8>>();
+
+        let r = (0..SHARE_BITS)
+            .map(|_| ECScalar::new_random())
+            .collect::<Vec<FE>>();
+        let c1 = r.iter().map(|r_k| &g * r_k).collect::<Vec<GE>>();
+        let c2 = bits
+            .iter()
+            .zip(r.iter())
+            .map(|(b, r_k)| {
+                let masked = recovery_key * r_k;
+                if *b == 1 {
+                    masked + &g
+                } else {
+                    masked
+                }
+            })
+            .collect::<Vec<GE>>();
+
+        // the real branch is proven with nonce w, the other one simulated
+        // from a chosen challenge and response
+        let mut nonces = Vec::with_capacity(SHARE_BITS);
+        let mut simulated = Vec::with_capacity(SHARE_BITS);
+        let mut bit_commitments = Vec::with_capacity(SHARE_BITS);
+        for ((b, u), v) in bits.iter().zip(c1.iter()).zip(c2.iter()) {
+            let w: FE = ECScalar::new_random();
+            let c_sim: FE = ECScalar::new_random();
+            let z_sim: FE = ECScalar::new_random();
+            let d_sim = if *b == 1 { v.clone() } else { sub(v, &g) };
+            let real = [&g * &w, recovery_key * &w];
+            let sim = [
+                sub(&(&g * &z_sim), &(u * &c_sim)),
+                sub(&(recovery_key * &z_sim), &(&d_sim * &c_sim)),
+            ];
+            let [real_a, real_b] = real;
+            let [sim_a, sim_b] = sim;
+            bit_commitments.push(if *b == 0 {
+                [real_a, real_b, sim_a, sim_b]
+            } else {
+                [sim_a, sim_b, real_a, real_b]
+            });
+            nonces.push(w);
+            simulated.push((c_sim, z_sim));
+        }
+
+        let weights = weights();
+        let r_sum = r
+            .iter()
+            .zip(weights.iter())
+            .fold(FE::zero(), |acc, (r_k, w)| acc + r_k.clone() * w);
+        let sum_nonce: FE = ECScalar::new_random();
+        let sum_a = &g * &sum_nonce;
+        let sum_b = recovery_key * &sum_nonce;
+
+        let x_i_g = &g * &self.shared_keys.x_i;
+        let mut transcript = transcript(
+            self.party_id,
+            recovery_key,
+            &x_i_g,
+            &c1,
+            &c2,
+            &bit_commitments,
+            (&sum_a, &sum_b),
+        );
+        bind_context(&mut transcript, context);
+        let bit_proofs = bit_commitments
+            .into_iter()
+            .zip(simulated)
+            .zip(nonces)
+            .zip(bits.iter().zip(r.iter()))
+            .map(|(((commitments, (c_sim, z_sim)), w), (b, r_k))| {
+                let c = transcript.challenge_scalar(b"bit challenge");
+                let c_real = c.sub(&c_sim.get_element());
+                let z_real = w + c_real.clone() * r_k;
+                let [a0, b0, a1, b1] = commitments;
+                let (c0, z0, z1) = if *b == 0 {
+                    (c_real, z_real, z_sim)
+                } else {
+                    (c_sim, z_sim, z_real)
+                };
+                BitProof {
+                    a0,
+                    b0,
+                    a1,
+                    b1,
+                    c0,
+                    z0,
+                    z1,
+                }
+            })
+            .collect();
+        let c = transcript.challenge_scalar(b"sum challenge");
+        let sum_z = sum_nonce + c * &r_sum;
+
+        EncryptedShare {
+            party_id: self.party_id,
+            recovery_key: recovery_key.clone(),
+            c1,
+            c2,
+            bit_proofs,
+            sum_a,
+            sum_b,
+            sum_z,
+            context: context.to_vec(),
+        }
+    }
+
+    /// Checks that `encrypted` holds, under `recovery_key`, the share of the
+    /// party it names, as committed to in this key's keygen data.
+    pub fn verify_encrypted_share(
+        &self,
+        recovery_key: &GE,
+        encrypted: &EncryptedShare,
+    ) -> Result<(), EncryptionError> {
+        if &encrypted.recovery_key != recovery_key {
+            return Err(EncryptionError::WrongRecoveryKey);
+        }
+        if encrypted.c1.len() != SHARE_BITS
+            || encrypted.c2.len() != SHARE_BITS
+            || encrypted.bit_proofs.len() != SHARE_BITS
+        {
+            return Err(EncryptionError::InvalidCiphertext);
+        }
+        let g: GE = ECPoint::generator();
+        let x_i_g = public_share(&self.vss_scheme_vec, encrypted.party_id)?;
+        let bit_commitments = encrypted
+            .bit_proofs
+            .iter()
+            .map(|p| [p.a0.clone(), p.b0.clone(), p.a1.clone(), p.b1.clone()])
+            .collect::<Vec<[GE; 4]>>();
+        let mut transcript = transcript(
+            encrypted.party_id,
+            recovery_key,
+            &x_i_g,
+            &encrypted.c1,
+            &encrypted.c2,
+            &bit_commitments,
+            (&encrypted.sum_a, &encrypted.sum_b),
+        );
+        bind_context(&mut transcript, &encrypted.context);
+
+        let bits = encrypted
+            .bit_proofs
+            .iter()
+            .zip(encrypted.c1.iter().zip(encrypted.c2.iter()));
+        for (p, (u, v)) in bits {
+            let c = transcript.challenge_scalar(b"bit challenge");
+            let c1 = c.sub(&p.c0.get_element());
+            // c2 - G would be the point at infinity
+            if *v == g {
+                return Err(EncryptionError::InvalidCiphertext);
+            }
+            let v_minus_g = sub(v, &g);
+            let valid = &g * &p.z0 == p.a0.clone() + &(u * &p.c0)
+                && recovery_key * &p.z0 == p.b0.clone() + &(v * &p.c0)
+                && &g * &p.z1 == p.a1.clone() + &(u * &c1)
+                && recovery_key * &p.z1 == p.b1.clone() + &(&v_minus_g * &c1);
+            if !valid {
+                return Err(EncryptionError::InvalidProof);
+            }
+        }
+
+        let weights = weights();
+        let u_sum = weighted_sum(&encrypted.c1, &weights);
+        let v_sum = sub(&weighted_sum(&encrypted.c2, &weights), &x_i_g);
+        let c = transcript.challenge_scalar(b"sum challenge");
+        if &g * &encrypted.sum_z != encrypted.sum_a.clone() + &(&u_sum * &c)
+            || recovery_key * &encrypted.sum_z != encrypted.sum_b.clone() + &(&v_sum * &c)
+        {
+            return Err(EncryptionError::InvalidProof);
+        }
+        Ok(())
+    }
+}
+
+impl EncryptedShare {
+    /// Recovers the share with the recovery key's secret and checks it
+    /// against the keygen commitments.
+    pub fn decrypt(
+        &self,
+        recovery_secret: &FE,
+        vss_scheme_vec: &[VerifiableSS],
+    ) -> Result<FE, EncryptionError> {
+        let g: GE = ECPoint::generator();
+        if &g * recovery_secret != self.recovery_key {
+            return Err(EncryptionError::WrongRecoveryKey);
+        }
+        let masks = self
+            .c1
+            .iter()
+            .map(|u| u * recovery_secret)
+            .collect::<Vec<GE>>();
+        self.decrypt_with_masks(&masks, vss_scheme_vec)
+    }
+
+    /// Recovers the share from the masks `r_k R` of every bit, e.g. combined
+    /// from the partial decryptions of the recovery key's shareholders.
+    pub fn decrypt_with_masks(
+        &self,
+        masks: &[GE],
+        vss_scheme_vec: &[VerifiableSS],
+    ) -> Result<FE, EncryptionError> {
+        let g: GE = ECPoint::generator();
+        if self.c1.len() != SHARE_BITS || self.c2.len() != SHARE_BITS || masks.len() != SHARE_BITS {
+            return Err(EncryptionError::InvalidCiphertext);
+        }
+        let mut x_bytes = [0u8; 32];
+        for (k, (v, mask)) in self.c2.iter().zip(masks).enumerate() {
+            if *v == mask.clone() + &g {
+                x_bytes[31 - k / 8] |= 1 << (k % 8);
+            } else if v != mask {
+                return Err(EncryptionError::InvalidCiphertext);
+            }
+        }
+        let x_i = FE::from_bytes(&x_bytes).map_err(|_| EncryptionError::InvalidShare)?;
+        if &g * &x_i != public_share(vss_scheme_vec, self.party_id)? {
+            return Err(EncryptionError::InvalidShare);
+        }
+        Ok(x_i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    // party 2's key, its share encrypted to a fresh recovery key, and that
+    // key's secret
+    fn encrypted_share() -> (LocalKey, EncryptedShare, FE) {
+        let key = dealer_keys(1, 3).1.remove(1);
+        let recovery_secret: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let encrypted = key.encrypt_share_to(&(&g * &recovery_secret));
+        (key, encrypted, recovery_secret)
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_verifiable_encryption() {
+        let (key, encrypted, recovery_secret) = encrypted_share();
+        let g: GE = ECPoint::generator();
+        assert_eq!(
+            key.verify_encrypted_share(&(&g * &recovery_secret), &encrypted),
+            Ok(())
+        );
+        assert_eq!(
+            encrypted.decrypt(&recovery_secret, &key.vss_scheme_vec),
+            Ok(key.shared_keys.x_i.clone())
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_forged_ciphertext() {
+        // a ciphertext of another share fails the sum proof
+        let (key, encrypted, recovery_secret) = encrypted_share();
+        let g: GE = ECPoint::generator();
+        let recovery_key = &g * &recovery_secret;
+        let mut forged = encrypted.clone();
+        forged.party_id = 3;
+        assert_eq!(
+            key.verify_encrypted_share(&recovery_key, &forged),
+            Err(EncryptionError::InvalidProof)
+        );
+        let mut flipped = encrypted;
+        flipped.c2[0] = flipped.c2[0].clone() + &g;
+        assert_eq!(
+            key.verify_encrypted_share(&recovery_key, &flipped),
+            Err(EncryptionError::InvalidProof)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_wrong_recovery_key() {
+        let (key, encrypted, recovery_secret) = encrypted_share();
+        let g: GE = ECPoint::generator();
+        let other_key = &g * &recovery_secret.invert();
+        assert_eq!(
+            key.verify_encrypted_share(&other_key, &encrypted),
+            Err(EncryptionError::WrongRecoveryKey)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Party identity keys behind a trait.
+//!
+//! A party is known outside the ceremony by a secp256k1 identity key, the
+//! one its attestations are checked under and its relay messages are
+//! signed with. `IdentitySigner` is all the crate asks of that key: its
+//! public half and ECDSA signatures over 32 byte digests. `LocalIdentity`
+//! keeps the secret in memory; `ExternalIdentity` asks something else to
+//! sign, an HSM, a secure enclave or a remote signing service, so that the
+//! secret never enters the process.
+//!
+//! `SignedEntry` authenticates relay messages with it: the relay can drop a
+//! signed message but not alter or forge one, provided the receiver checks
+//! it under the sender's known identity key.
+#![cfg(feature = "gg18")]
+
+use sha2::{Digest, Sha256};
+
+use crate::common::Entry;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+use crate::ecdsa::{self, RecoverableSignature};
+
+const ENTRY_DOMAIN: &[u8] = b"tss-wasm relay entry";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum IdentityError {
+    /// The backend did not sign, e.g. the HSM was unreachable or refused.
+    Unavailable(String),
+    /// A signature that does not verify under the identity key.
+    InvalidSignature,
+}
+
+/// A party's identity key, wherever it is kept.
+pub trait IdentitySigner {
+    fn public_key(&self) -> GE;
+
+    /// ECDSA over `digest`, with s in the lower half of the group order and
+    /// the recovery id.
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<RecoverableSignature, IdentityError>;
+}
+
+/// An identity key held in memory.
+#[derive(Clone)]
+pub struct LocalIdentity {
+    secret: FE,
+    public_key: GE,
+}
+
+impl LocalIdentity {
+    pub fn new(secret: FE) -> LocalIdentity {
+        let g: GE = ECPoint::generator();
+        LocalIdentity {
+            public_key: &g * &secret,
+            secret,
+        }
+    }
+}
+
+impl IdentitySigner for LocalIdentity {
+    fn public_key(&self) -> GE {
+        self.public_key.clone()
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<RecoverableSignature, IdentityError> {
+        Ok(ecdsa::sign(&self.secret, digest))
+    }
+}
+
+/// An identity key held by a device or service, signing through `sign`.
+/// `sign` returns r and s as the device gives them; they are checked
+/// against `public_key`, normalized to low s and given their recovery id,
+/// so a device configured with the wrong key fails here rather than at
+/// every verifier.
+pub struct ExternalIdentity<F> {
+    public_key: GE,
+    sign: F,
+}
+
+impl<F> ExternalIdentity<F>
+where
+    F: Fn(&[u8; 32]) -> Result<(FE, FE), IdentityError>,
+{
+    pub fn new(public_key: GE, sign: F) -> ExternalIdentity<F> {
+        ExternalIdentity { public_key, sign }
+    }
+}
+
+impl<F> IdentitySigner for ExternalIdentity<F>
+where
+    F: Fn(&[u8; 32]) -> Result<(FE, FE), IdentityError>,
+{
+    fn public_key(&self) -> GE {
+        self.public_key.clone()
+    }
+
+    fn sign_digest(&self, digest: &[u8; 32]) -> Result<RecoverableSignature, IdentityError> {
+        let (r, s) = (self.sign)(digest)?;
+        ecdsa::verify(&self.public_key, digest, &r, &s)
+            .map_err(|_| IdentityError::InvalidSignature)?;
+        let (s, _) = ecdsa::normalize_s(&s, 0);
+        let recid = ecdsa::recovery_id(&self.public_key, digest, &r, &s)
+            .ok_or(IdentityError::InvalidSignature)?;
+        Ok(RecoverableSignature { r, s, recid })
+    }
+}
+
+/// A relay message with its sender's signature. The entry key names the
+/// sender, the round and the ceremony, so a signed message cannot be
+/// replayed into another round or ceremony either.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SignedEntry {
+    pub entry: Entry,
+    pub signature: RecoverableSignature,
+}
+
+impl SignedEntry {
+    pub fn sign<I: IdentitySigner + ?Sized>(
+        entry: Entry,
+        identity: &I,
+    ) -> Result<SignedEntry, IdentityError> {
+        let signature = identity.sign_digest(&entry_digest(&entry))?;
+        Ok(SignedEntry { entry, signature })
+    }
+
+    /// The entry, if `sender` signed it.
+    pub fn verify(&self, sender: &GE) -> Result<&Entry, IdentityError> {
+        ecdsa::verify(
+            sender,
+            &entry_digest(&self.entry),
+            &self.signature.r,
+            &self.signature.s,
+        )
+        .map_err(|_| IdentityError::InvalidSignature)?;
+        Ok(&self.entry)
+    }
+}
+
+fn entry_digest(entry: &Entry) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(ENTRY_DOMAIN);
+    hasher.update((entry.key.len() as u64).to_be_bytes());
+    hasher.update(entry.key.as_bytes());
+    hasher.update(entry.value.as_bytes());
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_external_identity() {
+        let secret: FE = ECScalar::new_random();
+        let local = LocalIdentity::new(secret.clone());
+        // a device that hands back high s and no recovery id
+        let device = ExternalIdentity::new(local.public_key(), |digest: &[u8; 32]| {
+            let sig = ecdsa::sign(&secret, digest);
+            let high_s: FE = ECScalar::from(&(FE::q() - sig.s.to_big_int()));
+            Ok((sig.r, high_s))
+        });
+        let digest = [7u8; 32];
+        let sig = device.sign_digest(&digest).unwrap();
+        assert!(ecdsa::is_low_s(&sig.s));
+        assert_eq!(
+            ecdsa::recover(&digest, &sig.r, &sig.s, sig.recid),
+            Ok(local.public_key())
+        );
+
+        let other: FE = ECScalar::new_random();
+        let misconfigured = ExternalIdentity::new(local.public_key(), |digest: &[u8; 32]| {
+            let sig = ecdsa::sign(&other, digest);
+            Ok((sig.r, sig.s))
+        });
+        assert_eq!(
+            misconfigured.sign_digest(&digest).err(),
+            Some(IdentityError::InvalidSignature)
+        );
+        let offline = ExternalIdentity::new(local.public_key(), |_: &[u8; 32]| {
+            Err(IdentityError::Unavailable("hsm offline".to_string()))
+        });
+        assert!(matches!(
+            offline.sign_digest(&digest),
+            Err(IdentityError::Unavailable(_))
+        ));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_signed_entry() {
+        let secret: FE = ECScalar::new_random();
+        let identity = LocalIdentity::new(secret);
+        let entry = Entry {
+            key: "2-round1-7b0c".to_string(),
+            value: "{}".to_string(),
+        };
+        let signed = SignedEntry::sign(entry.clone(), &identity).unwrap();
+        let json = serde_json::to_string(&signed).unwrap();
+        let parsed: SignedEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.verify(&identity.public_key()), Ok(&entry));
+
+        let mut moved = parsed.clone();
+        moved.entry.key = "2-round2-7b0c".to_string();
+        assert_eq!(
+            moved.verify(&identity.public_key()),
+            Err(IdentityError::InvalidSignature)
+        );
+        let other: FE = ECScalar::new_random();
+        let stranger = LocalIdentity::new(other);
+        assert_eq!(
+            parsed.verify(&stranger.public_key()),
+            Err(IdentityError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Key stores encrypted under a password.
+//!
+//! `EncryptedKeyStore` seals a key store with AES-256-GCM under a key
+//! stretched from the password by a memory-hard KDF: Argon2id, or scrypt
+//! where an existing deployment already uses it. The KDF and its parameters
+//! are stored next to the ciphertext, so stores made with different
+//! settings open the same way.
+//!
+//! Browsers and servers need very different settings. A browser tab runs
+//! wasm on one thread with little memory to spare and a user waiting; a
+//! server has cores and memory to make each guess expensive.
+//! `KdfParams::WASM` and `KdfParams::SERVER` are presets for the two, and
+//! any other setting is checked against minimums below which a password
+//! guess gets cheap. Parameters read from a store are checked against the
+//! same bounds, and against maximums, so a store from elsewhere can neither
+//! downgrade the KDF nor make opening it take unbounded memory or time.
+#![cfg(feature = "keystore")]
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Version};
+use rand::{rngs::OsRng, RngCore};
+use std::fmt;
+
+use crate::storage::{self, Storage, StorageError, KEY_STORES};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+const KEY_STORE_VERSION: u8 = 1;
+const KEY_BYTES_LEN: usize = 32;
+const SALT_BYTES_LEN: usize = 16;
+const NONCE_BYTES_LEN: usize = 12;
+const KEY_STORE_AAD: &[u8] = b"tss-wasm password key store";
+
+/// Argon2id bounds, after the OWASP recommendations: at least 7 MiB, and
+/// memory times passes at least 37 MiB, e.g. 19 MiB with 2 passes.
+pub const MIN_ARGON2_MEMORY_KIB: u32 = 7 * 1024;
+pub const MIN_ARGON2_MEMORY_PASSES_KIB: u64 = 37 * 1024;
+pub const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024;
+pub const MAX_ARGON2_ITERATIONS: u32 = 64;
+pub const MAX_ARGON2_PARALLELISM: u32 = 16;
+/// scrypt bounds: N at least 2^15 with r at least 8, i.e. 32 MiB, and at
+/// most 1 GiB.
+pub const MIN_SCRYPT_LOG_N: u8 = 15;
+pub const MIN_SCRYPT_R: u32 = 8;
+pub const MAX_SCRYPT_MEMORY_BYTES: u64 = 1 << 30;
+pub const MAX_SCRYPT_P: u32 = 16;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum KeyStoreError {
+    /// KDF parameters below the minimums.
+    WeakParams,
+    /// KDF parameters above the maximums.
+    CostlyParams,
+    UnsupportedVersion,
+    /// A salt, nonce or ciphertext that is not hex or has the wrong length.
+    Malformed,
+    /// The password is wrong or the store was modified.
+    Decryption,
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyStoreError::WeakParams => write!(f, "KDF parameters below the minimums"),
+            KeyStoreError::CostlyParams => write!(f, "KDF parameters above the maximums"),
+            KeyStoreError::UnsupportedVersion => write!(f, "unsupported key store version"),
+            KeyStoreError::Malformed => write!(f, "malformed key store"),
+            KeyStoreError::Decryption => write!(f, "wrong password or modified key store"),
+        }
+    }
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedArgon2Params")]
+pub struct Argon2Params {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+#[derive(Deserialize)]
+struct UncheckedArgon2Params {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl TryFrom<UncheckedArgon2Params> for Argon2Params {
+    type Error = KeyStoreError;
+
+    fn try_from(p: UncheckedArgon2Params) -> Result<Self, Self::Error> {
+        Argon2Params::new(p.memory_kib, p.iterations, p.parallelism)
+    }
+}
+
+impl Argon2Params {
+    pub fn new(
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    ) -> Result<Argon2Params, KeyStoreError> {
+        if memory_kib < MIN_ARGON2_MEMORY_KIB
+            || iterations == 0
+            || parallelism == 0
+            || u64::from(memory_kib) * u64::from(iterations) < MIN_ARGON2_MEMORY_PASSES_KIB
+        {
+            return Err(KeyStoreError::WeakParams);
+        }
+        if memory_kib > MAX_ARGON2_MEMORY_KIB
+            || iterations > MAX_ARGON2_ITERATIONS
+            || parallelism > MAX_ARGON2_PARALLELISM
+        {
+            return Err(KeyStoreError::CostlyParams);
+        }
+        Ok(Argon2Params {
+            memory_kib,
+            iterations,
+            parallelism,
+        })
+    }
+
+    pub fn memory_kib(&self) -> u32 {
+        self.memory_kib
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    pub fn parallelism(&self) -> u32 {
+        self.parallelism
+    }
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedScryptParams")]
+pub struct ScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+#[derive(Deserialize)]
+struct UncheckedScryptParams {
+    log_n: u8,
+    r: u32,
+    p: u32,
+}
+
+impl TryFrom<UncheckedScryptParams> for ScryptParams {
+    type Error = KeyStoreError;
+
+    fn try_from(p: UncheckedScryptParams) -> Result<Self, Self::Error> {
+        ScryptParams::new(p.log_n, p.r, p.p)
+    }
+}
+
+impl ScryptParams {
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<ScryptParams, KeyStoreError> {
+        if log_n < MIN_SCRYPT_LOG_N || r < MIN_SCRYPT_R || p == 0 {
+            return Err(KeyStoreError::WeakParams);
+        }
+        // scrypt takes 128 r N bytes
+        if log_n >= 32 || 128 * u64::from(r) > MAX_SCRYPT_MEMORY_BYTES >> log_n || p > MAX_SCRYPT_P
+        {
+            return Err(KeyStoreError::CostlyParams);
+        }
+        Ok(ScryptParams { log_n, r, p })
+    }
+
+    pub fn log_n(&self) -> u8 {
+        self.log_n
+    }
+
+    pub fn r(&self) -> u32 {
+        self.r
+    }
+
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "lowercase")]
+pub enum KdfParams {
+    Argon2id(Argon2Params),
+    Scrypt(ScryptParams),
+}
+
+impl KdfParams {
+    /// 19 MiB and 2 passes on one lane: the OWASP minimum, which a browser
+    /// tab runs in about a second on a phone.
+    pub const WASM: KdfParams = KdfParams::Argon2id(Argon2Params {
+        memory_kib: 19 * 1024,
+        iterations: 2,
+        parallelism: 1,
+    });
+    /// 64 MiB, 3 passes on 4 lanes, the RFC 9106 second recommendation
+    /// with fewer lanes.
+    pub const SERVER: KdfParams = KdfParams::Argon2id(Argon2Params {
+        memory_kib: 64 * 1024,
+        iterations: 3,
+        parallelism: 4,
+    });
+    /// N = 2^17, r = 8, p = 1: 128 MiB, for where Argon2 is not an option.
+    pub const SCRYPT: KdfParams = KdfParams::Scrypt(ScryptParams {
+        log_n: 17,
+        r: 8,
+        p: 1,
+    });
+
+    /// The 32 byte AES key for `password` and `salt`.
+    pub fn derive_key(&self, password: &[u8], salt: &[u8]) -> [u8; KEY_BYTES_LEN] {
+        let mut key = [0u8; KEY_BYTES_LEN];
+        match self {
+            KdfParams::Argon2id(p) => {
+                let params = argon2::Params::new(
+                    p.memory_kib,
+                    p.iterations,
+                    p.parallelism,
+                    Some(KEY_BYTES_LEN),
+                )
+                .expect("checked Argon2 parameters");
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key)
+                    .expect("checked Argon2 parameters");
+            }
+            KdfParams::Scrypt(p) => {
+                let params =
+                    scrypt::Params::new(p.log_n, p.r, p.p).expect("checked scrypt parameters");
+                scrypt::scrypt(password, salt, &params, &mut key).expect("32 byte output");
+            }
+        }
+        key
+    }
+}
+
+/// `WASM` on wasm targets, `SERVER` elsewhere.
+impl Default for KdfParams {
+    fn default() -> KdfParams {
+        if cfg!(target_arch = "wasm32") {
+            KdfParams::WASM
+        } else {
+            KdfParams::SERVER
+        }
+    }
+}
+
+/// A key store encrypted under a password, with hex `salt`, `nonce` and
+/// `ciphertext`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct EncryptedKeyStore {
+    pub version: u8,
+    pub kdf: KdfParams,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedKeyStore {
+    pub fn encrypt(key_store: &str, password: &[u8], kdf: KdfParams) -> EncryptedKeyStore {
+        let mut rng = OsRng::new().unwrap();
+        let mut salt = [0u8; SALT_BYTES_LEN];
+        let mut nonce = [0u8; NONCE_BYTES_LEN];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut nonce);
+
+        let key = kdf.derive_key(password, &salt);
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let payload = Payload {
+            msg: key_store.as_bytes(),
+            aad: KEY_STORE_AAD,
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("encryption failure!");
+        EncryptedKeyStore {
+            version: KEY_STORE_VERSION,
+            kdf,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        }
+    }
+
+    pub fn decrypt(&self, password: &[u8]) -> Result<String, KeyStoreError> {
+        if self.version != KEY_STORE_VERSION {
+            return Err(KeyStoreError::UnsupportedVersion);
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| KeyStoreError::Malformed)?;
+        let nonce = hex::decode(&self.nonce).map_err(|_| KeyStoreError::Malformed)?;
+        let ciphertext = hex::decode(&self.ciphertext).map_err(|_| KeyStoreError::Malformed)?;
+        if salt.len() != SALT_BYTES_LEN || nonce.len() != NONCE_BYTES_LEN {
+            return Err(KeyStoreError::Malformed);
+        }
+
+        let key = self.kdf.derive_key(password, &salt);
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let payload = Payload {
+            msg: &ciphertext,
+            aad: KEY_STORE_AAD,
+        };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| KeyStoreError::Decryption)?;
+        String::from_utf8(plaintext).map_err(|_| KeyStoreError::Decryption)
+    }
+
+    /// Saves the encrypted store as `name` in the `KEY_STORES` namespace.
+    pub fn save<S: Storage + ?Sized>(
+        &self,
+        storage: &mut S,
+        name: &str,
+    ) -> Result<(), StorageError> {
+        storage::save(storage, KEY_STORES, name, self)
+    }
+
+    pub fn load<S: Storage + ?Sized>(
+        storage: &S,
+        name: &str,
+    ) -> Result<Option<EncryptedKeyStore>, StorageError> {
+        storage::load(storage, KEY_STORES, name)
+    }
+}
+
+/// Encrypts `key_store` under `password`. `kdf` is the json of a
+/// `KdfParams`, `KdfParams::WASM` when absent. Returns the json of an
+/// `EncryptedKeyStore`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn gg18_encrypt_key_store(
+    key_store: String,
+    password: String,
+    kdf: Option<String>,
+) -> Result<String, JsValue> {
+    let kdf = match kdf {
+        Some(kdf) => serde_json::from_str::<KdfParams>(&kdf)
+            .map_err(|e| JsValue::from_str(&format!("invalid KDF parameters: {}", e)))?,
+        None => KdfParams::WASM,
+    };
+    let encrypted = EncryptedKeyStore::encrypt(&key_store, password.as_bytes(), kdf);
+    Ok(serde_json::to_string(&encrypted).unwrap())
+}
+
+/// Decrypts the output of `gg18_encrypt_key_store`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn gg18_decrypt_key_store(encrypted: String, password: String) -> Result<String, JsValue> {
+    let encrypted = serde_json::from_str::<EncryptedKeyStore>(&encrypted)
+        .map_err(|e| JsValue::from_str(&format!("invalid encrypted key store: {}", e)))?;
+    encrypted
+        .decrypt(password.as_bytes())
+        .map_err(|e| JsValue::from_str(&format!("{:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_kdf_params() {
+        assert_eq!(
+            Argon2Params::new(19 * 1024, 1, 1),
+            Err(KeyStoreError::WeakParams)
+        );
+        assert_eq!(
+            Argon2Params::new(4 * 1024, 10, 1),
+            Err(KeyStoreError::WeakParams)
+        );
+        assert!(Argon2Params::new(46 * 1024, 1, 1).is_ok());
+        assert_eq!(
+            Argon2Params::new(2 * 1024 * 1024, 1, 1),
+            Err(KeyStoreError::CostlyParams)
+        );
+        assert_eq!(ScryptParams::new(14, 8, 1), Err(KeyStoreError::WeakParams));
+        assert_eq!(ScryptParams::new(17, 4, 1), Err(KeyStoreError::WeakParams));
+        assert_eq!(
+            ScryptParams::new(24, 8, 1),
+            Err(KeyStoreError::CostlyParams)
+        );
+        for preset in [KdfParams::WASM, KdfParams::SERVER, KdfParams::SCRYPT] {
+            let json = serde_json::to_string(&preset).unwrap();
+            assert_eq!(serde_json::from_str::<KdfParams>(&json).unwrap(), preset);
+        }
+
+        // a store cannot carry parameters `new` refuses
+        let weak = r#"{"algorithm":"argon2id","memory_kib":8,"iterations":1,"parallelism":1}"#;
+        assert!(serde_json::from_str::<KdfParams>(weak).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key_store = r#"{"party_id":1}"#;
+        for kdf in [KdfParams::WASM, KdfParams::SCRYPT] {
+            let encrypted = EncryptedKeyStore::encrypt(key_store, b"correct horse", kdf);
+            let json = serde_json::to_string(&encrypted).unwrap();
+            let parsed: EncryptedKeyStore = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.decrypt(b"correct horse").unwrap(), key_store);
+            assert_eq!(
+                parsed.decrypt(b"battery staple"),
+                Err(KeyStoreError::Decryption)
+            );
+        }
+
+        let mut truncated = EncryptedKeyStore::encrypt(key_store, b"pw", KdfParams::WASM);
+        truncated.salt.truncate(8);
+        assert_eq!(truncated.decrypt(b"pw"), Err(KeyStoreError::Malformed));
+    }
+}
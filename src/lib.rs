@@ -21,7 +21,31 @@ pub mod paillier;
 #[macro_use]
 pub mod common;
 
+pub mod address;
 pub mod api;
+pub mod api_error;
+pub mod attestation;
+pub mod backup;
+pub mod coordinator;
+pub mod ecdsa;
+pub mod ecies;
+pub mod ethereum;
+pub mod fuzzing;
+pub mod identity;
+pub mod keystore;
+pub mod metrics;
+pub mod noise;
+pub mod rustcrypto;
+pub mod schema;
+pub mod session;
+pub mod signer;
+pub mod storage;
+pub mod verify;
+pub mod wallet;
+pub mod webcrypto;
+
+#[cfg(test)]
+mod fixtures;
 
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
 pub enum Error {
@@ -29,10 +53,20 @@ pub enum Error {
     InvalidSS,
     InvalidCom,
     InvalidSig,
+    InvalidCiphertext,
+    InvalidPlaintext,
+    InvalidRandomness,
+    InvalidDigest,
+    /// Paillier randomness already spent in the session.
+    ReusedRandomness,
+    /// An MtA answer decrypts to more than an honest Bob's share can be;
+    /// its sender is to blame.
+    MtaShareOutOfRange,
 }
 #[derive(Copy, PartialEq, Eq, Clone, Debug)]
 pub enum ErrorKey {
     InvalidPublicKey,
+    InvalidScalar,
 }
 
 pub enum ErrorSS {
@@ -0,0 +1,120 @@
+//! Telemetry hooks for the protocol driver.
+//!
+//! The relay client reports every message it sends and every round of
+//! messages it collects, and the keygen and signing rounds of the wasm API
+//! report the proofs they verified and how long they took. Nothing is
+//! reported until an implementation is installed with `set_metrics`, e.g.
+//! one that feeds Prometheus counters. The callbacks run inline, so they
+//! should only update counters.
+
+#![cfg(feature = "gg18")]
+
+use std::sync::{Arc, RwLock};
+
+use crate::common::now_ms;
+use crate::session::Ceremony;
+
+pub trait Metrics: Send + Sync {
+    /// A broadcast or p2p message of `bytes` sent for `round`.
+    fn message_sent(&self, _round: &str, _bytes: usize) {}
+
+    /// A message of `bytes` from party `from` read for `round`.
+    fn message_received(&self, _round: &str, _from: u16, _bytes: usize) {}
+
+    /// All `count` messages of `round` collected, `bytes` in total.
+    fn messages_received(&self, _round: &str, _count: usize, _bytes: usize) {}
+
+    /// `count` zero-knowledge proofs verified in `round`.
+    fn proofs_verified(&self, _round: &str, _count: usize) {}
+
+    /// `round` of a ceremony completed after `duration_ms`, network waits
+    /// included. Rounds that fail are not reported.
+    fn round_finished(&self, _ceremony: Ceremony, _round: &str, _duration_ms: f64) {}
+}
+
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
+
+lazy_static::lazy_static! {
+    static ref METRICS: RwLock<Arc<dyn Metrics>> = RwLock::new(Arc::new(NoMetrics));
+}
+
+/// Installs `metrics` for all ceremonies of this process.
+pub fn set_metrics(metrics: Arc<dyn Metrics>) {
+    *METRICS.write().unwrap() = metrics;
+}
+
+pub fn metrics() -> Arc<dyn Metrics> {
+    METRICS.read().unwrap().clone()
+}
+
+/// Times one round, see `Metrics::round_finished`.
+pub struct RoundTimer {
+    ceremony: Ceremony,
+    round: &'static str,
+    started_ms: f64,
+}
+
+impl RoundTimer {
+    pub fn start(ceremony: Ceremony, round: &'static str) -> RoundTimer {
+        RoundTimer {
+            ceremony,
+            round,
+            started_ms: now_ms(),
+        }
+    }
+
+    pub fn finish(self) {
+        metrics().round_finished(self.ceremony, self.round, now_ms() - self.started_ms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl Metrics for Recorder {
+        fn message_sent(&self, round: &str, bytes: usize) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("sent {} {}", round, bytes));
+        }
+
+        fn round_finished(&self, ceremony: Ceremony, round: &str, duration_ms: f64) {
+            assert!(duration_ms >= 0.0);
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("finished {:?} {}", ceremony, round));
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_metrics_hooks() {
+        let recorder = Arc::new(Recorder::default());
+        set_metrics(recorder.clone());
+        metrics().message_sent("round1", 42);
+        // the defaults are no-ops
+        metrics().proofs_verified("round1", 3);
+        RoundTimer::start(Ceremony::Sign, "round1").finish();
+        set_metrics(Arc::new(NoMetrics));
+        metrics().message_sent("round2", 1);
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec!["sent round1 42", "finished Sign round1"]
+        );
+    }
+}
@@ -0,0 +1,582 @@
+//! Noise handshakes and transport for authenticated channels between parties.
+//!
+//! Enabled with the `noise` feature. Implements the XX and IK patterns of
+//! the Noise Protocol Framework (revision 34) as
+//! `Noise_XX_secp256k1_AESGCM_SHA256` and `Noise_IK_secp256k1_AESGCM_SHA256`,
+//! so the static keys are the same secp256k1 identity keys used elsewhere in
+//! the crate. Following BOLT 8, the DH output is the SHA-256 of the
+//! compressed shared point; public keys travel as the 64 byte `x || y`.
+//!
+//! The module only produces and consumes byte strings, so it runs over any
+//! transport the caller has, including the relay from wasm. Once the
+//! handshake finishes, the relay sees only ciphertext and cannot
+//! impersonate either side, provided the remote static key is checked
+//! against the expected identity.
+#![cfg(feature = "noise")]
+
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256};
+
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::*;
+
+const HASHLEN: usize = 32;
+const PUBLIC_KEY_LEN: usize = 64;
+const TAG_LEN: usize = 16;
+/// Largest Noise message, handshake or transport.
+pub const MAX_MESSAGE_LEN: usize = 65535;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum NoiseError {
+    InvalidMessage,
+    InvalidPublicKey,
+    DecryptFailed,
+    MessageTooLong,
+    MissingRemoteStatic,
+    WrongTurn,
+    HandshakeFinished,
+    HandshakeNotFinished,
+    NonceExhausted,
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum HandshakePattern {
+    /// Both static keys are sent during the handshake; three messages.
+    XX,
+    /// The initiator knows the responder's static key; two messages.
+    IK,
+}
+
+#[derive(Copy, Clone)]
+enum Token {
+    E,
+    S,
+    EE,
+    ES,
+    SE,
+    SS,
+}
+
+impl HandshakePattern {
+    fn name(&self) -> &'static str {
+        match self {
+            HandshakePattern::XX => "Noise_XX_secp256k1_AESGCM_SHA256",
+            HandshakePattern::IK => "Noise_IK_secp256k1_AESGCM_SHA256",
+        }
+    }
+
+    fn messages(&self) -> &'static [&'static [Token]] {
+        use Token::*;
+        match self {
+            HandshakePattern::XX => &[&[E], &[E, EE, S, ES], &[S, SE]],
+            HandshakePattern::IK => &[&[E, ES, S, SS], &[E, EE, SE]],
+        }
+    }
+
+    fn responder_static_premessage(&self) -> bool {
+        match self {
+            HandshakePattern::XX => false,
+            HandshakePattern::IK => true,
+        }
+    }
+}
+
+fn public_key(secret: &FE) -> GE {
+    let g: GE = ECPoint::generator();
+    &g * secret
+}
+
+fn encode_public(point: &GE) -> Vec<u8> {
+    point.to_bytes(false)[1..].to_vec()
+}
+
+fn decode_public(bytes: &[u8]) -> Result<GE, NoiseError> {
+    if bytes.len() != PUBLIC_KEY_LEN {
+        return Err(NoiseError::InvalidPublicKey);
+    }
+    GE::from_bytes(bytes).map_err(|_| NoiseError::InvalidPublicKey)
+}
+
+fn dh(secret: &FE, public: &GE) -> [u8; HASHLEN] {
+    let shared = public * secret;
+    let mut out = [0u8; HASHLEN];
+    out.copy_from_slice(&Sha256::digest(&shared.to_bytes(true)));
+    out
+}
+
+fn hmac_sha256(key: &[u8], data: &[&[u8]]) -> [u8; HASHLEN] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC must take a key of any length");
+    for d in data {
+        mac.update(d);
+    }
+    let mut out = [0u8; HASHLEN];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    out
+}
+
+fn hkdf(chaining_key: &[u8], ikm: &[u8]) -> ([u8; HASHLEN], [u8; HASHLEN]) {
+    let temp_key = hmac_sha256(chaining_key, &[ikm]);
+    let out1 = hmac_sha256(&temp_key, &[&[1u8][..]]);
+    let out2 = hmac_sha256(&temp_key, &[&out1[..], &[2u8][..]]);
+    (out1, out2)
+}
+
+// The DH a token calls for, from the point of view of the side given by
+// `initiator`.
+fn token_dh(
+    token: Token,
+    initiator: bool,
+    s: &FE,
+    e: Option<&FE>,
+    rs: Option<&GE>,
+    re: Option<&GE>,
+) -> Result<[u8; HASHLEN], NoiseError> {
+    let (local, remote) = match (token, initiator) {
+        (Token::EE, _) => (e, re),
+        (Token::SS, _) => (Some(s), rs),
+        (Token::ES, true) | (Token::SE, false) => (e, rs),
+        (Token::ES, false) | (Token::SE, true) => (Some(s), re),
+        (Token::E, _) | (Token::S, _) => unreachable!(),
+    };
+    match (local, remote) {
+        (Some(local), Some(remote)) => Ok(dh(local, remote)),
+        _ => Err(NoiseError::MissingRemoteStatic),
+    }
+}
+
+#[derive(Clone)]
+struct CipherState {
+    key: Option<[u8; 32]>,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn new(key: Option<[u8; 32]>) -> CipherState {
+        CipherState { key, nonce: 0 }
+    }
+
+    fn gcm_nonce(&self) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_be_bytes());
+        nonce
+    }
+
+    fn encrypt_with_ad(&mut self, ad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let key = match self.key {
+            Some(key) => key,
+            None => return Ok(plaintext.to_vec()),
+        };
+        if self.nonce == u64::MAX {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let cipher = Aes256Gcm::new(aes_gcm::Key::from_slice(&key));
+        let ciphertext = cipher
+            .encrypt(
+                Nonce::from_slice(&self.gcm_nonce()),
+                Payload {
+                    msg: plaintext,
+                    aad: ad,
+                },
+            )
+            .expect("encryption failure!");
+        self.nonce += 1;
+        Ok(ciphertext)
+    }
+
+    fn decrypt_with_ad(&mut self, ad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let key = match self.key {
+            Some(key) => key,
+            None => return Ok(ciphertext.to_vec()),
+        };
+        if self.nonce == u64::MAX {
+            return Err(NoiseError::NonceExhausted);
+        }
+        let cipher = Aes256Gcm::new(aes_gcm::Key::from_slice(&key));
+        let plaintext = cipher
+            .decrypt(
+                Nonce::from_slice(&self.gcm_nonce()),
+                Payload {
+                    msg: ciphertext,
+                    aad: ad,
+                },
+            )
+            .map_err(|_| NoiseError::DecryptFailed)?;
+        self.nonce += 1;
+        Ok(plaintext)
+    }
+}
+
+#[derive(Clone)]
+struct SymmetricState {
+    cipher: CipherState,
+    chaining_key: [u8; HASHLEN],
+    hash: [u8; HASHLEN],
+}
+
+impl SymmetricState {
+    fn new(protocol_name: &str) -> SymmetricState {
+        let mut hash = [0u8; HASHLEN];
+        if protocol_name.len() <= HASHLEN {
+            hash[..protocol_name.len()].copy_from_slice(protocol_name.as_bytes());
+        } else {
+            hash.copy_from_slice(&Sha256::digest(protocol_name.as_bytes()));
+        }
+        SymmetricState {
+            cipher: CipherState::new(None),
+            chaining_key: hash,
+            hash,
+        }
+    }
+
+    fn mix_key(&mut self, ikm: &[u8]) {
+        let (chaining_key, temp_key) = hkdf(&self.chaining_key, ikm);
+        self.chaining_key = chaining_key;
+        self.cipher = CipherState::new(Some(temp_key));
+    }
+
+    fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.hash);
+        hasher.update(data);
+        self.hash.copy_from_slice(&hasher.finalize());
+    }
+
+    fn encrypt_and_hash(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let ciphertext = self.cipher.encrypt_with_ad(&self.hash, plaintext)?;
+        self.mix_hash(&ciphertext);
+        Ok(ciphertext)
+    }
+
+    fn decrypt_and_hash(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        let plaintext = self.cipher.decrypt_with_ad(&self.hash, ciphertext)?;
+        self.mix_hash(ciphertext);
+        Ok(plaintext)
+    }
+
+    fn split(&self) -> (CipherState, CipherState) {
+        let (k1, k2) = hkdf(&self.chaining_key, &[]);
+        (CipherState::new(Some(k1)), CipherState::new(Some(k2)))
+    }
+
+    fn has_key(&self) -> bool {
+        self.cipher.key.is_some()
+    }
+}
+
+/// One side of a handshake. Each side alternately calls `write_message` and
+/// `read_message`, starting with the initiator writing, until
+/// `is_finished`, then turns the handshake into a `Transport`.
+pub struct Handshake {
+    pattern: HandshakePattern,
+    initiator: bool,
+    s: FE,
+    e: Option<FE>,
+    rs: Option<GE>,
+    re: Option<GE>,
+    symmetric: SymmetricState,
+    step: usize,
+}
+
+impl Handshake {
+    /// Starts a handshake as initiator with static key `s`. IK requires the
+    /// responder's static key `rs`; XX learns it during the handshake.
+    pub fn initiator(
+        pattern: HandshakePattern,
+        s: FE,
+        rs: Option<GE>,
+        prologue: &[u8],
+    ) -> Result<Handshake, NoiseError> {
+        if pattern.responder_static_premessage() && rs.is_none() {
+            return Err(NoiseError::MissingRemoteStatic);
+        }
+        let mut handshake = Handshake::new(pattern, true, s, rs, prologue);
+        if pattern.responder_static_premessage() {
+            let rs = encode_public(handshake.rs.as_ref().unwrap());
+            handshake.symmetric.mix_hash(&rs);
+        }
+        Ok(handshake)
+    }
+
+    pub fn responder(pattern: HandshakePattern, s: FE, prologue: &[u8]) -> Handshake {
+        let mut handshake = Handshake::new(pattern, false, s, None, prologue);
+        if pattern.responder_static_premessage() {
+            let s = encode_public(&public_key(&handshake.s));
+            handshake.symmetric.mix_hash(&s);
+        }
+        handshake
+    }
+
+    fn new(
+        pattern: HandshakePattern,
+        initiator: bool,
+        s: FE,
+        rs: Option<GE>,
+        prologue: &[u8],
+    ) -> Handshake {
+        let mut symmetric = SymmetricState::new(pattern.name());
+        symmetric.mix_hash(prologue);
+        Handshake {
+            pattern,
+            initiator,
+            s,
+            e: None,
+            rs,
+            re: None,
+            symmetric,
+            step: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.step == self.pattern.messages().len()
+    }
+
+    /// Whether the next message is ours to write.
+    pub fn is_my_turn(&self) -> bool {
+        !self.is_finished() && self.step.is_multiple_of(2) == self.initiator
+    }
+
+    /// The peer's static key, once the handshake has revealed it.
+    pub fn remote_static(&self) -> Option<&GE> {
+        self.rs.as_ref()
+    }
+
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if self.is_finished() {
+            return Err(NoiseError::HandshakeFinished);
+        }
+        if !self.is_my_turn() {
+            return Err(NoiseError::WrongTurn);
+        }
+        let mut symmetric = self.symmetric.clone();
+        let mut e = self.e.clone();
+        let mut message = Vec::new();
+        for token in self.pattern.messages()[self.step] {
+            match token {
+                Token::E => {
+                    let e_new: FE = ECScalar::new_random();
+                    let e_pub = encode_public(&public_key(&e_new));
+                    symmetric.mix_hash(&e_pub);
+                    message.extend_from_slice(&e_pub);
+                    e = Some(e_new);
+                }
+                Token::S => {
+                    let s_pub = encode_public(&public_key(&self.s));
+                    message.extend(symmetric.encrypt_and_hash(&s_pub)?);
+                }
+                _ => symmetric.mix_key(&token_dh(
+                    *token,
+                    self.initiator,
+                    &self.s,
+                    e.as_ref(),
+                    self.rs.as_ref(),
+                    self.re.as_ref(),
+                )?),
+            }
+        }
+        message.extend(symmetric.encrypt_and_hash(payload)?);
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.symmetric = symmetric;
+        self.e = e;
+        self.step += 1;
+        Ok(message)
+    }
+
+    /// Processes the peer's handshake message and returns its payload. A
+    /// message that fails to authenticate leaves the handshake unchanged.
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if self.is_finished() {
+            return Err(NoiseError::HandshakeFinished);
+        }
+        if self.is_my_turn() {
+            return Err(NoiseError::WrongTurn);
+        }
+        if message.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        let mut symmetric = self.symmetric.clone();
+        let (mut re, mut rs) = (self.re.clone(), self.rs.clone());
+        let mut rest = message;
+        for token in self.pattern.messages()[self.step] {
+            match token {
+                Token::E => {
+                    if rest.len() < PUBLIC_KEY_LEN {
+                        return Err(NoiseError::InvalidMessage);
+                    }
+                    let (e_pub, tail) = rest.split_at(PUBLIC_KEY_LEN);
+                    re = Some(decode_public(e_pub)?);
+                    symmetric.mix_hash(e_pub);
+                    rest = tail;
+                }
+                Token::S => {
+                    let len = if symmetric.has_key() {
+                        PUBLIC_KEY_LEN + TAG_LEN
+                    } else {
+                        PUBLIC_KEY_LEN
+                    };
+                    if rest.len() < len {
+                        return Err(NoiseError::InvalidMessage);
+                    }
+                    let (s_enc, tail) = rest.split_at(len);
+                    rs = Some(decode_public(&symmetric.decrypt_and_hash(s_enc)?)?);
+                    rest = tail;
+                }
+                _ => symmetric.mix_key(&token_dh(
+                    *token,
+                    self.initiator,
+                    &self.s,
+                    self.e.as_ref(),
+                    rs.as_ref(),
+                    re.as_ref(),
+                )?),
+            }
+        }
+        let payload = symmetric.decrypt_and_hash(rest)?;
+        self.symmetric = symmetric;
+        self.re = re;
+        self.rs = rs;
+        self.step += 1;
+        Ok(payload)
+    }
+
+    /// The transport for a finished handshake.
+    pub fn into_transport(self) -> Result<Transport, NoiseError> {
+        if !self.is_finished() {
+            return Err(NoiseError::HandshakeNotFinished);
+        }
+        let (c1, c2) = self.symmetric.split();
+        let (send, recv) = if self.initiator { (c1, c2) } else { (c2, c1) };
+        Ok(Transport {
+            send,
+            recv,
+            remote_static: self.rs.ok_or(NoiseError::MissingRemoteStatic)?,
+            handshake_hash: self.symmetric.hash,
+        })
+    }
+}
+
+/// An established channel. Messages must be decrypted in the order they
+/// were encrypted.
+pub struct Transport {
+    send: CipherState,
+    recv: CipherState,
+    remote_static: GE,
+    handshake_hash: [u8; HASHLEN],
+}
+
+impl Transport {
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if plaintext.len() + TAG_LEN > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.send.encrypt_with_ad(&[], plaintext)
+    }
+
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, NoiseError> {
+        if ciphertext.len() > MAX_MESSAGE_LEN {
+            return Err(NoiseError::MessageTooLong);
+        }
+        self.recv.decrypt_with_ad(&[], ciphertext)
+    }
+
+    /// The authenticated static key of the peer; compare it with the
+    /// identity the peer is expected to have before trusting the channel.
+    pub fn remote_static(&self) -> &GE {
+        &self.remote_static
+    }
+
+    /// Identical on both sides; can serve as a session id for the ceremony.
+    pub fn handshake_hash(&self) -> &[u8; HASHLEN] {
+        &self.handshake_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn run_handshake(mut initiator: Handshake, mut responder: Handshake) -> (Transport, Transport) {
+        let mut turn = 0;
+        while !initiator.is_finished() {
+            let (writer, reader) = if turn % 2 == 0 {
+                (&mut initiator, &mut responder)
+            } else {
+                (&mut responder, &mut initiator)
+            };
+            let payload = format!("message {}", turn);
+            let message = writer.write_message(payload.as_bytes()).unwrap();
+            assert_eq!(reader.read_message(&message).unwrap(), payload.as_bytes());
+            turn += 1;
+        }
+        assert!(responder.is_finished());
+        (
+            initiator.into_transport().unwrap(),
+            responder.into_transport().unwrap(),
+        )
+    }
+
+    fn check_transport(mut a: Transport, mut b: Transport) {
+        assert_eq!(a.handshake_hash(), b.handshake_hash());
+        let ct = a.encrypt(b"share for party 2").unwrap();
+        assert_eq!(b.decrypt(&ct).unwrap(), b"share for party 2".to_vec());
+        let ct = b.encrypt(b"ack").unwrap();
+        assert_eq!(a.decrypt(&ct).unwrap(), b"ack".to_vec());
+        // replaying a message fails, the nonce has moved on
+        assert_eq!(a.decrypt(&ct), Err(NoiseError::DecryptFailed));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_xx_handshake() {
+        let s_i: FE = ECScalar::new_random();
+        let s_r: FE = ECScalar::new_random();
+        let initiator =
+            Handshake::initiator(HandshakePattern::XX, s_i.clone(), None, b"tss").unwrap();
+        let responder = Handshake::responder(HandshakePattern::XX, s_r.clone(), b"tss");
+        let (a, b) = run_handshake(initiator, responder);
+        assert_eq!(a.remote_static(), &public_key(&s_r));
+        assert_eq!(b.remote_static(), &public_key(&s_i));
+        check_transport(a, b);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_ik_handshake() {
+        let s_i: FE = ECScalar::new_random();
+        let s_r: FE = ECScalar::new_random();
+        let initiator = Handshake::initiator(
+            HandshakePattern::IK,
+            s_i.clone(),
+            Some(public_key(&s_r)),
+            b"tss",
+        )
+        .unwrap();
+        let responder = Handshake::responder(HandshakePattern::IK, s_r, b"tss");
+        let (a, b) = run_handshake(initiator, responder);
+        assert_eq!(b.remote_static(), &public_key(&s_i));
+        check_transport(a, b);
+
+        // an initiator expecting another responder key cannot complete
+        let mut initiator = Handshake::initiator(
+            HandshakePattern::IK,
+            ECScalar::new_random(),
+            Some(GE::random_point()),
+            b"tss",
+        )
+        .unwrap();
+        let mut responder =
+            Handshake::responder(HandshakePattern::IK, ECScalar::new_random(), b"tss");
+        let message = initiator.write_message(b"").unwrap();
+        assert_eq!(
+            responder.read_message(&message),
+            Err(NoiseError::DecryptFailed)
+        );
+    }
+}
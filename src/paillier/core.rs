@@ -4,6 +4,7 @@ use std::borrow::{Borrow, Cow};
 
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::arithmetic::traits::*;
+use crate::curv::cryptographic_primitives::hashing::canonical::CanonicalEncode;
 use crate::paillier::traits::*;
 use crate::paillier::{
     DecryptionKey, EncryptionKey, Keypair, MinimalDecryptionKey, MinimalEncryptionKey, Paillier,
@@ -20,6 +21,30 @@ impl Keypair {
     }
 }
 
+/// `nn` is derived from `n`, so only the modulus is encoded.
+impl CanonicalEncode for EncryptionKey {
+    fn encode_canonical(&self, out: &mut Vec<u8>) {
+        self.n.encode_canonical(out);
+    }
+}
+
+impl EncryptionKey {
+    /// A ciphertext must be an element of Z*_{N^2}: below N^2 and coprime to N.
+    pub fn is_valid_ciphertext(&self, c: &BigInt) -> bool {
+        c < &self.nn && c.gcd(&self.n) == BigInt::one()
+    }
+
+    /// A plaintext must be below N.
+    pub fn is_valid_plaintext(&self, m: &BigInt) -> bool {
+        m < &self.n
+    }
+
+    /// Encryption randomness must be an element of Z*_N.
+    pub fn is_valid_randomness(&self, r: &BigInt) -> bool {
+        r < &self.n && r.gcd(&self.n) == BigInt::one()
+    }
+}
+
 impl<'p, 'q> From<(&'p BigInt, &'q BigInt)> for Keypair {
     fn from((p, q): (&'p BigInt, &'q BigInt)) -> Keypair {
         Keypair {
@@ -43,7 +68,7 @@ impl<'e> From<&'e EncryptionKey> for MinimalEncryptionKey {
     }
 }
 
-impl<'e> From<MinimalEncryptionKey> for EncryptionKey {
+impl From<MinimalEncryptionKey> for EncryptionKey {
     fn from(ek: MinimalEncryptionKey) -> Self {
         let nn = &ek.n * &ek.n;
         let n = ek.n;
@@ -98,7 +123,7 @@ impl<'e> From<&'e DecryptionKey> for MinimalDecryptionKey {
     }
 }
 
-impl<'e> From<MinimalDecryptionKey> for DecryptionKey {
+impl From<MinimalDecryptionKey> for DecryptionKey {
     fn from(dk: MinimalDecryptionKey) -> Self {
         let p = dk.p;
         let q = dk.q;
@@ -190,9 +215,9 @@ impl<'b> From<RawCiphertext<'b>> for BigInt {
 
 impl<'m, 'd> Encrypt<EncryptionKey, RawPlaintext<'m>, RawCiphertext<'d>> for Paillier {
     fn encrypt(ek: &EncryptionKey, m: RawPlaintext<'m>) -> RawCiphertext<'d> {
-        let r = Randomness::sample(&ek);
+        let r = Randomness::sample(ek);
         let rn = BigInt::mod_pow(&r.0, &ek.n, &ek.nn);
-        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1 as u16)) % &ek.nn;
+        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1_u16)) % &ek.nn;
         let c = (gm * rn) % &ek.nn;
         RawCiphertext(Cow::Owned(c))
     }
@@ -208,7 +233,7 @@ impl<'m, 'r, 'd>
         r: &'r Randomness,
     ) -> RawCiphertext<'d> {
         let rn = BigInt::mod_pow(&r.0, &ek.n, &ek.nn);
-        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1 as u16)) % &ek.nn;
+        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1_u16)) % &ek.nn;
         let c = (gm * rn) % &ek.nn;
         RawCiphertext(Cow::Owned(c))
     }
@@ -227,7 +252,7 @@ impl<'m, 'r, 'd>
         m: RawPlaintext<'m>,
         rn: &'r PrecomputedRandomness,
     ) -> RawCiphertext<'d> {
-        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1 as u16)) % &ek.nn;
+        let gm: BigInt = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1_u16)) % &ek.nn;
         let c = (gm * &rn.0) % &ek.nn;
         RawCiphertext(Cow::Owned(c))
     }
@@ -243,12 +268,12 @@ impl<'m, 'd> Encrypt<DecryptionKey, RawPlaintext<'m>, RawCiphertext<'d>> for Pai
 
         let rp = BigInt::sample_below(&dk.p);
         let rnp = BigInt::mod_pow(&rp, &dk_n, &dk_pp);
-        let gmp = (BigInt::from(1 as u16) + mp * &dk_n) % &dk_pp; // TODO[Morten] maybe there's more to get here
+        let gmp = (BigInt::from(1_u16) + mp * &dk_n) % &dk_pp; // TODO[Morten] maybe there's more to get here
         let cp = (gmp * rnp) % &dk_pp;
 
         let rq = BigInt::sample_below(&dk.q);
         let rnq = BigInt::mod_pow(&rq, &dk_n, &dk_qq);
-        let gmq = (BigInt::from(1 as u16) + mq * &dk_n) % &dk_qq; // TODO[Morten] maybe there's more to get here
+        let gmq = (BigInt::from(1_u16) + mq * &dk_n) % &dk_qq; // TODO[Morten] maybe there's more to get here
         let cq = (gmq * rnq) % &dk_qq;
 
         let c = crt_recombine(cp, cq, &dk_pp, &dk_qq, &dk_ppinv);
@@ -273,11 +298,11 @@ impl<'m, 'r, 'd>
         let (rp, rq) = crt_decompose(&r.0, &dk_pp, &dk_qq);
 
         let rnp = BigInt::mod_pow(&rp, &dk_n, &dk_pp);
-        let gmp = (BigInt::from(1 as u16) + mp * &dk_n) % &dk_pp; // TODO[Morten] maybe there's more to get here
+        let gmp = (BigInt::from(1_u16) + mp * &dk_n) % &dk_pp; // TODO[Morten] maybe there's more to get here
         let cp = (gmp * rnp) % &dk_pp;
 
         let rnq = BigInt::mod_pow(&rq, &dk_n, &dk_qq);
-        let gmq = (BigInt::from(1 as u16) + mq * &dk_n) % &dk_qq; // TODO[Morten] maybe there's more to get here
+        let gmq = (BigInt::from(1_u16) + mq * &dk_n) % &dk_qq; // TODO[Morten] maybe there's more to get here
         let cq = (gmq * rnq) % &dk_qq;
 
         let c = crt_recombine(cp, cq, &dk_pp, &dk_qq, &dk_ppinv);
@@ -300,7 +325,7 @@ impl<'m, 'r, 'd>
     ) -> RawCiphertext<'d> {
         let dk_n = &dk.q * &dk.p;
         let dk_nn = &dk_n * &dk_n;
-        let gm = (BigInt::from(1 as u16) + m.0.borrow() as &BigInt * &dk_n) % &dk_nn;
+        let gm = (BigInt::from(1_u16) + m.0.borrow() as &BigInt * &dk_n) % &dk_nn;
         let c = (gm * &rn.0) % &dk_nn;
         RawCiphertext(Cow::Owned(c))
     }
@@ -402,7 +427,7 @@ impl<'c, 'm, 'd> Add<EncryptionKey, RawCiphertext<'c>, RawPlaintext<'m>, RawCiph
 {
     fn add(ek: &EncryptionKey, c: RawCiphertext<'c>, m: RawPlaintext<'m>) -> RawCiphertext<'d> {
         let c1 = c.0.borrow() as &BigInt;
-        let c2 = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1 as u16)) % &ek.nn;
+        let c2 = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1_u16)) % &ek.nn;
         let d = (c1 * c2) % &ek.nn;
         RawCiphertext(Cow::Owned(d))
     }
@@ -412,7 +437,7 @@ impl<'c, 'm, 'd> Add<EncryptionKey, RawPlaintext<'m>, RawCiphertext<'c>, RawCiph
     for Paillier
 {
     fn add(ek: &EncryptionKey, m: RawPlaintext<'m>, c: RawCiphertext<'c>) -> RawCiphertext<'d> {
-        let c1 = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1 as u16)) % &ek.nn;
+        let c1 = (m.0.borrow() as &BigInt * &ek.n + BigInt::from(1_u16)) % &ek.nn;
         let c2 = c.0.borrow() as &BigInt;
         let d = (c1 * c2) % &ek.nn;
         RawCiphertext(Cow::Owned(d))
@@ -450,16 +475,15 @@ fn h(p: &BigInt, pp: &BigInt, n: &BigInt) -> BigInt {
     //  - g = 1 + n
 
     // compute g^{p-1} mod p^2
-    let gp = (pp + BigInt::from(1 as u16) - n.mod_floor(&pp)) % pp;
+    let gp = (pp + BigInt::from(1_u16) - n.mod_floor(pp)) % pp;
     // compute L_p(.)
     let lp = l(&gp, p);
     // compute L_p(.)^{-1}
-    let hp = BigInt::mod_inv(&lp, p);
-    hp
+    BigInt::mod_inv(&lp, p)
 }
 
 fn l(u: &BigInt, n: &BigInt) -> BigInt {
-    (u - BigInt::from(1 as u16)) / n
+    (u - BigInt::from(1_u16)) / n
 }
 
 fn crt_decompose<X, M1, M2>(x: X, m1: M1, m2: M2) -> (BigInt, BigInt)
@@ -505,8 +529,7 @@ pub fn extract_nroot(dk: &DecryptionKey, z: &BigInt) -> BigInt {
     let rp = BigInt::mod_pow(&zp, &dk_dp, &dk.p);
     let rq = BigInt::mod_pow(&zq, &dk_dq, &dk.q);
 
-    let r = crt_recombine(rp, rq, &dk.p, &dk.q, &dk_pinv);
-    r
+    crt_recombine(rp, rq, &dk.p, &dk.q, &dk_pinv)
 }
 
 #[cfg(test)]
@@ -530,7 +553,7 @@ mod tests {
     fn test_correct_encryption_decryption() {
         let (ek, dk) = test_keypair().keys();
 
-        let p = RawPlaintext::from(BigInt::from(10 as u16));
+        let p = RawPlaintext::from(BigInt::from(10_u16));
         let c = Paillier::encrypt(&ek, p.clone());
 
         let recovered_p = Paillier::decrypt(&dk, c);
@@ -542,7 +565,7 @@ mod tests {
     fn test_correct_opening() {
         let (ek, dk) = test_keypair().keys();
 
-        let c = Paillier::encrypt(&ek, RawPlaintext::from(BigInt::from(10 as u16)));
+        let c = Paillier::encrypt(&ek, RawPlaintext::from(BigInt::from(10_u16)));
         let (m, r) = Paillier::open(&dk, &c);
         let d = Paillier::encrypt_with_chosen_randomness(&ek, m, &r);
         assert_eq!(c, d);
@@ -553,14 +576,14 @@ mod tests {
     fn test_correct_addition() {
         let (ek, dk) = test_keypair().keys();
 
-        let m1 = RawPlaintext::from(BigInt::from(10 as u16));
+        let m1 = RawPlaintext::from(BigInt::from(10_u16));
         let c1 = Paillier::encrypt(&ek, m1);
-        let m2 = RawPlaintext::from(BigInt::from(20 as u16));
+        let m2 = RawPlaintext::from(BigInt::from(20_u16));
         let c2 = Paillier::encrypt(&ek, m2);
 
         let c = Paillier::add(&ek, c1, c2);
         let m = Paillier::decrypt(&dk, c);
-        assert_eq!(m, BigInt::from(30 as u16).into());
+        assert_eq!(m, BigInt::from(30_u16).into());
     }
 
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
@@ -568,28 +591,45 @@ mod tests {
     fn correct_multiplication() {
         let (ek, dk) = test_keypair().keys();
 
-        let m1 = RawPlaintext::from(BigInt::from(10 as u16));
+        let m1 = RawPlaintext::from(BigInt::from(10_u16));
         let c1 = Paillier::encrypt(&ek, m1);
-        let m2 = RawPlaintext::from(BigInt::from(20 as u16));
+        let m2 = RawPlaintext::from(BigInt::from(20_u16));
 
         let c = Paillier::mul(&ek, c1, m2);
         let m = Paillier::decrypt(&dk, c);
-        assert_eq!(m, BigInt::from(200 as u16).into());
+        assert_eq!(m, BigInt::from(200_u16).into());
     }
 
-    #[cfg(feature = "keygen")]
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_correct_keygen() {
         let (ek, dk): (EncryptionKey, _) = Paillier::keypair_with_modulus_size(2048).keys();
 
-        let m = RawPlaintext::from(BigInt::from(10));
+        let m = RawPlaintext::from(BigInt::from(10_u16));
         let c = Paillier::encrypt(&ek, m.clone()); // TODO avoid clone
 
         let recovered_m = Paillier::decrypt(&dk, c);
         assert_eq!(recovered_m, m);
     }
 
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_ciphertext_validation() {
+        let keypair = test_keypair();
+        let (ek, _) = keypair.keys();
+
+        let c = Paillier::encrypt(&ek, RawPlaintext::from(BigInt::from(10_u16)));
+        assert!(ek.is_valid_ciphertext(&c.0));
+        assert!(!ek.is_valid_ciphertext(&BigInt::from(0_u16)));
+        assert!(!ek.is_valid_ciphertext(&ek.nn));
+        assert!(!ek.is_valid_ciphertext(&keypair.p));
+        assert!(!ek.is_valid_ciphertext(&(&ek.nn + c.0.as_ref())));
+
+        assert!(ek.is_valid_randomness(&BigInt::one()));
+        assert!(!ek.is_valid_randomness(&keypair.q));
+        assert!(!ek.is_valid_plaintext(&ek.n));
+    }
+
     #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
     #[test]
     fn test_key_serialization() {
@@ -609,7 +649,7 @@ mod tests {
     fn test_failing_deserialize() {
         let illformatted = "{\"n\":\"12345abcdef\"}";
 
-        let result: Result<EncryptionKey, _> = serde_json::from_str(&illformatted);
+        let result: Result<EncryptionKey, _> = serde_json::from_str(illformatted);
         assert!(result.is_err())
     }
 }
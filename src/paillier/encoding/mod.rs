@@ -24,8 +24,8 @@ where
 {
     let mut packed = BigInt::from(components[0]);
     for component in &components[1..] {
-        packed = packed << component_bitsize;
-        packed = packed + BigInt::from(*component);
+        packed <<= component_bitsize;
+        packed += BigInt::from(*component);
     }
     packed
 }
@@ -62,12 +62,12 @@ fn test_pack() {
 
     let component_bitsize = 64;
 
-    let packed = pack(&*v, component_bitsize);
+    let packed = pack(&v, component_bitsize);
     assert_eq!(
         packed,
-        BigInt::from(1 as u32) * (BigInt::from(1 as u32) << 2 * component_bitsize)
-            + BigInt::from(2 as u32) * (BigInt::from(1 as u32) << 1 * component_bitsize)
-            + BigInt::from(3 as u32) * (BigInt::from(1 as u32) << 0 * component_bitsize)
+        BigInt::from(1_u32) * (BigInt::from(1_u32) << (2 * component_bitsize))
+            + BigInt::from(2_u32) * (BigInt::from(1_u32) << component_bitsize)
+            + BigInt::from(3_u32)
     );
 
     let unpacked: Vec<u64> = unpack(packed, component_bitsize, 3);
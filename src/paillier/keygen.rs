@@ -1,10 +1,39 @@
 //! Key generation following standard recommendations.
+//!
+//! Primes come from an incremental search. The residues of a random start
+//! modulo the small primes are computed once and updated with each step, so
+//! only candidates that no small prime divides reach the exponentiations.
+//! The search is plain data: it can test a bounded number of candidates at
+//! a time, e.g. between turns of the wasm event loop, and be saved and
+//! resumed.
 
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::arithmetic::traits::*;
 use crate::paillier::traits::*;
 use crate::paillier::{Keypair, Paillier};
-use num_traits::{One, Zero};
+use num_bigint::RandBigInt;
+use num_traits::One;
+use rand::{thread_rng, Rng};
+
+// the sieve must not reject a candidate for being one of the small primes
+const MIN_PRIME_BITS: usize = 32;
+
+// Miller-Rabin rounds by candidate size for an error probability below
+// 2^-80, Handbook of Applied Cryptography, table 4.4
+const MILLER_RABIN_ROUNDS: [(usize, usize); 11] = [
+    (1300, 2),
+    (850, 3),
+    (650, 4),
+    (550, 5),
+    (450, 6),
+    (400, 7),
+    (350, 8),
+    (300, 9),
+    (250, 12),
+    (200, 15),
+    (150, 18),
+];
+
 impl KeyGeneration<Keypair> for Paillier {
     fn keypair_with_modulus_size(bit_length: usize) -> Keypair {
         let p = BigInt::sample_prime(bit_length / 2);
@@ -13,130 +42,329 @@ impl KeyGeneration<Keypair> for Paillier {
     }
 }
 
+impl Paillier {
+    /// Like `keypair_with_modulus_size`, with the prime candidates drawn
+    /// from `rng`. A deterministic `rng` gives a deterministic key.
+    pub fn keypair_with_rng<R: Rng>(bit_length: usize, rng: &mut R) -> Keypair {
+        let p = sample_prime_with_rng(bit_length / 2, rng);
+        let q = sample_prime_with_rng(bit_length / 2, rng);
+        Keypair { p, q }
+    }
+
+    /// Like `keypair_with_rng`, with safe primes. Takes far longer.
+    pub fn safe_keypair_with_rng<R: Rng>(bit_length: usize, rng: &mut R) -> Keypair {
+        let p = sample_safe_prime_with_rng(bit_length / 2, rng);
+        let q = sample_safe_prime_with_rng(bit_length / 2, rng);
+        Keypair { p, q }
+    }
+}
+
 pub trait PrimeSampable {
     fn sample_prime(bitsize: usize) -> Self;
 }
 
 impl PrimeSampable for BigInt {
     fn sample_prime(bitsize: usize) -> Self {
-        // See Practical Considerations section inside the section 11.5 "Prime Number Generation"
-        // Applied Cryptography, Bruce Schneier.
-        let one = BigInt::one();
-        let two = &one + &one;
+        sample_prime_with_rng(bitsize, &mut thread_rng())
+    }
+}
+
+/// A prime of `bitsize` bits whose candidates are drawn from `rng`. The
+/// primality tests use their own randomness, which only matters with
+/// negligible probability.
+pub fn sample_prime_with_rng<R: Rng>(bitsize: usize, rng: &mut R) -> BigInt {
+    PrimeSearch::new(bitsize, PrimeConfig::for_bits(bitsize), rng).run(rng)
+}
+
+/// A safe prime p = 2q + 1, q prime, of `bitsize` bits, as
+/// `sample_prime_with_rng`.
+pub fn sample_safe_prime_with_rng<R: Rng>(bitsize: usize, rng: &mut R) -> BigInt {
+    let config = PrimeConfig {
+        safe: true,
+        ..PrimeConfig::for_bits(bitsize)
+    };
+    PrimeSearch::new(bitsize, config, rng).run(rng)
+}
+
+/// How a prime search tests its candidates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct PrimeConfig {
+    /// Miller-Rabin rounds for a candidate that passed the sieve and a base 2
+    /// Fermat test.
+    pub miller_rabin_rounds: usize,
+    /// Only accept safe primes p = 2q + 1 with q prime.
+    pub safe: bool,
+}
+
+impl PrimeConfig {
+    /// Enough Miller-Rabin rounds for an error probability below 2^-80 on
+    /// candidates of `bitsize` bits.
+    pub fn for_bits(bitsize: usize) -> PrimeConfig {
+        let miller_rabin_rounds = MILLER_RABIN_ROUNDS
+            .iter()
+            .find(|(bits, _)| bitsize >= *bits)
+            .map_or(27, |(_, rounds)| *rounds);
+        PrimeConfig {
+            miller_rabin_rounds,
+            safe: false,
+        }
+    }
+}
+
+/// A resumable search for a prime of `bitsize` bits.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrimeSearch {
+    bitsize: usize,
+    config: PrimeConfig,
+    candidate: BigInt,
+    // candidate mod SMALL_PRIMES[i + 1], 2 never divides a candidate
+    residues: Vec<u32>,
+    tested: u64,
+}
+
+impl PrimeSearch {
+    pub fn new<R: Rng>(bitsize: usize, config: PrimeConfig, rng: &mut R) -> PrimeSearch {
+        assert!(
+            bitsize >= MIN_PRIME_BITS,
+            "primes need at least {} bits",
+            MIN_PRIME_BITS
+        );
+        let (candidate, residues) = start(bitsize, config.safe, rng);
+        PrimeSearch {
+            bitsize,
+            config,
+            candidate,
+            residues,
+            tested: 0,
+        }
+    }
+
+    /// Candidates tested so far.
+    pub fn tested(&self) -> u64 {
+        self.tested
+    }
+
+    /// Tests up to `budget` candidates and returns the first prime. On
+    /// `None` the budget ran out, and the next call carries on from there.
+    /// `rng` is only drawn from when the search runs past `bitsize` bits
+    /// and starts over.
+    pub fn step<R: Rng>(&mut self, budget: usize, rng: &mut R) -> Option<BigInt> {
+        for _ in 0..budget {
+            if self.candidate.bits() > self.bitsize {
+                let (candidate, residues) = start(self.bitsize, self.config.safe, rng);
+                self.candidate = candidate;
+                self.residues = residues;
+            }
+            let prime = if self.passes_sieve() && self.passes_tests() {
+                Some(self.candidate.clone())
+            } else {
+                None
+            };
+            self.advance();
+            if prime.is_some() {
+                return prime;
+            }
+        }
+        None
+    }
+
+    /// Searches until it finds a prime.
+    pub fn run<R: Rng>(&mut self, rng: &mut R) -> BigInt {
         loop {
-            let mut candidate = Self::sample(bitsize);
-            // We flip the LSB to make sure tue candidate is odd.
-            //  BitManipulation::set_bit(&mut candidate, 0, true);
-            BigInt::set_bit(&mut candidate, 0, true);
-
-            // To ensure the appropiate size
-            // we set the MSB of the candidate.
-            BigInt::set_bit(&mut candidate, bitsize - 1, true);
-
-            // If no prime number is found in 500 iterations,
-            // restart the loop (re-seed).
-            // FIXME: Why 500?
-            for _ in 0..500 {
-                if is_prime(&candidate) {
-                    return candidate;
-                }
-                candidate = candidate + &two;
+            if let Some(prime) = self.step(usize::MAX, rng) {
+                return prime;
             }
         }
     }
-}
 
-// Runs the following three tests on a given `candidate` to determine
-// primality:
-//
-// 1. Divide the candidate by the first 999 small prime numbers.
-// 2. Run Fermat's Little Theorem against the candidate.
-// 3. Run five rounds of the Miller-Rabin test on the candidate.
-fn is_prime(candidate: &BigInt) -> bool {
-    // First, simple trial divide
-    for p in SMALL_PRIMES.iter() {
-        let prime = BigInt::from(*p);
-        let r = candidate % &prime;
-        if !NumberTests::is_zero(&r) {
-            continue;
+    // Odd candidates, and p = 3 mod 4 for safe primes so that q is odd
+    fn step_size(&self) -> u32 {
+        if self.config.safe {
+            4
         } else {
-            return false;
+            2
         }
     }
-    // Second, do a little Fermat test on the candidate
-    if !fermat(candidate) {
-        return false;
+
+    fn advance(&mut self) {
+        let step = self.step_size();
+        self.candidate = &self.candidate + BigInt::from(step);
+        for (residue, p) in self.residues.iter_mut().zip(SMALL_PRIMES[1..].iter()) {
+            *residue = (*residue + step) % p;
+        }
+        self.tested += 1;
     }
 
-    // Finally, do a Miller-Rabin test
-    // NIST recommendation is 5 rounds for 512 and 1024 bits. For 1536 bits, the recommendation is 4 rounds.
-    if !miller_rabin(candidate, 5) {
-        return false;
+    // For a safe prime no small prime may divide q either, and r divides
+    // q = (p - 1) / 2 exactly when p = 1 mod r.
+    fn passes_sieve(&self) -> bool {
+        let smallest = if self.config.safe { 2 } else { 1 };
+        self.residues.iter().all(|residue| *residue >= smallest)
     }
-    true
+
+    // Once q is prime, p = 2q + 1 is prime if 2^(p - 1) = 1 mod p, by
+    // Pocklington's criterion: q > sqrt(p) and gcd(2^2 - 1, p) = 1.
+    fn passes_tests(&self) -> bool {
+        let rounds = self.config.miller_rabin_rounds;
+        if !self.config.safe {
+            return fermat(&self.candidate) && probable_prime(&self.candidate, rounds);
+        }
+        let q = &self.candidate >> 1_usize;
+        fermat(&q) && fermat(&self.candidate) && probable_prime(&q, rounds)
+    }
+}
+
+/// A resumable search for a keypair with a modulus of `bit_length` bits,
+/// one `PrimeSearch` for p and then one for q.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeypairSearch {
+    bit_length: usize,
+    config: PrimeConfig,
+    search: PrimeSearch,
+    p: Option<BigInt>,
+    q: Option<BigInt>,
+    tested: u64,
+}
+
+impl KeypairSearch {
+    pub fn new<R: Rng>(bit_length: usize, safe: bool, rng: &mut R) -> KeypairSearch {
+        let config = PrimeConfig {
+            safe,
+            ..PrimeConfig::for_bits(bit_length / 2)
+        };
+        KeypairSearch {
+            bit_length,
+            config,
+            search: PrimeSearch::new(bit_length / 2, config, rng),
+            p: None,
+            q: None,
+            tested: 0,
+        }
+    }
+
+    /// Candidates tested so far, for p and q together.
+    pub fn tested(&self) -> u64 {
+        self.tested + self.search.tested()
+    }
+
+    /// Tests up to `budget` candidates, as `PrimeSearch::step`. Returns the
+    /// keypair once both primes are found, on this call or an earlier one.
+    pub fn step<R: Rng>(&mut self, budget: usize, rng: &mut R) -> Option<Keypair> {
+        let mut left = budget;
+        while self.q.is_none() && left > 0 {
+            let before = self.search.tested();
+            let prime = self.search.step(left, rng);
+            left -= (self.search.tested() - before) as usize;
+            if let Some(prime) = prime {
+                // q = p would give a modulus anyone can factor
+                if self.p.as_ref() == Some(&prime) {
+                    self.restart(rng);
+                } else if self.p.is_none() {
+                    self.p = Some(prime);
+                    self.restart(rng);
+                } else {
+                    self.q = Some(prime);
+                }
+            }
+        }
+        self.keypair()
+    }
+
+    /// The keypair, once both primes are found.
+    pub fn keypair(&self) -> Option<Keypair> {
+        Some(Keypair {
+            p: self.p.clone()?,
+            q: self.q.clone()?,
+        })
+    }
+
+    fn restart<R: Rng>(&mut self, rng: &mut R) {
+        self.tested += self.search.tested();
+        self.search = PrimeSearch::new(self.bit_length / 2, self.config, rng);
+    }
+}
+
+fn start<R: Rng>(bitsize: usize, safe: bool, rng: &mut R) -> (BigInt, Vec<u32>) {
+    // set_bit returns the changed value, it does not change its argument
+    let mut candidate = rng.gen_biguint(bitsize);
+    candidate = BigInt::set_bit(&candidate, 0, true);
+    if safe {
+        candidate = BigInt::set_bit(&candidate, 1, true);
+    }
+    // To ensure the appropiate size
+    // we set the MSB of the candidate.
+    candidate = BigInt::set_bit(&candidate, bitsize - 1, true);
+
+    let bytes = candidate.to_bytes_be();
+    let residues = SMALL_PRIMES[1..]
+        .iter()
+        .map(|p| {
+            let p = u64::from(*p);
+            bytes
+                .iter()
+                .fold(0u64, |r, byte| (r * 256 + u64::from(*byte)) % p) as u32
+        })
+        .collect();
+    (candidate, residues)
 }
 
-/// Perform test based on Fermat's little theorem
-/// This might be performed more than once, see Handbook of Applied Cryptography [Algorithm 4.9 p136]
+/// Fermat test to base 2, a cheap filter ahead of Miller-Rabin.
 fn fermat(candidate: &BigInt) -> bool {
-    let random = BigInt::sample_below(candidate);
-    let result = BigInt::mod_pow(&random, &(candidate - &BigInt::one()), candidate);
+    let one = BigInt::one();
+    BigInt::mod_pow(&BigInt::from(2u32), &(candidate - &one), candidate) == one
+}
 
-    result == BigInt::one()
+#[cfg(all(feature = "gmp", not(target_arch = "wasm32")))]
+fn probable_prime(candidate: &BigInt, rounds: usize) -> bool {
+    crate::curv::arithmetic::gmp::is_probably_prime(candidate, rounds as u32)
 }
 
-/// Perform Miller-Rabin primality test
-fn miller_rabin(candidate: &BigInt, limit: usize) -> bool {
-    // Iterations recommended for which  p < (1/2)^{80}
-    //  500 bits => 6 iterations
-    // 1000 bits => 3 iterations
-    // 2000 bits => 2 iterations
+#[cfg(not(all(feature = "gmp", not(target_arch = "wasm32"))))]
+fn probable_prime(candidate: &BigInt, rounds: usize) -> bool {
+    miller_rabin(candidate, rounds)
+}
 
-    let (s, d) = rewrite(&(candidate - &BigInt::one()));
+/// Perform Miller-Rabin primality test
+#[cfg(not(all(feature = "gmp", not(target_arch = "wasm32"))))]
+fn miller_rabin(candidate: &BigInt, rounds: usize) -> bool {
     let one = BigInt::one();
     let two = &one + &one;
+    let minus_one = candidate - &one;
+    let (s, d) = rewrite(&minus_one);
 
-    for _ in 0..limit {
-        let basis = BigInt::sample_range(&two, &(candidate - &two));
+    'rounds: for _ in 0..rounds {
+        let basis = BigInt::sample_range(&two, &minus_one);
         let mut y = BigInt::mod_pow(&basis, &d, candidate);
-
-        if y == one || y == (candidate - &one) {
+        if y == one || y == minus_one {
             continue;
-        } else {
-            let mut counter = BigInt::one();
-            while counter < (&s - &one) {
-                y = BigInt::mod_pow(&y, &two, candidate);
-                if y == one {
-                    return false;
-                } else if y == candidate - &one {
-                    break;
-                }
-                counter = counter + BigInt::one();
+        }
+        for _ in 1..s {
+            y = BigInt::mod_pow(&y, &two, candidate);
+            if y == minus_one {
+                continue 'rounds;
             }
-            return false;
         }
+        return false;
     }
     true
 }
 
 /// Rewrite a number n = 2^s * d
 /// (i.e., 2^s is the largest power of 2 that divides the candidate).
-fn rewrite(n: &BigInt) -> (BigInt, BigInt) {
+#[cfg(not(all(feature = "gmp", not(target_arch = "wasm32"))))]
+fn rewrite(n: &BigInt) -> (usize, BigInt) {
     let mut d = n.clone();
-    let mut s = BigInt::zero();
-    let one = BigInt::one();
-
+    let mut s = 0;
     while BigInt::is_even(&d) {
-        d = d >> 1_usize;
-        s = &s + &one;
+        d >>= 1_usize;
+        s += 1;
     }
-
     (s, d)
 }
 
 // BoringSSL's table.
 // https://boringssl.googlesource.com/boringssl/+/master/crypto/bn/prime.c
-#[cfg_attr(rustfmt, rustfmt_skip)]
+#[rustfmt::skip]
 static SMALL_PRIMES: [u32; 2048] = [
     2,     3,     5,     7,     11,    13,    17,    19,    23,    29,    31,
     37,    41,    43,    47,    53,    59,    61,    67,    71,    73,    79,
@@ -325,3 +553,93 @@ static SMALL_PRIMES: [u32; 2048] = [
     17609, 17623, 17627, 17657, 17659, 17669, 17681, 17683, 17707, 17713, 17729,
     17737, 17747, 17749, 17761, 17783, 17789, 17791, 17807, 17827, 17837, 17839,
     17851, 17863 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_probable_prime() {
+        // 65537 - 1 = 2^16, the whole squaring chain is needed
+        assert!(probable_prime(&BigInt::from(65537u32), 20));
+        // Carmichael number 561 = 3 * 11 * 17 fools Fermat, not Miller-Rabin
+        assert!(!probable_prime(&BigInt::from(561u32), 20));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_start() {
+        // an even start would step through even candidates only
+        let mut rng = StdRng::from_seed([11u8; 32]);
+        for _ in 0..32 {
+            let (candidate, residues) = start(256, false, &mut rng);
+            assert_eq!(candidate.bits(), 256);
+            assert_eq!(&candidate % BigInt::from(2u32), BigInt::one());
+            assert_eq!(BigInt::from(residues[0]), &candidate % BigInt::from(3u32));
+
+            let (candidate, _) = start(256, true, &mut rng);
+            assert_eq!(candidate.bits(), 256);
+            assert_eq!(&candidate % BigInt::from(4u32), BigInt::from(3u32));
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_prime_search_resumes() {
+        let config = PrimeConfig::for_bits(256);
+        let prime = PrimeSearch::new(256, config, &mut StdRng::from_seed([7u8; 32]))
+            .run(&mut StdRng::from_seed([8u8; 32]));
+        assert_eq!(prime.bits(), 256);
+        assert!(probable_prime(&prime, 40));
+
+        // one candidate at a time, saved and restored in between
+        let mut search = PrimeSearch::new(256, config, &mut StdRng::from_seed([7u8; 32]));
+        let mut rng = StdRng::from_seed([8u8; 32]);
+        let resumed = loop {
+            let saved = serde_json::to_string(&search).unwrap();
+            search = serde_json::from_str(&saved).unwrap();
+            if let Some(prime) = search.step(1, &mut rng) {
+                break prime;
+            }
+        };
+        assert_eq!(resumed, prime);
+        assert!(search.tested() > 0);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_keypair_search() {
+        let mut rng = StdRng::from_seed([10u8; 32]);
+        let mut search = KeypairSearch::new(512, false, &mut rng);
+        let mut steps = 0;
+        let keypair = loop {
+            steps += 1;
+            if let Some(keypair) = search.step(16, &mut rng) {
+                break keypair;
+            }
+        };
+        assert!(steps > 1);
+        assert!(search.tested() <= 16 * steps);
+        assert_eq!(search.step(16, &mut rng), Some(keypair.clone()));
+        assert_ne!(keypair.p, keypair.q);
+        assert_eq!(keypair.p.bits(), 256);
+        assert_eq!(keypair.q.bits(), 256);
+        assert!(probable_prime(&keypair.p, 40));
+        assert!(probable_prime(&keypair.q, 40));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_safe_prime() {
+        let p = sample_safe_prime_with_rng(128, &mut StdRng::from_seed([9u8; 32]));
+        assert_eq!(p.bits(), 128);
+        assert!(probable_prime(&p, 40));
+        assert!(probable_prime(&(&p >> 1_usize), 40));
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "paillier")]
+
 pub mod core;
 pub mod encoding;
 pub mod keygen;
@@ -27,7 +29,13 @@ pub struct Keypair {
 ///
 /// Used e.g. for serialization of `EncryptionKey`.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MinimalEncryptionKey {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub n: BigInt,
 }
 
@@ -35,9 +43,20 @@ pub struct MinimalEncryptionKey {
 ///
 /// Used e.g. for serialization of `DecryptionKey`.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MinimalDecryptionKey {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub p: BigInt,
 
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub q: BigInt,
 }
 
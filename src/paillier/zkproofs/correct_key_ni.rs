@@ -19,6 +19,7 @@ use crate::curv::arithmetic::num_bigint::{from, BigInt};
 use crate::curv::arithmetic::traits::*;
 use crate::curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
 use crate::curv::cryptographic_primitives::hashing::traits::Hash;
+use crate::impl_canonical_encode;
 use crate::paillier::{extract_nroot, DecryptionKey, EncryptionKey};
 use num_integer::Integer;
 use num_traits::{One, Zero};
@@ -35,10 +36,18 @@ const DIGEST_SIZE: usize = 256;
 #[derive(Debug)]
 pub struct CorrectKeyProofError;
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct NICorrectKeyProof {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "Vec<crate::schema::BigIntSchema>")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex::vec")]
     pub sigma_vec: Vec<BigInt>,
 }
 
+impl_canonical_encode!(NICorrectKeyProof { sigma_vec });
+
 impl NICorrectKeyProof {
     pub fn proof(dk: &DecryptionKey) -> NICorrectKeyProof {
         let dk_n = &dk.q * &dk.p;
@@ -49,35 +58,30 @@ impl NICorrectKeyProof {
         // TODO: use flatten (Morten?)
         let rho_vec = (0..M2)
             .map(|i| {
-                let seed_bn =
-                    HSha256::create_hash(&[&dk_n, &salt_bn, &BigInt::from(i.clone() as u32)]);
-                mask_generation(&key_length, &seed_bn) % &dk_n
+                let seed_bn = HSha256::create_hash(&[&dk_n, &salt_bn, &BigInt::from(i as u32)]);
+                mask_generation(key_length, &seed_bn) % &dk_n
             })
             .collect::<Vec<BigInt>>();
 
         let sigma_vec = rho_vec
             .iter()
-            .map(|i| {
-                let sigma_i = extract_nroot(dk, i);
-                sigma_i
-            })
+            .map(|i| extract_nroot(dk, i))
             .collect::<Vec<BigInt>>();
         NICorrectKeyProof { sigma_vec }
     }
 
     pub fn verify(&self, ek: &EncryptionKey) -> Result<(), CorrectKeyProofError> {
-        let key_length = ek.n.bits() as usize;
+        let key_length = ek.n.bits();
         let salt_bn = from(SALT_STRING);
 
         let rho_vec = (0..M2)
             .map(|i| {
-                let seed_bn =
-                    HSha256::create_hash(&[&ek.n, &salt_bn, &BigInt::from(i.clone() as u32)]);
+                let seed_bn = HSha256::create_hash(&[&ek.n, &salt_bn, &BigInt::from(i as u32)]);
 
                 mask_generation(&key_length, &seed_bn) % &ek.n
             })
             .collect::<Vec<BigInt>>();
-        let alpha_primorial: BigInt = str::parse(&P).unwrap();
+        let alpha_primorial: BigInt = str::parse(P).unwrap();
         let gcd_test = alpha_primorial.gcd(&ek.n);
 
         let derived_rho_vec = (0..M2)
@@ -97,7 +101,7 @@ impl NICorrectKeyProof {
 pub fn mask_generation(out_length: &usize, seed: &BigInt) -> BigInt {
     let msklen = out_length / DIGEST_SIZE + 1; // adding one sha256 is more efficient then rejection sampling (see A.4 (e) in the paper)
     let msklen_hash_vec = (0..msklen)
-        .map(|j| HSha256::create_hash(&[seed, &BigInt::from(j.clone() as u32)]))
+        .map(|j| HSha256::create_hash(&[seed, &BigInt::from(j as u32)]))
         .collect::<Vec<BigInt>>();
     msklen_hash_vec
         .iter()
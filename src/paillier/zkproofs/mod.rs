@@ -15,7 +15,8 @@
 */
 
 mod correct_key_ni;
+pub(crate) use self::correct_key_ni::mask_generation;
 pub use self::correct_key_ni::CorrectKeyProofError;
 pub use self::correct_key_ni::NICorrectKeyProof;
 mod wi_dlog_proof;
-pub use self::wi_dlog_proof::DLogStatement;
+pub use self::wi_dlog_proof::{CompositeDLogProof, DLogStatement};
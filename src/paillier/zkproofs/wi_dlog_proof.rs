@@ -16,7 +16,9 @@ use std::iter;
 
 use crate::curv::arithmetic::num_bigint::BigInt;
 use crate::curv::arithmetic::traits::*;
+use crate::impl_canonical_encode;
 use crate::num_integer::Integer;
+use crate::paillier::{KeyGeneration, Paillier};
 use serde::{Deserialize, Serialize};
 
 use crate::num_traits::One;
@@ -65,17 +67,64 @@ const SAMPLE_S: usize = 256;
 /// The prover wants to prove knowledge of a secret s given a public v = g^-{s} mod N for composite N
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CompositeDLogProof {
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub x: BigInt,
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub y: BigInt,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct DLogStatement {
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub N: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub g: BigInt,
+    #[cfg_attr(
+        feature = "json-schema",
+        schemars(with = "crate::schema::BigIntSchema")
+    )]
+    #[serde(with = "crate::curv::arithmetic::bigint_hex")]
     pub ni: BigInt,
 }
 
+impl_canonical_encode!(CompositeDLogProof { x, y });
+impl_canonical_encode!(DLogStatement { N, g, ni });
+
+impl DLogStatement {
+    /// A fresh statement and its secret: h1 of Jacobi symbol -1 modulo a
+    /// new Paillier modulus, and h2 = h1^-secret with secret below 2^SAMPLE_S.
+    /// The factors of N are dropped, whoever runs this knows only the secret.
+    pub fn generate() -> (DLogStatement, BigInt) {
+        let (ek, dk) = Paillier::keypair().keys();
+        let one = BigInt::one();
+        // per definition 3 in the paper h1 is an asymmetric basis
+        let h1 = loop {
+            let h1 = BigInt::sample_range(&one, &(&ek.n - &one));
+            if legendre_symbol(&h1, &dk.p) * legendre_symbol(&h1, &dk.q) == -1 {
+                break h1;
+            }
+        };
+        let secret = BigInt::sample_below(&BigInt::from(2u32).pow(SAMPLE_S as u32));
+        let h1_inv = BigInt::mod_inv(&h1, &ek.n);
+        let h2 = BigInt::mod_pow(&h1_inv, &secret, &ek.n);
+        let statement = DLogStatement {
+            N: ek.n,
+            g: h1,
+            ni: h2,
+        };
+        (statement, secret)
+    }
+}
+
 impl CompositeDLogProof {
     pub fn prove(statement: &DLogStatement, secret: &BigInt) -> CompositeDLogProof {
         //   pub fn prove(statement: &DLogStatement, secret: &BigInt, dk: &DecryptionKey) -> DLogProof{
@@ -0,0 +1,174 @@
+//! Conversions to and from the RustCrypto secp256k1 types.
+//!
+//! Enabled with the `k256` feature. Scalars, points and signatures convert
+//! through their canonical encodings: 32 big-endian bytes, SEC1 compressed
+//! points, and r || s. Conversions into this crate are fallible where the
+//! RustCrypto type can hold a value ours cannot: the zero scalar and the
+//! point at infinity.
+#![cfg(all(feature = "k256", feature = "gg18"))]
+
+use k256::ecdsa::Signature as K256Signature;
+use k256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use k256::elliptic_curve::PrimeField;
+use k256::{AffinePoint, EncodedPoint, FieldBytes, Scalar};
+
+use crate::curv::elliptic::curves::secp256_k1::{Secp256k1Point, Secp256k1Scalar};
+use crate::curv::elliptic::curves::traits::ECPoint;
+use crate::gg_2018::party_i::Signature;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum ConversionError {
+    /// Zero, which `Secp256k1Scalar` does not represent.
+    ZeroScalar,
+    /// The point at infinity, which `Secp256k1Point` does not represent.
+    Identity,
+    /// r or s is zero or not below the group order.
+    InvalidSignature,
+}
+
+impl From<&Secp256k1Scalar> for Scalar {
+    fn from(fe: &Secp256k1Scalar) -> Scalar {
+        // our scalars are reduced, so the repr is always canonical
+        Option::from(Scalar::from_repr(FieldBytes::from(fe.to_bytes_be()))).unwrap()
+    }
+}
+
+impl From<Secp256k1Scalar> for Scalar {
+    fn from(fe: Secp256k1Scalar) -> Scalar {
+        Scalar::from(&fe)
+    }
+}
+
+impl TryFrom<&Scalar> for Secp256k1Scalar {
+    type Error = ConversionError;
+
+    fn try_from(scalar: &Scalar) -> Result<Secp256k1Scalar, ConversionError> {
+        Secp256k1Scalar::from_bytes(&scalar.to_repr()).map_err(|_| ConversionError::ZeroScalar)
+    }
+}
+
+impl TryFrom<Scalar> for Secp256k1Scalar {
+    type Error = ConversionError;
+
+    fn try_from(scalar: Scalar) -> Result<Secp256k1Scalar, ConversionError> {
+        Secp256k1Scalar::try_from(&scalar)
+    }
+}
+
+impl From<&Secp256k1Point> for AffinePoint {
+    fn from(point: &Secp256k1Point) -> AffinePoint {
+        let encoded = EncodedPoint::from_bytes(point.to_bytes(true)).unwrap();
+        Option::from(AffinePoint::from_encoded_point(&encoded)).unwrap()
+    }
+}
+
+impl From<Secp256k1Point> for AffinePoint {
+    fn from(point: Secp256k1Point) -> AffinePoint {
+        AffinePoint::from(&point)
+    }
+}
+
+impl TryFrom<&AffinePoint> for Secp256k1Point {
+    type Error = ConversionError;
+
+    fn try_from(point: &AffinePoint) -> Result<Secp256k1Point, ConversionError> {
+        // the identity encodes as the single byte 0x00
+        Secp256k1Point::from_sec1_bytes(point.to_encoded_point(true).as_bytes())
+            .map_err(|_| ConversionError::Identity)
+    }
+}
+
+impl TryFrom<AffinePoint> for Secp256k1Point {
+    type Error = ConversionError;
+
+    fn try_from(point: AffinePoint) -> Result<Secp256k1Point, ConversionError> {
+        Secp256k1Point::try_from(&point)
+    }
+}
+
+impl From<&Signature> for K256Signature {
+    fn from(sig: &Signature) -> K256Signature {
+        // r and s are nonzero scalars, which is all from_scalars checks
+        K256Signature::from_scalars(sig.r.to_bytes_be(), sig.s.to_bytes_be()).unwrap()
+    }
+}
+
+impl From<Signature> for K256Signature {
+    fn from(sig: Signature) -> K256Signature {
+        K256Signature::from(&sig)
+    }
+}
+
+impl TryFrom<&K256Signature> for Signature {
+    type Error = ConversionError;
+
+    fn try_from(sig: &K256Signature) -> Result<Signature, ConversionError> {
+        let bytes: &[u8] = sig.as_ref();
+        let r = Secp256k1Scalar::from_bytes(&bytes[..32])
+            .map_err(|_| ConversionError::InvalidSignature)?;
+        let s = Secp256k1Scalar::from_bytes(&bytes[32..])
+            .map_err(|_| ConversionError::InvalidSignature)?;
+        Ok(Signature { r, s })
+    }
+}
+
+impl TryFrom<K256Signature> for Signature {
+    type Error = ConversionError;
+
+    fn try_from(sig: K256Signature) -> Result<Signature, ConversionError> {
+        Signature::try_from(&sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::arithmetic::num_bigint::BigInt;
+    use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+    use crate::curv::elliptic::curves::traits::*;
+    use crate::gg_2018::party_i::verify;
+    use k256::ecdsa::signature::Verifier;
+    use k256::ecdsa::VerifyingKey;
+    use sha2::{Digest, Sha256};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_round_trip() {
+        let x: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let y = &g * &x;
+
+        let k_x = Scalar::from(&x);
+        assert_eq!(Secp256k1Scalar::try_from(&k_x).unwrap(), x);
+        assert_eq!(
+            Secp256k1Scalar::try_from(Scalar::ZERO).err(),
+            Some(ConversionError::ZeroScalar)
+        );
+        let k_y = AffinePoint::from(&y);
+        assert_eq!(Secp256k1Point::try_from(&k_y).unwrap(), y);
+        assert_eq!(
+            Secp256k1Point::try_from(AffinePoint::IDENTITY).err(),
+            Some(ConversionError::Identity)
+        );
+
+        // a signature of ours verifies with k256, and comes back unchanged
+        let message = b"interop";
+        let m = BigInt::from_bytes_be(&Sha256::digest(message));
+        let k: FE = ECScalar::new_random();
+        let r: FE = ECScalar::from(&(&g * &k).x_coor().unwrap());
+        let m_fe: FE = ECScalar::from(&m);
+        let s = k.invert() * (m_fe + r.clone() * x);
+        let sig = Signature { r, s };
+        assert!(verify(&sig, &y, &m).is_ok());
+
+        let k_sig = K256Signature::from(&sig);
+        assert_eq!(Signature::try_from(&k_sig).unwrap(), sig);
+        // k256 takes low s only
+        let low_s = k_sig.normalize_s().unwrap_or(k_sig);
+        let verifying_key = VerifyingKey::from_sec1_bytes(&y.to_bytes(true)).unwrap();
+        assert!(verifying_key.verify(message, &low_s).is_ok());
+    }
+}
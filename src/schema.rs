@@ -0,0 +1,201 @@
+//! JSON Schemas for the protocol messages, for peers that are not written in Rust.
+//!
+//! Enabled with the `json-schema` feature. Message structs derive
+//! `JsonSchema`; the curve and Paillier types that serialize by hand get
+//! their schemas here. `BigInt` is a foreign type, so fields holding one
+//! point at `BigIntSchema` with `#[schemars(with = ...)]`.
+#![cfg(feature = "json-schema")]
+
+use std::collections::BTreeMap;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, Metadata, RootSchema, Schema, SchemaObject, StringValidation,
+};
+use schemars::{schema_for, JsonSchema};
+
+use crate::common::{Entry, Index, PartySignup, AEAD};
+use crate::curv::cryptographic_primitives::proofs::sigma_correct_homomorphic_elgamal_enc::HomoELGamalProof;
+use crate::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{Secp256k1Point, Secp256k1Scalar};
+use crate::gg_2018::contribution::ContributionProof;
+use crate::gg_2018::handshake::Capabilities;
+use crate::gg_2018::mta::{MessageA, MessageB};
+use crate::gg_2018::party_i::{
+    KeyGenBroadcastMessage1, KeyGenDecommitMessage1, Phase5ADecom1, Phase5Com1, Phase5Com2,
+    Phase5DDecom2, SignBroadcastPhase1, SignDecommitPhase1, Signature,
+};
+use crate::paillier::zkproofs::{DLogStatement, NICorrectKeyProof};
+use crate::paillier::{DecryptionKey, EncryptionKey, MinimalDecryptionKey, MinimalEncryptionKey};
+
+const HEX_PATTERN: &str = "^[0-9a-f]+$";
+
+fn hex_string(description: &str) -> SchemaObject {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_string()),
+            ..Default::default()
+        })),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(HEX_PATTERN.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+}
+
+/// Schema stand-in for `BigInt`, which goes on the wire as the big-endian
+/// hex of its bytes, see `curv::arithmetic::bigint_hex`.
+pub struct BigIntSchema;
+
+impl JsonSchema for BigIntSchema {
+    fn schema_name() -> String {
+        "BigInt".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let mut schema = hex_string("unsigned integer, big-endian, no leading zero bytes");
+        schema.string = Some(Box::new(StringValidation {
+            pattern: Some("^([0-9a-f]{2})+$".to_string()),
+            ..Default::default()
+        }));
+        schema.into()
+    }
+}
+
+impl JsonSchema for Secp256k1Scalar {
+    fn schema_name() -> String {
+        "Secp256k1Scalar".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        hex_string("scalar mod the group order, lowercase hex without leading zeros").into()
+    }
+}
+
+impl JsonSchema for Secp256k1Point {
+    fn schema_name() -> String {
+        "Secp256k1Point".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        let mut schema = hex_string("compressed SEC1 encoding, 33 bytes in lowercase hex");
+        if let Some(string) = schema.string.as_mut() {
+            string.pattern = Some("^0[23][0-9a-f]{64}$".to_string());
+        }
+        schema.into()
+    }
+}
+
+impl JsonSchema for EncryptionKey {
+    fn schema_name() -> String {
+        "EncryptionKey".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        MinimalEncryptionKey::json_schema(gen)
+    }
+}
+
+impl JsonSchema for DecryptionKey {
+    fn schema_name() -> String {
+        "DecryptionKey".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        MinimalDecryptionKey::json_schema(gen)
+    }
+}
+
+/// Root schemas of every message exchanged through the state manager, by type name.
+pub fn message_schemas() -> BTreeMap<&'static str, RootSchema> {
+    let mut schemas = BTreeMap::new();
+    schemas.insert("AEAD", schema_for!(AEAD));
+    schemas.insert("PartySignup", schema_for!(PartySignup));
+    schemas.insert("Index", schema_for!(Index));
+    schemas.insert("Entry", schema_for!(Entry));
+    schemas.insert("Capabilities", schema_for!(Capabilities));
+    schemas.insert(
+        "KeyGenBroadcastMessage1",
+        schema_for!(KeyGenBroadcastMessage1),
+    );
+    schemas.insert(
+        "KeyGenDecommitMessage1",
+        schema_for!(KeyGenDecommitMessage1),
+    );
+    schemas.insert("ContributionProof", schema_for!(ContributionProof));
+    schemas.insert("VerifiableSS", schema_for!(VerifiableSS));
+    schemas.insert("DLogProof", schema_for!(DLogProof));
+    schemas.insert("NICorrectKeyProof", schema_for!(NICorrectKeyProof));
+    schemas.insert("DLogStatement", schema_for!(DLogStatement));
+    schemas.insert("SignBroadcastPhase1", schema_for!(SignBroadcastPhase1));
+    schemas.insert("SignDecommitPhase1", schema_for!(SignDecommitPhase1));
+    schemas.insert("MessageA", schema_for!(MessageA));
+    schemas.insert("MessageB", schema_for!(MessageB));
+    schemas.insert("Phase5Com1", schema_for!(Phase5Com1));
+    schemas.insert("Phase5ADecom1", schema_for!(Phase5ADecom1));
+    schemas.insert("HomoELGamalProof", schema_for!(HomoELGamalProof));
+    schemas.insert("Phase5Com2", schema_for!(Phase5Com2));
+    schemas.insert("Phase5DDecom2", schema_for!(Phase5DDecom2));
+    schemas.insert("Signature", schema_for!(Signature));
+    schemas
+}
+
+/// All message schemas as one pretty-printed json object keyed by type name.
+pub fn message_schemas_json() -> String {
+    serde_json::to_string_pretty(&message_schemas()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::traits::{ECPoint, ECScalar};
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn property_names(schema: &RootSchema) -> Vec<String> {
+        schema
+            .schema
+            .object
+            .as_ref()
+            .map(|o| o.properties.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // The schema must describe what serde actually writes.
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_schema_matches_serialization() {
+        let schemas = message_schemas();
+        let g = Secp256k1Point::generator();
+        let decom = KeyGenDecommitMessage1 {
+            blind_factor: crate::curv::arithmetic::num_bigint::BigInt::from(5 as u16),
+            y_i: g,
+        };
+        let value = serde_json::to_value(&decom).unwrap();
+        let mut keys = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+        keys.sort();
+        assert_eq!(keys, property_names(&schemas["KeyGenDecommitMessage1"]));
+        assert_eq!(value["blind_factor"], "05");
+        assert_eq!(value["y_i"].as_str().unwrap().len(), 66);
+
+        let sig = Signature {
+            r: Secp256k1Scalar::new_random(),
+            s: Secp256k1Scalar::new_random(),
+        };
+        let value = serde_json::to_value(&sig).unwrap();
+        assert!(value["r"].is_string());
+        assert_eq!(
+            property_names(&schemas["Signature"]),
+            vec!["r".to_string(), "s".to_string()]
+        );
+    }
+}
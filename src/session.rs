@@ -0,0 +1,528 @@
+//! Registry of in-flight ceremonies, keyed by session id.
+//!
+//! Each keygen or signing ceremony keeps its own round state under its own
+//! id, so one instance can drive several of them at once without one
+//! ceremony's state overwriting another's. The state between rounds is a
+//! `Checkpoint`, which serializes, so a ceremony can be saved after any round
+//! and resumed after a restart.
+//!
+//! `SessionLimits` bound what callers and peers can make an instance hold:
+//! the registry refuses sessions beyond `max_sessions`, and the relay client
+//! refuses, and blames, a party whose messages are too large or too many.
+//! Party numbers are per ceremony, so each session counts its peers'
+//! messages in its own `PeerLimiter`, kept by the registry.
+#![cfg(feature = "gg18")]
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::storage::{self, Storage, StorageError, SESSIONS};
+
+pub type SessionId = String;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum SessionError {
+    UnknownSession,
+    SessionExists,
+    /// `max_sessions` ceremonies are already open.
+    TooManySessions,
+    /// A message from `party` over `max_message_bytes`.
+    MessageTooLarge {
+        party: u16,
+    },
+    /// `party` sent more messages in a window than `message_rate` allows.
+    RateLimited {
+        party: u16,
+    },
+}
+
+/// Limits on what callers and peers can make this instance hold. `None`,
+/// the default, is unlimited.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct SessionLimits {
+    /// Ceremonies open at the same time.
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+    /// Length of a single message received from a peer.
+    #[serde(default)]
+    pub max_message_bytes: Option<usize>,
+    /// Messages accepted from one party of a session.
+    #[serde(default)]
+    pub message_rate: Option<RateLimit>,
+}
+
+/// At most `messages` in any window of `window_ms` starting with a message.
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub messages: u32,
+    pub window_ms: u32,
+}
+
+/// Counts the messages received from each party of one session against
+/// `SessionLimits`.
+#[derive(Clone, Debug, Default)]
+pub struct PeerLimiter {
+    limits: SessionLimits,
+    // party -> start of its current window and the messages in it
+    windows: HashMap<u16, (f64, u32)>,
+}
+
+impl PeerLimiter {
+    pub fn new(limits: SessionLimits) -> PeerLimiter {
+        PeerLimiter {
+            limits,
+            windows: HashMap::new(),
+        }
+    }
+
+    pub fn limits(&self) -> &SessionLimits {
+        &self.limits
+    }
+
+    /// Accepts a message of `bytes` from `party` received at `now_ms`, or
+    /// refuses it. Refused messages do not count against the rate.
+    pub fn admit(&mut self, party: u16, bytes: usize, now_ms: f64) -> Result<(), SessionError> {
+        if matches!(self.limits.max_message_bytes, Some(max) if bytes > max) {
+            return Err(SessionError::MessageTooLarge { party });
+        }
+        if let Some(rate) = self.limits.message_rate {
+            let window = self.windows.entry(party).or_insert((now_ms, 0));
+            if now_ms - window.0 >= f64::from(rate.window_ms) {
+                *window = (now_ms, 0);
+            }
+            if window.1 >= rate.messages {
+                return Err(SessionError::RateLimited { party });
+            }
+            window.1 += 1;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum Ceremony {
+    Keygen,
+    Sign,
+}
+
+impl Ceremony {
+    /// Round 0 of both is the version handshake of `gg_2018::handshake`.
+    pub fn first_round(&self) -> u8 {
+        0
+    }
+
+    pub fn last_round(&self) -> u8 {
+        match self {
+            Ceremony::Keygen => 5,
+            Ceremony::Sign => 9,
+        }
+    }
+
+    /// Whether parties send `round` to each party separately rather than
+    /// broadcast it.
+    pub fn is_p2p_round(&self, round: u8) -> bool {
+        match self {
+            Ceremony::Keygen => round == 3,
+            Ceremony::Sign => round == 2,
+        }
+    }
+}
+
+/// A ceremony between two rounds: the round to run next and the json
+/// context produced by the previous one.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub ceremony: Ceremony,
+    pub next_round: u8,
+    pub context: String,
+    /// How long a round waits for the other parties' messages before it
+    /// reports them as missing; `None` waits forever.
+    #[serde(default)]
+    pub round_timeout_ms: Option<u32>,
+}
+
+impl Checkpoint {
+    pub fn new(ceremony: Ceremony, context: String) -> Checkpoint {
+        Checkpoint {
+            ceremony,
+            next_round: ceremony.first_round(),
+            context,
+            round_timeout_ms: None,
+        }
+    }
+
+    /// The checkpoint after `next_round` completed with output `context`.
+    pub fn advance(&self, context: String) -> Checkpoint {
+        Checkpoint {
+            ceremony: self.ceremony,
+            next_round: self.next_round + 1,
+            context,
+            round_timeout_ms: self.round_timeout_ms,
+        }
+    }
+
+    pub fn is_final_round(&self) -> bool {
+        self.next_round == self.ceremony.last_round()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionRegistry<T> {
+    sessions: HashMap<SessionId, T>,
+    limits: SessionLimits,
+    // each open session's count of its peers' messages
+    limiters: HashMap<SessionId, PeerLimiter>,
+}
+
+impl<T> Default for SessionRegistry<T> {
+    fn default() -> Self {
+        SessionRegistry::new()
+    }
+}
+
+impl<T> SessionRegistry<T> {
+    pub fn new() -> SessionRegistry<T> {
+        SessionRegistry {
+            sessions: HashMap::new(),
+            limits: SessionLimits::default(),
+            limiters: HashMap::new(),
+        }
+    }
+
+    /// Installs `limits` for the open sessions and those opened later,
+    /// resetting their message counts. Sessions already open stay open.
+    pub fn set_limits(&mut self, limits: SessionLimits) {
+        for limiter in self.limiters.values_mut() {
+            *limiter = PeerLimiter::new(limits.clone());
+        }
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &SessionLimits {
+        &self.limits
+    }
+
+    /// A limiter under this registry's limits for a ceremony run outside of
+    /// it, e.g. round by round through the client API, whose counts last as
+    /// long as the caller keeps it.
+    pub fn new_limiter(&self) -> PeerLimiter {
+        PeerLimiter::new(self.limits.clone())
+    }
+
+    /// The message counts of session `id`, copied out for a round that
+    /// waits on the network; `update_limiter` puts them back.
+    pub fn limiter(&self, id: &str) -> Result<PeerLimiter, SessionError> {
+        self.get(id)?;
+        Ok(self
+            .limiters
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| self.new_limiter()))
+    }
+
+    /// Stores the counts of a round of session `id`. Those of a session
+    /// closed while the round ran are dropped.
+    pub fn update_limiter(&mut self, id: &str, limiter: PeerLimiter) {
+        if self.sessions.contains_key(id) {
+            self.limiters.insert(id.to_string(), limiter);
+        }
+    }
+
+    pub fn open(&mut self, id: &str, state: T) -> Result<(), SessionError> {
+        if self.sessions.contains_key(id) {
+            return Err(SessionError::SessionExists);
+        }
+        if matches!(self.limits.max_sessions, Some(max) if self.sessions.len() >= max) {
+            return Err(SessionError::TooManySessions);
+        }
+        self.sessions.insert(id.to_string(), state);
+        self.limiters.insert(id.to_string(), self.new_limiter());
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<&T, SessionError> {
+        self.sessions.get(id).ok_or(SessionError::UnknownSession)
+    }
+
+    /// Replaces the state of a running session, returning the previous one.
+    pub fn update(&mut self, id: &str, state: T) -> Result<T, SessionError> {
+        let slot = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionError::UnknownSession)?;
+        Ok(std::mem::replace(slot, state))
+    }
+
+    pub fn close(&mut self, id: &str) -> Result<T, SessionError> {
+        self.limiters.remove(id);
+        self.sessions.remove(id).ok_or(SessionError::UnknownSession)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.sessions.contains_key(id)
+    }
+
+    /// Open session ids, sorted.
+    pub fn ids(&self) -> Vec<SessionId> {
+        let mut ids = self.sessions.keys().cloned().collect::<Vec<SessionId>>();
+        ids.sort();
+        ids
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SessionRegistry<T> {
+    /// Writes every open session to the `SESSIONS` namespace, and deletes
+    /// the ones stored there that have closed since.
+    pub fn save<S: Storage + ?Sized>(&self, storage: &mut S) -> Result<(), StorageError> {
+        self.save_in(storage, SESSIONS)
+    }
+
+    /// The sessions written by `save`, e.g. after a restart.
+    pub fn load<S: Storage + ?Sized>(storage: &S) -> Result<SessionRegistry<T>, StorageError> {
+        SessionRegistry::load_from(storage, SESSIONS)
+    }
+
+    /// `save` to another namespace, for a registry kept apart from the
+    /// instance's own, e.g. the wallet's.
+    pub fn save_in<S: Storage + ?Sized>(
+        &self,
+        storage: &mut S,
+        namespace: &str,
+    ) -> Result<(), StorageError> {
+        for id in storage.keys(namespace)? {
+            if !self.sessions.contains_key(&id) {
+                storage.delete(namespace, &id)?;
+            }
+        }
+        for (id, state) in self.sessions.iter() {
+            storage::save(storage, namespace, id, state)?;
+        }
+        Ok(())
+    }
+
+    /// The sessions written by `save_in`.
+    pub fn load_from<S: Storage + ?Sized>(
+        storage: &S,
+        namespace: &str,
+    ) -> Result<SessionRegistry<T>, StorageError> {
+        let mut registry = SessionRegistry::new();
+        for id in storage.keys(namespace)? {
+            let state = storage::load(storage, namespace, &id)?.ok_or(StorageError::Malformed)?;
+            registry.sessions.insert(id, state);
+        }
+        Ok(registry)
+    }
+}
+
+/// When each party was last heard from, e.g. by a relay message or a
+/// heartbeat. A party is live if it was heard from within `window_ms` and
+/// not suspected, for stalling or failing a check, since.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Liveness {
+    window_ms: f64,
+    last_seen: BTreeMap<u16, f64>,
+    suspected: BTreeMap<u16, f64>,
+}
+
+impl Liveness {
+    pub fn new(window_ms: f64) -> Liveness {
+        Liveness {
+            window_ms,
+            last_seen: BTreeMap::new(),
+            suspected: BTreeMap::new(),
+        }
+    }
+
+    pub fn seen(&mut self, party: u16, now_ms: f64) {
+        self.last_seen.insert(party, now_ms);
+    }
+
+    /// Counts `party` out until it is heard from again.
+    pub fn suspect(&mut self, party: u16, now_ms: f64) {
+        self.suspected.insert(party, now_ms);
+    }
+
+    pub fn is_live(&self, party: u16, now_ms: f64) -> bool {
+        match self.last_seen.get(&party) {
+            Some(seen) => {
+                now_ms - seen <= self.window_ms
+                    && !matches!(self.suspected.get(&party), Some(at) if at >= seen)
+            }
+            None => false,
+        }
+    }
+
+    /// The live parties, ascending.
+    pub fn live(&self, now_ms: f64) -> Vec<u16> {
+        self.last_seen
+            .keys()
+            .copied()
+            .filter(|party| self.is_live(*party, now_ms))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_sessions_are_isolated() {
+        let mut registry = SessionRegistry::new();
+        registry.open("a", 1).unwrap();
+        registry.open("b", 2).unwrap();
+        assert_eq!(registry.open("a", 3), Err(SessionError::SessionExists));
+
+        assert_eq!(registry.update("a", 10), Ok(1));
+        assert_eq!(registry.get("a"), Ok(&10));
+        assert_eq!(registry.get("b"), Ok(&2));
+        assert_eq!(registry.ids(), vec!["a".to_string(), "b".to_string()]);
+
+        assert_eq!(registry.close("a"), Ok(10));
+        assert_eq!(registry.get("a"), Err(SessionError::UnknownSession));
+        assert_eq!(registry.update("a", 1), Err(SessionError::UnknownSession));
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_limits() {
+        let limits = SessionLimits {
+            max_sessions: Some(1),
+            max_message_bytes: Some(100),
+            message_rate: Some(RateLimit {
+                messages: 2,
+                window_ms: 1000,
+            }),
+        };
+        let mut registry = SessionRegistry::new();
+        registry.set_limits(limits.clone());
+        registry.open("a", 1).unwrap();
+        assert_eq!(registry.open("b", 2), Err(SessionError::TooManySessions));
+        registry.close("a").unwrap();
+        registry.open("b", 2).unwrap();
+
+        let mut limiter = PeerLimiter::new(limits);
+        assert_eq!(
+            limiter.admit(2, 101, 0.0),
+            Err(SessionError::MessageTooLarge { party: 2 })
+        );
+        assert_eq!(limiter.admit(2, 100, 0.0), Ok(()));
+        assert_eq!(limiter.admit(2, 100, 10.0), Ok(()));
+        assert_eq!(
+            limiter.admit(2, 100, 20.0),
+            Err(SessionError::RateLimited { party: 2 })
+        );
+        // other parties have their own count, and the window moves on
+        assert_eq!(limiter.admit(3, 100, 20.0), Ok(()));
+        assert_eq!(limiter.admit(2, 100, 1000.0), Ok(()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_limits_are_per_session() {
+        let mut registry = SessionRegistry::new();
+        registry.set_limits(SessionLimits {
+            max_sessions: None,
+            max_message_bytes: None,
+            message_rate: Some(RateLimit {
+                messages: 1,
+                window_ms: 1000,
+            }),
+        });
+        registry.open("a", 1).unwrap();
+        registry.open("b", 2).unwrap();
+
+        // party 2 of session a uses up its budget, party 2 of session b,
+        // another peer, keeps its own
+        let mut a = registry.limiter("a").unwrap();
+        assert_eq!(a.admit(2, 10, 0.0), Ok(()));
+        registry.update_limiter("a", a);
+        let mut a = registry.limiter("a").unwrap();
+        assert_eq!(
+            a.admit(2, 10, 10.0),
+            Err(SessionError::RateLimited { party: 2 })
+        );
+        let mut b = registry.limiter("b").unwrap();
+        assert_eq!(b.admit(2, 10, 10.0), Ok(()));
+
+        // a closed session's counts go with it
+        registry.close("a").unwrap();
+        registry.update_limiter("a", a);
+        assert_eq!(
+            registry.limiter("a").err(),
+            Some(SessionError::UnknownSession)
+        );
+        registry.open("a", 1).unwrap();
+        let mut a = registry.limiter("a").unwrap();
+        assert_eq!(a.admit(2, 10, 20.0), Ok(()));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut checkpoint = Checkpoint::new(Ceremony::Keygen, "{}".to_string());
+        checkpoint.round_timeout_ms = Some(30_000);
+        assert_eq!(checkpoint.next_round, 0);
+        for _ in 0..5 {
+            checkpoint = checkpoint.advance("{}".to_string());
+        }
+        assert!(checkpoint.is_final_round());
+        assert_eq!(checkpoint.round_timeout_ms, Some(30_000));
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let restored: Checkpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, checkpoint);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_registry_storage() {
+        let mut storage = crate::storage::MemoryStorage::new();
+        let mut registry = SessionRegistry::new();
+        registry
+            .open("a", Checkpoint::new(Ceremony::Keygen, "{}".to_string()))
+            .unwrap();
+        registry
+            .open("b", Checkpoint::new(Ceremony::Sign, "{}".to_string()))
+            .unwrap();
+        registry.save(&mut storage).unwrap();
+
+        registry.close("a").unwrap();
+        registry.save(&mut storage).unwrap();
+        let restored: SessionRegistry<Checkpoint> = SessionRegistry::load(&storage).unwrap();
+        assert_eq!(restored.ids(), vec!["b".to_string()]);
+        assert_eq!(restored.get("b"), registry.get("b"));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_liveness() {
+        let mut liveness = Liveness::new(1000.0);
+        liveness.seen(1, 0.0);
+        liveness.seen(2, 500.0);
+        liveness.seen(3, 500.0);
+        assert_eq!(liveness.live(900.0), vec![1, 2, 3]);
+        assert_eq!(liveness.live(1200.0), vec![2, 3]);
+
+        liveness.suspect(3, 1200.0);
+        assert_eq!(liveness.live(1200.0), vec![2]);
+        liveness.seen(3, 1300.0);
+        assert_eq!(liveness.live(1300.0), vec![2, 3]);
+        assert!(!liveness.is_live(4, 1300.0));
+    }
+}
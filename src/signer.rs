@@ -0,0 +1,414 @@
+//! A queue of signing requests in front of the ceremony.
+//!
+//! Every integrator ends up writing the same loop: take the next request,
+//! pick t + 1 parties that are online, run the signing ceremony with them,
+//! and on failure drop the parties that stalled or were blamed and try
+//! again. `Signer` is that loop. It has no I/O of its own, like
+//! `Coordinator`: `next` hands out a request with its quorum, the caller
+//! runs the ceremony however it talks to the other parties, and reports back
+//! with `complete` or `fail`. `run_next` does all three with a
+//! `CeremonyRunner` for callers that can sign synchronously.
+//!
+//! Quorums are chosen from `session::Liveness` by a `QuorumSelector`.
+//! Parties are numbered from 1, as in `ThresholdSignature::signers`.
+#![cfg(feature = "gg18")]
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::curv::elliptic::curves::secp256_k1::GE;
+use crate::gg_2018::party_i::SigningDigest;
+use crate::gg_2018::policy::SigningRequest;
+use crate::gg_2018::threshold_signature::ThresholdSignature;
+use crate::session::Liveness;
+
+pub type RequestId = u64;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SignerError {
+    /// No request with this id is running.
+    UnknownRequest,
+    /// The signature is not over the request's digest, under the signer's
+    /// key, by the quorum it was handed out with.
+    InvalidSignature,
+    /// The ceremony failed `max_attempts` times; `blamed` are the parties
+    /// named by the last failure.
+    Failed { attempts: u32, blamed: Vec<u16> },
+}
+
+/// Picks `size` parties out of `live`, ascending, or `None` if it cannot.
+pub trait QuorumSelector {
+    fn select(&self, live: &[u16], size: usize) -> Option<Vec<u16>>;
+}
+
+impl<F> QuorumSelector for F
+where
+    F: Fn(&[u16], usize) -> Option<Vec<u16>>,
+{
+    fn select(&self, live: &[u16], size: usize) -> Option<Vec<u16>> {
+        self(live, size)
+    }
+}
+
+/// The live parties with the lowest numbers.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct LowestLive;
+
+impl QuorumSelector for LowestLive {
+    fn select(&self, live: &[u16], size: usize) -> Option<Vec<u16>> {
+        if live.len() < size {
+            return None;
+        }
+        Some(live[..size].to_vec())
+    }
+}
+
+/// The live parties in order of preference, e.g. the always-on servers
+/// before the phones. Live parties not listed come last, lowest first.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Preferred {
+    pub order: Vec<u16>,
+}
+
+impl QuorumSelector for Preferred {
+    fn select(&self, live: &[u16], size: usize) -> Option<Vec<u16>> {
+        let unlisted = live.iter().filter(|party| !self.order.contains(*party));
+        let mut quorum = self
+            .order
+            .iter()
+            .filter(|party| live.contains(*party))
+            .chain(unlisted)
+            .copied()
+            .take(size)
+            .collect::<Vec<u16>>();
+        if quorum.len() < size {
+            return None;
+        }
+        quorum.sort_unstable();
+        Some(quorum)
+    }
+}
+
+/// Why a ceremony did not produce a signature.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CeremonyFailure {
+    /// A round never completed for lack of these parties' messages.
+    Stalled { missing: Vec<u16> },
+    /// A check failed and named these parties, empty when it cannot tell.
+    Aborted { blamed: Vec<u16> },
+}
+
+impl CeremonyFailure {
+    fn parties(&self) -> &[u16] {
+        match self {
+            CeremonyFailure::Stalled { missing } => missing,
+            CeremonyFailure::Aborted { blamed } => blamed,
+        }
+    }
+}
+
+/// Runs a signing ceremony to the end, for `Signer::run_next`.
+pub trait CeremonyRunner {
+    fn sign(&mut self, job: &Job) -> Result<ThresholdSignature, CeremonyFailure>;
+}
+
+/// A request handed out for signing, with the parties to sign it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: RequestId,
+    pub request: SigningRequest,
+    /// Counted from 1, ascending.
+    pub quorum: Vec<u16>,
+    /// Earlier attempts that failed.
+    pub attempts: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Queued {
+    id: RequestId,
+    digest: SigningDigest,
+    metadata: Vec<u8>,
+    attempts: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Signer<S> {
+    public_key: GE,
+    threshold: u16,
+    selector: S,
+    liveness: Liveness,
+    max_attempts: u32,
+    queue: VecDeque<Queued>,
+    running: HashMap<RequestId, (Queued, Vec<u16>)>,
+    next_id: RequestId,
+}
+
+impl<S: QuorumSelector> Signer<S> {
+    /// Signs under `public_key` with quorums of `threshold` + 1 parties.
+    pub fn new(public_key: GE, threshold: u16, selector: S, liveness: Liveness) -> Signer<S> {
+        Signer {
+            public_key,
+            threshold,
+            selector,
+            liveness,
+            max_attempts: 3,
+            queue: VecDeque::new(),
+            running: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// How many ceremonies a request gets before it is given up, 3 by
+    /// default.
+    pub fn set_max_attempts(&mut self, max_attempts: u32) {
+        self.max_attempts = max_attempts.max(1);
+    }
+
+    pub fn liveness(&self) -> &Liveness {
+        &self.liveness
+    }
+
+    /// For the caller to record the parties it hears from.
+    pub fn liveness_mut(&mut self) -> &mut Liveness {
+        &mut self.liveness
+    }
+
+    /// Queues a request behind the ones already queued.
+    pub fn submit(&mut self, digest: SigningDigest, metadata: Vec<u8>) -> RequestId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queue.push_back(Queued {
+            id,
+            digest,
+            metadata,
+            attempts: 0,
+        });
+        id
+    }
+
+    /// Requests waiting for a quorum.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Requests handed out and not yet completed or failed.
+    pub fn running(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Hands out the request at the head of the queue with a quorum of live
+    /// parties. `None` if the queue is empty or too few parties are live;
+    /// the request then stays at the head.
+    pub fn next(&mut self, now_ms: f64) -> Option<Job> {
+        let size = usize::from(self.threshold) + 1;
+        let live = self.liveness.live(now_ms);
+        if self.queue.is_empty() {
+            return None;
+        }
+        let quorum = self.selector.select(&live, size)?;
+        let valid = quorum.len() == size
+            && quorum.windows(2).all(|w| w[0] < w[1])
+            && quorum
+                .iter()
+                .all(|party| *party >= 1 && live.contains(party));
+        if !valid {
+            return None;
+        }
+        let queued = self.queue.pop_front()?;
+        let job = Job {
+            id: queued.id,
+            request: SigningRequest {
+                digest: queued.digest.clone(),
+                public_key: self.public_key.clone(),
+                signers: quorum.iter().map(|party| usize::from(*party) - 1).collect(),
+                metadata: queued.metadata.clone(),
+            },
+            quorum: quorum.clone(),
+            attempts: queued.attempts,
+        };
+        self.running.insert(queued.id, (queued, quorum));
+        Some(job)
+    }
+
+    /// Finishes a running request. The signature is checked against it; a
+    /// signature that does not match leaves the request running.
+    pub fn complete(
+        &mut self,
+        id: RequestId,
+        signature: &ThresholdSignature,
+    ) -> Result<ThresholdSignature, SignerError> {
+        let (queued, quorum) = self.running.get(&id).ok_or(SignerError::UnknownRequest)?;
+        let matches = signature.public_key == self.public_key
+            && signature.digest == *queued.digest.as_bytes()
+            && signature.signers == *quorum;
+        if !matches {
+            return Err(SignerError::InvalidSignature);
+        }
+        let checked = ThresholdSignature::new(
+            &signature.signature(),
+            &self.public_key,
+            &queued.digest,
+            quorum,
+            signature.presignature_id.clone(),
+        )
+        .map_err(|_| SignerError::InvalidSignature)?;
+        self.running.remove(&id);
+        Ok(checked)
+    }
+
+    /// Records a failed ceremony. The parties it names are suspected until
+    /// heard from again, and the request goes back to the head of the queue
+    /// unless it used up its attempts.
+    pub fn fail(
+        &mut self,
+        id: RequestId,
+        failure: &CeremonyFailure,
+        now_ms: f64,
+    ) -> Result<(), SignerError> {
+        let (mut queued, _) = self
+            .running
+            .remove(&id)
+            .ok_or(SignerError::UnknownRequest)?;
+        for party in failure.parties() {
+            self.liveness.suspect(*party, now_ms);
+        }
+        queued.attempts += 1;
+        if queued.attempts >= self.max_attempts {
+            return Err(SignerError::Failed {
+                attempts: queued.attempts,
+                blamed: failure.parties().to_vec(),
+            });
+        }
+        self.queue.push_front(queued);
+        Ok(())
+    }
+
+    /// Takes the next request, signs it with `runner` and reports the
+    /// outcome. `None` if no request could be handed out; a failure that
+    /// requeued the request gives `Ok(None)`.
+    #[allow(clippy::type_complexity)]
+    pub fn run_next<R: CeremonyRunner>(
+        &mut self,
+        runner: &mut R,
+        now_ms: f64,
+    ) -> Option<(RequestId, Result<Option<ThresholdSignature>, SignerError>)> {
+        let job = self.next(now_ms)?;
+        let result = match runner.sign(&job) {
+            Ok(signature) => {
+                let result = self.complete(job.id, &signature);
+                if result.is_err() {
+                    // a runner that returns a bad signature is not retried
+                    self.running.remove(&job.id);
+                }
+                result.map(Some)
+            }
+            Err(failure) => self.fail(job.id, &failure, now_ms).map(|_| None),
+        };
+        Some((job.id, result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::secp256_k1::FE;
+    use crate::curv::elliptic::curves::traits::*;
+    use crate::ecdsa;
+    use crate::gg_2018::party_i::Signature;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    // signs with the whole key, and fails each quorum that includes `faulty`
+    struct LocalRunner {
+        secret: FE,
+        public_key: GE,
+        faulty: u16,
+    }
+
+    impl CeremonyRunner for LocalRunner {
+        fn sign(&mut self, job: &Job) -> Result<ThresholdSignature, CeremonyFailure> {
+            if job.quorum.contains(&self.faulty) {
+                return Err(CeremonyFailure::Aborted {
+                    blamed: vec![self.faulty],
+                });
+            }
+            let sig = ecdsa::sign(&self.secret, job.request.digest.as_bytes());
+            let sig = Signature { r: sig.r, s: sig.s };
+            Ok(ThresholdSignature::new(
+                &sig,
+                &self.public_key,
+                &job.request.digest,
+                &job.quorum,
+                None,
+            )
+            .unwrap())
+        }
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_selectors() {
+        assert_eq!(LowestLive.select(&[2, 3, 5], 2), Some(vec![2, 3]));
+        assert_eq!(LowestLive.select(&[2], 2), None);
+        let preferred = Preferred { order: vec![5, 4] };
+        assert_eq!(preferred.select(&[1, 2, 5], 2), Some(vec![1, 5]));
+        assert_eq!(preferred.select(&[1, 2, 4, 5], 2), Some(vec![4, 5]));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_queue_skips_blamed_party() {
+        let secret: FE = ECScalar::new_random();
+        let g: GE = ECPoint::generator();
+        let public_key = &g * &secret;
+        let mut runner = LocalRunner {
+            secret,
+            public_key: public_key.clone(),
+            faulty: 1,
+        };
+
+        let mut liveness = Liveness::new(10_000.0);
+        for party in 1..=3 {
+            liveness.seen(party, 0.0);
+        }
+        let mut signer = Signer::new(public_key, 1, LowestLive, liveness);
+        let first = signer.submit(SigningDigest::from_prehashed(&[1u8; 32]).unwrap(), vec![]);
+        let second = signer.submit(SigningDigest::from_prehashed(&[2u8; 32]).unwrap(), vec![]);
+
+        // party 1 is blamed and dropped, the retry goes to 2 and 3
+        assert_eq!(signer.run_next(&mut runner, 10.0), Some((first, Ok(None))));
+        assert!(!signer.liveness().is_live(1, 10.0));
+        let (id, result) = signer.run_next(&mut runner, 20.0).unwrap();
+        assert_eq!(id, first);
+        let signature = result.unwrap().unwrap();
+        assert_eq!(signature.signers, vec![2, 3]);
+        assert_eq!(signature.digest, [1u8; 32]);
+
+        // with only party 2 live there is no quorum, and the request waits
+        signer.liveness_mut().suspect(3, 30.0);
+        assert_eq!(signer.next(30.0), None);
+        assert_eq!(signer.queued(), 1);
+        signer.liveness_mut().seen(3, 40.0);
+        let job = signer.next(40.0).unwrap();
+        assert_eq!(job.id, second);
+        assert_eq!(job.request.signers, vec![1, 2]);
+
+        // a signature over another digest is refused
+        assert_eq!(
+            signer.complete(job.id, &signature),
+            Err(SignerError::InvalidSignature)
+        );
+        assert_eq!(signer.running(), 1);
+        let retries = CeremonyFailure::Stalled { missing: vec![] };
+        signer.set_max_attempts(1);
+        assert_eq!(
+            signer.fail(job.id, &retries, 50.0),
+            Err(SignerError::Failed {
+                attempts: 1,
+                blamed: vec![]
+            })
+        );
+        assert_eq!(signer.running(), 0);
+        assert_eq!(signer.queued(), 0);
+    }
+}
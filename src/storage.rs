@@ -0,0 +1,397 @@
+//! Where key stores, presignatures and session checkpoints are kept.
+//!
+//! `Storage` is a byte map split into namespaces, with get, put and delete.
+//! The key store, the presignature store, the session registry and the
+//! wallet save and load themselves through it, so a server can put all of
+//! them in its own database by implementing the trait, and a page can keep
+//! them in the browser.
+//!
+//! Three implementations come with the crate: `MemoryStorage`, for tests and
+//! short-lived processes; `FileStorage`, one file per entry under a
+//! directory, on native targets; and `IndexedDbStorage` in wasm. IndexedDB
+//! only answers asynchronously, so `IndexedDbStorage` reads the whole
+//! database into memory when it opens and writes changes back on `flush`.
+//! Nothing stored is encrypted here: store key stores in their encrypted
+//! form.
+
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_futures::JsFuture;
+#[cfg(target_arch = "wasm32")]
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode};
+
+/// The namespaces the crate's own types save under.
+pub const KEY_STORES: &str = "key-stores";
+pub const PRESIGNATURES: &str = "presignatures";
+pub const SESSIONS: &str = "sessions";
+pub const WALLET_USERS: &str = "wallet-users";
+pub const WALLET_SESSIONS: &str = "wallet-sessions";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum StorageError {
+    /// The backend failed, with its message.
+    Backend(String),
+    /// An entry that does not parse as the type it was read as.
+    Malformed,
+}
+
+pub trait Storage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Replaces any value stored under `key`.
+    fn put(&mut self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    /// Deleting a key that is not there is not an error.
+    fn delete(&mut self, namespace: &str, key: &str) -> Result<(), StorageError>;
+
+    /// The keys in `namespace`, sorted.
+    fn keys(&self, namespace: &str) -> Result<Vec<String>, StorageError>;
+}
+
+/// Reads the json value stored under `key`.
+pub fn load<T: DeserializeOwned, S: Storage + ?Sized>(
+    storage: &S,
+    namespace: &str,
+    key: &str,
+) -> Result<Option<T>, StorageError> {
+    match storage.get(namespace, key)? {
+        Some(bytes) => serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|_| StorageError::Malformed),
+        None => Ok(None),
+    }
+}
+
+/// Stores `value` as json under `key`.
+pub fn save<T: Serialize, S: Storage + ?Sized>(
+    storage: &mut S,
+    namespace: &str,
+    key: &str,
+    value: &T,
+) -> Result<(), StorageError> {
+    let bytes = serde_json::to_vec(value).map_err(|e| StorageError::Backend(e.to_string()))?;
+    storage.put(namespace, key, &bytes)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStorage {
+    entries: BTreeMap<(String, String), Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let entry = (namespace.to_string(), key.to_string());
+        Ok(self.entries.get(&entry).cloned())
+    }
+
+    fn put(&mut self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        let entry = (namespace.to_string(), key.to_string());
+        self.entries.insert(entry, value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&mut self, namespace: &str, key: &str) -> Result<(), StorageError> {
+        self.entries
+            .remove(&(namespace.to_string(), key.to_string()));
+        Ok(())
+    }
+
+    fn keys(&self, namespace: &str) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .entries
+            .keys()
+            .filter(|(ns, _)| ns == namespace)
+            .map(|(_, key)| key.clone())
+            .collect())
+    }
+}
+
+/// One file per entry, `root/hex(namespace)/hex(key)`, so that any key is a
+/// valid file name. A put writes a temporary file and renames it over the
+/// old one, so a crash leaves either value, not half of one.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStorage {
+    root: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new<P: Into<std::path::PathBuf>>(root: P) -> FileStorage {
+        FileStorage { root: root.into() }
+    }
+
+    fn dir(&self, namespace: &str) -> std::path::PathBuf {
+        self.root.join(hex::encode(namespace))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn io_error(e: std::io::Error) -> StorageError {
+    StorageError::Backend(e.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match std::fs::read(self.dir(namespace).join(hex::encode(key))) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    fn put(&mut self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        let dir = self.dir(namespace);
+        std::fs::create_dir_all(&dir).map_err(io_error)?;
+        let path = dir.join(hex::encode(key));
+        let tmp = dir.join(format!("{}.tmp", hex::encode(key)));
+        std::fs::write(&tmp, value).map_err(io_error)?;
+        std::fs::rename(&tmp, &path).map_err(io_error)
+    }
+
+    fn delete(&mut self, namespace: &str, key: &str) -> Result<(), StorageError> {
+        match std::fs::remove_file(self.dir(namespace).join(hex::encode(key))) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn keys(&self, namespace: &str) -> Result<Vec<String>, StorageError> {
+        let entries = match std::fs::read_dir(self.dir(namespace)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error(e)),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            let name = entry.map_err(io_error)?.file_name();
+            // temporary files of an interrupted put end in ".tmp", which is
+            // not hex
+            let key = name
+                .to_str()
+                .and_then(|name| hex::decode(name).ok())
+                .and_then(|key| String::from_utf8(key).ok());
+            if let Some(key) = key {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+const OBJECT_STORE: &str = "entries";
+
+// entries of every namespace share one object store, keyed
+// "namespace/key"; namespaces hold no "/"
+#[cfg(target_arch = "wasm32")]
+fn idb_key(namespace: &str, key: &str) -> String {
+    format!("{}/{}", namespace, key)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn js_error(e: JsValue) -> StorageError {
+    StorageError::Backend(format!("{:?}", e))
+}
+
+// resolves once `request` succeeds, with its result
+#[cfg(target_arch = "wasm32")]
+async fn request_result(request: &IdbRequest) -> Result<JsValue, StorageError> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        request.set_onsuccess(Some(&resolve));
+        request.set_onerror(Some(&reject));
+    });
+    JsFuture::from(promise).await.map_err(js_error)?;
+    request.result().map_err(js_error)
+}
+
+/// A `Storage` in an IndexedDB database of the page's origin. Reads are
+/// served from memory; puts and deletes apply in memory at once and reach
+/// the database on `flush`.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Debug)]
+pub struct IndexedDbStorage {
+    db: IdbDatabase,
+    cache: MemoryStorage,
+    // the latest change of each key since the last flush, None a delete
+    pending: BTreeMap<String, Option<Vec<u8>>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl IndexedDbStorage {
+    /// Opens, or creates, the database `name` and reads it into memory.
+    pub async fn open(name: &str) -> Result<IndexedDbStorage, StorageError> {
+        let factory = web_sys::window()
+            .ok_or_else(|| StorageError::Backend("no window".to_string()))?
+            .indexed_db()
+            .map_err(js_error)?
+            .ok_or_else(|| StorageError::Backend("IndexedDB unavailable".to_string()))?;
+        let open = factory.open_with_u32(name, 1).map_err(js_error)?;
+        let upgrade_request = open.clone();
+        let on_upgrade = Closure::once(move |_: JsValue| {
+            if let Ok(db) = upgrade_request.result() {
+                let _ = db
+                    .unchecked_into::<IdbDatabase>()
+                    .create_object_store(OBJECT_STORE);
+            }
+        });
+        open.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+        let db = request_result(&open).await?.unchecked_into::<IdbDatabase>();
+        open.set_onupgradeneeded(None);
+        drop(on_upgrade);
+
+        let store = db
+            .transaction_with_str(OBJECT_STORE)
+            .and_then(|transaction| transaction.object_store(OBJECT_STORE))
+            .map_err(js_error)?;
+        let keys = request_result(&store.get_all_keys().map_err(js_error)?).await?;
+        let values = request_result(&store.get_all().map_err(js_error)?).await?;
+        let keys = js_sys::Array::from(&keys);
+        let values = js_sys::Array::from(&values);
+
+        let mut cache = MemoryStorage::new();
+        for (key, value) in keys.iter().zip(values.iter()) {
+            let key = key.as_string().ok_or(StorageError::Malformed)?;
+            let (namespace, key) = key.split_once('/').ok_or(StorageError::Malformed)?;
+            let value = js_sys::Uint8Array::new(&value).to_vec();
+            cache.put(namespace, key, &value)?;
+        }
+        Ok(IndexedDbStorage {
+            db,
+            cache,
+            pending: BTreeMap::new(),
+        })
+    }
+
+    /// Changes made since the last flush.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Writes the changes made since the last flush to the database.
+    pub async fn flush(&mut self) -> Result<(), StorageError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let store = self.store(IdbTransactionMode::Readwrite)?;
+        let pending = std::mem::take(&mut self.pending);
+        for (key, value) in pending.iter() {
+            let key = JsValue::from_str(key);
+            let request = match value {
+                Some(value) => {
+                    let value = js_sys::Uint8Array::from(value.as_slice());
+                    store.put_with_key(&value, &key)
+                }
+                None => store.delete(&key),
+            }
+            .map_err(js_error)?;
+            request_result(&request).await?;
+        }
+        Ok(())
+    }
+
+    fn store(&self, mode: IdbTransactionMode) -> Result<IdbObjectStore, StorageError> {
+        self.db
+            .transaction_with_str_and_mode(OBJECT_STORE, mode)
+            .and_then(|transaction| transaction.object_store(OBJECT_STORE))
+            .map_err(js_error)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for IndexedDbStorage {
+    fn get(&self, namespace: &str, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        self.cache.get(namespace, key)
+    }
+
+    fn put(&mut self, namespace: &str, key: &str, value: &[u8]) -> Result<(), StorageError> {
+        if namespace.contains('/') {
+            return Err(StorageError::Backend("namespace with '/'".to_string()));
+        }
+        self.cache.put(namespace, key, value)?;
+        self.pending
+            .insert(idb_key(namespace, key), Some(value.to_vec()));
+        Ok(())
+    }
+
+    fn delete(&mut self, namespace: &str, key: &str) -> Result<(), StorageError> {
+        self.cache.delete(namespace, key)?;
+        self.pending.insert(idb_key(namespace, key), None);
+        Ok(())
+    }
+
+    fn keys(&self, namespace: &str) -> Result<Vec<String>, StorageError> {
+        self.cache.keys(namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    fn exercise<S: Storage>(storage: &mut S) {
+        assert_eq!(storage.get("a", "x"), Ok(None));
+        storage.put("a", "x", b"1").unwrap();
+        storage.put("a", "y/z", b"2").unwrap();
+        storage.put("b", "x", b"3").unwrap();
+        storage.put("a", "x", b"4").unwrap();
+        assert_eq!(storage.get("a", "x"), Ok(Some(b"4".to_vec())));
+        assert_eq!(storage.get("b", "x"), Ok(Some(b"3".to_vec())));
+        assert_eq!(
+            storage.keys("a"),
+            Ok(vec!["x".to_string(), "y/z".to_string()])
+        );
+
+        storage.delete("a", "x").unwrap();
+        storage.delete("a", "x").unwrap();
+        assert_eq!(storage.get("a", "x"), Ok(None));
+        assert_eq!(storage.keys("a"), Ok(vec!["y/z".to_string()]));
+
+        save(storage, "c", "json", &vec![1u8, 2]).unwrap();
+        assert_eq!(
+            load::<Vec<u8>, _>(&*storage, "c", "json"),
+            Ok(Some(vec![1, 2]))
+        );
+        assert_eq!(
+            load::<String, _>(&*storage, "c", "json"),
+            Err(StorageError::Malformed)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_memory_storage() {
+        exercise(&mut MemoryStorage::new());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_file_storage() {
+        let root = std::env::temp_dir().join(format!("tss-wasm-storage-{}", std::process::id()));
+        let mut storage = FileStorage::new(&root);
+        exercise(&mut storage);
+        // a second instance sees what the first wrote
+        let reopened = FileStorage::new(&root);
+        assert_eq!(reopened.get("b", "x"), Ok(Some(b"3".to_vec())));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
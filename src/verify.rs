@@ -0,0 +1,181 @@
+//! Checks on public data, for auditors and monitoring pages.
+//!
+//! Enabled with the `verify` feature. The group key and each party's public
+//! share follow from the VSS commitments every party broadcasts in keygen,
+//! so a page that has the commitments and the signatures can check both
+//! without any secret. Built with `default-features = false, features =
+//! ["verify"]` the crate contains no code that handles keys or shares.
+#![cfg(feature = "verify")]
+
+use crate::curv::cryptographic_primitives::secret_sharing::feldman_vss::VerifiableSS;
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::ecdsa;
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum VerifyError {
+    InvalidCommitments,
+    InvalidIndex,
+    InvalidShare,
+    InvalidSig,
+}
+
+/// Verifies `(r, s)` over `digest` under the group key `pk`.
+pub fn verify_signature(pk: &GE, digest: &[u8; 32], r: &FE, s: &FE) -> Result<(), VerifyError> {
+    ecdsa::verify(pk, digest, r, s).map_err(|_| VerifyError::InvalidSig)
+}
+
+/// The group key, the sum of the dealers' constant term commitments.
+pub fn group_public_key(vss_scheme_vec: &[VerifiableSS]) -> Result<GE, VerifyError> {
+    Ok(combine(vss_scheme_vec)?.commitments[0].clone())
+}
+
+/// The public share `x_i * G` of the party with keygen index `index`,
+/// counted from 1.
+pub fn party_public_key(vss_scheme_vec: &[VerifiableSS], index: usize) -> Result<GE, VerifyError> {
+    let combined = combine(vss_scheme_vec)?;
+    if index == 0 || index > combined.parameters.share_count {
+        return Err(VerifyError::InvalidIndex);
+    }
+    Ok(combined.get_point_commitment(index))
+}
+
+/// Checks a public share a party claims, e.g. in its key file or an
+/// attestation, against the keygen commitments.
+pub fn check_party_public_key(
+    vss_scheme_vec: &[VerifiableSS],
+    index: usize,
+    public_share: &GE,
+) -> Result<(), VerifyError> {
+    if party_public_key(vss_scheme_vec, index)? == *public_share {
+        Ok(())
+    } else {
+        Err(VerifyError::InvalidShare)
+    }
+}
+
+// one sharing per dealer; `VerifiableSS::combine` checks that they agree
+fn combine(vss_scheme_vec: &[VerifiableSS]) -> Result<VerifiableSS, VerifyError> {
+    let combined =
+        VerifiableSS::combine(vss_scheme_vec).map_err(|_| VerifyError::InvalidCommitments)?;
+    if vss_scheme_vec.len() != combined.parameters.share_count {
+        return Err(VerifyError::InvalidCommitments);
+    }
+    Ok(combined)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn parse_commitments(vss_scheme_vec: &str) -> Result<Vec<VerifiableSS>, JsValue> {
+    serde_json::from_str(vss_scheme_vec).map_err(|_| JsValue::from_str("invalid commitments"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn js_error(err: VerifyError) -> JsValue {
+    JsValue::from_str(&format!("{:?}", err))
+}
+
+/// `true` if `r` and `s`, in hex as output by signing, sign the 32 byte
+/// hex `digest` under the SEC1 hex `public_key`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn audit_verify_signature(
+    public_key: String,
+    digest: String,
+    r: String,
+    s: String,
+) -> Result<bool, JsValue> {
+    use crate::curv::arithmetic::num_bigint::BigInt;
+    use crate::curv::arithmetic::traits::Converter;
+
+    let pk = GE::from_hex(&public_key).map_err(|_| JsValue::from_str("invalid public key"))?;
+    let digest: [u8; 32] = hex::decode(digest.strip_prefix("0x").unwrap_or(&digest))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| JsValue::from_str("invalid digest"))?;
+    let scalar = |hex: &str| {
+        BigInt::try_from_hex(hex)
+            .and_then(|n| FE::from_big_int_checked(&n).ok())
+            .ok_or_else(|| JsValue::from_str("invalid signature"))
+    };
+    Ok(verify_signature(&pk, &digest, &scalar(&r)?, &scalar(&s)?).is_ok())
+}
+
+/// The group key in SEC1 hex, from the JSON array of every party's keygen
+/// `VerifiableSS`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn audit_group_public_key(vss_scheme_vec: String) -> Result<String, JsValue> {
+    let vss_scheme_vec = parse_commitments(&vss_scheme_vec)?;
+    Ok(group_public_key(&vss_scheme_vec)
+        .map_err(js_error)?
+        .to_hex())
+}
+
+/// The public share of party `index` in SEC1 hex, as `audit_group_public_key`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn audit_party_public_key(vss_scheme_vec: String, index: usize) -> Result<String, JsValue> {
+    let vss_scheme_vec = parse_commitments(&vss_scheme_vec)?;
+    Ok(party_public_key(&vss_scheme_vec, index)
+        .map_err(js_error)?
+        .to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curv::elliptic::curves::traits::*;
+    use crate::fixtures::dealings;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_group_public_key() {
+        let g: GE = ECPoint::generator();
+        let (secrets, vss_scheme_vec, _) = dealings(1, 3);
+        let y = secrets.iter().fold(FE::zero(), |acc, u| acc + u);
+        assert_eq!(group_public_key(&vss_scheme_vec), Ok(&g * &y));
+        assert_eq!(
+            group_public_key(&vss_scheme_vec[..2]),
+            Err(VerifyError::InvalidCommitments)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_party_public_key() {
+        let g: GE = ECPoint::generator();
+        let (_, vss_scheme_vec, shares) = dealings(1, 3);
+        for index in 1..=3 {
+            let x_i = shares.iter().fold(FE::zero(), |acc, s| acc + &s[index - 1]);
+            let public_share = &g * &x_i;
+            assert_eq!(
+                party_public_key(&vss_scheme_vec, index),
+                Ok(public_share.clone())
+            );
+            assert_eq!(
+                check_party_public_key(&vss_scheme_vec, index, &public_share),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            party_public_key(&vss_scheme_vec, 4),
+            Err(VerifyError::InvalidIndex)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_wrong_party_public_key() {
+        let g: GE = ECPoint::generator();
+        let (_, vss_scheme_vec, _) = dealings(1, 3);
+        assert_eq!(
+            check_party_public_key(&vss_scheme_vec, 1, &g),
+            Err(VerifyError::InvalidShare)
+        );
+    }
+}
@@ -0,0 +1,1020 @@
+//! Per-end-user key management for wallet-as-a-service deployments.
+//!
+//! A single signer deployment usually holds one share for each of many end
+//! users. `Wallet` keeps those shares apart, keyed by user id, together with
+//! the policies attached to each user and the schedule on which each share
+//! should be refreshed. The keygen and signing ceremonies run for a user
+//! are sessions of the wallet's own `SessionRegistry`, each tied to its user:
+//! a finished keygen becomes that user's share, a new one or a refresh, and
+//! no session of one user signs with or replaces another user's share. The
+//! rounds themselves run as in the protocol APIs, with the `Checkpoint` of
+//! each session advanced between them. Every share is checked with
+//! `LocalKey::validate` on the way in, and the wallet saves and loads its
+//! users and sessions through a `Storage`.
+//!
+//! `ThresholdWallet` is what one party of one group key works with: the
+//! share, child keys along non-hardened BIP-32 paths, the presignatures
+//! computed ahead of time, and the addresses and signed transactions of the
+//! chains the crate knows. Every party derives the same child key from the
+//! same path and chain code, with no round, so a derived wallet's key store
+//! is signed with as any other. The wasm bindings mirror it as the
+//! `gg18_wallet_*` exports over the wallet as json. Presignatures are not
+//! part of that json: they are kept in a `Storage` only, so that a copy of
+//! the wallet can not bring back one that has been consumed.
+#![cfg(feature = "gg18")]
+
+use std::collections::HashMap;
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::address::{self, BitcoinNetwork};
+use crate::common::{aes_encrypt, AEAD, AES_KEY_BYTES_LEN};
+use crate::curv::elliptic::curves::secp256_k1::{FE, GE};
+use crate::curv::elliptic::curves::traits::ECPoint;
+#[cfg(feature = "ethereum")]
+use crate::ethereum::{EthereumError, Transaction};
+use crate::gg_2018::party_i::{LocalKey, LocalSignature, SharedKeys, SigningDigest};
+use crate::gg_2018::presign::{Presignature, PresignatureError, PresignatureStore};
+#[cfg(feature = "ethereum")]
+use crate::gg_2018::threshold_signature::ThresholdSignature;
+use crate::session::{Ceremony, Checkpoint, SessionError, SessionId, SessionRegistry};
+use crate::storage::{self, Storage, StorageError, WALLET_SESSIONS, WALLET_USERS};
+
+pub type UserId = String;
+
+/// Child indices from this one on are hardened, which needs the private key
+/// no party holds.
+pub const HARDENED: u32 = 0x8000_0000;
+
+#[derive(Copy, PartialEq, Eq, Clone, Debug)]
+pub enum WalletError {
+    UnknownUser,
+    UserExists,
+    InvalidKeyStore,
+    InvalidExportKey,
+    InvalidPath,
+    HardenedPath,
+    /// A derived tweak out of range, which happens with probability below
+    /// 2^-127.
+    InvalidTweak,
+    /// A presignature or signature for another key than the wallet's, or a
+    /// signing context for another share than the user's.
+    WrongKey,
+    /// A signing context that does not parse.
+    InvalidContext,
+    Presignature(PresignatureError),
+    /// The storage the presignatures are kept in failed, or holds an entry
+    /// that does not parse.
+    Storage,
+    Session(SessionError),
+    /// A keygen session finished as a signing one, or the other way round.
+    WrongCeremony,
+    #[cfg(feature = "ethereum")]
+    Ethereum(EthereumError),
+}
+
+/// An opaque policy document attached to a user, evaluated by the integrator.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PolicyAttachment {
+    pub id: String,
+    pub document: String,
+}
+
+/// Times are seconds since the unix epoch, supplied by the caller since wasm
+/// has no clock of its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct RefreshSchedule {
+    pub interval: u64,
+    pub last_refreshed: u64,
+}
+
+impl RefreshSchedule {
+    pub fn new(interval: u64, now: u64) -> RefreshSchedule {
+        RefreshSchedule {
+            interval,
+            last_refreshed: now,
+        }
+    }
+
+    pub fn next_refresh(&self) -> u64 {
+        self.last_refreshed.saturating_add(self.interval)
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        now >= self.next_refresh()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UserEntry {
+    pub key: LocalKey,
+    pub policies: Vec<PolicyAttachment>,
+    pub refresh: Option<RefreshSchedule>,
+}
+
+/// A ceremony the wallet runs for `user`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct UserSession {
+    pub user: UserId,
+    pub checkpoint: Checkpoint,
+}
+
+// The share a signing client context signs with, the rest of it left out.
+#[derive(Deserialize)]
+struct SignContextShare {
+    y_sum: GE,
+    shared_keys: SharedKeys,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Wallet {
+    users: HashMap<UserId, UserEntry>,
+    sessions: SessionRegistry<UserSession>,
+}
+
+impl Wallet {
+    pub fn new() -> Wallet {
+        Wallet {
+            users: HashMap::new(),
+            sessions: SessionRegistry::new(),
+        }
+    }
+
+    /// Registers the key store produced by a finished keygen ceremony for `user`.
+    pub fn create_user_key(&mut self, user: &str, key_store: &str) -> Result<(), WalletError> {
+        let key = LocalKey::from_key_store(key_store).map_err(|_| WalletError::InvalidKeyStore)?;
+        self.insert_user_key(user, key)
+    }
+
+    pub fn insert_user_key(&mut self, user: &str, key: LocalKey) -> Result<(), WalletError> {
+        check_key(&key)?;
+        if self.users.contains_key(user) {
+            return Err(WalletError::UserExists);
+        }
+        self.users.insert(
+            user.to_string(),
+            UserEntry {
+                key,
+                policies: Vec::new(),
+                refresh: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the user along with the sessions still open for them.
+    pub fn remove_user(&mut self, user: &str) -> Result<UserEntry, WalletError> {
+        let entry = self.users.remove(user).ok_or(WalletError::UnknownUser)?;
+        for id in self.user_sessions(user) {
+            self.sessions.close(&id).map_err(WalletError::Session)?;
+        }
+        Ok(entry)
+    }
+
+    pub fn users(&self) -> Vec<UserId> {
+        let mut users = self.users.keys().cloned().collect::<Vec<UserId>>();
+        users.sort();
+        users
+    }
+
+    pub fn user(&self, user: &str) -> Result<&UserEntry, WalletError> {
+        self.users.get(user).ok_or(WalletError::UnknownUser)
+    }
+
+    pub fn key(&self, user: &str) -> Result<&LocalKey, WalletError> {
+        self.user(user).map(|entry| &entry.key)
+    }
+
+    /// Replaces the user's share, e.g. with the output of a refresh ceremony.
+    pub fn replace_user_key(&mut self, user: &str, key: LocalKey) -> Result<(), WalletError> {
+        check_key(&key)?;
+        let entry = self.users.get_mut(user).ok_or(WalletError::UnknownUser)?;
+        entry.key = key;
+        Ok(())
+    }
+
+    /// Attaches a policy to the user, replacing any policy with the same id.
+    pub fn attach_policy(
+        &mut self,
+        user: &str,
+        policy: PolicyAttachment,
+    ) -> Result<(), WalletError> {
+        let entry = self.users.get_mut(user).ok_or(WalletError::UnknownUser)?;
+        entry.policies.retain(|p| p.id != policy.id);
+        entry.policies.push(policy);
+        Ok(())
+    }
+
+    pub fn detach_policy(&mut self, user: &str, policy_id: &str) -> Result<(), WalletError> {
+        let entry = self.users.get_mut(user).ok_or(WalletError::UnknownUser)?;
+        entry.policies.retain(|p| p.id != policy_id);
+        Ok(())
+    }
+
+    pub fn policies(&self, user: &str) -> Result<&[PolicyAttachment], WalletError> {
+        self.user(user).map(|entry| &entry.policies[..])
+    }
+
+    pub fn schedule_refresh(
+        &mut self,
+        user: &str,
+        interval: u64,
+        now: u64,
+    ) -> Result<(), WalletError> {
+        let entry = self.users.get_mut(user).ok_or(WalletError::UnknownUser)?;
+        entry.refresh = Some(RefreshSchedule::new(interval, now));
+        Ok(())
+    }
+
+    /// Puts every user on the same refresh interval.
+    pub fn schedule_refresh_all(&mut self, interval: u64, now: u64) {
+        for entry in self.users.values_mut() {
+            entry.refresh = Some(RefreshSchedule::new(interval, now));
+        }
+    }
+
+    /// Users whose refresh is due at `now`, in id order.
+    pub fn due_for_refresh(&self, now: u64) -> Vec<UserId> {
+        let mut due = self
+            .users
+            .iter()
+            .filter(|(_, entry)| entry.refresh.is_some_and(|r| r.is_due(now)))
+            .map(|(user, _)| user.clone())
+            .collect::<Vec<UserId>>();
+        due.sort();
+        due
+    }
+
+    pub fn mark_refreshed(&mut self, user: &str, now: u64) -> Result<(), WalletError> {
+        let entry = self.users.get_mut(user).ok_or(WalletError::UnknownUser)?;
+        if let Some(schedule) = entry.refresh.as_mut() {
+            schedule.last_refreshed = now;
+        }
+        Ok(())
+    }
+
+    /// Exports the user's share in the wasm key store format.
+    pub fn export(&self, user: &str) -> Result<String, WalletError> {
+        self.key(user).map(|key| key.to_key_store())
+    }
+
+    /// Exports the user's share encrypted under a 32 byte AES-GCM key.
+    pub fn export_encrypted(&self, user: &str, key: &[u8]) -> Result<AEAD, WalletError> {
+        if key.len() != AES_KEY_BYTES_LEN {
+            return Err(WalletError::InvalidExportKey);
+        }
+        let key_store = self.export(user)?;
+        Ok(aes_encrypt(key, key_store.as_bytes()))
+    }
+
+    /// Opens a keygen session whose key store becomes `user`'s share when
+    /// `finish_keygen` closes it: a new user's, or a refresh of an existing
+    /// one. `context` is the keygen client context to start from.
+    pub fn begin_keygen(
+        &mut self,
+        user: &str,
+        session_id: &str,
+        context: String,
+    ) -> Result<(), WalletError> {
+        self.open_session(user, session_id, Checkpoint::new(Ceremony::Keygen, context))
+    }
+
+    /// Opens a signing session for `user`, whose context the caller made
+    /// from `export(user)`. Fails with `WrongKey` if the context holds
+    /// another share than the user's, e.g. another user's.
+    pub fn begin_sign(
+        &mut self,
+        user: &str,
+        session_id: &str,
+        context: String,
+    ) -> Result<(), WalletError> {
+        let key = self.key(user)?;
+        let share: SignContextShare =
+            serde_json::from_str(&context).map_err(|_| WalletError::InvalidContext)?;
+        if share.y_sum != key.y_sum
+            || share.shared_keys.y != key.shared_keys.y
+            || share.shared_keys.x_i != key.shared_keys.x_i
+        {
+            return Err(WalletError::WrongKey);
+        }
+        self.open_session(user, session_id, Checkpoint::new(Ceremony::Sign, context))
+    }
+
+    fn open_session(
+        &mut self,
+        user: &str,
+        session_id: &str,
+        checkpoint: Checkpoint,
+    ) -> Result<(), WalletError> {
+        let session = UserSession {
+            user: user.to_string(),
+            checkpoint,
+        };
+        self.sessions
+            .open(session_id, session)
+            .map_err(WalletError::Session)
+    }
+
+    pub fn session(&self, session_id: &str) -> Result<&UserSession, WalletError> {
+        self.sessions.get(session_id).map_err(WalletError::Session)
+    }
+
+    /// Ids of the sessions open for `user`, sorted.
+    pub fn user_sessions(&self, user: &str) -> Vec<SessionId> {
+        self.sessions
+            .ids()
+            .into_iter()
+            .filter(|id| matches!(self.sessions.get(id), Ok(s) if s.user == user))
+            .collect()
+    }
+
+    /// Records that the session's next round completed with output `context`.
+    pub fn advance_session(
+        &mut self,
+        session_id: &str,
+        context: String,
+    ) -> Result<(), WalletError> {
+        let session = self.session(session_id)?;
+        let advanced = UserSession {
+            user: session.user.clone(),
+            checkpoint: session.checkpoint.advance(context),
+        };
+        self.sessions
+            .update(session_id, advanced)
+            .map(|_| ())
+            .map_err(WalletError::Session)
+    }
+
+    /// Closes a keygen session with the key store its last round returned,
+    /// and stores the key as the session's user's share. Returns the user.
+    pub fn finish_keygen(
+        &mut self,
+        session_id: &str,
+        key_store: &str,
+    ) -> Result<UserId, WalletError> {
+        let user = self.session_of(session_id, Ceremony::Keygen)?;
+        let key = LocalKey::from_key_store(key_store).map_err(|_| WalletError::InvalidKeyStore)?;
+        check_key(&key)?;
+        self.sessions
+            .close(session_id)
+            .map_err(WalletError::Session)?;
+        match self.users.get_mut(&user) {
+            Some(entry) => entry.key = key,
+            None => {
+                let entry = UserEntry {
+                    key,
+                    policies: Vec::new(),
+                    refresh: None,
+                };
+                self.users.insert(user.clone(), entry);
+            }
+        }
+        Ok(user)
+    }
+
+    /// Closes a signing session after its last round. Returns the user.
+    pub fn finish_sign(&mut self, session_id: &str) -> Result<UserId, WalletError> {
+        let user = self.session_of(session_id, Ceremony::Sign)?;
+        self.sessions
+            .close(session_id)
+            .map_err(WalletError::Session)?;
+        Ok(user)
+    }
+
+    /// Drops a session, e.g. after its ceremony was aborted.
+    pub fn abort_session(&mut self, session_id: &str) -> Result<UserSession, WalletError> {
+        self.sessions
+            .close(session_id)
+            .map_err(WalletError::Session)
+    }
+
+    fn session_of(&self, session_id: &str, ceremony: Ceremony) -> Result<UserId, WalletError> {
+        let session = self.session(session_id)?;
+        if session.checkpoint.ceremony != ceremony {
+            return Err(WalletError::WrongCeremony);
+        }
+        Ok(session.user.clone())
+    }
+
+    /// Writes every user to the `WALLET_USERS` namespace and every open
+    /// session to `WALLET_SESSIONS`, and deletes the ones stored there that
+    /// have been removed or closed since. Shares and session contexts are
+    /// stored as they are: give the wallet a storage that encrypts.
+    pub fn save<S: Storage + ?Sized>(&self, storage: &mut S) -> Result<(), StorageError> {
+        for user in storage.keys(WALLET_USERS)? {
+            if !self.users.contains_key(&user) {
+                storage.delete(WALLET_USERS, &user)?;
+            }
+        }
+        for (user, entry) in self.users.iter() {
+            storage::save(storage, WALLET_USERS, user, entry)?;
+        }
+        self.sessions.save_in(storage, WALLET_SESSIONS)
+    }
+
+    /// The users and sessions written by `save`. A stored share that fails
+    /// `LocalKey::validate` is `Malformed`.
+    pub fn load<S: Storage + ?Sized>(storage: &S) -> Result<Wallet, StorageError> {
+        let mut wallet = Wallet::new();
+        for user in storage.keys(WALLET_USERS)? {
+            let entry: UserEntry =
+                storage::load(storage, WALLET_USERS, &user)?.ok_or(StorageError::Malformed)?;
+            check_key(&entry.key).map_err(|_| StorageError::Malformed)?;
+            wallet.users.insert(user, entry);
+        }
+        wallet.sessions = SessionRegistry::load_from(storage, WALLET_SESSIONS)?;
+        Ok(wallet)
+    }
+}
+
+fn check_key(key: &LocalKey) -> Result<(), WalletError> {
+    key.validate().map_err(|_| WalletError::InvalidKeyStore)
+}
+
+/// One party's wallet over one group key, or a key derived from it. Its key
+/// passes `LocalKey::validate`, also when deserialized.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedThresholdWallet")]
+pub struct ThresholdWallet {
+    key: LocalKey,
+    chain_code: [u8; 32],
+    /// The path from the keygen's key, empty for that key itself.
+    path: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct UncheckedThresholdWallet {
+    key: LocalKey,
+    chain_code: [u8; 32],
+    path: Vec<u32>,
+}
+
+impl TryFrom<UncheckedThresholdWallet> for ThresholdWallet {
+    type Error = &'static str;
+
+    fn try_from(wallet: UncheckedThresholdWallet) -> Result<Self, Self::Error> {
+        check_key(&wallet.key).map_err(|_| "wallet key failed its health check")?;
+        Ok(ThresholdWallet {
+            key: wallet.key,
+            chain_code: wallet.chain_code,
+            path: wallet.path,
+        })
+    }
+}
+
+impl ThresholdWallet {
+    /// A wallet over the key of a finished keygen, with a chain code derived
+    /// from the group key so that every party has the same one. A key that
+    /// fails `LocalKey::validate` is `InvalidKeyStore`.
+    pub fn new(key: LocalKey) -> Result<ThresholdWallet, WalletError> {
+        check_key(&key)?;
+        let mut hasher = Sha256::new();
+        hasher.update(b"tss-wasm chain code");
+        hasher.update(key.y_sum.to_bytes(true));
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&hasher.finalize());
+        Ok(ThresholdWallet {
+            key,
+            chain_code,
+            path: Vec::new(),
+        })
+    }
+
+    pub fn from_key_store(key_store: &str) -> Result<ThresholdWallet, WalletError> {
+        let key = LocalKey::from_key_store(key_store).map_err(|_| WalletError::InvalidKeyStore)?;
+        ThresholdWallet::new(key)
+    }
+
+    /// Replaces the chain code, e.g. with one published alongside the group
+    /// key as part of an xpub. All parties set the same one.
+    pub fn with_chain_code(mut self, chain_code: [u8; 32]) -> ThresholdWallet {
+        self.chain_code = chain_code;
+        self
+    }
+
+    pub fn key(&self) -> &LocalKey {
+        &self.key
+    }
+
+    /// The key store to start a signing ceremony with.
+    pub fn to_key_store(&self) -> String {
+        self.key.to_key_store()
+    }
+
+    pub fn public_key(&self) -> &GE {
+        &self.key.y_sum
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn path(&self) -> &[u32] {
+        &self.path
+    }
+
+    /// The wallet of the child key at `path` below this one. The child's
+    /// presignatures are stored apart from the parent's, which do not sign
+    /// for it.
+    pub fn derive(&self, path: &[u32]) -> Result<ThresholdWallet, WalletError> {
+        let mut key = self.key.clone();
+        let mut chain_code = self.chain_code;
+        for index in path {
+            let (tweak, child_chain_code) = child_tweak(&key.y_sum, &chain_code, *index)?;
+            key = key.tweak_add(&tweak);
+            chain_code = child_chain_code;
+        }
+        Ok(ThresholdWallet {
+            key,
+            chain_code,
+            path: self.path.iter().chain(path).cloned().collect(),
+        })
+    }
+
+    /// `derive` with a path such as `m/44/60/0/0/0`.
+    pub fn derive_path(&self, path: &str) -> Result<ThresholdWallet, WalletError> {
+        self.derive(&parse_path(path)?)
+    }
+
+    /// The name the wallet's `PresignatureStore` is saved under: the
+    /// compressed public key in hex.
+    pub fn presignature_store_name(&self) -> String {
+        hex::encode(self.public_key().to_bytes(true))
+    }
+
+    /// The wallet's presignatures as saved in `storage`, empty if none were.
+    pub fn presignatures<S: Storage + ?Sized>(
+        &self,
+        storage: &S,
+    ) -> Result<PresignatureStore, WalletError> {
+        PresignatureStore::load(storage, &self.presignature_store_name())
+            .map(Option::unwrap_or_default)
+            .map_err(|_| WalletError::Storage)
+    }
+
+    /// Adds a presignature computed for this wallet's key to those saved in
+    /// `storage` and returns its id.
+    pub fn add_presignature<S: Storage + ?Sized>(
+        &self,
+        storage: &mut S,
+        presignature: Presignature,
+    ) -> Result<String, WalletError> {
+        if &presignature.y != self.public_key() {
+            return Err(WalletError::WrongKey);
+        }
+        let mut presignatures = self.presignatures(storage)?;
+        let id = presignatures
+            .insert(presignature)
+            .map_err(WalletError::Presignature)?;
+        presignatures
+            .save(storage, &self.presignature_store_name())
+            .map_err(|_| WalletError::Storage)?;
+        Ok(id)
+    }
+
+    pub fn presignature_ids<S: Storage + ?Sized>(
+        &self,
+        storage: &S,
+    ) -> Result<Vec<String>, WalletError> {
+        Ok(self.presignatures(storage)?.available_ids())
+    }
+
+    /// Consumes the presignature in `storage`, saves the store there, and
+    /// only then computes this party's local signature over `digest`, see
+    /// `PresignatureStore::sign`.
+    pub fn sign_presigned<S: Storage + ?Sized>(
+        &self,
+        storage: &mut S,
+        id: &str,
+        digest: &SigningDigest,
+    ) -> Result<LocalSignature, WalletError> {
+        let mut presignatures = self.presignatures(storage)?;
+        presignatures
+            .sign(storage, &self.presignature_store_name(), id, digest)
+            .map_err(WalletError::Presignature)
+    }
+
+    pub fn ethereum_address(&self) -> String {
+        address::ethereum_address(self.public_key())
+    }
+
+    pub fn bitcoin_address(&self, network: BitcoinNetwork) -> String {
+        address::bitcoin_p2wpkh_address(self.public_key(), network)
+    }
+
+    pub fn cosmos_address(&self, hrp: &str) -> String {
+        address::cosmos_address(self.public_key(), hrp)
+    }
+
+    /// `transaction` signed with `signature`, from a ceremony over
+    /// `transaction.signing_digest()` with this wallet's key store.
+    #[cfg(feature = "ethereum")]
+    pub fn ethereum_raw_transaction(
+        &self,
+        transaction: &Transaction,
+        signature: &ThresholdSignature,
+    ) -> Result<Vec<u8>, WalletError> {
+        if &signature.public_key != self.public_key() {
+            return Err(WalletError::WrongKey);
+        }
+        transaction
+            .encode_signed(signature)
+            .map_err(WalletError::Ethereum)
+    }
+}
+
+/// Parses a derivation path, `m/` and all, into child indices. Hardened
+/// steps are refused.
+pub fn parse_path(path: &str) -> Result<Vec<u32>, WalletError> {
+    let mut indices = Vec::new();
+    for (i, part) in path.split('/').enumerate() {
+        if i == 0 && part == "m" {
+            continue;
+        }
+        if part.ends_with('\'') || part.ends_with('h') {
+            return Err(WalletError::HardenedPath);
+        }
+        let index: u32 = part.parse().map_err(|_| WalletError::InvalidPath)?;
+        if index >= HARDENED {
+            return Err(WalletError::HardenedPath);
+        }
+        indices.push(index);
+    }
+    Ok(indices)
+}
+
+// BIP-32 CKDpub: the tweak to add to the parent key, and the child's chain
+// code
+fn child_tweak(
+    parent: &GE,
+    chain_code: &[u8; 32],
+    index: u32,
+) -> Result<(FE, [u8; 32]), WalletError> {
+    if index >= HARDENED {
+        return Err(WalletError::HardenedPath);
+    }
+    let mut mac = Hmac::<Sha512>::new_from_slice(chain_code).unwrap();
+    mac.update(&parent.to_bytes(true));
+    mac.update(&index.to_be_bytes());
+    let out = mac.finalize().into_bytes();
+    let tweak = FE::from_bytes(&out[..32]).map_err(|_| WalletError::InvalidTweak)?;
+    let mut child_chain_code = [0u8; 32];
+    child_chain_code.copy_from_slice(&out[32..]);
+    Ok((tweak, child_chain_code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::dealer_keys;
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::*;
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_refresh_schedule() {
+        let schedule = RefreshSchedule::new(60, 1000);
+        assert_eq!(schedule.next_refresh(), 1060);
+        assert!(!schedule.is_due(1059));
+        assert!(schedule.is_due(1060));
+
+        let schedule = RefreshSchedule::new(u64::MAX, 1000);
+        assert!(!schedule.is_due(u64::MAX - 1));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_unknown_user() {
+        let mut wallet = Wallet::new();
+        assert_eq!(
+            wallet.export("alice").unwrap_err(),
+            WalletError::UnknownUser
+        );
+        assert_eq!(
+            wallet.schedule_refresh("alice", 60, 0).unwrap_err(),
+            WalletError::UnknownUser
+        );
+        assert_eq!(
+            wallet.create_user_key("alice", "{}").unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        assert!(wallet.due_for_refresh(u64::MAX).is_empty());
+    }
+
+    fn test_wallet() -> ThresholdWallet {
+        ThresholdWallet::new(dealer_keys(1, 3).1.remove(0)).unwrap()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_wallet_storage() {
+        let key = test_wallet().key().clone();
+        let mut storage = crate::storage::MemoryStorage::new();
+        let mut wallet = Wallet::new();
+        wallet.insert_user_key("alice", key.clone()).unwrap();
+        wallet.insert_user_key("bob", key).unwrap();
+        let policy = PolicyAttachment {
+            id: "limit".to_string(),
+            document: "{}".to_string(),
+        };
+        wallet.attach_policy("alice", policy).unwrap();
+        wallet.save(&mut storage).unwrap();
+
+        wallet.remove_user("bob").unwrap();
+        wallet.save(&mut storage).unwrap();
+        let restored = Wallet::load(&storage).unwrap();
+        assert_eq!(restored.users(), vec!["alice".to_string()]);
+        assert_eq!(restored.policies("alice"), wallet.policies("alice"));
+        assert_eq!(restored.export("alice"), wallet.export("alice"));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_key_without_commitments() {
+        let mut key = test_wallet().key().clone();
+        key.vss_scheme_vec.clear();
+        let mut wallet = Wallet::new();
+        assert_eq!(
+            wallet.insert_user_key("alice", key.clone()).unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        assert_eq!(
+            ThresholdWallet::from_key_store(&key.to_key_store()).unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        assert_eq!(
+            ThresholdWallet::new(key.clone()).unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        let json = serde_json::to_string(&key).unwrap();
+        assert!(serde_json::from_str::<LocalKey>(&json).is_err());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_key_failing_validate() {
+        use crate::curv::elliptic::curves::traits::ECScalar;
+
+        let key = test_wallet().key().clone();
+        let mut wrong_party_key = key.clone();
+        wrong_party_key.party_keys.u_i = ECScalar::new_random();
+        let mut wallet = Wallet::new();
+        assert_eq!(
+            wallet
+                .insert_user_key("alice", wrong_party_key.clone())
+                .unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        wallet.insert_user_key("alice", key).unwrap();
+        assert_eq!(
+            wallet
+                .replace_user_key("alice", wrong_party_key.clone())
+                .unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+
+        // nor is a share that went bad in storage loaded
+        let mut storage = crate::storage::MemoryStorage::new();
+        wallet.save(&mut storage).unwrap();
+        let mut entry = wallet.user("alice").unwrap().clone();
+        entry.key = wrong_party_key;
+        storage::save(&mut storage, WALLET_USERS, "alice", &entry).unwrap();
+        assert_eq!(Wallet::load(&storage).unwrap_err(), StorageError::Malformed);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_threshold_wallet_failing_validate() {
+        use crate::curv::elliptic::curves::traits::ECScalar;
+
+        let wallet = test_wallet();
+        let mut wrong_party_key = wallet.key().clone();
+        wrong_party_key.party_keys.u_i = ECScalar::new_random();
+        assert_eq!(
+            ThresholdWallet::new(wrong_party_key.clone()).unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        assert_eq!(
+            ThresholdWallet::from_key_store(&wrong_party_key.to_key_store()).unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+
+        // nor does a wallet passed around as json take a bad key in
+        let json = serde_json::to_string(&wallet).unwrap();
+        assert!(serde_json::from_str::<ThresholdWallet>(&json).is_ok());
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["key"] = serde_json::to_value(&wrong_party_key).unwrap();
+        assert!(serde_json::from_value::<ThresholdWallet>(value).is_err());
+    }
+
+    // the part of a signing client context made from `key` that
+    // `begin_sign` reads
+    fn sign_context(key: &LocalKey) -> String {
+        serde_json::json!({
+            "y_sum": key.y_sum,
+            "shared_keys": key.shared_keys,
+            "party_id": key.party_id,
+        })
+        .to_string()
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_begin_sign_with_another_users_context() {
+        let mut keys = dealer_keys(1, 3).1;
+        let (alice, bob) = (keys.remove(0), keys.remove(0));
+        let mut wallet = Wallet::new();
+        wallet.insert_user_key("alice", alice.clone()).unwrap();
+        wallet.insert_user_key("bob", bob.clone()).unwrap();
+
+        assert_eq!(
+            wallet.begin_sign("alice", "s1", sign_context(&bob)),
+            Err(WalletError::WrongKey)
+        );
+        assert_eq!(
+            wallet.begin_sign("alice", "s1", "{}".to_string()),
+            Err(WalletError::InvalidContext)
+        );
+        // a share of another group key at the same index
+        let other = dealer_keys(1, 3).1.remove(0);
+        assert_eq!(
+            wallet.begin_sign("alice", "s1", sign_context(&other)),
+            Err(WalletError::WrongKey)
+        );
+        assert!(wallet.user_sessions("alice").is_empty());
+
+        wallet
+            .begin_sign("alice", "s1", sign_context(&alice))
+            .unwrap();
+        wallet.begin_sign("bob", "s2", sign_context(&bob)).unwrap();
+        assert_eq!(wallet.user_sessions("alice"), vec!["s1".to_string()]);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_user_sessions() {
+        let key_store = test_wallet().to_key_store();
+        let mut wallet = Wallet::new();
+        assert_eq!(
+            wallet.begin_sign("alice", "s1", "{}".to_string()),
+            Err(WalletError::UnknownUser)
+        );
+        wallet
+            .begin_keygen("alice", "k1", "{}".to_string())
+            .unwrap();
+        wallet.begin_keygen("bob", "k2", "{}".to_string()).unwrap();
+        assert_eq!(
+            wallet.begin_keygen("bob", "k1", "{}".to_string()),
+            Err(WalletError::Session(SessionError::SessionExists))
+        );
+        wallet.advance_session("k1", "{}".to_string()).unwrap();
+        assert_eq!(wallet.session("k1").unwrap().checkpoint.next_round, 1);
+        assert_eq!(wallet.user_sessions("alice"), vec!["k1".to_string()]);
+
+        // the sessions survive a restart
+        let mut storage = crate::storage::MemoryStorage::new();
+        wallet.save(&mut storage).unwrap();
+        let mut wallet = Wallet::load(&storage).unwrap();
+        assert_eq!(wallet.session("k1").unwrap().checkpoint.next_round, 1);
+
+        assert_eq!(
+            wallet.finish_sign("k1").unwrap_err(),
+            WalletError::WrongCeremony
+        );
+        assert_eq!(
+            wallet.finish_keygen("k1", "{}").unwrap_err(),
+            WalletError::InvalidKeyStore
+        );
+        assert_eq!(
+            wallet.finish_keygen("k1", &key_store),
+            Ok("alice".to_string())
+        );
+        assert_eq!(wallet.export("alice"), Ok(key_store.clone()));
+        assert!(wallet.user_sessions("alice").is_empty());
+
+        let key = wallet.key("alice").unwrap().clone();
+        wallet
+            .begin_sign("alice", "s1", sign_context(&key))
+            .unwrap();
+        assert_eq!(
+            wallet.finish_keygen("s1", &key_store),
+            Err(WalletError::WrongCeremony)
+        );
+        wallet.remove_user("alice").unwrap();
+        assert_eq!(
+            wallet.finish_sign("s1"),
+            Err(WalletError::Session(SessionError::UnknownSession))
+        );
+        assert_eq!(wallet.user_sessions("bob"), vec!["k2".to_string()]);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_threshold_wallet_derive() {
+        let wallet = test_wallet();
+        let child = wallet.derive_path("m/44/60").unwrap();
+        let grandchild = child.derive(&[7]).unwrap();
+        assert_eq!(child.path(), &[44, 60]);
+        assert_eq!(grandchild.path(), &[44, 60, 7]);
+        assert_eq!(
+            grandchild.public_key(),
+            wallet.derive(&[44, 60, 7]).unwrap().public_key()
+        );
+
+        // the public key moves by the CKDpub tweak, and the share with it
+        let (tweak, chain_code) =
+            child_tweak(wallet.public_key(), wallet.chain_code(), 44).unwrap();
+        let g: GE = ECPoint::generator();
+        let first = wallet.derive(&[44]).unwrap();
+        assert_eq!(
+            first.public_key(),
+            &(wallet.public_key().clone() + &(&g * &tweak))
+        );
+        assert_eq!(first.chain_code(), &chain_code);
+        assert_ne!(child.ethereum_address(), wallet.ethereum_address());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("m"), Ok(Vec::new()));
+        assert_eq!(parse_path("m/0/1"), Ok(vec![0, 1]));
+        assert_eq!(parse_path("m/44'/0"), Err(WalletError::HardenedPath));
+        assert_eq!(parse_path("m/2147483648"), Err(WalletError::HardenedPath));
+        assert_eq!(parse_path("m//1"), Err(WalletError::InvalidPath));
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_threshold_wallet_presignatures() {
+        use crate::curv::elliptic::curves::traits::ECScalar;
+
+        let wallet = test_wallet();
+        let mut storage = crate::storage::MemoryStorage::new();
+        let presignature = |y: &GE| Presignature {
+            k_i: ECScalar::new_random(),
+            sigma_i: ECScalar::new_random(),
+            R: GE::random_point(),
+            y: y.clone(),
+        };
+        assert_eq!(
+            wallet.add_presignature(&mut storage, presignature(&GE::random_point())),
+            Err(WalletError::WrongKey)
+        );
+        let y = wallet.public_key().clone();
+        let id = wallet
+            .add_presignature(&mut storage, presignature(&y))
+            .unwrap();
+        assert_eq!(wallet.presignature_ids(&storage), Ok(vec![id.clone()]));
+        let child = wallet.derive(&[0]).unwrap();
+        assert_eq!(child.presignature_ids(&storage), Ok(Vec::new()));
+
+        let digest = SigningDigest::from_prehashed(&[1u8; 32]).unwrap();
+        assert!(wallet.sign_presigned(&mut storage, &id, &digest).is_ok());
+        let name = hex::encode(y.to_bytes(true));
+        let saved = PresignatureStore::load(&storage, &name).unwrap().unwrap();
+        assert!(saved.is_consumed(&id));
+        assert_eq!(
+            wallet
+                .sign_presigned(&mut storage, &id, &digest)
+                .unwrap_err(),
+            WalletError::Presignature(PresignatureError::AlreadyConsumed)
+        );
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[test]
+    fn test_threshold_wallet_copy_has_no_presignatures() {
+        use crate::curv::elliptic::curves::traits::ECScalar;
+
+        let wallet = test_wallet();
+        let mut storage = crate::storage::MemoryStorage::new();
+        let id = wallet
+            .add_presignature(
+                &mut storage,
+                Presignature {
+                    k_i: ECScalar::new_random(),
+                    sigma_i: ECScalar::new_random(),
+                    R: GE::random_point(),
+                    y: wallet.public_key().clone(),
+                },
+            )
+            .unwrap();
+        // a copy taken before the presignature is used
+        let json = serde_json::to_string(&wallet).unwrap();
+        assert!(!json.contains(&id));
+        let stale: ThresholdWallet = serde_json::from_str(&json).unwrap();
+
+        let digest = SigningDigest::from_prehashed(&[2u8; 32]).unwrap();
+        wallet.sign_presigned(&mut storage, &id, &digest).unwrap();
+        assert_eq!(
+            stale
+                .sign_presigned(&mut storage, &id, &digest)
+                .unwrap_err(),
+            WalletError::Presignature(PresignatureError::AlreadyConsumed)
+        );
+        assert_eq!(stale.presignature_ids(&storage), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,91 @@
+//! Key store encryption under a WebCrypto key.
+//!
+//! The wrapping key is a non-extractable AES-GCM `CryptoKey` created by the
+//! page, e.g. with `crypto.subtle.generateKey({name: "AES-GCM", length: 256},
+//! false, ["encrypt", "decrypt"])` and kept in IndexedDB, or imported from a
+//! WebAuthn PRF output with `extractable` false. Its bytes never reach
+//! JavaScript or wasm, so a stolen database holds only ciphertext, and the
+//! platform keystore decides who can use the key.
+//!
+//! The sessions in `api` hand the key store to these functions directly, so
+//! a key store produced or used there is only seen by JavaScript in wrapped
+//! form.
+#![cfg(all(target_arch = "wasm32", feature = "gg18"))]
+
+use rand::{rngs::OsRng, RngCore};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AesGcmParams, Crypto, CryptoKey, SubtleCrypto};
+
+const IV_BYTES_LEN: usize = 12;
+// binds a ciphertext to its use, so other data under the same key does not
+// decrypt as a key store
+const KEY_STORE_AAD: &[u8] = b"tss-wasm key store";
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKeyStore {
+    iv: String,
+    ciphertext: String,
+}
+
+// `crypto` of the window or worker this instance runs in.
+fn subtle_crypto() -> Result<SubtleCrypto, JsValue> {
+    let crypto = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("crypto"))?;
+    Ok(crypto.dyn_into::<Crypto>()?.subtle())
+}
+
+fn aes_gcm_params(iv: &[u8]) -> AesGcmParams {
+    let iv = js_sys::Uint8Array::from(iv);
+    let params = AesGcmParams::new("AES-GCM", &iv);
+    params.set_additional_data(&js_sys::Uint8Array::from(KEY_STORE_AAD));
+    params
+}
+
+pub async fn wrap_key_store(key_store: &str, key: &CryptoKey) -> Result<String, JsValue> {
+    let mut iv = [0u8; IV_BYTES_LEN];
+    let mut rng = OsRng::new().map_err(|e| JsValue::from_str(&e.to_string()))?;
+    rng.fill_bytes(&mut iv);
+
+    let data = js_sys::Uint8Array::from(key_store.as_bytes());
+    let promise =
+        subtle_crypto()?.encrypt_with_object_and_buffer_source(&aes_gcm_params(&iv), key, &data)?;
+    let ciphertext = js_sys::Uint8Array::new(&JsFuture::from(promise).await?).to_vec();
+
+    Ok(serde_json::to_string(&WrappedKeyStore {
+        iv: hex::encode(iv),
+        ciphertext: hex::encode(ciphertext),
+    })
+    .unwrap())
+}
+
+pub async fn unwrap_key_store(wrapped: &str, key: &CryptoKey) -> Result<String, JsValue> {
+    let invalid = || JsValue::from_str("invalid wrapped key store");
+    let wrapped = serde_json::from_str::<WrappedKeyStore>(wrapped).map_err(|_| invalid())?;
+    let iv = hex::decode(&wrapped.iv).map_err(|_| invalid())?;
+    if iv.len() != IV_BYTES_LEN {
+        return Err(invalid());
+    }
+    let ciphertext = hex::decode(&wrapped.ciphertext).map_err(|_| invalid())?;
+
+    let data = js_sys::Uint8Array::from(&ciphertext[..]);
+    let promise =
+        subtle_crypto()?.decrypt_with_object_and_buffer_source(&aes_gcm_params(&iv), key, &data)?;
+    // a wrong key or a modified ciphertext rejects the promise
+    let plaintext = js_sys::Uint8Array::new(&JsFuture::from(promise).await?).to_vec();
+    String::from_utf8(plaintext).map_err(|_| invalid())
+}
+
+/// Encrypts a plaintext key store, e.g. one created before wrapping was
+/// available, under `key`. Returns json with hex `iv` and `ciphertext`.
+#[wasm_bindgen]
+pub async fn gg18_wrap_key_store(key_store: String, key: CryptoKey) -> Result<String, JsValue> {
+    wrap_key_store(&key_store, &key).await
+}
+
+/// Decrypts the output of `gg18_wrap_key_store`. Prefer passing the wrapped
+/// key store to `gg18_sign_session_new_wrapped`, which keeps it in wasm.
+#[wasm_bindgen]
+pub async fn gg18_unwrap_key_store(wrapped: String, key: CryptoKey) -> Result<String, JsValue> {
+    unwrap_key_store(&wrapped, &key).await
+}
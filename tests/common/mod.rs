@@ -19,7 +19,9 @@ pub const BENCH_SAMPLE_SIZE: usize = 10;
     @license GPL-3.0+ <https://github.com/KZen-networks/multi-party-ecdsa/blob/master/LICENSE>
 */
 
-use tss_wasm::curv::arithmetic::num_bigint::from;
+use sha2::Sha256;
+use tss_wasm::curv::cryptographic_primitives::commitments::hash_commitment::HashCommitment;
+use tss_wasm::curv::cryptographic_primitives::commitments::traits::Commitment;
 use tss_wasm::curv::cryptographic_primitives::hashing::hash_sha256::HSha256;
 use tss_wasm::curv::cryptographic_primitives::hashing::traits::Hash;
 use tss_wasm::curv::cryptographic_primitives::proofs::sigma_dlog::DLogProof;
@@ -34,23 +36,18 @@ pub fn keygen_t_n_parties(
     t: usize,
     n: usize,
 ) -> (Vec<Keys>, Vec<SharedKeys>, Vec<GE>, GE, VerifiableSS) {
-    let parames = Parameters {
-        threshold: t,
-        share_count: n.clone(),
-    };
-    let party_keys_vec = (0..n.clone())
-        .map(|i| Keys::create(i))
-        .collect::<Vec<Keys>>();
+    let parames = Parameters::new(t, n).unwrap();
+    let party_keys_vec = (0..n).map(Keys::create).collect::<Vec<Keys>>();
 
     let mut bc1_vec = Vec::new();
     let mut decom_vec = Vec::new();
-    for i in 0..n.clone() {
-        let (bc1, decom1) = party_keys_vec[i].phase1_broadcast_phase3_proof_of_correct_key();
+    for party_keys in &party_keys_vec {
+        let (bc1, decom1) = party_keys.phase1_broadcast_phase3_proof_of_correct_key();
         bc1_vec.push(bc1);
         decom_vec.push(decom1);
     }
 
-    let y_vec = (0..n.clone())
+    let y_vec = (0..n)
         .map(|i| decom_vec[i].y_i.clone())
         .collect::<Vec<GE>>();
     let mut y_vec_iter = y_vec.iter();
@@ -60,8 +57,8 @@ pub fn keygen_t_n_parties(
     let mut vss_scheme_vec = Vec::new();
     let mut secret_shares_vec = Vec::new();
     let mut index_vec = Vec::new();
-    for i in 0..n.clone() {
-        let (vss_scheme, secret_shares, index) = party_keys_vec[i]
+    for party_keys in &party_keys_vec {
+        let (vss_scheme, secret_shares, index) = party_keys
             .phase1_verify_com_phase3_verify_correct_key_phase2_distribute(
                 &parames, &decom_vec, &bc1_vec,
             )
@@ -72,9 +69,9 @@ pub fn keygen_t_n_parties(
     }
     let vss_scheme_for_test = vss_scheme_vec.clone();
 
-    let party_shares = (0..n.clone())
+    let party_shares = (0..n)
         .map(|i| {
-            (0..n.clone())
+            (0..n)
                 .map(|j| {
                     let vec_j = &secret_shares_vec[j];
                     vec_j[i].clone()
@@ -85,7 +82,7 @@ pub fn keygen_t_n_parties(
 
     let mut shared_keys_vec = Vec::new();
     let mut dlog_proof_vec = Vec::new();
-    for i in 0..n.clone() {
+    for i in 0..n {
         let (shared_keys, dlog_proof) = party_keys_vec[i]
             .phase2_verify_vss_construct_keypair_phase3_pok_dlog(
                 &parames,
@@ -99,7 +96,7 @@ pub fn keygen_t_n_parties(
         dlog_proof_vec.push(dlog_proof);
     }
 
-    let pk_vec = (0..n.clone())
+    let pk_vec = (0..n)
         .map(|i| dlog_proof_vec[i].pk.clone())
         .collect::<Vec<GE>>();
 
@@ -107,12 +104,12 @@ pub fn keygen_t_n_parties(
     Keys::verify_dlog_proofs(&parames, &dlog_proof_vec, &y_vec).expect("bad dlog proof");
 
     //test
-    let xi_vec = (0..t.clone() + 1)
+    let xi_vec = (0..t + 1)
         .map(|i| shared_keys_vec[i].x_i.clone())
         .collect::<Vec<FE>>();
     let x = vss_scheme_for_test[0]
         .clone()
-        .reconstruct(&index_vec[0..t.clone() + 1], &xi_vec);
+        .reconstruct(&index_vec[0..t + 1], &xi_vec);
     let sum_u_i = party_keys_vec
         .iter()
         .fold(FE::zero(), |acc, x| acc + &x.u_i);
@@ -129,9 +126,16 @@ pub fn keygen_t_n_parties(
 
 #[allow(dead_code)]
 pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
+    sign_with_dropouts(t, n, ttag, s, &[]);
+}
+
+// Like `sign`, with the parties in `dropped` leaving after phase 1. The
+// others interpolate their w_i over themselves and go on with the
+// commitments and encryptions of k_i they already sent.
+#[allow(dead_code)]
+pub fn sign_with_dropouts(t: usize, n: usize, ttag: usize, s: Vec<usize>, dropped: &[usize]) {
     // full key gen emulation
-    let (party_keys_vec, shared_keys_vec, _pk_vec, y, vss_scheme) =
-        keygen_t_n_parties(t.clone(), n);
+    let (party_keys_vec, shared_keys_vec, _pk_vec, y, vss_scheme) = keygen_t_n_parties(t, n);
 
     let private_vec = (0..shared_keys_vec.len())
         .map(|i| PartyPrivate::set_private(party_keys_vec[i].clone(), shared_keys_vec[i].clone()))
@@ -152,8 +156,8 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     // each party computes [Ci,Di] = com(g^gamma_i) and broadcast the commitments
     let mut bc1_vec = Vec::new();
     let mut decommit_vec1 = Vec::new();
-    for i in 0..ttag.clone() {
-        let (com, decommit_phase_1) = sign_keys_vec[i].phase1_broadcast();
+    for sign_keys in &sign_keys_vec {
+        let (com, decommit_phase_1) = sign_keys.phase1_broadcast();
         bc1_vec.push(com);
         decommit_vec1.push(decommit_phase_1);
     }
@@ -161,12 +165,38 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     // each party i sends encryption of k_i under her Paillier key
     // m_a_vec = [ma_0;ma_1;,...]
     let mut m_a_vec = Vec::new();
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let (m_a_k, _) = MessageA::a(&sign_keys_vec[i].k_i, &party_keys_vec[s[i]].ek, &[]);
 
         m_a_vec.push(m_a_k);
     }
 
+    // nothing sent so far depends on w_i, so the survivors only recompute it
+    let kept = (0..ttag)
+        .filter(|i| !dropped.contains(&s[*i]))
+        .collect::<Vec<usize>>();
+    let s = kept.iter().map(|i| s[*i]).collect::<Vec<usize>>();
+    let ttag = s.len();
+    assert!(ttag > t);
+    let bc1_vec = kept.iter().map(|i| bc1_vec[*i].clone()).collect::<Vec<_>>();
+    let decommit_vec1 = kept
+        .iter()
+        .map(|i| decommit_vec1[*i].clone())
+        .collect::<Vec<_>>();
+    let m_a_vec = kept.iter().map(|i| m_a_vec[*i].clone()).collect::<Vec<_>>();
+    let sign_keys_vec = kept
+        .iter()
+        .zip(s.iter())
+        .map(|(i, index)| {
+            let survivor = SignKeys::create(&private_vec[*index], &vss_scheme, *index, &s);
+            SignKeys {
+                w_i: survivor.w_i,
+                g_w_i: survivor.g_w_i,
+                ..sign_keys_vec[*i].clone()
+            }
+        })
+        .collect::<Vec<SignKeys>>();
+
     // each party i sends responses to m_a_vec she received (one response with input gamma_i and one with w_i)
     // m_b_gamma_vec_all is a matrix where column i is a vector of message_b's that party i answers to all ma_{j!=i} using paillier key of party j to answer to ma_j
 
@@ -176,7 +206,7 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     let mut m_b_w_vec_all = Vec::new();
     let mut ni_vec_all = Vec::new();
 
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let mut m_b_gamma_vec = Vec::new();
         let mut beta_vec = Vec::new();
         let mut m_b_w_vec = Vec::new();
@@ -190,6 +220,8 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
                 &party_keys_vec[s[ind]].ek,
                 m_a_vec[ind].clone(),
                 &[],
+                b"sign",
+                s[i] as u16 + 1,
             )
             .unwrap();
             let (m_b_w, beta_wi, _, _) = MessageB::b(
@@ -197,6 +229,8 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
                 &party_keys_vec[s[ind]].ek,
                 m_a_vec[ind].clone(),
                 &[],
+                b"sign",
+                s[i] as u16 + 1,
             )
             .unwrap();
 
@@ -219,7 +253,7 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     let mut alpha_vec_all = Vec::new();
     let mut miu_vec_all = Vec::new();
 
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let mut alpha_vec = Vec::new();
         let mut miu_vec = Vec::new();
 
@@ -231,11 +265,23 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
             let m_b = m_b_gamma_vec_i[j].clone();
 
             let alpha_ij_gamma = m_b
-                .verify_proofs_get_alpha(&party_keys_vec[s[ind]].dk, &sign_keys_vec[ind].k_i)
+                .verify_proofs_get_alpha(
+                    &party_keys_vec[s[ind]].dk,
+                    &sign_keys_vec[ind].k_i,
+                    false,
+                    b"sign",
+                    s[i] as u16 + 1,
+                )
                 .expect("wrong dlog or m_b");
             let m_b = m_b_w_vec_i[j].clone();
             let alpha_ij_wi = m_b
-                .verify_proofs_get_alpha(&party_keys_vec[s[ind]].dk, &sign_keys_vec[ind].k_i)
+                .verify_proofs_get_alpha(
+                    &party_keys_vec[s[ind]].dk,
+                    &sign_keys_vec[ind].k_i,
+                    false,
+                    b"sign",
+                    s[i] as u16 + 1,
+                )
                 .expect("wrong dlog or m_b");
 
             // since we actually run two MtAwc each party needs to make sure that the values B are the same as the public values
@@ -254,7 +300,7 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     let mut delta_vec = Vec::new();
     let mut sigma_vec = Vec::new();
 
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let delta = sign_keys_vec[i].phase2_delta_i(&alpha_vec_all[i], &beta_vec_all[i]);
         let sigma = sign_keys_vec[i].phase2_sigma_i(&miu_vec_all[i], &ni_vec_all[i]);
         delta_vec.push(delta);
@@ -280,22 +326,21 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
                     &b_gamma_vec[0].b_proof
                 })
                 .collect::<Vec<&DLogProof>>();
-            let R = SignKeys::phase4(&delta_inv, &b_proof_vec, decommit_vec1.clone(), &bc1_vec)
-                .expect("bad gamma_i decommit");
-            R
+            SignKeys::phase4(&delta_inv, &b_proof_vec, decommit_vec1.clone(), &bc1_vec)
+                .expect("bad gamma_i decommit")
         })
         .collect::<Vec<GE>>();
 
     let message: [u8; 4] = [79, 77, 69, 82];
 
-    let message_bn = HSha256::create_hash(&vec![&from(&message[..])]);
+    let message_digest = SigningDigest::hash::<Sha256>(&message);
     let mut local_sig_vec = Vec::new();
 
     // each party computes s_i but don't send it yet. we start with phase5
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let local_sig = LocalSignature::phase5_local_sig(
             &sign_keys_vec[i].k_i,
-            &message_bn,
+            &message_digest,
             &R_vec[i],
             &sigma_vec[i],
             &y,
@@ -308,9 +353,9 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
     let mut helgamal_proof_vec = Vec::new();
     // we notice that the proof for V= R^sg^l, B = A^l is a general form of homomorphic elgamal.
     let mut dlog_proof_rho_vec = Vec::new();
-    for i in 0..ttag.clone() {
+    for local_sig in &local_sig_vec {
         let (phase5_com, phase_5a_decom, helgamal_proof, dlog_proof_rho) =
-            local_sig_vec[i].phase5a_broadcast_5b_zkproof();
+            local_sig.phase5a_broadcast_5b_zkproof();
         phase5_com_vec.push(phase5_com);
         phase_5a_decom_vec.push(phase_5a_decom);
         helgamal_proof_vec.push(helgamal_proof);
@@ -319,7 +364,7 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
 
     let mut phase5_com2_vec = Vec::new();
     let mut phase_5d_decom2_vec = Vec::new();
-    for i in 0..ttag.clone() {
+    for i in 0..ttag {
         let mut phase_5a_decom_vec_clone = phase_5a_decom_vec.clone();
         let mut phase_5a_com_vec_clone = phase5_com_vec.clone();
         let mut phase_5b_elgamal_vec_clone = helgamal_proof_vec.clone();
@@ -347,13 +392,37 @@ pub fn sign(t: usize, n: usize, ttag: usize, s: Vec<usize>) {
 
     // assuming phase5 checks passes each party sends s_i and compute sum_i{s_i}
     let mut s_vec: Vec<FE> = Vec::new();
-    for i in 0..ttag.clone() {
-        let s_i = local_sig_vec[i]
+    for local_sig in &local_sig_vec {
+        let s_i = local_sig
             .phase5d(&phase_5d_decom2_vec, &phase5_com2_vec, &phase_5a_decom_vec)
             .expect("bad com 5d");
         s_vec.push(s_i);
     }
 
+    // a signer whose u_i is not rho_i V is named, even with a commitment
+    // that opens
+    let mut bad_decom2_vec = phase_5d_decom2_vec.clone();
+    let mut bad_com2_vec = phase5_com2_vec.clone();
+    let g: GE = ECPoint::generator();
+    bad_decom2_vec[1].u_i = bad_decom2_vec[1].u_i.clone() + &g;
+    let input_hash =
+        HSha256::create_hash_from_ge(&[&bad_decom2_vec[1].u_i, &bad_decom2_vec[1].t_i])
+            .to_big_int();
+    bad_com2_vec[1].com = HashCommitment::create_commitment_with_user_defined_randomness(
+        &input_hash,
+        &bad_decom2_vec[1].blind_factor,
+    );
+    assert_eq!(
+        local_sig_vec[0].phase5d_blame(&bad_decom2_vec, &bad_com2_vec, &phase_5a_decom_vec),
+        vec![Blame {
+            index: 1,
+            reason: BlameReason::Phase5DProof,
+        }]
+    );
+    assert!(local_sig_vec[0]
+        .phase5d(&bad_decom2_vec, &bad_com2_vec, &phase_5a_decom_vec)
+        .is_err());
+
     // here we compute the signature only of party i=0 to demonstrate correctness.
     s_vec.remove(0);
     let _sig = local_sig_vec[0]
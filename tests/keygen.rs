@@ -19,6 +19,45 @@ fn test_keygen_t1_n2() {
     common::keygen_t_n_parties(1, 2);
 }
 
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_parameters() {
+    use tss_wasm::gg_2018::party_i::{Parameters, ParametersError};
+
+    assert_eq!(Parameters::new(1, 1), Err(ParametersError::TooFewParties));
+    assert_eq!(
+        Parameters::new(2, 2),
+        Err(ParametersError::ThresholdTooLarge)
+    );
+    let params = Parameters::new(1, 3).unwrap();
+    assert_eq!(params.signer_count(), 2);
+    assert_eq!(params.check_index(3), Ok(()));
+    assert_eq!(params.check_index(0), Err(ParametersError::IndexOutOfRange));
+    assert_eq!(params.check_signers(&[2, 0]), Ok(()));
+    assert_eq!(
+        params.check_signers(&[0, 1, 2]),
+        Err(ParametersError::WrongSignerCount)
+    );
+    assert_eq!(
+        params.check_signers(&[1, 1]),
+        Err(ParametersError::DuplicateSigner)
+    );
+    assert_eq!(
+        params.check_signers(&[0, 3]),
+        Err(ParametersError::IndexOutOfRange)
+    );
+    assert_eq!(params.check_online_set(&[0, 1, 2]), Ok(()));
+    assert_eq!(
+        params.check_online_set(&[1]),
+        Err(ParametersError::WrongSignerCount)
+    );
+
+    // deserializing goes through the same checks
+    let json = serde_json::to_string(&params).unwrap();
+    assert_eq!(serde_json::from_str::<Parameters>(&json).unwrap(), params);
+    assert!(serde_json::from_str::<Parameters>(r#"{"threshold":3,"share_count":3}"#).is_err());
+}
+
 /* TODO: comment to speed up CI
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
 #[test]
@@ -53,12 +92,21 @@ pub mod bench {
         });
     }
 
+    // prime generation dominates keygen; compare with and without the gmp
+    // feature
+    pub fn bench_paillier_keypair(c: &mut Criterion) {
+        use tss_wasm::paillier::{KeyGeneration, Paillier};
+
+        c.bench_function("paillier keypair 2048", move |b| b.iter(Paillier::keypair));
+    }
+
     criterion_group! {
     name = keygen;
     config = Criterion::default().sample_size(super::common::BENCH_SAMPLE_SIZE);
     targets =
     self::bench_full_keygen_party_one_two,
-    self::bench_full_keygen_party_two_three
+    self::bench_full_keygen_party_two_three,
+    self::bench_paillier_keypair
     }
 }
 
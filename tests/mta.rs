@@ -44,12 +44,245 @@ fn test_mta() {
     */
     let bob_input: FE = ECScalar::new_random();
     let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
-    let (m_b, beta, _, _) = MessageB::b(&bob_input, &ek_alice, m_a, &[]).unwrap();
+    let (m_b, beta, _, _) = MessageB::b(&bob_input, &ek_alice, m_a, &[], b"session", 2).unwrap();
     let alpha = m_b
-        .verify_proofs_get_alpha(&dk_alice, &alice_input)
+        .verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"session", 2)
         .expect("wrong dlog or m_b");
 
+    // the proofs name Bob and the session
+    assert_eq!(
+        m_b.verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"session", 3)
+            .unwrap_err(),
+        tss_wasm::Error::InvalidKey
+    );
+    assert_eq!(
+        m_b.verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"other", 2)
+            .unwrap_err(),
+        tss_wasm::Error::InvalidKey
+    );
+
     let left = alpha.0 + beta;
     let right = alice_input * bob_input;
     assert_eq!(left.get_element(), right.get_element());
 }
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_mta_rejects_invalid_ciphertext() {
+    let alice_input: FE = ECScalar::new_random();
+    let (ek_alice, dk_alice) = Paillier::keypair().keys();
+    let bob_input: FE = ECScalar::new_random();
+
+    let (mut m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
+    m_a.c = ek_alice.nn.clone();
+    let result = MessageB::b(&bob_input, &ek_alice, m_a.clone(), &[], b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidCiphertext);
+
+    m_a.c = ek_alice.n.clone();
+    let result = MessageB::b(&bob_input, &ek_alice, m_a, &[], b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidCiphertext);
+
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
+    let (mut m_b, _, _, _) = MessageB::b(&bob_input, &ek_alice, m_a, &[], b"session", 2).unwrap();
+    m_b.c = ek_alice.nn.clone();
+    let result = m_b.verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidCiphertext);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_mta_rejects_out_of_range_share() {
+    use tss_wasm::paillier::zkproofs::DLogStatement;
+
+    let alice_input: FE = ECScalar::new_random();
+    let (ek_alice, dk_alice) = Paillier::keypair().keys();
+    let bob_input: FE = ECScalar::new_random();
+    let (statement, _) = DLogStatement::generate();
+    let statements = [statement];
+
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &statements);
+    let (mut m_b, _, _, _) =
+        MessageB::b(&bob_input, &ek_alice, m_a, &statements, b"session", 2).unwrap();
+    // a multiple of q leaves alpha mod q, and so the proofs, as they were
+    let shift = alpha_bound(&ek_alice, true);
+    let c_shift: RawCiphertext = Paillier::encrypt(&ek_alice, RawPlaintext::from(shift));
+    let c = Paillier::add(&ek_alice, RawCiphertext::from(m_b.c), c_shift);
+    m_b.c = c.0.into_owned();
+    let result = m_b.verify_proofs_get_alpha(&dk_alice, &alice_input, true, b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::MtaShareOutOfRange);
+
+    // Bob's mask is held to the same bound
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &statements);
+    let randomness = ek_alice.n.clone() - 2u32;
+    let result = MessageB::b_with_predefined_randomness(
+        &bob_input,
+        &ek_alice,
+        m_a,
+        &randomness,
+        &beta_tag_bound(&ek_alice, true),
+        &statements,
+        b"session",
+        2,
+    );
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidPlaintext);
+
+    // without range proofs the mask goes up to N, and so may the share
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
+    let beta_tag = beta_tag_bound(&ek_alice, true) + 1u32;
+    let (m_b, _) = MessageB::b_with_predefined_randomness(
+        &bob_input,
+        &ek_alice,
+        m_a,
+        &randomness,
+        &beta_tag,
+        &[],
+        b"session",
+        2,
+    )
+    .unwrap();
+    assert!(m_b
+        .verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"session", 2)
+        .is_ok());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_mta_rejects_out_of_range_a() {
+    use tss_wasm::curv::arithmetic::num_bigint::BigInt;
+    use tss_wasm::curv::arithmetic::traits::Samplable;
+    use tss_wasm::gg_2018::range_proofs::AliceProof;
+    use tss_wasm::paillier::zkproofs::DLogStatement;
+
+    let (ek_alice, _) = Paillier::keypair().keys();
+    let bob_input: FE = ECScalar::new_random();
+    let (statement, _) = DLogStatement::generate();
+    let statements = [statement];
+
+    // an a far above q^3 would leave w_i in the high bits of a*w_i + beta'
+    let a = BigInt::from(1u32) << 1400;
+    let randomness = BigInt::sample_below(&ek_alice.n);
+    let c = Paillier::encrypt_with_chosen_randomness(
+        &ek_alice,
+        RawPlaintext::from(a.clone()),
+        &Randomness::from(randomness.clone()),
+    )
+    .0
+    .into_owned();
+    let range_proofs = vec![AliceProof::generate(
+        &a,
+        &c,
+        &ek_alice,
+        &statements[0],
+        &randomness,
+    )];
+    let m_a = MessageA { c, range_proofs };
+    let result = MessageB::b(
+        &bob_input,
+        &ek_alice,
+        m_a.clone(),
+        &statements,
+        b"session",
+        2,
+    );
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidKey);
+
+    // nor does it get through with the proofs left out
+    let m_a = MessageA {
+        range_proofs: vec![],
+        ..m_a
+    };
+    let result = MessageB::b(&bob_input, &ek_alice, m_a, &statements, b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidKey);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_mta_rejects_zero_share() {
+    let alice_input: FE = ECScalar::new_random();
+    let (ek_alice, dk_alice) = Paillier::keypair().keys();
+    let bob_input: FE = ECScalar::new_random();
+
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
+    let (mut m_b, _, _, _) = MessageB::b(&bob_input, &ek_alice, m_a, &[], b"session", 2).unwrap();
+    // q is a valid plaintext, but no scalar
+    let c: RawCiphertext = Paillier::encrypt(&ek_alice, RawPlaintext::from(FE::q()));
+    m_b.c = c.0.into_owned();
+    let result = m_b.verify_proofs_get_alpha(&dk_alice, &alice_input, false, b"session", 2);
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidPlaintext);
+
+    // nor is Bob's mask
+    let (m_a, _) = MessageA::a(&alice_input, &ek_alice, &[]);
+    let randomness = ek_alice.n.clone() - 2u32;
+    let result = MessageB::b_with_predefined_randomness(
+        &bob_input,
+        &ek_alice,
+        m_a,
+        &randomness,
+        &FE::q(),
+        &[],
+        b"session",
+        2,
+    );
+    assert_eq!(result.unwrap_err(), tss_wasm::Error::InvalidPlaintext);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_mta_proof_params() {
+    use tss_wasm::gg_2018::range_proofs::{AliceProofParams, ProofParamsError};
+    use tss_wasm::paillier::zkproofs::DLogStatement;
+
+    assert_eq!(AliceProofParams::default(), AliceProofParams::BITS_128);
+    assert_eq!(
+        AliceProofParams::new(64, 128),
+        Err(ProofParamsError::ChallengeBits)
+    );
+    assert_eq!(
+        AliceProofParams::new(128, 1024),
+        Err(ProofParamsError::StatisticalBits)
+    );
+
+    let alice_input: FE = ECScalar::new_random();
+    let bob_input: FE = ECScalar::new_random();
+    let (ek_alice, dk_alice) = Paillier::keypair().keys();
+    let (statement, _) = DLogStatement::generate();
+    let statements = [statement];
+    let params = AliceProofParams::new(96, 96).unwrap();
+    let randomness = ek_alice.n.clone() - 2u32;
+
+    let m_a = MessageA::a_with_params(&alice_input, &ek_alice, &randomness, &statements, &params);
+    let beta_tag = BigInt::from(7u32);
+    let b_randomness = ek_alice.n.clone() - 4u32;
+    let (m_b, beta) = MessageB::b_with_params(
+        &bob_input,
+        &ek_alice,
+        m_a.clone(),
+        &b_randomness,
+        &beta_tag,
+        &statements,
+        &params,
+        b"session",
+        2,
+    )
+    .unwrap();
+    let (alpha, _) = m_b
+        .verify_proofs_get_alpha(&dk_alice, &alice_input, true, b"session", 2)
+        .unwrap();
+    assert_eq!(alpha + beta, alice_input.clone() * &bob_input);
+
+    // proofs do not verify under other parameters
+    assert_eq!(
+        MessageB::b_with_predefined_randomness(
+            &bob_input,
+            &ek_alice,
+            m_a,
+            &b_randomness,
+            &beta_tag,
+            &statements,
+            b"session",
+            2,
+        )
+        .unwrap_err(),
+        tss_wasm::Error::InvalidKey
+    );
+}
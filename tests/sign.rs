@@ -25,6 +25,118 @@ fn test_sign_n3_t2_ttag3() {
     common::sign(2, 3, 3, vec![0, 1, 2]);
 }
 
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_sign_n5_t2_dropout() {
+    // five parties start and party 1 drops out after phase 1
+    common::sign_with_dropouts(2, 5, 5, vec![0, 1, 2, 3, 4], &[1]);
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_signing_digest() {
+    use sha2::{Sha256, Sha512};
+    use tss_wasm::curv::arithmetic::num_bigint::BigInt;
+    use tss_wasm::curv::elliptic::curves::secp256_k1::FE;
+    use tss_wasm::curv::elliptic::curves::traits::ECScalar;
+    use tss_wasm::gg_2018::party_i::SigningDigest;
+
+    assert!(SigningDigest::from_prehashed(&[1u8; 31]).is_err());
+    assert!(SigningDigest::from_prehashed(&[1u8; 33]).is_err());
+    let digest = SigningDigest::from_prehashed(&[0xffu8; 32]).unwrap();
+    assert!(digest.to_big_int() > digest.to_scalar().to_big_int());
+
+    let long = SigningDigest::hash::<Sha512>(b"message");
+    let full = <Sha512 as sha2::Digest>::digest(b"message");
+    assert_eq!(&long.as_bytes()[..], &full[..32]);
+    assert_ne!(long, SigningDigest::hash::<Sha256>(b"message"));
+
+    // unhashed messages of any length are reduced, as the digest of a
+    // prehashed one is when it is signed
+    assert_eq!(
+        SigningDigest::from_unhashed(&[0xffu8; 32]).to_big_int(),
+        digest.to_scalar().to_big_int()
+    );
+    let short = SigningDigest::from_unhashed(b"message");
+    assert_eq!(short.to_big_int(), BigInt::from_bytes_be(b"message"));
+    assert_eq!(SigningDigest::from_unhashed(&[]).as_bytes(), &[0u8; 32]);
+    let wide = SigningDigest::from_unhashed(&[0xffu8; 40]);
+    assert!(wide.to_big_int() < FE::q());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_zero_digest() {
+    use tss_wasm::curv::arithmetic::num_bigint::BigInt;
+    use tss_wasm::curv::arithmetic::traits::Converter;
+    use tss_wasm::curv::elliptic::curves::secp256_k1::{FE, GE};
+    use tss_wasm::curv::elliptic::curves::traits::{ECPoint, ECScalar};
+    use tss_wasm::gg_2018::party_i::{message_point, verify, Signature, SigningDigest};
+
+    // 0 and q are digests like any other, and reduce to the zero scalar
+    let zero = SigningDigest::from_prehashed(&[0u8; 32]).unwrap();
+    assert_eq!(zero.to_scalar(), FE::zero());
+    let q = SigningDigest::from_prehashed(&BigInt::to_vec(&FE::q())).unwrap();
+    assert_eq!(q.to_scalar(), FE::zero());
+    assert_eq!(SigningDigest::from_unhashed(&[0u8]), zero);
+    assert!(message_point(&zero.to_big_int()).is_none());
+
+    // s = k^-1 r x signs m = 0
+    let g: GE = ECPoint::generator();
+    let x: FE = ECScalar::new_random();
+    let k: FE = ECScalar::new_random();
+    let r: FE = ECScalar::from(&(&g * &k).x_coor().unwrap());
+    let s = k.invert() * (r.clone() * &x);
+    let sig = Signature { r, s };
+    assert!(verify(&sig, &(&g * &x), &zero.to_big_int()).is_ok());
+    assert!(verify(&sig, &(&g * &x), &BigInt::from(1u32)).is_err());
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+#[test]
+fn test_phase4_blame() {
+    use tss_wasm::curv::cryptographic_primitives::proofs::sigma_dlog::{DLogProof, ProveDLog};
+    use tss_wasm::curv::elliptic::curves::secp256_k1::{FE, GE};
+    use tss_wasm::curv::elliptic::curves::traits::{ECPoint, ECScalar};
+    use tss_wasm::gg_2018::party_i::{Blame, BlameReason, SignKeys};
+
+    let g: GE = ECPoint::generator();
+    let sign_keys_vec = (0..3)
+        .map(|_| {
+            let w_i: FE = ECScalar::new_random();
+            let gamma_i: FE = ECScalar::new_random();
+            SignKeys {
+                g_w_i: &g * &w_i,
+                w_i,
+                k_i: ECScalar::new_random(),
+                g_gamma_i: &g * &gamma_i,
+                gamma_i,
+            }
+        })
+        .collect::<Vec<SignKeys>>();
+    let (bc1_vec, mut decommit_vec): (Vec<_>, Vec<_>) =
+        sign_keys_vec.iter().map(|k| k.phase1_broadcast()).unzip();
+    let b_proofs = sign_keys_vec
+        .iter()
+        .map(|k| DLogProof::prove(&k.gamma_i))
+        .collect::<Vec<DLogProof>>();
+    let b_proof_vec = b_proofs.iter().collect::<Vec<&DLogProof>>();
+    assert!(SignKeys::phase4_blame(&b_proof_vec, &decommit_vec, &bc1_vec).is_empty());
+
+    // the second signer opens to another g^gamma than it committed to
+    let other_gamma: FE = ECScalar::new_random();
+    decommit_vec[1].g_gamma_i = &g * &other_gamma;
+    assert_eq!(
+        SignKeys::phase4_blame(&b_proof_vec, &decommit_vec, &bc1_vec),
+        vec![Blame {
+            index: 1,
+            reason: BlameReason::GammaDecommitment,
+        }]
+    );
+    let delta_inv: FE = ECScalar::new_random();
+    assert!(SignKeys::phase4(&delta_inv, &b_proof_vec, decommit_vec, &bc1_vec).is_err());
+}
+
 /* TODO: comment to speed up CI
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
 #[test]